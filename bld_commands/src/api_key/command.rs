@@ -0,0 +1,168 @@
+use crate::capabilities::require_feature;
+use crate::BldCommand;
+use actix_web::rt::System;
+use anyhow::Result;
+use bld_config::definitions::VERSION;
+use bld_config::BldConfig;
+use bld_server::requests::ApiKeyInfo;
+use bld_server::responses::{ApiKeyEntry, ApiKeyResponse, FEATURE_API_KEYS};
+use bld_utils::request;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use std::collections::HashMap;
+use tabled::{Style, Table};
+use tracing::debug;
+
+static API_KEY: &str = "api-key";
+static CREATE: &str = "create";
+static LIST: &str = "list";
+static REVOKE: &str = "revoke";
+static NAME: &str = "name";
+static SERVER: &str = "server";
+
+pub struct ApiKeyCommand;
+
+impl BldCommand for ApiKeyCommand {
+    fn boxed() -> Box<Self> {
+        Box::new(Self)
+    }
+
+    fn id(&self) -> &'static str {
+        API_KEY
+    }
+
+    fn interface(&self) -> Command {
+        let name = Arg::new(NAME)
+            .short('n')
+            .long(NAME)
+            .help("The name of the api key, whose requests are made as the user that created it")
+            .required(true)
+            .action(ArgAction::Set);
+
+        let server = Arg::new(SERVER)
+            .short('s')
+            .long(SERVER)
+            .help("The name of the server of the api keys")
+            .action(ArgAction::Set);
+
+        let create = Command::new(CREATE)
+            .about("Creates an api key on a server and prints it. The key can't be retrieved again")
+            .args(&[name.clone(), server.clone()]);
+
+        let list = Command::new(LIST)
+            .about("Lists the api keys created by the user, or all of them for an admin")
+            .arg(server.clone());
+
+        let revoke = Command::new(REVOKE)
+            .about("Revokes an api key created by the user, or any api key for an admin")
+            .args(&[name, server]);
+
+        Command::new(API_KEY)
+            .about("Manages the api keys of a server")
+            .version(VERSION)
+            .subcommand_required(true)
+            .subcommands([create, list, revoke])
+    }
+
+    fn exec(&self, matches: &ArgMatches) -> Result<()> {
+        let config = BldConfig::load()?;
+        let (subcommand, matches) = match matches.subcommand() {
+            Some(subcommand) => subcommand,
+            None => return Ok(()),
+        };
+        let name = matches
+            .try_get_one::<String>(NAME)
+            .ok()
+            .flatten()
+            .cloned()
+            .unwrap_or_default();
+        let server = config
+            .remote
+            .server_or_first(matches.get_one::<String>(SERVER))?;
+        let url = format!(
+            "{}://{}:{}",
+            server.http_protocol(),
+            server.host,
+            server.port
+        );
+        let headers = request::server_headers(&config.remote, server)?;
+
+        debug!(
+            "running {API_KEY} {subcommand} subcommand with --name: {name}, --server: {}",
+            server.name
+        );
+
+        System::new().block_on(async move {
+            require_feature(&url, &headers, FEATURE_API_KEYS).await?;
+            match subcommand {
+                "list" => list(url, headers).await,
+                "revoke" => revoke(url, headers, name).await,
+                _ => create(url, headers, name).await,
+            }
+        })
+    }
+}
+
+async fn create(url: String, headers: HashMap<String, String>, name: String) -> Result<()> {
+    let url = format!("{url}/api-keys");
+    debug!("sending http request to {url}");
+    let response = request::post(url, headers, ApiKeyInfo::new(&name)).await?;
+    let response: ApiKeyResponse = serde_json::from_str(&response)?;
+    println!("{}", response.key);
+    eprintln!(
+        "Created api key {}. Store it now since it can't be shown again",
+        response.name
+    );
+    Ok(())
+}
+
+async fn list(url: String, headers: HashMap<String, String>) -> Result<()> {
+    let url = format!("{url}/api-keys");
+    debug!("sending http request to {url}");
+    let response = request::get(url, headers).await?;
+    let keys: Vec<ApiKeyEntry> = serde_json::from_str(&response)?;
+    println!("{}", Table::new(keys).with(Style::modern()));
+    Ok(())
+}
+
+async fn revoke(url: String, headers: HashMap<String, String>, name: String) -> Result<()> {
+    let url = format!("{url}/api-keys/{name}");
+    debug!("sending http request to {url}");
+    request::delete(url, headers).await?;
+    eprintln!("Revoked api key {name}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cli_api_key_create_requires_a_name() {
+        let command = ApiKeyCommand::boxed().interface();
+        let matches = command
+            .clone()
+            .get_matches_from(["api-key", "create", "-n", "ci-bot", "-s", "ci"]);
+        let matches = matches.subcommand_matches(CREATE).unwrap();
+
+        assert_eq!(matches.get_one::<String>(NAME), Some(&"ci-bot".to_string()));
+        assert!(command.try_get_matches_from(["api-key", "create"]).is_err());
+    }
+
+    #[test]
+    fn cli_api_key_revoke_requires_a_name() {
+        let command = ApiKeyCommand::boxed().interface();
+        let matches = command
+            .clone()
+            .get_matches_from(["api-key", "revoke", "-n", "ci-bot"]);
+        let matches = matches.subcommand_matches(REVOKE).unwrap();
+
+        assert_eq!(matches.get_one::<String>(NAME), Some(&"ci-bot".to_string()));
+        assert!(command
+            .clone()
+            .try_get_matches_from(["api-key", "revoke"])
+            .is_err());
+        assert!(command
+            .try_get_matches_from(["api-key", "list", "-s", "ci"])
+            .is_ok());
+    }
+}
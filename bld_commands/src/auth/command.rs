@@ -54,6 +54,10 @@ impl BldCommand for AuthCommand {
                 Ok(())
             }
             Auth::Ldap => Err(anyhow!("unsupported authentication method ldap")),
+            Auth::ApiKey(_) => Err(anyhow!(
+                "server {} authenticates with an api key which doesn't require a login",
+                server.name
+            )),
             Auth::None => Err(anyhow!("no authentication method setup")),
         }
     }
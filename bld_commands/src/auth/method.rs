@@ -1,12 +1,10 @@
 use anyhow::{anyhow, Result};
-use bld_config::definitions;
 use bld_config::OAuth2Info;
+use bld_utils::auth::persist_tokens;
 use oauth2::basic::BasicClient;
 use oauth2::reqwest::http_client;
 use oauth2::{AuthorizationCode, CsrfToken, PkceCodeChallenge, TokenResponse};
-use std::fs::{create_dir, remove_file, File};
-use std::io::{stdin, Write};
-use std::path::PathBuf;
+use std::io::stdin;
 
 fn oauth2_url_summary() {
     println!(
@@ -20,21 +18,6 @@ fn oauth2_input_summary() {
     println!("After logging in input both the provided code and state here.");
 }
 
-fn persist_access_token(server: &str, token: &str) -> Result<()> {
-    let mut path = PathBuf::new();
-    path.push(definitions::REMOTE_SERVER_OAUTH2);
-    if !path.is_dir() {
-        create_dir(&path)?;
-    }
-    path.push(server);
-    if path.is_file() {
-        remove_file(&path)?;
-    }
-    let mut handle = File::create(path)?;
-    handle.write_all(token.as_bytes())?;
-    Ok(())
-}
-
 fn stdin_with_label(label: &str) -> Result<String> {
     let mut value = String::new();
     println!("{label}: ");
@@ -81,7 +64,11 @@ impl Login for OAuth2Info {
             .request(http_client)
             .map_err(|e| anyhow!(e))?;
 
-        persist_access_token(server, token_res.access_token().secret())?;
+        persist_tokens(
+            server,
+            token_res.access_token().secret(),
+            token_res.refresh_token().map(|t| t.secret().as_str()),
+        )?;
 
         Ok(String::new())
     }
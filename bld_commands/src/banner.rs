@@ -0,0 +1,55 @@
+use bld_config::definitions::{GIT_COMMIT, VERSION};
+use bld_config::{AuthValidation, BldConfig};
+use bld_core::database::{new_connection, schema_version};
+use bld_runner::docker_version;
+use std::time::Duration;
+use tokio::time::timeout;
+use tracing::info;
+
+const DOCKER_VERSION_TIMEOUT_SECONDS: u64 = 5;
+
+fn database_info(config: &BldConfig) -> String {
+    let version = new_connection(&config.local.db).and_then(|mut conn| schema_version(&mut conn));
+    match version {
+        Ok(Some(version)) => format!("sqlite, schema version {version}"),
+        Ok(None) => "sqlite, no schema".to_string(),
+        Err(e) => format!("sqlite, unavailable ({e})"),
+    }
+}
+
+async fn docker_info(config: &BldConfig) -> String {
+    let duration = Duration::from_secs(DOCKER_VERSION_TIMEOUT_SECONDS);
+    match timeout(duration, docker_version(&config.local.docker_url)).await {
+        Ok(Ok(version)) => version,
+        Ok(Err(e)) => format!("unavailable ({e})"),
+        Err(_) => "unavailable (timed out)".to_string(),
+    }
+}
+
+/// Logs the information of the environment that a server or a supervisor starts in, so that
+/// it can be included in bug reports. The full configuration is printed by `bld config dump`.
+pub async fn startup_banner(config: &BldConfig, component: &str) {
+    let local = &config.local;
+    let config_file = match BldConfig::path() {
+        Ok(path) if path.is_file() => path.display().to_string(),
+        _ => "none, using the defaults".to_string(),
+    };
+    let auth = match &local.auth {
+        AuthValidation::OAuth2(_) => "oauth2",
+        AuthValidation::Ldap => "ldap",
+        AuthValidation::None => "none",
+    };
+    info!("bld {component}");
+    info!("version: {VERSION} (commit {GIT_COMMIT})");
+    info!("config: {config_file}");
+    info!("server: {}:{}", local.server.host, local.server.port);
+    info!(
+        "supervisor: {}:{} with {} workers",
+        local.supervisor.host, local.supervisor.port, local.supervisor.workers
+    );
+    info!("auth: {auth}");
+    info!("ha-mode: {}", local.ha_mode);
+    info!("logs: {}", local.logs);
+    info!("database: {}", database_info(config));
+    info!("docker: {}", docker_info(config).await);
+}
@@ -0,0 +1,48 @@
+use anyhow::{bail, Result};
+use bld_server::responses::CapabilitiesResponse;
+use bld_utils::request;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tracing::debug;
+
+/// The capabilities of the servers that have been fetched during this invocation.
+static CAPABILITIES: Mutex<Vec<(String, CapabilitiesResponse)>> = Mutex::new(Vec::new());
+
+/// Fetches the capabilities of a server once per invocation. Servers that don't respond
+/// with a manifest are treated as having none of the optional features.
+pub async fn capabilities(
+    url: &str,
+    headers: &HashMap<String, String>,
+) -> Result<CapabilitiesResponse> {
+    let cached = CAPABILITIES
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(u, _)| u == url)
+        .map(|(_, c)| c.clone());
+    if let Some(capabilities) = cached {
+        return Ok(capabilities);
+    }
+    let mut headers = headers.clone();
+    headers.insert("Accept".to_string(), "application/json".to_string());
+    debug!("sending http request to {url}/");
+    let response = request::get(format!("{url}/"), headers).await?;
+    let capabilities = serde_json::from_str(&response).unwrap_or_else(|_| {
+        debug!("no capabilities manifest found for {url}");
+        CapabilitiesResponse::legacy()
+    });
+    let mut cache = CAPABILITIES.lock().unwrap();
+    cache.push((url.to_string(), capabilities.clone()));
+    Ok(capabilities)
+}
+
+pub async fn require_feature(
+    url: &str,
+    headers: &HashMap<String, String>,
+    feature: &str,
+) -> Result<()> {
+    if capabilities(url, headers).await?.feature(feature).is_none() {
+        bail!("the server at {url} doesn't support {feature}");
+    }
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use crate::BldCommand;
+use anyhow::{bail, Result};
+use bld_config::definitions::{TOOL_DEFAULT_PIPELINE, VERSION};
+use bld_core::proxies::PipelineFileSystemProxy;
+use bld_runner::{check_pipeline, lint_report};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use tracing::debug;
+
+const CHECK: &str = "check";
+const PIPELINE: &str = "pipeline";
+
+pub struct CheckCommand;
+
+impl BldCommand for CheckCommand {
+    fn boxed() -> Box<Self> {
+        Box::new(Self)
+    }
+
+    fn id(&self) -> &'static str {
+        CHECK
+    }
+
+    fn interface(&self) -> Command {
+        let pipeline = Arg::new(PIPELINE)
+            .short('p')
+            .long(PIPELINE)
+            .help("The name of the pipeline to check")
+            .default_value(TOOL_DEFAULT_PIPELINE)
+            .action(ArgAction::Set);
+
+        Command::new(CHECK)
+            .about("Checks a local pipeline strictly and lists all of its problems, such as unknown entries, undeclared variables and missing called pipelines")
+            .version(VERSION)
+            .args(&[pipeline])
+    }
+
+    fn exec(&self, matches: &ArgMatches) -> Result<()> {
+        // using an unwrap here because the pipeline option has a default value.
+        let pipeline = matches.get_one::<String>(PIPELINE).cloned().unwrap();
+
+        debug!("running {} subcommand with --pipeline: {}", CHECK, pipeline);
+
+        let prx = PipelineFileSystemProxy::Local;
+        let problems = check_pipeline(&prx, &pipeline)?;
+        if problems.is_empty() {
+            println!("pipeline {pipeline} has no problems");
+            return Ok(());
+        }
+        print!("{}", lint_report(&prx.read(&pipeline)?, &problems));
+        bail!("pipeline {pipeline} has {} problem(s)", problems.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cli_check_pipeline_arg_defaults_to_the_default_pipeline() {
+        let command = CheckCommand::boxed().interface();
+        let matches = command.get_matches_from(["check"]);
+
+        assert_eq!(
+            matches.get_one::<String>(PIPELINE),
+            Some(&TOOL_DEFAULT_PIPELINE.to_string())
+        );
+
+        let command = CheckCommand::boxed().interface();
+        let matches = command.get_matches_from(["check", "-p", "deploy.yaml"]);
+
+        assert_eq!(
+            matches.get_one::<String>(PIPELINE),
+            Some(&"deploy.yaml".to_string())
+        )
+    }
+}
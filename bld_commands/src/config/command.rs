@@ -3,9 +3,11 @@ use anyhow::Result;
 use bld_config::definitions::VERSION;
 use bld_config::{Auth, AuthValidation, BldConfig, BldLocalConfig, BldRemoteConfig};
 use bld_utils::term;
-use clap::{ArgMatches, Command};
+use clap::{Arg, ArgAction, ArgMatches, Command};
 
 static CONFIG: &str = "config";
+static DUMP: &str = "dump";
+static REDACTED: &str = "redacted";
 
 pub struct ConfigCommand;
 
@@ -39,6 +41,9 @@ impl ConfigCommand {
         println!("  - host: {}", local.supervisor.host);
         println!("  - port: {}", local.supervisor.port);
         println!("  - workers: {}", local.supervisor.workers);
+        if let Some(socket) = &local.supervisor.socket {
+            println!("  - socket: {}", socket);
+        }
         if let Some(tls) = &local.supervisor.tls {
             println!("  - tls:");
             println!("    - cert-chain:  {}", tls.cert_chain);
@@ -47,6 +52,22 @@ impl ConfigCommand {
         println!("- logs: {}", local.logs);
         println!("- db: {}", local.db);
         println!("- docker-url: {}", local.docker_url);
+        if let Some(score) = local.container_min_oom_score {
+            println!("- container-min-oom-score: {}", score);
+        }
+        println!("- artifacts-zstd-level: {}", local.artifacts_zstd_level);
+        if !local.container_oom_kill_disable_allowlist.is_empty() {
+            println!("- container-oom-kill-disable-allowlist:");
+            for pipeline in local.container_oom_kill_disable_allowlist.iter() {
+                println!("  - {}", pipeline);
+            }
+        }
+        if !local.container_volumes_allowlist.is_empty() {
+            println!("- container-volumes-allowlist:");
+            for path in local.container_volumes_allowlist.iter() {
+                println!("  - {}", path);
+            }
+        }
         Ok(())
     }
 
@@ -58,6 +79,9 @@ impl ConfigCommand {
             println!("- host: {}", server.host);
             println!("- port: {}", server.port);
             println!("- tls:  {}", server.tls);
+            if let Some(ca_file) = &server.ca_file {
+                println!("- ca-file: {ca_file}");
+            }
             match &server.auth {
                 Auth::OAuth2(info) => {
                     println!("- auth:");
@@ -78,6 +102,11 @@ impl ConfigCommand {
                     println!("- auth:");
                     println!("  - method: ldap");
                 }
+                Auth::ApiKey(_) => {
+                    println!("- auth:");
+                    println!("  - method: api-key");
+                    println!("  - key: ***********");
+                }
                 _ => {}
             }
             if i < remote.servers.len() - 1 {
@@ -106,13 +135,29 @@ impl BldCommand for ConfigCommand {
     }
 
     fn interface(&self) -> Command {
+        let redacted = Arg::new(REDACTED)
+            .long(REDACTED)
+            .help(
+                "Masks the hosts, urls and user names of the configuration as well as its secrets",
+            )
+            .action(ArgAction::SetTrue);
+
+        let dump = Command::new(DUMP)
+            .about("Prints the effective configuration, with the defaults applied, as yaml with its secrets masked")
+            .arg(redacted);
+
         Command::new(CONFIG)
             .about("Lists bld's configuration")
             .version(VERSION)
+            .subcommand(dump)
     }
 
-    fn exec(&self, _matches: &ArgMatches) -> Result<()> {
+    fn exec(&self, matches: &ArgMatches) -> Result<()> {
         let config = BldConfig::load()?;
+        if let Some(matches) = matches.subcommand_matches(DUMP) {
+            print!("{}", config.dump(matches.get_flag(REDACTED))?);
+            return Ok(());
+        }
         Self::list_all(&config)
     }
 }
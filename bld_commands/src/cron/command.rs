@@ -0,0 +1,209 @@
+use crate::capabilities::require_feature;
+use crate::run::parse_variables;
+use crate::BldCommand;
+use actix_web::rt::System;
+use anyhow::Result;
+use bld_config::definitions::VERSION;
+use bld_config::BldConfig;
+use bld_server::requests::ScheduleInfo;
+use bld_server::responses::{ScheduleEntry, FEATURE_SCHEDULES};
+use bld_utils::request;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use tabled::{Style, Table};
+use tracing::debug;
+
+static CRON: &str = "cron";
+static ADD: &str = "add";
+static LIST: &str = "ls";
+static REMOVE: &str = "rm";
+static PIPELINE: &str = "pipeline";
+static SCHEDULE: &str = "schedule";
+static VARIABLES: &str = "variables";
+static ENVIRONMENT: &str = "environment";
+static DISABLED: &str = "disabled";
+static ID: &str = "id";
+static SERVER: &str = "server";
+static JSON: &str = "json";
+
+pub struct CronCommand;
+
+impl BldCommand for CronCommand {
+    fn boxed() -> Box<Self> {
+        Box::new(Self)
+    }
+
+    fn id(&self) -> &'static str {
+        CRON
+    }
+
+    fn interface(&self) -> Command {
+        let server = Arg::new(SERVER)
+            .short('s')
+            .long(SERVER)
+            .help("The name of the server that runs the schedules")
+            .action(ArgAction::Set);
+
+        let pipeline = Arg::new(PIPELINE)
+            .short('p')
+            .long(PIPELINE)
+            .help("The name of the pipeline to run on the schedule")
+            .required(true)
+            .action(ArgAction::Set);
+
+        let schedule = Arg::new(SCHEDULE)
+            .long(SCHEDULE)
+            .help("The cron expression of the schedule in UTC, such as \"0 2 * * *\" for every night at 02:00")
+            .required(true)
+            .action(ArgAction::Set);
+
+        let variables = Arg::new(VARIABLES)
+            .short('v')
+            .long(VARIABLES)
+            .help("Define values for variables of the scheduled runs")
+            .action(ArgAction::Append);
+
+        let environment = Arg::new(ENVIRONMENT)
+            .short('e')
+            .long(ENVIRONMENT)
+            .help("Define values for environment variables of the scheduled runs")
+            .action(ArgAction::Append);
+
+        let disabled = Arg::new(DISABLED)
+            .long(DISABLED)
+            .help("Add the schedule disabled, so that it doesn't run the pipeline")
+            .action(ArgAction::SetTrue);
+
+        let json = Arg::new(JSON)
+            .long(JSON)
+            .help("Prints the schedules as json instead of a table")
+            .action(ArgAction::SetTrue);
+
+        let id = Arg::new(ID)
+            .long(ID)
+            .help("The id of the schedule to remove")
+            .required(true)
+            .action(ArgAction::Set);
+
+        let add = Command::new(ADD)
+            .about("Adds a schedule that runs a pipeline of a server")
+            .args(&[
+                pipeline,
+                schedule,
+                variables,
+                environment,
+                disabled,
+                server.clone(),
+            ]);
+
+        let list = Command::new(LIST)
+            .about("Lists the schedules of a server")
+            .args(&[json, server.clone()]);
+
+        let remove = Command::new(REMOVE)
+            .about("Removes a schedule from a server")
+            .args(&[id, server]);
+
+        Command::new(CRON)
+            .about("Manages the scheduled runs of pipelines on a server")
+            .version(VERSION)
+            .subcommand_required(true)
+            .subcommands([add, list, remove])
+    }
+
+    fn exec(&self, matches: &ArgMatches) -> Result<()> {
+        let config = BldConfig::load()?;
+        let (subcommand, matches) = match matches.subcommand() {
+            Some(subcommand) => subcommand,
+            None => return Ok(()),
+        };
+        let server = config
+            .remote
+            .server_or_first(matches.get_one::<String>(SERVER))?;
+        let url = format!(
+            "{}://{}:{}",
+            server.http_protocol(),
+            server.host,
+            server.port
+        );
+        let headers = request::server_headers(&config.remote, server)?;
+
+        debug!(
+            "running {CRON} {subcommand} subcommand with --server: {}",
+            server.name
+        );
+
+        System::new().block_on(async move {
+            require_feature(&url, &headers, FEATURE_SCHEDULES).await?;
+            let url = format!("{url}/schedules");
+            match subcommand {
+                "add" => {
+                    let pipeline = matches.get_one::<String>(PIPELINE).unwrap();
+                    let schedule = matches.get_one::<String>(SCHEDULE).unwrap();
+                    let mut info = ScheduleInfo::new(pipeline, schedule);
+                    info.variables = Some(parse_variables(matches, VARIABLES));
+                    info.environment = Some(parse_variables(matches, ENVIRONMENT));
+                    info.enabled = Some(!matches.get_flag(DISABLED));
+                    debug!("sending http request to {url}");
+                    let response = request::post(url, headers, info).await?;
+                    let entry: ScheduleEntry = serde_json::from_str(&response)?;
+                    println!("{}", entry.id);
+                    eprintln!(
+                        "Added schedule of {} with next run at {}",
+                        entry.pipeline, entry.next_run
+                    );
+                }
+                "ls" => {
+                    debug!("sending http request to {url}");
+                    let response = request::get(url, headers).await?;
+                    let entries: Vec<ScheduleEntry> = serde_json::from_str(&response)?;
+                    if matches.get_flag(JSON) {
+                        println!("{}", serde_json::to_string_pretty(&entries)?);
+                    } else {
+                        println!("{}", Table::new(entries).with(Style::modern()));
+                    }
+                }
+                _ => {
+                    let id = matches.get_one::<String>(ID).unwrap();
+                    let url = format!("{url}/{id}");
+                    debug!("sending http request to {url}");
+                    request::delete(url, headers).await?;
+                    println!("Removed schedule {id}");
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cli_cron_add_requires_a_pipeline_and_a_schedule() {
+        let command = CronCommand::boxed().interface();
+        let matches = command.clone().get_matches_from([
+            "cron",
+            "add",
+            "-p",
+            "nightly.yaml",
+            "--schedule",
+            "0 2 * * *",
+            "-v",
+            "branch=main",
+        ]);
+        let matches = matches.subcommand_matches(ADD).unwrap();
+        assert_eq!(
+            matches.get_one::<String>(SCHEDULE),
+            Some(&"0 2 * * *".to_string())
+        );
+        assert_eq!(parse_variables(matches, VARIABLES)["branch"], "main");
+        assert!(!matches.get_flag(DISABLED));
+
+        assert!(command
+            .clone()
+            .try_get_matches_from(["cron", "add", "-p", "nightly.yaml"])
+            .is_err());
+        assert!(command.try_get_matches_from(["cron", "rm"]).is_err());
+    }
+}
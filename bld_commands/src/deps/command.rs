@@ -0,0 +1,134 @@
+use crate::BldCommand;
+use actix_web::rt::System;
+use anyhow::{bail, Result};
+use bld_config::definitions::VERSION;
+use bld_config::BldConfig;
+use bld_core::proxies::PipelineFileSystemProxy;
+use bld_runner::{dependency_tree, DependencyNode, DependencyState};
+use bld_utils::request;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use tracing::debug;
+
+const DEPS: &str = "deps";
+const PIPELINE: &str = "pipeline";
+const SERVER: &str = "server";
+
+pub struct DepsCommand;
+
+impl BldCommand for DepsCommand {
+    fn boxed() -> Box<Self> {
+        Box::new(Self)
+    }
+
+    fn id(&self) -> &'static str {
+        DEPS
+    }
+
+    fn interface(&self) -> Command {
+        let pipeline = Arg::new(PIPELINE)
+            .short('p')
+            .long(PIPELINE)
+            .help("The name of the pipeline")
+            .required(true)
+            .action(ArgAction::Set);
+
+        let server = Arg::new(SERVER)
+            .short('s')
+            .long(SERVER)
+            .help("The name of the server to list the dependencies of the pipeline from")
+            .action(ArgAction::Set);
+
+        Command::new(DEPS)
+            .about("Shows the pipelines called by a local pipeline as a tree, flagging the missing ones and the cycles")
+            .version(VERSION)
+            .args(&[pipeline, server])
+    }
+
+    fn exec(&self, matches: &ArgMatches) -> Result<()> {
+        // using an unwrap here because the pipeline option is required.
+        let pipeline = matches.get_one::<String>(PIPELINE).cloned().unwrap();
+        let server = matches.get_one::<String>(SERVER);
+
+        debug!(
+            "running {} subcommand with --pipeline: {}, --server: {:?}",
+            DEPS, pipeline, server
+        );
+
+        match server {
+            Some(server) => remote_deps(server, pipeline),
+            None => local_deps(&pipeline),
+        }
+    }
+}
+
+fn local_deps(pipeline: &str) -> Result<()> {
+    let tree = dependency_tree(&PipelineFileSystemProxy::Local, pipeline);
+    for line in render(&tree) {
+        println!("{line}");
+    }
+    if tree.has_errors() {
+        bail!("pipeline {pipeline} has missing, invalid or cyclic dependencies");
+    }
+    Ok(())
+}
+
+fn remote_deps(server: &str, pipeline: String) -> Result<()> {
+    let config = BldConfig::load()?;
+    let server = config.remote.server(server)?;
+    let protocol = server.http_protocol();
+    let url = format!("{protocol}://{}:{}/deps", server.host, server.port);
+    let headers = request::server_headers(&config.remote, server)?;
+
+    let mut deps = System::new().block_on(async move {
+        debug!("sending http request to {url}");
+        let response = request::post(url, headers, pipeline).await?;
+        let deps: Vec<String> = serde_json::from_str(&response)?;
+        Ok::<Vec<String>, anyhow::Error>(deps)
+    })?;
+
+    deps.sort();
+    for dep in deps {
+        println!("{dep}");
+    }
+    Ok(())
+}
+
+fn render_node(node: &DependencyNode, depth: usize, lines: &mut Vec<String>) {
+    let state = match &node.state {
+        DependencyState::Resolved => String::new(),
+        state => format!(" ({state})"),
+    };
+    lines.push(format!("{}{}{state}", "  ".repeat(depth), node.pipeline));
+    for child in node.children.iter() {
+        render_node(child, depth + 1, lines);
+    }
+}
+
+/// Renders the dependencies of a pipeline as an indented list with a line for each call.
+fn render(tree: &DependencyNode) -> Vec<String> {
+    let mut lines = vec![];
+    render_node(tree, 0, &mut lines);
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cli_deps_pipeline_arg_is_required() {
+        let command = DepsCommand::boxed().interface();
+        let matches = command.try_get_matches_from(["deps"]);
+
+        assert!(matches.is_err())
+    }
+
+    #[test]
+    fn cli_deps_server_arg_accepts_value() {
+        let server = "mock_server";
+        let command = DepsCommand::boxed().interface();
+        let matches = command.get_matches_from(["deps", "-p", "main.yaml", "-s", server]);
+
+        assert_eq!(matches.get_one::<String>(SERVER), Some(&server.to_string()))
+    }
+}
@@ -0,0 +1,256 @@
+use crate::BldCommand;
+use anyhow::Result;
+use bld_config::definitions::{TOOL_DEFAULT_PIPELINE, VERSION};
+use bld_core::proxies::PipelineFileSystemProxy;
+use bld_runner::{BuildStep, Pipeline, Variable};
+use bld_utils::term::print_info;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs::{create_dir_all, write};
+use std::path::Path;
+use tracing::debug;
+
+const DOCUMENT: &str = "document";
+const PIPELINE: &str = "pipeline";
+const OUTPUT: &str = "output";
+const DOCS_DIR: &str = "docs/pipelines";
+
+pub struct DocumentCommand;
+
+impl BldCommand for DocumentCommand {
+    fn boxed() -> Box<Self> {
+        Box::new(Self)
+    }
+
+    fn id(&self) -> &'static str {
+        DOCUMENT
+    }
+
+    fn interface(&self) -> Command {
+        let pipeline = Arg::new(PIPELINE)
+            .short('p')
+            .long(PIPELINE)
+            .help("The name of the pipeline to document")
+            .default_value(TOOL_DEFAULT_PIPELINE)
+            .action(ArgAction::Set);
+
+        let output = Arg::new(OUTPUT)
+            .short('o')
+            .long(OUTPUT)
+            .help("The path of the generated markdown file. Defaults to docs/pipelines/<name>.md")
+            .action(ArgAction::Set);
+
+        Command::new(DOCUMENT)
+            .about("Generates markdown documentation for a local pipeline using the comments of its variables and environment")
+            .version(VERSION)
+            .args(&[pipeline, output])
+    }
+
+    fn exec(&self, matches: &ArgMatches) -> Result<()> {
+        // using an unwrap here because pipeline option has a default value.
+        let pipeline = matches.get_one::<String>(PIPELINE).cloned().unwrap();
+        let name = pipeline.trim_end_matches(".yaml");
+        let output = matches
+            .get_one::<String>(OUTPUT)
+            .cloned()
+            .unwrap_or_else(|| format!("{DOCS_DIR}/{name}.md"));
+
+        debug!(
+            "running {} subcommand with --pipeline: {}, --output: {}",
+            DOCUMENT, pipeline, output
+        );
+
+        let content = PipelineFileSystemProxy::Local.read(&pipeline)?;
+        let markdown = document(name, &content)?;
+        if let Some(parent) = Path::new(&output).parent() {
+            create_dir_all(parent)?;
+        }
+        write(&output, markdown)?;
+        print_info(&format!("documentation generated at {output}"))?;
+        Ok(())
+    }
+}
+
+/// Extracts the comments immediately preceding the entries of a top level section of the
+/// pipeline, since the yaml parser discards them.
+fn comments(content: &str, section: &str) -> HashMap<String, String> {
+    let mut comments = HashMap::new();
+    let mut pending: Vec<String> = vec![];
+    let mut in_section = false;
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if let Some(comment) = trimmed.strip_prefix('#') {
+            pending.push(comment.trim().to_string());
+            continue;
+        }
+        if trimmed.is_empty() {
+            pending.clear();
+            continue;
+        }
+        let is_top_level = !line.starts_with(' ') && !line.starts_with('-');
+        if is_top_level {
+            in_section = trimmed.trim_end_matches(':') == section;
+            pending.clear();
+            continue;
+        }
+        if in_section {
+            let entry = trimmed.trim_start_matches('-').trim();
+            if let Some((key, _)) = entry.split_once(':') {
+                if !pending.is_empty() {
+                    comments.insert(key.trim().to_string(), pending.join(" "));
+                }
+            }
+        }
+        pending.clear();
+    }
+    comments
+}
+
+/// The comments at the start of the file are used as the description of the pipeline.
+fn description(content: &str) -> String {
+    content
+        .lines()
+        .map(|l| l.trim())
+        .take_while(|l| l.starts_with('#') || l.is_empty())
+        .filter_map(|l| l.strip_prefix('#'))
+        .map(|l| l.trim())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+fn table(
+    markdown: &mut String,
+    title: &str,
+    entries: &[Variable],
+    comments: &HashMap<String, String>,
+) -> Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    writeln!(markdown, "## {title}\n")?;
+    writeln!(markdown, "| Name | Default | Description |")?;
+    writeln!(markdown, "| ---- | ------- | ----------- |")?;
+    for entry in entries.iter() {
+        let comment = comments.get(&entry.name).map(|c| &c[..]).unwrap_or("");
+        writeln!(
+            markdown,
+            "| {} | {} | {} |",
+            entry.name, entry.default_value, comment
+        )?;
+    }
+    writeln!(markdown)?;
+    Ok(())
+}
+
+fn steps(markdown: &mut String, steps: &[BuildStep], indent: &str) -> Result<()> {
+    for (i, step) in steps.iter().enumerate() {
+        let name = step
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("Step {}", i + 1));
+        match &step.group {
+            Some(group) if group.parallel => writeln!(markdown, "{indent}- {name} (parallel)")?,
+            _ => writeln!(markdown, "{indent}- {name}")?,
+        }
+        if let Some(group) = &step.group {
+            self::steps(markdown, &group.steps, &format!("{indent}  "))?;
+        }
+    }
+    Ok(())
+}
+
+fn document(name: &str, content: &str) -> Result<String> {
+    let pipeline = Pipeline::parse(content)?;
+    let mut markdown = String::new();
+    let title = pipeline.name.as_deref().unwrap_or(name);
+    writeln!(markdown, "# {title}\n")?;
+    let description = description(content);
+    if !description.is_empty() {
+        writeln!(markdown, "{description}\n")?;
+    }
+    writeln!(markdown, "Runs on: {}\n", pipeline.runs_on)?;
+    table(
+        &mut markdown,
+        "Variables",
+        &pipeline.variables,
+        &comments(content, "variables"),
+    )?;
+    table(
+        &mut markdown,
+        "Environment",
+        &pipeline.environment,
+        &comments(content, "environment"),
+    )?;
+    if !pipeline.steps.is_empty() {
+        writeln!(markdown, "## Steps\n")?;
+        steps(&mut markdown, &pipeline.steps, "")?;
+    }
+    Ok(markdown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PIPELINE_CONTENT: &str = r"# Builds the project
+# and runs its tests.
+name: mock pipeline
+runs-on: machine
+
+variables:
+# The branch to build
+- BRANCH: master
+- CONFIG: release
+
+environment:
+# Enables the tests
+- RUN_TESTS: true
+
+steps:
+- name: build
+  exec:
+  - echo build
+";
+
+    #[test]
+    fn cli_document_pipeline_arg_accepts_value() {
+        let pipeline_name = "mock_pipeline_name";
+        let command = DocumentCommand::boxed().interface();
+        let matches = command.get_matches_from(["document", "-p", pipeline_name]);
+
+        assert_eq!(
+            matches.get_one::<String>(PIPELINE),
+            Some(&pipeline_name.to_string())
+        )
+    }
+
+    #[test]
+    fn cli_document_output_arg_accepts_value() {
+        let output = "mock_output.md";
+        let command = DocumentCommand::boxed().interface();
+        let matches = command.get_matches_from(["document", "-o", output]);
+
+        assert_eq!(matches.get_one::<String>(OUTPUT), Some(&output.to_string()))
+    }
+
+    #[test]
+    fn document_extracts_comments_of_entries() {
+        let variables = comments(PIPELINE_CONTENT, "variables");
+        let environment = comments(PIPELINE_CONTENT, "environment");
+
+        assert_eq!(
+            variables.get("BRANCH"),
+            Some(&"The branch to build".to_string())
+        );
+        assert_eq!(variables.get("CONFIG"), None);
+        assert_eq!(
+            environment.get("RUN_TESTS"),
+            Some(&"Enables the tests".to_string())
+        );
+        assert_eq!(
+            description(PIPELINE_CONTENT),
+            "Builds the project and runs its tests."
+        );
+    }
+}
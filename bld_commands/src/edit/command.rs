@@ -0,0 +1,192 @@
+use crate::capabilities::require_feature;
+use crate::BldCommand;
+use actix_web::rt::System;
+use anyhow::{anyhow, bail, Result};
+use bld_config::{definitions::VERSION, BldConfig};
+use bld_core::proxies::content_hash;
+use bld_runner::Pipeline;
+use bld_server::endpoints::LOCK_TOKEN_HEADER;
+use bld_server::requests::{LockInfo, PushInfo};
+use bld_server::responses::{LockResponse, PullResponse, FEATURE_PIPELINE_LOCKS};
+use bld_utils::cache::ResponseCache;
+use bld_utils::request;
+use bld_utils::term::print_info;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use std::collections::HashMap;
+use std::env::{temp_dir, var};
+use std::fs::{read_to_string, remove_file, write};
+use std::path::Path;
+use std::process;
+use tracing::debug;
+use uuid::Uuid;
+
+const EDIT: &str = "edit";
+const SERVER: &str = "server";
+const PIPELINE: &str = "pipeline";
+const EDIT_LOCK_TTL_SECONDS: u64 = 1800;
+
+pub struct EditCommand;
+
+impl BldCommand for EditCommand {
+    fn boxed() -> Box<Self> {
+        Box::new(Self)
+    }
+
+    fn id(&self) -> &'static str {
+        EDIT
+    }
+
+    fn interface(&self) -> Command {
+        let server = Arg::new(SERVER)
+            .short('s')
+            .long(SERVER)
+            .help("The name of the bld server")
+            .action(ArgAction::Set);
+
+        let pipeline = Arg::new(PIPELINE)
+            .short('p')
+            .long(PIPELINE)
+            .help("The name of the pipeline")
+            .required(true)
+            .action(ArgAction::Set);
+
+        Command::new(EDIT)
+            .about("Locks a pipeline of a bld server and opens it in the editor, pushing the changes when the editor exits")
+            .version(VERSION)
+            .args(&[server, pipeline])
+    }
+
+    fn exec(&self, matches: &ArgMatches) -> Result<()> {
+        let config = BldConfig::load()?;
+        let server = config
+            .remote
+            .server_or_first(matches.get_one::<String>(SERVER))?;
+        // using an unwrap here because the pipeline option is required.
+        let pip = matches.get_one::<String>(PIPELINE).cloned().unwrap();
+
+        debug!(
+            "running {EDIT} subcommand with --server: {} and --pipeline: {pip}",
+            server.name
+        );
+
+        let headers = request::server_headers(&config.remote, server)?;
+        let url = format!(
+            "{}://{}:{}",
+            server.http_protocol(),
+            server.host,
+            server.port
+        );
+
+        let cache = ResponseCache::new(server);
+        let result = System::new().block_on(do_edit(url, headers, pip.clone()));
+        cache.invalidate_pipeline(&pip);
+        result
+    }
+}
+
+/// Holds the lock of the pipeline for the duration of the edit so that pushes of other
+/// users are rejected, and releases it regardless of the outcome.
+async fn do_edit(url: String, headers: HashMap<String, String>, name: String) -> Result<()> {
+    require_feature(&url, &headers, FEATURE_PIPELINE_LOCKS).await?;
+    let lock_url = format!("{url}/pipelines/{name}/lock");
+    debug!("sending http request to {lock_url}");
+    let lock_info = LockInfo::new(Some(EDIT_LOCK_TTL_SECONDS), None);
+    let lock: LockResponse = request::post(lock_url.clone(), headers.clone(), lock_info)
+        .await
+        .and_then(|r| serde_json::from_str(&r).map_err(|e| anyhow!(e)))?;
+
+    let result = edit_locked(&url, &headers, &name, &lock.token).await;
+
+    let mut headers = headers;
+    headers.insert(LOCK_TOKEN_HEADER.to_string(), lock.token);
+    debug!("sending http request to {lock_url}");
+    let release = request::delete(lock_url, headers).await;
+    result.and(release.map(|_| ()))
+}
+
+async fn edit_locked(
+    url: &str,
+    headers: &HashMap<String, String>,
+    name: &str,
+    token: &str,
+) -> Result<()> {
+    let pull_url = format!("{url}/pull");
+    debug!("sending http request to {pull_url}");
+    let pulled: PullResponse = request::post(pull_url, headers.clone(), name.to_string())
+        .await
+        .and_then(|r| serde_json::from_str(&r).map_err(|e| anyhow!(e)))?;
+
+    let path = temp_dir().join(format!("bld-edit-{}.yaml", Uuid::new_v4()));
+    write(&path, &pulled.content)?;
+    open_editor(&path)?;
+    let content = read_to_string(&path)?;
+    if content == pulled.content {
+        remove_file(&path)?;
+        return print_info(&format!("no changes made to pipeline {name}"));
+    }
+
+    // the edited file is kept when the pipeline can't be pushed so that changes aren't lost.
+    if let Err(e) = Pipeline::parse(&content) {
+        bail!(
+            "invalid pipeline, changes are kept in {}. {e}",
+            path.display()
+        );
+    }
+    let mut info = PushInfo::new(name, &content);
+    info.base_hash = Some(content_hash(&pulled.content));
+    info.lock_token = Some(token.to_string());
+    let push_url = format!("{url}/push");
+    debug!("sending http request to {push_url}");
+    if let Err(e) = request::post(push_url, headers.clone(), info).await {
+        bail!("push failed, changes are kept in {}. {e}", path.display());
+    }
+    remove_file(&path)?;
+    print_info(&format!("pipeline {name} updated"))
+}
+
+fn open_editor(path: &Path) -> Result<()> {
+    let editor = var("VISUAL")
+        .or_else(|_| var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let mut parts = editor.split_whitespace();
+    let program = parts
+        .next()
+        .ok_or_else(|| anyhow!("no editor configured"))?;
+    let status = process::Command::new(program)
+        .args(parts)
+        .arg(path)
+        .status()?;
+    if !status.success() {
+        bail!("editor {editor} exited with {status}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cli_edit_pipeline_arg_accepts_value() {
+        let pipeline_name = "mock_pipeline_name";
+        let command = EditCommand::boxed().interface();
+        let matches = command.get_matches_from(["edit", "-p", pipeline_name]);
+
+        assert_eq!(
+            matches.get_one::<String>(PIPELINE),
+            Some(&pipeline_name.to_string())
+        )
+    }
+
+    #[test]
+    fn cli_edit_server_arg_accepts_value() {
+        let server_name = "mock_server_name";
+        let command = EditCommand::boxed().interface();
+        let matches = command.get_matches_from(["edit", "-p", "mockPipeline", "-s", server_name]);
+
+        assert_eq!(
+            matches.get_one::<String>(SERVER),
+            Some(&server_name.to_string())
+        )
+    }
+}
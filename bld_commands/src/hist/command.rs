@@ -1,15 +1,50 @@
+use crate::capabilities::require_feature;
 use crate::BldCommand;
 use actix_web::rt::System;
-use anyhow::Result;
-use bld_config::{definitions::VERSION, BldConfig};
-use bld_server::responses::HistoryEntry;
+use anyhow::{bail, Result};
+use bld_config::definitions::{HIST_PAGE_SIZE, VERSION};
+use bld_config::BldConfig;
+use bld_core::database::pipeline_runs::{
+    PR_STATE_FAULTED, PR_STATE_FINISHED, PR_STATE_INITIAL, PR_STATE_INTERRUPTED, PR_STATE_QUEUED,
+    PR_STATE_RUNNING,
+};
+use bld_server::requests::{parse_label, HistQueryInfo};
+use bld_server::responses::{HistoryEntry, RunSummary, FEATURE_RUN_SUMMARIES};
 use bld_utils::request;
-use clap::{Arg, ArgAction, ArgMatches, Command};
-use tabled::{Style, Table};
+use bld_utils::term::format_duration;
+use chrono::{Duration, NaiveDate, NaiveDateTime, Utc};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use std::collections::HashMap;
+use std::io::{self, stdin, stdout, BufRead, ErrorKind, IsTerminal, Write};
+use std::thread;
+use tabled::{Style, Table, Tabled};
+use tokio::signal::ctrl_c;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
 use tracing::debug;
 
 static HIST: &str = "hist";
 static SERVER: &str = "server";
+static PIPELINE: &str = "pipeline";
+static STATE: &str = "state";
+static LIMIT: &str = "limit";
+static JSON: &str = "json";
+static SINCE: &str = "since";
+static UNTIL: &str = "until";
+static NO_PAGER: &str = "no-pager";
+static LABEL: &str = "label";
+static PAGE: &str = "page";
+static PER_PAGE: &str = "per-page";
+static ID: &str = "id";
+static SLOWEST: &str = "slowest";
+static TIME_FORMAT: &str = "%F %X";
+
+#[derive(Tabled)]
+struct CommandRow {
+    step: String,
+    index: usize,
+    command: String,
+    duration: String,
+}
 
 pub struct HistCommand;
 
@@ -29,10 +64,95 @@ impl BldCommand for HistCommand {
             .action(ArgAction::Set)
             .help("The name of the server from which to fetch execution history");
 
+        let pipeline = Arg::new(PIPELINE)
+            .short('p')
+            .long("pipeline")
+            .visible_alias("name")
+            .action(ArgAction::Set)
+            .help("Fetch the execution history of a specific pipeline");
+
+        let state = Arg::new(STATE)
+            .long("state")
+            .action(ArgAction::Set)
+            .value_parser([
+                PR_STATE_INITIAL,
+                PR_STATE_QUEUED,
+                PR_STATE_RUNNING,
+                PR_STATE_FINISHED,
+                PR_STATE_FAULTED,
+                PR_STATE_INTERRUPTED,
+            ])
+            .help("Fetch the execution history of runs in a specific state");
+
+        let limit = Arg::new(LIMIT)
+            .short('l')
+            .long("limit")
+            .action(ArgAction::Set)
+            .value_parser(value_parser!(i64))
+            .help("The maximum number of history entries to fetch");
+
+        let json = Arg::new(JSON)
+            .long("json")
+            .action(ArgAction::SetTrue)
+            .help("Prints the history entries as json instead of a table");
+
+        let since = Arg::new(SINCE)
+            .long("since")
+            .action(ArgAction::Set)
+            .help("Fetch the runs started after a duration ago such as 7d, 12h or 30m or after a date such as 2024-01-31");
+
+        let until = Arg::new(UNTIL)
+            .long("until")
+            .action(ArgAction::Set)
+            .help("Fetch the runs started before a duration ago such as 7d, 12h or 30m or before a date such as 2024-01-31");
+
+        let no_pager = Arg::new(NO_PAGER)
+            .long("no-pager")
+            .action(ArgAction::SetTrue)
+            .help(
+                "Prints all the history entries at once instead of a page at a time in a terminal",
+            );
+
+        let label = Arg::new(LABEL)
+            .long(LABEL)
+            .action(ArgAction::Append)
+            .help("Fetch the runs with a label in the name:value format, with every label required when given more than once");
+
+        let page = Arg::new(PAGE)
+            .long(PAGE)
+            .action(ArgAction::Set)
+            .value_parser(value_parser!(i64).range(1..))
+            .help(
+                "Fetch a single page of the history, starting from 1, instead of paging through it",
+            );
+
+        let per_page = Arg::new(PER_PAGE)
+            .long(PER_PAGE)
+            .action(ArgAction::Set)
+            .value_parser(value_parser!(i64).range(1..))
+            .help("The number of history entries in a page");
+
+        let id = Arg::new(ID)
+            .long(ID)
+            .action(ArgAction::Set)
+            .help("Prints the duration of every command of a run instead of the history");
+
+        let slowest = Arg::new(SLOWEST)
+            .long(SLOWEST)
+            .action(ArgAction::Set)
+            .num_args(0..=1)
+            .default_missing_value("10")
+            .value_parser(value_parser!(u64).range(1..))
+            .requires(ID)
+            .help("Prints only the slowest commands of the run, 10 unless a number is given");
+
         Command::new(HIST)
             .about("Fetches execution history of pipelines on a server")
             .version(VERSION)
-            .args(&[server])
+            .args(&[
+                server, pipeline, state, limit, json, since, until, no_pager, label, page,
+                per_page, id, slowest,
+            ])
     }
 
     fn exec(&self, matches: &ArgMatches) -> Result<()> {
@@ -41,22 +161,201 @@ impl BldCommand for HistCommand {
             .remote
             .server_or_first(matches.get_one::<String>(SERVER))?;
 
-        debug!("running {} subcommand with --server: {}", HIST, server.name);
+        if let Some(run_id) = matches.get_one::<String>(ID) {
+            let protocol = server.http_protocol();
+            let url = format!("{protocol}://{}:{}", server.host, server.port);
+            let headers = request::server_headers(&config.remote, server)?;
+            let slowest = matches.get_one::<u64>(SLOWEST).map(|s| *s as usize);
+            debug!(
+                "running {} subcommand with --server: {}, --id: {run_id}, --slowest: {slowest:?}",
+                HIST, server.name
+            );
+            let summary = System::new().block_on(async move {
+                require_feature(&url, &headers, FEATURE_RUN_SUMMARIES).await?;
+                let url = format!("{url}/runs/{run_id}/summary");
+                debug!("sending http request to {url}");
+                let response = request::get(url, headers).await?;
+                let summary: RunSummary = serde_json::from_str(&response)?;
+                Ok::<RunSummary, anyhow::Error>(summary)
+            })?;
+            return print_summary(&summary, slowest, matches.get_flag(JSON));
+        }
+
+        let mut params = HistQueryInfo::new(
+            matches.get_one::<String>(PIPELINE).cloned(),
+            matches.get_one::<String>(STATE).cloned(),
+            matches.get_one::<i64>(LIMIT).cloned(),
+        );
+        params.from = matches
+            .get_one::<String>(SINCE)
+            .map(|since| time_filter(since, false))
+            .transpose()?;
+        params.to = matches
+            .get_one::<String>(UNTIL)
+            .map(|until| time_filter(until, true))
+            .transpose()?;
+        if let Some(labels) = matches.get_many::<String>(LABEL) {
+            let labels: Vec<&str> = labels.map(|l| l.as_str()).collect();
+            for label in labels.iter() {
+                parse_label(label)?;
+            }
+            params.label = Some(labels.join(","));
+        }
+        params.page = matches.get_one::<i64>(PAGE).cloned();
+        params.per_page = matches.get_one::<i64>(PER_PAGE).cloned();
+
+        debug!(
+            "running {} subcommand with --server: {}, --pipeline: {:?}, --state: {:?}, --limit: {:?}, --since: {:?}, --until: {:?}, --label: {:?}, --page: {:?}, --per-page: {:?}",
+            HIST, server.name, params.pipeline, params.state, params.limit, params.from, params.to, params.label, params.page, params.per_page
+        );
 
-        let server_auth = config.remote.same_auth_as(server)?;
         let protocol = server.http_protocol();
         let url = format!("{protocol}://{}:{}/hist", server.host, server.port);
-        let headers = request::headers(&server_auth.name, &server_auth.auth)?;
+        let headers = request::server_headers(&config.remote, server)?;
 
         debug!("sending http request to {}", url);
 
+        let json = matches.get_flag(JSON);
+        let paged =
+            !json && !matches.get_flag(NO_PAGER) && params.page.is_none() && stdout().is_terminal();
         System::new().block_on(async move {
-            let res = request::get(url, headers).await?;
+            if paged {
+                return tokio::select! {
+                    res = page(url, headers, params) => res,
+                    _ = ctrl_c() => print(""),
+                };
+            }
+            let res = request::get_with_query(url, headers, &params).await?;
             let history: Vec<HistoryEntry> = serde_json::from_str(&res)?;
-            let table = Table::new(history).with(Style::modern()).to_string();
-            println!("{table}");
-            Ok(())
+            if json {
+                print(&serde_json::to_string_pretty(&history)?)
+            } else {
+                print(&Table::new(history).with(Style::modern()).to_string())
+            }
+        })
+    }
+}
+
+/// Prints the commands of a run in the order of their steps, or the slowest of them starting
+/// from the slowest.
+fn print_summary(summary: &RunSummary, slowest: Option<usize>, json: bool) -> Result<()> {
+    if json {
+        return print(&serde_json::to_string_pretty(summary)?);
+    }
+    let commands = match slowest {
+        Some(count) => summary.slowest(count),
+        None => summary
+            .steps
+            .iter()
+            .flat_map(|s| s.commands.iter().map(move |c| (s, c)))
+            .collect(),
+    };
+    let rows: Vec<CommandRow> = commands
+        .into_iter()
+        .map(|(step, command)| CommandRow {
+            step: step.label(),
+            index: command.index,
+            command: command.preview.clone(),
+            duration: format_duration(std::time::Duration::from_millis(command.duration_millis)),
         })
+        .collect();
+    print(&Table::new(rows).with(Style::modern()).to_string())
+}
+
+/// Fetches the history a page at a time, loading the next page only when the user asks for it
+/// and stopping at the limit of the command if there is one.
+async fn page(
+    url: String,
+    headers: HashMap<String, String>,
+    mut params: HistQueryInfo,
+) -> Result<()> {
+    let page_size = params.per_page.take().unwrap_or(HIST_PAGE_SIZE);
+    let mut remaining = params.limit;
+    let mut offset = 0;
+    let mut input = input_lines();
+    loop {
+        let size = remaining.map_or(page_size, |r| r.min(page_size));
+        params.limit = Some(size);
+        params.offset = Some(offset);
+        let res = request::get_with_query(url.clone(), headers.clone(), &params).await?;
+        let history: Vec<HistoryEntry> = serde_json::from_str(&res)?;
+        let count = history.len() as i64;
+        if count > 0 || offset == 0 {
+            print(&Table::new(history).with(Style::modern()).to_string())?;
+        }
+        offset += count;
+        remaining = remaining.map(|r| r - count);
+        if count < size || remaining == Some(0) {
+            return Ok(());
+        }
+        write(&format!(
+            "-- {offset} runs shown, press enter for more or q to quit -- "
+        ))?;
+        match input.recv().await {
+            Some(line) if line.trim() != "q" => continue,
+            _ => return Ok(()),
+        }
+    }
+}
+
+/// The lines of stdin, read on a thread of their own so that a pending read doesn't keep the
+/// runtime from shutting down when the user quits the pager with Ctrl+C.
+fn input_lines() -> UnboundedReceiver<String> {
+    let (tx, rx) = unbounded_channel();
+    thread::spawn(move || {
+        for line in stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// Converts a value of the since or until options to the date time format of the runs. The value
+/// is either a duration ago such as 7d, 12h, 30m or 2w, a date or a date with a time.
+fn time_filter(value: &str, end_of_day: bool) -> Result<String> {
+    let value = value.trim();
+    let unit = value.chars().last().unwrap_or_default();
+    let amount = value[..value.len() - unit.len_utf8().min(value.len())].parse::<i64>();
+    let duration = match (amount, unit) {
+        (Ok(amount), 's') => Some(Duration::seconds(amount)),
+        (Ok(amount), 'm') => Some(Duration::minutes(amount)),
+        (Ok(amount), 'h') => Some(Duration::hours(amount)),
+        (Ok(amount), 'd') => Some(Duration::days(amount)),
+        (Ok(amount), 'w') => Some(Duration::weeks(amount)),
+        _ => None,
+    };
+    if let Some(duration) = duration {
+        return Ok((Utc::now().naive_utc() - duration)
+            .format(TIME_FORMAT)
+            .to_string());
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%F") {
+        let time = if end_of_day { "23:59:59" } else { "00:00:00" };
+        return Ok(format!("{date} {time}"));
+    }
+    if let Ok(date_time) = NaiveDateTime::parse_from_str(value, TIME_FORMAT) {
+        return Ok(date_time.format(TIME_FORMAT).to_string());
+    }
+    bail!("invalid time {value}, expected a duration such as 7d, 12h or 30m or a date such as 2024-01-31")
+}
+
+fn print(text: &str) -> Result<()> {
+    write(&format!("{text}\n"))
+}
+
+/// Writes to stdout, ignoring a closed pipe such as the one of `bld hist | head` instead of
+/// panicking like the print macros do.
+fn write(text: &str) -> Result<()> {
+    let mut stdout = stdout().lock();
+    match stdout
+        .write_all(text.as_bytes())
+        .and_then(|_| stdout.flush())
+    {
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+        res => res.map_err(io::Error::into),
     }
 }
 
@@ -75,4 +374,109 @@ mod tests {
             Some(&server_name.to_string())
         )
     }
+
+    #[test]
+    fn cli_hist_pipeline_arg_accepts_value() {
+        let pipeline_name = "mock_pipeline_name";
+        let command = HistCommand::boxed().interface();
+        let matches = command.get_matches_from(["hist", "-p", pipeline_name]);
+
+        assert_eq!(
+            matches.get_one::<String>(PIPELINE),
+            Some(&pipeline_name.to_string())
+        )
+    }
+
+    #[test]
+    fn cli_hist_state_arg_accepts_known_state() {
+        let command = HistCommand::boxed().interface();
+        let matches = command.get_matches_from(["hist", "--state", PR_STATE_FINISHED]);
+
+        assert_eq!(
+            matches.get_one::<String>(STATE),
+            Some(&PR_STATE_FINISHED.to_string())
+        )
+    }
+
+    #[test]
+    fn cli_hist_state_arg_rejects_unknown_state() {
+        let command = HistCommand::boxed().interface();
+        let matches = command.try_get_matches_from(["hist", "--state", "mock_state"]);
+
+        assert!(matches.is_err())
+    }
+
+    #[test]
+    fn cli_hist_limit_arg_accepts_number() {
+        let command = HistCommand::boxed().interface();
+        let matches = command.get_matches_from(["hist", "-l", "10"]);
+
+        assert_eq!(matches.get_one::<i64>(LIMIT), Some(&10))
+    }
+
+    #[test]
+    fn cli_hist_time_filters_accept_durations_and_dates() {
+        let since = time_filter("7d", false).unwrap();
+        let expected = (Utc::now().naive_utc() - Duration::days(7))
+            .format(TIME_FORMAT)
+            .to_string();
+        assert_eq!(&since[..10], &expected[..10]);
+        assert_eq!(
+            time_filter("2024-01-31", false).unwrap(),
+            "2024-01-31 00:00:00"
+        );
+        assert_eq!(
+            time_filter("2024-01-31", true).unwrap(),
+            "2024-01-31 23:59:59"
+        );
+        assert_eq!(
+            time_filter("2024-01-31 10:30:00", true).unwrap(),
+            "2024-01-31 10:30:00"
+        );
+        assert!(time_filter("7x", false).is_err());
+        assert!(time_filter("last week", false).is_err());
+        assert!(time_filter("", false).is_err());
+    }
+
+    #[test]
+    fn cli_hist_page_args_accept_positive_numbers() {
+        let command = HistCommand::boxed().interface();
+        let matches = command.get_matches_from(["hist", "--page", "2", "--per-page", "25"]);
+        assert_eq!(matches.get_one::<i64>(PAGE), Some(&2));
+        assert_eq!(matches.get_one::<i64>(PER_PAGE), Some(&25));
+
+        let command = HistCommand::boxed().interface();
+        let matches = command.get_matches_from(["hist", "--name", "build.yaml"]);
+        assert_eq!(
+            matches.get_one::<String>(PIPELINE),
+            Some(&"build.yaml".to_string())
+        );
+
+        let command = HistCommand::boxed().interface();
+        assert!(command
+            .try_get_matches_from(["hist", "--page", "0"])
+            .is_err());
+    }
+
+    #[test]
+    fn cli_hist_slowest_arg_defaults_to_ten_and_requires_an_id() {
+        let command = HistCommand::boxed().interface();
+        let matches = command.get_matches_from(["hist", "--id", "run", "--slowest"]);
+        assert_eq!(matches.get_one::<u64>(SLOWEST), Some(&10));
+
+        let command = HistCommand::boxed().interface();
+        let matches = command.get_matches_from(["hist", "--id", "run", "--slowest", "3"]);
+        assert_eq!(matches.get_one::<u64>(SLOWEST), Some(&3));
+
+        let command = HistCommand::boxed().interface();
+        assert!(command.try_get_matches_from(["hist", "--slowest"]).is_err());
+    }
+
+    #[test]
+    fn cli_hist_json_arg_is_a_flag() {
+        let command = HistCommand::boxed().interface();
+        let matches = command.get_matches_from(["hist", "--json"]);
+
+        assert!(matches.get_flag(JSON))
+    }
 }
@@ -3,6 +3,8 @@ use actix_web::rt::System;
 use anyhow::Result;
 use bld_config::definitions::VERSION;
 use bld_config::BldConfig;
+use bld_runner::Pipeline;
+use bld_utils::cache::{inspect_key, ResponseCache};
 use bld_utils::request;
 use clap::{Arg, ArgAction, ArgMatches, Command};
 use tracing::debug;
@@ -10,6 +12,8 @@ use tracing::debug;
 static INSPECT: &str = "inspect";
 static PIPELINE: &str = "pipeline";
 static SERVER: &str = "server";
+static RESOLVE: &str = "resolve";
+static NO_CACHE: &str = "no-cache";
 
 pub struct InspectCommand;
 
@@ -36,35 +40,58 @@ impl BldCommand for InspectCommand {
             .help("The name of the server from which to inspect the pipeline")
             .action(ArgAction::Set);
 
+        let resolve = Arg::new(RESOLVE)
+            .long("resolve")
+            .help("Prints the pipeline with the steps that use step templates expanded")
+            .action(ArgAction::SetTrue);
+
+        let no_cache = Arg::new(NO_CACHE)
+            .long("no-cache")
+            .help("Fetches the pipeline from the server without using the cached response")
+            .action(ArgAction::SetTrue);
+
         Command::new(INSPECT)
             .about("Inspects the contents of a pipeline on a bld server")
             .version(VERSION)
-            .args(&[pipeline, server])
+            .args(&[pipeline, server, resolve, no_cache])
     }
 
     fn exec(&self, matches: &ArgMatches) -> Result<()> {
         let config = BldConfig::load()?;
         let pip = matches.get_one::<String>(PIPELINE).cloned().unwrap();
+        let resolve = matches.get_flag(RESOLVE);
         let server = config
             .remote
             .server_or_first(matches.get_one::<String>(SERVER))?;
 
         debug!(
-            "running {} subcommand with --pipeline: {}, --server: {}",
-            INSPECT, pip, server.name
+            "running {} subcommand with --pipeline: {}, --server: {}, --resolve: {}",
+            INSPECT, pip, server.name, resolve
         );
 
-        let server_auth = config.remote.same_auth_as(server)?;
         let protocol = server.http_protocol();
         let url = format!("{protocol}://{}:{}/inspect", server.host, server.port);
-        let headers = request::headers(&server_auth.name, &server_auth.auth)?;
+        let headers = request::server_headers(&config.remote, server)?;
+        let cache = (!matches.get_flag(NO_CACHE)).then(|| ResponseCache::new(server));
 
         debug!("sending http request to {}", url);
 
+        // the content is printed as is so that it can be redirected to a file or diffed
+        // against the local pipeline.
         System::new().block_on(async move {
-            request::post(url, headers, pip).await.map(|r| {
-                println!("{r}");
-            })
+            let content = match cache {
+                Some(cache) => {
+                    let key = inspect_key(&pip);
+                    request::post_cached(url, headers, pip, &cache, &key).await?
+                }
+                None => request::post(url, headers, pip).await?,
+            };
+            if resolve {
+                print!("{}", Pipeline::resolve(&content)?);
+            } else {
+                print!("{content}");
+            }
+            Ok(())
         })
     }
 }
@@ -85,6 +112,15 @@ mod tests {
         );
     }
 
+    #[test]
+    fn cli_inspect_resolve_arg_defaults_to_false() {
+        let command = InspectCommand::boxed().interface();
+        let matches = command.get_matches_from(["inspect", "-p", "mockPipeline"]);
+
+        assert!(!matches.get_flag(RESOLVE));
+        assert!(!matches.get_flag(NO_CACHE));
+    }
+
     #[test]
     fn cli_inspect_server_arg_accepts_value() {
         let server_name = "mock_server_name";
@@ -1,19 +1,36 @@
+pub mod api_key;
 pub mod auth;
+mod banner;
+mod capabilities;
+pub mod check;
 mod cli;
 pub mod config;
+pub mod cron;
+pub mod deps;
+pub mod document;
+pub mod edit;
 pub mod hist;
 pub mod init;
 pub mod inspect;
 pub mod list;
+pub mod logs;
+pub mod migrate;
 pub mod monit;
 pub mod pull;
 pub mod push;
 pub mod remove;
 pub mod run;
+pub mod schema;
+pub mod search;
 pub mod server;
+pub mod stats;
 pub mod stop;
 pub mod supervisor;
+mod telemetry;
+pub mod tree;
+pub mod wait;
 pub mod worker;
+mod ws;
 
 pub use cli::*;
 
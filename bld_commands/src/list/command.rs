@@ -3,12 +3,16 @@ use actix_web::rt::System;
 use anyhow::Result;
 use bld_config::definitions::VERSION;
 use bld_config::BldConfig;
+use bld_server::responses::PipelineEntry;
+use bld_utils::cache::{ResponseCache, LIST_KEY};
 use bld_utils::request;
 use clap::{Arg, ArgAction, ArgMatches, Command};
 use tracing::debug;
 
 static LIST: &str = "ls";
 static SERVER: &str = "server";
+static JSON: &str = "json";
+static NO_CACHE: &str = "no-cache";
 
 pub struct ListCommand;
 
@@ -28,10 +32,20 @@ impl BldCommand for ListCommand {
             .help("The name of the server from which to fetch pipeline information")
             .action(ArgAction::Set);
 
+        let json = Arg::new(JSON)
+            .long("json")
+            .help("Prints the pipelines and their information as json")
+            .action(ArgAction::SetTrue);
+
+        let no_cache = Arg::new(NO_CACHE)
+            .long("no-cache")
+            .help("Fetches the pipelines from the server without using the cached response")
+            .action(ArgAction::SetTrue);
+
         Command::new(LIST)
             .about("Lists information of pipelines in a bld server")
             .version(VERSION)
-            .args(&vec![server])
+            .args(vec![server, json, no_cache])
     }
 
     fn exec(&self, matches: &ArgMatches) -> Result<()> {
@@ -42,15 +56,29 @@ impl BldCommand for ListCommand {
 
         debug!("running {} subcommand with --server: {}", LIST, server.name);
 
-        let server_auth = config.remote.same_auth_as(server)?;
         let protocol = server.http_protocol();
         let url = format!("{protocol}://{}:{}/list", server.host, server.port);
-        let headers = request::headers(&server_auth.name, &server_auth.auth)?;
+        let headers = request::server_headers(&config.remote, server)?;
 
         debug!("sending {protocol} request to {}", url);
 
-        System::new()
-            .block_on(async move { request::get(url, headers).await.map(|r| println!("{r}")) })
+        let json = matches.get_flag(JSON);
+        let cache = (!matches.get_flag(NO_CACHE)).then(|| ResponseCache::new(server));
+        System::new().block_on(async move {
+            let res = match cache {
+                Some(cache) => request::get_cached(url, headers, &cache, LIST_KEY).await?,
+                None => request::get(url, headers).await?,
+            };
+            let pipelines: Vec<PipelineEntry> = serde_json::from_str(&res)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&pipelines)?);
+            } else {
+                for pipeline in pipelines.iter() {
+                    println!("{}", pipeline.name);
+                }
+            }
+            Ok(())
+        })
     }
 }
 
@@ -70,4 +98,22 @@ mod tests {
             Some(&server_name.to_string())
         )
     }
+
+    #[test]
+    fn cli_list_json_arg_is_a_flag() {
+        let command = ListCommand::boxed().interface();
+        let matches = command.get_matches_from(["ls", "--json"]);
+
+        assert!(matches.get_flag("json"))
+    }
+
+    #[test]
+    fn cli_list_no_cache_arg_is_a_flag() {
+        let command = ListCommand::boxed().interface();
+        let matches = command.clone().get_matches_from(["ls"]);
+        assert!(!matches.get_flag("no-cache"));
+
+        let matches = command.get_matches_from(["ls", "--no-cache"]);
+        assert!(matches.get_flag("no-cache"))
+    }
 }
@@ -0,0 +1,137 @@
+use crate::BldCommand;
+use actix_web::rt::System;
+use anyhow::{anyhow, Result};
+use bld_config::definitions::VERSION;
+use bld_config::BldConfig;
+use bld_server::endpoints::{LOGS_FINISHED_HEADER, LOGS_OFFSET_HEADER};
+use bld_server::requests::LogsQueryInfo;
+use bld_utils::request;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use std::collections::HashMap;
+use std::io::{stdout, Write};
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::debug;
+
+static LOGS: &str = "logs";
+static ID: &str = "id";
+static FOLLOW: &str = "follow";
+static SERVER: &str = "server";
+
+/// The interval between the requests for new content when following the logs of a run.
+const FOLLOW_INTERVAL_SECONDS: u64 = 1;
+
+pub struct LogsCommand;
+
+impl BldCommand for LogsCommand {
+    fn boxed() -> Box<Self> {
+        Box::new(Self)
+    }
+
+    fn id(&self) -> &'static str {
+        LOGS
+    }
+
+    fn interface(&self) -> Command {
+        let id = Arg::new(ID)
+            .short('i')
+            .long("id")
+            .help("The id of a run on a server")
+            .required(true)
+            .action(ArgAction::Set);
+
+        let follow = Arg::new(FOLLOW)
+            .short('f')
+            .long("follow")
+            .help("Keep printing the logs of a run that is still active until it finishes")
+            .action(ArgAction::SetTrue);
+
+        let server = Arg::new(SERVER)
+            .short('s')
+            .long("server")
+            .help("The name of the server that the pipeline ran on")
+            .action(ArgAction::Set);
+
+        Command::new(LOGS)
+            .about("Prints the logs of a run on a server")
+            .version(VERSION)
+            .args(&[id, follow, server])
+    }
+
+    fn exec(&self, matches: &ArgMatches) -> Result<()> {
+        let config = BldConfig::load()?;
+        let id = matches.get_one::<String>(ID).cloned().unwrap();
+        let follow = matches.get_flag(FOLLOW);
+
+        let server = config
+            .remote
+            .server_or_first(matches.get_one::<String>(SERVER))?;
+
+        let protocol = server.http_protocol();
+        let url = format!("{protocol}://{}:{}/logs/{id}", server.host, server.port);
+        let headers = request::server_headers(&config.remote, server)?;
+
+        System::new().block_on(print_logs(url, headers, follow))
+    }
+}
+
+/// Fetches the logs of the run and writes them to stdout as they are received. When following,
+/// the content after the last offset is requested until the server reports that the run has finished.
+async fn print_logs(url: String, headers: HashMap<String, String>, follow: bool) -> Result<()> {
+    let mut offset = 0;
+    loop {
+        let query = LogsQueryInfo {
+            offset: Some(offset),
+        };
+        debug!("sending http request to {url} with offset {offset}");
+        let response_headers =
+            request::get_streamed(url.clone(), headers.clone(), &query, |chunk| {
+                let mut stdout = stdout().lock();
+                stdout.write_all(chunk)?;
+                stdout.flush()?;
+                Ok(())
+            })
+            .await?;
+
+        let header = |name: &str| {
+            response_headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string())
+                .ok_or_else(|| {
+                    anyhow!("invalid response from the server, {name} header is missing")
+                })
+        };
+        offset = header(LOGS_OFFSET_HEADER)?.parse()?;
+        let finished = header(LOGS_FINISHED_HEADER)? == "true";
+        if finished || !follow {
+            return Ok(());
+        }
+        sleep(Duration::from_secs(FOLLOW_INTERVAL_SECONDS)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cli_logs_follow_arg_defaults_to_false() {
+        let command = LogsCommand::boxed().interface();
+        let matches = command.get_matches_from(["logs", "-i", "mock_run_id"]);
+
+        assert_eq!(
+            matches.get_one::<String>(ID),
+            Some(&"mock_run_id".to_string())
+        );
+        assert!(!matches.get_flag(FOLLOW))
+    }
+
+    #[test]
+    fn cli_logs_id_arg_is_required() {
+        let command = LogsCommand::boxed().interface();
+        let result = command.try_get_matches_from(["logs", "-f"]);
+
+        assert!(result.is_err())
+    }
+}
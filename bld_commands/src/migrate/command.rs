@@ -0,0 +1,79 @@
+use crate::BldCommand;
+use anyhow::Result;
+use bld_config::definitions::{TOOL_DEFAULT_PIPELINE, VERSION};
+use bld_core::proxies::PipelineFileSystemProxy;
+use bld_runner::{Pipeline, PIPELINE_VERSION};
+use bld_utils::term::print_info;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use tracing::debug;
+
+const MIGRATE: &str = "migrate";
+const PIPELINE: &str = "pipeline";
+
+pub struct MigrateCommand;
+
+impl BldCommand for MigrateCommand {
+    fn boxed() -> Box<Self> {
+        Box::new(Self)
+    }
+
+    fn id(&self) -> &'static str {
+        MIGRATE
+    }
+
+    fn interface(&self) -> Command {
+        let pipeline = Arg::new(PIPELINE)
+            .short('p')
+            .long(PIPELINE)
+            .help("The name of the pipeline to migrate")
+            .default_value(TOOL_DEFAULT_PIPELINE)
+            .action(ArgAction::Set);
+
+        Command::new(MIGRATE)
+            .about("Updates a local pipeline to the latest version of the pipeline format")
+            .version(VERSION)
+            .args(&[pipeline])
+    }
+
+    fn exec(&self, matches: &ArgMatches) -> Result<()> {
+        // using an unwrap here because pipeline option has a default value.
+        let pipeline = matches.get_one::<String>(PIPELINE).cloned().unwrap();
+
+        debug!(
+            "running {} subcommand with --pipeline: {}",
+            MIGRATE, pipeline
+        );
+
+        let proxy = PipelineFileSystemProxy::Local;
+        let content = proxy.read(&pipeline)?;
+        match Pipeline::migrate(&content)? {
+            Some(migrated) => {
+                proxy.create(&pipeline, &migrated)?;
+                print_info(&format!(
+                    "pipeline {pipeline} migrated to version {PIPELINE_VERSION}"
+                ))?;
+            }
+            None => print_info(&format!(
+                "pipeline {pipeline} is already at version {PIPELINE_VERSION}"
+            ))?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cli_migrate_pipeline_arg_accepts_value() {
+        let pipeline_name = "mock_pipeline_name";
+        let command = MigrateCommand::boxed().interface();
+        let matches = command.get_matches_from(["migrate", "-p", pipeline_name]);
+
+        assert_eq!(
+            matches.get_one::<String>(PIPELINE),
+            Some(&pipeline_name.to_string())
+        )
+    }
+}
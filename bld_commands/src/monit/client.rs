@@ -47,6 +47,9 @@ impl StreamHandler<Result<Frame, WsProtocolError>> for MonitClient {
     fn handle(&mut self, msg: Result<Frame, WsProtocolError>, ctx: &mut Context<Self>) {
         match msg {
             Ok(Frame::Text(bt)) => println!("{}", String::from_utf8_lossy(&bt)),
+            Ok(Frame::Ping(msg)) => {
+                let _ = self.writer.write(Message::Pong(msg));
+            }
             Ok(Frame::Close(_)) => ctx.stop(),
             _ => {}
         }
@@ -1,13 +1,12 @@
 use crate::monit::MonitClient;
+use crate::ws::ws_connect;
 use crate::BldCommand;
 use actix::{io::SinkWrite, Actor, StreamHandler};
 use actix_web::rt::System;
-use anyhow::{anyhow, Result};
-use awc::http::Version;
-use awc::Client;
+use anyhow::Result;
 use bld_config::{definitions::VERSION, BldConfig};
 use bld_server::requests::MonitInfo;
-use bld_utils::request::headers;
+use bld_utils::request::server_headers;
 use clap::{Arg, ArgAction, ArgMatches, Command};
 use futures::stream::StreamExt;
 use std::collections::HashMap;
@@ -18,15 +17,17 @@ static PIPELINE_ID: &str = "pipeline-id";
 static PIPELINE: &str = "pipeline";
 static SERVER: &str = "server";
 static LAST: &str = "last";
+static ALL: &str = "all";
 
 struct MonitConnectionInfo {
     host: String,
     port: i64,
     protocol: String,
     headers: HashMap<String, String>,
-    pip_id: Option<String>,
+    pip_ids: Vec<String>,
     pip_name: Option<String>,
     pip_last: bool,
+    pip_all: bool,
 }
 
 pub struct MonitCommand;
@@ -44,8 +45,8 @@ impl BldCommand for MonitCommand {
         let pipeline_id = Arg::new(PIPELINE_ID)
             .short('i')
             .long("pipeline-id")
-            .help("The id of the pipeline to monitor. Takes precedence over pipeline")
-            .action(ArgAction::Set);
+            .help("The id of the pipeline run to monitor. Can be given multiple times to monitor many runs. Takes precedence over pipeline")
+            .action(ArgAction::Append);
 
         let pipeline = Arg::new(PIPELINE)
             .short('p')
@@ -64,40 +65,50 @@ impl BldCommand for MonitCommand {
             .help("Monitor the execution of the last invoked pipeline. Takes precedence over pipeline-id and pipeline")
             .action(ArgAction::SetTrue);
 
+        let all = Arg::new(ALL)
+            .long("all")
+            .help("Monitor all running and queued runs, including the ones that start while monitoring")
+            .conflicts_with_all([PIPELINE_ID, PIPELINE, LAST])
+            .action(ArgAction::SetTrue);
+
         Command::new(MONIT)
             .about("Connects to a bld server to monitor the execution of a pipeline")
             .version(VERSION)
-            .args(&vec![pipeline_id, pipeline, server, last])
+            .args(vec![pipeline_id, pipeline, server, last, all])
     }
 
     fn exec(&self, matches: &ArgMatches) -> Result<()> {
         let config = BldConfig::load()?;
-        let pip_id = matches.get_one::<String>(PIPELINE_ID).cloned();
+        let pip_ids: Vec<String> = matches
+            .get_many::<String>(PIPELINE_ID)
+            .map(|ids| ids.cloned().collect())
+            .unwrap_or_default();
         let pip_name = matches.get_one::<String>(PIPELINE).cloned();
         let pip_last = matches.get_flag(LAST);
+        let pip_all = matches.get_flag(ALL);
         let server = config
             .remote
             .server_or_first(matches.get_one::<String>(SERVER))?;
 
         debug!(
-            "running {} subcommand with --pipeline-id: {:?}, --pipeline: {:?}, --server: {}, --last: {}",
+            "running {} subcommand with --pipeline-id: {:?}, --pipeline: {:?}, --server: {}, --last: {}, --all: {}",
             MONIT,
-            pip_id,
+            pip_ids,
             pip_name,
             server.name,
-            pip_last
+            pip_last,
+            pip_all
         );
 
-        let server_auth = config.remote.same_auth_as(server)?;
-
         spawn(MonitConnectionInfo {
             host: server.host.to_string(),
             port: server.port,
             protocol: server.ws_protocol(),
-            headers: headers(&server_auth.name, &server_auth.auth)?,
-            pip_id,
+            headers: server_headers(&config.remote, server)?,
+            pip_ids,
             pip_name,
             pip_last,
+            pip_all,
         })
     }
 }
@@ -107,14 +118,7 @@ async fn request(info: MonitConnectionInfo) -> Result<()> {
 
     debug!("establishing web socket connection on {}", url);
 
-    let client = Client::builder()
-        .max_http_version(Version::HTTP_11)
-        .finish();
-    let mut client = client.ws(url);
-    for (key, value) in info.headers.iter() {
-        client = client.header(&key[..], &value[..]);
-    }
-    let (_, framed) = client.connect().await.map_err(|e| anyhow!(e.to_string()))?;
+    let framed = ws_connect(&url, &info.headers).await?;
 
     let (sink, stream) = framed.split();
     let addr = MonitClient::create(|ctx| {
@@ -123,12 +127,15 @@ async fn request(info: MonitConnectionInfo) -> Result<()> {
     });
 
     debug!(
-        "sending data over: {:?} {:?} {}",
-        info.pip_id, info.pip_name, info.pip_last
+        "sending data over: {:?} {:?} {} {}",
+        info.pip_ids, info.pip_name, info.pip_last, info.pip_all
     );
 
-    addr.send(MonitInfo::new(info.pip_id, info.pip_name, info.pip_last))
-        .await?;
+    let mut pip_ids = info.pip_ids.into_iter();
+    let pip_id = pip_ids.next();
+    let monit_info =
+        MonitInfo::new(pip_id, info.pip_name, info.pip_last).runs(pip_ids.collect(), info.pip_all);
+    addr.send(monit_info).await?;
     Ok(())
 }
 
@@ -187,4 +194,21 @@ mod tests {
 
         assert!(matches.get_flag(LAST))
     }
+
+    #[test]
+    fn cli_monit_id_arg_accepts_multiple_values_and_all_is_a_flag() {
+        let command = MonitCommand::boxed().interface();
+        let matches = command.get_matches_from(["monit", "-i", "first", "-i", "second"]);
+        let ids: Vec<&String> = matches.get_many::<String>(PIPELINE_ID).unwrap().collect();
+        assert_eq!(ids, vec!["first", "second"]);
+
+        let command = MonitCommand::boxed().interface();
+        let matches = command.get_matches_from(["monit", "--all"]);
+        assert!(matches.get_flag(ALL));
+
+        let command = MonitCommand::boxed().interface();
+        assert!(command
+            .try_get_matches_from(["monit", "--all", "-i", "first"])
+            .is_err());
+    }
 }
@@ -1,8 +1,8 @@
 use crate::BldCommand;
 use actix_web::rt::System;
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use bld_config::{definitions::VERSION, BldConfig};
-use bld_core::proxies::PipelineFileSystemProxy;
+use bld_core::proxies::{save_base_hash, PipelineFileSystemProxy};
 use bld_server::responses::PullResponse;
 use bld_utils::fs::IsYaml;
 use bld_utils::request;
@@ -16,6 +16,7 @@ const PULL: &str = "pull";
 const SERVER: &str = "server";
 const PIPELINE: &str = "pipeline";
 const IGNORE_DEPS: &str = "ignore-deps";
+const FORCE: &str = "force";
 
 pub struct PullCommand;
 
@@ -47,10 +48,16 @@ impl BldCommand for PullCommand {
             .help("Do not include other pipeline dependencies")
             .action(ArgAction::SetTrue);
 
+        let force = Arg::new(FORCE)
+            .short('f')
+            .long(FORCE)
+            .help("Overwrite the pipelines that already exist locally")
+            .action(ArgAction::SetTrue);
+
         Command::new(PULL)
             .about("Pull a pipeline from a bld server and stores it localy")
             .version(VERSION)
-            .args(&[server, pipeline, ignore_deps])
+            .args(&[server, pipeline, ignore_deps, force])
     }
 
     fn exec(&self, matches: &ArgMatches) -> Result<()> {
@@ -61,36 +68,41 @@ impl BldCommand for PullCommand {
         // using an unwrap here because the pipeline option is required.
         let pip = matches.get_one::<String>(PIPELINE).cloned().unwrap();
         let ignore = matches.get_flag(IGNORE_DEPS);
+        let force = matches.get_flag(FORCE);
 
         debug!(
-            "running {PULL} subcommand with --server: {}, --pipeline: {pip} and --ignore-deps: {ignore}",
+            "running {PULL} subcommand with --server: {}, --pipeline: {pip}, --ignore-deps: {ignore} and --force: {force}",
             server.name
         );
 
-        let server_auth = config.remote.same_auth_as(server)?;
-        let headers = request::headers(&server_auth.name, &server_auth.auth)?;
+        let headers = request::server_headers(&config.remote, server)?;
 
         System::new().block_on(async move {
             do_pull(
+                server.name.clone(),
                 server.host.clone(),
                 server.port,
                 server.http_protocol(),
                 headers,
                 pip,
                 ignore,
+                force,
             )
             .await
         })
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn do_pull(
+    server: String,
     host: String,
     port: i64,
     protocol: String,
     headers: HashMap<String, String>,
     name: String,
     ignore_deps: bool,
+    force: bool,
 ) -> Result<()> {
     let mut pipelines = vec![name.to_string()];
     if !ignore_deps {
@@ -110,26 +122,55 @@ async fn do_pull(
             })?;
         pipelines.append(&mut deps);
     }
-    for pipeline in pipelines.iter() {
+    let mut written = vec![];
+    let mut skipped = vec![];
+    let mut failed = vec![];
+    for pipeline in pipelines.into_iter() {
+        let path = PipelineFileSystemProxy::Local.path(&pipeline)?;
+        if path.is_yaml() && !force {
+            println!("Skipping pipeline {pipeline} since it already exists locally");
+            skipped.push(pipeline);
+            continue;
+        }
         let url = format!("{protocol}://{host}:{port}/pull");
         debug!("sending http request to {url}");
         print!("Pulling pipeline {pipeline}...");
-        let _ = request::post(url, headers.clone(), pipeline.to_string())
+        let result = request::post(url, headers.clone(), pipeline.to_string())
             .await
             .and_then(|r| serde_json::from_str(&r).map_err(|e| anyhow!(e)))
-            .and_then(save_pipeline)
-            .map(|_| {
+            .and_then(|data| save_pipeline(&server, data));
+        match result {
+            Ok(_) => {
                 println!("Done.");
-            })
-            .map_err(|e| {
+                written.push(pipeline);
+            }
+            Err(e) => {
                 println!("Error. {e}");
-                e
-            });
+                failed.push(pipeline);
+            }
+        }
+    }
+    print_summary(&written, &skipped, &failed);
+    if !failed.is_empty() {
+        bail!("unable to pull {} pipeline(s)", failed.len());
     }
     Ok(())
 }
 
-fn save_pipeline(data: PullResponse) -> Result<()> {
+fn print_summary(written: &[String], skipped: &[String], failed: &[String]) {
+    println!("Summary:");
+    for (title, pipelines) in [
+        ("written", written),
+        ("skipped, use --force to overwrite", skipped),
+        ("failed", failed),
+    ] {
+        if !pipelines.is_empty() {
+            println!("  {} {title}: {}", pipelines.len(), pipelines.join(", "));
+        }
+    }
+}
+
+fn save_pipeline(server: &str, data: PullResponse) -> Result<()> {
     let path = PipelineFileSystemProxy::Local.path(&data.name)?;
     if path.is_yaml() {
         remove_file(&path)?;
@@ -138,7 +179,7 @@ fn save_pipeline(data: PullResponse) -> Result<()> {
     }
     let mut handle = File::create(&path)?;
     handle.write_all(data.content.as_bytes())?;
-    Ok(())
+    save_base_hash(server, &data.name, &data.content)
 }
 
 #[cfg(test)]
@@ -176,4 +217,12 @@ mod tests {
 
         assert_eq!(matches.get_flag(IGNORE_DEPS), true);
     }
+
+    #[test]
+    fn cli_pull_force_is_a_flag() {
+        let command = PullCommand::boxed().interface();
+        let matches = command.get_matches_from(["pull", "-p", "mockPipeline", "--force"]);
+
+        assert!(matches.get_flag(FORCE));
+    }
 }
@@ -1,20 +1,30 @@
 use crate::BldCommand;
 use actix_web::rt::System;
-use anyhow::{anyhow, Result};
-use bld_config::definitions::VERSION;
-use bld_config::BldConfig;
-use bld_core::proxies::PipelineFileSystemProxy;
+use anyhow::{anyhow, bail, Result};
+use bld_config::definitions::{
+    LOCAL_MACHINE_TMP_DIR, REMOTE_PIPELINE_HASHES, REMOTE_RESPONSE_CACHE, TOOL_DIR, VERSION,
+};
+use bld_config::{path, BldConfig};
+use bld_core::proxies::{base_hash, save_base_hash, PipelineFileSystemProxy};
 use bld_runner::Pipeline;
 use bld_server::requests::PushInfo;
+use bld_server::responses::AtomicPushResponse;
+use bld_utils::cache::ResponseCache;
+use bld_utils::fs::IsYaml;
 use bld_utils::request;
+use bld_utils::term::print_warning;
 use clap::{Arg, ArgAction, ArgMatches, Command};
 use std::collections::HashMap;
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
 use tracing::debug;
 
 static PUSH: &str = "push";
 static PIPELINE: &str = "pipeline";
 static SERVER: &str = "server";
 static IGNORE_DEPS: &str = "ignore-deps";
+static ALL: &str = "all";
+static ATOMIC: &str = "atomic";
 
 pub struct PushCommand;
 
@@ -33,7 +43,8 @@ impl BldCommand for PushCommand {
             .long("pipeline")
             .help("The name of the pipeline to push")
             .action(ArgAction::Set)
-            .required(true);
+            .required_unless_present(ALL)
+            .conflicts_with(ALL);
 
         let server = Arg::new(SERVER)
             .short('s')
@@ -46,58 +57,71 @@ impl BldCommand for PushCommand {
             .help("Don't include other pipeline dependencies")
             .action(ArgAction::SetTrue);
 
+        let all = Arg::new(ALL)
+            .long(ALL)
+            .help("Push all the local pipelines")
+            .action(ArgAction::SetTrue);
+
+        let atomic = Arg::new(ATOMIC)
+            .long(ATOMIC)
+            .help("Push the pipelines all at once, so that either all of them are pushed or none of them")
+            .action(ArgAction::SetTrue);
+
         Command::new(PUSH)
             .about("Pushes the contents of a pipeline to a bld server")
             .version(VERSION)
-            .args(&[pipeline, server, ignore])
+            .args(&[pipeline, server, ignore, all, atomic])
     }
 
     fn exec(&self, matches: &ArgMatches) -> Result<()> {
         let config = BldConfig::load()?;
-        // using unwrap here because the pipeline option is required.
-        let pip = matches.get_one::<String>(PIPELINE).cloned().unwrap();
+        let pip = matches.get_one::<String>(PIPELINE).cloned();
         let server = config
             .remote
             .server_or_first(matches.get_one::<String>(SERVER))?;
         let ignore = matches.get_flag(IGNORE_DEPS);
+        let atomic = matches.get_flag(ATOMIC);
 
         debug!(
-            "running {PUSH} subcommand with --server: {} and --pipeline: {pip}",
+            "running {PUSH} subcommand with --server: {}, --pipeline: {pip:?} and --atomic: {atomic}",
             server.name
         );
 
-        let server_auth = config.remote.same_auth_as(server)?;
-        let headers = request::headers(&server_auth.name, &server_auth.auth)?;
+        let pipelines = match pip {
+            Some(pip) => pipelines(&pip, ignore)?,
+            None => local_pipelines(&config)?
+                .into_iter()
+                .map(|name| {
+                    let content = PipelineFileSystemProxy::Local.read(&name)?;
+                    Ok(PushInfo::new(&name, &content))
+                })
+                .collect::<Result<Vec<PushInfo>>>()?,
+        };
+        let headers = request::server_headers(&config.remote, server)?;
+        let protocol = server.http_protocol();
+        let cache = ResponseCache::new(server);
 
         System::new().block_on(async move {
-            do_push(
-                server.host.clone(),
-                server.port,
-                server.http_protocol(),
-                headers,
-                pip,
-                ignore,
-            )
-            .await
+            if atomic {
+                let url = format!("{protocol}://{}:{}/push/atomic", server.host, server.port);
+                do_push_atomic(&server.name, &cache, url, headers, pipelines).await
+            } else {
+                let url = format!("{protocol}://{}:{}/push", server.host, server.port);
+                do_push(&server.name, &cache, url, headers, pipelines).await
+            }
         })
     }
 }
 
-async fn do_push(
-    host: String,
-    port: i64,
-    protocol: String,
-    headers: HashMap<String, String>,
-    name: String,
-    ignore_deps: bool,
-) -> Result<()> {
+/// The pipeline to push along with the pipelines that it calls, unless they are ignored.
+fn pipelines(name: &str, ignore_deps: bool) -> Result<Vec<PushInfo>> {
     let mut pipelines = vec![PushInfo::new(
-        &name,
-        &PipelineFileSystemProxy::Local.read(&name)?,
+        name,
+        &PipelineFileSystemProxy::Local.read(name)?,
     )];
     if !ignore_deps {
         print!("Resolving dependecies...");
-        let mut deps = deps(&name)
+        let mut deps = deps(name)
             .map(|pips| {
                 println!("Done.");
                 pips.iter().map(|(n, s)| PushInfo::new(n, s)).collect()
@@ -108,14 +132,79 @@ async fn do_push(
             })?;
         pipelines.append(&mut deps);
     }
-    for info in pipelines.into_iter() {
+    Ok(pipelines)
+}
+
+/// The names of the local pipelines, skipping the directories of the config such as the one of
+/// the server pipelines or the logs.
+fn local_pipelines(config: &BldConfig) -> Result<Vec<String>> {
+    let root = path![TOOL_DIR];
+    let skipped: Vec<PathBuf> = [
+        config.local.server.pipelines.as_str(),
+        config.local.logs.as_str(),
+        config.local.db.as_str(),
+        REMOTE_PIPELINE_HASHES,
+        REMOTE_RESPONSE_CACHE,
+        LOCAL_MACHINE_TMP_DIR,
+    ]
+    .iter()
+    .map(|dir| path![dir])
+    .collect();
+    let mut names = vec![];
+    let mut pending = vec![root.clone()];
+    while let Some(dir) = pending.pop() {
+        for entry in read_dir(&dir)? {
+            let path = entry?.path();
+            let hidden = path
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with('.'));
+            if path.is_dir() && !hidden && !skipped.iter().any(|s| is_same_dir(s, &path)) {
+                pending.push(path);
+            } else if path.is_yaml() {
+                let name = path.strip_prefix(&root)?.to_string_lossy().to_string();
+                names.push(name);
+            }
+        }
+    }
+    if names.is_empty() {
+        bail!("no pipelines found in {TOOL_DIR}");
+    }
+    names.sort();
+    Ok(names)
+}
+
+fn is_same_dir(left: &Path, right: &Path) -> bool {
+    match (left.canonicalize(), right.canonicalize()) {
+        (Ok(left), Ok(right)) => left == right,
+        _ => false,
+    }
+}
+
+async fn do_push(
+    server: &str,
+    cache: &ResponseCache,
+    url: String,
+    headers: HashMap<String, String>,
+    pipelines: Vec<PushInfo>,
+) -> Result<()> {
+    for mut info in pipelines.into_iter() {
         print!("Pushing {}...", info.name);
-        let url = format!("{protocol}://{}:{}/push", host, port);
         debug!("sending request to {url}");
-        let _ = request::post(url.clone(), headers.clone(), info)
-            .await
-            .map(|_| {
+        // the hash of the pipeline from a prior pull lets the server reject the push if
+        // the pipeline was changed by someone else in the meantime.
+        info.base_hash = base_hash(server, &info.name);
+        let name = info.name.to_string();
+        let content = info.content.to_string();
+        let result = request::post(url.clone(), headers.clone(), info).await;
+        cache.invalidate_pipeline(&name);
+        let _ = result
+            .and_then(|body| save_base_hash(server, &name, &content).map(|_| body))
+            .map(|body| {
                 println!("Done.");
+                // the server responds with warnings about the pipeline, if any.
+                for warning in body.lines().filter(|l| !l.is_empty()) {
+                    let _ = print_warning(&format!("  {warning}"));
+                }
             })
             .map_err(|e| {
                 println!("Error. {e}");
@@ -125,6 +214,57 @@ async fn do_push(
     Ok(())
 }
 
+/// Pushes the pipelines in a single request, printing the result of the validation of each one.
+async fn do_push_atomic(
+    server: &str,
+    cache: &ResponseCache,
+    url: String,
+    headers: HashMap<String, String>,
+    mut pipelines: Vec<PushInfo>,
+) -> Result<()> {
+    for info in pipelines.iter_mut() {
+        info.base_hash = base_hash(server, &info.name);
+    }
+    let contents: Vec<(String, String)> = pipelines
+        .iter()
+        .map(|info| (info.name.to_string(), info.content.to_string()))
+        .collect();
+    println!("Pushing {} pipelines atomically...", pipelines.len());
+    debug!("sending request to {url}");
+    // a rejected push still has the results of the validation in the body of the response.
+    let body = match request::post(url, headers, pipelines).await {
+        Ok(body) => body,
+        Err(e) => e.to_string(),
+    };
+    let response: AtomicPushResponse =
+        serde_json::from_str(&body).map_err(|_| anyhow!(body.to_string()))?;
+    for result in response.pipelines.iter() {
+        if result.errors.is_empty() {
+            println!("{}...Valid.", result.name);
+        } else {
+            println!("{}...Error.", result.name);
+            for error in result.errors.iter() {
+                println!("  {error}");
+            }
+        }
+        for warning in result.warnings.iter() {
+            print_warning(&format!("  {warning}"))?;
+        }
+    }
+    if !response.pushed {
+        match response.error {
+            Some(error) => bail!("none of the pipelines were pushed. {error}"),
+            None => bail!("none of the pipelines were pushed"),
+        }
+    }
+    for (name, content) in contents {
+        cache.invalidate_pipeline(&name);
+        save_base_hash(server, &name, &content)?;
+    }
+    println!("Done.");
+    Ok(())
+}
+
 fn deps(name: &str) -> Result<HashMap<String, String>> {
     deps_recursive(name).map(|mut hs| {
         hs.remove(name);
@@ -142,7 +282,7 @@ fn deps_recursive(name: &str) -> Result<HashMap<String, String>> {
     set.insert(name.to_string(), src);
     for step in pipeline.steps.iter() {
         for call in &step.call {
-            let subset = deps_recursive(call)?;
+            let subset = deps_recursive(&call.pipeline)?;
             for (k, v) in subset {
                 set.insert(k, v);
             }
@@ -186,4 +326,19 @@ mod tests {
 
         assert_eq!(matches.get_flag(IGNORE_DEPS), true);
     }
+
+    #[test]
+    fn cli_push_all_replaces_the_pipeline_arg() {
+        let command = PushCommand::boxed().interface();
+        let matches = command.get_matches_from(["push", "--all", "--atomic"]);
+        assert!(matches.get_flag(ALL));
+        assert!(matches.get_flag(ATOMIC));
+
+        let command = PushCommand::boxed().interface();
+        assert!(command.try_get_matches_from(["push", "--atomic"]).is_err());
+
+        let command = PushCommand::boxed().interface();
+        let matches = command.try_get_matches_from(["push", "-p", "mockPipeline", "--all"]);
+        assert!(matches.is_err());
+    }
 }
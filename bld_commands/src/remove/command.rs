@@ -1,14 +1,22 @@
+use crate::capabilities::require_feature;
 use crate::BldCommand;
 use actix_web::rt::System;
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use bld_config::{definitions::VERSION, BldConfig};
+use bld_server::requests::RemoveInfo;
+use bld_server::responses::{PipelineEntry, RemoveResult, FEATURE_BULK_REMOVE};
+use bld_utils::cache::ResponseCache;
 use bld_utils::request;
 use clap::{Arg, ArgAction, ArgMatches, Command};
+use glob::Pattern;
+use std::collections::HashMap;
 use tracing::debug;
 
 const REMOVE: &str = "rm";
 const SERVER: &str = "server";
 const PIPELINE: &str = "pipeline";
+const PATTERN: &str = "pattern";
+const DRY_RUN: &str = "dry-run";
 
 pub struct RemoveCommand;
 
@@ -31,14 +39,24 @@ impl BldCommand for RemoveCommand {
         let pipeline = Arg::new(PIPELINE)
             .short('p')
             .long(PIPELINE)
-            .help("The name of the pipeline")
-            .action(ArgAction::Set)
-            .required(true);
+            .help("The name of the pipeline. Can be given multiple times to remove many pipelines")
+            .action(ArgAction::Append)
+            .required_unless_present(PATTERN);
+
+        let pattern = Arg::new(PATTERN)
+            .long(PATTERN)
+            .help("A glob pattern, such as 'feature/*', of the names of the pipelines to remove")
+            .action(ArgAction::Set);
+
+        let dry_run = Arg::new(DRY_RUN)
+            .long(DRY_RUN)
+            .help("Prints the pipelines that would be removed without removing them")
+            .action(ArgAction::SetTrue);
 
         Command::new(REMOVE)
-            .about("Removes a pipeline from a bld server")
+            .about("Removes pipelines from a bld server")
             .version(VERSION)
-            .args(&vec![server, pipeline])
+            .args([server, pipeline, pattern, dry_run])
     }
 
     fn exec(&self, matches: &ArgMatches) -> Result<()> {
@@ -51,21 +69,126 @@ async fn do_remove(matches: &ArgMatches) -> Result<()> {
     let server = config
         .remote
         .server_or_first(matches.get_one::<String>(SERVER))?;
-    // using an unwrap here because the pipeline option is requried.
-    let pipeline = matches.get_one::<String>(PIPELINE).cloned().unwrap();
+    let pipelines: Vec<String> = matches
+        .get_many::<String>(PIPELINE)
+        .map(|p| p.cloned().collect())
+        .unwrap_or_default();
+    let pattern = matches
+        .get_one::<String>(PATTERN)
+        .map(|p| Pattern::new(p).map_err(|e| anyhow!("invalid pattern {p}, {e}")))
+        .transpose()?;
+    let dry_run = matches.get_flag(DRY_RUN);
 
     debug!(
-        "running {} subcommand with --server: {} and --pipeline: {pipeline}",
+        "running {} subcommand with --server: {}, --pipeline: {pipelines:?}, --pattern: {pattern:?} and --dry-run: {dry_run}",
         REMOVE, server.name
     );
 
-    let server_auth = config.remote.same_auth_as(server)?;
     let protocol = server.http_protocol();
-    let url = format!("{protocol}://{}:{}/remove", server.host, server.port);
-    let headers = request::headers(&server_auth.name, &server_auth.auth)?;
+    let url = format!("{protocol}://{}:{}", server.host, server.port);
+    let headers = request::server_headers(&config.remote, server)?;
+    let cache = ResponseCache::new(server);
+
+    if pattern.is_none() && !dry_run && pipelines.len() == 1 {
+        // a single pipeline is sent as a string so that servers without bulk removes accept it.
+        let pipeline = pipelines[0].to_string();
+        let url = format!("{url}/remove");
+        debug!("sending {protocol} request to {url}");
+        let result = request::post(url, headers, pipeline.clone()).await;
+        cache.invalidate_pipeline(&pipeline);
+        return result.map(|r| {
+            println!("{r}");
+        });
+    }
+
+    let listed = if pattern.is_some() || dry_run {
+        listed(&url, &headers).await?
+    } else {
+        vec![]
+    };
+    let mut names = pipelines;
+    if let Some(pattern) = &pattern {
+        for name in listed.iter() {
+            if pattern.matches(name) && !names.contains(name) {
+                names.push(name.to_string());
+            }
+        }
+    }
+
+    if dry_run {
+        let names: Vec<&String> = names.iter().filter(|n| listed.contains(n)).collect();
+        if names.is_empty() {
+            println!("No pipelines would be removed.");
+        }
+        for name in names {
+            println!("Would remove {name}");
+        }
+        return Ok(());
+    }
 
+    if names.is_empty() {
+        bail!("no pipelines match the pattern");
+    }
+    require_feature(&url, &headers, FEATURE_BULK_REMOVE).await?;
+    let url = format!("{url}/remove");
     debug!("sending {protocol} request to {url}");
-    request::post(url, headers, pipeline).await.map(|r| {
-        println!("{r}");
-    })
+    let response = request::post(url, headers, RemoveInfo::Many(names.clone())).await;
+    for name in names.iter() {
+        cache.invalidate_pipeline(name);
+    }
+    let results: Vec<RemoveResult> = serde_json::from_str(&response?)?;
+    let mut failed = 0;
+    for result in results.iter() {
+        match &result.error {
+            Some(error) => {
+                failed += 1;
+                println!("{}...Error. {error}", result.name);
+            }
+            None => println!("{}...Removed.", result.name),
+        }
+    }
+    if failed > 0 {
+        bail!(
+            "{failed} of {} pipelines could not be removed",
+            results.len()
+        );
+    }
+    Ok(())
+}
+
+/// The names of the pipelines of the server.
+async fn listed(url: &str, headers: &HashMap<String, String>) -> Result<Vec<String>> {
+    let url = format!("{url}/list");
+    debug!("sending request to {url}");
+    let response = request::get(url, headers.clone()).await?;
+    let pipelines: Vec<PipelineEntry> = serde_json::from_str(&response)?;
+    Ok(pipelines.into_iter().map(|p| p.name).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cli_remove_pipeline_arg_accepts_many_values() {
+        let command = RemoveCommand::boxed().interface();
+        let matches = command.get_matches_from(["rm", "-p", "build.yaml", "-p", "deploy.yaml"]);
+        let pipelines: Vec<&String> = matches.get_many::<String>(PIPELINE).unwrap().collect();
+
+        assert_eq!(pipelines, vec!["build.yaml", "deploy.yaml"]);
+        assert!(!matches.get_flag(DRY_RUN));
+    }
+
+    #[test]
+    fn cli_remove_requires_a_pipeline_or_a_pattern() {
+        let command = RemoveCommand::boxed().interface();
+        assert!(command.clone().try_get_matches_from(["rm"]).is_err());
+
+        let matches = command.get_matches_from(["rm", "--pattern", "feature/*", "--dry-run"]);
+        assert_eq!(
+            matches.get_one::<String>(PATTERN),
+            Some(&"feature/*".to_string())
+        );
+        assert!(matches.get_flag(DRY_RUN));
+    }
 }
@@ -0,0 +1,196 @@
+use anyhow::{anyhow, bail, Result};
+use bld_server::requests::ArtifactQueryInfo;
+use bld_server::responses::ArtifactsResponse;
+use bld_utils::fs::artifact_files;
+use bld_utils::request;
+use std::collections::HashMap;
+use std::fs::{copy, create_dir_all, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// The outcome of collecting a file of the artifacts of a run into the output directory.
+pub enum CollectedArtifact {
+    Copied { name: String, size: u64 },
+    Failed { name: String, reason: String },
+}
+
+fn output_path(output: &Path, name: &str) -> Result<PathBuf> {
+    let path = output.join(name);
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+    Ok(path)
+}
+
+/// Copies the files of the paths that the `get` artifacts of a local run were copied to.
+pub fn collect_local(paths: &[String], output: &Path) -> Vec<CollectedArtifact> {
+    let mut collected = vec![];
+    for artifact in paths {
+        let artifact = Path::new(artifact);
+        let files = match artifact_files(artifact) {
+            Ok(files) if !files.is_empty() => files,
+            Ok(_) => {
+                collected.push(CollectedArtifact::Failed {
+                    name: artifact.display().to_string(),
+                    reason: "not found".to_string(),
+                });
+                continue;
+            }
+            Err(e) => {
+                collected.push(CollectedArtifact::Failed {
+                    name: artifact.display().to_string(),
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+        for (name, path) in files {
+            let result = output_path(output, &name).and_then(|to| Ok(copy(&path, to)?));
+            collected.push(match result {
+                Ok(size) => CollectedArtifact::Copied { name, size },
+                Err(e) => CollectedArtifact::Failed {
+                    name,
+                    reason: e.to_string(),
+                },
+            });
+        }
+    }
+    collected
+}
+
+/// Downloads the files of the `get` artifacts that a finished run has copied to the server.
+pub async fn collect_remote(
+    url: &str,
+    headers: &HashMap<String, String>,
+    run_id: &str,
+    output: &Path,
+) -> Result<Vec<CollectedArtifact>> {
+    let list_url = format!("{url}/runs/{run_id}/artifacts");
+    let response = request::get(list_url, headers.clone()).await?;
+    let response: ArtifactsResponse = serde_json::from_str(&response)?;
+    if !response.run_ended() {
+        bail!(
+            "run {run_id} is {}, its artifacts can be collected after it has finished",
+            response.state
+        );
+    }
+    let mut collected = vec![];
+    for artifact in response.artifacts {
+        if artifact.size.is_none() {
+            collected.push(CollectedArtifact::Failed {
+                name: artifact.name,
+                reason: "not found on the server".to_string(),
+            });
+            continue;
+        }
+        let result = download(url, headers, run_id, &artifact.name, output).await;
+        collected.push(match result {
+            Ok(size) => CollectedArtifact::Copied {
+                name: artifact.name,
+                size,
+            },
+            Err(e) => CollectedArtifact::Failed {
+                name: artifact.name,
+                reason: e.to_string(),
+            },
+        });
+    }
+    Ok(collected)
+}
+
+async fn download(
+    url: &str,
+    headers: &HashMap<String, String>,
+    run_id: &str,
+    name: &str,
+    output: &Path,
+) -> Result<u64> {
+    let artifact_url = format!("{url}/runs/{run_id}/artifact");
+    let query = ArtifactQueryInfo {
+        name: name.to_string(),
+    };
+    let mut file = File::create(output_path(output, name)?)?;
+    let mut size = 0;
+    request::get_streamed(artifact_url, headers.clone(), &query, |chunk| {
+        size += chunk.len() as u64;
+        file.write_all(chunk).map_err(|e| anyhow!(e))
+    })
+    .await?;
+    Ok(size)
+}
+
+/// The exit code when some of the artifacts couldn't be collected and the collection is strict.
+const EXIT_ARTIFACTS_MISSING: i32 = 4;
+
+/// Prints the collected artifacts with their sizes and the total. The artifacts that couldn't
+/// be collected fail the command with a separate exit code only when the collection is strict.
+pub fn report_artifacts(collected: &[CollectedArtifact], output: &Path, strict: bool) {
+    let failed = print_artifacts(collected, output);
+    if strict && failed > 0 {
+        eprintln!("{failed} artifacts could not be collected");
+        process::exit(EXIT_ARTIFACTS_MISSING);
+    }
+}
+
+/// Prints the collected artifacts and returns the number of the ones that failed.
+fn print_artifacts(collected: &[CollectedArtifact], output: &Path) -> usize {
+    let mut total = 0;
+    let mut copied = 0;
+    let mut failed = 0;
+    for artifact in collected {
+        match artifact {
+            CollectedArtifact::Copied { name, size } => {
+                println!("[bld] Collected artifact {name} ({size} bytes)");
+                total += size;
+                copied += 1;
+            }
+            CollectedArtifact::Failed { name, reason } => {
+                println!("[bld] Unable to collect artifact {name}, {reason}");
+                failed += 1;
+            }
+        }
+    }
+    println!(
+        "[bld] Collected {copied} artifacts into {}, {total} bytes in total",
+        output.display()
+    );
+    failed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::{read_to_string, remove_dir_all, write};
+
+    #[test]
+    fn local_artifacts_are_collected_with_their_names() {
+        let root = temp_dir().join(format!("bld-collect-artifacts-{}", std::process::id()));
+        let dist = root.join("dist");
+        create_dir_all(&dist).unwrap();
+        write(dist.join("app"), "binary").unwrap();
+        write(root.join("report.txt"), "ok").unwrap();
+        let output = root.join("out");
+
+        let paths = vec![
+            dist.display().to_string(),
+            root.join("report.txt").display().to_string(),
+            root.join("missing").display().to_string(),
+        ];
+        let collected = collect_local(&paths, &output);
+
+        assert_eq!(
+            read_to_string(output.join("dist").join("app")).unwrap(),
+            "binary"
+        );
+        assert_eq!(read_to_string(output.join("report.txt")).unwrap(), "ok");
+        assert!(matches!(
+            &collected[2],
+            CollectedArtifact::Failed { name, .. } if name.ends_with("missing")
+        ));
+        assert_eq!(print_artifacts(&collected, &output), 1);
+
+        remove_dir_all(root).unwrap();
+    }
+}
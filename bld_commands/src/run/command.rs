@@ -1,11 +1,15 @@
 use crate::run::invoke::InvokeRun;
+use crate::run::request::{request_files, RunRequest};
 use crate::BldCommand;
-use anyhow::Result;
+use anyhow::{bail, Result};
 use bld_config::definitions::{TOOL_DEFAULT_PIPELINE, VERSION};
 use bld_config::BldConfig;
+use bld_server::requests::parse_label;
+use clap::parser::ValueSource;
 use clap::{Arg, ArgAction, ArgMatches, Command};
 use std::collections::HashMap;
 use std::fmt::Write;
+use std::path::Path;
 use tracing::debug;
 
 const RUN: &str = "run";
@@ -14,6 +18,15 @@ const SERVER: &str = "server";
 const DETACH: &str = "detach";
 const VARIABLES: &str = "variables";
 const ENVIRONMENT: &str = "environment";
+const SECRET: &str = "secret";
+const GIT_URL: &str = "git-url";
+const GIT_REF: &str = "git-ref";
+const DRY_RUN: &str = "dry-run";
+const COLLECT_ARTIFACTS: &str = "collect-artifacts";
+const STRICT_ARTIFACTS: &str = "strict-artifacts";
+const LABEL: &str = "label";
+const REQUEST: &str = "request";
+const REQUEST_DIR: &str = "request-dir";
 
 pub struct RunCommand;
 
@@ -58,34 +71,192 @@ impl BldCommand for RunCommand {
             .help("Define values for environment variables of a pipeline")
             .action(ArgAction::Append);
 
+        let secret = Arg::new(SECRET)
+            .long(SECRET)
+            .help("Define values for secrets of a pipeline, which are masked in its logs")
+            .action(ArgAction::Append);
+
+        let git_url = Arg::new(GIT_URL)
+            .long(GIT_URL)
+            .help("The url of a git repository that the server will clone to run the pipeline from")
+            .requires(SERVER)
+            .action(ArgAction::Set);
+
+        let git_ref = Arg::new(GIT_REF)
+            .long(GIT_REF)
+            .help("The branch, tag or commit of the git repository")
+            .requires(GIT_URL)
+            .action(ArgAction::Set);
+
+        let dry_run = Arg::new(DRY_RUN)
+            .long(DRY_RUN)
+            .help("Prints the commands and artifacts of the pipeline and the pipelines it calls without executing them")
+            .action(ArgAction::SetTrue);
+
+        let collect_artifacts = Arg::new(COLLECT_ARTIFACTS)
+            .long(COLLECT_ARTIFACTS)
+            .help("A directory to collect the get artifacts of the run into after it has finished")
+            .conflicts_with(DETACH)
+            .action(ArgAction::Set);
+
+        let strict_artifacts = Arg::new(STRICT_ARTIFACTS)
+            .long(STRICT_ARTIFACTS)
+            .help("Fails the command if any of the artifacts could not be collected")
+            .requires(COLLECT_ARTIFACTS)
+            .action(ArgAction::SetTrue);
+
+        let label = Arg::new(LABEL)
+            .long(LABEL)
+            .help("A label of the run in the name:value format, which can be used to filter the history of the server")
+            .requires(SERVER)
+            .action(ArgAction::Append);
+
+        let request = Arg::new(REQUEST)
+            .long(REQUEST)
+            .help("A yaml or json file that declares the run, with any of the other options overriding its entries")
+            .action(ArgAction::Set);
+
+        let request_dir = Arg::new(REQUEST_DIR)
+            .long(REQUEST_DIR)
+            .help("A directory of run request files to submit one after the other")
+            .conflicts_with(REQUEST)
+            .action(ArgAction::Set);
+
         Command::new(RUN)
             .about("Executes a build pipeline")
             .version(VERSION)
-            .args(&[pipeline, server, detach, variables, environment])
+            .args(&[
+                pipeline,
+                server,
+                detach,
+                variables,
+                environment,
+                secret,
+                git_url,
+                git_ref,
+                dry_run,
+                collect_artifacts,
+                strict_artifacts,
+                label,
+                request,
+                request_dir,
+            ])
     }
 
     fn exec(&self, matches: &ArgMatches) -> Result<()> {
-        let config = BldConfig::load()?;
-        // using an unwrap here because pipeline option has a default value.
-        let pipeline = matches.get_one::<String>(PIPELINE).cloned().unwrap();
-        let detach = matches.get_flag(DETACH);
-        let env = parse_variables(matches, ENVIRONMENT);
-        let vars = parse_variables(matches, VARIABLES);
-        let server = matches.get_one::<String>(SERVER);
-
-        let mut message = format!(
-            "running {} subcommand with --pipeline: {}, --variables: {:?}",
-            RUN, pipeline, vars
-        );
-
-        if let Some(server_name) = server {
-            write!(message, ", --server: {}", server_name)?;
+        let options = RunRequest {
+            pipeline: matches
+                .get_one::<String>(PIPELINE)
+                .filter(|_| matches.value_source(PIPELINE) == Some(ValueSource::CommandLine))
+                .cloned(),
+            server: matches.get_one::<String>(SERVER).cloned(),
+            variables: parse_variables(matches, VARIABLES),
+            environment: parse_variables(matches, ENVIRONMENT),
+            git_url: matches.get_one::<String>(GIT_URL).cloned(),
+            git_ref: matches.get_one::<String>(GIT_REF).cloned(),
+            dry_run: matches.get_flag(DRY_RUN),
+            detach: matches.get_flag(DETACH),
+            collect_artifacts: matches.get_one::<String>(COLLECT_ARTIFACTS).cloned(),
+            strict_artifacts: matches.get_flag(STRICT_ARTIFACTS),
+            labels: matches
+                .get_many::<String>(LABEL)
+                .unwrap_or_default()
+                .map(|label| parse_label(label))
+                .collect::<Result<_>>()?,
+        };
+        let secrets = parse_variables(matches, SECRET);
+
+        if let Some(dir) = matches.get_one::<String>(REQUEST_DIR) {
+            return submit_requests(Path::new(dir), options, secrets);
+        }
+
+        let request = match matches.get_one::<String>(REQUEST) {
+            Some(path) => {
+                let request = RunRequest::load(Path::new(path))?.merge(options);
+                request.validate()?;
+                request
+            }
+            None => options,
+        };
+        submit(request, secrets)
+    }
+}
+
+/// Submits the request files of a directory in order, continuing after the ones that fail
+/// and summarizing the results at the end.
+fn submit_requests(
+    dir: &Path,
+    options: RunRequest,
+    secrets: HashMap<String, String>,
+) -> Result<()> {
+    let files = request_files(dir)?;
+    if files.is_empty() {
+        bail!("no run request files found in {}", dir.display());
+    }
+    let mut failed = vec![];
+    for file in files.iter() {
+        println!("[bld] Submitting run request {}", file.display());
+        let result = RunRequest::load(file)
+            .map(|request| request.merge(options.clone()))
+            .and_then(|request| {
+                request.validate()?;
+                submit(request, secrets.clone())
+            });
+        match result {
+            Ok(_) => println!("[bld] Run request {} succeeded", file.display()),
+            Err(e) => {
+                println!("[bld] Run request {} failed, {e}", file.display());
+                failed.push(file);
+            }
         }
+    }
+    println!(
+        "[bld] Submitted {} run requests, {} succeeded and {} failed",
+        files.len(),
+        files.len() - failed.len(),
+        failed.len()
+    );
+    if !failed.is_empty() {
+        bail!("{} of the run requests failed", failed.len());
+    }
+    Ok(())
+}
+
+fn submit(request: RunRequest, secrets: HashMap<String, String>) -> Result<()> {
+    let config = BldConfig::load()?;
+    let pipeline = request
+        .pipeline
+        .unwrap_or_else(|| TOOL_DEFAULT_PIPELINE.to_string());
 
-        debug!(message);
+    let mut message = format!(
+        "running {} subcommand with --pipeline: {}, --variables: {:?}",
+        RUN, pipeline, request.variables
+    );
 
-        InvokeRun::new(config, pipeline, server, vars, env, detach)?.start()
+    if let Some(server_name) = &request.server {
+        write!(message, ", --server: {}", server_name)?;
     }
+
+    if let Some(git_url) = &request.git_url {
+        write!(message, ", --git-url: {}", git_url)?;
+    }
+
+    debug!(message);
+
+    InvokeRun::new(
+        config,
+        pipeline,
+        request.server.as_ref(),
+        request.variables,
+        request.environment,
+        request.detach,
+    )?
+    .secrets(secrets)
+    .git(request.git_url, request.git_ref)
+    .dry_run(request.dry_run)
+    .labels(request.labels)
+    .collect_artifacts(request.collect_artifacts, request.strict_artifacts)
+    .start()
 }
 
 pub fn parse_variables(matches: &ArgMatches, arg: &str) -> HashMap<String, String> {
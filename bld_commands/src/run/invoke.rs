@@ -1,21 +1,26 @@
+use crate::capabilities::require_feature;
+use crate::run::artifacts::{collect_local, collect_remote, report_artifacts};
+use crate::telemetry::{run_tracer, shutdown_tracer};
+use crate::ws::ws_connect;
 use actix::{io::SinkWrite, Actor, StreamHandler};
 use actix_web::rt::System;
 use anyhow::{anyhow, Result};
-use awc::http::Version;
-use awc::Client;
 use bld_config::BldConfig;
 use bld_core::logger::Logger;
 use bld_runner::RunnerBuilder;
 use bld_server::requests::RunInfo;
+use bld_server::responses::{RunResponse, FEATURE_ARTIFACTS, FEATURE_DRY_RUNS, FEATURE_GIT_RUNS};
 use bld_server::sockets::ExecClient;
-use bld_utils::request::{self, headers};
+use bld_utils::request::{self, server_headers};
 use futures::stream::StreamExt;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 use tokio::runtime::Runtime;
 use tracing::debug;
 
 struct ServerProperties {
+    pub url: String,
     pub host: String,
     pub port: i64,
     pub protocol: String,
@@ -29,6 +34,13 @@ pub struct InvokeRun {
     pipeline: String,
     environment: HashMap<String, String>,
     variables: HashMap<String, String>,
+    secrets: HashMap<String, String>,
+    git_url: Option<String>,
+    git_ref: Option<String>,
+    dry_run: bool,
+    labels: HashMap<String, String>,
+    collect_artifacts: Option<String>,
+    strict_artifacts: bool,
 }
 
 impl InvokeRun {
@@ -43,8 +55,13 @@ impl InvokeRun {
         let mut server_props = None;
         if let Some(server) = server {
             let server = config.remote.server(server)?;
-            let server_auth = config.remote.same_auth_as(server)?;
             server_props = Some(ServerProperties {
+                url: format!(
+                    "{}://{}:{}",
+                    server.http_protocol(),
+                    server.host,
+                    server.port
+                ),
                 host: server.host.clone(),
                 port: server.port,
                 protocol: if detach {
@@ -52,7 +69,7 @@ impl InvokeRun {
                 } else {
                     server.ws_protocol()
                 },
-                headers: headers(&server_auth.name, &server_auth.auth)?,
+                headers: server_headers(&config.remote, server)?,
             });
         }
         Ok(Self {
@@ -62,9 +79,49 @@ impl InvokeRun {
             pipeline,
             environment: env,
             variables: vars,
+            secrets: HashMap::new(),
+            git_url: None,
+            git_ref: None,
+            dry_run: false,
+            labels: HashMap::new(),
+            collect_artifacts: None,
+            strict_artifacts: false,
         })
     }
 
+    /// Sets the secrets of the run, which are masked in its logs.
+    pub fn secrets(mut self, secrets: HashMap<String, String>) -> Self {
+        self.secrets = secrets;
+        self
+    }
+
+    /// Sets the git repository that the server will clone to run the pipeline from.
+    pub fn git(mut self, url: Option<String>, git_ref: Option<String>) -> Self {
+        self.git_url = url;
+        self.git_ref = git_ref;
+        self
+    }
+
+    /// Logs the commands and artifacts of the run instead of executing them.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Sets the labels that the server stores with the run.
+    pub fn labels(mut self, labels: HashMap<String, String>) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Sets the directory that the `get` artifacts of the run are collected into after it has
+    /// finished and whether failing to collect any of them fails the command.
+    pub fn collect_artifacts(mut self, output: Option<String>, strict: bool) -> Self {
+        self.collect_artifacts = output;
+        self.strict_artifacts = strict;
+        self
+    }
+
     pub fn start(&self) -> Result<()> {
         match &self.server {
             Some(_) => self.invoke_server(),
@@ -74,31 +131,92 @@ impl InvokeRun {
 
     fn invoke_local(&self) -> Result<()> {
         let rt = Runtime::new()?;
-        rt.block_on(async {
+        let artifacts = Arc::new(Mutex::new(vec![]));
+        let tracer = run_tracer(&self.config)?;
+        let result = rt.block_on(async {
             let runner = RunnerBuilder::default()
                 .config(self.config.clone())
                 .pipeline(&self.pipeline)
                 .logger(Logger::shell_atom())
                 .environment(Arc::new(self.environment.clone()))
                 .variables(Arc::new(self.variables.clone()))
-                .build()
+                .secrets(Arc::new(self.secrets.clone()))
+                .dry_run(self.dry_run)
+                .artifacts(artifacts.clone())
+                .tracer(tracer)
+                .build_matrix()
                 .await?;
-            runner.run().await.await
-        })
+            runner.run().await
+        });
+        shutdown_tracer();
+        if let Some(output) = &self.collect_artifacts {
+            let output = Path::new(output);
+            let collected = collect_local(&artifacts.lock().unwrap(), output);
+            report_artifacts(&collected, output, self.strict_artifacts);
+        }
+        result
     }
 
     fn invoke_server(&self) -> Result<()> {
         debug!("spawing actix system");
         if self.detach {
-            System::new().block_on(async move { self.send_run_request().await })
+            System::new().block_on(async move {
+                self.check_capabilities().await?;
+                self.send_run_request().await
+            })
         } else {
+            let run_id = Arc::new(Mutex::new(None));
             let sys = System::new();
-            let res = sys.block_on(async move { self.connect_to_exec_socket().await });
+            sys.block_on(self.check_capabilities())?;
+            let res = sys.block_on(self.connect_to_exec_socket(run_id.clone()));
             sys.run()?;
-            res
+            res?;
+            match &self.collect_artifacts {
+                Some(output) => self.collect_remote_artifacts(&run_id, output),
+                None => Ok(()),
+            }
         }
     }
 
+    fn collect_remote_artifacts(&self, run_id: &Mutex<Option<String>>, output: &str) -> Result<()> {
+        let server_props = self
+            .server
+            .as_ref()
+            .ok_or_else(|| anyhow!("no server properties"))?;
+        let run_id = run_id
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| anyhow!("the server didn't send the id of the run"))?;
+        let output = Path::new(output);
+        let collected = System::new().block_on(collect_remote(
+            &server_props.url,
+            &server_props.headers,
+            &run_id,
+            output,
+        ))?;
+        report_artifacts(&collected, output, self.strict_artifacts);
+        Ok(())
+    }
+
+    async fn check_capabilities(&self) -> Result<()> {
+        let server_props = match &self.server {
+            Some(server_props) => server_props,
+            None => return Ok(()),
+        };
+        if self.git_url.is_some() {
+            require_feature(&server_props.url, &server_props.headers, FEATURE_GIT_RUNS).await?;
+        }
+        // a server that doesn't support dry runs would ignore the flag and execute the pipeline.
+        if self.dry_run {
+            require_feature(&server_props.url, &server_props.headers, FEATURE_DRY_RUNS).await?;
+        }
+        if self.collect_artifacts.is_some() {
+            require_feature(&server_props.url, &server_props.headers, FEATURE_ARTIFACTS).await?;
+        }
+        Ok(())
+    }
+
     async fn send_run_request(&self) -> Result<()> {
         let server_props = self
             .server
@@ -116,15 +234,23 @@ impl InvokeRun {
             &self.pipeline,
             Some(self.environment.clone()),
             Some(self.variables.clone()),
-        );
-        request::post(url, server_props.headers.clone(), request_data)
-            .await
-            .map(|_| {
-                println!("pipeline has been scheduled to run");
-            })
+        )
+        .secrets(Some(self.secrets.clone()))
+        .git(self.git_url.clone(), self.git_ref.clone())
+        .dry_run(self.dry_run)
+        .labels(Some(self.labels.clone()));
+        let response = request::post(url, server_props.headers.clone(), request_data).await?;
+        match serde_json::from_str::<RunResponse>(&response) {
+            Ok(response) => println!(
+                "pipeline has been scheduled to run with id {}",
+                response.run_id
+            ),
+            Err(_) => println!("pipeline has been scheduled to run"),
+        }
+        Ok(())
     }
 
-    async fn connect_to_exec_socket(&self) -> Result<()> {
+    async fn connect_to_exec_socket(&self, run_id: Arc<Mutex<Option<String>>>) -> Result<()> {
         let server_props = self
             .server
             .as_ref()
@@ -137,19 +263,11 @@ impl InvokeRun {
 
         debug!("establishing web socker connection on {}", url);
 
-        let client = Client::builder()
-            .max_http_version(Version::HTTP_11)
-            .finish();
-        let mut client = client.ws(url);
-        for (key, value) in server_props.headers.iter() {
-            client = client.header(&key[..], &value[..]);
-        }
-
-        let (_, framed) = client.connect().await.map_err(|e| anyhow!(e.to_string()))?;
+        let framed = ws_connect(&url, &server_props.headers).await?;
         let (sink, stream) = framed.split();
         let addr = ExecClient::create(|ctx| {
             ExecClient::add_stream(stream, ctx);
-            ExecClient::new(SinkWrite::new(sink, ctx))
+            ExecClient::new(SinkWrite::new(sink, ctx)).run_id(run_id)
         });
 
         debug!(
@@ -157,11 +275,17 @@ impl InvokeRun {
             self.pipeline, self.variables
         );
 
-        addr.send(RunInfo::new(
-            &self.pipeline,
-            Some(self.environment.clone()),
-            Some(self.variables.clone()),
-        ))
+        addr.send(
+            RunInfo::new(
+                &self.pipeline,
+                Some(self.environment.clone()),
+                Some(self.variables.clone()),
+            )
+            .secrets(Some(self.secrets.clone()))
+            .git(self.git_url.clone(), self.git_ref.clone())
+            .dry_run(self.dry_run)
+            .labels(Some(self.labels.clone())),
+        )
         .await
         .map_err(|e| anyhow!(e))
     }
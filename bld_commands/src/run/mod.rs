@@ -1,4 +1,6 @@
+mod artifacts;
 mod command;
 mod invoke;
+mod request;
 
 pub use command::*;
@@ -0,0 +1,246 @@
+use anyhow::{anyhow, bail, Result};
+use bld_server::requests::validate_label;
+use std::collections::HashMap;
+use std::fs::{read_dir, read_to_string};
+use std::path::{Path, PathBuf};
+use yaml_rust::{Yaml, YamlLoader};
+
+const PIPELINE: &str = "pipeline";
+const SERVER: &str = "server";
+const VARIABLES: &str = "variables";
+const ENVIRONMENT: &str = "environment";
+const GIT_URL: &str = "git-url";
+const GIT_REF: &str = "git-ref";
+const DRY_RUN: &str = "dry-run";
+const DETACH: &str = "detach";
+const COLLECT_ARTIFACTS: &str = "collect-artifacts";
+const STRICT_ARTIFACTS: &str = "strict-artifacts";
+const LABELS: &str = "labels";
+
+const ENTRIES: [&str; 11] = [
+    PIPELINE,
+    SERVER,
+    VARIABLES,
+    ENVIRONMENT,
+    GIT_URL,
+    GIT_REF,
+    DRY_RUN,
+    DETACH,
+    COLLECT_ARTIFACTS,
+    STRICT_ARTIFACTS,
+    LABELS,
+];
+
+/// A run declared in a request file, with entries named after the options of the run command.
+/// The options of the command are also gathered in a request so that they can be merged with
+/// the ones of a file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RunRequest {
+    pub pipeline: Option<String>,
+    pub server: Option<String>,
+    pub variables: HashMap<String, String>,
+    pub environment: HashMap<String, String>,
+    pub git_url: Option<String>,
+    pub git_ref: Option<String>,
+    pub dry_run: bool,
+    pub detach: bool,
+    pub collect_artifacts: Option<String>,
+    pub strict_artifacts: bool,
+    pub labels: HashMap<String, String>,
+}
+
+impl RunRequest {
+    pub fn load(path: &Path) -> Result<Self> {
+        let src = read_to_string(path)
+            .map_err(|e| anyhow!("unable to read run request {}, {e}", path.display()))?;
+        Self::parse(&src).map_err(|e| anyhow!("invalid run request {}, {e}", path.display()))
+    }
+
+    /// Parses a run request from yaml, or json which is accepted by the yaml parser as well.
+    /// Unknown entries are rejected so that a misspelled entry isn't silently ignored.
+    pub fn parse(src: &str) -> Result<Self> {
+        let yaml = YamlLoader::load_from_str(src)?
+            .into_iter()
+            .next()
+            .unwrap_or(Yaml::BadValue);
+        let entries = yaml
+            .as_hash()
+            .ok_or_else(|| anyhow!("a run request should be a mapping of its entries"))?;
+        for key in entries.keys() {
+            match key.as_str() {
+                Some(key) if ENTRIES.contains(&key) => {}
+                Some(key) => bail!(
+                    "unknown entry {key}, the entries of a run request are {}",
+                    ENTRIES.join(", ")
+                ),
+                None => bail!("the entries of a run request should be named"),
+            }
+        }
+        Ok(Self {
+            pipeline: Self::string(&yaml, PIPELINE)?,
+            server: Self::string(&yaml, SERVER)?,
+            variables: Self::values(&yaml, VARIABLES)?,
+            environment: Self::values(&yaml, ENVIRONMENT)?,
+            git_url: Self::string(&yaml, GIT_URL)?,
+            git_ref: Self::string(&yaml, GIT_REF)?,
+            dry_run: Self::boolean(&yaml, DRY_RUN)?,
+            detach: Self::boolean(&yaml, DETACH)?,
+            collect_artifacts: Self::string(&yaml, COLLECT_ARTIFACTS)?,
+            strict_artifacts: Self::boolean(&yaml, STRICT_ARTIFACTS)?,
+            labels: Self::values(&yaml, LABELS)?,
+        })
+    }
+
+    fn string(yaml: &Yaml, entry: &str) -> Result<Option<String>> {
+        match &yaml[entry] {
+            Yaml::BadValue => Ok(None),
+            Yaml::String(value) if !value.is_empty() => Ok(Some(value.to_string())),
+            _ => bail!("{entry} should be a non empty string"),
+        }
+    }
+
+    fn boolean(yaml: &Yaml, entry: &str) -> Result<bool> {
+        match &yaml[entry] {
+            Yaml::BadValue => Ok(false),
+            Yaml::Boolean(value) => Ok(*value),
+            _ => bail!("{entry} should be true or false"),
+        }
+    }
+
+    fn values(yaml: &Yaml, entry: &str) -> Result<HashMap<String, String>> {
+        let values = match &yaml[entry] {
+            Yaml::BadValue => return Ok(HashMap::new()),
+            Yaml::Hash(values) => values,
+            _ => bail!("{entry} should be a mapping of names to values"),
+        };
+        let mut parsed = HashMap::new();
+        for (name, value) in values.iter() {
+            let name = name
+                .as_str()
+                .ok_or_else(|| anyhow!("the names of {entry} should be strings"))?;
+            let value = match value {
+                Yaml::String(value) => value.to_string(),
+                Yaml::Integer(value) => value.to_string(),
+                Yaml::Real(value) => value.to_string(),
+                Yaml::Boolean(value) => value.to_string(),
+                _ => bail!("the value of {name} in {entry} should be a string, number or boolean"),
+            };
+            parsed.insert(name.to_string(), value);
+        }
+        Ok(parsed)
+    }
+
+    /// Merges the options of the command into the request, with the options taking precedence
+    /// over the entries of the request.
+    pub fn merge(mut self, options: RunRequest) -> Self {
+        self.pipeline = options.pipeline.or(self.pipeline);
+        self.server = options.server.or(self.server);
+        self.variables.extend(options.variables);
+        self.environment.extend(options.environment);
+        self.git_url = options.git_url.or(self.git_url);
+        self.git_ref = options.git_ref.or(self.git_ref);
+        self.dry_run |= options.dry_run;
+        self.detach |= options.detach;
+        self.collect_artifacts = options.collect_artifacts.or(self.collect_artifacts);
+        self.strict_artifacts |= options.strict_artifacts;
+        self.labels.extend(options.labels);
+        self
+    }
+
+    /// Checks the entries that depend on each other, which the command checks for its options.
+    pub fn validate(&self) -> Result<()> {
+        if self.git_url.is_some() && self.server.is_none() {
+            bail!("{GIT_URL} requires a {SERVER}");
+        }
+        if self.git_ref.is_some() && self.git_url.is_none() {
+            bail!("{GIT_REF} requires a {GIT_URL}");
+        }
+        if self.collect_artifacts.is_some() && self.detach {
+            bail!("{COLLECT_ARTIFACTS} can not be used with {DETACH}");
+        }
+        if self.strict_artifacts && self.collect_artifacts.is_none() {
+            bail!("{STRICT_ARTIFACTS} requires {COLLECT_ARTIFACTS}");
+        }
+        if !self.labels.is_empty() && self.server.is_none() {
+            bail!("{LABELS} require a {SERVER}");
+        }
+        for (name, value) in self.labels.iter() {
+            validate_label(name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// The request files of a directory, which are its yaml and json files in order of their names.
+pub fn request_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for entry in read_dir(dir)
+        .map_err(|e| anyhow!("unable to read run requests of {}, {e}", dir.display()))?
+    {
+        let path = entry?.path();
+        let is_request = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| matches!(e, "yaml" | "yml" | "json"))
+            .unwrap_or(false);
+        if path.is_file() && is_request {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_request_is_parsed_strictly_and_merged_with_the_options() {
+        let request = RunRequest::parse(
+            r"
+pipeline: deploy.yaml
+server: ci
+variables:
+  environment: staging
+  replicas: 3
+detach: true
+",
+        )
+        .unwrap();
+        assert_eq!(request.pipeline.as_deref(), Some("deploy.yaml"));
+        assert_eq!(request.variables["replicas"], "3");
+        assert!(request.detach);
+
+        let options = RunRequest {
+            server: Some("local".to_string()),
+            variables: HashMap::from([("environment".to_string(), "production".to_string())]),
+            ..Default::default()
+        };
+        let merged = request.merge(options);
+        assert_eq!(merged.pipeline.as_deref(), Some("deploy.yaml"));
+        assert_eq!(merged.server.as_deref(), Some("local"));
+        assert_eq!(merged.variables["environment"], "production");
+        assert_eq!(merged.variables["replicas"], "3");
+
+        let json = RunRequest::parse(r#"{"pipeline": "build.yaml", "dry-run": true}"#).unwrap();
+        assert!(json.dry_run);
+
+        let unknown = RunRequest::parse("pipeline: build.yaml\nvariable:\n  a: b").unwrap_err();
+        assert!(unknown.to_string().contains("unknown entry variable"));
+        assert!(RunRequest::parse("detach: yes please").is_err());
+        assert!(RunRequest::parse("variables: [a, b]").is_err());
+        assert!(RunRequest::parse("collect-artifacts: out\ndetach: true")
+            .unwrap()
+            .validate()
+            .is_err());
+        assert!(RunRequest::parse("labels:\n  env: production")
+            .unwrap()
+            .validate()
+            .is_err());
+        assert!(RunRequest::parse("server: ci\nlabels:\n  env: production")
+            .unwrap()
+            .validate()
+            .is_ok());
+    }
+}
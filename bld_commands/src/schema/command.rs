@@ -0,0 +1,109 @@
+use crate::BldCommand;
+use anyhow::{bail, Result};
+use bld_config::definitions::VERSION;
+use bld_runner::{json_schema, markdown_schema};
+use bld_utils::term::print_info;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use std::fs::{create_dir_all, write};
+use std::path::Path;
+use tracing::debug;
+
+const SCHEMA: &str = "schema";
+const FORMAT: &str = "format";
+const OUTPUT: &str = "output";
+const JSON: &str = "json";
+const MARKDOWN: &str = "markdown";
+
+pub struct SchemaCommand;
+
+impl BldCommand for SchemaCommand {
+    fn boxed() -> Box<Self> {
+        Box::new(Self)
+    }
+
+    fn id(&self) -> &'static str {
+        SCHEMA
+    }
+
+    fn interface(&self) -> Command {
+        let format = Arg::new(FORMAT)
+            .short('f')
+            .long(FORMAT)
+            .help("The format of the schema, json or markdown")
+            .value_parser([JSON, MARKDOWN])
+            .default_value(JSON)
+            .action(ArgAction::Set);
+
+        let output = Arg::new(OUTPUT)
+            .short('o')
+            .long(OUTPUT)
+            .help("The path of the generated file. If not provided the schema is printed")
+            .action(ArgAction::Set);
+
+        Command::new(SCHEMA)
+            .about("Generates the schema of the pipeline format as JSON Schema for editors or as markdown")
+            .version(VERSION)
+            .args(&[format, output])
+    }
+
+    fn exec(&self, matches: &ArgMatches) -> Result<()> {
+        // using an unwrap here because format option has a default value.
+        let format = matches.get_one::<String>(FORMAT).cloned().unwrap();
+        let output = matches.get_one::<String>(OUTPUT).cloned();
+
+        debug!(
+            "running {} subcommand with --format: {}, --output: {:?}",
+            SCHEMA, format, output
+        );
+
+        let content = match format.as_str() {
+            JSON => serde_json::to_string_pretty(&json_schema())?,
+            MARKDOWN => markdown_schema(),
+            _ => bail!("unsupported schema format {format}"),
+        };
+
+        match output {
+            Some(output) => {
+                if let Some(parent) = Path::new(&output).parent() {
+                    create_dir_all(parent)?;
+                }
+                write(&output, content)?;
+                print_info(&format!("schema generated at {output}"))
+            }
+            None => {
+                println!("{content}");
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cli_schema_format_arg_defaults_to_json() {
+        let command = SchemaCommand::boxed().interface();
+        let matches = command.get_matches_from(["schema"]);
+
+        assert_eq!(matches.get_one::<String>(FORMAT), Some(&JSON.to_string()))
+    }
+
+    #[test]
+    fn cli_schema_format_arg_rejects_unknown_value() {
+        let command = SchemaCommand::boxed().interface();
+        let matches = command.try_get_matches_from(["schema", "-f", "yaml"]);
+
+        assert!(matches.is_err())
+    }
+
+    #[test]
+    fn cli_schema_output_arg_accepts_value() {
+        let output = "mock_output.json";
+        let command = SchemaCommand::boxed().interface();
+        let matches = command.get_matches_from(["schema", "-o", output]);
+
+        assert_eq!(matches.get_one::<String>(OUTPUT), Some(&output.to_string()))
+    }
+}
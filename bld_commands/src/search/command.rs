@@ -0,0 +1,105 @@
+use crate::capabilities::require_feature;
+use crate::BldCommand;
+use actix_web::rt::System;
+use anyhow::Result;
+use bld_config::definitions::VERSION;
+use bld_config::BldConfig;
+use bld_server::requests::SearchQueryInfo;
+use bld_server::responses::{SearchEntry, FEATURE_SEARCH};
+use bld_utils::request;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use tabled::{Style, Table};
+use tracing::debug;
+
+static SEARCH: &str = "search";
+static TERM: &str = "term";
+static SERVER: &str = "server";
+static JSON: &str = "json";
+
+pub struct SearchCommand;
+
+impl BldCommand for SearchCommand {
+    fn boxed() -> Box<Self> {
+        Box::new(Self)
+    }
+
+    fn id(&self) -> &'static str {
+        SEARCH
+    }
+
+    fn interface(&self) -> Command {
+        let term = Arg::new(TERM)
+            .help("The text to search for in the pipelines, ignoring its case")
+            .required(true)
+            .action(ArgAction::Set);
+
+        let server = Arg::new(SERVER)
+            .short('s')
+            .long("server")
+            .help("The name of the server to search the pipelines of")
+            .action(ArgAction::Set);
+
+        let json = Arg::new(JSON)
+            .long("json")
+            .help("Prints the matching pipelines as json instead of a table")
+            .action(ArgAction::SetTrue);
+
+        Command::new(SEARCH)
+            .about("Searches the content of the pipelines of a server")
+            .version(VERSION)
+            .args(&[term, server, json])
+    }
+
+    fn exec(&self, matches: &ArgMatches) -> Result<()> {
+        let config = BldConfig::load()?;
+        let term = matches.get_one::<String>(TERM).cloned().unwrap();
+        let server = config
+            .remote
+            .server_or_first(matches.get_one::<String>(SERVER))?;
+
+        debug!(
+            "running {} subcommand with term: {}, --server: {}",
+            SEARCH, term, server.name
+        );
+
+        let protocol = server.http_protocol();
+        let url = format!("{protocol}://{}:{}", server.host, server.port);
+        let headers = request::server_headers(&config.remote, server)?;
+        let json = matches.get_flag(JSON);
+
+        System::new().block_on(async move {
+            require_feature(&url, &headers, FEATURE_SEARCH).await?;
+            let url = format!("{url}/search");
+            debug!("sending http request to {url}");
+            let params = SearchQueryInfo::new(&term);
+            let response = request::get_with_query(url, headers, &params).await?;
+            let entries: Vec<SearchEntry> = serde_json::from_str(&response)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&entries)?);
+            } else if entries.is_empty() {
+                println!("no pipelines contain {term}");
+            } else {
+                println!("{}", Table::new(entries).with(Style::modern()));
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cli_search_requires_a_term() {
+        let command = SearchCommand::boxed().interface();
+        assert!(command.clone().try_get_matches_from(["search"]).is_err());
+
+        let matches = command.get_matches_from(["search", "docker", "-s", "local"]);
+        assert_eq!(matches.get_one::<String>(TERM), Some(&"docker".to_string()));
+        assert_eq!(
+            matches.get_one::<String>(SERVER),
+            Some(&"local".to_string())
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use crate::banner::startup_banner;
 use crate::BldCommand;
 use actix_web::rt::System;
 use anyhow::Result;
@@ -9,6 +10,8 @@ use tracing::debug;
 static SERVER: &str = "server";
 static HOST: &str = "host";
 static PORT: &str = "port";
+static TEST_REDACTION: &str = "test-redaction";
+static LINE: &str = "line";
 
 pub struct ServerCommand;
 
@@ -34,15 +37,33 @@ impl BldCommand for ServerCommand {
             .help("The server's port")
             .action(ArgAction::Set);
 
+        let line = Arg::new(LINE)
+            .long(LINE)
+            .short('l')
+            .help("The sample line to apply the rules to")
+            .required(true)
+            .action(ArgAction::Set);
+
+        let test_redaction = Command::new(TEST_REDACTION)
+            .about("Applies the log redaction rules of the server config to a sample line")
+            .arg(line);
+
         Command::new(SERVER)
             .about("Start bld in server mode, listening to incoming build requests")
             .version(VERSION)
             .args(&[host, port])
+            .subcommand(test_redaction)
     }
 
     fn exec(&self, matches: &ArgMatches) -> Result<()> {
         let config = BldConfig::load()?;
 
+        if let Some(matches) = matches.subcommand_matches(TEST_REDACTION) {
+            // using an unwrap here because the line option is required.
+            let line = matches.get_one::<String>(LINE).unwrap();
+            return test_redaction(&config, line);
+        }
+
         let host = matches
             .get_one::<String>("host")
             .unwrap_or(&config.local.server.host)
@@ -55,8 +76,22 @@ impl BldCommand for ServerCommand {
 
         debug!("running {SERVER} subcommand with --host: {host} --port: {port}",);
 
-        System::new().block_on(async move { bld_server::start(config, host, port).await })
+        System::new().block_on(async move {
+            startup_banner(&config, SERVER).await;
+            bld_server::start(config, host, port).await
+        })
+    }
+}
+
+fn test_redaction(config: &BldConfig, line: &str) -> Result<()> {
+    let mut line = line.to_string();
+    for rule in config.local.log_redaction.iter() {
+        let (redacted, count) = rule.apply(&line);
+        println!("{}: {count} matches", rule.name);
+        line = redacted;
     }
+    println!("{line}");
+    Ok(())
 }
 
 #[cfg(test)]
@@ -80,4 +115,14 @@ mod tests {
 
         assert_eq!(matches.get_one::<String>(PORT), Some(&port.to_string()))
     }
+
+    #[test]
+    fn cli_server_test_redaction_line_arg_accepts_value() {
+        let line = "mock line";
+        let command = ServerCommand::boxed().interface();
+        let matches = command.get_matches_from(["server", "test-redaction", "--line", line]);
+        let matches = matches.subcommand_matches(TEST_REDACTION).unwrap();
+
+        assert_eq!(matches.get_one::<String>(LINE), Some(&line.to_string()))
+    }
 }
@@ -0,0 +1,106 @@
+use crate::capabilities::require_feature;
+use crate::BldCommand;
+use actix_web::rt::System;
+use anyhow::Result;
+use bld_config::definitions::VERSION;
+use bld_config::BldConfig;
+use bld_server::requests::StatsQueryInfo;
+use bld_server::responses::{PipelineStats, FEATURE_STATS};
+use bld_utils::request;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use tabled::{Style, Table};
+use tracing::debug;
+
+static STATS: &str = "stats";
+static PIPELINE: &str = "pipeline";
+static SERVER: &str = "server";
+static JSON: &str = "json";
+
+pub struct StatsCommand;
+
+impl BldCommand for StatsCommand {
+    fn boxed() -> Box<Self> {
+        Box::new(Self)
+    }
+
+    fn id(&self) -> &'static str {
+        STATS
+    }
+
+    fn interface(&self) -> Command {
+        let pipeline = Arg::new(PIPELINE)
+            .short('p')
+            .long(PIPELINE)
+            .visible_alias("name")
+            .help("The name of the pipeline to print the statistics of")
+            .required(true)
+            .action(ArgAction::Set);
+
+        let server = Arg::new(SERVER)
+            .short('s')
+            .long(SERVER)
+            .help("The name of the server from which to fetch the statistics")
+            .action(ArgAction::Set);
+
+        let json = Arg::new(JSON)
+            .long(JSON)
+            .help("Prints the statistics as json instead of a table")
+            .action(ArgAction::SetTrue);
+
+        Command::new(STATS)
+            .about("Prints the statistics of the runs of a pipeline on a server")
+            .version(VERSION)
+            .args(&[pipeline, server, json])
+    }
+
+    fn exec(&self, matches: &ArgMatches) -> Result<()> {
+        let config = BldConfig::load()?;
+        let pipeline = matches.get_one::<String>(PIPELINE).cloned().unwrap();
+        let server = config
+            .remote
+            .server_or_first(matches.get_one::<String>(SERVER))?;
+
+        debug!(
+            "running {} subcommand with --pipeline: {}, --server: {}",
+            STATS, pipeline, server.name
+        );
+
+        let protocol = server.http_protocol();
+        let url = format!("{protocol}://{}:{}", server.host, server.port);
+        let headers = request::server_headers(&config.remote, server)?;
+        let json = matches.get_flag(JSON);
+
+        System::new().block_on(async move {
+            require_feature(&url, &headers, FEATURE_STATS).await?;
+            let url = format!("{url}/stats");
+            debug!("sending http request to {url}");
+            let params = StatsQueryInfo::new(&pipeline);
+            let response = request::get_with_query(url, headers, &params).await?;
+            let stats: PipelineStats = serde_json::from_str(&response)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&stats)?);
+            } else {
+                println!("{}", Table::new([stats]).with(Style::modern()));
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cli_stats_requires_a_pipeline() {
+        let command = StatsCommand::boxed().interface();
+        assert!(command.clone().try_get_matches_from(["stats"]).is_err());
+
+        let matches = command.get_matches_from(["stats", "--name", "build.yaml", "-s", "local"]);
+        assert_eq!(
+            matches.get_one::<String>(PIPELINE),
+            Some(&"build.yaml".to_string())
+        );
+        assert!(!matches.get_flag(JSON));
+    }
+}
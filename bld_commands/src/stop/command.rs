@@ -49,10 +49,9 @@ impl BldCommand for StopCommand {
             .remote
             .server_or_first(matches.get_one::<String>(SERVER))?;
 
-        let server_auth = config.remote.same_auth_as(server)?;
         let protocol = server.http_protocol();
         let url = format!("{protocol}://{}:{}/stop", server.host, server.port);
-        let headers = request::headers(&server_auth.name, &server_auth.auth)?;
+        let headers = request::server_headers(&config.remote, server)?;
 
         System::new().block_on(async move {
             request::post(url, headers, id).await.map(|r| {
@@ -1,13 +1,15 @@
+use crate::banner::startup_banner;
 use crate::BldCommand;
 use actix_web::rt::System;
 use anyhow::Result;
 use bld_config::definitions::VERSION;
 use bld_config::BldConfig;
 use bld_supervisor::supervisor;
-use clap::{ArgMatches, Command};
+use clap::{Arg, ArgAction, ArgMatches, Command};
 use tracing::debug;
 
 static SUPERVISOR: &str = "supervisor";
+static SOCKET: &str = "socket";
 
 pub struct SupervisorCommand;
 
@@ -21,14 +23,40 @@ impl BldCommand for SupervisorCommand {
     }
 
     fn interface(&self) -> Command {
+        let socket = Arg::new(SOCKET)
+            .long(SOCKET)
+            .help("The path of the unix socket that the supervisor will listen to. Overrides the configuration value")
+            .action(ArgAction::Set);
+
         Command::new(SUPERVISOR)
             .about("Starts a bld supervisor that manages the pipeline worker queue. should be only invoked by the server")
             .version(VERSION)
+            .args(&[socket])
     }
 
-    fn exec(&self, _matches: &ArgMatches) -> Result<()> {
-        let config = BldConfig::load()?;
+    fn exec(&self, matches: &ArgMatches) -> Result<()> {
+        let mut config = BldConfig::load()?;
+        if let Some(socket) = matches.get_one::<String>(SOCKET) {
+            config.local.supervisor.socket = Some(socket.to_string());
+        }
         debug!("starting supervisor");
-        System::new().block_on(async move { supervisor::start(config).await })
+        System::new().block_on(async move {
+            startup_banner(&config, SUPERVISOR).await;
+            supervisor::start(config).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cli_supervisor_socket_arg_accepts_value() {
+        let socket = "/var/run/bld/supervisor.sock";
+        let command = SupervisorCommand::boxed().interface();
+        let matches = command.get_matches_from(["supervisor", "--socket", socket]);
+
+        assert_eq!(matches.get_one::<String>(SOCKET), Some(&socket.to_string()))
     }
 }
@@ -0,0 +1,26 @@
+use anyhow::Result;
+use bld_config::BldConfig;
+use bld_runner::AtomicTracer;
+use opentelemetry::global;
+use std::sync::Arc;
+
+const TRACER_NAME: &str = "bld";
+
+/// Installs the jaeger exporter of the telemetry config as the global tracer provider and
+/// returns the tracer for the spans of a run, or none if telemetry isn't configured.
+pub fn run_tracer(config: &BldConfig) -> Result<Option<AtomicTracer>> {
+    let telemetry = match &config.local.telemetry {
+        Some(telemetry) => telemetry,
+        None => return Ok(None),
+    };
+    opentelemetry_jaeger::new_pipeline()
+        .with_agent_endpoint(telemetry.agent_endpoint.as_str())
+        .with_service_name(&telemetry.service_name)
+        .install_simple()?;
+    Ok(Some(Arc::new(global::tracer(TRACER_NAME))))
+}
+
+/// Exports the spans that haven't been exported yet, which should be done before the process exits.
+pub fn shutdown_tracer() {
+    global::shutdown_tracer_provider();
+}
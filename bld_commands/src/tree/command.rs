@@ -0,0 +1,168 @@
+use crate::capabilities::require_feature;
+use crate::BldCommand;
+use actix_web::rt::System;
+use anyhow::Result;
+use bld_config::definitions::VERSION;
+use bld_config::BldConfig;
+use bld_server::responses::{RunTreeNode, FEATURE_RUN_TREES};
+use bld_utils::request;
+use chrono::NaiveDateTime;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use tracing::debug;
+
+static TREE: &str = "tree";
+static RUN_ID: &str = "run-id";
+static SERVER: &str = "server";
+
+/// The format of the dates of a run as stored by the server.
+const RUN_DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+pub struct TreeCommand;
+
+impl BldCommand for TreeCommand {
+    fn boxed() -> Box<Self> {
+        Box::new(Self)
+    }
+
+    fn id(&self) -> &'static str {
+        TREE
+    }
+
+    fn interface(&self) -> Command {
+        let run_id = Arg::new(RUN_ID)
+            .short('i')
+            .long("run-id")
+            .help("The id of a pipeline run on a server")
+            .required(true)
+            .action(ArgAction::Set);
+
+        let server = Arg::new(SERVER)
+            .short('s')
+            .long("server")
+            .help("The name of the server that the pipeline was run")
+            .action(ArgAction::Set);
+
+        Command::new(TREE)
+            .about("Shows the pipelines called by a run on a server along with their state and duration")
+            .version(VERSION)
+            .args(&[run_id, server])
+    }
+
+    fn exec(&self, matches: &ArgMatches) -> Result<()> {
+        let config = BldConfig::load()?;
+        let run_id = matches.get_one::<String>(RUN_ID).cloned().unwrap();
+
+        let server = config
+            .remote
+            .server_or_first(matches.get_one::<String>(SERVER))?;
+
+        let protocol = server.http_protocol();
+        let url = format!("{protocol}://{}:{}", server.host, server.port);
+        let headers = request::server_headers(&config.remote, server)?;
+
+        let tree = System::new().block_on(async move {
+            require_feature(&url, &headers, FEATURE_RUN_TREES).await?;
+            let url = format!("{url}/runs/{run_id}/tree");
+            debug!("sending http request to {url}");
+            let response = request::get(url, headers).await?;
+            let tree: RunTreeNode = serde_json::from_str(&response)?;
+            Ok::<RunTreeNode, anyhow::Error>(tree)
+        })?;
+
+        for line in render(&tree) {
+            println!("{line}");
+        }
+        Ok(())
+    }
+}
+
+fn duration(node: &RunTreeNode) -> Option<String> {
+    let start = NaiveDateTime::parse_from_str(&node.start_date_time, RUN_DATE_FORMAT).ok()?;
+    let end = node.end_date_time.as_ref()?;
+    let end = NaiveDateTime::parse_from_str(end, RUN_DATE_FORMAT).ok()?;
+    let seconds = (end - start).num_seconds().max(0);
+    Some(format!("{}m {}s", seconds / 60, seconds % 60))
+}
+
+fn render_node(node: &RunTreeNode, depth: usize, lines: &mut Vec<String>) {
+    let step = node
+        .step_name
+        .as_ref()
+        .map(|s| format!(" from step {s}"))
+        .unwrap_or_default();
+    let duration = duration(node).unwrap_or_else(|| "-".to_string());
+    let rollup = match (&node.rollup, &node.effective_state) {
+        (Some(rollup), Some(effective)) => format!(" (rollup: {rollup}, effective: {effective})"),
+        _ => String::new(),
+    };
+    lines.push(format!(
+        "{}{} ({}){step}: {} in {duration}{rollup}",
+        "  ".repeat(depth),
+        node.pipeline,
+        node.run_id,
+        node.state
+    ));
+    for child in node.children.iter() {
+        render_node(child, depth + 1, lines);
+    }
+}
+
+/// Renders the call tree of a run as an indented list with a line for each run.
+fn render(tree: &RunTreeNode) -> Vec<String> {
+    let mut lines = vec![];
+    render_node(tree, 0, &mut lines);
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(run_id: &str, step_name: Option<&str>, children: Vec<RunTreeNode>) -> RunTreeNode {
+        RunTreeNode {
+            run_id: run_id.to_string(),
+            pipeline: format!("{run_id}.yaml"),
+            step_name: step_name.map(|s| s.to_string()),
+            state: "finished".to_string(),
+            start_date_time: "2022-10-16 10:00:00".to_string(),
+            end_date_time: Some("2022-10-16 10:01:05".to_string()),
+            rollup: None,
+            effective_state: None,
+            children,
+        }
+    }
+
+    #[test]
+    fn cli_tree_run_id_arg_is_required() {
+        let command = TreeCommand::boxed().interface();
+        let result = command.try_get_matches_from(["tree", "-s", "mock_server"]);
+
+        assert!(result.is_err())
+    }
+
+    #[test]
+    fn tree_is_rendered_with_a_line_per_run() {
+        let mut running = node("test", Some("Test"), vec![]);
+        running.state = "running".to_string();
+        running.end_date_time = None;
+        let mut notify = node("notify", Some("Notify"), vec![]);
+        notify.state = "faulted".to_string();
+        notify.rollup = Some("warn".to_string());
+        notify.effective_state = Some("finished".to_string());
+        let tree = node(
+            "deploy",
+            None,
+            vec![node("build", Some("Build"), vec![running]), notify],
+        );
+
+        assert_eq!(
+            render(&tree),
+            vec![
+                "deploy.yaml (deploy): finished in 1m 5s",
+                "  build.yaml (build) from step Build: finished in 1m 5s",
+                "    test.yaml (test) from step Test: running in -",
+                "  notify.yaml (notify) from step Notify: faulted in 1m 5s (rollup: warn, effective: finished)",
+            ]
+        );
+    }
+}
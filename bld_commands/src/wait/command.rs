@@ -0,0 +1,170 @@
+use crate::capabilities::require_feature;
+use crate::BldCommand;
+use actix_web::rt::System;
+use anyhow::Result;
+use bld_config::definitions::VERSION;
+use bld_config::BldConfig;
+use bld_server::requests::CheckpointsQueryInfo;
+use bld_server::responses::{CheckpointsResponse, FEATURE_CHECKPOINTS};
+use bld_utils::request;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use std::collections::HashMap;
+use std::process;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+static WAIT: &str = "wait";
+static ID: &str = "id";
+static CHECKPOINT: &str = "checkpoint";
+static TIMEOUT: &str = "timeout";
+static SERVER: &str = "server";
+
+/// The exit code when the run ends without reaching the checkpoint.
+const EXIT_RUN_ENDED: i32 = 2;
+/// The exit code when the checkpoint isn't reached before the timeout.
+const EXIT_TIMEOUT: i32 = 3;
+
+enum WaitOutcome {
+    Reached(String),
+    RunEnded(String),
+    Timeout,
+}
+
+pub struct WaitCommand;
+
+impl BldCommand for WaitCommand {
+    fn boxed() -> Box<Self> {
+        Box::new(Self)
+    }
+
+    fn id(&self) -> &'static str {
+        WAIT
+    }
+
+    fn interface(&self) -> Command {
+        let id = Arg::new(ID)
+            .short('i')
+            .long("id")
+            .help("The id of a pipeline running on a server")
+            .required(true)
+            .action(ArgAction::Set);
+
+        let checkpoint = Arg::new(CHECKPOINT)
+            .short('c')
+            .long("checkpoint")
+            .help("The name of the checkpoint to wait for")
+            .required(true)
+            .action(ArgAction::Set);
+
+        let timeout = Arg::new(TIMEOUT)
+            .short('t')
+            .long("timeout")
+            .help("The number of seconds to wait for the checkpoint")
+            .value_parser(clap::value_parser!(u64))
+            .default_value("60")
+            .action(ArgAction::Set);
+
+        let server = Arg::new(SERVER)
+            .short('s')
+            .long("server")
+            .help("The name of the server that the pipeline is running")
+            .action(ArgAction::Set);
+
+        Command::new(WAIT)
+            .about("Waits for a run on a server to reach a checkpoint. Exits with 2 if the run ends without reaching it and 3 on timeout")
+            .version(VERSION)
+            .args(&[id, checkpoint, timeout, server])
+    }
+
+    fn exec(&self, matches: &ArgMatches) -> Result<()> {
+        let config = BldConfig::load()?;
+        let id = matches.get_one::<String>(ID).cloned().unwrap();
+        let checkpoint = matches.get_one::<String>(CHECKPOINT).cloned().unwrap();
+        let timeout = matches.get_one::<u64>(TIMEOUT).copied().unwrap();
+
+        let server = config
+            .remote
+            .server_or_first(matches.get_one::<String>(SERVER))?;
+
+        let protocol = server.http_protocol();
+        let url = format!("{protocol}://{}:{}", server.host, server.port);
+        let headers = request::server_headers(&config.remote, server)?;
+
+        let outcome = System::new().block_on(async move {
+            require_feature(&url, &headers, FEATURE_CHECKPOINTS).await?;
+            wait_checkpoint(&url, headers, &id, &checkpoint, timeout).await
+        })?;
+
+        match outcome {
+            WaitOutcome::Reached(date) => {
+                println!("checkpoint reached at {date}");
+                Ok(())
+            }
+            WaitOutcome::RunEnded(state) => {
+                println!("run has {state} without reaching the checkpoint");
+                process::exit(EXIT_RUN_ENDED)
+            }
+            WaitOutcome::Timeout => {
+                println!("timed out waiting for the checkpoint");
+                process::exit(EXIT_TIMEOUT)
+            }
+        }
+    }
+}
+
+/// Long polls the checkpoints of the run until the checkpoint is reached, the run ends or the
+/// timeout expires. The server limits the duration of a single request so it is repeated until then.
+async fn wait_checkpoint(
+    url: &str,
+    headers: HashMap<String, String>,
+    id: &str,
+    checkpoint: &str,
+    timeout: u64,
+) -> Result<WaitOutcome> {
+    let url = format!("{url}/runs/{id}/checkpoints");
+    let deadline = Instant::now() + Duration::from_secs(timeout);
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let query = CheckpointsQueryInfo {
+            wait: Some(checkpoint.to_string()),
+            timeout: Some(remaining.as_secs()),
+        };
+        debug!("sending http request to {url}");
+        let response = request::get_with_query(url.clone(), headers.clone(), &query).await?;
+        let response: CheckpointsResponse = serde_json::from_str(&response)?;
+        if let Some(entry) = response.checkpoints.iter().find(|c| c.name == checkpoint) {
+            return Ok(WaitOutcome::Reached(entry.date_created.clone()));
+        }
+        if response.run_ended() {
+            return Ok(WaitOutcome::RunEnded(response.state));
+        }
+        if Instant::now() >= deadline {
+            return Ok(WaitOutcome::Timeout);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cli_wait_timeout_arg_defaults_to_a_minute() {
+        let command = WaitCommand::boxed().interface();
+        let matches = command.get_matches_from(["wait", "-i", "mock_run_id", "-c", "published"]);
+
+        assert_eq!(
+            matches.get_one::<String>(CHECKPOINT),
+            Some(&"published".to_string())
+        );
+        assert_eq!(matches.get_one::<u64>(TIMEOUT), Some(&60))
+    }
+
+    #[test]
+    fn cli_wait_checkpoint_arg_is_required() {
+        let command = WaitCommand::boxed().interface();
+        let result = command.try_get_matches_from(["wait", "-i", "mock_run_id"]);
+
+        assert!(result.is_err())
+    }
+}
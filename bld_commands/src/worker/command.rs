@@ -1,32 +1,43 @@
 use crate::run::parse_variables;
+use crate::telemetry::{run_tracer, shutdown_tracer};
 use crate::BldCommand;
 use actix::io::SinkWrite;
 use actix::{Actor, StreamHandler};
 use actix_web::rt::{spawn, System};
 use anyhow::{anyhow, Result};
-use awc::http::Version;
-use awc::Client;
+use bld_config::definitions::WORKER_SECRETS_ENV;
 use bld_config::BldConfig;
 use bld_core::context::Context;
 use bld_core::database::{new_connection_pool, pipeline_runs};
-use bld_core::execution::Execution;
+use bld_core::execution::{execution_journal, Execution};
 use bld_core::logger::Logger;
 use bld_core::proxies::PipelineFileSystemProxy;
-use bld_runner::RunnerBuilder;
+use bld_runner::{trace_parent_context, RunnerBuilder};
 use bld_supervisor::base::WorkerMessages;
+use bld_supervisor::client::ws_client;
 use bld_supervisor::sockets::WorkerClient;
 use clap::{Arg, ArgAction, ArgMatches, Command};
 use futures::join;
 use futures::stream::StreamExt;
+use std::collections::HashMap;
+use std::env;
 use std::sync::Arc;
+use tokio::signal::ctrl_c;
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
 use tokio::sync::mpsc::{channel, Receiver};
-use tracing::{debug, error};
+use tracing::{debug, error, info};
 
 const WORKER: &str = "worker";
 const PIPELINE: &str = "pipeline";
 const RUN_ID: &str = "run-id";
 const VARIABLES: &str = "variables";
 const ENVIRONMENT: &str = "environment";
+const SOCKET: &str = "socket";
+const GIT_URL: &str = "git-url";
+const GIT_REF: &str = "git-ref";
+const DRY_RUN: &str = "dry-run";
+const TRACEPARENT: &str = "traceparent";
 
 pub struct WorkerCommand;
 
@@ -66,37 +77,107 @@ impl BldCommand for WorkerCommand {
             .help("Define values for environment variables in the server pipeline")
             .action(ArgAction::Append);
 
+        let socket = Arg::new(SOCKET)
+            .long(SOCKET)
+            .help("The path to the unix socket of the supervisor")
+            .action(ArgAction::Set);
+
+        let git_url = Arg::new(GIT_URL)
+            .long(GIT_URL)
+            .help("The url of a git repository to clone and run the pipeline from")
+            .action(ArgAction::Set);
+
+        let git_ref = Arg::new(GIT_REF)
+            .long(GIT_REF)
+            .help("The branch, tag or commit of the git repository")
+            .requires(GIT_URL)
+            .action(ArgAction::Set);
+
+        let dry_run = Arg::new(DRY_RUN)
+            .long(DRY_RUN)
+            .help("Logs the commands and artifacts of the pipeline instead of executing them")
+            .action(ArgAction::SetTrue);
+
+        let traceparent = Arg::new(TRACEPARENT)
+            .long(TRACEPARENT)
+            .help("The W3C traceparent of the request that started the run")
+            .action(ArgAction::Set);
+
         Command::new(WORKER)
             .about("A sub command that creates a worker process for a bld server in order to run a pipeline.")
-            .args(&[pipeline, run_id, variables, environment])
+                        .args(&[
+                pipeline,
+                run_id,
+                variables,
+                environment,
+                socket,
+                git_url,
+                git_ref,
+                dry_run,
+                traceparent,
+            ])
     }
 
     fn exec(&self, matches: &ArgMatches) -> Result<()> {
-        let cfg = Arc::new(BldConfig::load()?);
+        let mut cfg = BldConfig::load()?;
+        if let Some(socket) = matches.get_one::<String>(SOCKET) {
+            cfg.local.supervisor.socket = Some(socket.to_string());
+        }
+        let cfg = Arc::new(cfg);
         let socket_cfg = Arc::clone(&cfg);
 
         let pipeline = Arc::new(matches.get_one::<String>(PIPELINE).cloned().unwrap());
         let run_id = Arc::new(matches.get_one::<String>(RUN_ID).cloned().unwrap());
         let variables = Arc::new(parse_variables(matches, VARIABLES));
         let environment = Arc::new(parse_variables(matches, ENVIRONMENT));
+        let secrets = Arc::new(worker_secrets()?);
+        let dry_run = matches.get_flag(DRY_RUN);
+        let tracer = run_tracer(&cfg)?;
+        let trace_parent = matches
+            .get_one::<String>(TRACEPARENT)
+            .and_then(|t| trace_parent_context(t));
 
         let pool = Arc::new(new_connection_pool(&cfg.local.db)?);
         let mut conn = pool.get()?;
         let pipeline_run = pipeline_runs::select_by_id(&mut conn, &run_id)?;
         let start_date_time = pipeline_run.start_date_time;
-        let proxy = Arc::new(PipelineFileSystemProxy::Server {
-            config: cfg.clone(),
-            pool: pool.clone(),
-        });
+        let proxy = match matches.get_one::<String>(GIT_URL) {
+            Some(url) => {
+                let git_ref = matches.get_one::<String>(GIT_REF).map(|r| r.as_str());
+                PipelineFileSystemProxy::git(url, git_ref)?
+            }
+            None => PipelineFileSystemProxy::Server {
+                config: cfg.clone(),
+                pool: pool.clone(),
+                cache: None,
+            },
+        };
+        let proxy = Arc::new(proxy);
 
         let logger = Logger::file_atom(cfg.clone(), &run_id)?;
-        let exec = Execution::pipeline_atom(pool.clone(), &run_id);
+        let journal = execution_journal(&cfg.local.db);
+        let exec = Execution::pipeline_atom(pool.clone(), &run_id, Some(journal));
+        let build_exec = exec.clone();
+        let signal_pool = pool.clone();
+        let signal_run_id = run_id.to_string();
         let context = Context::containers_atom(pool, &run_id);
 
         let (worker_tx, worker_rx) = channel(4096);
         let worker_tx = Arc::new(Some(worker_tx));
 
         System::new().block_on(async move {
+            // the signal task isn't joined since it only completes when a signal is received.
+            spawn(async move {
+                let stop = stop_on_signal(&signal_run_id, || {
+                    let mut conn = signal_pool.get()?;
+                    pipeline_runs::update_stopped(&mut conn, &signal_run_id, true)?;
+                    Ok(())
+                });
+                if let Err(e) = stop.await {
+                    error!("{e}");
+                }
+            });
+
             let socket_handle = spawn(async move {
                 if let Err(e) = connect_to_supervisor(socket_cfg, worker_rx).await {
                     error!("{e}");
@@ -108,23 +189,37 @@ impl BldCommand for WorkerCommand {
                     .run_id(&run_id)
                     .run_start_time(&start_date_time)
                     .config(cfg)
-                    .proxy(proxy)
+                    .proxy(proxy.clone())
                     .pipeline(&pipeline)
                     .execution(exec)
                     .logger(logger)
                     .environment(environment)
                     .variables(variables)
+                    .secrets(secrets)
+                    .dry_run(dry_run)
+                    .tracer(tracer)
+                    .trace_parent(trace_parent)
                     .context(context)
                     .ipc(worker_tx)
-                    .build()
+                    .build_matrix()
                     .await
                 {
                     Ok(runner) => {
-                        if let Err(e) = runner.run().await.await {
+                        if let Err(e) = runner.run().await {
                             error!("error with runner, {e}");
                         }
                     }
-                    Err(e) => error!("failed on building the runner, {e}"),
+                    Err(e) => {
+                        error!("failed on building the runner, {e}");
+                        {
+                            let mut exec = build_exec.lock().unwrap();
+                            let _ = exec.set_as_build_failed();
+                            exec.reconcile();
+                        }
+                        if let Err(e) = proxy.dispose() {
+                            error!("{e}");
+                        }
+                    }
                 }
             });
 
@@ -137,26 +232,64 @@ impl BldCommand for WorkerCommand {
                 _ => {}
             }
 
+            shutdown_tracer();
             Ok(())
         })
     }
 }
 
+/// Reads the secrets of the run that the supervisor passed through the environment and removes
+/// them from it, so that they are not inherited by the processes of the pipeline.
+fn worker_secrets() -> Result<HashMap<String, String>> {
+    match env::var(WORKER_SECRETS_ENV) {
+        Ok(secrets) => {
+            env::remove_var(WORKER_SECRETS_ENV);
+            serde_json::from_str(&secrets).map_err(|e| anyhow!("invalid secrets of run. {e}"))
+        }
+        Err(_) => Ok(HashMap::new()),
+    }
+}
+
+/// Stops the run when the supervisor sends a SIGTERM to the worker, so that the runner
+/// goes through its stop path and disposes the platform of the run before the worker exits.
+/// A ctrl-c in the terminal of the supervisor reaches the workers as well, since they are
+/// in its process group, so it's handled the same way.
+async fn stop_on_signal<F>(run_id: &str, stop: F) -> Result<()>
+where
+    F: FnOnce() -> Result<()>,
+{
+    tokio::select! {
+        result = terminated() => {
+            result?;
+            info!("received SIGTERM, stopping run {run_id}");
+        }
+        _ = ctrl_c() => info!("received SIGINT, stopping run {run_id}"),
+    }
+    stop()
+}
+
+/// Waits for a SIGTERM. The supervisor stops workers with taskkill on windows, so only
+/// ctrl-c is handled there.
+#[cfg(unix)]
+async fn terminated() -> Result<()> {
+    signal(SignalKind::terminate())?.recv().await;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn terminated() -> Result<()> {
+    std::future::pending().await
+}
+
 async fn connect_to_supervisor(
     config: Arc<BldConfig>,
     mut worker_rx: Receiver<WorkerMessages>,
 ) -> Result<()> {
-    let protocol = config.local.supervisor.ws_protocol();
-    let url = format!(
-        "{protocol}://{}:{}/ws-worker/",
-        config.local.supervisor.host, config.local.supervisor.port
-    );
+    let url = config.local.supervisor.ws_url("/ws-worker/");
 
     debug!("establishing web socket connection on {}", url);
 
-    let client = Client::builder()
-        .max_http_version(Version::HTTP_11)
-        .finish();
+    let client = ws_client(&config.local.supervisor);
     let client = client.ws(url).connect();
     let (_, framed) = client.await.map_err(|e| {
         error!("{e}");
@@ -257,4 +390,67 @@ mod tests {
             Some(3)
         )
     }
+
+    #[test]
+    fn cli_worker_socket_arg_accepts_value() {
+        let socket = "/var/run/bld/supervisor.sock";
+        let command = WorkerCommand::boxed().interface();
+        let matches = command.get_matches_from([
+            "worker",
+            "-p",
+            "mock_pipeline_name",
+            "-r",
+            "mock_run_id",
+            "--socket",
+            socket,
+        ]);
+
+        assert_eq!(matches.get_one::<String>(SOCKET), Some(&socket.to_string()))
+    }
+
+    #[test]
+    fn cli_worker_git_args_accept_values() {
+        let git_url = "https://github.com/user/repo.git";
+        let git_ref = "main";
+        let command = WorkerCommand::boxed().interface();
+        let matches = command.get_matches_from([
+            "worker",
+            "-p",
+            "mock_pipeline_name",
+            "-r",
+            "mock_run_id",
+            "--git-url",
+            git_url,
+            "--git-ref",
+            git_ref,
+        ]);
+
+        assert_eq!(
+            matches.get_one::<String>(GIT_URL),
+            Some(&git_url.to_string())
+        );
+        assert_eq!(
+            matches.get_one::<String>(GIT_REF),
+            Some(&git_ref.to_string())
+        )
+    }
+
+    #[test]
+    fn cli_worker_dry_run_arg_defaults_to_false() {
+        let command = WorkerCommand::boxed().interface();
+        let matches =
+            command.get_matches_from(["worker", "-p", "mock_pipeline_name", "-r", "mock_run_id"]);
+        assert!(!matches.get_flag(DRY_RUN));
+
+        let command = WorkerCommand::boxed().interface();
+        let matches = command.get_matches_from([
+            "worker",
+            "-p",
+            "mock_pipeline_name",
+            "-r",
+            "mock_run_id",
+            "--dry-run",
+        ]);
+        assert!(matches.get_flag(DRY_RUN))
+    }
 }
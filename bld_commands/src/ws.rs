@@ -0,0 +1,66 @@
+use actix_codec::Framed;
+use anyhow::{anyhow, Result};
+use awc::error::WsClientError;
+use awc::http::{StatusCode, Version};
+use awc::ws::Codec;
+use awc::{BoxedSocket, Client, Connector};
+use bld_config::definitions::LOCAL_MAX_LOG_LINE_LENGTH_LIMIT;
+use bld_utils::request;
+use openssl::ssl::{SslConnector, SslMethod};
+use std::collections::HashMap;
+
+/// The maximum size of the frames received from the web sockets of a server, which fits the
+/// longest log line that a server can be configured to send along with its prefix.
+pub const WS_MAX_FRAME_SIZE: usize = 2 * LOCAL_MAX_LOG_LINE_LENGTH_LIMIT;
+
+/// Creates an http 1.1 client for the web sockets of a server that also trusts the custom
+/// certificate authority of the server if one is configured.
+pub fn ws_client(url: &str) -> Result<Client> {
+    let client = match request::ca_file(url) {
+        Some(ca_file) => {
+            // the http version is limited through alpn since setting the max http version
+            // of the client replaces the tls connector with the default one.
+            let mut ssl = SslConnector::builder(SslMethod::tls())?;
+            ssl.set_ca_file(&ca_file)?;
+            ssl.set_alpn_protos(b"\x08http/1.1")?;
+            let connector = Connector::new().openssl(ssl.build());
+            Client::builder().connector(connector).finish()
+        }
+        None => Client::builder()
+            .max_http_version(Version::HTTP_11)
+            .finish(),
+    };
+    Ok(client)
+}
+
+async fn connect(
+    client: &Client,
+    url: &str,
+    headers: &HashMap<String, String>,
+) -> Result<Framed<BoxedSocket, Codec>, WsClientError> {
+    let mut request = client.ws(url).max_frame_size(WS_MAX_FRAME_SIZE);
+    for (key, value) in headers.iter() {
+        request = request.header(&key[..], &value[..]);
+    }
+    request.connect().await.map(|(_, framed)| framed)
+}
+
+/// Connects to a web socket of a server and when the server rejects the access token of the
+/// headers, connects once more with the refreshed access token of the server.
+pub async fn ws_connect(
+    url: &str,
+    headers: &HashMap<String, String>,
+) -> Result<Framed<BoxedSocket, Codec>> {
+    let client = ws_client(url)?;
+    let headers = match connect(&client, url, headers).await {
+        Err(WsClientError::InvalidResponseStatus(StatusCode::UNAUTHORIZED)) => {
+            request::refreshed_headers(url, headers)
+                .await?
+                .ok_or_else(|| anyhow!("the server rejected the connection as unauthorized"))?
+        }
+        result => return result.map_err(|e| anyhow!(e.to_string())),
+    };
+    connect(&client, url, &headers)
+        .await
+        .map_err(|e| anyhow!(e.to_string()))
+}
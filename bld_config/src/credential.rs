@@ -0,0 +1,137 @@
+use anyhow::{anyhow, bail, Result};
+use std::env;
+use yaml_rust::Yaml;
+
+/// The user name that is provided along with a git token when the credential doesn't define one.
+const GIT_TOKEN_DEFAULT_USERNAME: &str = "x-access-token";
+
+/// The secret of a credential. The token of git is read from an environment variable so that
+/// it isn't stored in the config file.
+#[derive(Debug)]
+pub enum BldCredentialKind {
+    SshKey {
+        key_file: String,
+    },
+    GitToken {
+        token_env: String,
+        username: String,
+        host: Option<String>,
+    },
+}
+
+/// A named credential of the server that pipelines reference in order to clone private
+/// repositories without relying on the credentials of the user that runs the server. Only the
+/// pipelines of its allowlist can use it.
+#[derive(Debug)]
+pub struct BldCredentialConfig {
+    pub name: String,
+    pub kind: BldCredentialKind,
+    pub allowed_pipelines: Vec<String>,
+}
+
+impl BldCredentialConfig {
+    pub fn load(yaml: &Yaml) -> Result<Self> {
+        let name = yaml["name"]
+            .as_str()
+            .filter(|n| !n.is_empty())
+            .ok_or_else(|| anyhow!("credential entry must define a name"))?
+            .to_string();
+        let entry = |key: &str| {
+            yaml[key]
+                .as_str()
+                .map(|v| v.to_string())
+                .ok_or_else(|| anyhow!("credential {name} must define a {key}"))
+        };
+        let kind = match yaml["type"].as_str() {
+            Some("ssh-key") => BldCredentialKind::SshKey {
+                key_file: entry("key-file")?,
+            },
+            Some("git-token") => BldCredentialKind::GitToken {
+                token_env: entry("token-env")?,
+                username: yaml["username"]
+                    .as_str()
+                    .unwrap_or(GIT_TOKEN_DEFAULT_USERNAME)
+                    .to_string(),
+                host: yaml["host"].as_str().map(|h| h.to_string()),
+            },
+            _ => bail!("credential {name} must be of type ssh-key or git-token"),
+        };
+        let allowed_pipelines = yaml["allowed-pipelines"]
+            .as_vec()
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter_map(|e| e.as_str().map(|e| e.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(Self {
+            name,
+            kind,
+            allowed_pipelines,
+        })
+    }
+
+    /// Checks if a pipeline is in the allowlist of the credential.
+    pub fn allows(&self, pipeline: &str) -> bool {
+        self.allowed_pipelines.iter().any(|p| p == pipeline)
+    }
+
+    pub fn kind_name(&self) -> &'static str {
+        match self.kind {
+            BldCredentialKind::SshKey { .. } => "ssh-key",
+            BldCredentialKind::GitToken { .. } => "git-token",
+        }
+    }
+
+    /// Reads the token of a git credential from its environment variable.
+    pub fn token(&self) -> Result<String> {
+        match &self.kind {
+            BldCredentialKind::GitToken { token_env, .. } => env::var(token_env).map_err(|_| {
+                anyhow!(
+                    "environment variable {token_env} with the token of credential {} is not set",
+                    self.name
+                )
+            }),
+            BldCredentialKind::SshKey { .. } => bail!("credential {} is not a token", self.name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn credential_requires_a_type_and_its_secret() {
+        let load =
+            |src: &str| BldCredentialConfig::load(&YamlLoader::load_from_str(src).unwrap()[0]);
+
+        let credential = load("name: deploy\ntype: ssh-key\nkey-file: /keys/deploy").unwrap();
+        assert_eq!(credential.kind_name(), "ssh-key");
+        let credential = load("name: github\ntype: git-token\ntoken-env: GITHUB_TOKEN").unwrap();
+        assert!(matches!(
+            credential.kind,
+            BldCredentialKind::GitToken { ref username, .. } if username == "x-access-token"
+        ));
+        assert!(load("name: deploy\ntype: ssh-key").is_err());
+        assert!(load("name: github\ntype: password\ntoken-env: GITHUB_TOKEN").is_err());
+        assert!(load("type: ssh-key\nkey-file: /keys/deploy").is_err());
+    }
+
+    #[test]
+    fn credential_is_allowed_only_for_the_pipelines_of_its_allowlist() {
+        let load =
+            |src: &str| BldCredentialConfig::load(&YamlLoader::load_from_str(src).unwrap()[0]);
+
+        let credential = load(
+            "name: deploy\ntype: ssh-key\nkey-file: /keys/deploy\nallowed-pipelines:\n- deploy.yaml",
+        )
+        .unwrap();
+        assert!(credential.allows("deploy.yaml"));
+        assert!(!credential.allows("build.yaml"));
+        let credential = load("name: deploy\ntype: ssh-key\nkey-file: /keys/deploy").unwrap();
+        assert!(!credential.allows("deploy.yaml"));
+    }
+}
@@ -1,12 +1,18 @@
 pub const VERSION: &str = "0.1";
+pub const GIT_COMMIT: &str = env!("BLD_GIT_COMMIT");
 pub const TOOL_DIR: &str = ".bld";
 pub const DB_NAME: &str = "bld-server.db";
+pub const DB_EXECUTION_JOURNAL: &str = "execution-journal";
 pub const PUSH: &str = "push";
 pub const GET: &str = "get";
+pub const TEMPLATE: &str = "template";
 pub const ENV_TOKEN: &str = "bld:env:";
 pub const VAR_TOKEN: &str = "bld:var:";
+pub const RUN_PROPS_TOKEN: &str = "bld:run:";
+pub const SECRET_TOKEN: &str = "bld:secret:";
 pub const RUN_PROPS_ID: &str = "bld:run:id";
 pub const RUN_PROPS_START_TIME: &str = "bld:run:start-time";
+pub const WORKER_SECRETS_ENV: &str = "BLD_WORKER_SECRETS";
 
 pub const TOOL_DEFAULT_PIPELINE: &str = "default";
 pub const TOOL_DEFAULT_PIPELINE_FILE: &str = "default.yaml";
@@ -16,20 +22,45 @@ pub const TOOL_DEFAULT_CONFIG_FILE: &str = "config.yaml";
 pub const LOCAL_SERVER_HOST: &str = "127.0.0.1";
 pub const LOCAL_SERVER_PORT: i64 = 6080;
 pub const LOCAL_SERVER_PIPELINES: &str = ".bld/server_pipelines";
+pub const LOCAL_SERVER_MAX_PIPELINE_SIZE: usize = 2_097_152;
+pub const LOCAL_SERVER_MAX_EXEC_FRAME: usize = 65_536;
+pub const LOCAL_SERVER_STALE_CONNECTION_SECONDS: u64 = 30;
+pub const LOCAL_SERVER_PIPELINE_CACHE_SIZE: usize = 256;
 pub const LOCAL_SUPERVISOR_HOST: &str = "127.0.0.1";
 pub const LOCAL_SUPERVISOR_PORT: i64 = 7080;
 pub const LOCAL_SUPERVISOR_WORKERS: i64 = 5;
+pub const LOCAL_SUPERVISOR_SHUTDOWN_GRACE_SECONDS: u64 = 10;
 pub const LOCAL_HA_MODE: bool = false;
+pub const LOCAL_ARTIFACTS_ZSTD_LEVEL: i32 = 3;
+pub const LOCAL_ARTIFACTS_CONCURRENT_TRANSFERS: usize = 2;
+pub const LOCAL_WS_MESSAGE_QUEUE_DEPTH: usize = 1000;
+pub const LOCAL_MAX_LOG_LINE_LENGTH: usize = 16_384;
+pub const LOCAL_MAX_LOG_LINE_LENGTH_LIMIT: usize = 1_048_576;
+pub const LOCAL_STEP_GRACEFUL_SHUTDOWN_SECS: u64 = 10;
+pub const LOCAL_SLOW_COMMAND_SECONDS: u64 = 300;
 pub const LOCAL_LOGS: &str = ".bld/logs";
 pub const LOCAL_DB: &str = ".bld/db";
 pub const LOCAL_DOCKER_URL: &str = "tcp://127.0.0.1:2376";
 pub const LOCAL_MACHINE_TMP_DIR: &str = ".bld/tmp";
+pub const LOCAL_LOG_REDACTION_REPLACEMENT: &str = "[REDACTED]";
+pub const LOCAL_SMTP_PORT: u16 = 587;
+pub const LOCAL_TELEMETRY_AGENT_ENDPOINT: &str = "127.0.0.1:6831";
+pub const LOCAL_TELEMETRY_SERVICE_NAME: &str = "bld";
+pub const LOCAL_STRICT_SUBSTITUTION: bool = false;
+pub const LOCAL_PREFIX_STEPS: bool = false;
 pub const REMOTE_SERVER_NAME: &str = "demo_server";
 pub const REMOTE_SERVER_HOST: &str = "127.0.0.1";
 pub const REMOTE_SERVER_PORT: i64 = 6080;
 pub const REMOTE_SERVER_OAUTH2: &str = ".bld/oauth2";
+pub const REMOTE_PIPELINE_HASHES: &str = ".bld/hashes";
+pub const REMOTE_RESPONSE_CACHE: &str = ".bld/cache";
+pub const REMOTE_SERVER_CACHE_TTL_SECONDS: u64 = 300;
+pub const HIST_DEFAULT_LIMIT: i64 = 100;
+pub const HIST_MAX_LIMIT: i64 = 1000;
+pub const HIST_PAGE_SIZE: i64 = 20;
 
-pub const DEFAULT_PIPELINE_CONTENT: &str = r"name: Default Pipeline
+pub const DEFAULT_PIPELINE_CONTENT: &str = r"version: 1
+name: Default Pipeline
 runs-on: machine
 steps: 
 - name: echo 
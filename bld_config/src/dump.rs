@@ -0,0 +1,450 @@
+use crate::{
+    Auth, AuthValidation, BldConfig, BldCredentialKind, BldLocalConfig, BldRegistryAuth,
+    BldRemoteConfig, BldTlsConfig,
+};
+use anyhow::{anyhow, Result};
+use yaml_rust::yaml::{Array, Hash};
+use yaml_rust::{Yaml, YamlEmitter};
+
+/// The text that secrets and, for redacted dumps, the values that identify a setup are replaced with.
+const DUMP_MASK: &str = "****";
+
+fn entry(hash: &mut Hash, key: &str, value: Yaml) {
+    hash.insert(Yaml::String(key.to_string()), value);
+}
+
+fn string(value: &str) -> Yaml {
+    Yaml::String(value.to_string())
+}
+
+fn strings(values: &[String]) -> Yaml {
+    Yaml::Array(values.iter().map(|v| string(v)).collect())
+}
+
+fn secret() -> Yaml {
+    string(DUMP_MASK)
+}
+
+struct Dump {
+    redacted: bool,
+}
+
+impl Dump {
+    /// A value that identifies the setup, such as a host or a url, which is masked in redacted dumps.
+    fn private(&self, value: &str) -> Yaml {
+        if self.redacted {
+            secret()
+        } else {
+            string(value)
+        }
+    }
+
+    fn tls(&self, hash: &mut Hash, tls: &Option<BldTlsConfig>) {
+        if let Some(tls) = tls {
+            let mut tls_hash = Hash::new();
+            entry(&mut tls_hash, "cert-chain", string(&tls.cert_chain));
+            entry(&mut tls_hash, "private-key", string(&tls.private_key));
+            entry(hash, "tls", Yaml::Hash(tls_hash));
+        }
+    }
+
+    fn local(&self, local: &BldLocalConfig) -> Yaml {
+        let mut hash = Hash::new();
+        entry(&mut hash, "ha-mode", Yaml::Boolean(local.ha_mode));
+        if let Some(node_id) = local.node_id {
+            entry(&mut hash, "node-id", Yaml::Integer(node_id as i64));
+        }
+        if local.ha_secret.is_some() {
+            entry(&mut hash, "ha-secret", secret());
+        }
+
+        let mut server = Hash::new();
+        entry(&mut server, "host", self.private(&local.server.host));
+        entry(&mut server, "port", Yaml::Integer(local.server.port));
+        entry(&mut server, "pipelines", string(&local.server.pipelines));
+        entry(
+            &mut server,
+            "max-pipeline-size",
+            Yaml::Integer(local.server.max_pipeline_size as i64),
+        );
+        entry(
+            &mut server,
+            "max-exec-frame",
+            Yaml::Integer(local.server.max_exec_frame as i64),
+        );
+        entry(
+            &mut server,
+            "stale-connection-seconds",
+            Yaml::Integer(local.server.stale_connection_seconds as i64),
+        );
+        entry(
+            &mut server,
+            "pipeline-cache-size",
+            Yaml::Integer(local.server.pipeline_cache_size as i64),
+        );
+        let admins = local
+            .server
+            .admins
+            .iter()
+            .map(|a| self.private(a))
+            .collect();
+        entry(&mut server, "admins", Yaml::Array(admins));
+        entry(
+            &mut server,
+            "strict-pipelines",
+            Yaml::Boolean(local.server.strict_pipelines),
+        );
+        self.tls(&mut server, &local.server.tls);
+        entry(&mut hash, "server", Yaml::Hash(server));
+
+        let mut supervisor = Hash::new();
+        entry(
+            &mut supervisor,
+            "host",
+            self.private(&local.supervisor.host),
+        );
+        entry(
+            &mut supervisor,
+            "port",
+            Yaml::Integer(local.supervisor.port),
+        );
+        entry(
+            &mut supervisor,
+            "workers",
+            Yaml::Integer(local.supervisor.workers),
+        );
+        entry(
+            &mut supervisor,
+            "shutdown-grace-seconds",
+            Yaml::Integer(local.supervisor.shutdown_grace_seconds as i64),
+        );
+        if let Some(socket) = &local.supervisor.socket {
+            entry(&mut supervisor, "socket", string(socket));
+        }
+        self.tls(&mut supervisor, &local.supervisor.tls);
+        entry(&mut hash, "supervisor", Yaml::Hash(supervisor));
+
+        entry(&mut hash, "logs", string(&local.logs));
+        entry(&mut hash, "db", string(&local.db));
+        let mut auth = Hash::new();
+        match &local.auth {
+            AuthValidation::OAuth2(url) => {
+                entry(&mut auth, "method", string("oauth2"));
+                entry(&mut auth, "validation-url", self.private(url));
+            }
+            AuthValidation::Ldap => entry(&mut auth, "method", string("ldap")),
+            AuthValidation::None => entry(&mut auth, "method", string("none")),
+        }
+        entry(&mut hash, "auth", Yaml::Hash(auth));
+        entry(&mut hash, "docker-url", self.private(&local.docker_url));
+        if let Some(score) = local.container_min_oom_score {
+            entry(&mut hash, "container-min-oom-score", Yaml::Integer(score));
+        }
+        entry(
+            &mut hash,
+            "container-oom-kill-disable-allowlist",
+            strings(&local.container_oom_kill_disable_allowlist),
+        );
+        entry(
+            &mut hash,
+            "container-volumes-allowlist",
+            strings(&local.container_volumes_allowlist),
+        );
+        let mut container_resources = Hash::new();
+        if !local.container_resources.default.is_empty() {
+            entry(
+                &mut container_resources,
+                "default",
+                local.container_resources.default.to_yaml(),
+            );
+        }
+        if !local.container_resources.max.is_empty() {
+            entry(
+                &mut container_resources,
+                "max",
+                local.container_resources.max.to_yaml(),
+            );
+        }
+        if !container_resources.is_empty() {
+            entry(
+                &mut hash,
+                "container-resources",
+                Yaml::Hash(container_resources),
+            );
+        }
+        entry(
+            &mut hash,
+            "artifacts-zstd-level",
+            Yaml::Integer(local.artifacts_zstd_level as i64),
+        );
+        entry(
+            &mut hash,
+            "artifacts-concurrent-transfers",
+            Yaml::Integer(local.artifacts_concurrent_transfers as i64),
+        );
+        let mut aliases: Vec<(&String, &String)> = local.pipeline_aliases.iter().collect();
+        aliases.sort();
+        let mut aliases_hash = Hash::new();
+        for (alias, name) in aliases {
+            entry(&mut aliases_hash, alias, string(name));
+        }
+        entry(&mut hash, "pipeline-aliases", Yaml::Hash(aliases_hash));
+        entry(
+            &mut hash,
+            "ws-message-queue-depth",
+            Yaml::Integer(local.ws_message_queue_depth as i64),
+        );
+        entry(
+            &mut hash,
+            "max-log-line-length",
+            Yaml::Integer(local.max_log_line_length as i64),
+        );
+        entry(
+            &mut hash,
+            "long-log-lines",
+            Yaml::String(local.long_log_lines.to_string()),
+        );
+        entry(
+            &mut hash,
+            "step-graceful-shutdown-secs",
+            Yaml::Integer(local.step_graceful_shutdown_secs as i64),
+        );
+        entry(
+            &mut hash,
+            "slow-command-seconds",
+            Yaml::Integer(local.slow_command_seconds as i64),
+        );
+        let rules = local
+            .log_redaction
+            .iter()
+            .map(|rule| {
+                let mut rule_hash = Hash::new();
+                entry(&mut rule_hash, "name", string(&rule.name));
+                entry(&mut rule_hash, "pattern", string(rule.pattern.as_str()));
+                entry(&mut rule_hash, "replacement", string(&rule.replacement));
+                Yaml::Hash(rule_hash)
+            })
+            .collect();
+        entry(&mut hash, "log-redaction", Yaml::Array(rules));
+        entry(
+            &mut hash,
+            "log-redaction-allowlist",
+            strings(&local.log_redaction_allowlist),
+        );
+        if let Some(smtp) = &local.smtp {
+            let mut smtp_hash = Hash::new();
+            entry(&mut smtp_hash, "host", self.private(&smtp.host));
+            entry(&mut smtp_hash, "port", Yaml::Integer(smtp.port as i64));
+            if let Some(username) = &smtp.username {
+                entry(&mut smtp_hash, "username", self.private(username));
+            }
+            if smtp.password.is_some() {
+                entry(&mut smtp_hash, "password", secret());
+            }
+            entry(&mut smtp_hash, "from", self.private(&smtp.from));
+            entry(&mut hash, "smtp", Yaml::Hash(smtp_hash));
+        }
+        if let Some(telemetry) = &local.telemetry {
+            let mut telemetry_hash = Hash::new();
+            entry(
+                &mut telemetry_hash,
+                "agent-endpoint",
+                self.private(&telemetry.agent_endpoint),
+            );
+            entry(
+                &mut telemetry_hash,
+                "service-name",
+                string(&telemetry.service_name),
+            );
+            entry(&mut hash, "telemetry", Yaml::Hash(telemetry_hash));
+        }
+        let registries: Array = local
+            .registries
+            .iter()
+            .map(|registry| {
+                let mut registry_hash = Hash::new();
+                if let Some(name) = &registry.name {
+                    entry(&mut registry_hash, "name", string(name));
+                }
+                entry(&mut registry_hash, "url", self.private(&registry.url));
+                // only the names of the environment variables with the credentials are in the config.
+                match &registry.auth {
+                    BldRegistryAuth::Password {
+                        username,
+                        password_env,
+                    } => {
+                        entry(&mut registry_hash, "username", self.private(username));
+                        entry(&mut registry_hash, "password-env", string(password_env));
+                    }
+                    BldRegistryAuth::Token { token_env } => {
+                        entry(&mut registry_hash, "token-env", string(token_env));
+                    }
+                }
+                Yaml::Hash(registry_hash)
+            })
+            .collect();
+        entry(&mut hash, "registries", Yaml::Array(registries));
+        let credentials = local
+            .credentials
+            .iter()
+            .map(|credential| {
+                let mut credential_hash = Hash::new();
+                entry(&mut credential_hash, "name", string(&credential.name));
+                entry(&mut credential_hash, "type", string(credential.kind_name()));
+                match &credential.kind {
+                    BldCredentialKind::SshKey { key_file } => {
+                        entry(&mut credential_hash, "key-file", string(key_file));
+                    }
+                    BldCredentialKind::GitToken {
+                        token_env,
+                        username,
+                        host,
+                    } => {
+                        entry(&mut credential_hash, "token-env", string(token_env));
+                        entry(&mut credential_hash, "username", self.private(username));
+                        if let Some(host) = host {
+                            entry(&mut credential_hash, "host", self.private(host));
+                        }
+                    }
+                }
+                entry(
+                    &mut credential_hash,
+                    "allowed-pipelines",
+                    strings(&credential.allowed_pipelines),
+                );
+                Yaml::Hash(credential_hash)
+            })
+            .collect();
+        entry(&mut hash, "credentials", Yaml::Array(credentials));
+        entry(
+            &mut hash,
+            "strict-substitution",
+            Yaml::Boolean(local.strict_substitution),
+        );
+        entry(&mut hash, "prefix-steps", Yaml::Boolean(local.prefix_steps));
+        if let Some(max) = local.default_max_log_lines {
+            entry(
+                &mut hash,
+                "default-max-log-lines",
+                Yaml::Integer(max as i64),
+            );
+        }
+        Yaml::Hash(hash)
+    }
+
+    fn remote(&self, remote: &BldRemoteConfig) -> Yaml {
+        let servers = remote
+            .servers
+            .iter()
+            .map(|server| {
+                let mut hash = Hash::new();
+                entry(&mut hash, "server", string(&server.name));
+                entry(&mut hash, "host", self.private(&server.host));
+                entry(&mut hash, "port", Yaml::Integer(server.port));
+                entry(&mut hash, "tls", Yaml::Boolean(server.tls));
+                if let Some(ca_file) = &server.ca_file {
+                    entry(&mut hash, "ca-file", string(ca_file));
+                }
+                if let Some(node_id) = server.node_id {
+                    entry(&mut hash, "node-id", Yaml::Integer(node_id as i64));
+                }
+                match &server.auth {
+                    Auth::OAuth2(info) => {
+                        let mut auth = Hash::new();
+                        entry(&mut auth, "method", string("oauth2"));
+                        entry(&mut auth, "auth-url", self.private(info.auth_url.as_str()));
+                        entry(
+                            &mut auth,
+                            "token-url",
+                            self.private(info.token_url.as_str()),
+                        );
+                        entry(&mut auth, "client-id", self.private(&info.client_id));
+                        entry(&mut auth, "client-secret", secret());
+                        let scopes = info.scopes.iter().map(|s| string(s)).collect();
+                        entry(&mut auth, "scopes", Yaml::Array(scopes));
+                        entry(&mut hash, "auth", Yaml::Hash(auth));
+                    }
+                    Auth::Ldap => {
+                        let mut auth = Hash::new();
+                        entry(&mut auth, "method", string("ldap"));
+                        entry(&mut hash, "auth", Yaml::Hash(auth));
+                    }
+                    Auth::ApiKey(_) => {
+                        let mut auth = Hash::new();
+                        entry(&mut auth, "method", string("api-key"));
+                        entry(&mut auth, "key", secret());
+                        entry(&mut hash, "auth", Yaml::Hash(auth));
+                    }
+                    Auth::None => {}
+                }
+                if let Some(name) = &server.same_auth_as {
+                    entry(&mut hash, "same-auth-as", string(name));
+                }
+                entry(
+                    &mut hash,
+                    "cache-ttl-seconds",
+                    Yaml::Integer(server.cache_ttl_seconds as i64),
+                );
+                Yaml::Hash(hash)
+            })
+            .collect();
+        Yaml::Array(servers)
+    }
+}
+
+impl BldConfig {
+    /// Dumps the effective configuration, with the defaults applied, as yaml. Secrets are always
+    /// masked, while a redacted dump also masks the hosts, urls and user names of the setup.
+    pub fn dump(&self, redacted: bool) -> Result<String> {
+        let dump = Dump { redacted };
+        let mut hash = Hash::new();
+        entry(&mut hash, "local", dump.local(&self.local));
+        entry(&mut hash, "remote", dump.remote(&self.remote));
+        let mut content = String::new();
+        YamlEmitter::new(&mut content)
+            .dump(&Yaml::Hash(hash))
+            .map_err(|e| anyhow!("unable to dump the config. {e:?}"))?;
+        content.push('\n');
+        Ok(content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BldRemoteServerConfig, BldSmtpConfig};
+
+    #[test]
+    fn config_dump_masks_secrets_and_redacted_values() {
+        let mut config = BldConfig::default();
+        config.local.smtp = Some(BldSmtpConfig {
+            host: "smtp.example.com".to_string(),
+            port: 587,
+            username: Some("bld".to_string()),
+            password: Some("smtp-password".to_string()),
+            from: "bld@example.com".to_string(),
+        });
+        config.remote.servers.push(BldRemoteServerConfig {
+            name: "ci".to_string(),
+            host: "ci.example.com".to_string(),
+            port: 6080,
+            tls: false,
+            ca_file: None,
+            node_id: None,
+            auth: Auth::ApiKey("bld-api-key".to_string()),
+            same_auth_as: None,
+            cache_ttl_seconds: 300,
+        });
+
+        let dump = config.dump(false).unwrap();
+        assert!(dump.contains("host: smtp.example.com"));
+        assert!(dump.contains("password: \"****\""));
+        assert!(!dump.contains("smtp-password"));
+        assert!(dump.contains("method: api-key"));
+        assert!(!dump.contains("bld-api-key"));
+
+        let dump = config.dump(true).unwrap();
+        assert!(!dump.contains("smtp.example.com"));
+        assert!(!dump.contains("smtp-password"));
+    }
+}
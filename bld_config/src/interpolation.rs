@@ -0,0 +1,117 @@
+use anyhow::{anyhow, bail, Result};
+use yaml_rust::yaml::Hash;
+use yaml_rust::Yaml;
+
+/// Expands the `${NAME}` references to environment variables in the string values of the
+/// config, failing with the name of the first variable that isn't set. A literal `${` is
+/// written as `$${`.
+pub fn expand_env(yaml: Yaml) -> Result<Yaml> {
+    expand_yaml(yaml, &|name| std::env::var(name).ok())
+}
+
+fn expand_yaml(yaml: Yaml, lookup: &dyn Fn(&str) -> Option<String>) -> Result<Yaml> {
+    let expanded = match yaml {
+        Yaml::String(value) => Yaml::String(expand_str(&value, lookup)?),
+        Yaml::Array(entries) => Yaml::Array(
+            entries
+                .into_iter()
+                .map(|e| expand_yaml(e, lookup))
+                .collect::<Result<Vec<Yaml>>>()?,
+        ),
+        Yaml::Hash(entries) => {
+            let mut hash = Hash::new();
+            for (key, value) in entries {
+                hash.insert(key, expand_yaml(value, lookup)?);
+            }
+            Yaml::Hash(hash)
+        }
+        yaml => yaml,
+    };
+    Ok(expanded)
+}
+
+fn expand_str(value: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(index) = rest.find('$') {
+        expanded.push_str(&rest[..index]);
+        rest = &rest[index..];
+        if let Some(escaped) = rest.strip_prefix("$${") {
+            expanded.push_str("${");
+            rest = escaped;
+        } else if let Some(reference) = rest.strip_prefix("${") {
+            let end = reference.find('}').ok_or_else(|| {
+                anyhow!("unterminated reference to an environment variable in the config")
+            })?;
+            let name = &reference[..end];
+            let is_valid = name
+                .chars()
+                .next()
+                .map(|c| c.is_ascii_alphabetic() || c == '_')
+                .unwrap_or(false)
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !is_valid {
+                bail!("invalid environment variable name {name:?} in the config");
+            }
+            let resolved = lookup(name).ok_or_else(|| {
+                anyhow!("environment variable {name} referenced in the config is not set")
+            })?;
+            expanded.push_str(&resolved);
+            rest = &reference[end + 1..];
+        } else {
+            expanded.push('$');
+            rest = &rest[1..];
+        }
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yaml_rust::YamlLoader;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "BLD_HOST" => Some("ci.example.com".to_string()),
+            "BLD_TOKEN" => Some("s3cr$t".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn references_are_expanded_in_the_string_values() {
+        let yaml = YamlLoader::load_from_str(
+            r"
+remote:
+- server: ${BLD_HOST}
+  port: 6080
+  auth:
+    token: ${BLD_TOKEN}
+  note: costs $5, literal $${BLD_HOST} and ${BLD_HOST}:6080
+",
+        )
+        .unwrap()
+        .remove(0);
+
+        let expanded = expand_yaml(yaml, &lookup).unwrap();
+        let server = &expanded["remote"][0];
+
+        assert_eq!(server["server"].as_str(), Some("ci.example.com"));
+        assert_eq!(server["port"].as_i64(), Some(6080));
+        assert_eq!(server["auth"]["token"].as_str(), Some("s3cr$t"));
+        assert_eq!(
+            server["note"].as_str(),
+            Some("costs $5, literal ${BLD_HOST} and ci.example.com:6080")
+        );
+    }
+
+    #[test]
+    fn unset_and_invalid_references_are_errors() {
+        let error = expand_str("${BLD_MISSING}", &lookup).unwrap_err();
+        assert!(error.to_string().contains("BLD_MISSING"));
+        assert!(expand_str("${BLD_HOST", &lookup).is_err());
+        assert!(expand_str("${1BLD}", &lookup).is_err());
+    }
+}
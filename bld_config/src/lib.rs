@@ -1,20 +1,36 @@
 mod auth;
+mod credential;
 pub mod definitions;
+mod dump;
 mod errors;
+mod interpolation;
 mod local;
+mod log_lines;
 mod path;
+mod redaction;
+mod registry;
 mod remote;
+mod resources;
 mod server;
+mod smtp;
 mod supervisor;
+mod telemetry;
 mod tls;
 
 pub use auth::*;
+pub use credential::*;
 pub use errors::*;
 pub use local::*;
+pub use log_lines::*;
 pub use path::*;
+pub use redaction::*;
+pub use registry::*;
 pub use remote::*;
+pub use resources::*;
 pub use server::*;
+pub use smtp::*;
 pub use supervisor::*;
+pub use telemetry::*;
 pub use tls::*;
 
 use anyhow::Result;
@@ -29,17 +45,22 @@ pub struct BldConfig {
 }
 
 impl BldConfig {
-    pub fn load() -> Result<Self> {
-        let path = path![
+    /// The path of the config file in the current directory, which may not exist.
+    pub fn path() -> Result<PathBuf> {
+        Ok(path![
             std::env::current_dir()?,
             definitions::TOOL_DIR,
             format!("{}.yaml", definitions::TOOL_DEFAULT_CONFIG)
-        ];
+        ])
+    }
+
+    pub fn load() -> Result<Self> {
+        let path = Self::path()?;
         debug!("loading config file from: {}", &path.display());
         match std::fs::read_to_string(&path) {
             Ok(content) => {
-                let yaml = YamlLoader::load_from_str(&content)?;
-                let yaml = &yaml[0];
+                let mut yaml = YamlLoader::load_from_str(&content)?;
+                let yaml = &interpolation::expand_env(yaml.remove(0))?;
 
                 Ok(Self {
                     local: BldLocalConfig::load(yaml)?,
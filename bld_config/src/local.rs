@@ -1,6 +1,11 @@
-use crate::{definitions, AuthValidation, BldLocalServerConfig, BldLocalSupervisorConfig};
-use anyhow::{anyhow, Result};
+use crate::{
+    definitions, AuthValidation, BldCredentialConfig, BldCredentialKind, BldLocalServerConfig,
+    BldLocalSupervisorConfig, BldRegistryAuth, BldRegistryConfig, BldSmtpConfig,
+    BldTelemetryConfig, ContainerResourceLimits, LogRedactionRule, LongLogLines,
+};
+use anyhow::{anyhow, bail, Result};
 use async_raft::NodeId;
+use std::collections::HashMap;
 use tracing::debug;
 use yaml_rust::Yaml;
 
@@ -8,12 +13,34 @@ use yaml_rust::Yaml;
 pub struct BldLocalConfig {
     pub ha_mode: bool,
     pub node_id: Option<NodeId>,
+    pub ha_secret: Option<String>,
     pub server: BldLocalServerConfig,
     pub supervisor: BldLocalSupervisorConfig,
     pub logs: String,
     pub db: String,
     pub auth: AuthValidation,
     pub docker_url: String,
+    pub container_min_oom_score: Option<i64>,
+    pub container_oom_kill_disable_allowlist: Vec<String>,
+    pub container_volumes_allowlist: Vec<String>,
+    pub container_resources: ContainerResourceLimits,
+    pub artifacts_zstd_level: i32,
+    pub pipeline_aliases: HashMap<String, String>,
+    pub artifacts_concurrent_transfers: usize,
+    pub ws_message_queue_depth: usize,
+    pub max_log_line_length: usize,
+    pub long_log_lines: LongLogLines,
+    pub step_graceful_shutdown_secs: u64,
+    pub slow_command_seconds: u64,
+    pub log_redaction: Vec<LogRedactionRule>,
+    pub log_redaction_allowlist: Vec<String>,
+    pub smtp: Option<BldSmtpConfig>,
+    pub telemetry: Option<BldTelemetryConfig>,
+    pub registries: Vec<BldRegistryConfig>,
+    pub credentials: Vec<BldCredentialConfig>,
+    pub strict_substitution: bool,
+    pub prefix_steps: bool,
+    pub default_max_log_lines: Option<usize>,
 }
 
 impl BldLocalConfig {
@@ -23,6 +50,10 @@ impl BldLocalConfig {
             .as_bool()
             .unwrap_or(definitions::LOCAL_HA_MODE);
         let node_id = local_yaml["node-id"].as_i64().map(|n| n as NodeId);
+        let ha_secret = local_yaml["ha-secret"].as_str().map(|s| s.to_string());
+        if ha_mode && ha_secret.as_deref().map(str::is_empty).unwrap_or(true) {
+            bail!("ha-secret is required when ha-mode is enabled");
+        }
         let server = BldLocalServerConfig::load(&local_yaml["server"])?;
         let supervisor = BldLocalSupervisorConfig::load(&local_yaml["supervisor"])?;
         let logs = local_yaml["logs"]
@@ -37,23 +68,201 @@ impl BldLocalConfig {
             .as_str()
             .unwrap_or(definitions::LOCAL_DOCKER_URL)
             .to_string();
+        let container_min_oom_score = local_yaml["container-min-oom-score"].as_i64();
+        let container_oom_kill_disable_allowlist = local_yaml
+            ["container-oom-kill-disable-allowlist"]
+            .as_vec()
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter_map(|e| e.as_str().map(|e| e.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let container_volumes_allowlist = local_yaml["container-volumes-allowlist"]
+            .as_vec()
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter_map(|e| e.as_str().map(|e| e.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let container_resources =
+            ContainerResourceLimits::load(&local_yaml["container-resources"])?;
+        let artifacts_zstd_level = local_yaml["artifacts-zstd-level"]
+            .as_i64()
+            .unwrap_or(definitions::LOCAL_ARTIFACTS_ZSTD_LEVEL as i64);
+        if !(1..=19).contains(&artifacts_zstd_level) {
+            return Err(anyhow!("artifacts-zstd-level should be between 1 and 19"));
+        }
+        let artifacts_zstd_level = artifacts_zstd_level as i32;
+        let pipeline_aliases = local_yaml["pipeline-aliases"]
+            .as_hash()
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter_map(|(k, v)| Some((k.as_str()?.to_string(), v.as_str()?.to_string())))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let artifacts_concurrent_transfers =
+            match local_yaml["artifacts-concurrent-transfers"].as_i64() {
+                Some(limit) if limit > 0 => limit as usize,
+                Some(_) => {
+                    return Err(anyhow!(
+                        "artifacts-concurrent-transfers should be a positive number"
+                    ))
+                }
+                None => definitions::LOCAL_ARTIFACTS_CONCURRENT_TRANSFERS,
+            };
+        let ws_message_queue_depth = match local_yaml["ws-message-queue-depth"].as_i64() {
+            Some(depth) if depth > 0 => depth as usize,
+            Some(_) => {
+                return Err(anyhow!(
+                    "ws-message-queue-depth should be a positive number"
+                ))
+            }
+            None => definitions::LOCAL_WS_MESSAGE_QUEUE_DEPTH,
+        };
+        let max_log_line_length = match local_yaml["max-log-line-length"].as_i64() {
+            Some(max)
+                if max > 0 && max as usize <= definitions::LOCAL_MAX_LOG_LINE_LENGTH_LIMIT =>
+            {
+                max as usize
+            }
+            Some(_) => {
+                return Err(anyhow!(
+                    "max-log-line-length should be a positive number up to {}",
+                    definitions::LOCAL_MAX_LOG_LINE_LENGTH_LIMIT
+                ))
+            }
+            None => definitions::LOCAL_MAX_LOG_LINE_LENGTH,
+        };
+        let long_log_lines = LongLogLines::load(&local_yaml["long-log-lines"])?;
+        let step_graceful_shutdown_secs = match local_yaml["step-graceful-shutdown-secs"].as_i64() {
+            Some(secs) if secs >= 0 => secs as u64,
+            Some(_) => {
+                return Err(anyhow!(
+                    "step-graceful-shutdown-secs should not be a negative number"
+                ))
+            }
+            None => definitions::LOCAL_STEP_GRACEFUL_SHUTDOWN_SECS,
+        };
+        let slow_command_seconds = match local_yaml["slow-command-seconds"].as_i64() {
+            Some(secs) if secs > 0 => secs as u64,
+            Some(_) => return Err(anyhow!("slow-command-seconds should be a positive number")),
+            None => definitions::LOCAL_SLOW_COMMAND_SECONDS,
+        };
+        let log_redaction = local_yaml["log-redaction"]
+            .as_vec()
+            .map(|entries| entries.iter().map(LogRedactionRule::load).collect())
+            .unwrap_or_else(|| Ok(vec![]))?;
+        let log_redaction_allowlist = local_yaml["log-redaction-allowlist"]
+            .as_vec()
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter_map(|e| e.as_str().map(|e| e.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let smtp = BldSmtpConfig::load(&local_yaml["smtp"])?;
+        let telemetry = BldTelemetryConfig::load(&local_yaml["telemetry"])?;
+        let registries = local_yaml["registries"]
+            .as_vec()
+            .map(|entries| entries.iter().map(BldRegistryConfig::load).collect())
+            .unwrap_or_else(|| Ok(vec![]))?;
+        let credentials = local_yaml["credentials"]
+            .as_vec()
+            .map(|entries| entries.iter().map(BldCredentialConfig::load).collect())
+            .unwrap_or_else(|| Ok(vec![]))?;
+        let strict_substitution = local_yaml["strict-substitution"]
+            .as_bool()
+            .unwrap_or(definitions::LOCAL_STRICT_SUBSTITUTION);
+        let prefix_steps = local_yaml["prefix-steps"]
+            .as_bool()
+            .unwrap_or(definitions::LOCAL_PREFIX_STEPS);
+        let default_max_log_lines = match local_yaml["default-max-log-lines"].as_i64() {
+            Some(max) if max > 0 => Some(max as usize),
+            Some(_) => return Err(anyhow!("default-max-log-lines should be a positive number")),
+            None => None,
+        };
         let auth = BldLocalConfig::auth_load(local_yaml)?;
         let instance = Self {
             ha_mode,
             node_id,
+            ha_secret,
             server,
             supervisor,
             logs,
             db,
             auth,
             docker_url,
+            container_min_oom_score,
+            container_oom_kill_disable_allowlist,
+            container_volumes_allowlist,
+            container_resources,
+            artifacts_zstd_level,
+            pipeline_aliases,
+            artifacts_concurrent_transfers,
+            ws_message_queue_depth,
+            max_log_line_length,
+            long_log_lines,
+            step_graceful_shutdown_secs,
+            slow_command_seconds,
+            log_redaction,
+            log_redaction_allowlist,
+            smtp,
+            telemetry,
+            registries,
+            credentials,
+            strict_substitution,
+            prefix_steps,
+            default_max_log_lines,
         };
         instance.debug_info();
         Ok(instance)
     }
 
+    /// Loads the auth method of the server. A server without an auth section, or with `auth: none`,
+    /// accepts every request, which is meant for local development.
+    /// Follows the aliases of a pipeline name to the name of the pipeline that it refers to,
+    /// returning the name itself when it isn't an alias.
+    pub fn canonical_pipeline<'a>(&'a self, name: &'a str) -> &'a str {
+        let mut name = name;
+        for _ in 0..self.pipeline_aliases.len() {
+            match self.pipeline_aliases.get(name) {
+                Some(target) => name = target,
+                None => break,
+            }
+        }
+        name
+    }
+
+    /// Checks that no chain of pipeline aliases leads back to one of its own names.
+    pub fn check_pipeline_aliases(&self) -> Result<()> {
+        let mut aliases: Vec<&String> = self.pipeline_aliases.keys().collect();
+        aliases.sort();
+        for alias in aliases {
+            let mut chain = vec![alias.as_str()];
+            while let Some(target) = self.pipeline_aliases.get(chain[chain.len() - 1]) {
+                let circular = chain.contains(&target.as_str());
+                chain.push(target);
+                if circular {
+                    bail!("circular pipeline aliases {}", chain.join(" -> "));
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn auth_load(yaml: &Yaml) -> Result<AuthValidation> {
-        let auth_validation = match yaml["auth"]["method"].as_str() {
+        let method = match &yaml["auth"] {
+            Yaml::String(method) => Some(method.as_str()),
+            auth => auth["method"].as_str(),
+        };
+        let auth_validation = match method {
             Some("ldap") => AuthValidation::Ldap,
             Some("oauth2") => AuthValidation::OAuth2(
                 yaml["auth"]["validation-url"]
@@ -61,7 +270,8 @@ impl BldLocalConfig {
                     .ok_or_else(|| anyhow!("no validation url found for auth in config"))?
                     .to_string(),
             ),
-            _ => AuthValidation::None,
+            Some("none") | None => AuthValidation::None,
+            Some(method) => bail!("unknown auth method {method}, expected oauth2, ldap or none"),
         };
         Ok(auth_validation)
     }
@@ -73,6 +283,24 @@ impl BldLocalConfig {
         debug!("server > host: {}", self.server.host);
         debug!("server > port: {}", self.server.port);
         debug!("server > pipelines: {}", self.server.pipelines);
+        debug!(
+            "server > max-pipeline-size: {}",
+            self.server.max_pipeline_size
+        );
+        debug!("server > max-exec-frame: {}", self.server.max_exec_frame);
+        debug!(
+            "server > stale-connection-seconds: {}",
+            self.server.stale_connection_seconds
+        );
+        debug!(
+            "server > pipeline-cache-size: {}",
+            self.server.pipeline_cache_size
+        );
+        debug!("server > admins: {:?}", self.server.admins);
+        debug!(
+            "server > strict-pipelines: {}",
+            self.server.strict_pipelines
+        );
         if let Some(tls) = &self.server.tls {
             debug!("server > tls > cert-chain: {}", tls.cert_chain);
             debug!("server > tls > private-key: {}", tls.private_key);
@@ -80,6 +308,13 @@ impl BldLocalConfig {
         debug!("supervisor > host {}", self.supervisor.host);
         debug!("supervisor > port {}", self.supervisor.port);
         debug!("supervisor > workers {}", self.supervisor.workers);
+        debug!(
+            "supervisor > shutdown-grace-seconds {}",
+            self.supervisor.shutdown_grace_seconds
+        );
+        if let Some(socket) = &self.supervisor.socket {
+            debug!("supervisor > socket {}", socket);
+        }
         if let Some(tls) = &self.supervisor.tls {
             debug!("supervisor > tls > cert-chain: {}", tls.cert_chain);
             debug!("supervisor > tls > private-key: {}", tls.private_key);
@@ -87,6 +322,93 @@ impl BldLocalConfig {
         debug!("logs: {}", self.logs);
         debug!("db: {}", self.db);
         debug!("docker-url: {}", self.docker_url);
+        if let Some(score) = self.container_min_oom_score {
+            debug!("container-min-oom-score: {}", score);
+        }
+        debug!(
+            "container-oom-kill-disable-allowlist: {:?}",
+            self.container_oom_kill_disable_allowlist
+        );
+        debug!(
+            "container-volumes-allowlist: {:?}",
+            self.container_volumes_allowlist
+        );
+        if !self.container_resources.default.is_empty() {
+            debug!(
+                "container-resources > default: {}",
+                self.container_resources.default
+            );
+        }
+        if !self.container_resources.max.is_empty() {
+            debug!(
+                "container-resources > max: {}",
+                self.container_resources.max
+            );
+        }
+        debug!("artifacts-zstd-level: {}", self.artifacts_zstd_level);
+        debug!("pipeline-aliases: {:?}", self.pipeline_aliases);
+        debug!(
+            "artifacts-concurrent-transfers: {}",
+            self.artifacts_concurrent_transfers
+        );
+        debug!("ws-message-queue-depth: {}", self.ws_message_queue_depth);
+        debug!("max-log-line-length: {}", self.max_log_line_length);
+        debug!("long-log-lines: {}", self.long_log_lines);
+        debug!(
+            "step-graceful-shutdown-secs: {}",
+            self.step_graceful_shutdown_secs
+        );
+        debug!("slow-command-seconds: {}", self.slow_command_seconds);
+        for rule in self.log_redaction.iter() {
+            debug!("log-redaction > {}: {}", rule.name, rule.pattern);
+        }
+        debug!(
+            "log-redaction-allowlist: {:?}",
+            self.log_redaction_allowlist
+        );
+        if let Some(smtp) = &self.smtp {
+            debug!("smtp > host: {}", smtp.host);
+            debug!("smtp > port: {}", smtp.port);
+            debug!("smtp > from: {}", smtp.from);
+        }
+        if let Some(telemetry) = &self.telemetry {
+            debug!("telemetry > agent-endpoint: {}", telemetry.agent_endpoint);
+            debug!("telemetry > service-name: {}", telemetry.service_name);
+        }
+        for registry in self.registries.iter() {
+            match &registry.auth {
+                BldRegistryAuth::Password {
+                    username,
+                    password_env,
+                } => debug!(
+                    "registries > {}: {} ({})",
+                    registry.label(),
+                    username,
+                    password_env
+                ),
+                BldRegistryAuth::Token { token_env } => {
+                    debug!("registries > {}: token ({})", registry.label(), token_env)
+                }
+            }
+        }
+        for credential in self.credentials.iter() {
+            match &credential.kind {
+                BldCredentialKind::SshKey { key_file } => {
+                    debug!("credentials > {}: ssh-key ({})", credential.name, key_file)
+                }
+                BldCredentialKind::GitToken { token_env, .. } => {
+                    debug!(
+                        "credentials > {}: git-token ({})",
+                        credential.name, token_env
+                    )
+                }
+            }
+        }
+        debug!("strict-substitution: {}", self.strict_substitution);
+        debug!("prefix-steps: {}", self.prefix_steps);
+        if let Some(max) = self.default_max_log_lines {
+            debug!("default-max-log-lines: {}", max);
+        }
         if let AuthValidation::OAuth2(url) = &self.auth {
             debug!("auth > method: oauth2");
             debug!("auth > validation-url: {}", url);
@@ -99,12 +421,84 @@ impl Default for BldLocalConfig {
         Self {
             ha_mode: definitions::LOCAL_HA_MODE,
             node_id: None,
+            ha_secret: None,
             server: BldLocalServerConfig::default(),
             supervisor: BldLocalSupervisorConfig::default(),
             logs: definitions::LOCAL_LOGS.to_string(),
             db: definitions::LOCAL_DB.to_string(),
             auth: AuthValidation::None,
             docker_url: definitions::LOCAL_DOCKER_URL.to_string(),
+            container_min_oom_score: None,
+            container_oom_kill_disable_allowlist: vec![],
+            container_volumes_allowlist: vec![],
+            container_resources: ContainerResourceLimits::default(),
+            artifacts_zstd_level: definitions::LOCAL_ARTIFACTS_ZSTD_LEVEL,
+            pipeline_aliases: HashMap::new(),
+            artifacts_concurrent_transfers: definitions::LOCAL_ARTIFACTS_CONCURRENT_TRANSFERS,
+            ws_message_queue_depth: definitions::LOCAL_WS_MESSAGE_QUEUE_DEPTH,
+            max_log_line_length: definitions::LOCAL_MAX_LOG_LINE_LENGTH,
+            long_log_lines: LongLogLines::default(),
+            step_graceful_shutdown_secs: definitions::LOCAL_STEP_GRACEFUL_SHUTDOWN_SECS,
+            slow_command_seconds: definitions::LOCAL_SLOW_COMMAND_SECONDS,
+            log_redaction: vec![],
+            log_redaction_allowlist: vec![],
+            smtp: None,
+            telemetry: None,
+            registries: vec![],
+            credentials: vec![],
+            strict_substitution: definitions::LOCAL_STRICT_SUBSTITUTION,
+            prefix_steps: definitions::LOCAL_PREFIX_STEPS,
+            default_max_log_lines: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(aliases: &[(&str, &str)]) -> BldLocalConfig {
+        BldLocalConfig {
+            pipeline_aliases: aliases
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ..Default::default()
         }
     }
+
+    #[test]
+    fn pipeline_aliases_are_followed_to_the_canonical_name() {
+        let config = config(&[("build-main", "build"), ("ci", "build-main")]);
+        assert!(config.check_pipeline_aliases().is_ok());
+        assert_eq!(config.canonical_pipeline("ci"), "build");
+        assert_eq!(config.canonical_pipeline("build-main"), "build");
+        assert_eq!(config.canonical_pipeline("deploy"), "deploy");
+    }
+
+    #[test]
+    fn circular_pipeline_aliases_are_rejected() {
+        let config = config(&[("a", "b"), ("b", "c"), ("c", "a")]);
+        let error = config.check_pipeline_aliases().unwrap_err().to_string();
+        assert_eq!(error, "circular pipeline aliases a -> b -> c -> a");
+    }
+
+    #[test]
+    fn ha_mode_requires_a_secret() {
+        let yaml = |content: &str| {
+            yaml_rust::YamlLoader::load_from_str(content)
+                .unwrap()
+                .remove(0)
+        };
+        let error = BldLocalConfig::load(&yaml("local:\n  ha-mode: true\n  node-id: 1"))
+            .unwrap_err()
+            .to_string();
+        assert_eq!(error, "ha-secret is required when ha-mode is enabled");
+
+        let config = BldLocalConfig::load(&yaml(
+            "local:\n  ha-mode: true\n  node-id: 1\n  ha-secret: cluster-secret",
+        ))
+        .unwrap();
+        assert_eq!(config.ha_secret.as_deref(), Some("cluster-secret"));
+    }
 }
@@ -0,0 +1,33 @@
+use anyhow::{anyhow, Result};
+use std::fmt::{self, Display, Formatter};
+use yaml_rust::Yaml;
+
+/// What happens to the part of a log line after the maximum line length. A split line is
+/// continued in new lines while a truncated line records the number of bytes that were dropped.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LongLogLines {
+    #[default]
+    Split,
+    Truncate,
+}
+
+impl LongLogLines {
+    pub fn load(yaml: &Yaml) -> Result<Self> {
+        match yaml.as_str() {
+            Some("split") | None => Ok(Self::Split),
+            Some("truncate") => Ok(Self::Truncate),
+            Some(policy) => Err(anyhow!(
+                "invalid long-log-lines policy {policy}, expected split or truncate"
+            )),
+        }
+    }
+}
+
+impl Display for LongLogLines {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Split => write!(f, "split"),
+            Self::Truncate => write!(f, "truncate"),
+        }
+    }
+}
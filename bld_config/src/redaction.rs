@@ -0,0 +1,69 @@
+use anyhow::{anyhow, Result};
+use regex::{Captures, Regex};
+use yaml_rust::Yaml;
+
+/// A rule of the log redaction section of the server config, with its pattern compiled once
+/// when the config is loaded.
+#[derive(Debug, Clone)]
+pub struct LogRedactionRule {
+    pub name: String,
+    pub pattern: Regex,
+    pub replacement: String,
+}
+
+impl LogRedactionRule {
+    pub fn load(yaml: &Yaml) -> Result<Self> {
+        let name = yaml["name"]
+            .as_str()
+            .ok_or_else(|| anyhow!("no name found for log redaction rule"))?;
+        let pattern = yaml["pattern"]
+            .as_str()
+            .ok_or_else(|| anyhow!("no pattern found for log redaction rule {name}"))?;
+        let pattern = Regex::new(pattern)
+            .map_err(|e| anyhow!("invalid pattern for log redaction rule {name}. {e}"))?;
+        let replacement = yaml["replacement"]
+            .as_str()
+            .unwrap_or(crate::definitions::LOCAL_LOG_REDACTION_REPLACEMENT);
+        Ok(Self {
+            name: name.to_string(),
+            pattern,
+            replacement: replacement.to_string(),
+        })
+    }
+
+    /// Replaces the matches of the rule in the text and returns the result along with the
+    /// number of matches. The replacement can reference capture groups of the pattern.
+    pub fn apply(&self, text: &str) -> (String, usize) {
+        let mut count = 0;
+        let redacted = self.pattern.replace_all(text, |caps: &Captures| {
+            count += 1;
+            let mut replacement = String::new();
+            caps.expand(&self.replacement, &mut replacement);
+            replacement
+        });
+        (redacted.to_string(), count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn log_redaction_rule_replaces_all_matches() {
+        let yaml = YamlLoader::load_from_str(
+            r"
+name: card
+pattern: '\d{4}-\d{4}-\d{4}-(\d{4})'
+replacement: '****-$1'
+",
+        )
+        .unwrap();
+        let rule = LogRedactionRule::load(&yaml[0]).unwrap();
+        let (text, count) = rule.apply("paid with 1234-5678-9012-3456 and 1111-2222-3333-4444");
+
+        assert_eq!(text, "paid with ****-3456 and ****-4444");
+        assert_eq!(count, 2);
+    }
+}
@@ -0,0 +1,129 @@
+use anyhow::{anyhow, bail, Result};
+use std::env;
+use yaml_rust::Yaml;
+
+const DOCKER_HUB_REGISTRY: &str = "docker.io";
+
+/// The way that a registry is authenticated with. The password or token is read from an
+/// environment variable so that it isn't stored in the config file.
+#[derive(Debug)]
+pub enum BldRegistryAuth {
+    Password {
+        username: String,
+        password_env: String,
+    },
+    Token {
+        token_env: String,
+    },
+}
+
+/// A private docker registry along with the credentials used to pull images from it.
+#[derive(Debug)]
+pub struct BldRegistryConfig {
+    pub name: Option<String>,
+    pub url: String,
+    pub auth: BldRegistryAuth,
+}
+
+impl BldRegistryConfig {
+    pub fn load(yaml: &Yaml) -> Result<Self> {
+        let name = yaml["name"].as_str().map(|n| n.to_string());
+        let url = yaml["url"]
+            .as_str()
+            .ok_or_else(|| anyhow!("registry entry must define a url"))?
+            .to_string();
+        let username = yaml["username"].as_str();
+        let password_env = yaml["password-env"].as_str();
+        let token_env = yaml["token-env"].as_str();
+        let auth = match (username, password_env, token_env) {
+            (Some(username), Some(password_env), None) => BldRegistryAuth::Password {
+                username: username.to_string(),
+                password_env: password_env.to_string(),
+            },
+            (_, None, Some(token_env)) => BldRegistryAuth::Token {
+                token_env: token_env.to_string(),
+            },
+            (None, Some(_), None) => bail!("registry {url} must define a username"),
+            _ => bail!("registry {url} must define either a password-env or a token-env"),
+        };
+        Ok(Self { name, url, auth })
+    }
+
+    /// The name of the registry if it has one, otherwise its url.
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.url)
+    }
+
+    /// The address of the registry as it appears in the names of its images.
+    pub fn address(&self) -> &str {
+        let url = self
+            .url
+            .strip_prefix("https://")
+            .or_else(|| self.url.strip_prefix("http://"))
+            .unwrap_or(&self.url);
+        url.trim_end_matches('/')
+    }
+
+    /// Reads the password or the token of the registry from its environment variable.
+    pub fn secret(&self) -> Result<String> {
+        let (name, kind) = match &self.auth {
+            BldRegistryAuth::Password { password_env, .. } => (password_env, "password"),
+            BldRegistryAuth::Token { token_env } => (token_env, "token"),
+        };
+        env::var(name).map_err(|_| {
+            anyhow!(
+                "environment variable {name} with the {kind} of registry {} is not set",
+                self.label()
+            )
+        })
+    }
+}
+
+/// The registry of an image, which is the first component of its name when it looks like a
+/// host, otherwise the image is pulled from docker hub.
+pub fn image_registry(image: &str) -> &str {
+    match image.split_once('/') {
+        Some((host, _)) if host.contains('.') || host.contains(':') || host == "localhost" => host,
+        _ => DOCKER_HUB_REGISTRY,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn image_registry_is_the_host_of_the_image() {
+        assert_eq!(image_registry("ubuntu"), "docker.io");
+        assert_eq!(image_registry("library/ubuntu:22.04"), "docker.io");
+        assert_eq!(
+            image_registry("registry.example.com:5000/team/app:1.0"),
+            "registry.example.com:5000"
+        );
+        assert_eq!(image_registry("localhost/app"), "localhost");
+
+        let registry = BldRegistryConfig {
+            name: None,
+            url: "https://registry.example.com:5000/".to_string(),
+            auth: BldRegistryAuth::Password {
+                username: "bld".to_string(),
+                password_env: "REGISTRY_PASSWORD".to_string(),
+            },
+        };
+        assert_eq!(registry.address(), "registry.example.com:5000");
+        assert_eq!(registry.label(), "https://registry.example.com:5000/");
+    }
+
+    #[test]
+    fn registry_requires_a_password_or_a_token() {
+        let load = |src: &str| BldRegistryConfig::load(&YamlLoader::load_from_str(src).unwrap()[0]);
+
+        let registry = load("name: ghcr\nurl: ghcr.io\ntoken-env: GHCR_TOKEN").unwrap();
+        assert_eq!(registry.label(), "ghcr");
+        assert!(matches!(registry.auth, BldRegistryAuth::Token { .. }));
+        assert!(load("url: ghcr.io\npassword-env: PASSWORD").is_err());
+        assert!(load("url: ghcr.io\nusername: bld").is_err());
+        assert!(load("url: ghcr.io\nusername: bld\npassword-env: A\ntoken-env: B").is_err());
+    }
+}
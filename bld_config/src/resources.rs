@@ -0,0 +1,217 @@
+use anyhow::{anyhow, bail, Result};
+use std::fmt::{self, Display, Formatter};
+use yaml_rust::yaml::Hash;
+use yaml_rust::Yaml;
+
+const MEMORY: &str = "memory";
+const CPUS: &str = "cpus";
+const CPU_SHARES: &str = "cpu-shares";
+const PIDS: &str = "pids";
+
+/// Resource limits of a docker container, with the memory in bytes. A missing limit leaves
+/// the container with the default of the docker daemon.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ContainerResources {
+    pub memory: Option<u64>,
+    pub cpus: Option<f64>,
+    pub cpu_shares: Option<u64>,
+    pub pids: Option<u64>,
+}
+
+impl ContainerResources {
+    /// Loads the limits of a section with a memory, cpus, cpu-shares and pids entry. The memory
+    /// is either a number of bytes or a number with a k, m or g suffix, such as 512m.
+    pub fn load(yaml: &Yaml, section: &str) -> Result<Self> {
+        let hash = match yaml {
+            Yaml::BadValue => return Ok(Self::default()),
+            Yaml::Hash(hash) => hash,
+            _ => bail!(
+                "{section} should contain the {MEMORY}, {CPUS}, {CPU_SHARES} or {PIDS} limits"
+            ),
+        };
+        for key in hash.keys() {
+            match key.as_str() {
+                Some(MEMORY | CPUS | CPU_SHARES | PIDS) => {}
+                _ => bail!(
+                    "unknown entry {} in {section}, expected {MEMORY}, {CPUS}, {CPU_SHARES} or {PIDS}",
+                    key.as_str().unwrap_or_default()
+                ),
+            }
+        }
+        let memory = match &yaml[MEMORY] {
+            Yaml::BadValue => None,
+            Yaml::Integer(bytes) if *bytes > 0 => Some(*bytes as u64),
+            Yaml::String(memory) => Some(Self::memory(memory).ok_or_else(|| {
+                anyhow!("invalid {MEMORY} {memory} in {section}, expected bytes or a size such as 512m or 2g")
+            })?),
+            _ => bail!("{MEMORY} in {section} should be a positive number of bytes or a size such as 512m"),
+        };
+        let cpus = match &yaml[CPUS] {
+            Yaml::BadValue => None,
+            Yaml::Integer(cpus) if *cpus > 0 => Some(*cpus as f64),
+            Yaml::Real(cpus) => match cpus.parse::<f64>() {
+                Ok(cpus) if cpus > 0.0 => Some(cpus),
+                _ => bail!("{CPUS} in {section} should be a positive number"),
+            },
+            _ => bail!("{CPUS} in {section} should be a positive number"),
+        };
+        Ok(Self {
+            memory,
+            cpus,
+            cpu_shares: Self::positive(&yaml[CPU_SHARES], CPU_SHARES, section)?,
+            pids: Self::positive(&yaml[PIDS], PIDS, section)?,
+        })
+    }
+
+    fn positive(yaml: &Yaml, name: &str, section: &str) -> Result<Option<u64>> {
+        match yaml {
+            Yaml::BadValue => Ok(None),
+            Yaml::Integer(value) if *value > 0 => Ok(Some(*value as u64)),
+            _ => bail!("{name} in {section} should be a positive number"),
+        }
+    }
+
+    fn memory(memory: &str) -> Option<u64> {
+        let memory = memory.trim().to_lowercase();
+        let (number, unit) = match memory.find(|c: char| !c.is_ascii_digit()) {
+            Some(index) => memory.split_at(index),
+            None => (memory.as_str(), ""),
+        };
+        let multiplier = match unit.trim_end_matches('b') {
+            "" => 1,
+            "k" => 1024,
+            "m" => 1024 * 1024,
+            "g" => 1024 * 1024 * 1024,
+            _ => return None,
+        };
+        number
+            .parse::<u64>()
+            .ok()
+            .filter(|n| *n > 0)
+            .and_then(|n| n.checked_mul(multiplier))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The limits of a container based on the ones of its pipeline, with the defaults of the
+    /// server used for the missing ones and every limit lowered to the maximum of the server.
+    pub fn within(&self, default: &Self, max: &Self) -> Self {
+        fn limit<T: PartialOrd + Copy>(
+            value: Option<T>,
+            default: Option<T>,
+            max: Option<T>,
+        ) -> Option<T> {
+            match (value.or(default), max) {
+                (Some(value), Some(max)) if value > max => Some(max),
+                (None, max) => max,
+                (value, _) => value,
+            }
+        }
+        Self {
+            memory: limit(self.memory, default.memory, max.memory),
+            cpus: limit(self.cpus, default.cpus, max.cpus),
+            cpu_shares: limit(self.cpu_shares, default.cpu_shares, max.cpu_shares),
+            pids: limit(self.pids, default.pids, max.pids),
+        }
+    }
+
+    /// The limits as yaml, with the memory in bytes.
+    pub fn to_yaml(&self) -> Yaml {
+        let mut hash = Hash::new();
+        let mut insert = |name: &str, value: Yaml| {
+            hash.insert(Yaml::String(name.to_string()), value);
+        };
+        if let Some(memory) = self.memory {
+            insert(MEMORY, Yaml::Integer(memory as i64));
+        }
+        if let Some(cpus) = self.cpus {
+            insert(CPUS, Yaml::Real(cpus.to_string()));
+        }
+        if let Some(cpu_shares) = self.cpu_shares {
+            insert(CPU_SHARES, Yaml::Integer(cpu_shares as i64));
+        }
+        if let Some(pids) = self.pids {
+            insert(PIDS, Yaml::Integer(pids as i64));
+        }
+        Yaml::Hash(hash)
+    }
+}
+
+// The cpus are always a positive number when loaded, so they are never compared as NaN.
+impl Eq for ContainerResources {}
+
+impl Display for ContainerResources {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut limits = vec![];
+        if let Some(memory) = self.memory {
+            limits.push(format!("{MEMORY}: {memory} bytes"));
+        }
+        if let Some(cpus) = self.cpus {
+            limits.push(format!("{CPUS}: {cpus}"));
+        }
+        if let Some(cpu_shares) = self.cpu_shares {
+            limits.push(format!("{CPU_SHARES}: {cpu_shares}"));
+        }
+        if let Some(pids) = self.pids {
+            limits.push(format!("{PIDS}: {pids}"));
+        }
+        write!(f, "{}", limits.join(", "))
+    }
+}
+
+/// The resource limits of the containers of a server, with the defaults applied to the
+/// pipelines that don't set a limit and the maximum that no pipeline can exceed.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ContainerResourceLimits {
+    pub default: ContainerResources,
+    pub max: ContainerResources,
+}
+
+impl ContainerResourceLimits {
+    pub fn load(yaml: &Yaml) -> Result<Self> {
+        Ok(Self {
+            default: ContainerResources::load(&yaml["default"], "container-resources > default")?,
+            max: ContainerResources::load(&yaml["max"], "container-resources > max")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yaml_rust::YamlLoader;
+
+    fn load(src: &str) -> Result<ContainerResources> {
+        let yaml = YamlLoader::load_from_str(src).unwrap();
+        ContainerResources::load(&yaml[0], "resources")
+    }
+
+    #[test]
+    fn container_resources_are_parsed_and_limited() {
+        let resources = load("memory: 512m\ncpus: 1.5\ncpu-shares: 512\npids: 100").unwrap();
+        assert_eq!(resources.memory, Some(512 * 1024 * 1024));
+        assert_eq!(resources.cpus, Some(1.5));
+        assert_eq!(resources.cpu_shares, Some(512));
+        assert_eq!(resources.pids, Some(100));
+        assert_eq!(load("memory: 2G").unwrap().memory, Some(2 << 30));
+        assert_eq!(load("memory: 1048576").unwrap().memory, Some(1 << 20));
+
+        assert!(load("memory: lots").is_err());
+        assert!(load("memory: 0").is_err());
+        assert!(load("cpus: -1").is_err());
+        assert!(load("pids: many").is_err());
+        assert!(load("memroy: 1g").is_err());
+
+        let default = load("memory: 1g\npids: 200").unwrap();
+        let max = load("memory: 2g\ncpus: 2").unwrap();
+        let limited = load("memory: 4g\ncpus: 1").unwrap().within(&default, &max);
+        assert_eq!(limited.memory, Some(2 << 30));
+        assert_eq!(limited.cpus, Some(1.0));
+        assert_eq!(limited.pids, Some(200));
+        let limited = ContainerResources::default().within(&default, &max);
+        assert_eq!(limited.memory, Some(1 << 30));
+        assert_eq!(limited.cpus, Some(2.0));
+    }
+}
@@ -1,6 +1,6 @@
 use crate::definitions;
 use crate::{Auth, BldTlsConfig, OAuth2Info};
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use async_raft::NodeId;
 use yaml_rust::Yaml;
 
@@ -10,6 +10,12 @@ pub struct BldLocalServerConfig {
     pub port: i64,
     pub tls: Option<BldTlsConfig>,
     pub pipelines: String,
+    pub max_pipeline_size: usize,
+    pub max_exec_frame: usize,
+    pub stale_connection_seconds: u64,
+    pub pipeline_cache_size: usize,
+    pub admins: Vec<String>,
+    pub strict_pipelines: bool,
 }
 
 impl BldLocalServerConfig {
@@ -26,14 +32,66 @@ impl BldLocalServerConfig {
             .as_str()
             .unwrap_or(definitions::LOCAL_SERVER_PIPELINES)
             .to_string();
+        let max_pipeline_size = Self::size(
+            &yaml["max-pipeline-size"],
+            "max-pipeline-size",
+            definitions::LOCAL_SERVER_MAX_PIPELINE_SIZE,
+        )?;
+        let max_exec_frame = Self::size(
+            &yaml["max-exec-frame"],
+            "max-exec-frame",
+            definitions::LOCAL_SERVER_MAX_EXEC_FRAME,
+        )?;
+        let stale_connection_seconds = match &yaml["stale-connection-seconds"] {
+            Yaml::BadValue => definitions::LOCAL_SERVER_STALE_CONNECTION_SECONDS,
+            Yaml::Integer(seconds) if *seconds > 0 => *seconds as u64,
+            _ => {
+                return Err(anyhow!(
+                    "stale-connection-seconds should be a positive number"
+                ))
+            }
+        };
+        let pipeline_cache_size = match &yaml["pipeline-cache-size"] {
+            Yaml::BadValue => definitions::LOCAL_SERVER_PIPELINE_CACHE_SIZE,
+            Yaml::Integer(size) if *size > 0 => *size as usize,
+            _ => return Err(anyhow!("pipeline-cache-size should be a positive number")),
+        };
+        let admins = yaml["admins"]
+            .as_vec()
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter_map(|e| e.as_str().map(|e| e.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let strict_pipelines = match &yaml["strict-pipelines"] {
+            Yaml::BadValue => false,
+            Yaml::Boolean(strict) => *strict,
+            _ => return Err(anyhow!("strict-pipelines should be true or false")),
+        };
         Ok(Self {
             host,
             port,
             tls,
             pipelines,
+            max_pipeline_size,
+            max_exec_frame,
+            stale_connection_seconds,
+            pipeline_cache_size,
+            admins,
+            strict_pipelines,
         })
     }
 
+    fn size(yaml: &Yaml, name: &str, default: usize) -> Result<usize> {
+        match yaml.as_i64() {
+            Some(size) if size > 0 => Ok(size as usize),
+            Some(_) => Err(anyhow!("{name} should be a positive number of bytes")),
+            None => Ok(default),
+        }
+    }
+
     pub fn http_protocol(&self) -> String {
         if self.tls.is_some() {
             "https".to_string()
@@ -58,6 +116,12 @@ impl Default for BldLocalServerConfig {
             port: definitions::LOCAL_SERVER_PORT,
             tls: None,
             pipelines: definitions::LOCAL_SERVER_PIPELINES.to_string(),
+            max_pipeline_size: definitions::LOCAL_SERVER_MAX_PIPELINE_SIZE,
+            max_exec_frame: definitions::LOCAL_SERVER_MAX_EXEC_FRAME,
+            stale_connection_seconds: definitions::LOCAL_SERVER_STALE_CONNECTION_SECONDS,
+            pipeline_cache_size: definitions::LOCAL_SERVER_PIPELINE_CACHE_SIZE,
+            admins: vec![],
+            strict_pipelines: false,
         }
     }
 }
@@ -68,9 +132,11 @@ pub struct BldRemoteServerConfig {
     pub host: String,
     pub port: i64,
     pub tls: bool,
+    pub ca_file: Option<String>,
     pub node_id: Option<NodeId>,
     pub auth: Auth,
     pub same_auth_as: Option<String>,
+    pub cache_ttl_seconds: u64,
 }
 
 impl BldRemoteServerConfig {
@@ -88,23 +154,37 @@ impl BldRemoteServerConfig {
             .ok_or_else(|| anyhow!("Server entry must define a port"))?;
         let tls = yaml["tls"].as_bool().unwrap_or(false);
         let protocol = Self::http_protocol_internal(tls);
+        let ca_file = yaml["ca-file"].as_str().map(|f| f.to_string());
         let node_id = yaml["node-id"].as_i64().map(|n| n as NodeId);
         let auth = match yaml["auth"]["method"].as_str() {
             Some("ldap") => Auth::Ldap,
+            Some("api-key") => Auth::ApiKey(
+                yaml["auth"]["key"]
+                    .as_str()
+                    .ok_or_else(|| anyhow!("No api key found in config"))?
+                    .to_string(),
+            ),
             Some("oauth2") => {
                 Auth::OAuth2(OAuth2Info::load(&host, port, &protocol, &yaml["auth"])?)
             }
             _ => Auth::None,
         };
         let same_auth_as = yaml["same-auth-as"].as_str().map(|s| s.to_string());
+        let cache_ttl_seconds = match yaml["cache-ttl-seconds"].as_i64() {
+            Some(seconds) if seconds >= 0 => seconds as u64,
+            Some(_) => bail!("cache-ttl-seconds should not be a negative number"),
+            None => definitions::REMOTE_SERVER_CACHE_TTL_SECONDS,
+        };
         Ok(Self {
             name,
             host,
             port,
             tls,
+            ca_file,
             node_id,
             auth,
             same_auth_as,
+            cache_ttl_seconds,
         })
     }
 
@@ -0,0 +1,42 @@
+use crate::definitions;
+use anyhow::{anyhow, Result};
+use yaml_rust::Yaml;
+
+/// The smtp server used to send the email notifications of pipelines.
+#[derive(Debug)]
+pub struct BldSmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+}
+
+impl BldSmtpConfig {
+    pub fn load(yaml: &Yaml) -> Result<Option<Self>> {
+        if yaml.is_badvalue() {
+            return Ok(None);
+        }
+        let host = yaml["host"]
+            .as_str()
+            .ok_or_else(|| anyhow!("smtp host not provided"))?
+            .to_string();
+        let port = yaml["port"]
+            .as_i64()
+            .unwrap_or(definitions::LOCAL_SMTP_PORT as i64);
+        let port = u16::try_from(port).map_err(|_| anyhow!("invalid smtp port {port}"))?;
+        let username = yaml["username"].as_str().map(|u| u.to_string());
+        let password = yaml["password"].as_str().map(|p| p.to_string());
+        let from = yaml["from"]
+            .as_str()
+            .ok_or_else(|| anyhow!("smtp from address not provided"))?
+            .to_string();
+        Ok(Some(Self {
+            host,
+            port,
+            username,
+            password,
+            from,
+        }))
+    }
+}
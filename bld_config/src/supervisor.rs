@@ -1,6 +1,6 @@
 use crate::definitions;
 use crate::BldTlsConfig;
-use anyhow::Result;
+use anyhow::{bail, Result};
 use yaml_rust::Yaml;
 
 #[derive(Debug)]
@@ -9,6 +9,8 @@ pub struct BldLocalSupervisorConfig {
     pub port: i64,
     pub tls: Option<BldTlsConfig>,
     pub workers: i64,
+    pub socket: Option<String>,
+    pub shutdown_grace_seconds: u64,
 }
 
 impl BldLocalSupervisorConfig {
@@ -21,14 +23,27 @@ impl BldLocalSupervisorConfig {
             .as_i64()
             .unwrap_or(definitions::LOCAL_SUPERVISOR_PORT);
         let tls = BldTlsConfig::load(&yaml["tls"])?;
-        let workers = yaml["workers"]
-            .as_i64()
-            .unwrap_or(definitions::LOCAL_SUPERVISOR_WORKERS);
+        let workers = match yaml["workers"].as_i64() {
+            Some(workers) if workers > 0 => workers,
+            Some(_) => bail!("supervisor workers should be a positive number"),
+            None => definitions::LOCAL_SUPERVISOR_WORKERS,
+        };
+        let socket = yaml["socket"].as_str().map(|s| s.to_string());
+        if socket.is_some() && cfg!(not(unix)) {
+            bail!("the supervisor socket is supported only on unix systems");
+        }
+        let shutdown_grace_seconds = match &yaml["shutdown-grace-seconds"] {
+            Yaml::BadValue => definitions::LOCAL_SUPERVISOR_SHUTDOWN_GRACE_SECONDS,
+            Yaml::Integer(seconds) if *seconds >= 0 => *seconds as u64,
+            _ => bail!("supervisor shutdown-grace-seconds should be a non negative number"),
+        };
         Ok(Self {
             host,
             port,
             tls,
             workers,
+            socket,
+            shutdown_grace_seconds,
         })
     }
 
@@ -47,6 +62,15 @@ impl BldLocalSupervisorConfig {
             "ws".to_string()
         }
     }
+
+    /// Creates the url of a supervisor web socket. When a unix socket is configured the
+    /// host of the url is only used for the http handshake so localhost is used instead.
+    pub fn ws_url(&self, path: &str) -> String {
+        match &self.socket {
+            Some(_) => format!("ws://localhost{path}"),
+            None => format!("{}://{}:{}{path}", self.ws_protocol(), self.host, self.port),
+        }
+    }
 }
 
 impl Default for BldLocalSupervisorConfig {
@@ -56,6 +80,8 @@ impl Default for BldLocalSupervisorConfig {
             port: definitions::LOCAL_SUPERVISOR_PORT,
             tls: None,
             workers: definitions::LOCAL_SUPERVISOR_WORKERS,
+            socket: None,
+            shutdown_grace_seconds: definitions::LOCAL_SUPERVISOR_SHUTDOWN_GRACE_SECONDS,
         }
     }
 }
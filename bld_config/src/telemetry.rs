@@ -0,0 +1,34 @@
+use crate::definitions;
+use anyhow::{bail, Result};
+use yaml_rust::Yaml;
+
+/// The jaeger agent that the traces of the pipeline runs are exported to. Tracing is
+/// disabled when the section is missing from the config.
+#[derive(Debug)]
+pub struct BldTelemetryConfig {
+    pub agent_endpoint: String,
+    pub service_name: String,
+}
+
+impl BldTelemetryConfig {
+    pub fn load(yaml: &Yaml) -> Result<Option<Self>> {
+        if yaml.is_badvalue() {
+            return Ok(None);
+        }
+        let agent_endpoint = yaml["agent-endpoint"]
+            .as_str()
+            .unwrap_or(definitions::LOCAL_TELEMETRY_AGENT_ENDPOINT)
+            .to_string();
+        let service_name = yaml["service-name"]
+            .as_str()
+            .unwrap_or(definitions::LOCAL_TELEMETRY_SERVICE_NAME)
+            .to_string();
+        if service_name.is_empty() {
+            bail!("telemetry service-name should not be empty");
+        }
+        Ok(Some(Self {
+            agent_endpoint,
+            service_name,
+        }))
+    }
+}
@@ -1,4 +1,5 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
+use std::path::Path;
 use yaml_rust::Yaml;
 
 #[derive(Debug)]
@@ -25,4 +26,18 @@ impl BldTlsConfig {
             private_key,
         }))
     }
+
+    /// Checks that the certificate chain and private key files exist so that a misconfigured
+    /// path is reported by name instead of with the error of the tls library.
+    pub fn check_files(&self) -> Result<()> {
+        for (kind, file) in [
+            ("certificate chain", &self.cert_chain),
+            ("private key", &self.private_key),
+        ] {
+            if !Path::new(file).is_file() {
+                bail!("{kind} file {file} not found");
+            }
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+create table pipeline_run_checkpoints (
+  id text primary key not null,
+  run_id text not null,
+  name text not null,
+  date_created text default current_timestamp not null,
+  foreign key(run_id) references pipeline_runs(id)
+);
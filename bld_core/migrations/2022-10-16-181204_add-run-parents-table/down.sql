@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+drop table pipeline_run_parents;
+
+drop trigger if exists pipeline_runs_after_update;
+
+create trigger pipeline_runs_after_update
+    after update on pipeline_runs
+begin
+    update pipeline_runs set end_date_time = current_timestamp where id = new.id and state = 'finished';
+end;
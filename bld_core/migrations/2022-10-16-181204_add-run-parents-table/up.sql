@@ -0,0 +1,18 @@
+-- Your SQL goes here
+create table pipeline_run_parents (
+  id text primary key not null,
+  child_run_id text not null,
+  parent_run_id text not null,
+  step_name text,
+  date_created text default current_timestamp not null,
+  foreign key(child_run_id) references pipeline_runs(id),
+  foreign key(parent_run_id) references pipeline_runs(id)
+);
+
+drop trigger if exists pipeline_runs_after_update;
+
+create trigger pipeline_runs_after_update
+    after update on pipeline_runs
+begin
+    update pipeline_runs set end_date_time = current_timestamp where id = new.id and state in ('finished', 'faulted') and end_date_time is null;
+end;
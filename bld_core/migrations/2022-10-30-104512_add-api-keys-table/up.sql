@@ -0,0 +1,8 @@
+-- Your SQL goes here
+create table api_keys (
+  id text primary key not null,
+  name text unique not null,
+  key_hash text unique not null,
+  created_by text not null,
+  date_created text default current_timestamp not null
+);
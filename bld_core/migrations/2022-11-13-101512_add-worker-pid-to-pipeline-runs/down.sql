@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+alter table pipeline_runs drop column worker_pid;
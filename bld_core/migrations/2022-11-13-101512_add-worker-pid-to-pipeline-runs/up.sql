@@ -0,0 +1,2 @@
+-- Your SQL goes here
+alter table pipeline_runs add column worker_pid integer;
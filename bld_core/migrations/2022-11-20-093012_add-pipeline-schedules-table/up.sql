@@ -0,0 +1,11 @@
+-- Your SQL goes here
+create table pipeline_schedules (
+  id text primary key not null,
+  pipeline text not null,
+  cron text not null,
+  variables text,
+  environment text,
+  enabled boolean not null default 1,
+  created_by text not null,
+  date_created text default current_timestamp not null
+);
@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+drop trigger if exists pipeline_runs_after_update;
+
+create trigger pipeline_runs_after_update
+    after update on pipeline_runs
+begin
+    update pipeline_runs set end_date_time = current_timestamp where id = new.id and state in ('finished', 'faulted') and end_date_time is null;
+end;
@@ -0,0 +1,13 @@
+-- Your SQL goes here
+create table pipeline_run_commands (
+  id text primary key not null,
+  run_id text not null,
+  pipeline text not null,
+  step_name text,
+  matrix text,
+  command_index integer not null,
+  preview text not null,
+  duration_millis bigint not null,
+  date_created text default (strftime('%Y-%m-%d %H:%M:%f', 'now')) not null,
+  foreign key(run_id) references pipeline_runs(id)
+);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+alter table pipeline_runs drop column docker_breaker_trips;
+alter table pipeline_runs drop column docker_retries;
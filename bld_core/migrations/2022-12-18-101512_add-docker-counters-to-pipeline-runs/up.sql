@@ -0,0 +1,3 @@
+-- Your SQL goes here
+alter table pipeline_runs add column docker_retries integer;
+alter table pipeline_runs add column docker_breaker_trips integer;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+alter table pipeline_runs add column started boolean;
+alter table pipeline_runs add column build_failed boolean;
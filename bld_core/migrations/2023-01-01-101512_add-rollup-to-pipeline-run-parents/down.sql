@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+alter table pipeline_run_parents drop column effective_state;
+alter table pipeline_run_parents drop column rollup;
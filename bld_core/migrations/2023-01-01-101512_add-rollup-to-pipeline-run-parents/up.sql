@@ -0,0 +1,3 @@
+-- Your SQL goes here
+alter table pipeline_run_parents add column rollup text;
+alter table pipeline_run_parents add column effective_state text;
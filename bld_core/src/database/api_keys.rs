@@ -0,0 +1,129 @@
+use crate::database::schema::api_keys;
+use crate::database::schema::api_keys::dsl::*;
+use anyhow::{anyhow, Result};
+use diesel::prelude::*;
+use diesel::query_dsl::{QueryDsl, RunQueryDsl};
+use diesel::sqlite::SqliteConnection;
+use diesel::{Identifiable, Insertable, Queryable};
+use sha2::{Digest, Sha256};
+use tracing::{debug, error};
+
+/// An api key of the server. Only the hash of the key is stored, so the key itself is
+/// available only when it's created.
+#[derive(Debug, Identifiable, Queryable)]
+#[diesel(table_name = api_keys)]
+pub struct ApiKey {
+    pub id: String,
+    pub name: String,
+    pub key_hash: String,
+    pub created_by: String,
+    pub date_created: String,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = api_keys)]
+pub struct InsertApiKey<'a> {
+    pub id: &'a str,
+    pub name: &'a str,
+    pub key_hash: &'a str,
+    pub created_by: &'a str,
+}
+
+/// The hash of an api key as it's stored in the database.
+pub fn hash(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
+
+pub fn select_by_id(conn: &mut SqliteConnection, ak_id: &str) -> Result<ApiKey> {
+    debug!("loading api key with id: {ak_id}");
+    api_keys
+        .filter(id.eq(ak_id))
+        .first(conn)
+        .inspect(|_| debug!("loaded api key successfully"))
+        .map_err(|e| {
+            error!("could not load api key. {e}");
+            anyhow!(e)
+        })
+}
+
+pub fn select_by_name(conn: &mut SqliteConnection, ak_name: &str) -> Result<ApiKey> {
+    debug!("loading api key with name: {ak_name}");
+    api_keys
+        .filter(name.eq(ak_name))
+        .first(conn)
+        .inspect(|_| debug!("loaded api key successfully"))
+        .map_err(|e| {
+            error!("could not load api key. {e}");
+            anyhow!(e)
+        })
+}
+
+pub fn select_by_key(conn: &mut SqliteConnection, key: &str) -> Result<ApiKey> {
+    debug!("loading api key by its hash");
+    api_keys
+        .filter(key_hash.eq(hash(key)))
+        .first(conn)
+        .inspect(|_| debug!("loaded api key successfully"))
+        .map_err(|e| {
+            error!("could not load api key. {e}");
+            anyhow!(e)
+        })
+}
+
+pub fn select_all(conn: &mut SqliteConnection) -> Result<Vec<ApiKey>> {
+    debug!("loading all api keys");
+    api_keys
+        .order(date_created)
+        .load(conn)
+        .inspect(|_| debug!("loaded all api keys successfully"))
+        .map_err(|e| {
+            error!("could not load api keys. {e}");
+            anyhow!(e)
+        })
+}
+
+pub fn select_by_created_by(
+    conn: &mut SqliteConnection,
+    ak_created_by: &str,
+) -> Result<Vec<ApiKey>> {
+    debug!("loading api keys created by: {ak_created_by}");
+    api_keys
+        .filter(created_by.eq(ak_created_by))
+        .order(date_created)
+        .load(conn)
+        .inspect(|_| debug!("loaded api keys successfully"))
+        .map_err(|e| {
+            error!("could not load api keys. {e}");
+            anyhow!(e)
+        })
+}
+
+pub fn insert(conn: &mut SqliteConnection, model: InsertApiKey) -> Result<ApiKey> {
+    debug!("inserting api key with name: {}", model.name);
+    conn.transaction(|conn| {
+        diesel::insert_into(api_keys)
+            .values(&model)
+            .execute(conn)
+            .map_err(|e| {
+                error!("could not insert api key. {e}");
+                anyhow!(e)
+            })
+            .and_then(|_| {
+                debug!("inserted api key successfully");
+                select_by_id(conn, model.id)
+            })
+    })
+}
+
+pub fn delete_by_name(conn: &mut SqliteConnection, ak_name: &str) -> Result<()> {
+    debug!("deleting api key with name: {ak_name}");
+    conn.transaction(|conn| {
+        diesel::delete(api_keys.filter(name.eq(ak_name)))
+            .execute(conn)
+            .map_err(|e| {
+                error!("could not delete api key. {e}");
+                anyhow!(e)
+            })
+            .map(|_| debug!("deleted api key successfully"))
+    })
+}
@@ -6,6 +6,7 @@ use diesel::connection::SimpleConnection;
 use diesel::r2d2::{ConnectionManager, CustomizeConnection, Error, Pool};
 use diesel::result::Error as DieselError;
 use diesel::sqlite::SqliteConnection;
+use diesel::Connection;
 use std::fmt::Write;
 use std::path::PathBuf;
 use std::time::Duration;
@@ -46,6 +47,19 @@ impl CustomizeConnection<SqliteConnection, Error> for SqliteConnectionOptions {
     }
 }
 
+/// Opens a single connection to the database, for reading information about it outside of a pool.
+pub fn new_connection(db: &str) -> Result<SqliteConnection> {
+    let path = path![db, DB_NAME].as_path().display().to_string();
+    let mut conn = SqliteConnection::establish(&path)?;
+    SqliteConnectionOptions {
+        enable_wal: false,
+        enabld_foreign_keys: true,
+        busy_timeout: Some(Duration::from_secs(30)),
+    }
+    .customize(&mut conn)?;
+    Ok(conn)
+}
+
 pub fn new_connection_pool(db: &str) -> Result<Pool<ConnectionManager<SqliteConnection>>> {
     let path = path![db, DB_NAME].as_path().display().to_string();
     debug!("creating sqlite connection pool");
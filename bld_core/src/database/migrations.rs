@@ -11,3 +11,9 @@ pub fn run_migrations(conn: &mut SqliteConnection) -> Result<()> {
     debug!("executed migrations");
     Ok(())
 }
+
+/// The version of the latest migration that has been applied to the database.
+pub fn schema_version(conn: &mut SqliteConnection) -> Result<Option<String>> {
+    let versions = conn.applied_migrations().map_err(|e| anyhow!(e))?;
+    Ok(versions.iter().max().map(|v| v.to_string()))
+}
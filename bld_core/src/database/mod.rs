@@ -1,3 +1,4 @@
+pub mod api_keys;
 mod connect;
 pub mod ha_client_serial_responses;
 pub mod ha_client_status;
@@ -9,8 +10,13 @@ pub mod ha_snapshot;
 pub mod ha_state_machine;
 mod migrations;
 pub mod pipeline;
+pub mod pipeline_run_artifacts;
+pub mod pipeline_run_checkpoints;
+pub mod pipeline_run_commands;
 pub mod pipeline_run_containers;
+pub mod pipeline_run_parents;
 pub mod pipeline_runs;
+pub mod pipeline_schedules;
 mod schema;
 
 pub use connect::*;
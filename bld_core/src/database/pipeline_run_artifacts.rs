@@ -0,0 +1,76 @@
+use crate::database::pipeline_runs::PipelineRuns;
+use crate::database::schema::pipeline_run_artifacts;
+use crate::database::schema::pipeline_run_artifacts::dsl::*;
+use anyhow::{anyhow, Result};
+use diesel::prelude::*;
+use diesel::query_dsl::{QueryDsl, RunQueryDsl};
+use diesel::sqlite::SqliteConnection;
+use diesel::{Associations, Identifiable, Insertable, Queryable};
+use tracing::{debug, error};
+
+/// A path on the server that an artifact of a run has been copied to.
+#[derive(Debug, Associations, Identifiable, Queryable)]
+#[diesel(belongs_to(PipelineRuns, foreign_key = run_id))]
+#[diesel(table_name = pipeline_run_artifacts)]
+pub struct PipelineRunArtifacts {
+    pub id: String,
+    pub run_id: String,
+    pub path: String,
+    pub date_created: String,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = pipeline_run_artifacts)]
+pub struct InsertPipelineRunArtifact<'a> {
+    pub id: &'a str,
+    pub run_id: &'a str,
+    pub path: &'a str,
+}
+
+pub fn select_by_run_id(
+    conn: &mut SqliteConnection,
+    pra_run_id: &str,
+) -> Result<Vec<PipelineRunArtifacts>> {
+    debug!("loading pipeline run artifacts for run with id: {pra_run_id}");
+    pipeline_run_artifacts
+        .filter(run_id.eq(pra_run_id))
+        .order(date_created)
+        .load(conn)
+        .inspect(|_| debug!("loaded pipeline run artifacts successfully"))
+        .map_err(|e| {
+            error!("could not load pipeline run artifacts. {e}");
+            anyhow!(e)
+        })
+}
+
+pub fn select_by_id(conn: &mut SqliteConnection, pra_id: &str) -> Result<PipelineRunArtifacts> {
+    debug!("loading pipeline run artifact with id: {pra_id}");
+    pipeline_run_artifacts
+        .filter(id.eq(pra_id))
+        .first(conn)
+        .inspect(|_| debug!("loaded pipeline run artifact successfully"))
+        .map_err(|e| {
+            error!("could not load pipeline run artifact. {e}");
+            anyhow!(e)
+        })
+}
+
+pub fn insert(
+    conn: &mut SqliteConnection,
+    model: InsertPipelineRunArtifact,
+) -> Result<PipelineRunArtifacts> {
+    debug!("inserting pipeline run artifact");
+    conn.transaction(|conn| {
+        diesel::insert_into(pipeline_run_artifacts)
+            .values(&model)
+            .execute(conn)
+            .map_err(|e| {
+                error!("could not insert pipeline run artifact. {e}");
+                anyhow!(e)
+            })
+            .and_then(|_| {
+                debug!("inserted pipeline run artifact successfully");
+                select_by_id(conn, model.id)
+            })
+    })
+}
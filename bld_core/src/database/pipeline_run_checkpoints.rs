@@ -0,0 +1,75 @@
+use crate::database::pipeline_runs::PipelineRuns;
+use crate::database::schema::pipeline_run_checkpoints;
+use crate::database::schema::pipeline_run_checkpoints::dsl::*;
+use anyhow::{anyhow, Result};
+use diesel::prelude::*;
+use diesel::query_dsl::{QueryDsl, RunQueryDsl};
+use diesel::sqlite::SqliteConnection;
+use diesel::{Associations, Identifiable, Insertable, Queryable};
+use tracing::{debug, error};
+
+#[derive(Debug, Associations, Identifiable, Queryable)]
+#[diesel(belongs_to(PipelineRuns, foreign_key = run_id))]
+#[diesel(table_name = pipeline_run_checkpoints)]
+pub struct PipelineRunCheckpoints {
+    pub id: String,
+    pub run_id: String,
+    pub name: String,
+    pub date_created: String,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = pipeline_run_checkpoints)]
+pub struct InsertPipelineRunCheckpoint<'a> {
+    pub id: &'a str,
+    pub run_id: &'a str,
+    pub name: &'a str,
+}
+
+pub fn select_by_run_id(
+    conn: &mut SqliteConnection,
+    prc_run_id: &str,
+) -> Result<Vec<PipelineRunCheckpoints>> {
+    debug!("loading pipeline run checkpoints for run with id: {prc_run_id}");
+    pipeline_run_checkpoints
+        .filter(run_id.eq(prc_run_id))
+        .order(date_created)
+        .load(conn)
+        .inspect(|_| debug!("loaded pipeline run checkpoints successfully"))
+        .map_err(|e| {
+            error!("could not load pipeline run checkpoints. {e}");
+            anyhow!(e)
+        })
+}
+
+pub fn select_by_id(conn: &mut SqliteConnection, prc_id: &str) -> Result<PipelineRunCheckpoints> {
+    debug!("loading pipeline run checkpoint with id: {prc_id}");
+    pipeline_run_checkpoints
+        .filter(id.eq(prc_id))
+        .first(conn)
+        .inspect(|_| debug!("loaded pipeline run checkpoint successfully"))
+        .map_err(|e| {
+            error!("could not load pipeline run checkpoint. {e}");
+            anyhow!(e)
+        })
+}
+
+pub fn insert(
+    conn: &mut SqliteConnection,
+    model: InsertPipelineRunCheckpoint,
+) -> Result<PipelineRunCheckpoints> {
+    debug!("inserting pipeline run checkpoint");
+    conn.transaction(|conn| {
+        diesel::insert_into(pipeline_run_checkpoints)
+            .values(&model)
+            .execute(conn)
+            .map_err(|e| {
+                error!("could not insert pipeline run checkpoint. {e}");
+                anyhow!(e)
+            })
+            .and_then(|_| {
+                debug!("inserted pipeline run checkpoint successfully");
+                select_by_id(conn, model.id)
+            })
+    })
+}
@@ -0,0 +1,87 @@
+use crate::database::pipeline_runs::PipelineRuns;
+use crate::database::schema::pipeline_run_commands;
+use crate::database::schema::pipeline_run_commands::dsl::*;
+use anyhow::{anyhow, Result};
+use diesel::prelude::*;
+use diesel::query_dsl::{QueryDsl, RunQueryDsl};
+use diesel::sqlite::SqliteConnection;
+use diesel::{Associations, Identifiable, Insertable, Queryable};
+use tracing::{debug, error};
+
+#[derive(Debug, Associations, Identifiable, Queryable)]
+#[diesel(belongs_to(PipelineRuns, foreign_key = run_id))]
+#[diesel(table_name = pipeline_run_commands)]
+pub struct PipelineRunCommands {
+    pub id: String,
+    pub run_id: String,
+    pub pipeline: String,
+    pub step_name: Option<String>,
+    pub matrix: Option<String>,
+    pub command_index: i32,
+    pub preview: String,
+    pub duration_millis: i64,
+    pub date_created: String,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = pipeline_run_commands)]
+pub struct InsertPipelineRunCommand<'a> {
+    pub id: &'a str,
+    pub run_id: &'a str,
+    pub pipeline: &'a str,
+    pub step_name: Option<&'a str>,
+    pub matrix: Option<&'a str>,
+    pub command_index: i32,
+    pub preview: &'a str,
+    pub duration_millis: i64,
+}
+
+/// The commands of a run in the order that they completed, with the commands of a step
+/// ordered by their index.
+pub fn select_by_run_id(
+    conn: &mut SqliteConnection,
+    prc_run_id: &str,
+) -> Result<Vec<PipelineRunCommands>> {
+    debug!("loading pipeline run commands for run with id: {prc_run_id}");
+    pipeline_run_commands
+        .filter(run_id.eq(prc_run_id))
+        .order((date_created, command_index))
+        .load(conn)
+        .inspect(|_| debug!("loaded pipeline run commands successfully"))
+        .map_err(|e| {
+            error!("could not load pipeline run commands. {e}");
+            anyhow!(e)
+        })
+}
+
+pub fn select_by_id(conn: &mut SqliteConnection, prc_id: &str) -> Result<PipelineRunCommands> {
+    debug!("loading pipeline run command with id: {prc_id}");
+    pipeline_run_commands
+        .filter(id.eq(prc_id))
+        .first(conn)
+        .inspect(|_| debug!("loaded pipeline run command successfully"))
+        .map_err(|e| {
+            error!("could not load pipeline run command. {e}");
+            anyhow!(e)
+        })
+}
+
+pub fn insert(
+    conn: &mut SqliteConnection,
+    model: InsertPipelineRunCommand,
+) -> Result<PipelineRunCommands> {
+    debug!("inserting pipeline run command");
+    conn.transaction(|conn| {
+        diesel::insert_into(pipeline_run_commands)
+            .values(&model)
+            .execute(conn)
+            .map_err(|e| {
+                error!("could not insert pipeline run command. {e}");
+                anyhow!(e)
+            })
+            .and_then(|_| {
+                debug!("inserted pipeline run command successfully");
+                select_by_id(conn, model.id)
+            })
+    })
+}
@@ -1,4 +1,6 @@
-use crate::database::pipeline_runs::{PipelineRuns, PR_STATE_FAULTED, PR_STATE_FINISHED};
+use crate::database::pipeline_runs::{
+    PipelineRuns, PR_STATE_FAULTED, PR_STATE_FINISHED, PR_STATE_INTERRUPTED,
+};
 use crate::database::schema::pipeline_run_containers;
 use crate::database::schema::pipeline_run_containers::dsl::*;
 use crate::database::schema::pipeline_runs::dsl as pr_dsl;
@@ -75,7 +77,7 @@ pub fn select_in_invalid_state(conn: &mut SqliteConnection) -> Result<Vec<Pipeli
         .inner_join(pipeline_run_containers)
         .filter(
             pr_dsl::state
-                .eq_any(&[PR_STATE_FINISHED, PR_STATE_FAULTED])
+                .eq_any(&[PR_STATE_FINISHED, PR_STATE_FAULTED, PR_STATE_INTERRUPTED])
                 .and(state.eq(PRC_STATE_ACTIVE)),
         )
         .load(conn)
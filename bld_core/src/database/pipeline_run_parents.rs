@@ -0,0 +1,100 @@
+use crate::database::schema::pipeline_run_parents;
+use crate::database::schema::pipeline_run_parents::dsl::*;
+use anyhow::{anyhow, Result};
+use diesel::prelude::*;
+use diesel::query_dsl::{QueryDsl, RunQueryDsl};
+use diesel::sqlite::SqliteConnection;
+use diesel::{Identifiable, Insertable, Queryable};
+use tracing::{debug, error};
+
+/// The link between a run and the run of a pipeline that it called from one of its steps, along
+/// with the rollup rule of the call and the state that the child contributed to its parent.
+#[derive(Debug, Identifiable, Queryable)]
+#[diesel(table_name = pipeline_run_parents)]
+pub struct PipelineRunParents {
+    pub id: String,
+    pub child_run_id: String,
+    pub parent_run_id: String,
+    pub step_name: Option<String>,
+    pub date_created: String,
+    pub rollup: Option<String>,
+    pub effective_state: Option<String>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = pipeline_run_parents)]
+pub struct InsertPipelineRunParent<'a> {
+    pub id: &'a str,
+    pub child_run_id: &'a str,
+    pub parent_run_id: &'a str,
+    pub step_name: Option<&'a str>,
+}
+
+pub fn select_by_parent_run_id(
+    conn: &mut SqliteConnection,
+    prp_parent_run_id: &str,
+) -> Result<Vec<PipelineRunParents>> {
+    debug!("loading the child runs of run with id: {prp_parent_run_id}");
+    pipeline_run_parents
+        .filter(parent_run_id.eq(prp_parent_run_id))
+        .order(date_created)
+        .load(conn)
+        .inspect(|_| debug!("loaded the child runs successfully"))
+        .map_err(|e| {
+            error!("could not load the child runs. {e}");
+            anyhow!(e)
+        })
+}
+
+pub fn select_by_id(conn: &mut SqliteConnection, prp_id: &str) -> Result<PipelineRunParents> {
+    debug!("loading pipeline run parent with id: {prp_id}");
+    pipeline_run_parents
+        .filter(id.eq(prp_id))
+        .first(conn)
+        .inspect(|_| debug!("loaded pipeline run parent successfully"))
+        .map_err(|e| {
+            error!("could not load pipeline run parent. {e}");
+            anyhow!(e)
+        })
+}
+
+/// Records the rollup rule of the call of a child run and the state that it contributed to its parent.
+pub fn update_rollup(
+    conn: &mut SqliteConnection,
+    prp_child_run_id: &str,
+    prp_rollup: &str,
+    prp_effective_state: &str,
+) -> Result<()> {
+    debug!("updating the rollup of child run with id: {prp_child_run_id}");
+    diesel::update(pipeline_run_parents.filter(child_run_id.eq(prp_child_run_id)))
+        .set((
+            rollup.eq(prp_rollup),
+            effective_state.eq(prp_effective_state),
+        ))
+        .execute(conn)
+        .map(|_| debug!("updated the rollup of the child run successfully"))
+        .map_err(|e| {
+            error!("could not update the rollup of the child run. {e}");
+            anyhow!(e)
+        })
+}
+
+pub fn insert(
+    conn: &mut SqliteConnection,
+    model: InsertPipelineRunParent,
+) -> Result<PipelineRunParents> {
+    debug!("inserting pipeline run parent");
+    conn.transaction(|conn| {
+        diesel::insert_into(pipeline_run_parents)
+            .values(&model)
+            .execute(conn)
+            .map_err(|e| {
+                error!("could not insert pipeline run parent. {e}");
+                anyhow!(e)
+            })
+            .and_then(|_| {
+                debug!("inserted pipeline run parent successfully");
+                select_by_id(conn, model.id)
+            })
+    })
+}
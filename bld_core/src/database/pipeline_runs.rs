@@ -1,10 +1,13 @@
+use crate::database::schema::pipeline_run_parents;
 use crate::database::schema::pipeline_runs;
 use crate::database::schema::pipeline_runs::dsl::*;
 use anyhow::{anyhow, Result};
+use diesel::dsl::count_star;
 use diesel::prelude::*;
 use diesel::query_dsl::RunQueryDsl;
 use diesel::sqlite::SqliteConnection;
 use diesel::Queryable;
+use std::collections::HashMap;
 use tracing::{debug, error};
 
 pub const PR_STATE_INITIAL: &str = "initial";
@@ -12,6 +15,7 @@ pub const PR_STATE_QUEUED: &str = "queued";
 pub const PR_STATE_RUNNING: &str = "running";
 pub const PR_STATE_FINISHED: &str = "finished";
 pub const PR_STATE_FAULTED: &str = "faulted";
+pub const PR_STATE_INTERRUPTED: &str = "interrupted";
 
 #[derive(Debug, Identifiable, Queryable)]
 #[diesel(table_name = pipeline_runs)]
@@ -23,6 +27,23 @@ pub struct PipelineRuns {
     pub start_date_time: String,
     pub end_date_time: Option<String>,
     pub stopped: Option<bool>,
+    pub labels: Option<String>,
+    pub worker_pid: Option<i32>,
+    pub trace_id: Option<String>,
+    pub docker_retries: Option<i32>,
+    pub docker_breaker_trips: Option<i32>,
+    pub started: Option<bool>,
+    pub build_failed: Option<bool>,
+}
+
+impl PipelineRuns {
+    /// The labels of the run, which are stored as a json object of names to values.
+    pub fn labels(&self) -> HashMap<String, String> {
+        self.labels
+            .as_deref()
+            .and_then(|value| serde_json::from_str(value).ok())
+            .unwrap_or_default()
+    }
 }
 
 #[derive(Insertable)]
@@ -32,6 +53,7 @@ struct InsertPipelineRun<'a> {
     pub name: &'a str,
     pub state: &'a str,
     pub user: &'a str,
+    pub labels: Option<&'a str>,
 }
 
 pub fn select_all(conn: &mut SqliteConnection) -> Result<Vec<PipelineRuns>> {
@@ -49,6 +71,150 @@ pub fn select_all(conn: &mut SqliteConnection) -> Result<Vec<PipelineRuns>> {
         })
 }
 
+/// The filters of the history of pipeline runs. The dates are compared as text in the sqlite
+/// timestamp format, so a date without a time such as 2024-01-31 is a valid bound as well.
+#[derive(Debug, Default)]
+pub struct HistoryFilter {
+    pub name: Option<String>,
+    pub state: Option<String>,
+    pub running: Option<bool>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub labels: Vec<(String, String)>,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+/// Loads the latest pipeline runs, optionally filtered by name, state, labels and a range for the
+/// start date time of the run.
+pub fn select_with_filters(
+    conn: &mut SqliteConnection,
+    filter: &HistoryFilter,
+) -> Result<Vec<PipelineRuns>> {
+    debug!("loading pipeline runs with filter: {:?}", filter);
+    let mut query = pipeline_runs.into_boxed();
+    if let Some(pip_name) = &filter.name {
+        query = query.filter(name.eq(pip_name));
+    }
+    if let Some(pip_state) = &filter.state {
+        query = query.filter(state.eq(pip_state));
+    }
+    match filter.running {
+        Some(true) => query = query.filter(state.eq(PR_STATE_RUNNING)),
+        Some(false) => query = query.filter(state.ne(PR_STATE_RUNNING)),
+        None => {}
+    }
+    if let Some(pip_from) = &filter.from {
+        query = query.filter(start_date_time.ge(pip_from));
+    }
+    if let Some(pip_to) = &filter.to {
+        query = query.filter(start_date_time.le(pip_to));
+    }
+    for (label, value) in &filter.labels {
+        query = query.filter(labels.like(label_pattern(label, value)?).escape('\\'));
+    }
+    query
+        .order(start_date_time.desc())
+        .limit(filter.limit)
+        .offset(filter.offset)
+        .load(conn)
+        .inspect(|_| debug!("loaded pipeline runs successfully"))
+        .map_err(|e| {
+            error!("could not load pipeline runs due to: {e}");
+            anyhow!(e)
+        })
+}
+
+/// The number of runs of each pipeline by state.
+pub fn count_by_state(conn: &mut SqliteConnection) -> Result<Vec<(String, String, i64)>> {
+    debug!("counting the pipeline runs by state");
+    pipeline_runs
+        .group_by((name, state))
+        .select((name, state, count_star()))
+        .load(conn)
+        .map_err(|e| {
+            error!("could not count pipeline runs due to: {e}");
+            anyhow!(e)
+        })
+}
+
+/// The number of runs of each pipeline that have started running.
+pub fn count_started(conn: &mut SqliteConnection) -> Result<Vec<(String, i64)>> {
+    debug!("counting the started pipeline runs");
+    pipeline_runs
+        .filter(started.eq(true))
+        .group_by(name)
+        .select((name, count_star()))
+        .load(conn)
+        .map_err(|e| {
+            error!("could not count pipeline runs due to: {e}");
+            anyhow!(e)
+        })
+}
+
+/// The number of runs of each pipeline whose runner failed to be built.
+pub fn count_build_failed(conn: &mut SqliteConnection) -> Result<Vec<(String, i64)>> {
+    debug!("counting the pipeline runs that failed to build");
+    pipeline_runs
+        .filter(build_failed.eq(true))
+        .group_by(name)
+        .select((name, count_star()))
+        .load(conn)
+        .map_err(|e| {
+            error!("could not count pipeline runs due to: {e}");
+            anyhow!(e)
+        })
+}
+
+/// The total number of retried docker api calls and circuit breaker trips of all the runs.
+pub fn sum_docker_counters(conn: &mut SqliteConnection) -> Result<(i64, i64)> {
+    debug!("summing the docker counters of the pipeline runs");
+    pipeline_runs
+        .select((
+            diesel::dsl::sum(docker_retries),
+            diesel::dsl::sum(docker_breaker_trips),
+        ))
+        .first::<(Option<i64>, Option<i64>)>(conn)
+        .map(|(retries, trips)| (retries.unwrap_or_default(), trips.unwrap_or_default()))
+        .map_err(|e| {
+            error!("could not sum the docker counters due to: {e}");
+            anyhow!(e)
+        })
+}
+
+/// The name, start and end date time of the runs that have ended.
+pub fn select_ended_dates(conn: &mut SqliteConnection) -> Result<Vec<(String, String, String)>> {
+    debug!("loading the dates of the ended pipeline runs");
+    pipeline_runs
+        .filter(end_date_time.is_not_null())
+        .select((name, start_date_time, end_date_time.assume_not_null()))
+        .load(conn)
+        .map_err(|e| {
+            error!("could not load pipeline runs due to: {e}");
+            anyhow!(e)
+        })
+}
+
+/// Loads the runs in a state that weren't called by another run, which are the runs that
+/// have a worker of their own, ordered by their start date time.
+pub fn select_top_level_by_state(
+    conn: &mut SqliteConnection,
+    pip_state: &str,
+) -> Result<Vec<PipelineRuns>> {
+    debug!("loading the top level pipeline runs with state: {pip_state}");
+    let child_runs = pipeline_run_parents::table.select(pipeline_run_parents::child_run_id);
+    pipeline_runs
+        .filter(state.eq(pip_state))
+        .filter(diesel::dsl::not(id.eq_any(child_runs)))
+        .order(start_date_time)
+        .load(conn)
+        .inspect(|_| debug!("loaded pipeline runs successfully"))
+        .map_err(|e| {
+            error!("could not load pipeline runs due to: {e}");
+            anyhow!(e)
+        })
+}
+
 pub fn select_running_by_id(conn: &mut SqliteConnection, run_id: &str) -> Result<PipelineRuns> {
     debug!("loading pipeline run with id: {run_id} that is in a running state");
     pipeline_runs
@@ -94,6 +260,23 @@ pub fn select_by_name(conn: &mut SqliteConnection, pip_name: &str) -> Result<Pip
         })
 }
 
+/// Loads every run of a pipeline, ordered by their start date time.
+pub fn select_all_by_name(
+    conn: &mut SqliteConnection,
+    pip_name: &str,
+) -> Result<Vec<PipelineRuns>> {
+    debug!("loading all runs of pipeline: {pip_name} from the database");
+    pipeline_runs
+        .filter(name.eq(pip_name))
+        .order(start_date_time)
+        .load(conn)
+        .inspect(|_| debug!("loaded pipeline runs successfully"))
+        .map_err(|e| {
+            error!("could not load pipeline runs due to: {e}");
+            anyhow!(e)
+        })
+}
+
 pub fn select_last(conn: &mut SqliteConnection) -> Result<PipelineRuns> {
     debug!("loading the last invoked pipeline from the database");
     pipeline_runs
@@ -109,11 +292,28 @@ pub fn select_last(conn: &mut SqliteConnection) -> Result<PipelineRuns> {
         })
 }
 
+/// The pattern that matches the runs with a label, using the json encoding of the label so that
+/// it matches a whole entry of the stored labels.
+fn label_pattern(label: &str, value: &str) -> Result<String> {
+    let entry = format!(
+        "{}:{}",
+        serde_json::to_string(label)?,
+        serde_json::to_string(value)?
+    );
+    let escaped = entry
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    Ok(format!("%{escaped}%"))
+}
+
+/// Inserts a run with its labels serialized as a json object, or without labels if it has none.
 pub fn insert(
     conn: &mut SqliteConnection,
     pip_id: &str,
     pip_name: &str,
     pip_user: &str,
+    pip_labels: Option<&str>,
 ) -> Result<PipelineRuns> {
     debug!("inserting new pipeline to the database");
     let run = InsertPipelineRun {
@@ -121,6 +321,7 @@ pub fn insert(
         name: pip_name,
         state: PR_STATE_INITIAL,
         user: pip_user,
+        labels: pip_labels,
     };
     conn.transaction(|conn| {
         diesel::insert_into(pipeline_runs::table)
@@ -146,9 +347,106 @@ pub fn update_state(
     pip_state: &str,
 ) -> Result<PipelineRuns> {
     debug!("updating pipeline id: {pip_id} with values state: {pip_state}");
+    conn.transaction(|conn| {
+        let update = diesel::update(pipeline_runs.filter(id.eq(pip_id)));
+        // the run is marked as started so that it's counted even after it has ended.
+        let result = if pip_state == PR_STATE_RUNNING {
+            update
+                .set((state.eq(pip_state), started.eq(true)))
+                .execute(conn)
+        } else {
+            update.set(state.eq(pip_state)).execute(conn)
+        };
+        result
+            .map_err(|e| {
+                error!("could not update pipeline run due to: {e}");
+                anyhow!(e)
+            })
+            .and_then(|_| {
+                debug!("updated pipeline successfully");
+                select_by_id(conn, pip_id)
+            })
+    })
+}
+
+/// Faults a run whose runner failed to be built, recording the failure so that it's counted
+/// separately from the runs that faulted while running.
+pub fn update_build_failed(conn: &mut SqliteConnection, pip_id: &str) -> Result<PipelineRuns> {
+    debug!("updating pipeline id: {pip_id} with values build_failed: true");
     conn.transaction(|conn| {
         diesel::update(pipeline_runs.filter(id.eq(pip_id)))
-            .set(state.eq(pip_state))
+            .set((state.eq(PR_STATE_FAULTED), build_failed.eq(true)))
+            .execute(conn)
+            .map_err(|e| {
+                error!("could not update pipeline run due to: {e}");
+                anyhow!(e)
+            })
+            .and_then(|_| {
+                debug!("updated pipeline successfully");
+                select_by_id(conn, pip_id)
+            })
+    })
+}
+
+/// Records the pid of the worker process that executes the run.
+pub fn update_worker_pid(
+    conn: &mut SqliteConnection,
+    pip_id: &str,
+    pip_worker_pid: i32,
+) -> Result<PipelineRuns> {
+    debug!("updating pipeline id: {pip_id} with values worker_pid: {pip_worker_pid}");
+    conn.transaction(|conn| {
+        diesel::update(pipeline_runs.filter(id.eq(pip_id)))
+            .set(worker_pid.eq(pip_worker_pid))
+            .execute(conn)
+            .map_err(|e| {
+                error!("could not update pipeline run due to: {e}");
+                anyhow!(e)
+            })
+            .and_then(|_| {
+                debug!("updated pipeline successfully");
+                select_by_id(conn, pip_id)
+            })
+    })
+}
+
+/// Records the number of docker api calls of the run that were retried and the number of times
+/// that the circuit breaker of the docker api opened during it.
+pub fn update_docker_counters(
+    conn: &mut SqliteConnection,
+    pip_id: &str,
+    retries: i32,
+    breaker_trips: i32,
+) -> Result<PipelineRuns> {
+    debug!("updating pipeline id: {pip_id} with values docker_retries: {retries}, docker_breaker_trips: {breaker_trips}");
+    conn.transaction(|conn| {
+        diesel::update(pipeline_runs.filter(id.eq(pip_id)))
+            .set((
+                docker_retries.eq(retries),
+                docker_breaker_trips.eq(breaker_trips),
+            ))
+            .execute(conn)
+            .map_err(|e| {
+                error!("could not update pipeline run due to: {e}");
+                anyhow!(e)
+            })
+            .and_then(|_| {
+                debug!("updated pipeline successfully");
+                select_by_id(conn, pip_id)
+            })
+    })
+}
+
+/// Records the id of the trace that the spans of the run are exported with.
+pub fn update_trace_id(
+    conn: &mut SqliteConnection,
+    pip_id: &str,
+    pip_trace_id: &str,
+) -> Result<PipelineRuns> {
+    debug!("updating pipeline id: {pip_id} with values trace_id: {pip_trace_id}");
+    conn.transaction(|conn| {
+        diesel::update(pipeline_runs.filter(id.eq(pip_id)))
+            .set(trace_id.eq(pip_trace_id))
             .execute(conn)
             .map_err(|e| {
                 error!("could not update pipeline run due to: {e}");
@@ -181,3 +479,132 @@ pub fn update_stopped(
             })
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::new_connection_pool;
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_dir_all};
+    use uuid::Uuid;
+
+    #[test]
+    fn pipeline_runs_are_filtered_by_their_labels() {
+        let root = temp_dir().join(format!("bld-labels-{}", Uuid::new_v4()));
+        create_dir_all(&root).unwrap();
+        let pool = new_connection_pool(&root.display().to_string()).unwrap();
+        let mut conn = pool.get().unwrap();
+        let production = r#"{"env":"production","team":"web"}"#;
+        let staging = r#"{"env":"staging_1","team":"web"}"#;
+        insert(&mut conn, "1", "deploy.yaml", "user", Some(production)).unwrap();
+        insert(&mut conn, "2", "deploy.yaml", "user", Some(staging)).unwrap();
+        insert(&mut conn, "3", "build.yaml", "user", None).unwrap();
+
+        let ids = |filters: &[(&str, &str)]| -> Vec<String> {
+            let filters: Vec<(String, String)> = filters
+                .iter()
+                .map(|(l, v)| (l.to_string(), v.to_string()))
+                .collect();
+            let mut conn = pool.get().unwrap();
+            let filter = HistoryFilter {
+                labels: filters,
+                limit: 10,
+                ..Default::default()
+            };
+            let mut ids: Vec<String> = select_with_filters(&mut conn, &filter)
+                .unwrap()
+                .into_iter()
+                .map(|run| run.id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(ids(&[]), vec!["1", "2", "3"]);
+        assert_eq!(ids(&[("env", "production")]), vec!["1"]);
+        assert_eq!(ids(&[("team", "web")]), vec!["1", "2"]);
+        assert_eq!(ids(&[("team", "web"), ("env", "staging_1")]), vec!["2"]);
+        assert!(ids(&[("env", "staging%")]).is_empty());
+        assert!(ids(&[("env", "prod")]).is_empty());
+        assert!(ids(&[("nv", "production")]).is_empty());
+
+        let run = select_by_id(&mut conn, "1").unwrap();
+        assert_eq!(run.labels()["env"], "production");
+        assert!(select_by_id(&mut conn, "3").unwrap().labels().is_empty());
+        drop(conn);
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn pipeline_runs_are_filtered_by_running_and_paged() {
+        let root = temp_dir().join(format!("bld-history-{}", Uuid::new_v4()));
+        create_dir_all(&root).unwrap();
+        let pool = new_connection_pool(&root.display().to_string()).unwrap();
+        let mut conn = pool.get().unwrap();
+        for i in 0..5 {
+            insert(&mut conn, &i.to_string(), "build.yaml", "user", None).unwrap();
+        }
+        update_state(&mut conn, "0", PR_STATE_RUNNING).unwrap();
+        update_state(&mut conn, "1", PR_STATE_RUNNING).unwrap();
+
+        let mut select = |filter: HistoryFilter| select_with_filters(&mut conn, &filter).unwrap();
+        let running = select(HistoryFilter {
+            running: Some(true),
+            limit: 10,
+            ..Default::default()
+        });
+        assert_eq!(running.len(), 2);
+        let not_running = select(HistoryFilter {
+            running: Some(false),
+            limit: 10,
+            ..Default::default()
+        });
+        assert!(not_running.iter().all(|run| run.state != PR_STATE_RUNNING));
+        assert_eq!(not_running.len(), 3);
+
+        let page = |offset| HistoryFilter {
+            name: Some("build.yaml".to_string()),
+            from: Some("2000-01-01".to_string()),
+            limit: 2,
+            offset,
+            ..Default::default()
+        };
+        let pages: Vec<usize> = [0, 2, 4]
+            .into_iter()
+            .map(|o| select(page(o)).len())
+            .collect();
+        assert_eq!(pages, vec![2, 2, 1]);
+        let future = select(HistoryFilter {
+            from: Some("2999-01-01".to_string()),
+            limit: 10,
+            ..Default::default()
+        });
+        assert!(future.is_empty());
+        drop(conn);
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn ended_runs_of_a_pipeline_have_an_end_date_time() {
+        let root = temp_dir().join(format!("bld-ended-{}", Uuid::new_v4()));
+        create_dir_all(&root).unwrap();
+        let pool = new_connection_pool(&root.display().to_string()).unwrap();
+        let mut conn = pool.get().unwrap();
+        let states = [PR_STATE_FINISHED, PR_STATE_FAULTED, PR_STATE_INTERRUPTED];
+        for (i, pip_state) in states.iter().enumerate() {
+            insert(&mut conn, &i.to_string(), "build.yaml", "user", None).unwrap();
+            update_state(&mut conn, &i.to_string(), PR_STATE_RUNNING).unwrap();
+            update_state(&mut conn, &i.to_string(), pip_state).unwrap();
+        }
+        insert(&mut conn, "3", "build.yaml", "user", None).unwrap();
+        update_state(&mut conn, "3", PR_STATE_RUNNING).unwrap();
+        insert(&mut conn, "4", "deploy.yaml", "user", None).unwrap();
+
+        let runs = select_all_by_name(&mut conn, "build.yaml").unwrap();
+        assert_eq!(runs.len(), 4);
+        for run in runs {
+            assert_eq!(run.end_date_time.is_some(), run.id != "3");
+        }
+        drop(conn);
+        remove_dir_all(root).unwrap();
+    }
+}
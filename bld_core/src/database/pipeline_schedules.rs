@@ -0,0 +1,167 @@
+use crate::database::schema::pipeline_schedules;
+use crate::database::schema::pipeline_schedules::dsl::{date_created, id};
+use anyhow::{anyhow, Result};
+use diesel::prelude::*;
+use diesel::query_dsl::{QueryDsl, RunQueryDsl};
+use diesel::sqlite::SqliteConnection;
+use diesel::{Identifiable, Insertable, Queryable};
+use std::collections::HashMap;
+use tracing::{debug, error};
+
+/// A pipeline that the server runs on a cron schedule, with the variables and environment
+/// of its runs stored as json.
+#[derive(Debug, Identifiable, Queryable)]
+#[diesel(table_name = pipeline_schedules)]
+pub struct PipelineSchedule {
+    pub id: String,
+    pub pipeline: String,
+    pub cron: String,
+    pub variables: Option<String>,
+    pub environment: Option<String>,
+    pub enabled: bool,
+    pub created_by: String,
+    pub date_created: String,
+}
+
+impl PipelineSchedule {
+    pub fn variables(&self) -> HashMap<String, String> {
+        parse_json(self.variables.as_deref())
+    }
+
+    pub fn environment(&self) -> HashMap<String, String> {
+        parse_json(self.environment.as_deref())
+    }
+}
+
+fn parse_json(value: Option<&str>) -> HashMap<String, String> {
+    value
+        .and_then(|v| serde_json::from_str(v).ok())
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = pipeline_schedules)]
+pub struct InsertPipelineSchedule<'a> {
+    pub id: &'a str,
+    pub pipeline: &'a str,
+    pub cron: &'a str,
+    pub variables: Option<&'a str>,
+    pub environment: Option<&'a str>,
+    pub enabled: bool,
+    pub created_by: &'a str,
+}
+
+pub fn select_all(conn: &mut SqliteConnection) -> Result<Vec<PipelineSchedule>> {
+    debug!("loading all pipeline schedules");
+    pipeline_schedules::table
+        .order(date_created)
+        .load(conn)
+        .inspect(|_| debug!("loaded pipeline schedules successfully"))
+        .map_err(|e| {
+            error!("could not load pipeline schedules. {e}");
+            anyhow!(e)
+        })
+}
+
+pub fn select_enabled(conn: &mut SqliteConnection) -> Result<Vec<PipelineSchedule>> {
+    debug!("loading enabled pipeline schedules");
+    pipeline_schedules::table
+        .filter(pipeline_schedules::enabled.eq(true))
+        .order(date_created)
+        .load(conn)
+        .inspect(|_| debug!("loaded enabled pipeline schedules successfully"))
+        .map_err(|e| {
+            error!("could not load enabled pipeline schedules. {e}");
+            anyhow!(e)
+        })
+}
+
+pub fn select_by_id(conn: &mut SqliteConnection, ps_id: &str) -> Result<PipelineSchedule> {
+    debug!("loading pipeline schedule with id: {ps_id}");
+    pipeline_schedules::table
+        .filter(id.eq(ps_id))
+        .first(conn)
+        .inspect(|_| debug!("loaded pipeline schedule successfully"))
+        .map_err(|e| {
+            error!("could not load pipeline schedule. {e}");
+            anyhow!(e)
+        })
+}
+
+pub fn insert(
+    conn: &mut SqliteConnection,
+    model: InsertPipelineSchedule,
+) -> Result<PipelineSchedule> {
+    debug!(
+        "inserting schedule {} for pipeline {}",
+        model.cron, model.pipeline
+    );
+    conn.transaction(|conn| {
+        diesel::insert_into(pipeline_schedules::table)
+            .values(&model)
+            .execute(conn)
+            .map_err(|e| {
+                error!("could not insert pipeline schedule. {e}");
+                anyhow!(e)
+            })
+            .and_then(|_| {
+                debug!("inserted pipeline schedule successfully");
+                select_by_id(conn, model.id)
+            })
+    })
+}
+
+/// Deletes a schedule and returns whether it existed.
+pub fn delete(conn: &mut SqliteConnection, ps_id: &str) -> Result<bool> {
+    debug!("deleting pipeline schedule with id: {ps_id}");
+    diesel::delete(pipeline_schedules::table.filter(id.eq(ps_id)))
+        .execute(conn)
+        .map(|deleted| {
+            debug!("deleted {deleted} pipeline schedules");
+            deleted > 0
+        })
+        .map_err(|e| {
+            error!("could not delete pipeline schedule. {e}");
+            anyhow!(e)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::new_connection_pool;
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_dir_all};
+    use uuid::Uuid;
+
+    #[test]
+    fn pipeline_schedules_are_inserted_filtered_and_deleted() {
+        let root = temp_dir().join(format!("bld-schedules-{}", Uuid::new_v4()));
+        create_dir_all(&root).unwrap();
+        let pool = new_connection_pool(&root.display().to_string()).unwrap();
+        let mut conn = pool.get().unwrap();
+        let schedule = |ps_id, ps_enabled| InsertPipelineSchedule {
+            id: ps_id,
+            pipeline: "nightly.yaml",
+            cron: "0 2 * * *",
+            variables: Some(r#"{"branch":"main"}"#),
+            environment: None,
+            enabled: ps_enabled,
+            created_by: "user",
+        };
+        insert(&mut conn, schedule("1", true)).unwrap();
+        insert(&mut conn, schedule("2", false)).unwrap();
+
+        assert_eq!(select_all(&mut conn).unwrap().len(), 2);
+        let enabled_schedules = select_enabled(&mut conn).unwrap();
+        assert_eq!(enabled_schedules.len(), 1);
+        assert_eq!(enabled_schedules[0].variables()["branch"], "main");
+        assert!(enabled_schedules[0].environment().is_empty());
+
+        assert!(delete(&mut conn, "1").unwrap());
+        assert!(!delete(&mut conn, "1").unwrap());
+        assert!(select_enabled(&mut conn).unwrap().is_empty());
+        drop(conn);
+        remove_dir_all(root).unwrap();
+    }
+}
@@ -1,3 +1,13 @@
+table! {
+    api_keys (id) {
+        id -> Text,
+        name -> Text,
+        key_hash -> Text,
+        created_by -> Text,
+        date_created -> Text,
+    }
+}
+
 table! {
     ha_client_serial_responses (id) {
         id -> Integer,
@@ -85,6 +95,50 @@ table! {
     }
 }
 
+table! {
+    pipeline_run_artifacts (id) {
+        id -> Text,
+        run_id -> Text,
+        path -> Text,
+        date_created -> Text,
+    }
+}
+
+table! {
+    pipeline_run_checkpoints (id) {
+        id -> Text,
+        run_id -> Text,
+        name -> Text,
+        date_created -> Text,
+    }
+}
+
+table! {
+    pipeline_run_parents (id) {
+        id -> Text,
+        child_run_id -> Text,
+        parent_run_id -> Text,
+        step_name -> Nullable<Text>,
+        date_created -> Text,
+        rollup -> Nullable<Text>,
+        effective_state -> Nullable<Text>,
+    }
+}
+
+table! {
+    pipeline_run_commands (id) {
+        id -> Text,
+        run_id -> Text,
+        pipeline -> Text,
+        step_name -> Nullable<Text>,
+        matrix -> Nullable<Text>,
+        command_index -> Integer,
+        preview -> Text,
+        duration_millis -> BigInt,
+        date_created -> Text,
+    }
+}
+
 table! {
     pipeline_run_containers (id) {
         id -> Text,
@@ -95,6 +149,19 @@ table! {
     }
 }
 
+table! {
+    pipeline_schedules (id) {
+        id -> Text,
+        pipeline -> Text,
+        cron -> Text,
+        variables -> Nullable<Text>,
+        environment -> Nullable<Text>,
+        enabled -> Bool,
+        created_by -> Text,
+        date_created -> Text,
+    }
+}
+
 table! {
     pipeline_runs (id) {
         id -> Text,
@@ -104,6 +171,13 @@ table! {
         start_date_time -> Text,
         end_date_time -> Nullable<Text>,
         stopped -> Nullable<Bool>,
+        labels -> Nullable<Text>,
+        worker_pid -> Nullable<Integer>,
+        trace_id -> Nullable<Text>,
+        docker_retries -> Nullable<Integer>,
+        docker_breaker_trips -> Nullable<Integer>,
+        started -> Nullable<Bool>,
+        build_failed -> Nullable<Bool>,
     }
 }
 
@@ -111,9 +185,13 @@ joinable!(ha_client_serial_responses -> ha_state_machine (state_machine_id));
 joinable!(ha_client_status -> ha_state_machine (state_machine_id));
 joinable!(ha_members -> ha_snapshot (snapshot_id));
 joinable!(ha_members_after_consensus -> ha_snapshot (snapshot_id));
+joinable!(pipeline_run_artifacts -> pipeline_runs (run_id));
+joinable!(pipeline_run_checkpoints -> pipeline_runs (run_id));
+joinable!(pipeline_run_commands -> pipeline_runs (run_id));
 joinable!(pipeline_run_containers -> pipeline_runs (run_id));
 
 allow_tables_to_appear_in_same_query!(
+    api_keys,
     ha_client_serial_responses,
     ha_client_status,
     ha_hard_state,
@@ -123,6 +201,11 @@ allow_tables_to_appear_in_same_query!(
     ha_snapshot,
     ha_state_machine,
     pipeline,
+    pipeline_run_artifacts,
+    pipeline_run_checkpoints,
+    pipeline_run_commands,
     pipeline_run_containers,
+    pipeline_run_parents,
     pipeline_runs,
+    pipeline_schedules,
 );
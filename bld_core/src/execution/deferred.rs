@@ -0,0 +1,518 @@
+use crate::database::pipeline_run_artifacts::{self, InsertPipelineRunArtifact};
+use crate::database::pipeline_run_checkpoints::{self, InsertPipelineRunCheckpoint};
+use crate::database::pipeline_run_commands::{self, InsertPipelineRunCommand};
+use crate::database::pipeline_run_parents::{self, InsertPipelineRunParent};
+use crate::database::pipeline_runs;
+use anyhow::Result;
+use bld_config::definitions::DB_EXECUTION_JOURNAL;
+use bld_config::path;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sqlite::SqliteConnection;
+use diesel::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{read_to_string, remove_file, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{sleep, spawn};
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+
+const RETRY_BASE_DELAY_MILLIS: u64 = 200;
+const RETRY_MAX_DELAY_MILLIS: u64 = 5000;
+const RECONCILE_ATTEMPTS: u32 = 10;
+
+/// An update to the persisted state of a run. The ids of the inserted records are generated
+/// when the update is created, so that applying an update again after it has been applied
+/// doesn't insert a record twice.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ExecutionUpdate {
+    State {
+        run_id: String,
+        state: String,
+    },
+    Checkpoint {
+        id: String,
+        run_id: String,
+        name: String,
+    },
+    Artifact {
+        id: String,
+        run_id: String,
+        path: String,
+    },
+    ChildRun {
+        id: String,
+        run_id: String,
+        pipeline: String,
+        parent_run_id: String,
+        step_name: Option<String>,
+    },
+    TraceId {
+        run_id: String,
+        trace_id: String,
+    },
+    DockerCounters {
+        run_id: String,
+        retries: u64,
+        breaker_trips: u64,
+    },
+    BuildFailed {
+        run_id: String,
+    },
+    Rollup {
+        run_id: String,
+        rollup: String,
+        effective_state: String,
+    },
+    Command {
+        id: String,
+        run_id: String,
+        pipeline: String,
+        step_name: Option<String>,
+        matrix: Option<String>,
+        index: usize,
+        preview: String,
+        duration_millis: u64,
+    },
+}
+
+impl ExecutionUpdate {
+    pub fn apply(&self, conn: &mut SqliteConnection) -> Result<()> {
+        match self {
+            Self::State { run_id, state } => {
+                pipeline_runs::update_state(conn, run_id, state).map(|_| ())
+            }
+            Self::Checkpoint { id, run_id, name } => {
+                if pipeline_run_checkpoints::select_by_id(conn, id).is_ok() {
+                    return Ok(());
+                }
+                let model = InsertPipelineRunCheckpoint { id, run_id, name };
+                pipeline_run_checkpoints::insert(conn, model).map(|_| ())
+            }
+            Self::Artifact { id, run_id, path } => {
+                if pipeline_run_artifacts::select_by_id(conn, id).is_ok() {
+                    return Ok(());
+                }
+                let model = InsertPipelineRunArtifact { id, run_id, path };
+                pipeline_run_artifacts::insert(conn, model).map(|_| ())
+            }
+            Self::ChildRun {
+                id,
+                run_id,
+                pipeline,
+                parent_run_id,
+                step_name,
+            } => conn.transaction(|conn| {
+                if pipeline_runs::select_by_id(conn, run_id).is_ok() {
+                    return Ok(());
+                }
+                let parent = pipeline_runs::select_by_id(conn, parent_run_id)?;
+                pipeline_runs::insert(
+                    conn,
+                    run_id,
+                    pipeline,
+                    &parent.user,
+                    parent.labels.as_deref(),
+                )?;
+                let model = InsertPipelineRunParent {
+                    id,
+                    child_run_id: run_id,
+                    parent_run_id,
+                    step_name: step_name.as_deref(),
+                };
+                pipeline_run_parents::insert(conn, model).map(|_| ())
+            }),
+            Self::TraceId { run_id, trace_id } => {
+                pipeline_runs::update_trace_id(conn, run_id, trace_id).map(|_| ())
+            }
+            Self::DockerCounters {
+                run_id,
+                retries,
+                breaker_trips,
+            } => pipeline_runs::update_docker_counters(
+                conn,
+                run_id,
+                *retries as i32,
+                *breaker_trips as i32,
+            )
+            .map(|_| ()),
+            Self::BuildFailed { run_id } => {
+                pipeline_runs::update_build_failed(conn, run_id).map(|_| ())
+            }
+            Self::Rollup {
+                run_id,
+                rollup,
+                effective_state,
+            } => pipeline_run_parents::update_rollup(conn, run_id, rollup, effective_state),
+            Self::Command {
+                id,
+                run_id,
+                pipeline,
+                step_name,
+                matrix,
+                index,
+                preview,
+                duration_millis,
+            } => {
+                if pipeline_run_commands::select_by_id(conn, id).is_ok() {
+                    return Ok(());
+                }
+                let model = InsertPipelineRunCommand {
+                    id,
+                    run_id,
+                    pipeline,
+                    step_name: step_name.as_deref(),
+                    matrix: matrix.as_deref(),
+                    command_index: *index as i32,
+                    preview,
+                    duration_millis: *duration_millis as i64,
+                };
+                pipeline_run_commands::insert(conn, model).map(|_| ())
+            }
+        }
+    }
+}
+
+/// The updates of a run that couldn't be persisted, shared by the run and the runs of the
+/// pipelines that it calls. The updates are applied in order, so an update is deferred as
+/// well while earlier ones are pending.
+#[derive(Default)]
+pub struct DeferredUpdates {
+    pending: Mutex<VecDeque<ExecutionUpdate>>,
+    retrying: AtomicBool,
+    journal: Option<PathBuf>,
+    deferred: AtomicU64,
+    recovered: AtomicU64,
+    journaled: AtomicU64,
+}
+
+impl DeferredUpdates {
+    pub fn new(journal: Option<PathBuf>) -> Self {
+        Self {
+            journal,
+            ..Default::default()
+        }
+    }
+
+    /// The number of updates that have been deferred, applied after being deferred and
+    /// written to the journal.
+    pub fn counters(&self) -> (u64, u64, u64) {
+        (
+            self.deferred.load(Ordering::Relaxed),
+            self.recovered.load(Ordering::Relaxed),
+            self.journaled.load(Ordering::Relaxed),
+        )
+    }
+
+    pub fn has_pending(&self) -> bool {
+        !self.pending.lock().unwrap().is_empty()
+    }
+
+    /// Applies the update, or defers it and retries in the background if it fails or
+    /// earlier updates are pending. Persistence errors never reach the run.
+    pub fn persist(
+        self: &Arc<Self>,
+        pool: &Arc<Pool<ConnectionManager<SqliteConnection>>>,
+        update: ExecutionUpdate,
+    ) {
+        {
+            let mut pending = self.pending.lock().unwrap();
+            if pending.is_empty() {
+                let result = pool
+                    .get()
+                    .map_err(anyhow::Error::from)
+                    .and_then(|mut conn| update.apply(&mut conn));
+                match result {
+                    Ok(_) => return,
+                    Err(e) => warn!("deferring update of run state, {e}"),
+                }
+            }
+            pending.push_back(update);
+            self.deferred.fetch_add(1, Ordering::Relaxed);
+        }
+        self.retry_in_background(pool.clone());
+    }
+
+    /// Applies the pending updates in order until one fails. Returns true when none are left.
+    fn flush(&self, pool: &Pool<ConnectionManager<SqliteConnection>>) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+        if pending.is_empty() {
+            return true;
+        }
+        let mut conn = match pool.get() {
+            Ok(conn) => conn,
+            Err(e) => {
+                debug!("unable to apply deferred updates, {e}");
+                return false;
+            }
+        };
+        while let Some(update) = pending.front() {
+            if let Err(e) = update.apply(&mut conn) {
+                debug!("unable to apply deferred update, {e}");
+                return false;
+            }
+            pending.pop_front();
+            self.recovered.fetch_add(1, Ordering::Relaxed);
+        }
+        info!("applied the deferred updates of run state");
+        true
+    }
+
+    fn retry_in_background(self: &Arc<Self>, pool: Arc<Pool<ConnectionManager<SqliteConnection>>>) {
+        if self.retrying.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let updates = self.clone();
+        spawn(move || loop {
+            let mut delay = RETRY_BASE_DELAY_MILLIS;
+            loop {
+                sleep(Duration::from_millis(delay));
+                if updates.flush(&pool) {
+                    break;
+                }
+                delay = (delay * 2).min(RETRY_MAX_DELAY_MILLIS);
+            }
+            updates.retrying.store(false, Ordering::Release);
+            // an update could have been deferred after the last flush and before the flag was
+            // cleared, in which case the retries continue in this thread.
+            if !updates.has_pending() || updates.retrying.swap(true, Ordering::AcqRel) {
+                break;
+            }
+        });
+    }
+
+    /// Applies the pending updates at the end of a run, retrying with a backoff for a bounded
+    /// number of attempts. The updates that are still pending are written to the journal so
+    /// that the server applies them when it starts.
+    pub fn reconcile(&self, pool: &Pool<ConnectionManager<SqliteConnection>>) {
+        self.reconcile_with(pool, RECONCILE_ATTEMPTS, RETRY_BASE_DELAY_MILLIS)
+    }
+
+    fn reconcile_with(
+        &self,
+        pool: &Pool<ConnectionManager<SqliteConnection>>,
+        attempts: u32,
+        base_delay_millis: u64,
+    ) {
+        let mut delay = base_delay_millis;
+        for attempt in 1..=attempts {
+            if self.flush(pool) {
+                break;
+            }
+            if attempt < attempts {
+                sleep(Duration::from_millis(delay));
+                delay = (delay * 2).min(RETRY_MAX_DELAY_MILLIS);
+            }
+        }
+        let (deferred, recovered, _) = self.counters();
+        if deferred > 0 {
+            info!(
+                "{deferred} updates of run state were deferred and {recovered} were applied later"
+            );
+        }
+        let mut pending = self.pending.lock().unwrap();
+        if pending.is_empty() {
+            return;
+        }
+        error!(
+            "unable to persist {} updates of run state after {attempts} attempts",
+            pending.len()
+        );
+        let journal = match &self.journal {
+            Some(journal) => journal,
+            None => {
+                error!("no journal available, the updates are lost: {:?}", pending);
+                return;
+            }
+        };
+        match append_journal(journal, pending.iter()) {
+            Ok(_) => {
+                error!(
+                    "the updates were written to {} and will be applied when the server starts",
+                    journal.display()
+                );
+                self.journaled
+                    .fetch_add(pending.len() as u64, Ordering::Relaxed);
+                pending.clear();
+            }
+            Err(e) => error!(
+                "unable to write the updates to {}, {e}. The updates are lost: {:?}",
+                journal.display(),
+                pending
+            ),
+        }
+    }
+}
+
+fn append_journal<'a>(
+    journal: &Path,
+    updates: impl Iterator<Item = &'a ExecutionUpdate>,
+) -> Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(journal)?;
+    for update in updates {
+        writeln!(file, "{}", serde_json::to_string(update)?)?;
+    }
+    file.sync_all()?;
+    Ok(())
+}
+
+/// The journal of the updates of run state that couldn't be persisted, next to the database.
+pub fn execution_journal(db: &str) -> PathBuf {
+    path![db, DB_EXECUTION_JOURNAL]
+}
+
+/// Applies the updates of run state that were written to the journal by runs that couldn't
+/// persist them. The journal is removed when all of them are applied, otherwise the ones
+/// that failed are kept in it for the next start.
+pub fn replay_journal(
+    pool: &Pool<ConnectionManager<SqliteConnection>>,
+    journal: &Path,
+) -> Result<()> {
+    if !journal.is_file() {
+        return Ok(());
+    }
+    let content = read_to_string(journal)?;
+    let mut conn = pool.get()?;
+    let mut failed = vec![];
+    let mut applied = 0;
+    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+        let update: ExecutionUpdate = match serde_json::from_str(line) {
+            Ok(update) => update,
+            Err(e) => {
+                error!("invalid entry in the execution journal, {e}: {line}");
+                continue;
+            }
+        };
+        match update.apply(&mut conn) {
+            Ok(_) => applied += 1,
+            Err(e) => {
+                error!("unable to apply entry of the execution journal, {e}");
+                failed.push(update);
+            }
+        }
+    }
+    info!(
+        "applied {applied} updates of run state from {}",
+        journal.display()
+    );
+    remove_file(journal)?;
+    if !failed.is_empty() {
+        append_journal(journal, failed.iter())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::pipeline_runs::{PR_STATE_FINISHED, PR_STATE_RUNNING};
+    use crate::database::{new_connection_pool, pipeline_run_parents};
+    use crate::execution::Execution;
+    use diesel::connection::SimpleConnection;
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_dir_all};
+    use uuid::Uuid;
+
+    const TABLES: [&str; 4] = [
+        "pipeline_runs",
+        "pipeline_run_checkpoints",
+        "pipeline_run_artifacts",
+        "pipeline_run_parents",
+    ];
+
+    /// Renames the tables that the updates of a run are persisted to, so that every update
+    /// fails as if the database was unavailable.
+    fn outage(pool: &Pool<ConnectionManager<SqliteConnection>>, offline: bool) {
+        let mut conn = pool.get().unwrap();
+        for table in TABLES {
+            let (from, to) = if offline {
+                (table.to_string(), format!("{table}_offline"))
+            } else {
+                (format!("{table}_offline"), table.to_string())
+            };
+            conn.batch_execute(&format!("alter table {from} rename to {to}"))
+                .unwrap();
+        }
+    }
+
+    fn state(pool: &Pool<ConnectionManager<SqliteConnection>>, run_id: &str) -> String {
+        let mut conn = pool.get().unwrap();
+        pipeline_runs::select_by_id(&mut conn, run_id)
+            .unwrap()
+            .state
+    }
+
+    #[test]
+    fn runs_complete_while_the_database_is_unavailable() {
+        let root = temp_dir().join(format!("bld-deferred-{}", Uuid::new_v4()));
+        create_dir_all(&root).unwrap();
+        let db = root.display().to_string();
+        let journal = execution_journal(&db);
+        let pool = Arc::new(new_connection_pool(&db).unwrap());
+        let run_id = Uuid::new_v4().to_string();
+        pipeline_runs::insert(
+            &mut pool.get().unwrap(),
+            &run_id,
+            "parent.yaml",
+            "user",
+            None,
+        )
+        .unwrap();
+
+        let ex = Execution::pipeline_atom(pool.clone(), &run_id, Some(journal.clone()));
+        outage(&pool, true);
+        let child_ex = {
+            let mut exec = ex.lock().unwrap();
+            exec.set_as_running().unwrap();
+            exec.add_checkpoint("built").unwrap();
+            exec.add_artifact("/tmp/app").unwrap();
+            exec.check_stop_signal().unwrap();
+            exec.child_atom("child.yaml", Some("call")).unwrap()
+        };
+        {
+            let mut child = child_ex.lock().unwrap();
+            child.set_as_running().unwrap();
+            child.set_as_finished().unwrap();
+        }
+        ex.lock().unwrap().set_as_finished().unwrap();
+        outage(&pool, false);
+        ex.lock().unwrap().reconcile();
+
+        assert_eq!(state(&pool, &run_id), PR_STATE_FINISHED);
+        let mut conn = pool.get().unwrap();
+        let children = pipeline_run_parents::select_by_parent_run_id(&mut conn, &run_id).unwrap();
+        assert_eq!(children.len(), 1);
+        assert_eq!(state(&pool, &children[0].child_run_id), PR_STATE_FINISHED);
+        let checkpoints = pipeline_run_checkpoints::select_by_run_id(&mut conn, &run_id).unwrap();
+        assert_eq!(checkpoints.len(), 1);
+        let artifacts = pipeline_run_artifacts::select_by_run_id(&mut conn, &run_id).unwrap();
+        assert_eq!(artifacts.len(), 1);
+
+        // an outage that outlasts the end of the run leaves the updates in the journal,
+        // which are applied once the database is available.
+        let run_id = Uuid::new_v4().to_string();
+        pipeline_runs::insert(&mut conn, &run_id, "parent.yaml", "user", None).unwrap();
+        pipeline_runs::update_state(&mut conn, &run_id, PR_STATE_RUNNING).unwrap();
+        drop(conn);
+        let ex = Execution::pipeline_atom(pool.clone(), &run_id, Some(journal.clone()));
+        outage(&pool, true);
+        let mut exec = ex.lock().unwrap();
+        exec.set_as_finished().unwrap();
+        if let Execution::Pipeline { deferred, .. } = &*exec {
+            deferred.reconcile_with(&pool, 2, 10);
+            assert_eq!(deferred.counters(), (1, 0, 1));
+            assert!(!deferred.has_pending());
+        }
+        outage(&pool, false);
+        assert_eq!(state(&pool, &run_id), PR_STATE_RUNNING);
+        replay_journal(&pool, &journal).unwrap();
+        assert_eq!(state(&pool, &run_id), PR_STATE_FINISHED);
+        assert!(!journal.is_file());
+
+        remove_dir_all(root).unwrap();
+    }
+}
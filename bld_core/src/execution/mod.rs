@@ -1,14 +1,50 @@
+mod deferred;
+
+pub use deferred::*;
+
 use crate::database::pipeline_runs::{self, PR_STATE_FAULTED, PR_STATE_FINISHED, PR_STATE_RUNNING};
 use anyhow::{bail, Result};
 use diesel::r2d2::{ConnectionManager, Pool};
 use diesel::sqlite::SqliteConnection;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::warn;
+use uuid::Uuid;
+
+/// The maximum number of characters of the preview of a command in the summary of a run.
+pub const COMMAND_PREVIEW_LENGTH: usize = 60;
+
+/// The first line of a command truncated to the preview length, marking the commands that
+/// have more text. The secrets of the command should be masked before.
+pub fn command_preview(command: &str) -> String {
+    let command = command.trim();
+    let first_line = command.lines().next().unwrap_or_default();
+    let mut preview: String = first_line.chars().take(COMMAND_PREVIEW_LENGTH).collect();
+    if preview.len() < command.len() {
+        preview.push_str("...");
+    }
+    preview
+}
+
+/// The duration of a command of a step, with the command identified by its index in the step
+/// and a preview of its text.
+pub struct CommandTiming<'a> {
+    pub pipeline: &'a str,
+    pub step_name: Option<&'a str>,
+    pub matrix: Option<&'a str>,
+    pub index: usize,
+    pub preview: &'a str,
+    pub duration: Duration,
+}
 
 pub enum Execution {
     Empty,
     Pipeline {
         pool: Arc<Pool<ConnectionManager<SqliteConnection>>>,
         run_id: String,
+        root_run_id: String,
+        deferred: Arc<DeferredUpdates>,
     },
 }
 
@@ -17,26 +53,73 @@ impl Execution {
         Arc::new(Mutex::new(Self::Empty))
     }
 
+    /// Creates the execution of a run whose state is persisted in the database. The updates
+    /// that can't be persisted by the end of the run are written to the journal.
     pub fn pipeline_atom(
         pool: Arc<Pool<ConnectionManager<SqliteConnection>>>,
         run_id: &str,
+        journal: Option<PathBuf>,
     ) -> Arc<Mutex<Self>> {
         Arc::new(Mutex::new(Self::Pipeline {
             pool,
             run_id: run_id.to_string(),
+            root_run_id: run_id.to_string(),
+            deferred: Arc::new(DeferredUpdates::new(journal)),
         }))
     }
 
-    fn update_state(&mut self, state: &str) -> Result<()> {
+    /// Records a run for a pipeline that is called from a step of the current run along with
+    /// its parent. The child shares the stop signal and checkpoints of the run that started the call tree.
+    pub fn child_atom(&self, pipeline: &str, step_name: Option<&str>) -> Result<Arc<Mutex<Self>>> {
         match self {
-            Self::Empty => Ok(()),
-            Self::Pipeline { pool, run_id } => {
-                let mut conn = pool.get()?;
-                pipeline_runs::update_state(&mut conn, run_id, state).map(|_| ())
+            Self::Empty => Ok(Self::empty_atom()),
+            Self::Pipeline {
+                pool,
+                run_id,
+                root_run_id,
+                deferred,
+            } => {
+                let child_run_id = Uuid::new_v4().to_string();
+                let update = ExecutionUpdate::ChildRun {
+                    id: Uuid::new_v4().to_string(),
+                    run_id: child_run_id.clone(),
+                    pipeline: pipeline.to_string(),
+                    parent_run_id: run_id.to_string(),
+                    step_name: step_name.map(|s| s.to_string()),
+                };
+                deferred.persist(pool, update);
+                Ok(Arc::new(Mutex::new(Self::Pipeline {
+                    pool: pool.clone(),
+                    run_id: child_run_id,
+                    root_run_id: root_run_id.clone(),
+                    deferred: deferred.clone(),
+                })))
             }
         }
     }
 
+    /// Persists an update of the run, deferring it when the database is unavailable so that
+    /// the run isn't affected by persistence errors.
+    fn persist(&self, update: impl FnOnce(&str, &str) -> ExecutionUpdate) {
+        if let Self::Pipeline {
+            pool,
+            run_id,
+            root_run_id,
+            deferred,
+        } = self
+        {
+            deferred.persist(pool, update(run_id, root_run_id));
+        }
+    }
+
+    fn update_state(&mut self, state: &str) -> Result<()> {
+        self.persist(|run_id, _| ExecutionUpdate::State {
+            run_id: run_id.to_string(),
+            state: state.to_string(),
+        });
+        Ok(())
+    }
+
     pub fn set_as_running(&mut self) -> Result<()> {
         self.update_state(PR_STATE_RUNNING)
     }
@@ -49,16 +132,125 @@ impl Execution {
         self.update_state(PR_STATE_FAULTED)
     }
 
+    /// Records that the run has reached the named checkpoint.
+    pub fn add_checkpoint(&mut self, name: &str) -> Result<()> {
+        self.persist(|_, root_run_id| ExecutionUpdate::Checkpoint {
+            id: Uuid::new_v4().to_string(),
+            run_id: root_run_id.to_string(),
+            name: name.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Records a path that an artifact of the run has been copied to, so that it can be
+    /// downloaded from the server after the run.
+    pub fn add_artifact(&mut self, artifact_path: &str) -> Result<()> {
+        self.persist(|_, root_run_id| ExecutionUpdate::Artifact {
+            id: Uuid::new_v4().to_string(),
+            run_id: root_run_id.to_string(),
+            path: artifact_path.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Records the id of the trace that the spans of the run are exported with.
+    pub fn set_trace_id(&mut self, trace_id: &str) -> Result<()> {
+        self.persist(|run_id, _| ExecutionUpdate::TraceId {
+            run_id: run_id.to_string(),
+            trace_id: trace_id.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Records the retries and circuit breaker trips of the docker api calls of the run.
+    pub fn set_docker_counters(&mut self, retries: u64, breaker_trips: u64) -> Result<()> {
+        self.persist(|run_id, _| ExecutionUpdate::DockerCounters {
+            run_id: run_id.to_string(),
+            retries,
+            breaker_trips,
+        });
+        Ok(())
+    }
+
+    /// Records the rollup rule of the call of a child run and the state that it contributed
+    /// to its parent.
+    pub fn set_rollup(&mut self, rollup: &str, effective_state: &str) -> Result<()> {
+        self.persist(|run_id, _| ExecutionUpdate::Rollup {
+            run_id: run_id.to_string(),
+            rollup: rollup.to_string(),
+            effective_state: effective_state.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Faults the run when its runner fails to be built, before any of its steps have started.
+    pub fn set_as_build_failed(&mut self) -> Result<()> {
+        self.persist(|run_id, _| ExecutionUpdate::BuildFailed {
+            run_id: run_id.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Records the duration of a command on the run that started the call tree, so that the
+    /// commands of the called pipelines are part of its summary.
+    pub fn add_command(&mut self, timing: &CommandTiming) -> Result<()> {
+        self.persist(|_, root_run_id| ExecutionUpdate::Command {
+            id: Uuid::new_v4().to_string(),
+            run_id: root_run_id.to_string(),
+            pipeline: timing.pipeline.to_string(),
+            step_name: timing.step_name.map(|s| s.to_string()),
+            matrix: timing.matrix.map(|m| m.to_string()),
+            index: timing.index,
+            preview: timing.preview.to_string(),
+            duration_millis: timing.duration.as_millis() as u64,
+        });
+        Ok(())
+    }
+
+    /// Applies the deferred updates of the run and the pipelines that it called, which should
+    /// be done once the final state of the run has been set.
+    pub fn reconcile(&self) {
+        if let Self::Pipeline { pool, deferred, .. } = self {
+            deferred.reconcile(pool);
+        }
+    }
+
     pub fn check_stop_signal(&self) -> Result<()> {
         match self {
             Self::Empty => Ok(()),
-            Self::Pipeline { pool, run_id } => {
-                let mut conn = pool.get()?;
-                pipeline_runs::select_by_id(&mut conn, run_id).and_then(|r| match r.stopped {
-                    Some(true) => bail!(""),
-                    _ => Ok(()),
-                })
+            Self::Pipeline {
+                pool, root_run_id, ..
+            } => {
+                // the run continues when the database is unavailable, since it can't be told
+                // whether the run has been stopped.
+                let run = pool
+                    .get()
+                    .map_err(anyhow::Error::from)
+                    .and_then(|mut conn| pipeline_runs::select_by_id(&mut conn, root_run_id));
+                match run {
+                    Ok(run) if run.stopped == Some(true) => bail!(""),
+                    Ok(_) => Ok(()),
+                    Err(e) => {
+                        warn!("unable to check whether run {root_run_id} has been stopped, {e}");
+                        Ok(())
+                    }
+                }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_previews_are_truncated() {
+        assert_eq!(command_preview("  cargo build  "), "cargo build");
+        assert_eq!(command_preview("echo one\necho two"), "echo one...");
+        let long = "x".repeat(COMMAND_PREVIEW_LENGTH + 1);
+        let preview = command_preview(&long);
+        assert_eq!(preview.len(), COMMAND_PREVIEW_LENGTH + 3);
+        assert!(preview.ends_with("..."));
+    }
+}
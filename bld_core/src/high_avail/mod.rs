@@ -47,9 +47,14 @@ impl HighAvailThread {
     ) -> Result<Self> {
         let (agent, agents) = agent_info(config)?;
         let node_id = agent.id();
+        let secret = config
+            .local
+            .ha_secret
+            .as_deref()
+            .ok_or_else(|| anyhow!("ha-secret not found"))?;
         let raft_config = Arc::new(Config::build("raft-group".into()).validate()?);
         let ids = agents.iter().map(|a| a.id()).collect::<HashSet<NodeId>>();
-        let network = Arc::new(HighAvailRouter::new(raft_config.clone(), agents).await?);
+        let network = Arc::new(HighAvailRouter::new(raft_config.clone(), agents, secret).await?);
         let store = Arc::new(HighAvailStore::new(pool, agent.id())?);
         let raft = HighAvailRaft::new(agent.id(), raft_config, network, store);
         raft.initialize(ids).await.map_err(|e| anyhow!(e))?;
@@ -16,14 +16,23 @@ use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tracing::debug;
 
+/// The header that the nodes of a cluster send their shared secret with, since the raft routes
+/// don't authenticate a user.
+pub const HA_SECRET_HEADER: &str = "X-Bld-Ha-Secret";
+
 pub struct HighAvailRouter {
     config: Arc<Config>,
     agents: HashSet<Agent>,
+    secret: String,
 }
 
 impl HighAvailRouter {
-    pub async fn new(config: Arc<Config>, agents: HashSet<Agent>) -> Result<Self> {
-        Ok(Self { config, agents })
+    pub async fn new(config: Arc<Config>, agents: HashSet<Agent>, secret: &str) -> Result<Self> {
+        Ok(Self {
+            config,
+            agents,
+            secret: secret.to_string(),
+        })
     }
 
     fn agent(&self, id: &NodeId) -> Result<&Agent> {
@@ -41,7 +50,9 @@ impl HighAvailRouter {
     {
         let agent = self.agent(&target)?;
         let url = format!("http://{}:{}{sub_url}", agent.host(), agent.port());
-        request::post(url, HashMap::new(), body).await
+        let mut headers = HashMap::new();
+        headers.insert(HA_SECRET_HEADER.to_string(), self.secret.clone());
+        request::post(url, headers, body).await
     }
 }
 
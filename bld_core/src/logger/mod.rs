@@ -1,129 +1,521 @@
 use anyhow::Result;
-use bld_config::{path, BldConfig};
+use bld_config::{path, BldConfig, LogRedactionRule, LongLogLines};
+use bld_utils::term::{is_verbose, stderr_stream, stdout_stream, write_colored};
+use std::cmp::Reverse;
+use std::fmt::Write as FmtWrite;
 use std::fs::File;
 use std::io::Write;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+use termcolor::Color;
 
-pub enum Logger {
+enum Output {
     Empty,
     Shell,
     File { handle: File },
 }
 
+/// The level of an entry, which sets the stream and the color that it's written with to the
+/// shell. Debug entries are written only when the verbose flag is given.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Level {
+    Plain,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+/// An entry written while the logger is in buffer mode, kept along with its level and whether
+/// it ends the line.
+struct Entry {
+    level: Level,
+    text: String,
+    newline: bool,
+}
+
+pub struct Logger {
+    output: Output,
+    buffer_mode: bool,
+    buffer: Vec<Entry>,
+    redaction: Vec<(LogRedactionRule, usize)>,
+    line_limit: Option<LineLimit>,
+    line_length: Option<LineLength>,
+    secrets: Vec<String>,
+    invalid_output: bool,
+}
+
+/// The text that the values of secrets are replaced with in the output.
+const SECRET_MASK: &str = "****";
+
+/// Keeps the number of lines written to a log file in order to stop writing to it once
+/// the maximum is reached.
+struct LineLimit {
+    max: usize,
+    lines: usize,
+    reached: bool,
+}
+
+impl LineLimit {
+    fn new(max: usize) -> Self {
+        Self {
+            max,
+            lines: 0,
+            reached: false,
+        }
+    }
+
+    /// Returns the part of the text that is within the limit and whether the limit was
+    /// reached by this text, which is true only once.
+    fn apply<'a>(&mut self, text: &'a str) -> (&'a str, bool) {
+        if self.reached {
+            return ("", false);
+        }
+        let mut end = 0;
+        for line in text.split_inclusive('\n') {
+            if self.lines >= self.max {
+                self.reached = true;
+                break;
+            }
+            end += line.len();
+            if line.ends_with('\n') {
+                self.lines += 1;
+            }
+        }
+        (&text[..end], self.reached)
+    }
+}
+
+/// The text that marks the end of a log line that was split or truncated.
+pub const LONG_LINE_MARKER: &str = "…";
+
+/// Keeps the length of the line that is being written to a log file in order to split or
+/// truncate the lines that exceed the maximum, along with the number of lines that did.
+struct LineLength {
+    max: usize,
+    policy: LongLogLines,
+    current: usize,
+    dropped: usize,
+    affected: usize,
+    is_affected: bool,
+}
+
+impl LineLength {
+    fn new(max: usize, policy: LongLogLines) -> Self {
+        Self {
+            max,
+            policy,
+            current: 0,
+            dropped: 0,
+            affected: 0,
+            is_affected: false,
+        }
+    }
+
+    /// Applies the policy to the text, which can continue the line of the previous text.
+    /// Lines are never cut in the middle of a character.
+    fn apply(&mut self, text: &str) -> String {
+        let mut output = String::with_capacity(text.len());
+        for ch in text.chars() {
+            if ch == '\n' {
+                if self.dropped > 0 {
+                    let _ = write!(
+                        output,
+                        "{LONG_LINE_MARKER} [{} bytes dropped]",
+                        self.dropped
+                    );
+                }
+                output.push(ch);
+                self.current = 0;
+                self.dropped = 0;
+                self.is_affected = false;
+                continue;
+            }
+            let len = ch.len_utf8();
+            if self.current + len > self.max {
+                if !self.is_affected {
+                    self.affected += 1;
+                    self.is_affected = true;
+                }
+                match self.policy {
+                    LongLogLines::Split => {
+                        output.push_str(LONG_LINE_MARKER);
+                        output.push('\n');
+                        self.current = 0;
+                    }
+                    LongLogLines::Truncate => {
+                        self.dropped += len;
+                        continue;
+                    }
+                }
+            }
+            output.push(ch);
+            self.current += len;
+        }
+        output
+    }
+}
+
+/// Splits a line that exceeds the maximum length into lines of at most the maximum length,
+/// with every line but the last one ending with the continuation marker.
+pub fn split_long_line(line: &str, max: usize) -> Vec<String> {
+    if line.len() <= max {
+        return vec![line.to_string()];
+    }
+    LineLength::new(max, LongLogLines::Split)
+        .apply(line)
+        .split('\n')
+        .map(|l| l.to_string())
+        .collect()
+}
+
+fn write_file(
+    handle: &mut File,
+    line_length: &mut Option<LineLength>,
+    line_limit: &mut Option<LineLimit>,
+    text: &str,
+) {
+    let text = match line_length {
+        Some(length) => length.apply(text),
+        None => text.to_string(),
+    };
+    let (text, reached) = match line_limit {
+        Some(limit) => limit.apply(&text),
+        None => (text.as_str(), false),
+    };
+    let mut result = write!(handle, "{text}");
+    if let (true, Some(limit)) = (reached, line_limit) {
+        result = result.and_then(|_| {
+            writeln!(
+                handle,
+                "[bld] Log line limit reached ({}), suppressing further output",
+                limit.max
+            )
+        });
+    }
+    if let Err(e) = result {
+        eprintln!("Couldn't write to file: {e}");
+    }
+}
+
+/// The name of the log file for a leg of a matrix run, so that each leg is logged separately
+/// from the main log file of the run.
+pub fn matrix_log_name(run_id: &str, index: usize) -> String {
+    format!("{run_id}-matrix-{index}")
+}
+
 impl Logger {
+    fn new(output: Output) -> Self {
+        Self {
+            output,
+            buffer_mode: false,
+            buffer: vec![],
+            redaction: vec![],
+            line_limit: None,
+            line_length: None,
+            secrets: vec![],
+            invalid_output: false,
+        }
+    }
+
     pub fn empty_atom() -> Arc<Mutex<Self>> {
-        Arc::new(Mutex::new(Self::Empty))
+        Arc::new(Mutex::new(Self::new(Output::Empty)))
     }
 
     pub fn shell_atom() -> Arc<Mutex<Self>> {
-        Arc::new(Mutex::new(Self::Shell))
+        Arc::new(Mutex::new(Self::new(Output::Shell)))
     }
 
     pub fn file_atom(config: Arc<BldConfig>, run_id: &str) -> Result<Arc<Mutex<Self>>> {
         let path = path![&config.local.logs, run_id];
-        Ok(Arc::new(Mutex::new(Self::File {
+        let mut logger = Self::new(Output::File {
             handle: match path.is_file() {
                 true => File::open(&path)?,
                 false => File::create(&path)?,
             },
-        })))
+        });
+        logger.redaction = config
+            .local
+            .log_redaction
+            .iter()
+            .map(|rule| (rule.clone(), 0))
+            .collect();
+        logger.line_limit = config.local.default_max_log_lines.map(LineLimit::new);
+        logger.line_length = Some(LineLength::new(
+            config.local.max_log_line_length,
+            config.local.long_log_lines,
+        ));
+        Ok(Arc::new(Mutex::new(logger)))
     }
 
-    pub fn dump(&mut self, text: &str) {
-        match self {
-            Self::Empty => {}
-            Self::Shell => {
-                print!("{}", text);
-            }
-            Self::File { handle } => {
-                if let Err(e) = write!(handle, "{text}") {
-                    eprintln!("Couldn't write to file: {e}");
-                }
-            }
+    pub fn is_file(&self) -> bool {
+        matches!(self.output, Output::File { .. })
+    }
+
+    /// Stops applying the log redaction rules of the server, for pipelines that are
+    /// allowed to opt out of them.
+    pub fn disable_redaction(&mut self) {
+        self.redaction.clear();
+    }
+
+    /// Limits the number of lines written to the log file, with the output after the limit
+    /// being suppressed. Has no effect for loggers that don't write to a file.
+    pub fn max_lines(&mut self, max: usize) {
+        if self.is_file() {
+            self.line_limit = Some(LineLimit::new(max));
         }
     }
 
-    pub fn dumpln(&mut self, text: &str) {
-        match self {
-            Self::Empty => {}
-            Self::Shell => {
-                println!("{text}");
-            }
-            Self::File { handle } => {
-                if let Err(e) = writeln!(handle, "{text}") {
-                    eprintln!("Couldn't write to file: {e}");
-                }
-            }
+    /// Logs a notice the first time that the output of a command had bytes that weren't valid
+    /// UTF-8, so that the replacement characters in the output are explained.
+    pub fn invalid_output_notice(&mut self) {
+        if self.invalid_output {
+            return;
         }
+        self.invalid_output = true;
+        self.infoln("[bld] The output of a command had bytes that aren't valid UTF-8, which were replaced with \u{FFFD}. Set output-encoding in the pipeline if its commands use another encoding");
     }
 
-    pub fn info(&mut self, text: &str) {
-        match self {
-            Self::Empty => {}
-            Self::Shell => {
-                let mut stdout = StandardStream::stdout(ColorChoice::Always);
-                let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)));
-                let _ = write!(&mut stdout, "{text}");
-                let _ = stdout.set_color(ColorSpec::new().set_fg(None));
-            }
-            Self::File { handle } => {
-                if let Err(e) = write!(handle, "{text}") {
-                    eprintln!("Couldn't write to file: {e}");
-                }
-            }
+    /// The number of lines written to the log file that exceeded the maximum line length.
+    pub fn long_lines(&self) -> usize {
+        self.line_length.as_ref().map(|l| l.affected).unwrap_or(0)
+    }
+
+    /// Masks any occurrence of the value in the output. Unlike the log redaction rules,
+    /// masking applies to every output and can't be disabled. The values are kept longest
+    /// first, so that a value that contains another one is masked as a whole.
+    pub fn add_mask(&mut self, value: &str) {
+        if !value.is_empty() && !self.secrets.iter().any(|s| s == value) {
+            self.secrets.push(value.to_string());
+            self.secrets.sort_by_key(|s| Reverse(s.len()));
         }
     }
 
-    pub fn infoln(&mut self, text: &str) {
-        match self {
-            Self::Empty => {}
-            Self::Shell => {
-                let mut stdout = StandardStream::stdout(ColorChoice::Always);
-                let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)));
-                let _ = writeln!(&mut stdout, "{text}");
-                let _ = stdout.set_color(ColorSpec::new().set_fg(None));
-            }
-            Self::File { handle } => {
-                if let Err(e) = writeln!(handle, "{text}") {
-                    eprintln!("Couldn't write to file: {e}");
-                }
-            }
+    pub fn mask_secrets<'a>(&mut self, values: impl Iterator<Item = &'a String>) {
+        for value in values {
+            self.add_mask(value);
         }
     }
 
-    pub fn error(&mut self, text: &str) {
-        match self {
-            Self::Empty => {}
-            Self::Shell => {
-                let mut stderr = StandardStream::stderr(ColorChoice::Always);
-                let _ = stderr.set_color(ColorSpec::new().set_fg(Some(Color::Red)));
-                let _ = write!(&mut stderr, "{text}");
-                let _ = stderr.set_color(ColorSpec::new().set_fg(None));
-            }
-            Self::File { handle } => {
-                if let Err(e) = write!(handle, "{text}") {
-                    eprintln!("Couldn't write to file: {e}");
-                }
-            }
+    /// The text with the masked values replaced, for text that is sent outside of the logs.
+    pub fn mask(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for secret in self.secrets.iter() {
+            text = text.replace(secret, SECRET_MASK);
         }
+        text
+    }
+
+    /// The number of matches of each log redaction rule so far.
+    pub fn redaction_summary(&self) -> Vec<(String, usize)> {
+        self.redaction
+            .iter()
+            .map(|(rule, count)| (rule.name.to_string(), *count))
+            .collect()
+    }
+
+    /// Masks the secrets before applying the log redaction rules, since a rule that rewrites part
+    /// of a secret would otherwise prevent the secret from being matched and leak the rest of it.
+    fn redact(&mut self, text: &str) -> String {
+        let mut text = self.mask(text);
+        for (rule, count) in self.redaction.iter_mut() {
+            let (redacted, matches) = rule.apply(&text);
+            *count += matches;
+            text = redacted;
+        }
+        text
+    }
+
+    /// In buffer mode the entries are accumulated instead of being written, until they are
+    /// either flushed or discarded.
+    pub fn buffer_mode(&mut self, enabled: bool) {
+        self.buffer_mode = enabled;
+    }
+
+    /// Writes the buffered entries and disables buffer mode.
+    pub fn flush_buffer(&mut self) {
+        self.buffer_mode = false;
+        for entry in std::mem::take(&mut self.buffer) {
+            self.write(entry.level, &entry.text, entry.newline);
+        }
+    }
+
+    /// Drops the buffered entries and disables buffer mode.
+    pub fn discard_buffer(&mut self) {
+        self.buffer_mode = false;
+        self.buffer.clear();
+    }
+
+    pub fn dump(&mut self, text: &str) {
+        self.write(Level::Plain, text, false);
+    }
+
+    pub fn dumpln(&mut self, text: &str) {
+        self.write(Level::Plain, text, true);
+    }
+
+    pub fn debug(&mut self, text: &str) {
+        self.write(Level::Debug, text, false);
+    }
+
+    pub fn debugln(&mut self, text: &str) {
+        self.write(Level::Debug, text, true);
+    }
+
+    pub fn info(&mut self, text: &str) {
+        self.write(Level::Info, text, false);
+    }
+
+    pub fn infoln(&mut self, text: &str) {
+        self.write(Level::Info, text, true);
+    }
+
+    pub fn warn(&mut self, text: &str) {
+        self.write(Level::Warn, text, false);
+    }
+
+    pub fn warnln(&mut self, text: &str) {
+        self.write(Level::Warn, text, true);
+    }
+
+    pub fn error(&mut self, text: &str) {
+        self.write(Level::Error, text, false);
     }
 
     pub fn errorln(&mut self, text: &str) {
-        match self {
-            Self::Empty => {}
-            Self::Shell => {
-                let mut stderr = StandardStream::stderr(ColorChoice::Always);
-                let _ = stderr.set_color(ColorSpec::new().set_fg(Some(Color::Red)));
-                let _ = writeln!(&mut stderr, "{text}");
-                let _ = stderr.set_color(ColorSpec::new().set_fg(None));
+        self.write(Level::Error, text, true);
+    }
+
+    /// Writes the entry to the shell with the color of its level, or as plain text to a file.
+    fn write(&mut self, level: Level, text: &str, newline: bool) {
+        if level == Level::Debug && !is_verbose() {
+            return;
+        }
+        if self.buffer_mode {
+            self.buffer.push(Entry {
+                level,
+                text: text.to_string(),
+                newline,
+            });
+            return;
+        }
+        let mut text = self.redact(text);
+        if newline {
+            text.push('\n');
+        }
+        match &mut self.output {
+            Output::Empty => {}
+            Output::Shell => {
+                let (mut stream, color) = match level {
+                    Level::Plain => {
+                        print!("{text}");
+                        return;
+                    }
+                    Level::Debug => (stdout_stream(), Color::Cyan),
+                    Level::Info => (stdout_stream(), Color::Green),
+                    Level::Warn => (stderr_stream(), Color::Yellow),
+                    Level::Error => (stderr_stream(), Color::Red),
+                };
+                let _ = write_colored(&mut stream, color, &text);
             }
-            Self::File { handle } => {
-                if let Err(e) = writeln!(handle, "{text}") {
-                    eprintln!("Couldn't write to file: {e}");
-                }
+            Output::File { handle } => {
+                write_file(handle, &mut self.line_length, &mut self.line_limit, &text)
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bld_utils::term::set_verbose;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn line_limit_suppresses_the_lines_after_the_maximum() {
+        let mut limit = LineLimit::new(2);
+        assert_eq!(limit.apply("first\nsec"), ("first\nsec", false));
+        assert_eq!(limit.apply("ond\nthird\n"), ("ond\n", true));
+        assert_eq!(limit.apply("fourth\n"), ("", false));
+    }
+
+    #[test]
+    fn line_length_splits_or_truncates_the_lines_after_the_maximum() {
+        let mut length = LineLength::new(4, LongLogLines::Split);
+        assert_eq!(length.apply("abc"), "abc");
+        assert_eq!(length.apply("défg\nok\n"), "d…\néfg\nok\n");
+        assert_eq!(length.affected, 1);
+
+        let mut length = LineLength::new(4, LongLogLines::Truncate);
+        assert_eq!(length.apply("abcdef"), "abcd");
+        assert_eq!(length.apply("gh\nok\n"), "… [4 bytes dropped]\nok\n");
+        assert_eq!(length.affected, 1);
+
+        assert_eq!(
+            split_long_line("abcdefghij", 4),
+            vec!["abcd…", "efgh…", "ij"]
+        );
+    }
+
+    #[test]
+    fn logger_masks_the_values_of_secrets() {
+        let mut logger = Logger::new(Output::Empty);
+        let secrets = ["s3cr3t".to_string(), String::new()];
+        logger.mask_secrets(secrets.iter());
+        logger.add_mask("p4ss");
+        logger.add_mask("");
+        logger.disable_redaction();
+
+        assert_eq!(
+            logger.redact("curl -H 'token: s3cr3t' -d s3cr3t -u user:p4ss"),
+            "curl -H 'token: ****' -d **** -u user:****"
+        );
+    }
+
+    #[test]
+    fn logger_masks_the_longest_secrets_first() {
+        let mut logger = Logger::new(Output::Empty);
+        logger.add_mask("abc");
+        logger.add_mask("abcdef");
+        logger.add_mask("def");
+
+        assert_eq!(logger.redact("abcdef abc def"), "**** **** ****");
+    }
+
+    #[test]
+    fn logger_masks_secrets_before_applying_the_redaction_rules() {
+        let yaml =
+            YamlLoader::load_from_str("name: digits\npattern: '[0-9]+'\nreplacement: '#'").unwrap();
+        let mut logger = Logger::new(Output::Empty);
+        logger.redaction = vec![(LogRedactionRule::load(&yaml[0]).unwrap(), 0)];
+        logger.add_mask("abc123xyz");
+
+        assert_eq!(
+            logger.redact("token abc123xyz port 80"),
+            "token **** port #"
+        );
+        assert_eq!(logger.redaction_summary(), vec![("digits".to_string(), 1)]);
+    }
+
+    #[test]
+    fn file_logger_writes_levels_as_plain_text_and_debug_only_when_verbose() {
+        let path = std::env::temp_dir().join(format!("bld-levels-{}", std::process::id()));
+        let mut logger = Logger::new(Output::File {
+            handle: File::create(&path).unwrap(),
+        });
+        set_verbose(false);
+        logger.debugln("hidden");
+        set_verbose(true);
+        logger.debugln("debug");
+        set_verbose(false);
+        logger.infoln("info");
+        logger.warnln("warn");
+        logger.errorln("error");
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "debug\ninfo\nwarn\nerror\n"
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,334 @@
+use crate::proxies::content_hash;
+use anyhow::Result;
+use notify::{recommended_watcher, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::fs::{create_dir_all, metadata, read_to_string, Metadata};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tracing::{debug, error, warn};
+
+/// The content of a pipeline along with its hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedPipeline {
+    pub content: String,
+    pub hash: String,
+}
+
+/// The hits and misses of the cache since the server started, for the metrics of the server.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PipelineCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    pub watched: bool,
+}
+
+struct CacheEntry {
+    pipeline: CachedPipeline,
+    modified: Option<SystemTime>,
+    len: u64,
+    used: u64,
+}
+
+#[derive(Default)]
+struct CacheEntries {
+    pipelines: HashMap<PathBuf, CacheEntry>,
+    /// Advanced on every invalidation, so that a read that started before an invalidation
+    /// doesn't cache the content that it read.
+    generation: u64,
+    tick: u64,
+}
+
+impl CacheEntries {
+    fn invalidate(&mut self, key: Option<&Path>) {
+        self.generation += 1;
+        match key {
+            Some(key) => {
+                self.pipelines.remove(key);
+            }
+            None => self.pipelines.clear(),
+        }
+    }
+}
+
+/// A read through cache of the pipelines of a server keyed by their path relative to the
+/// pipelines directory. The entries are invalidated by a watcher of the directory so that
+/// changes made outside of the server are picked up, and if the watcher can't be started or
+/// fails the modification time of a pipeline is checked on every read instead.
+pub struct PipelineCache {
+    root: PathBuf,
+    capacity: usize,
+    entries: Arc<Mutex<CacheEntries>>,
+    watched: Arc<AtomicBool>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    _watcher: Option<RecommendedWatcher>,
+}
+
+impl PipelineCache {
+    pub fn new(root: &Path, capacity: usize) -> Self {
+        let entries = Arc::new(Mutex::new(CacheEntries::default()));
+        let watched = Arc::new(AtomicBool::new(false));
+        let watcher = match watch(root, Arc::clone(&entries), Arc::clone(&watched)) {
+            Ok(watcher) => {
+                watched.store(true, Ordering::Relaxed);
+                Some(watcher)
+            }
+            Err(e) => {
+                warn!(
+                    "unable to watch the pipelines at {}, checking their modification time on every read instead. {e}",
+                    root.display()
+                );
+                None
+            }
+        };
+        Self {
+            root: root.to_path_buf(),
+            capacity,
+            entries,
+            watched,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            _watcher: watcher,
+        }
+    }
+
+    /// A cache that checks the modification time of the pipelines on every read, as when
+    /// the watcher fails.
+    pub fn unwatched(root: &Path, capacity: usize) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            capacity,
+            entries: Arc::new(Mutex::new(CacheEntries::default())),
+            watched: Arc::new(AtomicBool::new(false)),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            _watcher: None,
+        }
+    }
+
+    pub fn read(&self, path: &Path) -> Result<CachedPipeline> {
+        let key = self.key(path);
+        let file = if self.watched.load(Ordering::Relaxed) {
+            None
+        } else {
+            Some(metadata(path)?)
+        };
+        let generation = {
+            let mut entries = self.entries.lock().unwrap();
+            entries.tick += 1;
+            let tick = entries.tick;
+            if let Some(entry) = entries.pipelines.get_mut(&key) {
+                let fresh = match &file {
+                    Some(file) => entry.modified == file.modified().ok() && entry.len == file.len(),
+                    None => true,
+                };
+                if fresh {
+                    entry.used = tick;
+                    self.hits.fetch_add(1, Ordering::Relaxed);
+                    return Ok(entry.pipeline.clone());
+                }
+            }
+            entries.generation
+        };
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let content = read_to_string(path)?;
+        let pipeline = CachedPipeline {
+            hash: content_hash(&content),
+            content,
+        };
+        let mut entries = self.entries.lock().unwrap();
+        if entries.generation == generation {
+            let used = entries.tick;
+            let entry = CacheEntry {
+                pipeline: pipeline.clone(),
+                modified: file.as_ref().and_then(|f| f.modified().ok()),
+                len: file.as_ref().map_or(0, Metadata::len),
+                used,
+            };
+            entries.pipelines.insert(key, entry);
+            self.evict(&mut entries);
+        }
+        Ok(pipeline)
+    }
+
+    /// Removes the pipeline from the cache, for the changes made by the server itself to be
+    /// visible without waiting for the watcher.
+    pub fn invalidate(&self, path: &Path) {
+        let key = self.key(path);
+        self.entries.lock().unwrap().invalidate(Some(&key));
+    }
+
+    pub fn stats(&self) -> PipelineCacheStats {
+        PipelineCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.entries.lock().unwrap().pipelines.len(),
+            watched: self.watched.load(Ordering::Relaxed),
+        }
+    }
+
+    fn key(&self, path: &Path) -> PathBuf {
+        path.strip_prefix(&self.root).unwrap_or(path).to_path_buf()
+    }
+
+    /// Removes the least recently read pipelines that exceed the capacity of the cache.
+    fn evict(&self, entries: &mut CacheEntries) {
+        while entries.pipelines.len() > self.capacity {
+            let oldest = entries
+                .pipelines
+                .iter()
+                .min_by_key(|(_, entry)| entry.used)
+                .map(|(key, _)| key.clone());
+            match oldest {
+                Some(key) => {
+                    entries.pipelines.remove(&key);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+/// Watches the pipelines directory, invalidating the pipelines of every event and the whole
+/// cache for the events that can't be attributed to a pipeline. An error of the watcher or the
+/// removal of the directory disables the watcher so that reads check the modification time.
+fn watch(
+    root: &Path,
+    entries: Arc<Mutex<CacheEntries>>,
+    watched: Arc<AtomicBool>,
+) -> Result<RecommendedWatcher> {
+    create_dir_all(root)?;
+    let watched_root = root.to_path_buf();
+    let mut watcher = recommended_watcher(move |event: notify::Result<Event>| {
+        let mut entries = entries.lock().unwrap();
+        match event {
+            Ok(event) if event.need_rescan() => entries.invalidate(None),
+            Ok(event) => {
+                for path in event.paths.iter() {
+                    match path.strip_prefix(&watched_root) {
+                        Ok(key) if !key.as_os_str().is_empty() => entries.invalidate(Some(key)),
+                        _ => {
+                            if matches!(event.kind, EventKind::Remove(_)) {
+                                error!(
+                                    "the pipelines directory {} was removed, no longer watching it",
+                                    path.display()
+                                );
+                                watched.store(false, Ordering::Relaxed);
+                            }
+                            entries.invalidate(None);
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                error!("the watcher of the pipelines failed, no longer watching them. {e}");
+                watched.store(false, Ordering::Relaxed);
+                entries.invalidate(None);
+            }
+        }
+    })?;
+    watcher.watch(root, RecursiveMode::Recursive)?;
+    debug!("watching the pipelines at {}", root.display());
+    Ok(watcher)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::{remove_dir_all, write};
+    use std::thread::sleep;
+    use std::time::{Duration, Instant};
+    use uuid::Uuid;
+
+    const WATCH_LATENCY_MILLIS: u64 = 5000;
+
+    fn pipelines_dir() -> PathBuf {
+        let root = temp_dir().join(format!("bld-cache-{}", Uuid::new_v4()));
+        create_dir_all(&root).unwrap();
+        root
+    }
+
+    /// Reads the pipeline until its content changes or the latency of the watcher expires.
+    fn read_changed(cache: &PipelineCache, path: &Path, old: &str) -> String {
+        let start = Instant::now();
+        loop {
+            let content = cache.read(path).unwrap().content;
+            if content != old || start.elapsed() > Duration::from_millis(WATCH_LATENCY_MILLIS) {
+                return content;
+            }
+            sleep(Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn external_modifications_are_picked_up_by_the_watcher() {
+        let root = pipelines_dir();
+        let path = root.join("build.yaml");
+        write(&path, "old").unwrap();
+        let cache = PipelineCache::new(&root, 8);
+        assert!(cache.stats().watched);
+
+        assert_eq!(cache.read(&path).unwrap().content, "old");
+        assert_eq!(cache.read(&path).unwrap().content, "old");
+        assert_eq!(cache.stats().hits, 1);
+        assert_eq!(cache.stats().misses, 1);
+
+        write(&path, "new").unwrap();
+        assert_eq!(read_changed(&cache, &path, "old"), "new");
+        assert_eq!(cache.read(&path).unwrap().hash, content_hash("new"));
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn modification_times_are_checked_without_a_watcher() {
+        let root = pipelines_dir();
+        let path = root.join("build.yaml");
+        write(&path, "old").unwrap();
+        let cache = PipelineCache::unwatched(&root, 8);
+
+        assert_eq!(cache.read(&path).unwrap().content, "old");
+        assert_eq!(cache.read(&path).unwrap().content, "old");
+        assert_eq!(cache.stats().hits, 1);
+
+        write(&path, "newer").unwrap();
+        assert_eq!(cache.read(&path).unwrap().content, "newer");
+        assert_eq!(cache.stats().misses, 2);
+
+        cache.invalidate(&path);
+        assert_eq!(cache.read(&path).unwrap().content, "newer");
+        assert_eq!(cache.stats().misses, 3);
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn least_recently_read_pipelines_are_evicted() {
+        let root = pipelines_dir();
+        let paths: Vec<PathBuf> = (0..3).map(|i| root.join(format!("{i}.yaml"))).collect();
+        for path in paths.iter() {
+            write(path, "content").unwrap();
+        }
+        let cache = PipelineCache::unwatched(&root, 2);
+
+        cache.read(&paths[0]).unwrap();
+        cache.read(&paths[1]).unwrap();
+        cache.read(&paths[0]).unwrap();
+        cache.read(&paths[2]).unwrap();
+        assert_eq!(cache.stats().entries, 2);
+
+        // the second pipeline was the least recently read so it is read again.
+        cache.read(&paths[0]).unwrap();
+        cache.read(&paths[2]).unwrap();
+        assert_eq!(cache.stats().hits, 3);
+        cache.read(&paths[1]).unwrap();
+        assert_eq!(cache.stats().misses, 4);
+        assert_eq!(cache.stats().entries, 2);
+
+        remove_dir_all(root).unwrap();
+    }
+}
@@ -1,36 +1,121 @@
+mod cache;
+
+pub use cache::*;
+
 use crate::database::pipeline;
 use anyhow::{anyhow, bail};
-use bld_config::{definitions::TOOL_DIR, path, BldConfig};
+use bld_config::definitions::{REMOTE_PIPELINE_HASHES, TOOL_DIR};
+use bld_config::{path, BldConfig};
 use bld_utils::fs::IsYaml;
 use diesel::r2d2::{ConnectionManager, Pool};
 use diesel::sqlite::SqliteConnection;
+use sha2::{Digest, Sha256};
 use std::{
-    fs::{create_dir_all, read_to_string, remove_file, File},
+    env::temp_dir,
+    fs::{
+        create_dir_all, read_to_string, remove_dir_all, remove_file, rename, symlink_metadata,
+        write, File,
+    },
     io::Write,
-    path::PathBuf,
+    path::{Component, Path, PathBuf},
+    process::Command,
     sync::Arc,
 };
+use tracing::{debug, error};
+use uuid::Uuid;
 
 pub enum PipelineFileSystemProxy {
     Local,
     Server {
         config: Arc<BldConfig>,
         pool: Arc<Pool<ConnectionManager<SqliteConnection>>>,
+        cache: Option<Arc<PipelineCache>>,
+    },
+    /// A read only proxy for the pipelines of a git repository that has been cloned in a
+    /// temp dir, with the pipelines resolved relative to its root.
+    Git {
+        root: PathBuf,
     },
 }
 
 impl PipelineFileSystemProxy {
+    /// A proxy for the pipelines of a server that are read through a cache, for the servers
+    /// whose pipelines are on a slow filesystem.
+    pub fn server(
+        config: Arc<BldConfig>,
+        pool: Arc<Pool<ConnectionManager<SqliteConnection>>>,
+    ) -> Self {
+        let cache = PipelineCache::new(
+            Path::new(&config.local.server.pipelines),
+            config.local.server.pipeline_cache_size,
+        );
+        Self::Server {
+            config,
+            pool,
+            cache: Some(Arc::new(cache)),
+        }
+    }
+
+    /// Clones the git repository in a temp dir, checking out the provided branch, tag or
+    /// commit. Authentication for private repositories is left to git, for example
+    /// by using an ssh-agent.
+    pub fn git(url: &str, git_ref: Option<&str>) -> anyhow::Result<Self> {
+        validate_git_source(url, git_ref)?;
+        let root = temp_dir().join(format!("bld-git-{}", Uuid::new_v4()));
+        let clone = match git_ref {
+            Some(git_ref) => git(&["init", "-q", path_str(&root)?], None)
+                .and_then(|_| {
+                    git(
+                        &["fetch", "-q", "--depth", "1", "--", url, git_ref],
+                        Some(&root),
+                    )
+                })
+                .and_then(|_| git(&["checkout", "-q", "--detach", "FETCH_HEAD"], Some(&root))),
+            None => git(
+                &["clone", "-q", "--depth", "1", "--", url, path_str(&root)?],
+                None,
+            ),
+        };
+        if let Err(e) = clone {
+            let _ = remove_dir_all(&root);
+            bail!("unable to clone {url}, {e}");
+        }
+        debug!("cloned {url} at {}", root.display());
+        Ok(Self::Git { root })
+    }
+
+    /// Removes the clone of a git proxy.
+    pub fn dispose(&self) -> anyhow::Result<()> {
+        if let Self::Git { root } = self {
+            if root.is_dir() {
+                remove_dir_all(root)?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn path(&self, name: &str) -> anyhow::Result<PathBuf> {
         match self {
             Self::Local => Ok(path![std::env::current_dir()?, TOOL_DIR, name]),
-            Self::Server { config, pool } => {
+            Self::Server { config, pool, .. } => {
                 let mut conn = pool.get()?;
+                let name = config.local.canonical_pipeline(name);
                 let pip = pipeline::select_by_name(&mut conn, name)?;
                 Ok(path![
                     &config.local.server.pipelines,
                     format!("{}.yaml", pip.id)
                 ])
             }
+            Self::Git { root } => git_pipeline_path(root, name),
+        }
+    }
+
+    /// The name of the pipeline that a name refers to, following the pipeline aliases of
+    /// the server.
+    pub fn canonical_name(&self, name: &str) -> String {
+        match self {
+            Self::Local | Self::Git { root: _ } => name.to_string(),
+            Self::Server { config, .. } => config.local.canonical_pipeline(name).to_string(),
         }
     }
 
@@ -40,13 +125,29 @@ impl PipelineFileSystemProxy {
                 let path = self.path(name)?;
                 Ok(read_to_string(path)?)
             }
-            Self::Server { config: _, pool: _ } => {
+            Self::Server {
+                cache: Some(cache), ..
+            } => {
+                let path = self.path(name)?;
+                cache
+                    .read(&path)
+                    .map(|pipeline| pipeline.content)
+                    .map_err(|_| anyhow!("pipeline not found"))
+            }
+            Self::Server { cache: None, .. } => {
                 let path = self.path(name)?;
                 if path.is_yaml() {
                     return Ok(read_to_string(path)?);
                 }
                 Err(anyhow!("pipeline not found"))
             }
+            Self::Git { root } => {
+                let path = self.path(name)?;
+                if is_git_pipeline(root, &path) {
+                    return Ok(read_to_string(path)?);
+                }
+                Err(anyhow!("pipeline not found in the git repository"))
+            }
         }
     }
 
@@ -63,7 +164,7 @@ impl PipelineFileSystemProxy {
                 handle.write_all(content.as_bytes())?;
                 Ok(())
             }
-            Self::Server { config: _, pool: _ } => {
+            Self::Server { cache, .. } => {
                 let path = self.path(name)?;
                 if path.is_yaml() {
                     remove_file(&path)?;
@@ -71,8 +172,72 @@ impl PipelineFileSystemProxy {
                     create_dir_all(parent)?;
                 }
                 let mut handle = File::create(&path)?;
-                handle.write_all(content.as_bytes())?;
-                Ok(())
+                let written = handle.write_all(content.as_bytes());
+                if let Some(cache) = cache {
+                    cache.invalidate(&path);
+                }
+                Ok(written?)
+            }
+            Self::Git { root: _ } => bail!("pipelines of a git repository are read only"),
+        }
+    }
+
+    /// Creates a set of pipelines all at once. The contents are written to a staging dir next to
+    /// the pipelines and are renamed over them only after all of them were written, restoring
+    /// the pipelines that were already replaced if one of the renames fails.
+    pub fn create_all(&self, pipelines: &[(String, String)]) -> anyhow::Result<()> {
+        let root = match self {
+            Self::Local => path![std::env::current_dir()?, TOOL_DIR],
+            Self::Server { config, .. } => path![&config.local.server.pipelines],
+            Self::Git { root: _ } => bail!("pipelines of a git repository are read only"),
+        };
+        let inserted = self.insert_missing(pipelines)?;
+        let staging = root.join(format!(".staging-{}", Uuid::new_v4()));
+        let result = create_dir_all(&staging)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| {
+                let mut files = vec![];
+                for (i, (name, content)) in pipelines.iter().enumerate() {
+                    let staged = staging.join(i.to_string());
+                    write(&staged, content)?;
+                    files.push((staged, self.path(name)?));
+                }
+                let swapped = swap_files(&files, &staging);
+                self.invalidate(files.iter().map(|(_, target)| target));
+                swapped
+            });
+        let _ = remove_dir_all(&staging);
+        if result.is_err() {
+            self.delete_inserted(&inserted);
+        }
+        result
+    }
+
+    /// Inserts the server pipelines of the set that don't exist yet and returns their names.
+    fn insert_missing(&self, pipelines: &[(String, String)]) -> anyhow::Result<Vec<String>> {
+        let mut inserted = vec![];
+        if let Self::Server { pool, .. } = self {
+            let mut conn = pool.get()?;
+            for (name, _) in pipelines {
+                if pipeline::select_by_name(&mut conn, name).is_err() {
+                    let id = Uuid::new_v4().to_string();
+                    if let Err(e) = pipeline::insert(&mut conn, &id, name) {
+                        self.delete_inserted(&inserted);
+                        return Err(e);
+                    }
+                    inserted.push(name.to_string());
+                }
+            }
+        }
+        Ok(inserted)
+    }
+
+    fn delete_inserted(&self, names: &[String]) {
+        if let Self::Server { pool, .. } = self {
+            if let Ok(mut conn) = pool.get() {
+                for name in names {
+                    let _ = pipeline::delete_by_name(&mut conn, name);
+                }
             }
         }
     }
@@ -86,17 +251,285 @@ impl PipelineFileSystemProxy {
                 }
                 Ok(())
             }
-            Self::Server { config: _, pool } => {
+            Self::Server { config, pool, .. } => {
                 let path = self.path(name)?;
                 if path.is_yaml() {
                     let mut conn = pool.get()?;
-                    pipeline::delete_by_name(&mut conn, name)
-                        .and_then(|_| remove_file(path).map_err(|e| anyhow!(e)))
-                        .map_err(|_| anyhow!("unable to remove pipeline"))
+                    let name = config.local.canonical_pipeline(name);
+                    let removed = pipeline::delete_by_name(&mut conn, name)
+                        .and_then(|_| remove_file(&path).map_err(|e| anyhow!(e)))
+                        .map_err(|_| anyhow!("unable to remove pipeline"));
+                    self.invalidate([&path]);
+                    removed
                 } else {
                     bail!("pipeline not found")
                 }
             }
+            Self::Git { root: _ } => bail!("pipelines of a git repository are read only"),
+        }
+    }
+
+    /// The hash of the content of a pipeline, which is kept in the cache of a server.
+    pub fn hash(&self, name: &str) -> anyhow::Result<String> {
+        match self {
+            Self::Server {
+                cache: Some(cache), ..
+            } => {
+                let path = self.path(name)?;
+                cache
+                    .read(&path)
+                    .map(|pipeline| pipeline.hash)
+                    .map_err(|_| anyhow!("pipeline not found"))
+            }
+            _ => self.read(name).map(|content| content_hash(&content)),
+        }
+    }
+
+    /// The hits and misses of the cache of a server proxy.
+    pub fn cache_stats(&self) -> Option<PipelineCacheStats> {
+        match self {
+            Self::Server {
+                cache: Some(cache), ..
+            } => Some(cache.stats()),
+            _ => None,
         }
     }
+
+    fn invalidate<'a>(&self, paths: impl IntoIterator<Item = &'a PathBuf>) {
+        if let Self::Server {
+            cache: Some(cache), ..
+        } = self
+        {
+            for path in paths {
+                cache.invalidate(path);
+            }
+        }
+    }
+}
+
+/// Renames the staged files over their targets, moving the replaced files to the backup dir so
+/// that every target is restored if one of the renames fails.
+fn swap_files(files: &[(PathBuf, PathBuf)], backups: &Path) -> anyhow::Result<()> {
+    let mut swapped: Vec<(&PathBuf, Option<PathBuf>)> = vec![];
+    let mut result = Ok(());
+    for (i, (staged, target)) in files.iter().enumerate() {
+        let backup = target
+            .is_file()
+            .then(|| backups.join(format!("{i}.backup")));
+        let moved = match &backup {
+            Some(backup) => rename(target, backup),
+            None => target.parent().map_or(Ok(()), create_dir_all),
+        };
+        if let Err(e) = moved {
+            result = Err(e);
+            break;
+        }
+        swapped.push((target, backup));
+        if let Err(e) = rename(staged, target) {
+            result = Err(e);
+            break;
+        }
+    }
+    if let Err(e) = result {
+        for (target, backup) in swapped.into_iter().rev() {
+            let restored = match backup {
+                Some(backup) => rename(backup, target),
+                None if target.is_file() => remove_file(target),
+                None => Ok(()),
+            };
+            if let Err(e) = restored {
+                error!("unable to restore {}. {e}", target.display());
+            }
+        }
+        bail!("unable to replace the pipelines. {e}");
+    }
+    Ok(())
+}
+
+/// The path of a pipeline of a git repository. Names are relative to the .bld directory of the
+/// repository, so absolute names and names with parent directories are rejected in order for a
+/// client to not read files outside of the clone.
+fn git_pipeline_path(root: &Path, name: &str) -> anyhow::Result<PathBuf> {
+    let is_relative = Path::new(name)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if name.is_empty() || !is_relative {
+        bail!("invalid pipeline name {name}");
+    }
+    Ok(path![root, TOOL_DIR, name])
+}
+
+/// Checks that a pipeline of a git repository is a yaml file inside the clone. Symlinks are not
+/// followed, neither for the pipeline nor for its directories, since the repository controls
+/// where they point to.
+fn is_git_pipeline(root: &Path, path: &Path) -> bool {
+    let is_file = symlink_metadata(path)
+        .map(|m| m.file_type().is_file())
+        .unwrap_or(false);
+    let is_inside = match (root.canonicalize(), path.canonicalize()) {
+        (Ok(root), Ok(path)) => path.starts_with(root),
+        _ => false,
+    };
+    is_file && is_inside && path.is_yaml()
+}
+
+fn path_str(path: &Path) -> anyhow::Result<&str> {
+    path.to_str()
+        .ok_or_else(|| anyhow!("invalid path {}", path.display()))
+}
+
+/// Accepts only https and ssh repositories, including the scp like syntax of ssh such as
+/// `git@host:org/repo.git`, so that a client can't read the repositories of the server host
+/// through file urls or local paths. Values starting with a dash are rejected as well since
+/// git would parse them as options.
+fn validate_git_source(url: &str, git_ref: Option<&str>) -> anyhow::Result<()> {
+    if url.starts_with('-') {
+        bail!("invalid git url {url}");
+    }
+    let scp_like = match url.split_once(':') {
+        Some((host, path)) => host.contains('@') && !host.contains('/') && !path.starts_with("//"),
+        None => false,
+    };
+    if !url.starts_with("https://") && !url.starts_with("ssh://") && !scp_like {
+        bail!("invalid git url {url}, only https and ssh repositories are supported");
+    }
+    if let Some(git_ref) = git_ref {
+        if git_ref.is_empty() || git_ref.starts_with('-') {
+            bail!("invalid git ref {git_ref}");
+        }
+    }
+    Ok(())
+}
+
+fn git(args: &[&str], cwd: Option<&Path>) -> anyhow::Result<()> {
+    let mut command = Command::new("git");
+    command
+        .args(args)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .env("GIT_ALLOW_PROTOCOL", "https:ssh");
+    if let Some(cwd) = cwd {
+        command.current_dir(cwd);
+    }
+    let output = command.output()?;
+    if !output.status.success() {
+        bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+/// The hash of the content of a pipeline, used by the server to detect pushes that are
+/// based on an outdated version of the pipeline.
+pub fn content_hash(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+/// Stores the hash of a pipeline as it was last pulled from or pushed to a server.
+pub fn save_base_hash(server: &str, name: &str, content: &str) -> anyhow::Result<()> {
+    let path = path![REMOTE_PIPELINE_HASHES, server, name];
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+    write(path, content_hash(content))?;
+    Ok(())
+}
+
+/// The hash of a pipeline as it was last pulled from or pushed to a server, if any.
+pub fn base_hash(server: &str, name: &str) -> Option<String> {
+    read_to_string(path![REMOTE_PIPELINE_HASHES, server, name]).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::new_connection_pool;
+
+    #[test]
+    fn server_pipelines_are_created_all_at_once() {
+        let root = temp_dir().join(format!("bld-create-all-{}", Uuid::new_v4()));
+        create_dir_all(&root).unwrap();
+        let mut config = BldConfig::default();
+        config.local.server.pipelines = root.display().to_string();
+        let pool = Arc::new(new_connection_pool(&root.display().to_string()).unwrap());
+        pipeline::insert(&mut pool.get().unwrap(), "base-id", "base.yaml").unwrap();
+        let prx = PipelineFileSystemProxy::server(Arc::new(config), pool);
+        prx.create("base.yaml", "old").unwrap();
+
+        let pipelines = vec![
+            ("base.yaml".to_string(), "new".to_string()),
+            ("caller.yaml".to_string(), "caller".to_string()),
+        ];
+        prx.create_all(&pipelines).unwrap();
+        assert_eq!(prx.read("base.yaml").unwrap(), "new");
+        assert_eq!(prx.read("caller.yaml").unwrap(), "caller");
+
+        // the second rename fails since its staged file is missing, so the first is restored.
+        let staging = root.join("staging");
+        create_dir_all(&staging).unwrap();
+        write(staging.join("0"), "newer").unwrap();
+        let base = prx.path("base.yaml").unwrap();
+        let other = root.join("other.yaml");
+        let files = vec![
+            (staging.join("0"), base),
+            (staging.join("1"), other.clone()),
+        ];
+        assert!(swap_files(&files, &staging).is_err());
+        assert_eq!(prx.read("base.yaml").unwrap(), "new");
+        assert!(!other.exists());
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn git_pipelines_are_read_only_inside_the_clone() {
+        let root = temp_dir().join(format!("bld-git-read-{}", Uuid::new_v4()));
+        let outside = temp_dir().join(format!("bld-git-outside-{}", Uuid::new_v4()));
+        create_dir_all(root.join(TOOL_DIR).join("nested")).unwrap();
+        create_dir_all(&outside).unwrap();
+        write(root.join(TOOL_DIR).join("nested/build.yaml"), "build").unwrap();
+        write(outside.join("secret.yaml"), "secret").unwrap();
+        let prx = PipelineFileSystemProxy::Git { root: root.clone() };
+
+        assert_eq!(prx.read("nested/build.yaml").unwrap(), "build");
+        let escape = format!(
+            "../../{}/secret.yaml",
+            outside.file_name().unwrap().to_string_lossy()
+        );
+        assert!(prx.read(&escape).is_err());
+        assert!(prx
+            .read(&outside.join("secret.yaml").display().to_string())
+            .is_err());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::symlink;
+            symlink(
+                outside.join("secret.yaml"),
+                root.join(TOOL_DIR).join("link.yaml"),
+            )
+            .unwrap();
+            symlink(&outside, root.join(TOOL_DIR).join("dir")).unwrap();
+            assert!(prx.read("link.yaml").is_err());
+            assert!(prx.read("dir/secret.yaml").is_err());
+        }
+
+        remove_dir_all(root).unwrap();
+        remove_dir_all(outside).unwrap();
+    }
+
+    #[test]
+    fn git_sources_are_limited_to_https_and_ssh() {
+        assert!(validate_git_source("https://github.com/org/repo.git", Some("main")).is_ok());
+        assert!(validate_git_source("ssh://git@github.com/org/repo.git", None).is_ok());
+        assert!(validate_git_source("git@github.com:org/repo.git", None).is_ok());
+
+        assert!(validate_git_source("--upload-pack=touch /tmp/pwned", None).is_err());
+        assert!(
+            validate_git_source("https://github.com/org/repo.git", Some("--upload-pack=id"))
+                .is_err()
+        );
+        assert!(validate_git_source("file:///etc/repo", None).is_err());
+        assert!(validate_git_source("/srv/repos/private.git", None).is_err());
+        assert!(validate_git_source("ext::sh -c id", None).is_err());
+        assert!(PipelineFileSystemProxy::git("--upload-pack=id", None).is_err());
+    }
 }
@@ -1,3 +1,6 @@
 pub trait Scanner {
+    /// Fetches the complete lines that were added since the last fetch.
     fn fetch(&mut self) -> Vec<String>;
+    /// Fetches all the remaining content, including an incomplete last line.
+    fn flush(&mut self) -> Vec<String>;
 }
@@ -1,48 +1,54 @@
-use crate::scanner::Scanner;
+use crate::logger::split_long_line;
+use crate::scanner::{IncrementalReader, Scanner};
 use bld_config::{path, BldConfig};
-use std::fs::File;
-use std::io::{BufRead, BufReader};
 use std::path::PathBuf;
 use std::sync::Arc;
+use tracing::error;
 
+/// Scans the log file of a run for new lines. A fetch reads a bounded chunk of the file and
+/// the lines that exceed the maximum line length of the config are split.
 pub struct FileScanner {
-    path: PathBuf,
-    file_handle: Option<File>,
-    _cfg: Arc<BldConfig>,
+    reader: IncrementalReader,
+    max_line_length: usize,
 }
 
 impl FileScanner {
     pub fn new(cfg: Arc<BldConfig>, run_id: &str) -> Self {
+        let max_line_length = cfg.local.max_log_line_length;
         Self {
-            path: path![&cfg.local.logs, run_id],
-            file_handle: None,
-            _cfg: cfg,
+            reader: IncrementalReader::new(path![&cfg.local.logs, run_id], 0)
+                .max_line_length(max_line_length),
+            max_line_length,
         }
     }
 
-    fn try_open(&mut self) {
-        if self.file_handle.is_some() {
-            return;
+    fn read(&mut self, flush: bool) -> Option<Vec<String>> {
+        match self.reader.read(flush) {
+            Ok(content) if content.is_empty() => None,
+            Ok(content) => Some(
+                content
+                    .lines()
+                    .flat_map(|l| split_long_line(l, self.max_line_length))
+                    .collect(),
+            ),
+            Err(e) => {
+                error!("could not read log file. {e}");
+                None
+            }
         }
-        self.file_handle = match self.path.is_file() {
-            true => File::open(&self.path).map(Some).unwrap_or(None),
-            false => None,
-        };
     }
 }
 
 impl Scanner for FileScanner {
     fn fetch(&mut self) -> Vec<String> {
-        self.try_open();
-        let mut content: Vec<String> = vec![];
-        if let Some(file_handle) = &self.file_handle {
-            let reader = BufReader::new(file_handle);
-            for (_i, line) in reader.lines().enumerate() {
-                if let Ok(line) = line {
-                    content.push(line);
-                }
-            }
+        self.read(false).unwrap_or_default()
+    }
+
+    fn flush(&mut self) -> Vec<String> {
+        let mut lines = vec![];
+        while let Some(content) = self.read(true) {
+            lines.extend(content);
         }
-        content
+        lines
     }
 }
@@ -0,0 +1,193 @@
+use crate::logger::LONG_LINE_MARKER;
+use anyhow::Result;
+use std::fs::{metadata, File, Metadata};
+use std::io::{Read, Seek, SeekFrom};
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+use std::path::PathBuf;
+use tracing::debug;
+
+/// Reads a file incrementally starting from a byte offset. Only complete lines are read unless
+/// the read is flushed, and a file that was truncated or replaced since the previous read is
+/// read again from its start. An offset beyond the end of the file on the first read is moved
+/// to the end of the file.
+///
+/// With a maximum line length, a read is limited to a chunk of the file and a line that exceeds
+/// the maximum is returned in parts that end with the continuation marker, so that the memory
+/// of a read doesn't depend on the length of the lines.
+pub struct IncrementalReader {
+    path: PathBuf,
+    offset: u64,
+    inode: Option<u64>,
+    max_line_length: Option<usize>,
+}
+
+/// The maximum number of bytes of a read when the reader has a maximum line length.
+const READ_CHUNK_BYTES: u64 = 4 * 1024 * 1024;
+
+/// The identity of a file, used to detect that it was replaced. The inode is used on unix and the
+/// creation time on other systems, where the file index isn't available on stable rust.
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> u64 {
+    metadata.ino()
+}
+
+#[cfg(not(unix))]
+fn file_id(metadata: &Metadata) -> u64 {
+    metadata
+        .created()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+/// Moves the end back to the start of the character that it falls in. The end is kept when
+/// the bytes before it aren't a valid character, so that a part is never empty.
+fn char_boundary(buffer: &[u8], start: usize, end: usize) -> usize {
+    let is_continuation = |i: usize| i < buffer.len() && (buffer[i] & 0b1100_0000) == 0b1000_0000;
+    let mut boundary = end;
+    while boundary > start && end - boundary < 3 && is_continuation(boundary) {
+        boundary -= 1;
+    }
+    if boundary == start || is_continuation(boundary) {
+        end
+    } else {
+        boundary
+    }
+}
+
+impl IncrementalReader {
+    pub fn new(path: PathBuf, offset: u64) -> Self {
+        Self {
+            path,
+            offset,
+            inode: None,
+            max_line_length: None,
+        }
+    }
+
+    pub fn max_line_length(mut self, max: usize) -> Self {
+        self.max_line_length = Some(max);
+        self
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Reads the content after the current offset and moves the offset to the end of the
+    /// read content. When not flushing, content after the last new line is left for the next read.
+    pub fn read(&mut self, flush: bool) -> Result<String> {
+        let metadata = match metadata(&self.path) {
+            Ok(metadata) => metadata,
+            Err(_) => {
+                if self.inode.is_none() {
+                    self.offset = 0;
+                }
+                return Ok(String::new());
+            }
+        };
+        let len = metadata.len();
+        match self.inode {
+            Some(inode) if inode != file_id(&metadata) || len < self.offset => {
+                debug!(
+                    "file {} was rotated, reading from start",
+                    self.path.display()
+                );
+                self.offset = 0;
+            }
+            None if len < self.offset => self.offset = len,
+            _ => {}
+        }
+        self.inode = Some(file_id(&metadata));
+
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(self.offset))?;
+        let available = len - self.offset;
+        let limit = match self.max_line_length {
+            Some(max) => available.min(READ_CHUNK_BYTES.max(max as u64 + 1)),
+            None => available,
+        };
+        let mut buffer = vec![];
+        file.take(limit).read_to_end(&mut buffer)?;
+        let end = if flush && limit == available {
+            buffer.len()
+        } else {
+            buffer
+                .iter()
+                .rposition(|b| *b == b'\n')
+                .map(|position| position + 1)
+                .unwrap_or(0)
+        };
+        if let (0, Some(max)) = (end, self.max_line_length) {
+            if buffer.len() > max {
+                return Ok(self.split(&buffer, max));
+            }
+        }
+        buffer.truncate(end);
+        self.offset += end as u64;
+        Ok(String::from_utf8_lossy(&buffer).to_string())
+    }
+
+    /// Returns the parts of maximum length of a line without a new line in the buffer, leaving
+    /// the rest of the buffer for the next read as the line may end in it.
+    fn split(&mut self, buffer: &[u8], max: usize) -> String {
+        let mut content = String::new();
+        let mut start = 0;
+        while buffer.len() - start > max {
+            let end = char_boundary(buffer, start, start + max);
+            content.push_str(&String::from_utf8_lossy(&buffer[start..end]));
+            content.push_str(LONG_LINE_MARKER);
+            content.push('\n');
+            start = end;
+        }
+        self.offset += start as u64;
+        content
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{remove_file, write};
+
+    #[test]
+    fn incremental_reader_reads_complete_lines_after_offset() {
+        let path = std::env::temp_dir().join(format!("bld-reader-{}", std::process::id()));
+        write(&path, "first\nsecond\npartial").unwrap();
+
+        let mut reader = IncrementalReader::new(path.clone(), 6);
+        assert_eq!(reader.read(false).unwrap(), "second\n");
+        assert_eq!(reader.offset(), 13);
+        assert_eq!(reader.read(true).unwrap(), "partial");
+
+        let mut reader = IncrementalReader::new(path.clone(), 1000);
+        assert_eq!(reader.read(false).unwrap(), "");
+        assert_eq!(reader.offset(), 20);
+
+        write(&path, "new\n").unwrap();
+        assert_eq!(reader.read(false).unwrap(), "new\n");
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn incremental_reader_splits_lines_after_the_maximum_length() {
+        let path = std::env::temp_dir().join(format!("bld-reader-long-{}", std::process::id()));
+        write(&path, "abcdéfgh").unwrap();
+
+        let mut reader = IncrementalReader::new(path.clone(), 0).max_line_length(5);
+        assert_eq!(reader.read(false).unwrap(), "abcd…\n");
+        assert_eq!(reader.read(false).unwrap(), "");
+        assert_eq!(reader.read(true).unwrap(), "éfgh");
+
+        write(&path, [0x80; 7]).unwrap();
+        let mut reader = IncrementalReader::new(path.clone(), 0).max_line_length(3);
+        assert_eq!(
+            reader.read(false).unwrap(),
+            "\u{FFFD}\u{FFFD}\u{FFFD}…\n\u{FFFD}\u{FFFD}\u{FFFD}…\n"
+        );
+        assert_eq!(reader.read(true).unwrap(), "\u{FFFD}");
+        remove_file(path).unwrap();
+    }
+}
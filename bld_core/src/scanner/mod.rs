@@ -1,5 +1,7 @@
 mod base;
 mod file_scanner;
+mod incremental_reader;
 
 pub use base::*;
 pub use file_scanner::*;
+pub use incremental_reader::*;
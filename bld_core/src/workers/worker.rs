@@ -1,4 +1,10 @@
 use anyhow::{anyhow, Result};
+#[cfg(unix)]
+use nix::errno::Errno;
+#[cfg(unix)]
+use nix::sys::signal::{kill, Signal};
+#[cfg(unix)]
+use nix::unistd::Pid;
 use std::process::{Child, Command, ExitStatus};
 
 pub struct PipelineWorker {
@@ -28,6 +34,67 @@ impl PipelineWorker {
         self.child.as_ref().map(|c| c.id() == pid).unwrap_or(false)
     }
 
+    /// Checks whether a process with the pid exists, which is used for workers of runs that
+    /// were started before the supervisor was restarted.
+    #[cfg(unix)]
+    pub fn is_alive(pid: u32) -> bool {
+        match kill(Pid::from_raw(pid as i32), None) {
+            Ok(_) | Err(Errno::EPERM) => true,
+            Err(_) => false,
+        }
+    }
+
+    /// Checks whether a process with the pid exists using tasklist, since there are no
+    /// signals on windows.
+    #[cfg(windows)]
+    pub fn is_alive(pid: u32) -> bool {
+        Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {pid}"), "/NH"])
+            .output()
+            .map(|o| String::from_utf8_lossy(&o.stdout).contains(&pid.to_string()))
+            .unwrap_or(false)
+    }
+
+    /// Asks the worker to stop its run by sending it a SIGTERM.
+    #[cfg(unix)]
+    pub fn terminate(&self) -> Result<()> {
+        let pid = self
+            .get_pid()
+            .ok_or_else(|| anyhow!("worker has not spawned"))?;
+        kill(Pid::from_raw(pid as i32), Signal::SIGTERM).map_err(|e| anyhow!(e))
+    }
+
+    /// Stops the worker along with its child processes using taskkill, since there is no
+    /// graceful termination signal on windows.
+    #[cfg(windows)]
+    pub fn terminate(&self) -> Result<()> {
+        let pid = self
+            .get_pid()
+            .ok_or_else(|| anyhow!("worker has not spawned"))?;
+        let status = Command::new("taskkill")
+            .args(["/PID", &pid.to_string(), "/T", "/F"])
+            .status()?;
+        if !status.success() {
+            return Err(anyhow!("could not stop worker with pid {pid}"));
+        }
+        Ok(())
+    }
+
+    /// Kills the worker and waits for it to exit.
+    pub fn kill(&mut self) -> Result<ExitStatus> {
+        let child = self
+            .child
+            .as_mut()
+            .ok_or_else(|| anyhow!("worker has not spawned"))?;
+        child.kill()?;
+        child.wait().map_err(|e| anyhow!(e))
+    }
+
+    /// Checks whether the worker process has exited, without waiting for it.
+    pub fn has_exited(&mut self) -> bool {
+        matches!(self.try_wait(), Ok(Some(_)))
+    }
+
     fn try_wait(&mut self) -> Result<Option<ExitStatus>> {
         self.child
             .as_mut()
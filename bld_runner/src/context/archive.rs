@@ -0,0 +1,165 @@
+use crate::Compression;
+use anyhow::{anyhow, bail, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use glob::glob;
+use std::fs::create_dir_all;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tar::{Archive, Builder};
+
+/// The source of an artifact on the local machine, which is either a file, a directory or
+/// the paths that matched a glob pattern.
+pub enum ArtifactSource {
+    File(PathBuf),
+    Dir(PathBuf),
+    Glob(Vec<PathBuf>),
+}
+
+impl ArtifactSource {
+    pub fn local(from: &str) -> Result<Self> {
+        if is_glob(from) {
+            let paths = glob(from)?.collect::<Result<Vec<PathBuf>, _>>()?;
+            if paths.is_empty() {
+                bail!("no files matched the artifact pattern {from}");
+            }
+            return Ok(Self::Glob(paths));
+        }
+        let path = PathBuf::from(from);
+        if path.is_dir() {
+            Ok(Self::Dir(path))
+        } else {
+            Ok(Self::File(path))
+        }
+    }
+
+    /// Creates a tar with the entries placed under the prefix. The contents of a directory
+    /// are added without the directory itself, while the paths of a glob are added using
+    /// their file name.
+    pub fn pack(&self, prefix: &Path) -> Result<Vec<u8>> {
+        let mut builder = Builder::new(vec![]);
+        match self {
+            Self::Dir(path) if prefix.as_os_str().is_empty() => {
+                builder.append_dir_all(".", path)?
+            }
+            Self::Dir(path) => builder.append_dir_all(prefix, path)?,
+            Self::File(path) => {
+                builder.append_path_with_name(path, prefix.join(file_name(path)?))?
+            }
+            Self::Glob(paths) => {
+                for path in paths.iter() {
+                    let name = prefix.join(file_name(path)?);
+                    if path.is_dir() {
+                        builder.append_dir_all(name, path)?;
+                    } else {
+                        builder.append_path_with_name(path, name)?;
+                    }
+                }
+            }
+        }
+        Ok(builder.into_inner()?)
+    }
+}
+
+fn file_name(path: &Path) -> Result<&std::ffi::OsStr> {
+    path.file_name()
+        .ok_or_else(|| anyhow!("invalid artifact path {}", path.display()))
+}
+
+pub fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+pub fn compress(bytes: &[u8], compression: Compression, zstd_level: i32) -> Result<Vec<u8>> {
+    match compression {
+        Compression::None => Ok(bytes.to_vec()),
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(vec![], flate2::Compression::default());
+            encoder.write_all(bytes)?;
+            Ok(encoder.finish()?)
+        }
+        Compression::Zstd => Ok(zstd::encode_all(bytes, zstd_level)?),
+    }
+}
+
+pub fn decompress(bytes: &[u8], compression: Compression) -> Result<Vec<u8>> {
+    match compression {
+        Compression::None => Ok(bytes.to_vec()),
+        Compression::Gzip => {
+            let mut decompressed = vec![];
+            GzDecoder::new(bytes).read_to_end(&mut decompressed)?;
+            Ok(decompressed)
+        }
+        Compression::Zstd => Ok(zstd::decode_all(bytes)?),
+    }
+}
+
+/// Unpacks the tar in the directory, creating it if it doesn't exist.
+pub fn unpack_dir(bytes: &[u8], dest: &Path) -> Result<()> {
+    create_dir_all(dest)?;
+    Archive::new(bytes).unpack(dest)?;
+    Ok(())
+}
+
+/// Reads the content of the first file in a tar, used for archives fetched from a container.
+pub fn first_file(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut archive = Archive::new(bytes);
+    let mut entry = archive
+        .entries()?
+        .next()
+        .ok_or_else(|| anyhow!("empty archive"))??;
+    let mut content = vec![];
+    entry.read_to_end(&mut content)?;
+    Ok(content)
+}
+
+pub fn extension(compression: Compression) -> &'static str {
+    match compression {
+        Compression::None => "",
+        Compression::Gzip => ".gz",
+        Compression::Zstd => ".zst",
+    }
+}
+
+pub fn size_message(compression: Compression, original: usize, transferred: usize) -> String {
+    format!(
+        "[bld] Artifact transferred with {compression} compression, original size: {original} bytes, transferred size: {transferred} bytes"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn archive_glob_source_packs_matched_files() {
+        let dir = std::env::temp_dir().join(format!("bld-archive-{}", std::process::id()));
+        create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("a.so"), "a").unwrap();
+        std::fs::write(dir.join("b.so"), "b").unwrap();
+        std::fs::write(dir.join("c.txt"), "c").unwrap();
+        let pattern = format!("{}/*.so", dir.display());
+
+        let source = ArtifactSource::local(&pattern).unwrap();
+        let bytes = source.pack(Path::new("out")).unwrap();
+        let mut names = Archive::new(&bytes[..])
+            .entries()
+            .unwrap()
+            .map(|e| e.unwrap().path().unwrap().display().to_string())
+            .collect::<Vec<String>>();
+        names.sort();
+
+        assert_eq!(names, vec!["out/a.so", "out/b.so"]);
+        assert!(ArtifactSource::local(&format!("{}/*.dll", dir.display())).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn archive_compression_round_trip() {
+        let content = "bld artifact content ".repeat(100).into_bytes();
+        for compression in [Compression::None, Compression::Gzip, Compression::Zstd] {
+            let compressed = compress(&content, compression, 3).unwrap();
+            assert_eq!(decompress(&compressed, compression).unwrap(), content);
+        }
+    }
+}
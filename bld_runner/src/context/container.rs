@@ -1,29 +1,49 @@
-use anyhow::{bail, Result};
-use bld_config::BldConfig;
+use crate::context::archive::{
+    compress, decompress, extension, first_file, is_glob, size_message, unpack_dir, ArtifactSource,
+};
+use crate::context::{
+    create_container, docker_retry, is_auth_error, Credentials, DockerRetry, DockerTlsTunnel,
+    OutputDecoder, OutputSink, RegistrySecret, StepEnv,
+};
+use crate::{Compression, DockerOptions};
+use anyhow::{anyhow, bail, Result};
+use bld_config::{BldConfig, BldCredentialConfig};
 use bld_core::context::Context;
 use bld_core::execution::Execution;
 use bld_core::logger::Logger;
 use futures::TryStreamExt;
 use futures_util::StreamExt;
+use hyper::Body;
+use serde_json::{Map, Value};
 use shiplift::tty::TtyChunk;
 use shiplift::{
-    ContainerOptions, Docker, Exec, ExecContainerOptions, ImageListOptions, PullOptions,
+    ContainerOptions, Docker, Error as DockerError, Exec, ExecContainerOptions, ImageListOptions,
+    PullOptions, RegistryAuth,
 };
 use std::collections::HashMap;
+use std::fs::{create_dir_all, read, write};
 use std::path::Path;
 use std::sync::{Arc, Mutex};
-use tar::Archive;
-use tracing::error;
+use tracing::{debug, error};
+use uuid::Uuid;
 
 type AtomicLogger = Arc<Mutex<Logger>>;
 
+/// The path in the container that the ssh agent socket of the host is mounted to.
+const SSH_AUTH_SOCK_PATH: &str = "/run/bld/ssh-agent.sock";
+
 pub struct Container {
     pub id: Option<String>,
     pub config: Option<Arc<BldConfig>>,
     pub image: String,
+    pub options: DockerOptions,
     pub client: Option<Docker>,
+    pub retry: Arc<DockerRetry>,
     pub logger: AtomicLogger,
     pub containers: Arc<Mutex<Context>>,
+    pub credentials: Option<Credentials>,
+    /// The tunnel that the client connects through to a remote host with its own certificates.
+    pub tunnel: Option<DockerTlsTunnel>,
 }
 
 impl Container {
@@ -41,53 +61,183 @@ impl Container {
         }
     }
 
-    fn docker(config: &Arc<BldConfig>) -> Result<Docker> {
-        let url = config.local.docker_url.parse()?;
-        let host = Docker::host(url);
-        Ok(host)
+    /// Creates the client of the docker daemon that the container is created on, along with the url
+    /// that the requests which the client doesn't support are sent to and the tunnel of a remote host
+    /// with its own certificates.
+    fn docker(
+        config: &Arc<BldConfig>,
+        options: &DockerOptions,
+    ) -> Result<(Docker, String, Option<DockerTlsTunnel>)> {
+        let host = match &options.host {
+            Some(host) => host,
+            None => {
+                let url = config.local.docker_url.to_string();
+                return Ok((Docker::host(url.parse()?), url, None));
+            }
+        };
+        match &host.tls_cert_path {
+            Some(cert_path) => {
+                let tunnel = DockerTlsTunnel::start(host, cert_path)?;
+                let socket = tunnel.socket().display().to_string();
+                Ok((
+                    Docker::unix(socket.as_str()),
+                    format!("unix://{socket}"),
+                    Some(tunnel),
+                ))
+            }
+            None => {
+                let url = host.plain_url();
+                Ok((Docker::host(url.parse()?), url, None))
+            }
+        }
     }
 
-    async fn pull(client: &Docker, image: &str, logger: &mut AtomicLogger) -> Result<()> {
+    async fn pull(
+        client: &Docker,
+        retry: &DockerRetry,
+        image: &str,
+        docker_options: &DockerOptions,
+        logger: &mut AtomicLogger,
+    ) -> Result<()> {
         let options = ImageListOptions::builder().filter_name(image).build();
-        let images = client.images().list(&options).await?;
+        let images = retry
+            .call("image list", || async {
+                client.images().list(&options).await
+            })
+            .await?;
         if images.is_empty() {
             {
                 let mut logger = logger.lock().unwrap();
                 logger.info(&format!("Download image: {image}"));
             }
-            let options = PullOptions::builder().image(image).build();
-            let mut pull_iter = client.images().pull(&options);
-            while let Some(Ok(progress)) = pull_iter.next().await {
-                let mut logger = logger.lock().unwrap();
-                logger.dumpln(&progress.to_string());
+            let mut builder = PullOptions::builder();
+            builder.image(image);
+            if let Some(registry) = &docker_options.registry {
+                let auth = match &registry.secret {
+                    RegistrySecret::Password { username, password } => RegistryAuth::builder()
+                        .server_address(&registry.server_address)
+                        .username(username)
+                        .password(password)
+                        .build(),
+                    RegistrySecret::Token(token) => RegistryAuth::token(token),
+                };
+                builder.auth(auth);
             }
+            let options = builder.build();
+            let logger = &*logger;
+            retry
+                .call("image pull", || async {
+                    let mut pull_iter = client.images().pull(&options);
+                    while let Some(progress) = pull_iter.next().await {
+                        let progress = progress?;
+                        let mut logger = logger.lock().unwrap();
+                        logger.dumpln(&progress.to_string());
+                    }
+                    Ok(())
+                })
+                .await
+                .map_err(|e| {
+                    // the error of the daemon is replaced in order to not expose any details
+                    // of the authentication with the registry.
+                    let is_auth_error = e.downcast_ref::<DockerError>().map(is_auth_error);
+                    match (&docker_options.registry, is_auth_error) {
+                        (Some(registry), Some(true)) => {
+                            anyhow!("authentication to registry {} failed", registry.name)
+                        }
+                        _ => e,
+                    }
+                })?;
         }
         Ok(())
     }
 
     async fn create(
         client: &Docker,
+        docker_url: &str,
+        retry: &DockerRetry,
         image: &str,
         env: &[String],
+        docker_options: &DockerOptions,
         logger: &mut AtomicLogger,
     ) -> Result<String> {
-        Container::pull(client, image, logger).await?;
-        let options = ContainerOptions::builder(image).env(env).tty(true).build();
-        let info = client.containers().create(&options).await?;
-        client.containers().get(&info.id).start().await?;
-        Ok(info.id)
+        Container::pull(client, retry, image, docker_options, logger).await?;
+        let mut volumes: Vec<String> = docker_options.volumes.clone();
+        if let Some(socket) = &docker_options.ssh_agent_socket {
+            volumes.push(format!("{socket}:{SSH_AUTH_SOCK_PATH}"));
+        }
+        let mut builder = ContainerOptions::builder(image);
+        builder
+            .env(env)
+            .volumes(volumes.iter().map(|v| v.as_str()).collect())
+            .tty(true);
+        if let Some(network) = &docker_options.network {
+            builder.network_mode(network);
+        }
+        let resources = &docker_options.resources;
+        if let Some(memory) = resources.memory {
+            builder.memory(memory);
+        }
+        if let Some(cpus) = resources.cpus {
+            builder.nano_cpus((cpus * 1e9) as u64);
+        }
+        if let Some(cpu_shares) = resources.cpu_shares {
+            builder.cpu_shares(cpu_shares.min(u32::MAX as u64) as u32);
+        }
+        let options = builder.build();
+        // the oom and pids settings are part of the host config of the container since the update
+        // endpoint of docker doesn't apply all of them and an exec can't always change its oom score.
+        let mut host_config = Map::new();
+        if let Some(score) = docker_options.oom_score_adj {
+            host_config.insert("OomScoreAdj".to_string(), Value::from(score));
+        }
+        if docker_options.oom_kill_disable {
+            host_config.insert("OomKillDisable".to_string(), Value::Bool(true));
+        }
+        if let Some(pids) = resources.pids {
+            host_config.insert("PidsLimit".to_string(), Value::from(pids));
+        }
+        let id = if host_config.is_empty() {
+            retry
+                .call_once("container create", client.containers().create(&options))
+                .await?
+                .id
+        } else {
+            // only the default daemon is connected to with the certificates of the process.
+            let tls = docker_options.host.is_none() && std::env::var("DOCKER_CERT_PATH").is_ok();
+            create_container(docker_url, tls, &options, host_config).await?
+        };
+        retry
+            .call("container start", || async {
+                client.containers().get(&id).start().await
+            })
+            .await?;
+        Ok(id)
     }
 
     pub async fn new(
         image: &str,
+        options: DockerOptions,
         config: Arc<BldConfig>,
         env: Arc<HashMap<String, String>>,
         logger: AtomicLogger,
         containers: Arc<Mutex<Context>>,
     ) -> Result<Self> {
-        let client = Container::docker(&config)?;
+        let (client, docker_url, tunnel) = Container::docker(&config, &options)?;
         let env: Vec<String> = env.iter().map(|(k, v)| format!("{k}={v}")).collect();
-        let id = Container::create(&client, image, &env, &mut logger.clone()).await?;
+        let retry = match &options.host {
+            Some(host) => docker_retry(&host.url),
+            None => docker_retry(&config.local.docker_url),
+        };
+        let id = Container::create(
+            &client,
+            &docker_url,
+            &retry,
+            image,
+            &env,
+            &options,
+            &mut logger.clone(),
+        )
+        .await?;
         {
             let mut containers = containers.lock().unwrap();
             containers.add(&id)?;
@@ -95,36 +245,319 @@ impl Container {
         Ok(Self {
             config: Some(config),
             image: image.to_string(),
+            options,
             client: Some(client),
+            retry,
             id: Some(id),
             logger,
             containers,
+            credentials: None,
+            tunnel,
         })
     }
 
-    pub async fn copy_from(&self, from: &str, to: &str) -> Result<()> {
+    /// Copies the files of the credentials to a directory of the container that only its user can access.
+    pub async fn install_credentials(&mut self, entries: &[&BldCredentialConfig]) -> Result<()> {
+        let (credentials, archive) = Credentials::container(entries)?;
+        self.upload_archive(archive).await?;
+        self.credentials = Some(credentials);
+        Ok(())
+    }
+
+    pub async fn remove_credentials(&self) -> Result<()> {
+        let credentials = match &self.credentials {
+            Some(credentials) => credentials,
+            None => return Ok(()),
+        };
+        let (code, output) = self.exec_output(&credentials.remove_command()).await?;
+        if code > 0 {
+            bail!(
+                "could not remove the credentials from the container. {}",
+                output.trim()
+            );
+        }
+        Ok(())
+    }
+
+    async fn fetch(&self, from: &str) -> Result<Vec<u8>> {
         let client = self.get_client()?;
         let container = client.containers().get(self.get_id()?);
-        let bytes = container.copy_from(Path::new(from)).try_concat().await?;
-        let mut archive = Archive::new(&bytes[..]);
-        archive.unpack(Path::new(to))?;
-        Ok(())
+        self.retry
+            .call("copy from container", || async {
+                container.copy_from(Path::new(from)).try_concat().await
+            })
+            .await
     }
 
-    pub async fn copy_into(&self, from: &str, to: &str) -> Result<()> {
+    async fn upload(&self, to: &str, content: &[u8]) -> Result<()> {
         let client = self.get_client()?;
         let container = client.containers().get(self.get_id()?);
-        let content = std::fs::read(from)?;
-        container.copy_file_into(to, &content).await?;
+        self.retry
+            .call_once("copy into container", container.copy_file_into(to, content))
+            .await
+    }
+
+    /// Executes a command in the container without logging its output and returns
+    /// the exit code along with the output.
+    async fn exec_output(&self, input: &str) -> Result<(u64, String)> {
+        let client = self.get_client()?;
+        let id = self.get_id()?;
+        let options = ExecContainerOptions::builder()
+            .cmd(vec!["sh", "-c", input])
+            .attach_stdout(true)
+            .attach_stderr(true)
+            .build();
+        let exec = self
+            .retry
+            .call_once("exec create", Exec::create(client, id, &options))
+            .await?;
+        let mut output = String::new();
+        let mut exec_stream = exec.start();
+        while let Some(result) = exec_stream.next().await {
+            match result? {
+                TtyChunk::StdOut(bytes) | TtyChunk::StdErr(bytes) => {
+                    output.push_str(&String::from_utf8_lossy(&bytes))
+                }
+                TtyChunk::StdIn(_) => unreachable!(),
+            }
+        }
+        let inspect = self.retry.call("exec inspect", || exec.inspect()).await?;
+        Ok((inspect.exit_code.unwrap_or_default(), output))
+    }
+
+    /// Uploads a tar that is extracted at the root of the container, creating any
+    /// directories of its entries that don't exist.
+    async fn upload_archive(&self, archive: Vec<u8>) -> Result<()> {
+        let client = self.get_client()?;
+        let container = client.containers().get(self.get_id()?);
+        self.retry
+            .call_once(
+                "copy into container",
+                container.copy_to(Path::new("/"), Body::from(archive)),
+            )
+            .await
+    }
+
+    async fn require_tools(&self, needs_tar: bool, compression: Compression) -> Result<()> {
+        let mut tools = vec![];
+        if needs_tar {
+            tools.push("tar");
+        }
+        match compression {
+            Compression::Gzip => tools.push("gzip"),
+            Compression::Zstd => tools.push("zstd"),
+            Compression::None => {}
+        }
+        if tools.is_empty() {
+            return Ok(());
+        }
+        let check = format!(
+            "for tool in {}; do command -v $tool > /dev/null || {{ echo $tool; exit 1; }}; done",
+            tools.join(" ")
+        );
+        let (code, output) = self.exec_output(&check).await?;
+        if code == 0 {
+            return Ok(());
+        }
+        match compression {
+            Compression::None => bail!(
+                "the container is missing {} which is required for artifacts that use a glob pattern",
+                output.trim()
+            ),
+            _ => bail!(
+                "the container is missing {} which is required for artifacts with {compression} compression, consider using compress: none",
+                output.trim()
+            ),
+        }
+    }
+
+    fn log_sizes(&self, compression: Compression, original: usize, transferred: usize) {
+        let mut logger = self.logger.lock().unwrap();
+        logger.dumpln(&size_message(compression, original, transferred));
+    }
+
+    fn archive_path(is_dir: bool, compression: Compression) -> String {
+        let tar = if is_dir { ".tar" } else { "" };
+        format!(
+            "/tmp/bld-artifact-{}{tar}{}",
+            Uuid::new_v4(),
+            extension(compression)
+        )
+    }
+
+    fn compress_cmd(compression: Compression, zstd_level: i32) -> String {
+        match compression {
+            Compression::Zstd => format!("zstd -q -c -{zstd_level}"),
+            _ => "gzip -c".to_string(),
+        }
+    }
+
+    /// Packs an artifact in the container using the provided command, fetches the created
+    /// archive and returns its decompressed content.
+    async fn fetch_archive(
+        &self,
+        pack: &str,
+        archive: &str,
+        from: &str,
+        compression: Compression,
+    ) -> Result<Vec<u8>> {
+        let (code, output) = self.exec_output(pack).await?;
+        if code > 0 {
+            let _ = self.exec_output(&format!("rm -f {archive}")).await;
+            bail!("could not pack artifact {from} in the container. {output}");
+        }
+
+        let bytes = self.fetch(archive).await;
+        let _ = self.exec_output(&format!("rm -f {archive}")).await;
+        let transferred = first_file(&bytes?)?;
+        let original = decompress(&transferred, compression)?;
+        if compression != Compression::None {
+            self.log_sizes(compression, original.len(), transferred.len());
+        }
+        Ok(original)
+    }
+
+    /// Copies the paths that match a glob pattern in the container into the target directory
+    /// using a single tar that is created in the container.
+    async fn copy_glob_from(
+        &self,
+        from: &str,
+        to: &str,
+        compression: Compression,
+        zstd_level: i32,
+    ) -> Result<()> {
+        self.require_tools(true, compression).await?;
+        let archive = Container::archive_path(true, compression);
+        let tar = archive.trim_end_matches(extension(compression)).to_string();
+        let compress = match compression {
+            Compression::None => String::new(),
+            _ => format!(
+                " && {} {tar} > {archive} && rm -f {tar}",
+                Container::compress_cmd(compression, zstd_level)
+            ),
+        };
+        let pack = format!(
+            "found=0; for f in {from}; do [ -e \"$f\" ] || continue; found=1; tar -rf {tar} -C \"$(dirname \"$f\")\" \"$(basename \"$f\")\" || {{ rm -f {tar}; exit 1; }}; done; [ $found = 1 ] || {{ echo no files matched the artifact pattern {from}; exit 1; }}{compress}"
+        );
+        let original = self
+            .fetch_archive(&pack, &archive, from, compression)
+            .await?;
+        unpack_dir(&original, Path::new(to))
+    }
+
+    /// Copies a file of the container into the target directory, or the contents of a
+    /// directory or the paths that match a glob pattern, creating the directory if needed.
+    pub async fn copy_from(
+        &self,
+        from: &str,
+        to: &str,
+        compression: Compression,
+        zstd_level: i32,
+    ) -> Result<()> {
+        if is_glob(from) {
+            return self.copy_glob_from(from, to, compression, zstd_level).await;
+        }
+
+        let (code, _) = self.exec_output(&format!("test -d {from}")).await?;
+        let is_dir = code == 0;
+
+        if compression == Compression::None {
+            // fetching the contents of a directory instead of the directory itself.
+            let path = if is_dir {
+                format!("{}/.", from.trim_end_matches('/'))
+            } else {
+                from.to_string()
+            };
+            let bytes = self.fetch(&path).await?;
+            return unpack_dir(&bytes, Path::new(to));
+        }
+
+        self.require_tools(is_dir, compression).await?;
+        let archive = Container::archive_path(is_dir, compression);
+        let compress_cmd = Container::compress_cmd(compression, zstd_level);
+        let pack = if is_dir {
+            format!("tar -cf - -C {from} . | {compress_cmd} > {archive}")
+        } else {
+            format!("{compress_cmd} {from} > {archive}")
+        };
+        let original = self
+            .fetch_archive(&pack, &archive, from, compression)
+            .await?;
+
+        if is_dir {
+            unpack_dir(&original, Path::new(to))
+        } else {
+            let name = Path::new(from)
+                .file_name()
+                .ok_or_else(|| anyhow!("invalid artifact path {from}"))?;
+            create_dir_all(to)?;
+            write(Path::new(to).join(name), original)?;
+            Ok(())
+        }
+    }
+
+    /// Copies a local file to the target path of the container, or the contents of a directory
+    /// and the paths that match a glob pattern into the target directory.
+    pub async fn copy_into(
+        &self,
+        from: &str,
+        to: &str,
+        compression: Compression,
+        zstd_level: i32,
+    ) -> Result<()> {
+        let source = ArtifactSource::local(from)?;
+        let is_file = matches!(source, ArtifactSource::File(_));
+
+        if compression == Compression::None {
+            return match &source {
+                ArtifactSource::File(path) => self.upload(to, &read(path)?).await,
+                _ => {
+                    let archive = source.pack(Path::new(to.trim_start_matches('/')))?;
+                    self.upload_archive(archive).await
+                }
+            };
+        }
+
+        self.require_tools(!is_file, compression).await?;
+
+        let original = match &source {
+            ArtifactSource::File(path) => read(path)?,
+            _ => source.pack(Path::new(""))?,
+        };
+        let transferred = compress(&original, compression, zstd_level)?;
+        self.log_sizes(compression, original.len(), transferred.len());
+
+        let archive = Container::archive_path(!is_file, compression);
+        self.upload(&archive, &transferred).await?;
+
+        let decompress_cmd = match compression {
+            Compression::Zstd => format!("zstd -dcq {archive}"),
+            _ => format!("gzip -dc {archive}"),
+        };
+        let unpack = if is_file {
+            format!("mkdir -p $(dirname {to}) && {decompress_cmd} > {to}")
+        } else {
+            format!("mkdir -p {to} && {decompress_cmd} | tar -xf - -C {to}")
+        };
+        let unpack = format!("{unpack}; status=$?; rm -f {archive}; exit $status");
+        let (code, output) = self.exec_output(&unpack).await?;
+        if code > 0 {
+            bail!("could not unpack artifact {from} in the container. {output}");
+        }
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn sh(
         &self,
         working_dir: &Option<String>,
         input: &str,
+        prefix: &Option<String>,
+        ssh_agent_forwarding: bool,
+        capture_stdout: bool,
+        env: &StepEnv,
         ex: Arc<Mutex<Execution>>,
-    ) -> Result<()> {
+    ) -> Result<String> {
         let client = self.get_client()?;
         let id = self.get_id()?;
         let input = working_dir
@@ -132,40 +565,103 @@ impl Container {
             .map(|wd| format!("cd {wd} && {input}"))
             .or_else(|| Some(input.to_string()))
             .unwrap();
+        // the directories are prepended in the shell of the exec in order to keep the PATH of the image.
+        let input = if self.options.path_prepend.is_empty() {
+            input
+        } else {
+            let dirs = self.options.path_prepend.join(":");
+            format!("export PATH=\"{dirs}:$PATH\" && {input}")
+        };
 
-        let options = ExecContainerOptions::builder()
-            .cmd(vec!["bash", "-c", &input])
-            .attach_stdout(true)
-            .attach_stderr(true)
-            .build();
+        let credentials = self
+            .credentials
+            .as_ref()
+            .map(|c| c.env())
+            .unwrap_or_default();
+        let mut vars: Vec<String> = credentials
+            .iter()
+            .chain(env.vars.iter())
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect();
+        if ssh_agent_forwarding {
+            if self.options.ssh_agent_socket.is_none() {
+                bail!("the ssh agent of the host is not mounted to the container");
+            }
+            vars.push(format!("SSH_AUTH_SOCK={SSH_AUTH_SOCK_PATH}"));
+        }
+        // docker always merges the env of an exec with the env of the container, so the shell
+        // is started through env -i when the step should see only its own variables.
+        let mut cmd = vec![];
+        if !env.inherit_container_env {
+            cmd.push("env");
+            cmd.push("-i");
+            cmd.extend(vars.iter().map(|v| v.as_str()));
+        }
+        cmd.extend(["bash", "-c", &input]);
+
+        let mut builder = ExecContainerOptions::builder();
+        builder.cmd(cmd).attach_stdout(true).attach_stderr(true);
+        if env.inherit_container_env && !vars.is_empty() {
+            builder.env(vars.iter().map(|v| v.as_str()).collect());
+        }
+        let options = builder.build();
 
-        let exec = Exec::create(client, id, &options).await?;
+        let exec = self
+            .retry
+            .call_once("exec create", Exec::create(client, id, &options))
+            .await?;
+
+        // the start of the exec isn't retried since the daemon may have already started the
+        // command when the response failed.
         let mut exec_stream = exec.start();
+        let mut result = self
+            .retry
+            .call_once("exec start", async { exec_stream.next().await.transpose() })
+            .await?;
 
-        while let Some(result) = exec_stream.next().await {
+        let mut sink = OutputSink::new(&self.logger, prefix);
+        let mut stdout = String::new();
+        // the streams are decoded separately since their chunks are interleaved.
+        let mut stdout_decoder = OutputDecoder::new(env.output_encoding);
+        let mut stderr_decoder = OutputDecoder::new(env.output_encoding);
+        while let Some(tty_chunk) = result {
             {
                 let exec = ex.lock().unwrap();
                 exec.check_stop_signal()?
             }
 
-            let chunk = match result {
-                Ok(TtyChunk::StdOut(bytes)) => String::from_utf8(bytes)?,
-                Ok(TtyChunk::StdErr(bytes)) => String::from_utf8(bytes)?,
-                Ok(TtyChunk::StdIn(_)) => unreachable!(),
-                Err(e) => bail!(e),
+            let chunk = match tty_chunk {
+                TtyChunk::StdOut(bytes) => {
+                    let chunk = stdout_decoder.decode(&bytes);
+                    if capture_stdout {
+                        stdout.push_str(&chunk);
+                    }
+                    chunk
+                }
+                TtyChunk::StdErr(bytes) => stderr_decoder.decode(&bytes),
+                TtyChunk::StdIn(_) => unreachable!(),
             };
+            sink.write(&chunk);
+
+            result = exec_stream.next().await.transpose()?;
+        }
 
-            let mut logger = self.logger.lock().unwrap();
-            logger.dump(&chunk);
+        let rest = stdout_decoder.finish();
+        if capture_stdout {
+            stdout.push_str(&rest);
         }
+        sink.finish(
+            &(rest + &stderr_decoder.finish()),
+            stdout_decoder.is_lossy() || stderr_decoder.is_lossy(),
+        );
 
-        let inspect = exec.inspect().await?;
+        let inspect = self.retry.call("exec inspect", || exec.inspect()).await?;
         match inspect.exit_code {
             Some(code) if code > 0 => bail!("command finished with exit code: {code}"),
             _ => {}
         }
 
-        Ok(())
+        Ok(stdout)
     }
 
     pub fn keep_alive(&self) -> Result<()> {
@@ -178,20 +674,38 @@ impl Container {
         let client = self.get_client()?;
         let id = self.get_id()?;
 
-        if let Err(e) = client.containers().get(id).stop(None).await {
+        let stop = self
+            .retry
+            .call("container stop", || async {
+                client.containers().get(id).stop(None).await
+            })
+            .await;
+        if let Err(e) = stop {
             error!("could not stop container, {e}");
             let mut containers = self.containers.lock().unwrap();
             containers.set_as_faulted(id)?;
             bail!(e);
         }
 
-        if let Err(e) = client.containers().get(id).delete().await {
+        let delete = self
+            .retry
+            .call("container remove", || async {
+                client.containers().get(id).delete().await
+            })
+            .await;
+        if let Err(e) = delete {
             error!("could not stop container, {e}");
             let mut containers = self.containers.lock().unwrap();
             containers.set_as_faulted(id)?;
             bail!(e);
         }
 
+        debug!(
+            "docker api retries: {}, circuit breaker trips: {}",
+            self.retry.retries(),
+            self.retry.breaker_trips()
+        );
+
         let mut containers = self.containers.lock().unwrap();
         containers.set_as_removed(id)?;
 
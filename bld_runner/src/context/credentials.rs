@@ -0,0 +1,366 @@
+use crate::context::{quote, SSH_AUTH_SOCK};
+use anyhow::{anyhow, bail, Result};
+use bld_config::{BldCredentialConfig, BldCredentialKind};
+use std::fs::{
+    metadata, read, read_dir, remove_dir_all, remove_file, DirBuilder, File, OpenOptions,
+};
+use std::io::{self, empty, Write};
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::Mutex;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use tar::{Builder, EntryType, Header};
+use uuid::Uuid;
+
+const CREDENTIALS_DIR_PREFIX: &str = ".bld-credentials-";
+const CONTAINER_CREDENTIALS_PARENT_DIR: &str = "/tmp";
+const SSH_AGENT_DIR_PREFIX: &str = "bld-agent-";
+const SSH_AGENT_SOCKET: &str = "agent.sock";
+const SSH_AGENT_START_TIMEOUT: Duration = Duration::from_secs(5);
+const SSH_AGENT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+type EnvVars = Vec<(String, String)>;
+
+/// An ssh agent of a run along with the directory of its socket.
+struct SshAgent {
+    process: Child,
+    dir: String,
+}
+
+/// A file with a secret of the credentials along with its permissions.
+struct SecretFile {
+    name: String,
+    content: Vec<u8>,
+    mode: u32,
+}
+
+/// Creates a directory that only its owner can access. There are no permission bits on windows,
+/// where the directory inherits the access control list of its parent instead.
+fn create_private_dir(dir: &str, recursive: bool) -> io::Result<()> {
+    let mut builder = DirBuilder::new();
+    builder.recursive(recursive);
+    #[cfg(unix)]
+    builder.mode(0o700);
+    builder.create(dir)
+}
+
+/// Creates a new file with the permissions of a secret, which are ignored on windows.
+fn create_secret_file(path: &str, mode: u32) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(mode);
+    #[cfg(not(unix))]
+    let _ = mode;
+    options.open(path)
+}
+
+/// A git credential helper that provides the token to git and ignores the store and erase actions.
+fn git_helper(username: &str, token: &str) -> Vec<u8> {
+    format!(
+        "#!/bin/sh\ntest \"$1\" = get || exit 0\necho username={}\necho password={}\n",
+        quote(username),
+        quote(token)
+    )
+    .into_bytes()
+}
+
+/// Creates the files of the credentials, which are placed in the provided directory, along
+/// with the environment that makes git use them. The ssh keys are added as files only when
+/// requested, since on a machine they are loaded to an ssh agent instead.
+fn secret_files(
+    entries: &[&BldCredentialConfig],
+    dir: &str,
+    with_keys: bool,
+) -> Result<(Vec<SecretFile>, EnvVars)> {
+    let mut files = vec![];
+    let mut helpers = vec![];
+    let mut keys = vec![];
+    for (i, credential) in entries.iter().enumerate() {
+        match &credential.kind {
+            BldCredentialKind::SshKey { key_file } if with_keys => {
+                let name = format!("{i}.key");
+                let content = read(key_file).map_err(|e| {
+                    anyhow!(
+                        "could not read the key of credential {}, {e}",
+                        credential.name
+                    )
+                })?;
+                keys.push(format!("{dir}/{name}"));
+                files.push(SecretFile {
+                    name,
+                    content,
+                    mode: 0o600,
+                });
+            }
+            BldCredentialKind::SshKey { .. } => {}
+            BldCredentialKind::GitToken { username, host, .. } => {
+                let name = format!("{i}.git-credential");
+                let content = git_helper(username, &credential.token()?);
+                // a helper without a host is used for every host that git connects to.
+                let key = match host {
+                    Some(host) => format!("credential.https://{host}.helper"),
+                    None => "credential.helper".to_string(),
+                };
+                helpers.push((key, format!("{dir}/{name}")));
+                files.push(SecretFile {
+                    name,
+                    content,
+                    mode: 0o700,
+                });
+            }
+        }
+    }
+
+    let mut env = vec![];
+    if !helpers.is_empty() {
+        env.push(("GIT_CONFIG_COUNT".to_string(), helpers.len().to_string()));
+        for (i, (key, value)) in helpers.into_iter().enumerate() {
+            env.push((format!("GIT_CONFIG_KEY_{i}"), key));
+            env.push((format!("GIT_CONFIG_VALUE_{i}"), value));
+        }
+    }
+    if !keys.is_empty() {
+        let identities: String = keys.iter().map(|k| format!(" -i {k}")).collect();
+        env.push((
+            "GIT_SSH_COMMAND".to_string(),
+            format!("ssh -o IdentitiesOnly=yes{identities}"),
+        ));
+    }
+    Ok((files, env))
+}
+
+/// Overwrites the content of a file before removing it so that the secret doesn't remain on disk.
+fn shred(path: &Path) -> Result<()> {
+    let len = metadata(path)?.len() as usize;
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    file.write_all(&vec![0; len])?;
+    file.sync_all()?;
+    remove_file(path)?;
+    Ok(())
+}
+
+/// The credentials of the server that are provided to the steps of a run. They are placed in a
+/// directory that is private to the run and are available only through the environment of its steps.
+pub struct Credentials {
+    dir: String,
+    env: EnvVars,
+    agent: Mutex<Option<SshAgent>>,
+}
+
+impl Credentials {
+    /// Sets up the credentials in a new directory of the temp dir of a machine, starting an
+    /// ssh agent with the keys of the credentials if there are any.
+    pub fn machine(tmp_dir: &str, entries: &[&BldCredentialConfig]) -> Result<Self> {
+        let dir = format!("{tmp_dir}/{CREDENTIALS_DIR_PREFIX}{}", Uuid::new_v4());
+        create_private_dir(&dir, true)?;
+        let mut credentials = Self {
+            dir,
+            env: vec![],
+            agent: Mutex::new(None),
+        };
+        if let Err(e) = credentials.setup_machine(entries) {
+            let _ = credentials.remove();
+            return Err(e);
+        }
+        Ok(credentials)
+    }
+
+    fn setup_machine(&mut self, entries: &[&BldCredentialConfig]) -> Result<()> {
+        let (files, env) = secret_files(entries, &self.dir, false)?;
+        for file in files.iter() {
+            let mut handle = create_secret_file(&format!("{}/{}", self.dir, file.name), file.mode)?;
+            handle.write_all(&file.content)?;
+        }
+        self.env = env;
+
+        let keys: Vec<(&str, &str)> = entries
+            .iter()
+            .filter_map(|c| match &c.kind {
+                BldCredentialKind::SshKey { key_file } => {
+                    Some((c.name.as_str(), key_file.as_str()))
+                }
+                _ => None,
+            })
+            .collect();
+        if !keys.is_empty() {
+            self.start_agent(&keys)?;
+        }
+        Ok(())
+    }
+
+    fn start_agent(&mut self, keys: &[(&str, &str)]) -> Result<()> {
+        // the socket is created in the temp dir of the system since the path of a unix socket
+        // has a short length limit that the temp dir of the run may exceed.
+        let dir = std::env::temp_dir()
+            .join(format!("{SSH_AGENT_DIR_PREFIX}{}", Uuid::new_v4()))
+            .display()
+            .to_string();
+        create_private_dir(&dir, false)?;
+        let socket = format!("{dir}/{SSH_AGENT_SOCKET}");
+        let process = Command::new("ssh-agent")
+            .args(["-D", "-a", &socket])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let process = match process {
+            Ok(process) => process,
+            Err(e) => {
+                let _ = remove_dir_all(&dir);
+                bail!("could not start ssh-agent, {e}");
+            }
+        };
+        *self.agent.lock().unwrap() = Some(SshAgent { process, dir });
+
+        let start = Instant::now();
+        while !Path::new(&socket).exists() {
+            if start.elapsed() > SSH_AGENT_START_TIMEOUT {
+                bail!("ssh-agent didn't start in time");
+            }
+            sleep(SSH_AGENT_POLL_INTERVAL);
+        }
+
+        for (name, key_file) in keys.iter() {
+            // the output of ssh-add is discarded in order to not expose anything about the key.
+            let status = Command::new("ssh-add")
+                .arg(key_file)
+                .env(SSH_AUTH_SOCK, &socket)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .map_err(|e| anyhow!("could not execute ssh-add, {e}"))?;
+            if !status.success() {
+                bail!("could not add the key of credential {name} to the ssh agent");
+            }
+        }
+        self.env.push((SSH_AUTH_SOCK.to_string(), socket));
+        Ok(())
+    }
+
+    /// Prepares the credentials for a container and returns them along with an archive of
+    /// their files, which should be extracted to the root of the container.
+    pub fn container(entries: &[&BldCredentialConfig]) -> Result<(Self, Vec<u8>)> {
+        let dir = format!(
+            "{CONTAINER_CREDENTIALS_PARENT_DIR}/{CREDENTIALS_DIR_PREFIX}{}",
+            Uuid::new_v4()
+        );
+        let (files, env) = secret_files(entries, &dir, true)?;
+        let archive_dir = dir.trim_start_matches('/');
+        let mut builder = Builder::new(vec![]);
+        let mut header = Header::new_gnu();
+        header.set_entry_type(EntryType::Directory);
+        header.set_mode(0o700);
+        header.set_size(0);
+        builder.append_data(&mut header, archive_dir, empty())?;
+        for file in files.iter() {
+            let mut header = Header::new_gnu();
+            header.set_mode(file.mode);
+            header.set_size(file.content.len() as u64);
+            builder.append_data(
+                &mut header,
+                format!("{archive_dir}/{}", file.name),
+                file.content.as_slice(),
+            )?;
+        }
+        let credentials = Self {
+            dir,
+            env,
+            agent: Mutex::new(None),
+        };
+        Ok((credentials, builder.into_inner()?))
+    }
+
+    /// The environment that makes the steps use the credentials.
+    pub fn env(&self) -> &[(String, String)] {
+        &self.env
+    }
+
+    /// Stops the ssh agent and shreds the files of the credentials on the machine.
+    pub fn remove(&self) -> Result<()> {
+        self.stop_agent();
+        let dir = Path::new(&self.dir);
+        if !dir.is_dir() {
+            return Ok(());
+        }
+        for entry in read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_file() {
+                shred(&path)?;
+            }
+        }
+        remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    /// The command that shreds the files of the credentials in a container, which falls back
+    /// to removing them for images without shred.
+    pub fn remove_command(&self) -> String {
+        let dir = quote(&self.dir);
+        format!(
+            "if command -v shred > /dev/null; then find {dir} -type f -exec shred -u {{}} +; fi; rm -rf {dir}"
+        )
+    }
+
+    fn stop_agent(&self) {
+        if let Some(mut agent) = self.agent.lock().unwrap().take() {
+            let _ = agent.process.kill();
+            let _ = agent.process.wait();
+            let _ = remove_dir_all(&agent.dir);
+        }
+    }
+}
+
+impl Drop for Credentials {
+    fn drop(&mut self) {
+        // the agent is stopped even if the credentials weren't removed, such as when the run panics.
+        self.stop_agent();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bld_config::BldCredentialConfig;
+
+    #[test]
+    fn credentials_are_removed_from_the_machine() {
+        std::env::set_var("BLD_TEST_CREDENTIAL_TOKEN", "token-value");
+        let credential = BldCredentialConfig {
+            name: "github".to_string(),
+            kind: BldCredentialKind::GitToken {
+                token_env: "BLD_TEST_CREDENTIAL_TOKEN".to_string(),
+                username: "bld".to_string(),
+                host: Some("github.com".to_string()),
+            },
+            allowed_pipelines: vec![],
+        };
+        let tmp_dir = std::env::temp_dir().display().to_string();
+        let credentials = Credentials::machine(&tmp_dir, &[&credential]).unwrap();
+
+        let env = credentials.env();
+        assert!(env.contains(&("GIT_CONFIG_COUNT".to_string(), "1".to_string())));
+        assert!(env.contains(&(
+            "GIT_CONFIG_KEY_0".to_string(),
+            "credential.https://github.com.helper".to_string()
+        )));
+        let helper = &env
+            .iter()
+            .find(|(k, _)| k == "GIT_CONFIG_VALUE_0")
+            .unwrap()
+            .1;
+        let output = Command::new(helper).arg("get").output().unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "username=bld\npassword=token-value\n"
+        );
+
+        credentials.remove().unwrap();
+        assert!(!Path::new(helper).exists());
+        assert!(!Path::new(&credentials.dir).exists());
+    }
+}
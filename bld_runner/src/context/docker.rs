@@ -0,0 +1,450 @@
+use anyhow::{anyhow, bail, Result};
+use awc::http::Version;
+use awc::Client;
+use bld_supervisor::client::unix_socket_client;
+use serde_json::{Map, Value};
+use shiplift::{ContainerOptions, Docker, Error as DockerError};
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+use tracing::{debug, warn};
+
+const DOCKER_RETRY_ATTEMPTS: u32 = 3;
+const DOCKER_RETRY_BASE_DELAY_MILLIS: u64 = 500;
+const DOCKER_BREAKER_THRESHOLD: u64 = 5;
+const DOCKER_BREAKER_COOLDOWN_SECONDS: u64 = 30;
+
+/// The secret that a private registry is authenticated with.
+#[derive(Clone, PartialEq, Eq)]
+pub enum RegistrySecret {
+    Password { username: String, password: String },
+    Token(String),
+}
+
+/// The credentials of the private registry that an image is pulled from. The secret is
+/// left out of the debug output so that it never ends up in the logs.
+#[derive(Clone, PartialEq, Eq)]
+pub struct RegistryCredentials {
+    pub name: String,
+    pub server_address: String,
+    pub secret: RegistrySecret,
+}
+
+impl RegistryCredentials {
+    pub fn new(name: &str, server_address: &str, secret: RegistrySecret) -> Self {
+        Self {
+            name: name.to_string(),
+            server_address: server_address.to_string(),
+            secret,
+        }
+    }
+}
+
+impl Debug for RegistryCredentials {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RegistryCredentials")
+            .field("name", &self.name)
+            .field("server_address", &self.server_address)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Checks if a docker error was caused by a registry rejecting the credentials of a pull.
+pub fn is_auth_error(error: &DockerError) -> bool {
+    match error {
+        DockerError::Fault { code, message } => {
+            let message = message.to_lowercase();
+            code.as_u16() == 401
+                || code.as_u16() == 403
+                || message.contains("unauthorized")
+                || message.contains("authentication required")
+        }
+        _ => false,
+    }
+}
+
+/// A remote docker daemon that the container of a pipeline is created on. The tls certificate path
+/// is a directory with the `ca.pem`, `cert.pem` and `key.pem` files used to connect to the daemon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DockerHost {
+    pub url: String,
+    pub tls_cert_path: Option<String>,
+}
+
+impl DockerHost {
+    pub fn new(url: &str, tls_cert_path: Option<&str>) -> Self {
+        Self {
+            url: url.to_string(),
+            tls_cert_path: tls_cert_path.map(|p| p.to_string()),
+        }
+    }
+
+    /// The url of a host without certificates. Shiplift connects to every tcp url with the
+    /// certificates of the `DOCKER_CERT_PATH` variable of the process, so the url is changed
+    /// to http in order to not use them for this host.
+    pub fn plain_url(&self) -> String {
+        self.url.replacen("tcp://", "http://", 1)
+    }
+}
+
+#[derive(Default)]
+struct Breaker {
+    consecutive_failures: u64,
+    opened_at: Option<Instant>,
+}
+
+/// Retry layer for the docker api calls. Only transient errors (connection failures and
+/// server errors of the daemon) of idempotent calls are retried with an exponential backoff,
+/// while calls such as creating a container or starting an exec are made once since a failed
+/// response doesn't mean that the daemon didn't act on them. After a number of consecutive
+/// failures the breaker opens and every call fails fast until the cooldown passes.
+#[derive(Default)]
+pub struct DockerRetry {
+    breaker: Mutex<Breaker>,
+    retries: AtomicU64,
+    breaker_trips: AtomicU64,
+}
+
+/// The retry layers of the docker hosts of the process by the url of each host, shared by the
+/// containers of a host so that the breaker opens for a daemon that fails across the containers of
+/// a run without failing the calls to the other hosts.
+static DOCKER_RETRIES: OnceLock<Mutex<HashMap<String, Arc<DockerRetry>>>> = OnceLock::new();
+
+/// The retry layer for the docker api calls to a host.
+pub fn docker_retry(host: &str) -> Arc<DockerRetry> {
+    let mut retries = DOCKER_RETRIES.get_or_init(Default::default).lock().unwrap();
+    retries.entry(host.to_string()).or_default().clone()
+}
+
+/// The retries and circuit breaker trips of the docker api calls of the process so far,
+/// across all of its hosts.
+pub fn docker_counters() -> (u64, u64) {
+    let retries = DOCKER_RETRIES.get_or_init(Default::default).lock().unwrap();
+    retries.values().fold((0, 0), |(r, t), retry| {
+        (r + retry.retries(), t + retry.breaker_trips())
+    })
+}
+
+impl DockerRetry {
+    pub fn retries(&self) -> u64 {
+        self.retries.load(Ordering::Relaxed)
+    }
+
+    pub fn breaker_trips(&self) -> u64 {
+        self.breaker_trips.load(Ordering::Relaxed)
+    }
+
+    fn is_transient(error: &DockerError) -> bool {
+        match error {
+            DockerError::Hyper(_) | DockerError::IO(_) => true,
+            DockerError::Fault { code, .. } => code.is_server_error() && !is_auth_error(error),
+            _ => false,
+        }
+    }
+
+    fn check_breaker(&self, operation: &str) -> Result<()> {
+        let mut breaker = self.breaker.lock().unwrap();
+        if let Some(opened_at) = breaker.opened_at {
+            if opened_at.elapsed() < Duration::from_secs(DOCKER_BREAKER_COOLDOWN_SECONDS) {
+                bail!("docker {operation} failed fast since the docker daemon is unavailable");
+            }
+            debug!("docker circuit breaker cooldown passed, allowing {operation}");
+            breaker.opened_at = None;
+        }
+        Ok(())
+    }
+
+    fn on_success(&self) {
+        let mut breaker = self.breaker.lock().unwrap();
+        breaker.consecutive_failures = 0;
+    }
+
+    fn on_transient_failure(&self) {
+        let mut breaker = self.breaker.lock().unwrap();
+        breaker.consecutive_failures += 1;
+        if breaker.consecutive_failures >= DOCKER_BREAKER_THRESHOLD && breaker.opened_at.is_none() {
+            warn!(
+                "docker circuit breaker opened after {} consecutive failures",
+                breaker.consecutive_failures
+            );
+            breaker.opened_at = Some(Instant::now());
+            self.breaker_trips.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Makes a call that isn't idempotent without retrying it, while still counting its
+    /// failures for the breaker.
+    pub async fn call_once<T, Fut>(&self, operation: &str, f: Fut) -> Result<T>
+    where
+        Fut: Future<Output = Result<T, DockerError>>,
+    {
+        self.check_breaker(operation)?;
+        match f.await {
+            Ok(value) => {
+                self.on_success();
+                Ok(value)
+            }
+            Err(e) => {
+                if Self::is_transient(&e) {
+                    self.on_transient_failure();
+                }
+                Err(anyhow!(e))
+            }
+        }
+    }
+
+    /// Makes an idempotent call, retrying it on transient errors.
+    pub async fn call<T, F, Fut>(&self, operation: &str, mut f: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, DockerError>>,
+    {
+        let mut attempt = 1;
+        loop {
+            self.check_breaker(operation)?;
+            let error = match f().await {
+                Ok(value) => {
+                    self.on_success();
+                    return Ok(value);
+                }
+                Err(e) if Self::is_transient(&e) => e,
+                Err(e) => return Err(anyhow!(e)),
+            };
+            self.on_transient_failure();
+            if attempt >= DOCKER_RETRY_ATTEMPTS {
+                return Err(anyhow!(error));
+            }
+            let delay = DOCKER_RETRY_BASE_DELAY_MILLIS * 2u64.pow(attempt - 1);
+            warn!(
+                "docker {operation} failed with {error}, retrying in {delay}ms (attempt {}/{})",
+                attempt + 1,
+                DOCKER_RETRY_ATTEMPTS
+            );
+            self.retries.fetch_add(1, Ordering::Relaxed);
+            sleep(Duration::from_millis(delay)).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// The version of the docker engine and of its api, used in the startup information of bld.
+pub async fn docker_version(docker_url: &str) -> Result<String> {
+    let client = Docker::host(docker_url.parse()?);
+    let version = client.version().await?;
+    Ok(format!("{} (api {})", version.version, version.api_version))
+}
+
+/// The body of a container create request with the host config entries merged into the ones
+/// of the container options.
+fn create_body(options: &ContainerOptions, host_config: Map<String, Value>) -> Result<Value> {
+    let mut body: Value = serde_json::from_str(&options.serialize()?)?;
+    if let Some(Value::Object(config)) = body.get_mut("HostConfig") {
+        config.extend(host_config);
+    }
+    Ok(body)
+}
+
+/// Creates a container using the create endpoint of the docker api, adding to the host config of the
+/// container options the entries that shiplift doesn't support, such as the oom score adjustment,
+/// disabling the oom killer or the pids limit.
+pub async fn create_container(
+    docker_url: &str,
+    tls: bool,
+    options: &ContainerOptions,
+    host_config: Map<String, Value>,
+) -> Result<String> {
+    let (client, host) = match docker_url.strip_prefix("unix://") {
+        Some(socket) => (unix_socket_client(socket), "http://localhost".to_string()),
+        None if tls => {
+            bail!("creating containers with these options is not supported for docker hosts that use tls")
+        }
+        None => {
+            let client = Client::builder()
+                .max_http_version(Version::HTTP_11)
+                .finish();
+            (client, docker_url.replacen("tcp://", "http://", 1))
+        }
+    };
+    let body = create_body(options, host_config)?;
+    let url = format!("{host}/containers/create");
+    debug!("creating container on {url} with {body}");
+    let mut response = client
+        .post(url)
+        .send_json(&body)
+        .await
+        .map_err(|e| anyhow!(e.to_string()))?;
+    let content = response.body().await.map_err(|e| anyhow!(e.to_string()))?;
+    if !response.status().is_success() {
+        bail!(
+            "could not create container. {}",
+            String::from_utf8_lossy(&content)
+        );
+    }
+    let info: Value = serde_json::from_slice(&content)?;
+    info["Id"]
+        .as_str()
+        .map(|id| id.to_string())
+        .ok_or_else(|| anyhow!("no id found in the response of the container create"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn fault(code: u16) -> DockerError {
+        DockerError::Fault {
+            code: code.try_into().unwrap(),
+            message: String::new(),
+        }
+    }
+
+    #[test]
+    fn docker_retry_is_shared_by_the_containers_of_a_host() {
+        let local = docker_retry("unix:///var/run/docker.sock");
+        assert!(Arc::ptr_eq(
+            &local,
+            &docker_retry("unix:///var/run/docker.sock")
+        ));
+        assert!(!Arc::ptr_eq(&local, &docker_retry("tcp://build-box:2376")));
+    }
+
+    #[tokio::test]
+    async fn docker_retry_breaker_of_a_host_does_not_affect_the_other_hosts() {
+        let remote = docker_retry("tcp://failing-box:2376");
+        for _ in 0..2 {
+            let _: Result<()> = remote.call("test", || async { Err(fault(503)) }).await;
+        }
+        let remote_result: Result<()> = remote.call("test", || async { Ok(()) }).await;
+        let local_result: Result<()> = docker_retry("unix:///tmp/bld-test-docker.sock")
+            .call("test", || async { Ok(()) })
+            .await;
+
+        assert!(remote_result.is_err());
+        assert!(local_result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn docker_retry_does_not_retry_calls_that_are_not_idempotent() {
+        let retry = DockerRetry::default();
+        let calls = Cell::new(0);
+        let result: Result<()> = retry
+            .call_once("test", async {
+                calls.set(calls.get() + 1);
+                Err(fault(500))
+            })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 1);
+        assert_eq!(retry.retries(), 0);
+    }
+
+    #[test]
+    fn docker_hosts_without_certificates_use_plain_urls() {
+        let host = DockerHost::new("tcp://build-box:2375", None);
+        assert_eq!(host.plain_url(), "http://build-box:2375");
+        let host = DockerHost::new("http://build-box:2375", None);
+        assert_eq!(host.plain_url(), "http://build-box:2375");
+    }
+
+    #[test]
+    fn container_create_body_includes_the_host_config_entries() {
+        let options = ContainerOptions::builder("ubuntu").memory(1024).build();
+        let mut host_config = Map::new();
+        host_config.insert("OomScoreAdj".to_string(), Value::from(500));
+        host_config.insert("OomKillDisable".to_string(), Value::Bool(true));
+        host_config.insert("PidsLimit".to_string(), Value::from(64));
+
+        let body = create_body(&options, host_config).unwrap();
+
+        assert_eq!(body["Image"], "ubuntu");
+        assert_eq!(body["HostConfig"]["Memory"], 1024);
+        assert_eq!(body["HostConfig"]["OomScoreAdj"], 500);
+        assert_eq!(body["HostConfig"]["OomKillDisable"], true);
+        assert_eq!(body["HostConfig"]["PidsLimit"], 64);
+    }
+
+    #[tokio::test]
+    async fn docker_retry_retries_server_errors() {
+        let retry = DockerRetry::default();
+        let calls = Cell::new(0);
+        let result = retry
+            .call("test", || {
+                calls.set(calls.get() + 1);
+                let attempt = calls.get();
+                async move {
+                    if attempt < 3 {
+                        Err(fault(500))
+                    } else {
+                        Ok(attempt)
+                    }
+                }
+            })
+            .await;
+
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(retry.retries(), 2);
+    }
+
+    #[tokio::test]
+    async fn docker_retry_does_not_retry_client_errors() {
+        let retry = DockerRetry::default();
+        let calls = Cell::new(0);
+        let result: Result<()> = retry
+            .call("test", || {
+                calls.set(calls.get() + 1);
+                async { Err(fault(404)) }
+            })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 1);
+        assert_eq!(retry.retries(), 0);
+    }
+
+    #[tokio::test]
+    async fn docker_retry_does_not_retry_authentication_errors() {
+        let retry = DockerRetry::default();
+        let calls = Cell::new(0);
+        let result: Result<()> = retry
+            .call("test", || {
+                calls.set(calls.get() + 1);
+                async {
+                    Err(DockerError::Fault {
+                        code: 500.try_into().unwrap(),
+                        message: "unauthorized: incorrect username or password".to_string(),
+                    })
+                }
+            })
+            .await;
+
+        let error = result.unwrap_err();
+        assert!(is_auth_error(error.downcast_ref::<DockerError>().unwrap()));
+        assert_eq!(calls.get(), 1);
+        assert!(!is_auth_error(&fault(500)));
+    }
+
+    #[tokio::test]
+    async fn docker_retry_breaker_fails_fast_after_consecutive_failures() {
+        let retry = DockerRetry::default();
+        for _ in 0..2 {
+            let _: Result<()> = retry.call("test", || async { Err(fault(503)) }).await;
+        }
+        let calls = Cell::new(0);
+        let result: Result<()> = retry
+            .call("test", || {
+                calls.set(calls.get() + 1);
+                async { Ok(()) }
+            })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 0);
+        assert_eq!(retry.breaker_trips(), 1);
+    }
+}
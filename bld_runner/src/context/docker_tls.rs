@@ -0,0 +1,153 @@
+use crate::context::DockerHost;
+#[cfg(unix)]
+use anyhow::anyhow;
+use anyhow::{bail, Result};
+#[cfg(unix)]
+use hyper::Uri;
+#[cfg(unix)]
+use openssl::ssl::{SslConnector, SslFiletype, SslMethod};
+#[cfg(unix)]
+use std::env::temp_dir;
+use std::fs::remove_dir_all;
+#[cfg(unix)]
+use std::fs::DirBuilder;
+#[cfg(unix)]
+use std::os::unix::fs::DirBuilderExt;
+#[cfg(unix)]
+use std::path::Path;
+use std::path::PathBuf;
+#[cfg(unix)]
+use std::pin::Pin;
+#[cfg(unix)]
+use tokio::io::copy_bidirectional;
+#[cfg(unix)]
+use tokio::net::{TcpStream, UnixListener, UnixStream};
+#[cfg(unix)]
+use tokio::spawn;
+use tokio::task::JoinHandle;
+#[cfg(unix)]
+use tokio_openssl::SslStream;
+use tracing::debug;
+#[cfg(unix)]
+use tracing::warn;
+#[cfg(unix)]
+use uuid::Uuid;
+
+#[cfg(unix)]
+const DOCKER_TLS_PORT: u16 = 2376;
+const DOCKER_TLS_SOCKET: &str = "docker.sock";
+
+/// Forwards the connections of a unix socket to a remote docker daemon over tls with the certificates
+/// of its host. Shiplift reads the certificates of a client only from the environment of the process,
+/// so the client of a host with its own certificates connects to this socket instead, which is created
+/// in a directory that only the user of the process can access and is removed along with the tunnel.
+/// There are no unix sockets to forward on windows, where hosts with certificates aren't supported.
+pub struct DockerTlsTunnel {
+    dir: PathBuf,
+    task: JoinHandle<()>,
+}
+
+impl DockerTlsTunnel {
+    #[cfg(unix)]
+    pub fn start(host: &DockerHost, cert_path: &str) -> Result<Self> {
+        let uri: Uri = host.url.parse()?;
+        let address = uri
+            .host()
+            .ok_or_else(|| anyhow!("no host found in the docker url {}", host.url))?
+            .to_string();
+        let port = uri.port_u16().unwrap_or(DOCKER_TLS_PORT);
+        let connector = tls_connector(cert_path)?;
+        let dir = temp_dir().join(format!("bld-docker-{}", Uuid::new_v4()));
+        DirBuilder::new().mode(0o700).create(&dir)?;
+        let listener = UnixListener::bind(dir.join(DOCKER_TLS_SOCKET))?;
+        let task = spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        warn!("docker tls tunnel to {address}:{port} stopped, {e}");
+                        break;
+                    }
+                };
+                let connector = connector.clone();
+                let address = address.clone();
+                spawn(async move {
+                    if let Err(e) = forward(stream, &address, port, &connector).await {
+                        debug!("docker tls connection to {address}:{port} closed with {e}");
+                    }
+                });
+            }
+        });
+        Ok(Self { dir, task })
+    }
+
+    #[cfg(not(unix))]
+    pub fn start(_host: &DockerHost, _cert_path: &str) -> Result<Self> {
+        bail!("the tls-cert of docker-remote is supported only on unix")
+    }
+
+    /// The socket that the docker client of the host connects to.
+    pub fn socket(&self) -> PathBuf {
+        self.dir.join(DOCKER_TLS_SOCKET)
+    }
+}
+
+impl Drop for DockerTlsTunnel {
+    fn drop(&mut self) {
+        self.task.abort();
+        if let Err(e) = remove_dir_all(&self.dir) {
+            debug!("could not remove docker tls tunnel directory, {e}");
+        }
+    }
+}
+
+/// Creates the tls connector of a docker host, which verifies the daemon with the ca of the host.
+#[cfg(unix)]
+fn tls_connector(cert_path: &str) -> Result<SslConnector> {
+    let path = Path::new(cert_path);
+    for file in ["ca.pem", "cert.pem", "key.pem"] {
+        if !path.join(file).is_file() {
+            bail!("{file} not found in the tls certificates directory {cert_path}");
+        }
+    }
+    let mut builder = SslConnector::builder(SslMethod::tls())?;
+    builder.set_certificate_file(path.join("cert.pem"), SslFiletype::PEM)?;
+    builder.set_private_key_file(path.join("key.pem"), SslFiletype::PEM)?;
+    builder.set_ca_file(path.join("ca.pem"))?;
+    Ok(builder.build())
+}
+
+#[cfg(unix)]
+async fn forward(
+    mut stream: UnixStream,
+    address: &str,
+    port: u16,
+    connector: &SslConnector,
+) -> Result<()> {
+    let tcp = TcpStream::connect((address, port)).await?;
+    let ssl = connector.configure()?.into_ssl(address)?;
+    let mut tls = SslStream::new(ssl, tcp)?;
+    Pin::new(&mut tls).connect().await?;
+    copy_bidirectional(&mut stream, &mut tls).await?;
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn docker_tls_tunnel_requires_the_certificates_of_the_host() {
+        let dir = temp_dir().join(format!("bld-docker-certs-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let host = DockerHost::new("tcp://build-box:2376", dir.to_str());
+
+        let error = DockerTlsTunnel::start(&host, dir.to_str().unwrap())
+            .err()
+            .unwrap()
+            .to_string();
+
+        assert!(error.starts_with("ca.pem not found"), "{error}");
+        remove_dir_all(dir).unwrap();
+    }
+}
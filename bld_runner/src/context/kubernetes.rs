@@ -0,0 +1,240 @@
+use crate::context::{is_shell_identifier, quote, OutputDecoder, OutputSink, StepEnv};
+use anyhow::{anyhow, bail, Result};
+use bld_core::execution::Execution;
+use bld_core::logger::Logger;
+use k8s_openapi::api::core::v1::{Container, EnvVar, Pod, PodSpec};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{ObjectMeta, Status};
+use kube::api::{AttachParams, DeleteParams, PostParams};
+use kube::{Api, Client};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::time::sleep;
+use tracing::{debug, error};
+use uuid::Uuid;
+
+type AtomicLogger = Arc<Mutex<Logger>>;
+
+/// The name of the container of the pod that the commands are executed in.
+const POD_CONTAINER: &str = "bld";
+
+/// The number of seconds to wait for the pod to start running.
+const POD_START_TIMEOUT: u64 = 300;
+
+const STOP_SIGNAL_INTERVAL_MILLIS: u64 = 1000;
+
+pub struct KubernetesPlatform {
+    pub name: String,
+    pub image: String,
+    pub namespace: String,
+    pub pods: Api<Pod>,
+    pub path_prepend: Vec<String>,
+    pub logger: AtomicLogger,
+}
+
+impl KubernetesPlatform {
+    fn pod(
+        name: &str,
+        image: &str,
+        service_account: &Option<String>,
+        env: &HashMap<String, String>,
+    ) -> Pod {
+        let env = env
+            .iter()
+            .map(|(name, value)| EnvVar {
+                name: name.to_string(),
+                value: Some(value.to_string()),
+                ..Default::default()
+            })
+            .collect();
+        let mut labels = BTreeMap::new();
+        labels.insert(
+            "app.kubernetes.io/managed-by".to_string(),
+            "bld".to_string(),
+        );
+        Pod {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                labels: Some(labels),
+                ..Default::default()
+            },
+            spec: Some(PodSpec {
+                containers: vec![Container {
+                    name: POD_CONTAINER.to_string(),
+                    image: Some(image.to_string()),
+                    // the pod is kept alive until it's deleted so that the commands
+                    // of every step are executed in it.
+                    command: Some(vec![
+                        "sh".to_string(),
+                        "-c".to_string(),
+                        "trap 'exit 0' TERM; while true; do sleep 1; done".to_string(),
+                    ]),
+                    env: Some(env),
+                    ..Default::default()
+                }],
+                restart_policy: Some("Never".to_string()),
+                service_account_name: service_account.clone(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    async fn wait_running(pods: &Api<Pod>, name: &str) -> Result<()> {
+        for _ in 0..POD_START_TIMEOUT {
+            let pod = pods.get(name).await?;
+            let phase = pod.status.and_then(|s| s.phase).unwrap_or_default();
+            debug!("pod {name} is in phase {phase}");
+            match phase.as_str() {
+                "Running" => return Ok(()),
+                "Succeeded" | "Failed" => bail!("pod {name} exited before running any command"),
+                _ => sleep(Duration::from_secs(1)).await,
+            }
+        }
+        bail!("pod {name} did not start after {POD_START_TIMEOUT} seconds")
+    }
+
+    pub async fn new(
+        image: &str,
+        namespace: &str,
+        service_account: &Option<String>,
+        path_prepend: Vec<String>,
+        env: Arc<HashMap<String, String>>,
+        logger: AtomicLogger,
+    ) -> Result<Self> {
+        let client = Client::try_default().await?;
+        let pods: Api<Pod> = Api::namespaced(client, namespace);
+        let name = format!("bld-{}", Uuid::new_v4());
+        {
+            let mut logger = logger.lock().unwrap();
+            logger.info(&format!("Create pod: {name} in namespace {namespace}"));
+        }
+        let pod = Self::pod(&name, image, service_account, &env);
+        pods.create(&PostParams::default(), &pod).await?;
+        let platform = Self {
+            name,
+            image: image.to_string(),
+            namespace: namespace.to_string(),
+            pods,
+            path_prepend,
+            logger,
+        };
+        if let Err(e) = Self::wait_running(&platform.pods, &platform.name).await {
+            let _ = platform.dispose().await;
+            return Err(e);
+        }
+        Ok(platform)
+    }
+
+    /// Extracts the exit code of a command from the status that the api server
+    /// sends when an exec finishes.
+    fn exit_code(status: &Status) -> Option<i64> {
+        if status.status.as_deref() == Some("Success") {
+            return Some(0);
+        }
+        status
+            .details
+            .as_ref()
+            .and_then(|d| d.causes.as_ref())
+            .and_then(|causes| {
+                causes
+                    .iter()
+                    .find(|c| c.reason.as_deref() == Some("ExitCode"))
+                    .and_then(|c| c.message.as_ref())
+                    .and_then(|m| m.parse().ok())
+            })
+    }
+
+    pub async fn sh(
+        &self,
+        working_dir: &Option<String>,
+        input: &str,
+        prefix: &Option<String>,
+        ssh_agent_forwarding: bool,
+        env: &StepEnv,
+        ex: Arc<Mutex<Execution>>,
+    ) -> Result<()> {
+        if ssh_agent_forwarding {
+            bail!("ssh agent forwarding is not supported for kubernetes pods");
+        }
+        let input = working_dir
+            .as_ref()
+            .map(|wd| format!("cd {} && {input}", quote(wd)))
+            .unwrap_or_else(|| input.to_string());
+        let input = if self.path_prepend.is_empty() {
+            input
+        } else {
+            let dirs = self.path_prepend.join(":");
+            format!("export PATH=\"{dirs}:$PATH\" && {input}")
+        };
+        let mut exports = String::new();
+        for (name, value) in env.vars.iter() {
+            if !is_shell_identifier(name) {
+                bail!("invalid environment variable name {name} for a kubernetes pod");
+            }
+            exports.push_str(&format!("export {name}={}; ", quote(value)));
+        }
+        let input = format!("{exports}{input}");
+        // stderr is redirected to stdout in order to keep the order of the output.
+        let command = vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            format!("{{ {input}\n}} 2>&1"),
+        ];
+        let params = AttachParams::default()
+            .container(POD_CONTAINER)
+            .stderr(false);
+        let mut process = self.pods.exec(&self.name, command, &params).await?;
+        let mut stdout = process
+            .stdout()
+            .ok_or_else(|| anyhow!("could not attach to the output of the pod"))?;
+
+        let mut sink = OutputSink::new(&self.logger, prefix);
+        let mut decoder = OutputDecoder::new(env.output_encoding);
+        let mut buffer = [0; 1024];
+        loop {
+            // the stop signal is checked periodically as well, so that a command without output
+            // can be stopped.
+            let read = tokio::select! {
+                read = stdout.read(&mut buffer) => Some(read?),
+                _ = sleep(Duration::from_millis(STOP_SIGNAL_INTERVAL_MILLIS)) => None,
+            };
+
+            {
+                let exec = ex.lock().unwrap();
+                exec.check_stop_signal()?
+            }
+
+            let read = match read {
+                Some(0) => break,
+                Some(read) => read,
+                None => continue,
+            };
+            sink.write(&decoder.decode(&buffer[..read]));
+        }
+        sink.finish(&decoder.finish(), decoder.is_lossy());
+
+        let status = process.await;
+        match status.as_ref().and_then(Self::exit_code) {
+            Some(0) => Ok(()),
+            Some(code) => bail!("command finished with exit code: {code}"),
+            None => bail!(
+                "command failed. {}",
+                status.and_then(|s| s.message).unwrap_or_default()
+            ),
+        }
+    }
+
+    pub async fn dispose(&self) -> Result<()> {
+        let params = DeleteParams {
+            grace_period_seconds: Some(0),
+            ..Default::default()
+        };
+        if let Err(e) = self.pods.delete(&self.name, &params).await {
+            error!("could not delete pod {}, {e}", self.name);
+            bail!(e);
+        }
+        Ok(())
+    }
+}
@@ -1,23 +1,209 @@
+use crate::context::archive::ArtifactSource;
+use crate::context::{prefix_lines, Credentials, OutputDecoder, StepEnv, SSH_AUTH_SOCK};
+use crate::CgroupLimits;
+use crate::OutputEncoding;
 use anyhow::{anyhow, bail, Result};
 use bld_config::definitions::LOCAL_MACHINE_TMP_DIR;
-use bld_config::{os_name, path, OSname};
+use bld_config::{os_name, path, BldCredentialConfig, OSname};
+use bld_core::execution::Execution;
 use bld_core::logger::Logger;
+#[cfg(unix)]
+use nix::sys::signal::{killpg, Signal};
+#[cfg(unix)]
+use nix::unistd::{setpgid, Pid};
 use std::collections::HashMap;
 use std::env::current_dir;
 use std::fmt::Write;
-use std::fs::{copy, create_dir_all};
+use std::fs::{copy, create_dir_all, read_dir, read_to_string, remove_dir, write};
+#[cfg(unix)]
+use std::io;
 use std::path::{Path, PathBuf};
-use std::process::{Command, ExitStatus};
+use std::process::{ExitStatus, Output, Stdio};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::time::{sleep, timeout};
+use tracing::debug;
 
-fn could_not_spawn_shell() -> Result<()> {
+const STOP_SIGNAL_INTERVAL_MILLIS: u64 = 1000;
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+const CGROUP_DIR: &str = "bld";
+const CGROUP_CONTROLLERS: [&str; 2] = ["memory", "cpu"];
+const CGROUP_CPU_PERIOD: u64 = 100_000;
+
+fn could_not_spawn_shell<T>() -> Result<T> {
     Err(anyhow!("could not spawn shell"))
 }
 
+/// Copies the contents of a directory into the target directory, creating it if it doesn't exist.
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    create_dir_all(to)?;
+    for entry in read_dir(from)? {
+        let path = entry?.path();
+        let target = to.join(path.file_name().unwrap_or_default());
+        if path.is_dir() {
+            copy_dir(&path, &target)?;
+        } else {
+            copy(&path, &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// The process group of a spawned command. Terminates all the processes of the group when
+/// dropped, in order for processes forked by the command to not be orphaned when the
+/// pipeline is stopped or times out. A stopped command is terminated explicitly and is
+/// killed if it doesn't exit within the graceful shutdown period. On windows the process tree
+/// of the command is killed with taskkill, since there are no process groups to signal.
+struct ProcessGroup {
+    pid: Option<u32>,
+}
+
+impl ProcessGroup {
+    fn new(pid: Option<u32>) -> Self {
+        Self { pid }
+    }
+
+    fn finished(&mut self) {
+        self.pid = None;
+    }
+
+    #[cfg(unix)]
+    fn signal(&self, signal: Signal) {
+        if let Some(pid) = self.pid {
+            let pgid = Pid::from_raw(pid as i32);
+            debug!("sending {signal} to process group {pgid}");
+            if let Err(e) = killpg(pgid, signal) {
+                debug!("could not send {signal} to process group {pgid}. {e}");
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn kill_tree(&self) {
+        if let Some(pid) = self.pid {
+            debug!("killing the process tree of {pid}");
+            let status = std::process::Command::new("taskkill")
+                .args(["/PID", &pid.to_string(), "/T", "/F"])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status();
+            if let Err(e) = status {
+                debug!("could not kill the process tree of {pid}. {e}");
+            }
+        }
+    }
+
+    fn terminate(&self) {
+        #[cfg(unix)]
+        self.signal(Signal::SIGTERM);
+        #[cfg(not(unix))]
+        self.kill_tree();
+    }
+
+    fn kill(&mut self) {
+        #[cfg(unix)]
+        self.signal(Signal::SIGKILL);
+        #[cfg(not(unix))]
+        self.kill_tree();
+        self.pid = None;
+    }
+}
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        self.terminate();
+    }
+}
+
+/// A cgroup v2 that the process of a run on a machine is moved into, in order to limit the
+/// resources of the commands of its steps which inherit the cgroup of the process.
+pub struct MachineCgroup {
+    path: PathBuf,
+    original: PathBuf,
+}
+
+impl MachineCgroup {
+    /// Creates the cgroup of the run under the bld cgroup and moves the current process into it.
+    /// The limits are ignored on systems that don't support cgroup v2.
+    pub fn create(run_id: &str, limits: &CgroupLimits) -> Result<Option<Self>> {
+        let root = Path::new(CGROUP_ROOT);
+        if os_name() != OSname::Linux || !root.join("cgroup.controllers").is_file() {
+            debug!("cgroup v2 is not supported, ignoring the cgroup limits of run {run_id}");
+            return Ok(None);
+        }
+        Self::isolate(root, run_id, limits)
+            .map(Some)
+            .map_err(|e| anyhow!("unable to create the cgroup of run {run_id}, {e}"))
+    }
+
+    fn isolate(root: &Path, run_id: &str, limits: &CgroupLimits) -> Result<Self> {
+        let original = Self::current(root)?;
+        let parent = root.join(CGROUP_DIR);
+        create_dir_all(&parent)?;
+        Self::enable_controllers(root)?;
+        Self::enable_controllers(&parent)?;
+        let path = parent.join(run_id);
+        create_dir_all(&path)?;
+        if let Some(memory_mb) = limits.memory_mb {
+            let bytes = memory_mb * 1024 * 1024;
+            write(path.join("memory.max"), bytes.to_string())?;
+        }
+        if let Some(percent) = limits.cpu_quota_percent {
+            let quota = percent * CGROUP_CPU_PERIOD / 100;
+            write(path.join("cpu.max"), format!("{quota} {CGROUP_CPU_PERIOD}"))?;
+        }
+        debug!(
+            "moving process {} to cgroup {}",
+            std::process::id(),
+            path.display()
+        );
+        write(path.join("cgroup.procs"), std::process::id().to_string())?;
+        Ok(Self { path, original })
+    }
+
+    /// The cgroup of the current process, from the entry of the unified hierarchy.
+    fn current(root: &Path) -> Result<PathBuf> {
+        let content = read_to_string("/proc/self/cgroup")?;
+        let current = content
+            .lines()
+            .find_map(|line| line.strip_prefix("0::"))
+            .ok_or_else(|| anyhow!("the process is not part of a cgroup v2 hierarchy"))?;
+        Ok(root.join(current.trim_start_matches('/')))
+    }
+
+    /// Enables the memory and cpu controllers for the children of a cgroup, if they aren't already.
+    fn enable_controllers(path: &Path) -> Result<()> {
+        let subtree_control = path.join("cgroup.subtree_control");
+        let enabled = read_to_string(&subtree_control)?;
+        let missing: Vec<String> = CGROUP_CONTROLLERS
+            .iter()
+            .filter(|c| !enabled.split_whitespace().any(|e| e == **c))
+            .map(|c| format!("+{c}"))
+            .collect();
+        if !missing.is_empty() {
+            write(subtree_control, missing.join(" "))?;
+        }
+        Ok(())
+    }
+
+    /// Moves the current process back to its original cgroup and removes the cgroup of the run.
+    pub fn dispose(&self) -> Result<()> {
+        write(
+            self.original.join("cgroup.procs"),
+            std::process::id().to_string(),
+        )?;
+        remove_dir(&self.path)?;
+        Ok(())
+    }
+}
+
 pub struct Machine {
     tmp_dir: String,
     env: Arc<HashMap<String, String>>,
     lg: Arc<Mutex<Logger>>,
+    credentials: Option<Credentials>,
+    graceful_shutdown_secs: u64,
 }
 
 impl Machine {
@@ -25,17 +211,66 @@ impl Machine {
         id: &str,
         env: Arc<HashMap<String, String>>,
         lg: Arc<Mutex<Logger>>,
+        graceful_shutdown_secs: u64,
     ) -> Result<Self> {
         let tmp_path = path![current_dir()?, LOCAL_MACHINE_TMP_DIR, id];
         let tmp_dir = tmp_path.display().to_string();
         if !tmp_path.is_dir() {
             create_dir_all(tmp_path)?;
         }
-        Ok(Self { tmp_dir, env, lg })
+        Ok(Self {
+            tmp_dir,
+            env,
+            lg,
+            credentials: None,
+            graceful_shutdown_secs,
+        })
+    }
+
+    pub fn install_credentials(&mut self, entries: &[&BldCredentialConfig]) -> Result<()> {
+        self.credentials = Some(Credentials::machine(&self.tmp_dir, entries)?);
+        Ok(())
+    }
+
+    pub fn remove_credentials(&self) -> Result<()> {
+        match &self.credentials {
+            Some(credentials) => credentials.remove(),
+            None => Ok(()),
+        }
     }
 
+    fn create_parent(to: &str) -> Result<()> {
+        if let Some(parent) = Path::new(to).parent() {
+            create_dir_all(parent)?;
+        }
+        Ok(())
+    }
+
+    /// Copies a file to the target path, or the contents of a directory and the paths that
+    /// matched a glob into the target directory, creating it if it doesn't exist. The files are
+    /// copied directly since they are on the same file system, so they are never compressed.
     fn copy(&self, from: &str, to: &str) -> Result<()> {
-        copy(Path::new(from), Path::new(to))?;
+        let to = Path::new(to);
+        match ArtifactSource::local(from)? {
+            ArtifactSource::File(path) => {
+                Machine::create_parent(&to.display().to_string())?;
+                copy(path, to)?;
+            }
+            ArtifactSource::Dir(path) => copy_dir(&path, to)?,
+            ArtifactSource::Glob(paths) => {
+                create_dir_all(to)?;
+                for path in paths.iter() {
+                    let name = path
+                        .file_name()
+                        .ok_or_else(|| anyhow!("invalid artifact path {}", path.display()))?;
+                    if path.is_dir() {
+                        copy_dir(path, &to.join(name))?;
+                    } else {
+                        copy(path, to.join(name))?;
+                    }
+                }
+            }
+        }
         Ok(())
     }
 
@@ -47,7 +282,17 @@ impl Machine {
         self.copy(from, to)
     }
 
-    pub async fn sh(&self, working_dir: &Option<String>, input: &str) -> Result<()> {
+    #[allow(clippy::too_many_arguments)]
+    pub async fn sh(
+        &self,
+        working_dir: &Option<String>,
+        input: &str,
+        prefix: &Option<String>,
+        ssh_agent_forwarding: bool,
+        capture_stdout: bool,
+        env: &StepEnv,
+        exec: Arc<Mutex<Execution>>,
+    ) -> Result<String> {
         let os_name = os_name();
         let current_dir = working_dir.as_ref().unwrap_or(&self.tmp_dir).to_string();
         let current_dir = if Path::new(&current_dir).is_relative() {
@@ -66,30 +311,111 @@ impl Machine {
 
         let mut command = Command::new(shell);
         command.envs(&*self.env);
+        // the ssh agent of the host is available only to the steps that forward it.
+        if !ssh_agent_forwarding {
+            command.env_remove(SSH_AUTH_SOCK);
+        } else if std::env::var_os(SSH_AUTH_SOCK).is_none() {
+            bail!("unable to forward the ssh agent since {SSH_AUTH_SOCK} is not set");
+        }
+        if let Some(credentials) = &self.credentials {
+            command.envs(credentials.env().iter().map(|(name, value)| (name, value)));
+        }
+        command.envs(env.vars.iter().map(|(name, value)| (name, value)));
         command.args(&args);
         command.current_dir(current_dir);
+        command.kill_on_drop(true);
+        command.stdout(Stdio::piped());
+        command.stderr(Stdio::piped());
+        // placing the shell in a new process group so that the processes it spawns can be
+        // terminated along with it.
+        #[cfg(unix)]
+        unsafe {
+            command
+                .pre_exec(|| setpgid(Pid::from_raw(0), Pid::from_raw(0)).map_err(io::Error::from));
+        }
+
+        let child = command.spawn()?;
+        let mut group = ProcessGroup::new(child.id());
+        let output = child.wait_with_output();
+        tokio::pin!(output);
+        let process = loop {
+            tokio::select! {
+                result = &mut output => break result?,
+                _ = sleep(Duration::from_millis(STOP_SIGNAL_INTERVAL_MILLIS)) => {
+                    let stop_signal = {
+                        let exec = exec.lock().unwrap();
+                        exec.check_stop_signal()
+                    };
+                    if let Err(e) = stop_signal {
+                        group.terminate();
+                        let grace_period = Duration::from_secs(self.graceful_shutdown_secs);
+                        match timeout(grace_period, &mut output).await {
+                            Ok(result) => {
+                                group.finished();
+                                if let Ok(process) = result {
+                                    self.log_output(&process, prefix, env.output_encoding)?;
+                                }
+                            }
+                            Err(_) => {
+                                {
+                                    let mut logger = self.lg.lock().unwrap();
+                                    logger.errorln(&format!(
+                                        "[bld] Command didn't exit within {} seconds after SIGTERM, sending SIGKILL",
+                                        self.graceful_shutdown_secs
+                                    ));
+                                }
+                                group.kill();
+                            }
+                        }
+                        return Err(e);
+                    }
+                }
+            }
+        };
+        group.finished();
+        let stdout = self.log_output(&process, prefix, env.output_encoding)?;
 
-        let process = command.output()?;
+        if !ExitStatus::success(&process.status) {
+            bail!("command finished with {}", process.status);
+        }
+
+        Ok(if capture_stdout {
+            stdout
+        } else {
+            String::new()
+        })
+    }
+
+    /// Logs the output of the process and returns its decoded stdout.
+    fn log_output(
+        &self,
+        process: &Output,
+        prefix: &Option<String>,
+        encoding: OutputEncoding,
+    ) -> Result<String> {
         let mut output = String::new();
+        let mut stderr_decoder = OutputDecoder::new(encoding);
+        let mut stdout_decoder = OutputDecoder::new(encoding);
+        let stdout = stdout_decoder.decode_all(&process.stdout);
 
         if !process.stderr.is_empty() {
-            writeln!(output, "{}", String::from_utf8_lossy(&process.stderr))?;
+            writeln!(output, "{}", stderr_decoder.decode_all(&process.stderr))?;
         }
 
         if !process.stdout.is_empty() {
-            writeln!(output, "{}", String::from_utf8_lossy(&process.stdout))?;
+            writeln!(output, "{stdout}")?;
         }
 
-        {
-            let mut logger = self.lg.lock().unwrap();
-            logger.dump(&output);
+        if let Some(prefix) = prefix {
+            output = prefix_lines(prefix, &output);
         }
 
-        if !ExitStatus::success(&process.status) {
-            bail!("command finished with {}", process.status);
+        let mut logger = self.lg.lock().unwrap();
+        logger.dump(&output);
+        if stderr_decoder.is_lossy() || stdout_decoder.is_lossy() {
+            logger.invalid_output_notice();
         }
-
-        Ok(())
+        Ok(stdout)
     }
 
     pub fn dispose(&self) -> Result<()> {
@@ -97,3 +423,51 @@ impl Machine {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::remove_dir_all;
+    use uuid::Uuid;
+
+    #[test]
+    fn machine_copies_files_directories_and_globs() {
+        let root = temp_dir().join(format!("bld-machine-copy-{}", Uuid::new_v4()));
+        let source = root.join("source");
+        create_dir_all(source.join("nested")).unwrap();
+        write(source.join("a.txt"), "a").unwrap();
+        write(source.join("b.log"), "b").unwrap();
+        write(source.join("nested").join("c.txt"), "c").unwrap();
+        let machine = Machine {
+            tmp_dir: root.display().to_string(),
+            env: Arc::new(HashMap::new()),
+            lg: Logger::empty_atom(),
+            credentials: None,
+            graceful_shutdown_secs: 0,
+        };
+        let target = |name: &str| root.join(name).display().to_string();
+
+        machine
+            .copy_into(
+                &source.join("a.txt").display().to_string(),
+                &target("file/a.txt"),
+            )
+            .unwrap();
+        assert_eq!(read_to_string(root.join("file/a.txt")).unwrap(), "a");
+
+        machine
+            .copy_into(&source.display().to_string(), &target("dir"))
+            .unwrap();
+        assert_eq!(read_to_string(root.join("dir/nested/c.txt")).unwrap(), "c");
+        assert_eq!(read_to_string(root.join("dir/b.log")).unwrap(), "b");
+
+        machine
+            .copy_from(&format!("{}/*.txt", source.display()), &target("glob"))
+            .unwrap();
+        assert!(root.join("glob/a.txt").is_file());
+        assert!(!root.join("glob/b.log").exists());
+
+        remove_dir_all(root).unwrap();
+    }
+}
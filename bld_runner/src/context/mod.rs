@@ -1,5 +1,72 @@
+mod archive;
 mod container;
+mod credentials;
+mod docker;
+mod docker_tls;
+mod kubernetes;
 mod machine;
+mod output;
+mod ssh;
 
 pub use container::*;
+pub use credentials::*;
+pub use docker::*;
+pub use docker_tls::*;
+pub use kubernetes::*;
 pub use machine::*;
+pub use output::*;
+pub use ssh::*;
+
+use crate::OutputEncoding;
+
+pub(crate) const SSH_AUTH_SOCK: &str = "SSH_AUTH_SOCK";
+
+/// The environment variables of a step, which are set on top of the environment of the platform
+/// unless the step doesn't inherit the environment of the container, along with the encoding of
+/// the output of its commands.
+#[derive(Debug, Default, Clone)]
+pub struct StepEnv {
+    pub vars: Vec<(String, String)>,
+    pub inherit_container_env: bool,
+    pub output_encoding: OutputEncoding,
+}
+
+impl StepEnv {
+    pub fn new(
+        vars: Vec<(String, String)>,
+        inherit_container_env: bool,
+        output_encoding: OutputEncoding,
+    ) -> Self {
+        Self {
+            vars,
+            inherit_container_env,
+            output_encoding,
+        }
+    }
+}
+
+/// Prefixes every line of the text. Used in order to distinguish the output of steps that run in parallel.
+pub(crate) fn prefix_lines(prefix: &str, text: &str) -> String {
+    text.split_inclusive('\n')
+        .map(|line| {
+            if line.trim().is_empty() {
+                line.to_string()
+            } else {
+                format!("{prefix}{line}")
+            }
+        })
+        .collect()
+}
+
+/// Quotes a value in order to be used as a single word in a shell command.
+pub(crate) fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Checks that a name can be exported as a variable by a shell, since it's part of the
+/// command rather than a quoted value.
+pub(crate) fn is_shell_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
@@ -0,0 +1,272 @@
+use crate::context::prefix_lines;
+use crate::OutputEncoding;
+use bld_core::logger::Logger;
+use std::char::REPLACEMENT_CHARACTER;
+use std::str::from_utf8;
+use std::sync::Mutex;
+
+/// Converts the output of a command to text as it's received in chunks. An incomplete UTF-8
+/// sequence at the end of a chunk is kept for the next one so that characters split between
+/// chunks are decoded, while invalid bytes are replaced with U+FFFD. Latin-1 output is
+/// transcoded instead, since every byte is a character of it.
+#[derive(Debug, Default)]
+pub struct OutputDecoder {
+    encoding: OutputEncoding,
+    pending: Vec<u8>,
+    lossy: bool,
+}
+
+impl OutputDecoder {
+    pub fn new(encoding: OutputEncoding) -> Self {
+        Self {
+            encoding,
+            ..Default::default()
+        }
+    }
+
+    /// Whether bytes of the output have been replaced since they weren't valid UTF-8.
+    pub fn is_lossy(&self) -> bool {
+        self.lossy
+    }
+
+    pub fn decode(&mut self, bytes: &[u8]) -> String {
+        if self.encoding == OutputEncoding::Latin1 {
+            return bytes.iter().map(|b| *b as char).collect();
+        }
+        let mut buffer = std::mem::take(&mut self.pending);
+        buffer.extend_from_slice(bytes);
+        let mut text = String::with_capacity(buffer.len());
+        let mut rest = &buffer[..];
+        while !rest.is_empty() {
+            match from_utf8(rest) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    rest = &[];
+                }
+                Err(e) => {
+                    let (valid, invalid) = rest.split_at(e.valid_up_to());
+                    text.push_str(from_utf8(valid).unwrap_or_default());
+                    match e.error_len() {
+                        Some(len) => {
+                            text.push(REPLACEMENT_CHARACTER);
+                            self.lossy = true;
+                            rest = &invalid[len..];
+                        }
+                        None => {
+                            self.pending = invalid.to_vec();
+                            rest = &[];
+                        }
+                    }
+                }
+            }
+        }
+        text
+    }
+
+    /// Decodes the rest of the output, which is an incomplete sequence when the output
+    /// ended in the middle of a character.
+    pub fn finish(&mut self) -> String {
+        if self.pending.is_empty() {
+            return String::new();
+        }
+        self.pending.clear();
+        self.lossy = true;
+        REPLACEMENT_CHARACTER.to_string()
+    }
+
+    /// Decodes the whole output of a command.
+    pub fn decode_all(&mut self, bytes: &[u8]) -> String {
+        let mut text = self.decode(bytes);
+        text.push_str(&self.finish());
+        text
+    }
+}
+
+/// Writes the decoded output of a command to the logger as it's received. The output is written
+/// only on complete lines, since the chunks of steps that run in parallel are interleaved in the
+/// logger when a prefix is used and the values of secrets are masked only when they aren't split
+/// between chunks.
+pub(crate) struct OutputSink<'a> {
+    logger: &'a Mutex<Logger>,
+    prefix: &'a Option<String>,
+    pending: String,
+}
+
+impl<'a> OutputSink<'a> {
+    pub fn new(logger: &'a Mutex<Logger>, prefix: &'a Option<String>) -> Self {
+        Self {
+            logger,
+            prefix,
+            pending: String::new(),
+        }
+    }
+
+    pub fn write(&mut self, chunk: &str) {
+        self.pending.push_str(chunk);
+        if let Some(index) = self.pending.rfind('\n') {
+            let lines: String = self.pending.drain(..=index).collect();
+            let mut logger = self.logger.lock().unwrap();
+            match self.prefix {
+                Some(prefix) => logger.dump(&prefix_lines(prefix, &lines)),
+                None => logger.dump(&lines),
+            }
+        }
+    }
+
+    /// Writes the rest of the output, which is the last line when it doesn't end with a new
+    /// line, and notifies that the output had invalid bytes if any decoder was lossy.
+    pub fn finish(mut self, rest: &str, lossy: bool) {
+        self.pending.push_str(rest);
+        let mut logger = self.logger.lock().unwrap();
+        match self.prefix {
+            Some(prefix) if !self.pending.is_empty() => {
+                logger.dumpln(&prefix_lines(prefix, &self.pending))
+            }
+            None if !self.pending.is_empty() => logger.dump(&self.pending),
+            _ => {}
+        }
+        if lossy {
+            logger.invalid_output_notice();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bld_config::BldConfig;
+    use bld_core::scanner::{FileScanner, Scanner};
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_dir_all};
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    /// A xorshift generator so that the random output of a failed case can be reproduced.
+    struct Xorshift(u64);
+
+    impl Xorshift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        /// Mostly printable ascii and new lines, with arbitrary bytes that form multi-byte
+        /// characters, invalid bytes and sequences cut off by the end of the output.
+        fn output(&mut self) -> Vec<u8> {
+            let len = self.next() % 512;
+            (0..len)
+                .map(|_| match self.next() % 8 {
+                    0 => b'\n',
+                    1..=3 => (self.next() % 0x5f + 0x20) as u8,
+                    _ => self.next() as u8,
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn random_output_goes_through_the_whole_chain() {
+        let root = temp_dir().join(format!("bld-output-{}", Uuid::new_v4()));
+        create_dir_all(&root).unwrap();
+        let mut config = BldConfig::default();
+        config.local.logs = root.display().to_string();
+        config.local.max_log_line_length = 1 << 16;
+        let config = Arc::new(config);
+        let mut rng = Xorshift(0x2545_f491_4f6c_dd1d);
+
+        for case in 0..200 {
+            let bytes = rng.output();
+            let run_id = format!("case-{case}");
+            let logger = Logger::file_atom(config.clone(), &run_id).unwrap();
+            let prefix = Some("[step] ".to_string());
+            let mut sink = OutputSink::new(&logger, &prefix);
+            let mut decoder = OutputDecoder::new(OutputEncoding::Utf8);
+            let mut text = String::new();
+            let mut rest = &bytes[..];
+            while !rest.is_empty() {
+                let size = (rng.next() % 7 + 1).min(rest.len() as u64) as usize;
+                let (chunk, tail) = rest.split_at(size);
+                let chunk = decoder.decode(chunk);
+                sink.write(&chunk);
+                text.push_str(&chunk);
+                rest = tail;
+            }
+            let chunk = decoder.finish();
+            text.push_str(&chunk);
+            sink.finish(&chunk, decoder.is_lossy());
+            assert_eq!(text, String::from_utf8_lossy(&bytes), "case {case}");
+            assert_eq!(
+                decoder.is_lossy(),
+                from_utf8(&bytes).is_err(),
+                "case {case}"
+            );
+
+            drop(logger);
+            let output = prefix_lines("[step] ", &text);
+            let mut expected: Vec<&str> = output.lines().collect();
+            if decoder.is_lossy() {
+                expected.push("[bld] The output of a command had bytes that aren't valid UTF-8, which were replaced with \u{FFFD}. Set output-encoding in the pipeline if its commands use another encoding");
+            }
+            let logged = FileScanner::new(config.clone(), &run_id).flush();
+            assert_eq!(logged, expected, "case {case}");
+        }
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn secrets_split_between_chunks_are_masked() {
+        let root = temp_dir().join(format!("bld-output-mask-{}", Uuid::new_v4()));
+        create_dir_all(&root).unwrap();
+        let mut config = BldConfig::default();
+        config.local.logs = root.display().to_string();
+        let config = Arc::new(config);
+
+        for (run_id, prefix) in [("plain", None), ("prefixed", Some("[step] ".to_string()))] {
+            let logger = Logger::file_atom(config.clone(), run_id).unwrap();
+            logger.lock().unwrap().add_mask("s3cr3t");
+            let mut sink = OutputSink::new(&logger, &prefix);
+            for chunk in ["token: s3", "cr", "3t\nnext s3c", "r3t"] {
+                sink.write(chunk);
+            }
+            sink.finish("", false);
+            drop(logger);
+
+            let logged = FileScanner::new(config.clone(), run_id).flush();
+            let prefix = prefix.unwrap_or_default();
+            assert_eq!(
+                logged,
+                vec![format!("{prefix}token: ****"), format!("{prefix}next ****")]
+            );
+        }
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn output_is_decoded_across_chunks() {
+        let mut decoder = OutputDecoder::new(OutputEncoding::Utf8);
+        let bytes = "καλημέρα".as_bytes();
+        let mut text = String::new();
+        for chunk in bytes.chunks(3) {
+            text.push_str(&decoder.decode(chunk));
+        }
+        text.push_str(&decoder.finish());
+        assert_eq!(text, "καλημέρα");
+        assert!(!decoder.is_lossy());
+
+        let latin1 = b"caf\xe9 cr\xe8me";
+        let mut decoder = OutputDecoder::new(OutputEncoding::Utf8);
+        assert_eq!(decoder.decode_all(latin1), "caf\u{FFFD} cr\u{FFFD}me");
+        assert!(decoder.is_lossy());
+        let mut decoder = OutputDecoder::new(OutputEncoding::Latin1);
+        assert_eq!(decoder.decode_all(latin1), "café crème");
+        assert!(!decoder.is_lossy());
+
+        let mut decoder = OutputDecoder::new(OutputEncoding::Utf8);
+        assert_eq!(decoder.decode(b"ok \xce"), "ok ");
+        assert_eq!(decoder.finish(), "\u{FFFD}");
+    }
+}
@@ -0,0 +1,406 @@
+use crate::context::{is_shell_identifier, quote, OutputDecoder, OutputSink, StepEnv};
+use crate::Compression;
+use anyhow::{anyhow, bail, Result};
+use bld_config::definitions::LOCAL_MACHINE_TMP_DIR;
+use bld_core::execution::Execution;
+use bld_core::logger::Logger;
+use ssh2::{CheckResult, ExtendedData, KnownHostFileKind, Session};
+use std::collections::HashMap;
+use std::fs::{create_dir_all, read, write};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::unbounded_channel;
+use tokio::task::spawn_blocking;
+use tokio::time::sleep;
+use tracing::debug;
+use uuid::Uuid;
+
+const SSH_DEFAULT_PORT: u16 = 22;
+const SSH_KNOWN_HOSTS: &str = "~/.ssh/known_hosts";
+const STOP_SIGNAL_INTERVAL_MILLIS: u64 = 1000;
+
+/// Expands a leading `~` of a local path to the home directory of the user.
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => Path::new(&home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Splits the host of the pipeline to the host name and the port, which is optional.
+fn address(host: &str) -> Result<(String, u16)> {
+    match host.rsplit_once(':') {
+        Some((name, port)) => {
+            let port = port
+                .parse()
+                .map_err(|_| anyhow!("invalid port in ssh host {host}"))?;
+            Ok((name.to_string(), port))
+        }
+        None => Ok((host.to_string(), SSH_DEFAULT_PORT)),
+    }
+}
+
+/// Checks the key of the host against the known hosts of the user, since connecting to
+/// an unknown host would expose the commands and artifacts of the pipeline.
+fn verify_host_key(session: &Session, name: &str, port: u16) -> Result<()> {
+    let file = expand_home(SSH_KNOWN_HOSTS);
+    let mut known_hosts = session.known_hosts()?;
+    known_hosts
+        .read_file(&file, KnownHostFileKind::OpenSSH)
+        .map_err(|e| anyhow!("could not read {}, {e}", file.display()))?;
+    let (key, _) = session
+        .host_key()
+        .ok_or_else(|| anyhow!("no host key received from {name}"))?;
+    match known_hosts.check_port(name, port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::NotFound => {
+            bail!("the host key of {name} was not found in {}", file.display())
+        }
+        CheckResult::Mismatch => bail!(
+            "the host key of {name} doesn't match the one in {}",
+            file.display()
+        ),
+        CheckResult::Failure => bail!("could not verify the host key of {name}"),
+    }
+}
+
+fn connect(host: &str, user: &str, key_file: &str) -> Result<Session> {
+    let (name, port) = address(host)?;
+    let tcp = TcpStream::connect((name.as_str(), port))?;
+    let mut session = Session::new()?;
+    session.set_tcp_stream(tcp);
+    session.handshake()?;
+    verify_host_key(&session, &name, port)?;
+    session.userauth_pubkey_file(user, None, &expand_home(key_file), None)?;
+    if !session.authenticated() {
+        bail!("authentication to {host} as {user} failed");
+    }
+    Ok(session)
+}
+
+/// Executes a command on the remote machine with its stderr merged to stdout and passes
+/// the output to the callback, stopping early if the callback returns false.
+/// Returns the exit status of the command.
+fn exec<F>(session: &Session, command: &str, mut on_output: F) -> Result<i32>
+where
+    F: FnMut(&[u8]) -> bool,
+{
+    let mut channel = session.channel_session()?;
+    channel.handle_extended_data(ExtendedData::Merge)?;
+    channel.exec(command)?;
+    let mut buffer = [0; 4096];
+    loop {
+        let read = channel.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        if !on_output(&buffer[..read]) {
+            channel.close()?;
+            bail!("command was interrupted");
+        }
+    }
+    channel.wait_close()?;
+    Ok(channel.exit_status()?)
+}
+
+/// Executes a command on the remote machine and fails with its output if it exits with an error.
+fn exec_checked(session: &Session, command: &str) -> Result<()> {
+    let mut output = vec![];
+    let status = exec(session, command, |chunk| {
+        output.extend_from_slice(chunk);
+        true
+    })?;
+    if status > 0 {
+        bail!(
+            "command {command} finished with exit code: {status}. {}",
+            String::from_utf8_lossy(&output).trim()
+        );
+    }
+    Ok(())
+}
+
+pub struct SshMachine {
+    host: String,
+    user: String,
+    key_file: String,
+    tmp_dir: String,
+    env: Arc<HashMap<String, String>>,
+    path_prepend: Vec<String>,
+    session: Session,
+    lg: Arc<Mutex<Logger>>,
+}
+
+impl SshMachine {
+    pub async fn new(
+        id: &str,
+        host: &str,
+        user: &str,
+        key_file: &str,
+        path_prepend: Vec<String>,
+        env: Arc<HashMap<String, String>>,
+        lg: Arc<Mutex<Logger>>,
+    ) -> Result<Self> {
+        // the temp dir of the run is relative to the home directory of the user on the remote machine.
+        let tmp_dir = format!("{LOCAL_MACHINE_TMP_DIR}/{id}");
+        let session = {
+            let (host, user, key_file) = (host.to_string(), user.to_string(), key_file.to_string());
+            let tmp_dir = tmp_dir.clone();
+            spawn_blocking(move || {
+                let session = connect(&host, &user, &key_file)?;
+                exec_checked(&session, &format!("mkdir -p {}", quote(&tmp_dir)))?;
+                Ok::<Session, anyhow::Error>(session)
+            })
+            .await??
+        };
+        {
+            let mut logger = lg.lock().unwrap();
+            logger.info(&format!("Connected to {user}@{host}"));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            user: user.to_string(),
+            key_file: key_file.to_string(),
+            tmp_dir,
+            env,
+            path_prepend,
+            session,
+            lg,
+        })
+    }
+
+    /// Resolves a path of the remote machine, with relative paths being relative to the temp dir of the run.
+    fn remote_path(&self, path: &str) -> String {
+        if path.starts_with('/') {
+            path.to_string()
+        } else {
+            format!("{}/{path}", self.tmp_dir)
+        }
+    }
+
+    /// Creates the remote command of a step, which writes the pid of the remote shell to the pid
+    /// file so that the command can be stopped.
+    fn command(
+        &self,
+        working_dir: &Option<String>,
+        input: &str,
+        env: &StepEnv,
+        pid_file: &str,
+    ) -> Result<String> {
+        // the environment is exported in the command since ssh servers usually accept only
+        // a few variables from the client.
+        let mut command = format!("echo $$ > {}; ", quote(pid_file));
+        for (name, value) in self
+            .env
+            .iter()
+            .chain(env.vars.iter().map(|(name, value)| (name, value)))
+        {
+            if !is_shell_identifier(name) {
+                bail!("invalid environment variable name {name} for an ssh machine");
+            }
+            command.push_str(&format!("export {name}={}; ", quote(value)));
+        }
+        if !self.path_prepend.is_empty() {
+            let dirs = self.path_prepend.join(":");
+            command.push_str(&format!("export PATH=\"{dirs}:$PATH\"; "));
+        }
+        let current_dir = match working_dir {
+            Some(wd) => self.remote_path(wd),
+            None => self.tmp_dir.to_string(),
+        };
+        Ok(format!("{command}cd {} && {input}", quote(&current_dir)))
+    }
+
+    /// Terminates the process group of a stopped command, using the pid that its shell wrote to
+    /// the pid file. The ssh server starts the shell of each session in a new process group, so
+    /// the processes spawned by the command are terminated along with it.
+    async fn terminate(&self, pid_file: &str) {
+        let session = self.session.clone();
+        let command = format!(
+            "pid=$(cat {0}) && (kill -TERM -- -$pid 2>/dev/null || kill -TERM $pid); rm -f {0}",
+            quote(pid_file)
+        );
+        let result = spawn_blocking(move || exec_checked(&session, &command))
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|result| result);
+        if let Err(e) = result {
+            debug!("could not terminate the command on {}. {e}", self.host);
+        }
+    }
+
+    fn require_no_compression(compression: Compression) -> Result<()> {
+        if compression != Compression::None {
+            bail!("compression is not supported for artifacts of ssh machines");
+        }
+        Ok(())
+    }
+
+    /// Copies a file of the remote machine to the target path using scp.
+    pub async fn copy_from(
+        &self,
+        from: &str,
+        to: &str,
+        compression: Compression,
+        _zstd_level: i32,
+    ) -> Result<()> {
+        SshMachine::require_no_compression(compression)?;
+        let from = self.remote_path(from);
+        let session = self.session.clone();
+        let content = spawn_blocking(move || {
+            let (mut channel, stat) = session.scp_recv(Path::new(&from))?;
+            let mut content = Vec::with_capacity(stat.size() as usize);
+            channel.read_to_end(&mut content)?;
+            channel.send_eof()?;
+            channel.wait_eof()?;
+            channel.close()?;
+            channel.wait_close()?;
+            Ok::<Vec<u8>, anyhow::Error>(content)
+        })
+        .await??;
+        if let Some(parent) = Path::new(to).parent() {
+            create_dir_all(parent)?;
+        }
+        write(to, content)?;
+        Ok(())
+    }
+
+    /// Copies a local file to the target path of the remote machine using scp.
+    pub async fn copy_into(
+        &self,
+        from: &str,
+        to: &str,
+        compression: Compression,
+        _zstd_level: i32,
+    ) -> Result<()> {
+        SshMachine::require_no_compression(compression)?;
+        if !Path::new(from).is_file() {
+            bail!("only files can be copied to an ssh machine, {from} is not a file");
+        }
+        let content = read(from)?;
+        let to = self.remote_path(to);
+        let session = self.session.clone();
+        spawn_blocking(move || {
+            if let Some(parent) = Path::new(&to).parent().and_then(|p| p.to_str()) {
+                if !parent.is_empty() {
+                    exec_checked(&session, &format!("mkdir -p {}", quote(parent)))?;
+                }
+            }
+            let mut channel =
+                session.scp_send(Path::new(&to), 0o644, content.len() as u64, None)?;
+            channel.write_all(&content)?;
+            channel.send_eof()?;
+            channel.wait_eof()?;
+            channel.close()?;
+            channel.wait_close()?;
+            Ok(())
+        })
+        .await?
+    }
+
+    pub async fn sh(
+        &self,
+        working_dir: &Option<String>,
+        input: &str,
+        prefix: &Option<String>,
+        ssh_agent_forwarding: bool,
+        env: &StepEnv,
+        ex: Arc<Mutex<Execution>>,
+    ) -> Result<()> {
+        if ssh_agent_forwarding {
+            bail!("ssh agent forwarding is not supported for ssh machines");
+        }
+        let pid_file = format!("{}/.bld-pid-{}", self.tmp_dir, Uuid::new_v4());
+        let command = self.command(working_dir, input, env, &pid_file)?;
+        debug!("executing command on {}@{}", self.user, self.host);
+        let (host, user, key_file) = (
+            self.host.to_string(),
+            self.user.to_string(),
+            self.key_file.to_string(),
+        );
+        let (tx, mut rx) = unbounded_channel();
+        // the command is executed on a blocking thread with a session of its own, since the
+        // blocking reads of a session would stall the commands of steps that run in parallel.
+        // Its output is sent back in chunks, which stops reading once the receiver is dropped.
+        let handle = spawn_blocking(move || {
+            let session = connect(&host, &user, &key_file)?;
+            let status = exec(&session, &command, |chunk| tx.send(chunk.to_vec()).is_ok());
+            let _ = session.disconnect(None, "", None);
+            status
+        });
+
+        let mut sink = OutputSink::new(&self.lg, prefix);
+        let mut decoder = OutputDecoder::new(env.output_encoding);
+        loop {
+            let chunk = tokio::select! {
+                chunk = rx.recv() => Some(chunk),
+                _ = sleep(Duration::from_millis(STOP_SIGNAL_INTERVAL_MILLIS)) => None,
+            };
+            let stop_signal = {
+                let exec = ex.lock().unwrap();
+                exec.check_stop_signal()
+            };
+            if let Err(e) = stop_signal {
+                self.terminate(&pid_file).await;
+                return Err(e);
+            }
+            match chunk {
+                Some(Some(chunk)) => sink.write(&decoder.decode(&chunk)),
+                Some(None) => break,
+                None => continue,
+            }
+        }
+        sink.finish(&decoder.finish(), decoder.is_lossy());
+
+        let status = handle.await??;
+        if status > 0 {
+            bail!("command finished with exit code: {status}");
+        }
+        Ok(())
+    }
+
+    pub async fn dispose(&self, in_child_runner: bool) -> Result<()> {
+        let session = self.session.clone();
+        let tmp_dir = self.tmp_dir.clone();
+        spawn_blocking(move || {
+            // the temp dir is shared by the runners of the run so only the parent removes it.
+            if !in_child_runner {
+                exec_checked(&session, &format!("rm -rf {}", quote(&tmp_dir)))?;
+            }
+            session.disconnect(None, "", None)?;
+            Ok(())
+        })
+        .await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ssh_address_uses_the_default_port_if_none_is_provided() {
+        assert_eq!(
+            address("build-server").unwrap(),
+            ("build-server".to_string(), 22)
+        );
+        assert_eq!(
+            address("build-server:2222").unwrap(),
+            ("build-server".to_string(), 2222)
+        );
+        assert!(address("build-server:port").is_err());
+        assert_eq!(quote("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    fn ssh_exported_names_are_shell_identifiers() {
+        assert!(is_shell_identifier("PATH"));
+        assert!(is_shell_identifier("_build_1"));
+        assert!(!is_shell_identifier("1BUILD"));
+        assert!(!is_shell_identifier("A=B; rm -rf ~; X"));
+        assert!(!is_shell_identifier("BUILD-ID"));
+        assert!(!is_shell_identifier(""));
+    }
+}
@@ -0,0 +1,152 @@
+use crate::Pipeline;
+use anyhow::Result;
+use bld_core::proxies::PipelineFileSystemProxy;
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DependencyState {
+    Resolved,
+    Missing,
+    Invalid(String),
+    Cycle,
+}
+
+impl Display for DependencyState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Resolved => write!(f, "resolved"),
+            Self::Missing => write!(f, "missing"),
+            Self::Invalid(e) => write!(f, "invalid, {e}"),
+            Self::Cycle => write!(f, "cycle"),
+        }
+    }
+}
+
+/// A pipeline of the call graph along with the pipelines that it calls.
+#[derive(Debug)]
+pub struct DependencyNode {
+    pub pipeline: String,
+    pub state: DependencyState,
+    pub children: Vec<DependencyNode>,
+}
+
+impl DependencyNode {
+    fn new(pipeline: &str, state: DependencyState, children: Vec<DependencyNode>) -> Self {
+        Self {
+            pipeline: pipeline.to_string(),
+            state,
+            children,
+        }
+    }
+
+    /// Checks if the pipeline or any of its dependencies is missing, invalid or part of a cycle.
+    pub fn has_errors(&self) -> bool {
+        self.state != DependencyState::Resolved || self.children.iter().any(|c| c.has_errors())
+    }
+}
+
+impl Pipeline {
+    /// The names of the pipelines called by the steps of the pipeline and of their groups.
+    pub fn calls(&self) -> Vec<&str> {
+        self.steps
+            .iter()
+            .flat_map(|s| std::iter::once(s).chain(s.group.iter().flat_map(|g| g.steps.iter())))
+            .flat_map(|s| s.call.iter().map(|c| c.pipeline.as_str()))
+            .collect()
+    }
+}
+
+fn resolve<F>(read: &F, name: &str, ancestors: &mut Vec<String>) -> DependencyNode
+where
+    F: Fn(&str) -> Result<String>,
+{
+    if ancestors.iter().any(|a| a == name) {
+        return DependencyNode::new(name, DependencyState::Cycle, vec![]);
+    }
+    let src = match read(name) {
+        Ok(src) => src,
+        Err(_) => return DependencyNode::new(name, DependencyState::Missing, vec![]),
+    };
+    let pipeline = match Pipeline::parse(&src) {
+        Ok(pipeline) => pipeline,
+        Err(e) => {
+            return DependencyNode::new(name, DependencyState::Invalid(e.to_string()), vec![])
+        }
+    };
+    ancestors.push(name.to_string());
+    let children = pipeline
+        .calls()
+        .into_iter()
+        .map(|call| resolve(read, call, ancestors))
+        .collect();
+    ancestors.pop();
+    DependencyNode::new(name, DependencyState::Resolved, children)
+}
+
+/// Resolves the pipelines that a pipeline calls recursively. A pipeline that calls one of its
+/// callers is marked as a cycle and isn't resolved further.
+pub fn dependency_tree(prx: &PipelineFileSystemProxy, name: &str) -> DependencyNode {
+    resolve(&|name| prx.read(name), name, &mut vec![])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use std::collections::HashMap;
+
+    fn tree(pipelines: &[(&str, &str)], name: &str) -> DependencyNode {
+        let pipelines: HashMap<&str, &str> = pipelines.iter().cloned().collect();
+        let read = |name: &str| {
+            pipelines
+                .get(name)
+                .map(|src| src.to_string())
+                .ok_or_else(|| anyhow!("not found"))
+        };
+        resolve(&read, name, &mut vec![])
+    }
+
+    #[test]
+    fn dependency_tree_flags_missing_pipelines_and_cycles() {
+        let root = tree(
+            &[
+                (
+                    "main.yaml",
+                    "steps:\n- call:\n  - build.yaml\n  - missing.yaml\n",
+                ),
+                (
+                    "build.yaml",
+                    "steps:\n- group:\n  - call:\n    - main.yaml\n",
+                ),
+            ],
+            "main.yaml",
+        );
+
+        assert_eq!(root.state, DependencyState::Resolved);
+        assert!(root.has_errors());
+        let build = &root.children[0];
+        assert_eq!(build.pipeline, "build.yaml");
+        assert_eq!(build.children[0].pipeline, "main.yaml");
+        assert_eq!(build.children[0].state, DependencyState::Cycle);
+        assert_eq!(root.children[1].state, DependencyState::Missing);
+    }
+
+    #[test]
+    fn dependency_tree_resolves_shared_pipelines_without_a_cycle() {
+        let root = tree(
+            &[
+                (
+                    "main.yaml",
+                    "steps:\n- call:\n  - build.yaml\n  - test.yaml\n",
+                ),
+                ("build.yaml", "steps:\n- call:\n  - common.yaml\n"),
+                ("test.yaml", "steps:\n- call:\n  - common.yaml\n"),
+                ("common.yaml", "steps:\n- exec:\n  - echo common\n"),
+            ],
+            "main.yaml",
+        );
+
+        assert!(!root.has_errors());
+        assert_eq!(root.children[1].children[0].pipeline, "common.yaml");
+    }
+}
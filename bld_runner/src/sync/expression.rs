@@ -0,0 +1,222 @@
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+
+const EXPR_START: &str = "${{";
+const EXPR_END: &str = "}}";
+const VARS_PREFIX: &str = "vars.";
+const ENV_PREFIX: &str = "env.";
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    Value(String),
+    Reference(String),
+    Eq,
+    NotEq,
+    And,
+    Or,
+    LeftParen,
+    RightParen,
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = expr.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '(' => tokens.push(Token::LeftParen),
+            ')' => tokens.push(Token::RightParen),
+            '=' | '!' => match chars.next() {
+                Some('=') if c == '=' => tokens.push(Token::Eq),
+                Some('=') => tokens.push(Token::NotEq),
+                _ => bail!("invalid operator in expression: {expr}"),
+            },
+            '&' | '|' => match chars.next() {
+                Some(n) if n == c && c == '&' => tokens.push(Token::And),
+                Some(n) if n == c => tokens.push(Token::Or),
+                _ => bail!("invalid operator in expression: {expr}"),
+            },
+            '\'' | '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some(n) if n == c => break,
+                        Some(n) => value.push(n),
+                        None => bail!("unterminated string in expression: {expr}"),
+                    }
+                }
+                tokens.push(Token::Value(value));
+            }
+            _ => {
+                let mut word = String::from(c);
+                while let Some(n) = chars.peek() {
+                    if n.is_whitespace() || "()=!&|'\"".contains(*n) {
+                        break;
+                    }
+                    word.push(*n);
+                    chars.next();
+                }
+                let token = match &word[..] {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    w if w.starts_with(VARS_PREFIX) || w.starts_with(ENV_PREFIX) => {
+                        Token::Reference(word)
+                    }
+                    _ => Token::Value(word),
+                };
+                tokens.push(token);
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    vars: &'a HashMap<String, String>,
+    env: &'a HashMap<String, String>,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<bool> {
+        let mut result = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            let rhs = self.and()?;
+            result = result || rhs;
+        }
+        Ok(result)
+    }
+
+    fn and(&mut self) -> Result<bool> {
+        let mut result = self.comparison()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            let rhs = self.comparison()?;
+            result = result && rhs;
+        }
+        Ok(result)
+    }
+
+    fn comparison(&mut self) -> Result<bool> {
+        if self.peek() == Some(&Token::LeftParen) {
+            self.next();
+            let result = self.or()?;
+            return match self.next() {
+                Some(Token::RightParen) => Ok(result),
+                _ => bail!("expected closing parenthesis in expression"),
+            };
+        }
+        let lhs = self.operand()?;
+        match self.peek() {
+            Some(Token::Eq) => {
+                self.next();
+                Ok(lhs == self.operand()?)
+            }
+            Some(Token::NotEq) => {
+                self.next();
+                Ok(lhs != self.operand()?)
+            }
+            _ => Ok(lhs == "true"),
+        }
+    }
+
+    fn operand(&mut self) -> Result<String> {
+        let vars = self.vars;
+        let env = self.env;
+        match self.next() {
+            Some(Token::Value(value)) => Ok(value.to_string()),
+            Some(Token::Reference(reference)) => {
+                let value = match reference.strip_prefix(VARS_PREFIX) {
+                    Some(name) => vars.get(name),
+                    None => env.get(&reference[ENV_PREFIX.len()..]),
+                };
+                Ok(value.cloned().unwrap_or_default())
+            }
+            Some(token) => bail!("unexpected token {token:?} in expression"),
+            None => bail!("unexpected end of expression"),
+        }
+    }
+}
+
+/// Evaluates a condition of the form `${{ vars.NAME == 'value' and env.NAME != 'value' }}`
+/// against the provided variables and environment. The wrapping `${{ }}` is optional and
+/// references to missing values are evaluated as empty strings.
+pub fn evaluate(
+    expr: &str,
+    vars: &HashMap<String, String>,
+    env: &HashMap<String, String>,
+) -> Result<bool> {
+    let trimmed = expr.trim();
+    let inner = trimmed
+        .strip_prefix(EXPR_START)
+        .and_then(|e| e.strip_suffix(EXPR_END))
+        .unwrap_or(trimmed);
+    let mut parser = Parser {
+        tokens: tokenize(inner)?,
+        position: 0,
+        vars,
+        env,
+    };
+    if parser.tokens.is_empty() {
+        bail!("empty expression");
+    }
+    let result = parser.or()?;
+    match parser.peek() {
+        Some(token) => Err(anyhow!("unexpected token {token:?} in expression: {expr}")),
+        None => Ok(result),
+    }
+}
+
+/// Checks the syntax of a condition without any values, so that an invalid condition fails
+/// the pipeline when it's parsed instead of when its step is reached.
+pub fn validate(expr: &str) -> Result<()> {
+    evaluate(expr, &HashMap::new(), &HashMap::new()).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn maps() -> (HashMap<String, String>, HashMap<String, String>) {
+        let vars = HashMap::from([("RUN_TESTS".to_string(), "true".to_string())]);
+        let env = HashMap::from([("BRANCH".to_string(), "master".to_string())]);
+        (vars, env)
+    }
+
+    #[test]
+    fn expression_evaluates_equality_and_inequality() {
+        let (vars, env) = maps();
+        assert!(evaluate("${{ vars.RUN_TESTS == 'true' }}", &vars, &env).unwrap());
+        assert!(!evaluate("${{ vars.RUN_TESTS != 'true' }}", &vars, &env).unwrap());
+        assert!(evaluate("${{ env.MISSING == '' }}", &vars, &env).unwrap());
+    }
+
+    #[test]
+    fn expression_evaluates_boolean_operators() {
+        let (vars, env) = maps();
+        let expr = "${{ vars.RUN_TESTS == 'false' or env.BRANCH == \"master\" }}";
+        assert!(evaluate(expr, &vars, &env).unwrap());
+        let expr = "${{ vars.RUN_TESTS == 'true' && (env.BRANCH == 'dev' || false) }}";
+        assert!(!evaluate(expr, &vars, &env).unwrap());
+    }
+
+    #[test]
+    fn expression_fails_when_invalid() {
+        let (vars, env) = maps();
+        assert!(evaluate("${{ vars.RUN_TESTS = 'true' }}", &vars, &env).is_err());
+        assert!(evaluate("${{ vars.RUN_TESTS == 'true }}", &vars, &env).is_err());
+        assert!(evaluate("${{ }}", &vars, &env).is_err());
+    }
+}
@@ -0,0 +1,566 @@
+use crate::sync::schema::{markdown_type, pipeline_schema, SchemaField, SchemaType};
+use crate::sync::substitution::protect_escaped;
+use crate::Pipeline;
+use anyhow::Result;
+use bld_config::definitions::VAR_TOKEN;
+use bld_core::proxies::PipelineFileSystemProxy;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display, Formatter, Write};
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::Marker;
+use yaml_rust::{Yaml, YamlLoader};
+
+/// A problem of a pipeline along with the path of the entry that it concerns, such as
+/// `steps[0].exec`, and the line of that entry if it could be located.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintProblem {
+    pub path: String,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Display for LintProblem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match (self.line, self.path.is_empty()) {
+            (Some(line), false) => write!(f, "line {line}, {}: {}", self.path, self.message),
+            (Some(line), true) => write!(f, "line {line}: {}", self.message),
+            (None, false) => write!(f, "{}: {}", self.path, self.message),
+            (None, true) => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// The lines of the nodes of a yaml document by their path, with the line of a key used for
+/// its value.
+#[derive(Default)]
+struct Locations {
+    lines: HashMap<String, usize>,
+    stack: Vec<Frame>,
+}
+
+enum Frame {
+    Mapping { path: String, key: Option<String> },
+    Sequence { path: String, index: usize },
+}
+
+fn child_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+impl Locations {
+    fn load(src: &str) -> Self {
+        let mut locations = Self::default();
+        // syntax errors are reported by the yaml loader, so they are ignored here.
+        let _ = Parser::new(src.chars()).load(&mut locations, false);
+        locations
+    }
+
+    /// The path of the node that starts with the current event, or None if the event is a key.
+    fn next_path(&mut self, key: Option<&str>) -> Option<String> {
+        match self.stack.last_mut() {
+            None => Some(String::new()),
+            Some(Frame::Sequence { path, index }) => {
+                *index += 1;
+                Some(format!("{path}[{}]", *index - 1))
+            }
+            Some(Frame::Mapping { path, key: current }) => match current.take() {
+                Some(current) => Some(child_path(path, &current)),
+                None => {
+                    *current = Some(key.unwrap_or_default().to_string());
+                    None
+                }
+            },
+        }
+    }
+
+    /// The line of an entry, or of its closest parent that was located.
+    fn line(&self, path: &str) -> Option<usize> {
+        let mut path = path;
+        loop {
+            if let Some(line) = self.lines.get(path) {
+                return Some(*line);
+            }
+            path = &path[..path.rfind(['.', '['])?];
+        }
+    }
+}
+
+impl MarkedEventReceiver for Locations {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        let line = mark.line();
+        match ev {
+            Event::Scalar(value, ..) => {
+                let is_key = matches!(self.stack.last(), Some(Frame::Mapping { key: None, .. }));
+                match self.next_path(Some(&value)) {
+                    Some(path) => {
+                        self.lines.entry(path).or_insert(line);
+                    }
+                    None if is_key => {
+                        if let Some(Frame::Mapping { path, .. }) = self.stack.last() {
+                            self.lines.insert(child_path(path, &value), line);
+                        }
+                    }
+                    None => {}
+                }
+            }
+            Event::Alias(_) => {
+                if let Some(path) = self.next_path(None) {
+                    self.lines.entry(path).or_insert(line);
+                }
+            }
+            Event::SequenceStart(_) | Event::MappingStart(_) => {
+                let path = self.next_path(None).unwrap_or_default();
+                self.lines.entry(path.clone()).or_insert(line);
+                self.stack.push(match ev {
+                    Event::SequenceStart(_) => Frame::Sequence { path, index: 0 },
+                    _ => Frame::Mapping { path, key: None },
+                });
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                self.stack.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+struct Linter {
+    locations: Locations,
+    problems: Vec<LintProblem>,
+}
+
+impl Linter {
+    fn report(&mut self, path: &str, message: String) {
+        let problem = LintProblem {
+            path: path.to_string(),
+            line: self.locations.line(path),
+            message,
+        };
+        if !self.problems.contains(&problem) {
+            self.problems.push(problem);
+        }
+    }
+}
+
+/// Collects the problems of a value for a type of the schema without reporting them.
+fn kind_problems(kind: &SchemaType, yaml: &Yaml, path: &str) -> Vec<(String, String)> {
+    let mut problems = vec![];
+    let valid = match (kind, yaml) {
+        (SchemaType::String, Yaml::String(_)) => true,
+        (SchemaType::Integer, Yaml::Integer(_)) => true,
+        (SchemaType::Boolean, Yaml::Boolean(_)) => true,
+        (
+            SchemaType::Scalar,
+            Yaml::String(_) | Yaml::Integer(_) | Yaml::Real(_) | Yaml::Boolean(_),
+        ) => true,
+        (SchemaType::Enum(values), Yaml::String(value)) => values.contains(&value.as_str()),
+        (SchemaType::List(kind), Yaml::Array(entries)) => {
+            for (i, entry) in entries.iter().enumerate() {
+                problems.extend(kind_problems(kind, entry, &format!("{path}[{i}]")));
+            }
+            true
+        }
+        (SchemaType::Map(kind), Yaml::Hash(hash)) => {
+            for (key, value) in hash.iter() {
+                let key = key.as_str().unwrap_or_default();
+                problems.extend(kind_problems(kind, value, &child_path(path, key)));
+            }
+            true
+        }
+        (SchemaType::Object(fields), Yaml::Hash(_)) => {
+            problems.extend(object_problems(fields, yaml, path));
+            true
+        }
+        (SchemaType::OneOf(kinds), _) => {
+            // the problems of the alternative that comes closest to the value are reported,
+            // which is the one with the fewest problems among the ones of the same shape.
+            let closest = kinds
+                .iter()
+                .filter(|kind| same_shape(kind, yaml))
+                .map(|kind| kind_problems(kind, yaml, path))
+                .min_by_key(|problems| problems.len());
+            match closest {
+                Some(closest) => {
+                    problems.extend(closest);
+                    true
+                }
+                None => false,
+            }
+        }
+        _ => false,
+    };
+    if !valid {
+        problems.push((
+            path.to_string(),
+            format!("should be of type {}", markdown_type(kind)),
+        ));
+    }
+    problems
+}
+
+fn same_shape(kind: &SchemaType, yaml: &Yaml) -> bool {
+    match kind {
+        SchemaType::List(_) => matches!(yaml, Yaml::Array(_)),
+        SchemaType::Map(_) | SchemaType::Object(_) => matches!(yaml, Yaml::Hash(_)),
+        SchemaType::OneOf(kinds) => kinds.iter().any(|k| same_shape(k, yaml)),
+        _ => !matches!(yaml, Yaml::Array(_) | Yaml::Hash(_)),
+    }
+}
+
+fn object_problems(fields: &[SchemaField], yaml: &Yaml, path: &str) -> Vec<(String, String)> {
+    let mut problems = vec![];
+    let hash = match yaml.as_hash() {
+        Some(hash) => hash,
+        None => return problems,
+    };
+    for (key, value) in hash.iter() {
+        let key = match key.as_str() {
+            Some(key) => key,
+            None => {
+                problems.push((path.to_string(), "entries should be named".to_string()));
+                continue;
+            }
+        };
+        let key_path = child_path(path, key);
+        match fields.iter().find(|f| f.name == key) {
+            Some(field) => problems.extend(kind_problems(&field.kind, value, &key_path)),
+            None => problems.push((key_path, unknown_entry(fields, key))),
+        }
+    }
+    for field in fields.iter().filter(|f| f.required) {
+        if yaml[field.name].is_badvalue() {
+            problems.push((
+                path.to_string(),
+                format!("missing required entry {}", field.name),
+            ));
+        }
+    }
+    problems
+}
+
+fn unknown_entry(fields: &[SchemaField], key: &str) -> String {
+    let suggestion = fields
+        .iter()
+        .map(|f| (distance(f.name, key), f.name))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance);
+    match suggestion {
+        Some((_, name)) => format!("unknown entry {key}, did you mean {name}?"),
+        None => format!("unknown entry {key}"),
+    }
+}
+
+/// The edit distance of two names, used to suggest the entry that a misspelled one refers to.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            current.push(
+                (previous[j] + cost)
+                    .min(previous[j + 1] + 1)
+                    .min(current[j] + 1),
+            );
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+fn entries<'a>(yaml: &'a Yaml, path: &str) -> Vec<(String, &'a Yaml)> {
+    yaml.as_vec()
+        .map(|entries| {
+            entries
+                .iter()
+                .enumerate()
+                .map(|(i, e)| (format!("{path}[{i}]"), e))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The steps of the pipeline along with the steps of their groups.
+fn steps(yaml: &Yaml) -> Vec<(String, &Yaml)> {
+    let mut steps = vec![];
+    for (path, step) in entries(&yaml["steps"], "steps") {
+        let group = entries(&step["group"], &format!("{path}.group"));
+        steps.push((path, step));
+        steps.extend(group);
+    }
+    steps
+}
+
+fn check_runs_on(linter: &mut Linter, yaml: &Yaml) {
+    if let Some(target) = yaml["runs-on"].as_str() {
+        if target.is_empty() || target.contains(char::is_whitespace) {
+            linter.report(
+                "runs-on",
+                "should be machine or the image of a docker container".to_string(),
+            );
+        }
+    }
+}
+
+fn check_artifacts(linter: &mut Linter, yaml: &Yaml) {
+    for (path, artifact) in entries(&yaml["artifacts"], "artifacts") {
+        if artifact.as_hash().is_none() {
+            continue;
+        }
+        // an artifact without any of these entries is skipped by the runner.
+        for entry in ["method", "from", "to"] {
+            if artifact[entry].is_badvalue() {
+                linter.report(
+                    &path,
+                    format!("missing {entry}, the artifact would never be transferred"),
+                );
+            }
+        }
+    }
+}
+
+/// The variables that can be referenced by the pipeline, which are its declared variables,
+/// the axes of its matrix and the output variables of its steps.
+fn declared_variables(yaml: &Yaml) -> HashSet<String> {
+    let mut declared = HashSet::new();
+    for (_, variable) in entries(&yaml["variables"], "variables") {
+        if let Some(hash) = variable.as_hash() {
+            declared.extend(
+                hash.keys()
+                    .filter_map(|k| k.as_str())
+                    .map(|k| k.to_string()),
+            );
+        }
+    }
+    if let Some(matrix) = yaml["matrix"].as_hash() {
+        declared.extend(
+            matrix
+                .keys()
+                .filter_map(|k| k.as_str())
+                .map(|k| k.to_string()),
+        );
+    }
+    for (_, step) in steps(yaml) {
+        if let Some(output_var) = step["output-var"].as_str() {
+            declared.insert(output_var.to_string());
+        }
+    }
+    declared
+}
+
+fn variable_references(txt: &str) -> Vec<String> {
+    let txt = protect_escaped(txt);
+    txt.match_indices(VAR_TOKEN)
+        .map(|(index, _)| {
+            txt[index + VAR_TOKEN.len()..]
+                .chars()
+                .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '-')
+                .collect::<String>()
+        })
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+fn check_variables(linter: &mut Linter, yaml: &Yaml) {
+    let declared = declared_variables(yaml);
+    let mut values = vec![];
+    collect_strings(yaml, "", &mut values);
+    for (path, value) in values {
+        for name in variable_references(value) {
+            if !declared.contains(&name) {
+                linter.report(
+                    &path,
+                    format!("{VAR_TOKEN}{name} references an undeclared variable"),
+                );
+            }
+        }
+    }
+}
+
+fn collect_strings<'a>(yaml: &'a Yaml, path: &str, values: &mut Vec<(String, &'a str)>) {
+    match yaml {
+        Yaml::String(value) => values.push((path.to_string(), value)),
+        Yaml::Array(entries) => {
+            for (i, entry) in entries.iter().enumerate() {
+                collect_strings(entry, &format!("{path}[{i}]"), values);
+            }
+        }
+        Yaml::Hash(hash) => {
+            for (key, value) in hash.iter() {
+                let key = key.as_str().unwrap_or_default();
+                collect_strings(value, &child_path(path, key), values);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn check_calls<F>(linter: &mut Linter, yaml: &Yaml, read: &F)
+where
+    F: Fn(&str) -> Result<String>,
+{
+    for (path, step) in steps(yaml) {
+        for (path, call) in entries(&step["call"], &format!("{path}.call")) {
+            let target = call.as_str().or_else(|| call["pipeline"].as_str());
+            let target = match target {
+                Some(target) if !target.is_empty() => target,
+                _ => continue,
+            };
+            match read(target).map(|src| Pipeline::parse(&src)) {
+                Err(_) => linter.report(&path, format!("called pipeline {target} was not found")),
+                Ok(Err(e)) => {
+                    linter.report(&path, format!("called pipeline {target} is invalid, {e}"))
+                }
+                Ok(Ok(_)) => {}
+            }
+        }
+    }
+}
+
+fn lint_with<F>(src: &str, read: Option<&F>) -> Vec<LintProblem>
+where
+    F: Fn(&str) -> Result<String>,
+{
+    let yaml = match YamlLoader::load_from_str(src) {
+        Ok(yaml) => yaml.into_iter().next().unwrap_or(Yaml::BadValue),
+        Err(e) => {
+            return vec![LintProblem {
+                path: String::new(),
+                line: Some(e.marker().line()),
+                message: format!("invalid yaml, {e}"),
+            }]
+        }
+    };
+    let mut linter = Linter {
+        locations: Locations::load(src),
+        problems: vec![],
+    };
+    if yaml.as_hash().is_none() {
+        linter.report(
+            "",
+            "a pipeline should be a mapping of its entries".to_string(),
+        );
+        return linter.problems;
+    }
+    for (path, message) in object_problems(&pipeline_schema(), &yaml, "") {
+        linter.report(&path, message);
+    }
+    check_runs_on(&mut linter, &yaml);
+    check_artifacts(&mut linter, &yaml);
+    check_variables(&mut linter, &yaml);
+    if let Some(read) = read {
+        check_calls(&mut linter, &yaml, read);
+    }
+    // the parser checks what the schema can't describe, such as the dependencies of the steps.
+    if let Err(e) = Pipeline::load(&yaml) {
+        linter.report("", e.to_string());
+    }
+    linter
+        .problems
+        .sort_by_key(|p| p.line.unwrap_or(usize::MAX));
+    linter.problems
+}
+
+/// Checks a pipeline strictly, reporting every problem instead of stopping at the first one.
+/// Unknown entries and values of the wrong type, which the parser ignores, are problems too.
+/// The pipelines that it calls aren't resolved.
+pub fn lint_pipeline(src: &str) -> Vec<LintProblem> {
+    lint_with::<fn(&str) -> Result<String>>(src, None)
+}
+
+/// Checks a pipeline of the proxy strictly along with resolving the pipelines that it calls.
+pub fn check_pipeline(prx: &PipelineFileSystemProxy, name: &str) -> Result<Vec<LintProblem>> {
+    let src = prx.read(name)?;
+    Ok(lint_with(&src, Some(&|name: &str| prx.read(name))))
+}
+
+/// Lists the problems of a pipeline, each one followed by the line of the source that it concerns.
+pub fn lint_report(src: &str, problems: &[LintProblem]) -> String {
+    let lines: Vec<&str> = src.lines().collect();
+    let mut report = String::new();
+    for problem in problems.iter() {
+        let _ = writeln!(report, "{problem}");
+        if let Some(txt) = problem.line.and_then(|l| lines.get(l.wrapping_sub(1))) {
+            let _ = writeln!(report, "  {:>4} | {txt}", problem.line.unwrap_or_default());
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    fn messages(problems: &[LintProblem]) -> Vec<String> {
+        problems.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn lint_reports_every_problem_with_its_line() {
+        let src = r"runs-on: machine
+variables:
+- environment: staging
+steps:
+- name: build
+  comands:
+  - echo bld:var:environment
+- name: deploy
+  exec:
+  - echo bld:var:enviroment \bld:var:literal
+  retry:
+    delay-seconds: true
+artifacts:
+- method: push
+  from: out
+";
+        let problems = lint_pipeline(src);
+        let found = messages(&problems);
+
+        assert!(found.contains(&"line 6, steps[0].comands: unknown entry comands".to_string()));
+        assert!(found.contains(
+            &"line 10, steps[1].exec[0]: bld:var:enviroment references an undeclared variable"
+                .to_string()
+        ));
+        assert!(found.contains(
+            &"line 12, steps[1].retry.delay-seconds: should be of type integer".to_string()
+        ));
+        assert!(
+            found.contains(&"line 11, steps[1].retry: missing required entry attempts".to_string())
+        );
+        assert!(found.contains(
+            &"line 14, artifacts[0]: missing to, the artifact would never be transferred"
+                .to_string()
+        ));
+        assert!(!found.iter().any(|m| m.contains("literal")));
+        assert!(lint_report(src, &problems).contains("     6 |   comands:"));
+
+        let problems = lint_pipeline("runs-on:\n  image: ubuntu\n  netwrk: host\n");
+        assert_eq!(
+            messages(&problems),
+            vec!["line 3, runs-on.netwrk: unknown entry netwrk, did you mean network?"]
+        );
+        assert_eq!(lint_pipeline("steps: [\n").len(), 1);
+    }
+
+    #[test]
+    fn lint_resolves_the_called_pipelines() {
+        let src = "steps:\n- call:\n  - build.yaml\n  - pipeline: missing.yaml\n";
+        let read = |name: &str| match name {
+            "build.yaml" => Ok("steps:\n- exec:\n  - echo build\n".to_string()),
+            _ => Err(anyhow!("not found")),
+        };
+        let problems = lint_with(src, Some(&read));
+
+        assert_eq!(
+            messages(&problems),
+            vec!["line 4, steps[0].call[1]: called pipeline missing.yaml was not found"]
+        );
+        assert!(lint_pipeline(src).is_empty());
+    }
+}
@@ -1,7 +1,21 @@
+mod dependencies;
+mod expression;
+mod lint;
+mod notifications;
 mod pipeline;
 mod platform;
 mod runner;
+mod schema;
+mod substitution;
+mod telemetry;
+mod template;
+mod transfers;
 
+pub use dependencies::*;
+pub use lint::*;
 pub use pipeline::*;
 pub use platform::*;
 pub use runner::*;
+pub use schema::*;
+pub use telemetry::*;
+pub use transfers::*;
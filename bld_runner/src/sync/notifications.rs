@@ -0,0 +1,120 @@
+use crate::{NotificationTarget, NotifyOn};
+use anyhow::{anyhow, bail, Result};
+use bld_config::{BldConfig, BldSmtpConfig};
+use lettre::smtp::authentication::Credentials;
+use lettre::{ClientSecurity, ClientTlsParameters, SmtpClient, Transport};
+use lettre_email::EmailBuilder;
+use native_tls::TlsConnector;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::spawn_blocking;
+use tokio::time::timeout;
+
+const SMTPS_PORT: u16 = 465;
+/// The time to wait for a connection to a notification target.
+const NOTIFICATION_CONNECT_TIMEOUT_SECS: u64 = 10;
+/// The time to wait for a notification to be delivered, so that a target that hangs doesn't
+/// keep the worker of the run alive.
+const NOTIFICATION_TIMEOUT_SECS: u64 = 30;
+
+/// The outcome of a run that is sent to the targets of the pipeline notifications.
+pub struct RunReport {
+    pub pipeline: String,
+    pub run_id: String,
+    pub outcome: NotifyOn,
+}
+
+impl RunReport {
+    fn state(&self) -> &'static str {
+        match self.outcome {
+            NotifyOn::Success => "finished",
+            NotifyOn::Failure => "faulted",
+        }
+    }
+
+    fn message(&self) -> String {
+        format!(
+            "Pipeline {} with run id {} has {}",
+            self.pipeline,
+            self.run_id,
+            self.state()
+        )
+    }
+}
+
+pub async fn notify(
+    cfg: Arc<BldConfig>,
+    target: &NotificationTarget,
+    report: &RunReport,
+) -> Result<()> {
+    match target {
+        NotificationTarget::Slack { webhook_url } => {
+            post(webhook_url, json!({ "text": report.message() })).await
+        }
+        NotificationTarget::Webhook { url } => {
+            let body = json!({
+                "pipeline": report.pipeline,
+                "run_id": report.run_id,
+                "state": report.state(),
+            });
+            post(url, body).await
+        }
+        NotificationTarget::Email { to } => {
+            let to = to.clone();
+            let subject = format!("[bld] {} {}", report.pipeline, report.state());
+            let text = report.message();
+            // the smtp client has no timeout for connecting, so the whole delivery is limited.
+            let delivery = spawn_blocking(move || {
+                let smtp = cfg
+                    .local
+                    .smtp
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("no smtp server in the config"))?;
+                send_email(smtp, &to, &subject, &text)
+            });
+            timeout(Duration::from_secs(NOTIFICATION_TIMEOUT_SECS), delivery)
+                .await
+                .map_err(|_| {
+                    anyhow!("email wasn't sent within {NOTIFICATION_TIMEOUT_SECS} seconds")
+                })??
+        }
+    }
+}
+
+async fn post(url: &str, body: serde_json::Value) -> Result<()> {
+    let client = reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(NOTIFICATION_CONNECT_TIMEOUT_SECS))
+        .timeout(Duration::from_secs(NOTIFICATION_TIMEOUT_SECS))
+        .build()?;
+    let response = client.post(url).json(&body).send().await?;
+    if !response.status().is_success() {
+        bail!("{url} responded with {}", response.status());
+    }
+    Ok(())
+}
+
+fn send_email(smtp: &BldSmtpConfig, to: &[String], subject: &str, text: &str) -> Result<()> {
+    let mut builder = EmailBuilder::new()
+        .from(smtp.from.as_str())
+        .subject(subject)
+        .text(text);
+    for recipient in to.iter() {
+        builder = builder.to(recipient.as_str());
+    }
+    let email = builder.build()?;
+
+    let tls = ClientTlsParameters::new(smtp.host.to_string(), TlsConnector::new()?);
+    let security = if smtp.port == SMTPS_PORT {
+        ClientSecurity::Wrapper(tls)
+    } else {
+        ClientSecurity::Opportunistic(tls)
+    };
+    let mut client = SmtpClient::new((smtp.host.as_str(), smtp.port), security)?
+        .timeout(Some(Duration::from_secs(NOTIFICATION_CONNECT_TIMEOUT_SECS)));
+    if let (Some(username), Some(password)) = (&smtp.username, &smtp.password) {
+        client = client.credentials(Credentials::new(username.to_string(), password.to_string()));
+    }
+    client.transport().send(email.into())?;
+    Ok(())
+}
@@ -1,12 +1,74 @@
-use anyhow::{anyhow, Result};
+use crate::sync::expression::validate;
+use crate::{DockerHost, RegistryCredentials};
+use anyhow::{anyhow, bail, Result};
+use bld_config::ContainerResources;
 use bld_utils::errors::err_variable_in_yaml;
+use std::collections::HashMap;
+use std::error::Error;
 use std::fmt::{self, Display, Formatter};
-use yaml_rust::{Yaml, YamlLoader};
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+
+/// The latest version of the pipeline format that is supported.
+pub const PIPELINE_VERSION: u64 = 1;
+
+/// The namespace that the pod of a pipeline is created in when none is provided.
+pub const KUBERNETES_DEFAULT_NAMESPACE: &str = "default";
+
+/// The parts of the names of variables that are considered secrets.
+const SECRET_NAME_PATTERNS: [&str; 4] = ["SECRET", "TOKEN", "PASSWORD", "KEY"];
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DockerOptions {
+    pub oom_score_adj: Option<i64>,
+    pub oom_kill_disable: bool,
+    pub volumes: Vec<String>,
+    pub network: Option<String>,
+    pub registry: Option<RegistryCredentials>,
+    pub registry_name: Option<String>,
+    pub ssh_agent_socket: Option<String>,
+    pub host: Option<DockerHost>,
+    pub path_prepend: Vec<String>,
+    pub resources: ContainerResources,
+}
+
+impl DockerOptions {
+    pub fn new(
+        oom_score_adj: Option<i64>,
+        oom_kill_disable: bool,
+        volumes: Vec<String>,
+        network: Option<String>,
+    ) -> Self {
+        Self {
+            oom_score_adj,
+            oom_kill_disable,
+            volumes,
+            network,
+            registry: None,
+            registry_name: None,
+            ssh_agent_socket: None,
+            host: None,
+            path_prepend: vec![],
+            resources: ContainerResources::default(),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub enum RunsOn {
     Machine,
-    Docker(String),
+    Docker(String, DockerOptions),
+    DockerRemote(String, DockerOptions),
+    Kubernetes {
+        image: String,
+        namespace: String,
+        service_account: Option<String>,
+    },
+    Ssh {
+        host: String,
+        user: String,
+        key_file: String,
+    },
 }
 
 impl Default for RunsOn {
@@ -19,15 +81,79 @@ impl Display for RunsOn {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             Self::Machine => write!(f, "machine"),
-            Self::Docker(image) => write!(f, "docker [ {} ]", image),
+            Self::Docker(image, _) => write!(f, "docker [ {} ]", image),
+            Self::DockerRemote(image, options) => match &options.host {
+                Some(host) => write!(f, "docker [ {} ] on {}", image, host.url),
+                None => write!(f, "docker [ {} ]", image),
+            },
+            Self::Kubernetes {
+                image, namespace, ..
+            } => write!(f, "kubernetes [ {} ] in namespace {}", image, namespace),
+            Self::Ssh { host, user, .. } => write!(f, "ssh [ {}@{} ]", user, host),
         }
     }
 }
 
+/// The type that the values of a variable should conform to.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum VariableType {
+    #[default]
+    String,
+    Int,
+    Bool,
+}
+
+impl Display for VariableType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::String => write!(f, "string"),
+            Self::Int => write!(f, "int"),
+            Self::Bool => write!(f, "bool"),
+        }
+    }
+}
+
+impl VariableType {
+    /// Returns the value in its canonical form, which lowercases booleans, or none if
+    /// the value doesn't conform to the type.
+    pub fn conform(&self, value: &str) -> Option<String> {
+        match self {
+            Self::String => Some(value.to_string()),
+            Self::Int => value.parse::<i64>().ok().map(|_| value.to_string()),
+            Self::Bool if value.eq_ignore_ascii_case("true") => Some("true".to_string()),
+            Self::Bool if value.eq_ignore_ascii_case("false") => Some("false".to_string()),
+            Self::Bool => None,
+        }
+    }
+}
+
+/// The error of a variable or environment variable with a value that doesn't conform to
+/// its declared type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidVariableValue {
+    pub label: String,
+    pub name: String,
+    pub kind: VariableType,
+    pub value: String,
+}
+
+impl Display for InvalidVariableValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid value '{}' for {} {}, expected {}",
+            self.value, self.label, self.name, self.kind
+        )
+    }
+}
+
+impl Error for InvalidVariableValue {}
+
 #[derive(Debug)]
 pub struct Variable {
     pub name: String,
     pub default_value: String,
+    pub kind: VariableType,
 }
 
 impl Variable {
@@ -35,30 +161,230 @@ impl Variable {
         Variable {
             name,
             default_value,
+            kind: VariableType::default(),
+        }
+    }
+
+    /// Checks if the name of a variable looks like the name of a secret, such as a token
+    /// or a password, in which case its value is masked in the output of the run.
+    pub fn is_secret_name(name: &str) -> bool {
+        let name = name.to_uppercase();
+        SECRET_NAME_PATTERNS.iter().any(|p| name.contains(p))
+    }
+
+    /// Describes the entries of a section of the pipeline in messages.
+    pub fn label(section: &str) -> &'static str {
+        match section {
+            "environment" => "environment variable",
+            _ => "variable",
         }
     }
+
+    /// Checks that a value of the variable conforms to its type and returns it in its
+    /// canonical form. The label describes the variable in the error.
+    pub fn conform(&self, label: &str, value: &str) -> Result<String, InvalidVariableValue> {
+        self.kind
+            .conform(value)
+            .ok_or_else(|| InvalidVariableValue {
+                label: label.to_string(),
+                name: self.name.to_string(),
+                kind: self.kind,
+                value: value.to_string(),
+            })
+    }
+}
+
+/// An axis of the pipeline matrix along with the values that it takes.
+#[derive(Debug)]
+pub struct MatrixAxis {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+impl MatrixAxis {
+    pub fn new(name: String, values: Vec<String>) -> Self {
+        Self { name, values }
+    }
+}
+
+/// Defines how the result of a called pipeline affects the state of its parent.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Rollup {
+    Ignore,
+    Warn,
+    #[default]
+    Fail,
+}
+
+impl Display for Rollup {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ignore => write!(f, "ignore"),
+            Self::Warn => write!(f, "warn"),
+            Self::Fail => write!(f, "fail"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Call {
+    pub pipeline: String,
+    pub rollup: Rollup,
+}
+
+impl Call {
+    pub fn new(pipeline: String, rollup: Rollup) -> Self {
+        Self { pipeline, rollup }
+    }
+}
+
+/// The resource limits of a pipeline that runs on a machine, which are applied with a cgroup.
+#[derive(Debug, Clone)]
+pub struct CgroupLimits {
+    pub memory_mb: Option<u64>,
+    pub cpu_quota_percent: Option<u64>,
+}
+
+impl CgroupLimits {
+    pub fn new(memory_mb: Option<u64>, cpu_quota_percent: Option<u64>) -> Self {
+        Self {
+            memory_mb,
+            cpu_quota_percent,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Retry {
+    pub attempts: u64,
+    pub delay_seconds: u64,
+}
+
+impl Retry {
+    pub fn new(attempts: u64, delay_seconds: u64) -> Self {
+        Self {
+            attempts,
+            delay_seconds,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct StepGroup {
+    pub parallel: bool,
+    pub steps: Vec<BuildStep>,
+}
+
+impl StepGroup {
+    pub fn new(parallel: bool, steps: Vec<BuildStep>) -> Self {
+        Self { parallel, steps }
+    }
 }
 
 #[derive(Debug)]
 pub struct BuildStep {
     pub name: Option<String>,
     pub working_dir: Option<String>,
-    pub call: Vec<String>,
+    pub call: Vec<Call>,
     pub commands: Vec<String>,
+    pub retry: Option<Retry>,
+    pub when: Option<String>,
+    pub group: Option<StepGroup>,
+    pub depends_on: Vec<String>,
+    pub quiet: bool,
+    pub ssh_agent_forwarding: bool,
+    pub checkpoint: Option<String>,
+    pub environment: Vec<Variable>,
+    pub inherit_container_env: bool,
+    pub output_var: Option<String>,
+    pub ignore_errors: bool,
+    pub always_run: bool,
 }
 
 impl BuildStep {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         name: Option<String>,
         working_dir: Option<String>,
-        call: Vec<String>,
+        call: Vec<Call>,
         commands: Vec<String>,
+        retry: Option<Retry>,
+        when: Option<String>,
+        group: Option<StepGroup>,
+        depends_on: Vec<String>,
+        quiet: bool,
+        ssh_agent_forwarding: bool,
+        checkpoint: Option<String>,
+        environment: Vec<Variable>,
+        inherit_container_env: bool,
+        output_var: Option<String>,
+        ignore_errors: bool,
+        always_run: bool,
     ) -> Self {
         Self {
             name,
             working_dir,
             call,
             commands,
+            retry,
+            when,
+            group,
+            depends_on,
+            quiet,
+            ssh_agent_forwarding,
+            checkpoint,
+            environment,
+            inherit_container_env,
+            output_var,
+            ignore_errors,
+            always_run,
+        }
+    }
+
+    /// Checks if the step or any step of its group forwards the ssh agent of the host.
+    pub fn forwards_ssh_agent(&self) -> bool {
+        self.ssh_agent_forwarding
+            || self
+                .group
+                .as_ref()
+                .map(|g| g.steps.iter().any(|s| s.forwards_ssh_agent()))
+                .unwrap_or(false)
+    }
+}
+
+/// The compression used when transferring an artifact.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Display for Compression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Gzip => write!(f, "gzip"),
+            Self::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+/// The encoding of the output of the commands of a pipeline, which is converted to UTF-8 for
+/// the logs of the run.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputEncoding {
+    #[default]
+    Utf8,
+    Latin1,
+}
+
+impl Display for OutputEncoding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Utf8 => write!(f, "utf8"),
+            Self::Latin1 => write!(f, "latin1"),
         }
     }
 }
@@ -70,6 +396,8 @@ pub struct Artifacts {
     pub to: Option<String>,
     pub ignore_errors: bool,
     pub after: Option<String>,
+    pub compress: Compression,
+    pub checksum: Option<String>,
 }
 
 impl Artifacts {
@@ -79,6 +407,8 @@ impl Artifacts {
         to: Option<String>,
         after: Option<String>,
         ignore_errors: bool,
+        compress: Compression,
+        checksum: Option<String>,
     ) -> Self {
         Self {
             method,
@@ -86,19 +416,87 @@ impl Artifacts {
             to,
             ignore_errors,
             after,
+            compress,
+            checksum,
         }
     }
+
+    /// Parses a checksum of the form `sha256:<hex digest>` and returns the digest in lowercase.
+    fn checksum(yaml: &Yaml) -> Result<Option<String>> {
+        let checksum = match yaml.as_str() {
+            Some(checksum) => checksum,
+            None if yaml.is_badvalue() => return Ok(None),
+            None => bail!("checksum of artifact should be a string"),
+        };
+        let digest = checksum
+            .strip_prefix("sha256:")
+            .filter(|d| d.len() == 64 && d.chars().all(|c| c.is_ascii_hexdigit()))
+            .ok_or_else(|| {
+                anyhow!("invalid checksum {checksum} for artifact, expected sha256:<hex digest>")
+            })?;
+        Ok(Some(digest.to_lowercase()))
+    }
+}
+
+/// The outcomes of a run that a notification is sent for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotifyOn {
+    Success,
+    Failure,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotificationTarget {
+    Slack { webhook_url: String },
+    Email { to: Vec<String> },
+    Webhook { url: String },
+}
+
+impl Display for NotificationTarget {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Slack { .. } => write!(f, "slack"),
+            Self::Email { .. } => write!(f, "email"),
+            Self::Webhook { .. } => write!(f, "webhook"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub target: NotificationTarget,
+    pub on: Vec<NotifyOn>,
+}
+
+impl Notification {
+    pub fn new(target: NotificationTarget, on: Vec<NotifyOn>) -> Self {
+        Self { target, on }
+    }
 }
 
 #[derive(Debug, Default)]
 pub struct Pipeline {
+    pub version: u64,
     pub name: Option<String>,
     pub runs_on: RunsOn,
+    pub cgroup: Option<CgroupLimits>,
     pub dispose: bool,
+    pub timeout_seconds: Option<u64>,
+    pub max_log_lines: Option<usize>,
+    pub environment_file: Option<String>,
+    pub env_passthrough: Vec<String>,
+    pub path_prepend: Vec<String>,
+    pub ssh_agent_forwarding: bool,
+    pub strict_substitution: bool,
+    pub prefix_steps: bool,
+    pub output_encoding: OutputEncoding,
+    pub credentials: Vec<String>,
     pub environment: Vec<Variable>,
     pub variables: Vec<Variable>,
+    pub matrix: Vec<MatrixAxis>,
     pub artifacts: Vec<Artifacts>,
     pub steps: Vec<BuildStep>,
+    pub notifications: Vec<Notification>,
 }
 
 impl Pipeline {
@@ -113,20 +511,385 @@ impl Pipeline {
     }
 
     pub fn load(yaml: &Yaml) -> Result<Self> {
+        let yaml = &Self::expand_templates(yaml)?;
+        let runs_on = Self::runs_on(&yaml["runs-on"])?;
         Ok(Self {
+            version: Self::version(&yaml["version"])?,
             name: yaml["name"].as_str().map(|n| n.to_string()),
-            runs_on: match yaml["runs-on"].as_str() {
-                Some("machine") | None => RunsOn::Machine,
-                Some(target) => RunsOn::Docker(target.to_string()),
-            },
+            cgroup: Self::cgroup(&yaml["cgroup"], &runs_on)?,
+            runs_on,
             dispose: yaml["dispose"].as_bool().unwrap_or(true),
+            timeout_seconds: Self::timeout_seconds(&yaml["timeout-seconds"])?,
+            max_log_lines: Self::max_log_lines(&yaml["max-log-lines"])?,
+            environment_file: yaml["environment-file"].as_str().map(|f| f.to_string()),
+            env_passthrough: Self::env_passthrough(&yaml["env-passthrough"])?,
+            path_prepend: Self::path_prepend(&yaml["path-prepend"])?,
+            ssh_agent_forwarding: yaml["ssh-agent-forwarding"].as_bool().unwrap_or(false),
+            strict_substitution: yaml["strict-substitution"].as_bool().unwrap_or(false),
+            prefix_steps: yaml["prefix-steps"].as_bool().unwrap_or(false),
+            output_encoding: match yaml["output-encoding"].as_str() {
+                Some("utf8") | None => OutputEncoding::Utf8,
+                Some("latin1") => OutputEncoding::Latin1,
+                Some(encoding) => {
+                    bail!("invalid output-encoding {encoding}, expected utf8 or latin1")
+                }
+            },
+            credentials: Self::credentials(&yaml["credentials"])?,
             environment: Self::variables(yaml, "environment")?,
             variables: Self::variables(yaml, "variables")?,
-            artifacts: Self::artifacts(yaml),
-            steps: Self::steps(yaml),
+            matrix: Self::matrix(&yaml["matrix"])?,
+            artifacts: Self::artifacts(yaml)?,
+            steps: Self::steps(yaml)?,
+            notifications: Self::notifications(&yaml["notifications"])?,
         })
     }
 
+    /// Parses the version of the pipeline format, which is the first one for pipelines that
+    /// were created before the version was introduced.
+    fn version(yaml: &Yaml) -> Result<u64> {
+        let version = match yaml {
+            Yaml::BadValue => return Ok(1),
+            Yaml::Integer(version) if *version > 0 => *version as u64,
+            _ => bail!("version of the pipeline should be a positive number"),
+        };
+        if version > PIPELINE_VERSION {
+            bail!("pipeline version {version} is not supported, the latest supported version is {PIPELINE_VERSION}. Upgrade bld in order to run this pipeline");
+        }
+        Ok(version)
+    }
+
+    /// Updates the content of a pipeline to the latest version of the format and returns None
+    /// when it is already up to date. The content is updated as text in order to keep its comments.
+    pub fn migrate(src: &str) -> Result<Option<String>> {
+        let yaml = YamlLoader::load_from_str(src)?;
+        if yaml.is_empty() {
+            return Err(anyhow!("invalid yaml"));
+        }
+        if !yaml[0]["version"].is_badvalue() {
+            Self::version(&yaml[0]["version"])?;
+            return Ok(None);
+        }
+        // pipelines without a version are of the first version of the format, which
+        // only requires the version entry to be added before the first top level entry.
+        let mut lines: Vec<&str> = src.lines().collect();
+        let index = lines
+            .iter()
+            .position(|l| {
+                let l = l.trim();
+                !l.is_empty() && !l.starts_with('#') && l != "---"
+            })
+            .unwrap_or(lines.len());
+        let version = format!("version: {PIPELINE_VERSION}");
+        lines.insert(index, &version);
+        let mut migrated = lines.join("\n");
+        if src.ends_with('\n') {
+            migrated.push('\n');
+        }
+        Pipeline::parse(&migrated)?;
+        Ok(Some(migrated))
+    }
+
+    /// Returns the content of a pipeline with the steps that use a step template replaced by
+    /// the steps that they expand to, which is the form of the pipeline that is executed.
+    pub fn resolve(src: &str) -> Result<String> {
+        let yaml = YamlLoader::load_from_str(src)?;
+        if yaml.is_empty() {
+            return Err(anyhow!("invalid yaml"));
+        }
+        let resolved = Self::expand_templates(&yaml[0])?;
+        Pipeline::load(&resolved)?;
+        let mut content = String::new();
+        YamlEmitter::new(&mut content)
+            .dump(&resolved)
+            .map_err(|e| anyhow!("unable to resolve the pipeline. {e:?}"))?;
+        content.push('\n');
+        Ok(content)
+    }
+
+    fn max_log_lines(yaml: &Yaml) -> Result<Option<usize>> {
+        match yaml.as_i64() {
+            Some(max) if max <= 0 => bail!("max-log-lines should be a positive number"),
+            Some(max) => Ok(Some(max as usize)),
+            None if yaml.is_badvalue() => Ok(None),
+            None => bail!("max-log-lines should be a positive number"),
+        }
+    }
+
+    fn timeout_seconds(yaml: &Yaml) -> Result<Option<u64>> {
+        match yaml.as_i64() {
+            Some(seconds) if seconds <= 0 => bail!("timeout-seconds should be a positive number"),
+            Some(seconds) => Ok(Some(seconds as u64)),
+            None if yaml.is_badvalue() => Ok(None),
+            None => bail!("timeout-seconds should be a positive number"),
+        }
+    }
+
+    fn runs_on(yaml: &Yaml) -> Result<RunsOn> {
+        if yaml.as_hash().is_none() {
+            return Ok(match yaml.as_str() {
+                Some("machine") | None => RunsOn::Machine,
+                Some(target) => RunsOn::Docker(target.to_string(), DockerOptions::default()),
+            });
+        }
+        if !yaml["kubernetes"].is_badvalue() {
+            return Self::kubernetes(&yaml["kubernetes"]);
+        }
+        if !yaml["ssh"].is_badvalue() {
+            return Self::ssh(&yaml["ssh"]);
+        }
+        let image = yaml["image"]
+            .as_str()
+            .ok_or_else(|| anyhow!("no image provided in the runs-on section"))?;
+        let oom_score_adj = yaml["oom-score-adj"].as_i64();
+        if let Some(score) = oom_score_adj {
+            if !(-1000..=1000).contains(&score) {
+                bail!("oom-score-adj should be between -1000 and 1000");
+            }
+        }
+        let oom_kill_disable = yaml["oom-kill-disable"].as_bool().unwrap_or(false);
+        let volumes = Self::volumes(&yaml["volumes"])?;
+        let network = match &yaml["network"] {
+            Yaml::BadValue => None,
+            Yaml::String(network) if !network.is_empty() => Some(network.to_string()),
+            _ => bail!("network should be the name of a docker network"),
+        };
+        let mut options = DockerOptions::new(oom_score_adj, oom_kill_disable, volumes, network);
+        options.resources = ContainerResources::load(&yaml["resources"], "resources of runs-on")?;
+        options.registry_name = match &yaml["registry"] {
+            Yaml::BadValue => None,
+            Yaml::String(registry) if !registry.is_empty() => Some(registry.to_string()),
+            _ => bail!("registry should be the name of a registry of the config"),
+        };
+        match &yaml["docker-remote"] {
+            Yaml::BadValue => Ok(RunsOn::Docker(image.to_string(), options)),
+            remote => {
+                options.host = Some(Self::docker_host(remote)?);
+                Ok(RunsOn::DockerRemote(image.to_string(), options))
+            }
+        }
+    }
+
+    /// Parses the pod that the pipeline runs on when the kubernetes platform is used.
+    fn kubernetes(yaml: &Yaml) -> Result<RunsOn> {
+        let image = yaml["image"]
+            .as_str()
+            .ok_or_else(|| anyhow!("no image provided in the kubernetes section"))?;
+        let namespace = match &yaml["namespace"] {
+            Yaml::BadValue => KUBERNETES_DEFAULT_NAMESPACE,
+            Yaml::String(namespace) if !namespace.is_empty() => namespace.as_str(),
+            _ => bail!("namespace should be the name of a kubernetes namespace"),
+        };
+        let service_account = match &yaml["service-account"] {
+            Yaml::BadValue => None,
+            Yaml::String(account) if !account.is_empty() => Some(account.to_string()),
+            _ => bail!("service-account should be the name of a kubernetes service account"),
+        };
+        Ok(RunsOn::Kubernetes {
+            image: image.to_string(),
+            namespace: namespace.to_string(),
+            service_account,
+        })
+    }
+
+    /// Parses the remote machine that the steps of the pipeline are executed on over ssh.
+    fn ssh(yaml: &Yaml) -> Result<RunsOn> {
+        let entry = |name: &str| match &yaml[name] {
+            Yaml::String(value) if !value.is_empty() => Ok(value.to_string()),
+            _ => Err(anyhow!("no {name} provided in the ssh section")),
+        };
+        Ok(RunsOn::Ssh {
+            host: entry("host")?,
+            user: entry("user")?,
+            key_file: entry("key-file")?,
+        })
+    }
+
+    /// Parses the remote docker daemon that the container of the pipeline is created on.
+    fn docker_host(yaml: &Yaml) -> Result<DockerHost> {
+        let url = yaml["host"]
+            .as_str()
+            .ok_or_else(|| anyhow!("no host provided in the docker-remote section"))?;
+        let is_valid = ["tcp://", "http://", "https://"]
+            .iter()
+            .any(|scheme| url.starts_with(scheme));
+        if !is_valid {
+            bail!("the host of docker-remote should be a tcp, http or https url");
+        }
+        let tls_cert_path = match &yaml["tls-cert"] {
+            Yaml::BadValue => None,
+            Yaml::String(path) if !path.is_empty() => Some(path.as_str()),
+            _ => bail!("tls-cert should be the path of the certificates directory"),
+        };
+        Ok(DockerHost::new(url, tls_cert_path))
+    }
+
+    /// Parses the volumes of the container in the `host_path:container_path[:ro|rw]` format
+    /// of docker binds.
+    fn volumes(yaml: &Yaml) -> Result<Vec<String>> {
+        if yaml.is_badvalue() {
+            return Ok(vec![]);
+        }
+        let entries = yaml.as_vec().ok_or_else(|| {
+            anyhow!("volumes should be a list of host_path:container_path entries")
+        })?;
+        let mut volumes = vec![];
+        for entry in entries.iter() {
+            let volume = entry
+                .as_str()
+                .ok_or_else(|| anyhow!("invalid entry in volumes"))?;
+            let parts: Vec<&str> = volume.split(':').collect();
+            let is_valid = match parts[..] {
+                [host, container] => !host.is_empty() && container.starts_with('/'),
+                [host, container, mode] => {
+                    !host.is_empty() && container.starts_with('/') && (mode == "ro" || mode == "rw")
+                }
+                _ => false,
+            };
+            if !is_valid {
+                bail!("invalid volume {volume}, expected host_path:container_path[:ro|rw]");
+            }
+            volumes.push(volume.to_string());
+        }
+        Ok(volumes)
+    }
+
+    fn env_passthrough(yaml: &Yaml) -> Result<Vec<String>> {
+        if yaml.is_badvalue() {
+            return Ok(vec![]);
+        }
+        yaml.as_vec()
+            .ok_or_else(|| anyhow!("env-passthrough should be a list of variable names"))?
+            .iter()
+            .map(|name| {
+                name.as_str()
+                    .map(|n| n.to_string())
+                    .ok_or_else(|| anyhow!("invalid variable name in env-passthrough"))
+            })
+            .collect()
+    }
+
+    /// Parses the resource limits of the pipeline, which are supported only on a machine.
+    fn cgroup(yaml: &Yaml, runs_on: &RunsOn) -> Result<Option<CgroupLimits>> {
+        if yaml.is_badvalue() {
+            return Ok(None);
+        }
+        if !matches!(runs_on, RunsOn::Machine) {
+            bail!("cgroup is supported only for pipelines that run on a machine");
+        }
+        let memory_mb = Self::cgroup_limit(&yaml["memory-mb"], "memory-mb")?;
+        let cpu_quota_percent =
+            Self::cgroup_limit(&yaml["cpu-quota-percent"], "cpu-quota-percent")?;
+        if memory_mb.is_none() && cpu_quota_percent.is_none() {
+            bail!("cgroup should define memory-mb or cpu-quota-percent");
+        }
+        Ok(Some(CgroupLimits::new(memory_mb, cpu_quota_percent)))
+    }
+
+    fn cgroup_limit(yaml: &Yaml, name: &str) -> Result<Option<u64>> {
+        match yaml {
+            Yaml::BadValue => Ok(None),
+            Yaml::Integer(limit) if *limit > 0 => Ok(Some(*limit as u64)),
+            _ => bail!("{name} of cgroup should be a positive number"),
+        }
+    }
+
+    /// Parses the names of the credentials of the server that are provided to the steps.
+    fn credentials(yaml: &Yaml) -> Result<Vec<String>> {
+        if yaml.is_badvalue() {
+            return Ok(vec![]);
+        }
+        yaml.as_vec()
+            .ok_or_else(|| anyhow!("credentials should be a list of credential names"))?
+            .iter()
+            .map(|name| {
+                name.as_str()
+                    .filter(|n| !n.is_empty())
+                    .map(|n| n.to_string())
+                    .ok_or_else(|| anyhow!("invalid credential name in credentials"))
+            })
+            .collect()
+    }
+
+    /// Parses the directories that are prepended to the PATH of the steps.
+    fn path_prepend(yaml: &Yaml) -> Result<Vec<String>> {
+        if yaml.is_badvalue() {
+            return Ok(vec![]);
+        }
+        yaml.as_vec()
+            .ok_or_else(|| anyhow!("path-prepend should be a list of directories"))?
+            .iter()
+            .map(|entry| match entry.as_str() {
+                Some(dir) if !dir.is_empty() => Ok(dir.to_string()),
+                _ => Err(anyhow!("invalid directory in path-prepend")),
+            })
+            .collect()
+    }
+
+    /// Parses the slack, email and webhook notifications of the pipeline. Notifications
+    /// are sent only when a run fails unless the outcomes are set in their on section.
+    fn notifications(yaml: &Yaml) -> Result<Vec<Notification>> {
+        if yaml.is_badvalue() {
+            return Ok(vec![]);
+        }
+        let hash = yaml
+            .as_hash()
+            .ok_or_else(|| anyhow!("notifications should contain slack, email or webhook"))?;
+        let mut notifications = vec![];
+        for (kind, entry) in hash.iter() {
+            let target = match kind.as_str() {
+                Some("slack") => NotificationTarget::Slack {
+                    webhook_url: entry["webhook-url"]
+                        .as_str()
+                        .ok_or_else(|| anyhow!("no webhook-url provided for slack notifications"))?
+                        .to_string(),
+                },
+                Some("email") => NotificationTarget::Email {
+                    to: Self::notification_recipients(&entry["to"])?,
+                },
+                Some("webhook") => NotificationTarget::Webhook {
+                    url: entry["url"]
+                        .as_str()
+                        .ok_or_else(|| anyhow!("no url provided for webhook notifications"))?
+                        .to_string(),
+                },
+                _ => bail!("notifications should contain slack, email or webhook"),
+            };
+            let on = match &entry["on"] {
+                Yaml::BadValue => vec![NotifyOn::Failure],
+                Yaml::Array(outcomes) => outcomes
+                    .iter()
+                    .map(|o| match o.as_str() {
+                        Some("success") => Ok(NotifyOn::Success),
+                        Some("failure") => Ok(NotifyOn::Failure),
+                        _ => Err(anyhow!(
+                            "notifications should be sent on success or failure"
+                        )),
+                    })
+                    .collect::<Result<Vec<NotifyOn>>>()?,
+                _ => bail!("notifications should be sent on success or failure"),
+            };
+            notifications.push(Notification::new(target, on));
+        }
+        Ok(notifications)
+    }
+
+    fn notification_recipients(yaml: &Yaml) -> Result<Vec<String>> {
+        let recipients = yaml
+            .as_vec()
+            .ok_or_else(|| anyhow!("email notifications should have a list of recipients"))?
+            .iter()
+            .map(|r| {
+                r.as_str()
+                    .map(|r| r.to_string())
+                    .ok_or_else(|| anyhow!("invalid recipient in email notifications"))
+            })
+            .collect::<Result<Vec<String>>>()?;
+        if recipients.is_empty() {
+            bail!("email notifications should have at least one recipient");
+        }
+        Ok(recipients)
+    }
+
     fn variables(yaml: &Yaml, section: &str) -> Result<Vec<Variable>> {
         let mut variables = Vec::<Variable>::new();
         if let Some(entries) = &yaml[section].as_vec() {
@@ -138,19 +901,111 @@ impl Pipeline {
                     .and_then(|k| k.as_str())
                     .map(|k| k.to_string())
                     .ok_or_else(err_variable_in_yaml)?;
-                let default_value = hash
-                    .values()
-                    .next()
-                    .and_then(|v| v.as_str())
-                    .map(|v| v.to_string())
-                    .ok_or_else(err_variable_in_yaml)?;
-                variables.push(Variable::new(name, default_value));
+                let value = hash.values().next().ok_or_else(err_variable_in_yaml)?;
+                variables.push(Self::variable(name, value, section)?);
             }
         }
         Ok(variables)
     }
 
-    fn artifacts(yaml: &Yaml) -> Vec<Artifacts> {
+    /// Parses a variable that is either a value of type string or a mapping with its
+    /// default value and its type.
+    fn variable(name: String, yaml: &Yaml, section: &str) -> Result<Variable> {
+        let scalar = |yaml: &Yaml| match yaml {
+            Yaml::String(v) | Yaml::Real(v) => Some(v.to_string()),
+            Yaml::Integer(v) => Some(v.to_string()),
+            Yaml::Boolean(v) => Some(v.to_string()),
+            _ => None,
+        };
+        if yaml.as_hash().is_none() {
+            let default_value = scalar(yaml).ok_or_else(err_variable_in_yaml)?;
+            return Ok(Variable::new(name, default_value));
+        }
+        let label = Variable::label(section);
+        let default_value = scalar(&yaml["default"])
+            .ok_or_else(|| anyhow!("{label} {name} should have a default value"))?;
+        let kind = match yaml["type"].as_str() {
+            Some("string") => VariableType::String,
+            Some("int") => VariableType::Int,
+            Some("bool") => VariableType::Bool,
+            Some(kind) => bail!("invalid type {kind} for {label} {name}"),
+            None if yaml["type"].is_badvalue() => VariableType::String,
+            None => bail!("invalid type for {label} {name}"),
+        };
+        let mut variable = Variable::new(name, default_value);
+        variable.kind = kind;
+        variable.default_value = variable.conform(label, &variable.default_value)?;
+        Ok(variable)
+    }
+
+    fn matrix(yaml: &Yaml) -> Result<Vec<MatrixAxis>> {
+        let mut matrix = Vec::<MatrixAxis>::new();
+        if yaml.is_badvalue() {
+            return Ok(matrix);
+        }
+        let hash = yaml
+            .as_hash()
+            .ok_or_else(|| anyhow!("matrix should contain axes with a list of values"))?;
+        for (name, values) in hash.iter() {
+            let name = name
+                .as_str()
+                .ok_or_else(|| anyhow!("invalid name for matrix axis"))?;
+            let values = values
+                .as_vec()
+                .ok_or_else(|| anyhow!("matrix axis {name} should be a list of values"))?
+                .iter()
+                .map(|v| match v {
+                    Yaml::String(v) | Yaml::Real(v) => Ok(v.to_string()),
+                    Yaml::Integer(v) => Ok(v.to_string()),
+                    Yaml::Boolean(v) => Ok(v.to_string()),
+                    _ => Err(anyhow!("invalid value for matrix axis {name}")),
+                })
+                .collect::<Result<Vec<String>>>()?;
+            if values.is_empty() {
+                bail!("matrix axis {name} should have at least one value");
+            }
+            matrix.push(MatrixAxis::new(name.to_string(), values));
+        }
+        Ok(matrix)
+    }
+
+    /// Checks if any step of the pipeline forwards the ssh agent of the host.
+    pub fn forwards_ssh_agent(&self) -> bool {
+        self.steps.iter().any(|s| s.forwards_ssh_agent())
+    }
+
+    /// Expands the matrix into the cartesian product of its axes, with each combination
+    /// being a list of variables. A pipeline without a matrix has no combinations.
+    pub fn matrix_combinations(&self) -> Vec<Vec<(String, String)>> {
+        if self.matrix.is_empty() {
+            return vec![];
+        }
+        let mut combinations: Vec<Vec<(String, String)>> = vec![vec![]];
+        for axis in self.matrix.iter() {
+            combinations = combinations
+                .into_iter()
+                .flat_map(|combination| {
+                    axis.values.iter().map(move |value| {
+                        let mut combination = combination.clone();
+                        combination.push((axis.name.to_string(), value.to_string()));
+                        combination
+                    })
+                })
+                .collect();
+        }
+        combinations
+    }
+
+    /// Formats a combination of the matrix as `name=value` pairs.
+    pub fn matrix_label(combination: &[(String, String)]) -> String {
+        combination
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    fn artifacts(yaml: &Yaml) -> Result<Vec<Artifacts>> {
         let mut artifacts = Vec::<Artifacts>::new();
         if let Some(entries) = &yaml["artifacts"].as_vec() {
             for artifact in entries.iter() {
@@ -159,39 +1014,1214 @@ impl Pipeline {
                 let to = artifact["to"].as_str().map(|p| p.to_string());
                 let after = artifact["after"].as_str().map(|a| a.to_string());
                 let ignore_errors = artifact["ignore-errors"].as_bool().unwrap_or(false);
-                artifacts.push(Artifacts::new(method, from, to, after, ignore_errors));
+                let compress = match artifact["compress"].as_str() {
+                    Some("none") | None => Compression::None,
+                    Some("gzip") => Compression::Gzip,
+                    Some("zstd") => Compression::Zstd,
+                    Some(compress) => bail!("invalid compress value {compress} for artifact"),
+                };
+                let checksum = Artifacts::checksum(&artifact["checksum"])?;
+                artifacts.push(Artifacts::new(
+                    method,
+                    from,
+                    to,
+                    after,
+                    ignore_errors,
+                    compress,
+                    checksum,
+                ));
             }
         }
-        artifacts
+        Ok(artifacts)
+    }
+
+    fn call(yaml: &Yaml) -> Result<Option<Call>> {
+        let (pipeline, rollup) = match yaml.as_str() {
+            Some(pipeline) => (pipeline, None),
+            None => (
+                yaml["pipeline"].as_str().unwrap_or(""),
+                yaml["rollup"].as_str(),
+            ),
+        };
+        if pipeline.is_empty() {
+            return Ok(None);
+        }
+        let rollup = match rollup {
+            Some("ignore") => Rollup::Ignore,
+            Some("warn") => Rollup::Warn,
+            Some("fail") | None => Rollup::Fail,
+            Some(rollup) => bail!("invalid rollup value {rollup} for call to {pipeline}"),
+        };
+        Ok(Some(Call::new(pipeline.to_string(), rollup)))
     }
 
-    fn steps(yaml: &Yaml) -> Vec<BuildStep> {
+    /// Parses the retry section of a step, or the `retries` and `retry-backoff-seconds`
+    /// shorthand where the number of retries excludes the first attempt.
+    fn retry(step: &Yaml) -> Result<Option<Retry>> {
+        let yaml = &step["retry"];
+        let retries = &step["retries"];
+        if !retries.is_badvalue() {
+            if !yaml.is_badvalue() {
+                bail!("a step can not have both retry and retries entries");
+            }
+            let retries = retries
+                .as_i64()
+                .filter(|r| *r >= 0)
+                .ok_or_else(|| anyhow!("retries of a step should be a non negative number"))?;
+            let delay_seconds = step["retry-backoff-seconds"].as_i64().unwrap_or(0).max(0);
+            return Ok(Some(Retry::new(retries as u64 + 1, delay_seconds as u64)));
+        }
+        if yaml.is_badvalue() {
+            return Ok(None);
+        }
+        let attempts = yaml["attempts"]
+            .as_i64()
+            .filter(|a| *a > 0)
+            .ok_or_else(|| {
+                anyhow!("retry of a step should define a positive number of attempts")
+            })?;
+        let delay_seconds = yaml["delay-seconds"].as_i64().unwrap_or(0).max(0);
+        Ok(Some(Retry::new(attempts as u64, delay_seconds as u64)))
+    }
+
+    fn step(
+        step: &Yaml,
+        working_dir: &Option<String>,
+        ssh_agent_forwarding: bool,
+        inherit_container_env: bool,
+        in_group: bool,
+    ) -> Result<BuildStep> {
+        let name = step["name"].as_str().map(|n| n.to_string());
+        let working_dir = step["working-dir"]
+            .as_str()
+            .map(|w| w.to_string())
+            .or_else(|| working_dir.clone());
+        let ssh_agent_forwarding = step["ssh-agent-forwarding"]
+            .as_bool()
+            .unwrap_or(ssh_agent_forwarding);
+        let inherit_container_env = step["inherit-container-env"]
+            .as_bool()
+            .unwrap_or(inherit_container_env);
+        let environment = Self::variables(step, "environment")?;
+        let mut call = vec![];
+        for entry in step["call"].as_vec().unwrap_or(&Vec::<Yaml>::new()) {
+            if let Some(entry) = Self::call(entry)? {
+                call.push(entry);
+            }
+        }
+        let commands: Vec<String> = step["exec"]
+            .as_vec()
+            .unwrap_or(&Vec::<Yaml>::new())
+            .iter()
+            .map(|c| c.as_str().unwrap_or("").to_string())
+            .filter(|c| !c.is_empty())
+            .collect();
+        let retry = Self::retry(step)?;
+        let when = step["when"].as_str().map(|w| w.to_string());
+        if let Some(when) = &when {
+            validate(when).map_err(|e| {
+                let name = name.as_deref().unwrap_or_default();
+                anyhow!("invalid when condition of step {name}, {e}")
+            })?;
+        }
+        let quiet = step["quiet"].as_bool().unwrap_or(false);
+        if quiet && in_group {
+            bail!("a step of a group can not be quiet, set quiet on the group step instead");
+        }
+        let checkpoint = match &step["checkpoint"] {
+            Yaml::BadValue => None,
+            _ if in_group => {
+                bail!("a step of a group can not be a checkpoint, set checkpoint on the group step instead")
+            }
+            Yaml::String(checkpoint) if !checkpoint.is_empty() => Some(checkpoint.to_string()),
+            _ => bail!("checkpoint should be the name of the checkpoint"),
+        };
+        let ignore_errors = step["ignore-errors"].as_bool().unwrap_or(false);
+        let always_run = step["always-run"].as_bool().unwrap_or(false);
+        if always_run && in_group {
+            bail!(
+                "a step of a group can not be always-run, set always-run on the group step instead"
+            );
+        }
+        let output_var = match &step["output-var"] {
+            Yaml::BadValue => None,
+            Yaml::String(var)
+                if !var.is_empty()
+                    && var
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || c == '_' || c == '-') =>
+            {
+                Some(var.to_string())
+            }
+            _ => bail!("output-var should be the name of a variable"),
+        };
+        let group = match step["group"].as_vec() {
+            Some(_) if in_group => bail!("a step group can not contain other groups"),
+            Some(_) if !call.is_empty() || !commands.is_empty() => {
+                bail!("a step with a group can not have call or exec entries")
+            }
+            Some(_) if output_var.is_some() => {
+                bail!("a step with a group can not have an output-var entry")
+            }
+            Some(entries) => {
+                let parallel = step["parallel"].as_bool().unwrap_or(false);
+                let mut steps = vec![];
+                for entry in entries.iter() {
+                    steps.push(Self::step(
+                        entry,
+                        &working_dir,
+                        ssh_agent_forwarding,
+                        inherit_container_env,
+                        true,
+                    )?);
+                }
+                Some(StepGroup::new(parallel, steps))
+            }
+            None => None,
+        };
+        let depends_on = match &step["depends-on"] {
+            Yaml::BadValue => vec![],
+            _ if in_group => bail!("a step of a group can not have depends-on entries"),
+            Yaml::Array(entries) => entries
+                .iter()
+                .map(|d| {
+                    d.as_str()
+                        .map(|d| d.to_string())
+                        .ok_or_else(|| anyhow!("depends-on should be a list of step names"))
+                })
+                .collect::<Result<Vec<String>>>()?,
+            _ => bail!("depends-on should be a list of step names"),
+        };
+        Ok(BuildStep::new(
+            name,
+            working_dir,
+            call,
+            commands,
+            retry,
+            when,
+            group,
+            depends_on,
+            quiet,
+            ssh_agent_forwarding,
+            checkpoint,
+            environment,
+            inherit_container_env,
+            output_var,
+            ignore_errors,
+            always_run,
+        ))
+    }
+
+    fn steps(yaml: &Yaml) -> Result<Vec<BuildStep>> {
         let mut steps = Vec::<BuildStep>::new();
         let working_dir = yaml["working-dir"].as_str().map(|w| w.to_string());
+        let ssh_agent_forwarding = yaml["ssh-agent-forwarding"].as_bool().unwrap_or(false);
         if let Some(entries) = &yaml["steps"].as_vec() {
             for step in entries.iter() {
-                let name = step["name"].as_str().map(|n| n.to_string());
-                let working_dir = step["working-dir"]
-                    .as_str()
-                    .map(|w| w.to_string())
-                    .or_else(|| working_dir.clone());
-                let call = step["call"]
-                    .as_vec()
-                    .unwrap_or(&Vec::<Yaml>::new())
+                steps.push(Self::step(
+                    step,
+                    &working_dir,
+                    ssh_agent_forwarding,
+                    true,
+                    false,
+                )?);
+            }
+        }
+        Self::validate_dependencies(&steps)?;
+        let has_dependencies = steps.iter().any(|s| !s.depends_on.is_empty());
+        if has_dependencies && steps.iter().any(|s| s.quiet) {
+            bail!("quiet steps are not supported when steps have dependencies since they run concurrently");
+        }
+        Ok(steps)
+    }
+
+    /// Replaces the steps that use a step template, including the steps of groups, with the
+    /// entries of the template where its parameters are substituted with the provided values.
+    /// The step templates section is removed since it's not needed after the expansion.
+    fn expand_templates(yaml: &Yaml) -> Result<Yaml> {
+        let templates = match &yaml["step-templates"] {
+            Yaml::BadValue => return Ok(yaml.clone()),
+            Yaml::Hash(templates) => templates,
+            _ => bail!("step-templates should be a mapping of template names to steps"),
+        };
+        for (name, template) in templates.iter() {
+            let name = name
+                .as_str()
+                .ok_or_else(|| anyhow!("the names of step templates should be strings"))?;
+            Self::validate_template(name, template)?;
+        }
+        let mut expanded = yaml.as_hash().cloned().unwrap_or_default();
+        expanded.remove(&Yaml::String("step-templates".to_string()));
+        if let Some(entries) = yaml["steps"].as_vec() {
+            let steps = entries
+                .iter()
+                .enumerate()
+                .map(|(i, s)| Self::expand_step(s, templates, &format!("step {}", i + 1)))
+                .collect::<Result<Vec<Yaml>>>()?;
+            expanded.insert(Yaml::String("steps".to_string()), Yaml::Array(steps));
+        }
+        Ok(Yaml::Hash(expanded))
+    }
+
+    fn validate_template(name: &str, template: &Yaml) -> Result<()> {
+        if template.as_hash().is_none() {
+            bail!("step template {name} should be a mapping of the entries of a step");
+        }
+        let params = Self::template_params(name, template)?;
+        let mut uses = !template["use"].is_badvalue();
+        for entry in template["group"].as_vec().unwrap_or(&Vec::<Yaml>::new()) {
+            uses |= !entry["use"].is_badvalue();
+        }
+        if uses {
+            bail!("step template {name} can not use other step templates");
+        }
+        let mut values = vec![];
+        Self::template_strings(template, &mut values);
+        for value in values {
+            for param in Self::param_references(value) {
+                if !params.contains(&param) {
+                    bail!("step template {name} references parameter {param} which isn't declared in its params");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn template_params(name: &str, template: &Yaml) -> Result<Vec<String>> {
+        match &template["params"] {
+            Yaml::BadValue => Ok(vec![]),
+            Yaml::Array(params) => params
+                .iter()
+                .map(|p| {
+                    p.as_str().map(|p| p.to_string()).ok_or_else(|| {
+                        anyhow!("params of step template {name} should be a list of names")
+                    })
+                })
+                .collect(),
+            _ => bail!("params of step template {name} should be a list of names"),
+        }
+    }
+
+    fn template_strings<'a>(yaml: &'a Yaml, values: &mut Vec<&'a str>) {
+        match yaml {
+            Yaml::String(value) => values.push(value),
+            Yaml::Array(entries) => entries
+                .iter()
+                .for_each(|e| Self::template_strings(e, values)),
+            Yaml::Hash(hash) => hash
+                .values()
+                .for_each(|v| Self::template_strings(v, values)),
+            _ => {}
+        }
+    }
+
+    /// The names of the parameters that are referenced as `${{ param.NAME }}` in the text.
+    fn param_references(txt: &str) -> Vec<String> {
+        let mut params = vec![];
+        let mut rest = txt;
+        while let Some(start) = rest.find("${{") {
+            rest = &rest[start + 3..];
+            let end = match rest.find("}}") {
+                Some(end) => end,
+                None => break,
+            };
+            if let Some(param) = rest[..end].trim().strip_prefix("param.") {
+                params.push(param.trim().to_string());
+            }
+            rest = &rest[end + 2..];
+        }
+        params
+    }
+
+    fn substitute_params(yaml: &Yaml, values: &HashMap<String, String>) -> Yaml {
+        match yaml {
+            Yaml::String(value) => {
+                let mut substituted = String::new();
+                let mut rest = value.as_str();
+                while let Some(start) = rest.find("${{") {
+                    let end = match rest[start..].find("}}") {
+                        Some(end) => start + end + 2,
+                        None => break,
+                    };
+                    let param = rest[start + 3..end - 2]
+                        .trim()
+                        .strip_prefix("param.")
+                        .and_then(|p| values.get(p.trim()));
+                    substituted.push_str(&rest[..start]);
+                    substituted.push_str(param.map(|p| p.as_str()).unwrap_or(&rest[start..end]));
+                    rest = &rest[end..];
+                }
+                substituted.push_str(rest);
+                Yaml::String(substituted)
+            }
+            Yaml::Array(entries) => Yaml::Array(
+                entries
                     .iter()
-                    .map(|c| c.as_str().unwrap_or("").to_string())
-                    .filter(|c| !c.is_empty())
-                    .collect();
-                let commands: Vec<String> = step["exec"]
-                    .as_vec()
-                    .unwrap_or(&Vec::<Yaml>::new())
+                    .map(|e| Self::substitute_params(e, values))
+                    .collect(),
+            ),
+            Yaml::Hash(hash) => Yaml::Hash(
+                hash.iter()
+                    .map(|(k, v)| (k.clone(), Self::substitute_params(v, values)))
+                    .collect(),
+            ),
+            _ => yaml.clone(),
+        }
+    }
+
+    fn expand_step(step: &Yaml, templates: &Hash, position: &str) -> Result<Yaml> {
+        let label = step["name"]
+            .as_str()
+            .map(|n| format!("step {n}"))
+            .unwrap_or_else(|| position.to_string());
+        let mut step = match &step["use"] {
+            Yaml::BadValue => step.clone(),
+            Yaml::Hash(_) => Self::expand_use(step, templates, &label)?,
+            _ => bail!("use of {label} should define the template and its parameters"),
+        };
+        let group = match step["group"].as_vec() {
+            Some(entries) => entries
+                .iter()
+                .enumerate()
+                .map(|(i, s)| {
+                    Self::expand_step(
+                        s,
+                        templates,
+                        &format!("step {} of the group of {label}", i + 1),
+                    )
+                })
+                .collect::<Result<Vec<Yaml>>>()?,
+            None => return Ok(step),
+        };
+        if let Yaml::Hash(hash) = &mut step {
+            hash.insert(Yaml::String("group".to_string()), Yaml::Array(group));
+        }
+        Ok(step)
+    }
+
+    fn expand_use(step: &Yaml, templates: &Hash, label: &str) -> Result<Yaml> {
+        let name = step["use"]["template"]
+            .as_str()
+            .ok_or_else(|| anyhow!("use of {label} should define the name of the template"))?;
+        let template = templates
+            .get(&Yaml::String(name.to_string()))
+            .ok_or_else(|| anyhow!("{label} uses step template {name} which doesn't exist"))?;
+        let params = Self::template_params(name, template)?;
+        let provided = match &step["use"]["with"] {
+            Yaml::BadValue => Hash::new(),
+            Yaml::Hash(provided) => provided.clone(),
+            _ => bail!("with of {label} should be a mapping of the parameters of template {name}"),
+        };
+        let mut values = HashMap::new();
+        for (param, value) in provided.iter() {
+            let param = param.as_str().unwrap_or_default();
+            if !params.iter().any(|p| p == param) {
+                bail!(
+                    "{label} passes parameter {param} which isn't declared by step template {name}"
+                );
+            }
+            let value = match value {
+                Yaml::String(value) => value.to_string(),
+                Yaml::Integer(value) => value.to_string(),
+                Yaml::Real(value) => value.to_string(),
+                Yaml::Boolean(value) => value.to_string(),
+                _ => bail!("parameter {param} of {label} should be a scalar value"),
+            };
+            values.insert(param.to_string(), value);
+        }
+        if let Some(missing) = params.iter().find(|p| !values.contains_key(*p)) {
+            bail!("{label} doesn't provide parameter {missing} of step template {name}");
+        }
+        let generated = if params.is_empty() {
+            name.to_string()
+        } else {
+            let values: Vec<&str> = params.iter().map(|p| values[p].as_str()).collect();
+            format!("{name} ({})", values.join(", "))
+        };
+        let mut expanded = Hash::new();
+        expanded.insert(Yaml::String("name".to_string()), Yaml::String(generated));
+        if let Yaml::Hash(entries) = Self::substitute_params(template, &values) {
+            for (key, value) in entries {
+                if key.as_str() != Some("name") && key.as_str() != Some("params") {
+                    expanded.insert(key, value);
+                }
+            }
+        }
+        // the entries of the step, such as its name or dependencies, take precedence over the
+        // entries of the template.
+        for (key, value) in step.as_hash().into_iter().flatten() {
+            match expanded.get_mut(key) {
+                _ if key.as_str() == Some("use") => {}
+                Some(entry) => *entry = value.clone(),
+                None => {
+                    expanded.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        Ok(Yaml::Hash(expanded))
+    }
+
+    /// Checks that the dependencies of the steps reference existing steps and that
+    /// they form an acyclic graph.
+    fn validate_dependencies(steps: &[BuildStep]) -> Result<()> {
+        if steps.iter().all(|s| s.depends_on.is_empty()) {
+            return Ok(());
+        }
+        let mut indexes = HashMap::new();
+        for (i, step) in steps.iter().enumerate() {
+            if let Some(name) = &step.name {
+                if indexes.insert(name.as_str(), i).is_some() {
+                    bail!("step names should be unique when steps have dependencies, found {name} more than once");
+                }
+            }
+        }
+        let mut graph = vec![vec![]; steps.len()];
+        for (i, step) in steps.iter().enumerate() {
+            for dependency in step.depends_on.iter() {
+                let index = indexes
+                    .get(dependency.as_str())
+                    .ok_or_else(|| anyhow!("step depends on {dependency} which doesn't exist"))?;
+                graph[i].push(*index);
+            }
+        }
+        // 0 is unvisited, 1 is in the current path and 2 is visited.
+        let mut marks = vec![0; steps.len()];
+        let mut path = vec![];
+        for i in 0..steps.len() {
+            if let Some(cycle) = Self::find_cycle(i, &graph, &mut marks, &mut path) {
+                let cycle: Vec<String> = cycle
                     .iter()
-                    .map(|c| c.as_str().unwrap_or("").to_string())
-                    .filter(|c| !c.is_empty())
+                    .map(|i| steps[*i].name.clone().unwrap_or_default())
                     .collect();
-                steps.push(BuildStep::new(name, working_dir, call, commands));
+                bail!("cyclic dependency between steps {}", cycle.join(" -> "));
             }
         }
-        steps
+        Ok(())
+    }
+
+    fn find_cycle(
+        node: usize,
+        graph: &[Vec<usize>],
+        marks: &mut [u8],
+        path: &mut Vec<usize>,
+    ) -> Option<Vec<usize>> {
+        match marks[node] {
+            1 => {
+                let start = path.iter().position(|n| *n == node).unwrap_or(0);
+                let mut cycle = path[start..].to_vec();
+                cycle.push(node);
+                return Some(cycle);
+            }
+            2 => return None,
+            _ => {}
+        }
+        marks[node] = 1;
+        path.push(node);
+        for next in graph[node].iter() {
+            if let Some(cycle) = Self::find_cycle(*next, graph, marks, path) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        marks[node] = 2;
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pipeline_matrix_expands_to_cartesian_product() {
+        let pipeline = Pipeline::parse(
+            r"
+matrix:
+  os: [ubuntu, alpine]
+  version: [1.0, 2.0]
+",
+        )
+        .unwrap();
+        let combinations = pipeline.matrix_combinations();
+
+        assert_eq!(combinations.len(), 4);
+        assert_eq!(
+            combinations[1],
+            vec![
+                ("os".to_string(), "ubuntu".to_string()),
+                ("version".to_string(), "2.0".to_string())
+            ]
+        );
+        assert!(Pipeline::parse("name: test")
+            .unwrap()
+            .matrix_combinations()
+            .is_empty());
+    }
+
+    #[test]
+    fn pipeline_notifications_default_to_failures() {
+        let pipeline = Pipeline::parse(
+            r"
+notifications:
+  slack:
+    webhook-url: bld:env:SLACK_WEBHOOK
+    on: [failure, success]
+  email:
+    to: [ops@example.com]
+",
+        )
+        .unwrap();
+
+        assert_eq!(pipeline.notifications.len(), 2);
+        assert_eq!(
+            pipeline.notifications[0].on,
+            vec![NotifyOn::Failure, NotifyOn::Success]
+        );
+        assert_eq!(
+            pipeline.notifications[1].target,
+            NotificationTarget::Email {
+                to: vec!["ops@example.com".to_string()]
+            }
+        );
+        assert_eq!(pipeline.notifications[1].on, vec![NotifyOn::Failure]);
+        assert!(Pipeline::parse("notifications:\n  teams:\n    url: x").is_err());
+    }
+
+    #[test]
+    fn pipeline_ssh_agent_forwarding_is_inherited_by_steps() {
+        let pipeline = Pipeline::parse(
+            r"
+ssh-agent-forwarding: true
+steps:
+- name: push
+  exec:
+  - git push
+- name: build
+  ssh-agent-forwarding: false
+  group:
+  - exec:
+    - cargo build
+",
+        )
+        .unwrap();
+
+        assert!(pipeline.steps[0].ssh_agent_forwarding);
+        assert!(!pipeline.steps[1].forwards_ssh_agent());
+        assert!(pipeline.forwards_ssh_agent());
+        assert!(!Pipeline::parse(
+            "steps:
+- exec:
+  - ls"
+        )
+        .unwrap()
+        .forwards_ssh_agent());
+    }
+
+    #[test]
+    fn pipeline_invalid_when_condition_fails_parsing() {
+        let pipeline = Pipeline::parse(
+            r"
+steps:
+- name: deploy
+  when: bld:var:environment == 'production' && bld:env:BRANCH != main
+  exec:
+  - ./deploy.sh
+",
+        )
+        .unwrap();
+        assert!(pipeline.steps[0].when.is_some());
+
+        let error = Pipeline::parse(
+            r"
+steps:
+- name: deploy
+  when: bld:var:environment = production
+  exec:
+  - ./deploy.sh
+",
+        )
+        .unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("invalid when condition of step deploy"));
+    }
+
+    #[test]
+    fn pipeline_step_environment_is_parsed() {
+        let pipeline = Pipeline::parse(
+            r"
+steps:
+- name: test
+  environment:
+  - RUST_LOG: debug
+  exec:
+  - cargo test
+- name: package
+  inherit-container-env: false
+  group:
+  - environment:
+    - PATH: /usr/bin
+    exec:
+    - make package
+",
+        )
+        .unwrap();
+
+        let step = &pipeline.steps[0];
+        assert!(step.inherit_container_env);
+        assert_eq!(step.environment[0].name, "RUST_LOG");
+        assert_eq!(step.environment[0].default_value, "debug");
+        let group = pipeline.steps[1].group.as_ref().unwrap();
+        assert!(!group.steps[0].inherit_container_env);
+        assert_eq!(group.steps[0].environment[0].name, "PATH");
+    }
+
+    #[test]
+    fn pipeline_cgroup_is_parsed() {
+        let pipeline = Pipeline::parse(
+            r"
+runs-on: machine
+cgroup:
+  memory-mb: 512
+  cpu-quota-percent: 50
+",
+        )
+        .unwrap();
+        let cgroup = pipeline.cgroup.unwrap();
+        assert_eq!(cgroup.memory_mb, Some(512));
+        assert_eq!(cgroup.cpu_quota_percent, Some(50));
+
+        let error = Pipeline::parse(
+            r"
+runs-on: ubuntu
+cgroup:
+  memory-mb: 512
+",
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "cgroup is supported only for pipelines that run on a machine"
+        );
+
+        let error = Pipeline::parse(
+            r"
+runs-on: machine
+cgroup:
+  memory-mb: 0
+",
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "memory-mb of cgroup should be a positive number"
+        );
+    }
+
+    #[test]
+    fn pipeline_step_output_var_is_parsed() {
+        let pipeline = Pipeline::parse(
+            r"
+steps:
+- name: version
+  output-var: VERSION
+  exec:
+  - git describe --tags
+",
+        )
+        .unwrap();
+        assert_eq!(pipeline.steps[0].output_var.as_deref(), Some("VERSION"));
+
+        let error = Pipeline::parse(
+            r"
+steps:
+- name: version
+  output-var: the version
+  exec:
+  - git describe --tags
+",
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "output-var should be the name of a variable"
+        );
+
+        let error = Pipeline::parse(
+            r"
+steps:
+- output-var: VERSION
+  group:
+  - exec:
+    - git describe --tags
+",
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "a step with a group can not have an output-var entry"
+        );
+    }
+
+    #[test]
+    fn pipeline_version_defaults_to_first_and_rejects_newer_versions() {
+        assert_eq!(Pipeline::parse("name: test").unwrap().version, 1);
+        assert_eq!(Pipeline::parse("version: 1").unwrap().version, 1);
+
+        let error = Pipeline::parse("version: 2\nsteps: new-format")
+            .unwrap_err()
+            .to_string();
+        assert!(error.starts_with("pipeline version 2 is not supported"));
+        assert!(Pipeline::parse("version: latest").is_err());
+    }
+
+    #[test]
+    fn pipeline_migrate_adds_the_version_after_leading_comments() {
+        let migrated = Pipeline::migrate("# build pipeline\nname: test\nruns-on: machine\n")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            migrated,
+            "# build pipeline\nversion: 1\nname: test\nruns-on: machine\n"
+        );
+        assert_eq!(Pipeline::migrate(&migrated).unwrap(), None);
+        assert!(Pipeline::migrate("version: 2").is_err());
+    }
+
+    #[test]
+    fn pipeline_step_retries_shorthand_includes_first_attempt() {
+        let pipeline = Pipeline::parse(
+            r"
+steps:
+- name: fetch
+  retries: 2
+  retry-backoff-seconds: 5
+",
+        )
+        .unwrap();
+        let retry = pipeline.steps[0].retry.as_ref().unwrap();
+
+        assert_eq!((retry.attempts, retry.delay_seconds), (3, 5));
+        assert!(Pipeline::parse(
+            r"
+steps:
+- retries: 2
+  retry:
+    attempts: 3
+"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn pipeline_docker_volumes_are_validated() {
+        let pipeline = Pipeline::parse(
+            r"
+runs-on:
+  image: ubuntu
+  volumes:
+  - /data:/data:ro
+  - cache:/cache
+",
+        )
+        .unwrap();
+
+        match pipeline.runs_on {
+            RunsOn::Docker(_, options) => {
+                assert_eq!(options.volumes, vec!["/data:/data:ro", "cache:/cache"])
+            }
+            _ => panic!("pipeline should run on docker"),
+        }
+        assert!(Pipeline::parse("runs-on:\n  image: ubuntu\n  volumes: [/data]").is_err());
+        assert!(Pipeline::parse("runs-on:\n  image: ubuntu\n  volumes: ['/data:data']").is_err());
+        assert!(Pipeline::parse("runs-on:\n  image: ubuntu\n  volumes: ['/a:/b:x']").is_err());
+    }
+
+    #[test]
+    fn pipeline_docker_resources_are_parsed() {
+        let pipeline = Pipeline::parse(
+            r"
+runs-on:
+  image: ubuntu
+  resources:
+    memory: 2g
+    cpus: 1.5
+    pids: 512
+",
+        )
+        .unwrap();
+
+        match pipeline.runs_on {
+            RunsOn::Docker(_, options) => {
+                assert_eq!(options.resources.memory, Some(2 << 30));
+                assert_eq!(options.resources.cpus, Some(1.5));
+                assert_eq!(options.resources.cpu_shares, None);
+                assert_eq!(options.resources.pids, Some(512));
+            }
+            _ => panic!("pipeline should run on docker"),
+        }
+        assert!(
+            Pipeline::parse("runs-on:\n  image: ubuntu\n  resources:\n    memory: lots").is_err()
+        );
+        assert!(Pipeline::parse("runs-on:\n  image: ubuntu\n  resources: 2g").is_err());
+    }
+
+    #[test]
+    fn pipeline_docker_remote_host_is_parsed() {
+        let pipeline = Pipeline::parse(
+            r"
+runs-on:
+  image: ubuntu
+  docker-remote:
+    host: tcp://build-box:2376
+    tls-cert: /etc/bld/certs
+",
+        )
+        .unwrap();
+
+        match pipeline.runs_on {
+            RunsOn::DockerRemote(image, options) => {
+                assert_eq!(image, "ubuntu");
+                assert_eq!(
+                    options.host,
+                    Some(DockerHost::new(
+                        "tcp://build-box:2376",
+                        Some("/etc/bld/certs")
+                    ))
+                );
+            }
+            _ => panic!("pipeline should run on a remote docker host"),
+        }
+        assert!(Pipeline::parse("runs-on:\n  image: ubuntu\n  docker-remote: {}").is_err());
+        assert!(Pipeline::parse(
+            "runs-on:\n  image: ubuntu\n  docker-remote:\n    host: tcp://build-box:2376\n    tls-cert: 1"
+        )
+        .is_err());
+        assert!(Pipeline::parse(
+            "runs-on:\n  image: ubuntu\n  docker-remote:\n    host: unix:///var/run/docker.sock"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn pipeline_path_prepend_is_parsed() {
+        let pipeline = Pipeline::parse(
+            r"
+path-prepend:
+- /opt/tools/bin
+- bld:var:CUSTOM_SDK/bin
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            pipeline.path_prepend,
+            vec!["/opt/tools/bin", "bld:var:CUSTOM_SDK/bin"]
+        );
+        assert!(Pipeline::parse("path-prepend: /opt/tools/bin").is_err());
+        assert!(Pipeline::parse("path-prepend: ['']").is_err());
+    }
+
+    #[test]
+    fn pipeline_kubernetes_pod_is_parsed() {
+        let pipeline = Pipeline::parse(
+            r"
+runs-on:
+  kubernetes:
+    image: ubuntu
+    namespace: ci
+    service-account: builder
+",
+        )
+        .unwrap();
+
+        match pipeline.runs_on {
+            RunsOn::Kubernetes {
+                image,
+                namespace,
+                service_account,
+            } => {
+                assert_eq!(image, "ubuntu");
+                assert_eq!(namespace, "ci");
+                assert_eq!(service_account.as_deref(), Some("builder"));
+            }
+            _ => panic!("pipeline should run on kubernetes"),
+        }
+
+        let pipeline = Pipeline::parse("runs-on:\n  kubernetes:\n    image: ubuntu").unwrap();
+        match pipeline.runs_on {
+            RunsOn::Kubernetes {
+                namespace,
+                service_account,
+                ..
+            } => {
+                assert_eq!(namespace, KUBERNETES_DEFAULT_NAMESPACE);
+                assert!(service_account.is_none());
+            }
+            _ => panic!("pipeline should run on kubernetes"),
+        }
+        assert!(Pipeline::parse("runs-on:\n  kubernetes:\n    namespace: ci").is_err());
+        assert!(
+            Pipeline::parse("runs-on:\n  kubernetes:\n    image: ubuntu\n    namespace: ''")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn pipeline_artifact_checksum_is_validated() {
+        let pipeline = Pipeline::parse(
+            r"
+artifacts:
+- method: get
+  from: /app/out.tar
+  to: out.tar
+  checksum: sha256:E3B0C44298FC1C149AFBF4C8996FB92427AE41E4649B934CA495991B7852B855
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            pipeline.artifacts[0].checksum.as_deref(),
+            Some("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+        );
+        assert!(Pipeline::parse("artifacts: [{ checksum: md5:d41d8cd98f00b204 }]").is_err());
+        assert!(Pipeline::parse("artifacts: [{ checksum: sha256:abc123 }]").is_err());
+    }
+
+    #[test]
+    fn pipeline_ssh_machine_is_parsed() {
+        let pipeline = Pipeline::parse(
+            r"
+runs-on:
+  ssh:
+    host: 192.168.1.20:2222
+    user: ci
+    key-file: ~/.ssh/id_ed25519
+",
+        )
+        .unwrap();
+
+        match pipeline.runs_on {
+            RunsOn::Ssh {
+                host,
+                user,
+                key_file,
+            } => {
+                assert_eq!(host, "192.168.1.20:2222");
+                assert_eq!(user, "ci");
+                assert_eq!(key_file, "~/.ssh/id_ed25519");
+            }
+            _ => panic!("pipeline should run on an ssh machine"),
+        }
+        assert!(Pipeline::parse("runs-on:\n  ssh:\n    host: board\n    user: ci").is_err());
+    }
+
+    #[test]
+    fn pipeline_checkpoints_are_allowed_only_on_top_level_steps() {
+        let pipeline = Pipeline::parse(
+            r"
+steps:
+- name: publish
+  exec:
+  - ./publish.sh
+- checkpoint: artifact-published
+",
+        )
+        .unwrap();
+
+        assert_eq!(pipeline.steps[0].checkpoint, None);
+        assert_eq!(
+            pipeline.steps[1].checkpoint,
+            Some("artifact-published".to_string())
+        );
+        assert!(
+            Pipeline::parse("steps:\n- group:\n  - checkpoint: published\n    exec: [ls]").is_err()
+        );
+        assert!(Pipeline::parse("steps:\n- checkpoint: ''").is_err());
+    }
+
+    #[test]
+    fn pipeline_steps_can_ignore_errors_and_always_run() {
+        let pipeline = Pipeline::parse(
+            r"
+steps:
+- name: lint
+  ignore-errors: true
+  exec:
+  - ./lint.sh
+- name: cleanup
+  always-run: true
+  group:
+  - ignore-errors: true
+    exec: [./stop.sh]
+",
+        )
+        .unwrap();
+
+        assert!(pipeline.steps[0].ignore_errors && !pipeline.steps[0].always_run);
+        assert!(pipeline.steps[1].always_run && !pipeline.steps[1].ignore_errors);
+        assert!(pipeline.steps[1].group.as_ref().unwrap().steps[0].ignore_errors);
+        assert!(Pipeline::parse("steps:\n- group:\n  - always-run: true\n    exec: [ls]").is_err());
+    }
+
+    #[test]
+    fn pipeline_steps_with_cyclic_dependencies_are_rejected() {
+        let pipeline = Pipeline::parse(
+            r"
+steps:
+- name: build
+  exec: [echo build]
+- name: test
+  depends-on: [build, package]
+  exec: [echo test]
+- name: package
+  depends-on: [test]
+  exec: [echo package]
+",
+        );
+
+        assert_eq!(
+            pipeline.unwrap_err().to_string(),
+            "cyclic dependency between steps test -> package -> test"
+        );
+        assert!(Pipeline::parse(
+            r"
+steps:
+- name: build
+- name: test
+  depends-on: [build]
+- name: lint
+  depends-on: [build]
+"
+        )
+        .is_ok());
+        assert!(Pipeline::parse("steps:\n- depends-on: [missing]").is_err());
+    }
+
+    #[test]
+    fn pipeline_typed_variables_are_parsed_and_validated() {
+        let pipeline = Pipeline::parse(
+            r"
+environment:
+- RUST_LOG: debug
+- RETRIES:
+    default: 3
+    type: int
+variables:
+- release:
+    default: 'TRUE'
+    type: bool
+- tag: 1
+steps:
+- exec:
+  - echo bld:var:release
+",
+        )
+        .unwrap();
+
+        assert_eq!(pipeline.environment[0].kind, VariableType::String);
+        assert_eq!(pipeline.environment[1].kind, VariableType::Int);
+        assert_eq!(pipeline.environment[1].default_value, "3");
+        assert_eq!(pipeline.variables[0].kind, VariableType::Bool);
+        assert_eq!(pipeline.variables[0].default_value, "true");
+        assert_eq!(pipeline.variables[1].default_value, "1");
+        assert_eq!(
+            pipeline.variables[0]
+                .conform("variable", "yes")
+                .unwrap_err(),
+            InvalidVariableValue {
+                label: "variable".to_string(),
+                name: "release".to_string(),
+                kind: VariableType::Bool,
+                value: "yes".to_string(),
+            }
+        );
+
+        let error =
+            Pipeline::parse("variables:\n- count:\n    default: many\n    type: int").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid value 'many' for variable count, expected int"
+        );
+        assert!(Pipeline::parse("variables:\n- count:\n    default: 1\n    type: float").is_err());
+    }
+
+    #[test]
+    fn pipeline_step_templates_are_expanded() {
+        let src = r"
+step-templates:
+  build-module:
+    params: [dir]
+    working-dir: ${{ param.dir }}
+    exec:
+    - cargo build
+    - echo ${{param.dir}} ${{ vars.dir }}
+steps:
+- use:
+    template: build-module
+    with:
+      dir: payments
+- name: test
+  group:
+  - use:
+      template: build-module
+      with:
+        dir: ledger
+    name: ledger
+";
+        let pipeline = Pipeline::parse(src).unwrap();
+
+        assert_eq!(
+            pipeline.steps[0].name.as_deref(),
+            Some("build-module (payments)")
+        );
+        assert_eq!(pipeline.steps[0].working_dir.as_deref(), Some("payments"));
+        assert_eq!(
+            pipeline.steps[0].commands,
+            vec!["cargo build", "echo payments ${{ vars.dir }}"]
+        );
+        let group = pipeline.steps[1].group.as_ref().unwrap();
+        assert_eq!(group.steps[0].name.as_deref(), Some("ledger"));
+        assert_eq!(group.steps[0].working_dir.as_deref(), Some("ledger"));
+        let resolved = Pipeline::resolve(src).unwrap();
+        assert!(!resolved.contains("step-templates"));
+        assert!(resolved.contains("build-module (payments)"));
+
+        let templates = "step-templates:\n  build:\n    params: [dir]\n    exec:\n    - make ${{ param.dir }}\n";
+        let errors = [
+            (
+                "steps:\n- name: api\n  use:\n    template: build\n",
+                "step api doesn't provide parameter dir of step template build",
+            ),
+            (
+                "steps:\n- use:\n    template: build\n    with: {dir: api, target: x}\n",
+                "step 1 passes parameter target which isn't declared by step template build",
+            ),
+            (
+                "steps:\n- use:\n    template: missing\n",
+                "step 1 uses step template missing which doesn't exist",
+            ),
+        ];
+        for (steps, error) in errors {
+            let error_message = Pipeline::parse(&format!("{templates}{steps}"))
+                .unwrap_err()
+                .to_string();
+            assert_eq!(error_message, error);
+        }
+        assert!(Pipeline::parse(
+            "step-templates:\n  build:\n    exec:\n    - make ${{ param.dir }}\nsteps: []\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn variables_with_secret_names_are_detected() {
+        for name in ["API_KEY", "github_token", "DbPassword", "client-secret"] {
+            assert!(Variable::is_secret_name(name), "{name}");
+        }
+        for name in ["BRANCH", "RUST_LOG", "tag"] {
+            assert!(!Variable::is_secret_name(name), "{name}");
+        }
     }
 }
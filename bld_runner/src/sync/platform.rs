@@ -1,43 +1,144 @@
-use crate::context::{Container, Machine};
-use anyhow::Result;
+use crate::context::{Container, KubernetesPlatform, Machine, SshMachine, StepEnv};
+use crate::Compression;
+use anyhow::{bail, Result};
 use bld_core::execution::Execution;
 use std::sync::{Arc, Mutex};
 
 pub enum TargetPlatform {
     Machine(Box<Machine>),
     Container(Box<Container>),
+    RemoteContainer(Box<Container>),
+    KubernetesPod(Box<KubernetesPlatform>),
+    SshMachine(Box<SshMachine>),
 }
 
 impl TargetPlatform {
-    pub async fn push(&self, from: &str, to: &str) -> Result<()> {
+    pub async fn push(
+        &self,
+        from: &str,
+        to: &str,
+        compression: Compression,
+        zstd_level: i32,
+    ) -> Result<()> {
         match self {
             Self::Machine(machine) => machine.copy_into(from, to),
-            Self::Container(container) => container.copy_into(from, to).await,
+            Self::Container(container) | Self::RemoteContainer(container) => {
+                container.copy_into(from, to, compression, zstd_level).await
+            }
+            Self::KubernetesPod(_) => bail!("artifacts are not supported for kubernetes pods"),
+            Self::SshMachine(machine) => machine.copy_into(from, to, compression, zstd_level).await,
         }
     }
 
-    pub async fn get(&self, from: &str, to: &str) -> Result<()> {
+    pub async fn get(
+        &self,
+        from: &str,
+        to: &str,
+        compression: Compression,
+        zstd_level: i32,
+    ) -> Result<()> {
         match self {
             Self::Machine(machine) => machine.copy_from(from, to),
-            Self::Container(container) => container.copy_from(from, to).await,
+            Self::Container(container) | Self::RemoteContainer(container) => {
+                container.copy_from(from, to, compression, zstd_level).await
+            }
+            Self::KubernetesPod(_) => bail!("artifacts are not supported for kubernetes pods"),
+            Self::SshMachine(machine) => machine.copy_from(from, to, compression, zstd_level).await,
         }
     }
 
+    /// Executes a command on the platform and returns its standard output when it's captured,
+    /// so that the output of a step without an output variable isn't kept in memory. The output
+    /// of kubernetes pods and ssh machines is merged with the standard error, so it's never
+    /// captured.
+    #[allow(clippy::too_many_arguments)]
     pub async fn shell(
         &self,
         working_dir: &Option<String>,
         command: &str,
+        prefix: &Option<String>,
+        ssh_agent_forwarding: bool,
+        capture_stdout: bool,
+        env: &StepEnv,
         exec: Arc<Mutex<Execution>>,
-    ) -> Result<()> {
+    ) -> Result<String> {
+        match self {
+            Self::Container(_) | Self::RemoteContainer(_) => {}
+            _ if !env.inherit_container_env => {
+                bail!("inherit-container-env can be disabled only for steps that run on docker")
+            }
+            _ => {}
+        }
+        match self {
+            Self::Machine(machine) => {
+                machine
+                    .sh(
+                        working_dir,
+                        command,
+                        prefix,
+                        ssh_agent_forwarding,
+                        capture_stdout,
+                        env,
+                        exec,
+                    )
+                    .await
+            }
+            Self::Container(container) | Self::RemoteContainer(container) => {
+                container
+                    .sh(
+                        working_dir,
+                        command,
+                        prefix,
+                        ssh_agent_forwarding,
+                        capture_stdout,
+                        env,
+                        exec,
+                    )
+                    .await
+            }
+            Self::KubernetesPod(pod) => {
+                pod.sh(
+                    working_dir,
+                    command,
+                    prefix,
+                    ssh_agent_forwarding,
+                    env,
+                    exec,
+                )
+                .await?;
+                Ok(String::new())
+            }
+            Self::SshMachine(machine) => {
+                machine
+                    .sh(
+                        working_dir,
+                        command,
+                        prefix,
+                        ssh_agent_forwarding,
+                        env,
+                        exec,
+                    )
+                    .await?;
+                Ok(String::new())
+            }
+        }
+    }
+
+    /// Removes the credentials of the run from the platform, which is done regardless of
+    /// whether the platform is disposed or kept alive.
+    pub async fn remove_credentials(&self) -> Result<()> {
         match self {
-            Self::Machine(machine) => machine.sh(working_dir, command).await,
-            Self::Container(container) => container.sh(working_dir, command, exec).await,
+            Self::Machine(machine) => machine.remove_credentials(),
+            Self::Container(container) | Self::RemoteContainer(container) => {
+                container.remove_credentials().await
+            }
+            _ => Ok(()),
         }
     }
 
     pub fn keep_alive(&self) -> Result<()> {
         match self {
-            Self::Container(container) => container.keep_alive(),
+            Self::Container(container) | Self::RemoteContainer(container) => container.keep_alive(),
             _ => Ok(()),
         }
     }
@@ -47,7 +148,11 @@ impl TargetPlatform {
             // checking if the runner is a child in order to not cleanup the temp dir for the whole run
             Self::Machine(machine) if !in_child_runner => machine.dispose(),
             Self::Machine(_) => Ok(()),
-            Self::Container(container) => container.dispose().await,
+            Self::Container(container) | Self::RemoteContainer(container) => {
+                container.dispose().await
+            }
+            Self::KubernetesPod(pod) => pod.dispose().await,
+            Self::SshMachine(machine) => machine.dispose(in_child_runner).await,
         }
     }
 }
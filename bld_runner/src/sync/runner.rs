@@ -1,29 +1,62 @@
-use crate::{BuildStep, Container, Machine, Pipeline, RunsOn, TargetPlatform};
+use crate::sync::expression::evaluate;
+use crate::sync::notifications::{notify, RunReport};
+use crate::sync::substitution::{protect_escaped, restore_escaped, unresolved_token};
+use crate::sync::telemetry::{persist_trace_id, traced, AtomicTracer};
+use crate::sync::template::RenderedTemplate;
+use crate::{
+    docker_counters, BuildStep, Container, DockerOptions, KubernetesPlatform, Machine,
+    MachineCgroup, Notification, NotificationTarget, NotifyOn, Pipeline, RegistryCredentials,
+    RegistrySecret, Rollup, RunsOn, SshMachine, StepEnv, StepGroup, TargetPlatform,
+    TransferProgress, TransferScheduler, Variable, VariableType,
+};
 use anyhow::{anyhow, bail, Result};
 use bld_config::definitions::{
-    ENV_TOKEN, GET, PUSH, RUN_PROPS_ID, RUN_PROPS_START_TIME, VAR_TOKEN,
+    ENV_TOKEN, GET, LOCAL_MACHINE_TMP_DIR, PUSH, RUN_PROPS_ID, RUN_PROPS_START_TIME, SECRET_TOKEN,
+    TEMPLATE, VAR_TOKEN,
+};
+use bld_config::{
+    image_registry, path, BldConfig, BldCredentialConfig, BldLocalConfig, BldRegistryAuth,
+    LongLogLines,
 };
-use bld_config::BldConfig;
 use bld_core::context::Context;
-use bld_core::execution::Execution;
-use bld_core::logger::Logger;
+use bld_core::database::pipeline_runs::{PR_STATE_FAULTED, PR_STATE_FINISHED};
+use bld_core::execution::{command_preview, CommandTiming, Execution};
+use bld_core::logger::{matrix_log_name, Logger};
 use bld_core::proxies::PipelineFileSystemProxy;
 use bld_supervisor::base::WorkerMessages;
+use bld_utils::term::format_duration;
 use chrono::offset::Local;
-use std::collections::HashMap;
+use futures::future::join_all;
+use futures::stream::{FuturesUnordered, StreamExt};
+use opentelemetry::{Context as TraceContext, KeyValue};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::env::{current_dir, join_paths, split_paths};
+use std::fmt::{self, Display, Formatter};
+use std::fs::{canonicalize, remove_dir_all, File};
 use std::future::Future;
+use std::io;
+use std::path::{Component, Path, PathBuf};
 use std::pin::Pin;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc::Sender;
+use tokio::sync::OnceCell;
+use tokio::time::{sleep, timeout};
+use tracing::{debug, error};
 use uuid::Uuid;
 
 type RecursiveFuture = Pin<Box<dyn Future<Output = Result<()>>>>;
 type AtomicExec = Arc<Mutex<Execution>>;
 type AtomicLog = Arc<Mutex<Logger>>;
 type AtomicVars = Arc<HashMap<String, String>>;
+type AtomicMutVars = Arc<Mutex<HashMap<String, String>>>;
 type AtomicProxy = Arc<PipelineFileSystemProxy>;
 type AtomicContext = Arc<Mutex<Context>>;
+type AtomicTransfers = Arc<TransferScheduler>;
+type AtomicArtifacts = Arc<Mutex<Vec<String>>>;
 
+#[derive(Clone)]
 pub struct RunnerBuilder {
     run_id: String,
     run_start_time: String,
@@ -35,8 +68,17 @@ pub struct RunnerBuilder {
     ipc: Arc<Option<Sender<WorkerMessages>>>,
     env: Option<AtomicVars>,
     vars: Option<AtomicVars>,
+    secrets: AtomicVars,
     context: AtomicContext,
+    transfers: Option<AtomicTransfers>,
+    artifacts: AtomicArtifacts,
     is_child: bool,
+    callers: Vec<String>,
+    dry_run: bool,
+    matrix_index: Option<usize>,
+    machine_id: Option<String>,
+    tracer: Option<AtomicTracer>,
+    trace_parent: Option<TraceContext>,
 }
 
 impl Default for RunnerBuilder {
@@ -52,8 +94,17 @@ impl Default for RunnerBuilder {
             ipc: Arc::new(None),
             env: None,
             vars: None,
+            secrets: Arc::new(HashMap::new()),
             context: Arc::new(Mutex::new(Context::Empty)),
+            transfers: None,
+            artifacts: Arc::new(Mutex::new(vec![])),
             is_child: false,
+            callers: vec![],
+            dry_run: false,
+            matrix_index: None,
+            machine_id: None,
+            tracer: None,
+            trace_parent: None,
         }
     }
 }
@@ -109,16 +160,191 @@ impl RunnerBuilder {
         self
     }
 
+    pub fn secrets(mut self, secrets: AtomicVars) -> Self {
+        self.secrets = secrets;
+        self
+    }
+
     pub fn context(mut self, context: AtomicContext) -> Self {
         self.context = context;
         self
     }
 
+    pub fn transfers(mut self, transfers: AtomicTransfers) -> Self {
+        self.transfers = Some(transfers);
+        self
+    }
+
+    /// The paths that the `get` artifacts of the run, including its called pipelines and matrix
+    /// legs, are copied to, which are collected for the caller to use after the run.
+    pub fn artifacts(mut self, artifacts: AtomicArtifacts) -> Self {
+        self.artifacts = artifacts;
+        self
+    }
+
     pub fn is_child(mut self, is_child: bool) -> Self {
         self.is_child = is_child;
         self
     }
 
+    /// The pipelines that called this pipeline, from the root of the run, in order to detect
+    /// calls that form a cycle.
+    pub fn callers(mut self, callers: Vec<String>) -> Self {
+        self.callers = callers;
+        self
+    }
+
+    /// Logs the commands of the steps and the artifacts of the run instead of executing them.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn matrix_index(mut self, index: usize) -> Self {
+        self.matrix_index = Some(index);
+        self
+    }
+
+    /// The id of the temp dir of the machine, so that a called pipeline runs in the same
+    /// directory as its caller. Defaults to the run id, or the run id and the index of the
+    /// leg for the runners of a matrix.
+    pub fn machine_id(mut self, id: &str) -> Self {
+        self.machine_id = Some(String::from(id));
+        self
+    }
+
+    /// The tracer that the spans of the run, its steps and their commands are created with.
+    /// No spans are created without a tracer.
+    pub fn tracer(mut self, tracer: Option<AtomicTracer>) -> Self {
+        self.tracer = tracer;
+        self
+    }
+
+    /// The context of the span that the span of the run is a child of, such as the span of
+    /// the request that started the run. The current context is used if one isn't provided.
+    pub fn trace_parent(mut self, cx: Option<TraceContext>) -> Self {
+        self.trace_parent = cx;
+        self
+    }
+
+    /// Loads the environment file of a pipeline, resolving its path relative to the
+    /// directory that the pipeline is stored in.
+    fn environment_file(
+        prx: &PipelineFileSystemProxy,
+        pip_name: &str,
+        file: &str,
+    ) -> Result<HashMap<String, String>> {
+        let pip_path = prx.path(pip_name)?;
+        let path = match pip_path.parent() {
+            Some(parent) => parent.join(file),
+            None => PathBuf::from(file),
+        };
+        debug!("loading environment file {}", path.display());
+        let mut env = HashMap::new();
+        for entry in dotenvy::from_path_iter(&path)
+            .map_err(|e| anyhow!("unable to load environment file {file}, {e}"))?
+        {
+            let (name, value) = entry?;
+            env.insert(name, value);
+        }
+        Ok(env)
+    }
+
+    /// Applies the server limits to the docker options of the pipeline. The oom score is raised to
+    /// the configured minimum, the resources are lowered to the configured maximum and disabling the
+    /// oom killer is allowed only for allowlisted pipelines. Host paths are mounted only when they
+    /// are under one of the allowlisted paths. The credentials of the registry referenced by the
+    /// pipeline, or else of the configured registry that the image belongs to, are used for
+    /// pulling it and the ssh agent socket of the host is mounted when a step of the pipeline
+    /// forwards it.
+    fn docker_options(
+        cfg: &BldConfig,
+        pip_name: &str,
+        image: &str,
+        options: &DockerOptions,
+        ssh_agent_forwarding: bool,
+    ) -> Result<DockerOptions> {
+        let oom_score_adj = match (options.oom_score_adj, cfg.local.container_min_oom_score) {
+            (Some(score), Some(min)) => Some(score.max(min)),
+            (score, min) => score.or(min),
+        };
+        let allowlist = &cfg.local.container_oom_kill_disable_allowlist;
+        if options.oom_kill_disable && !allowlist.iter().any(|p| p == pip_name) {
+            bail!("pipeline {pip_name} is not allowed to disable the oom killer of its container");
+        }
+        for volume in options.volumes.iter() {
+            if !volume_allowed(volume, &cfg.local.container_volumes_allowlist) {
+                bail!("volume {volume} of pipeline {pip_name} is not in the volumes allowlist");
+            }
+        }
+        let mut docker_options = DockerOptions::new(
+            oom_score_adj,
+            options.oom_kill_disable,
+            options.volumes.clone(),
+            options.network.clone(),
+        );
+        docker_options.host = options.host.clone();
+        let limits = &cfg.local.container_resources;
+        docker_options.resources = options.resources.within(&limits.default, &limits.max);
+        let registries = &cfg.local.registries;
+        let registry = match &options.registry_name {
+            Some(name) => Some(
+                registries
+                    .iter()
+                    .find(|r| r.name.as_ref() == Some(name))
+                    .ok_or_else(|| anyhow!("registry {name} not found in the config"))?,
+            ),
+            None => {
+                let address = image_registry(image);
+                registries.iter().find(|r| r.address() == address)
+            }
+        };
+        if let Some(config) = registry {
+            debug!(
+                "using the credentials of registry {} for image {image}",
+                config.label()
+            );
+            let secret = match &config.auth {
+                BldRegistryAuth::Password { username, .. } => RegistrySecret::Password {
+                    username: username.to_string(),
+                    password: config.secret()?,
+                },
+                BldRegistryAuth::Token { .. } => RegistrySecret::Token(config.secret()?),
+            };
+            docker_options.registry = Some(RegistryCredentials::new(
+                config.label(),
+                config.address(),
+                secret,
+            ));
+        }
+        if ssh_agent_forwarding {
+            let socket = std::env::var("SSH_AUTH_SOCK").map_err(|_| {
+                anyhow!("unable to forward the ssh agent since SSH_AUTH_SOCK is not set")
+            })?;
+            docker_options.ssh_agent_socket = Some(socket);
+        }
+        Ok(docker_options)
+    }
+
+    /// Masks the values with names that look like secrets. Values declared as int or bool
+    /// aren't masked since that would mask every occurrence of a number or a boolean.
+    fn mask_secret_variables(
+        logger: &mut Logger,
+        declared: &[Variable],
+        values: &HashMap<String, String>,
+    ) {
+        for (name, value) in values.iter() {
+            let kind = declared
+                .iter()
+                .find(|v| &v.name == name)
+                .map(|v| v.kind)
+                .unwrap_or_default();
+            if Variable::is_secret_name(name) && kind == VariableType::String {
+                logger.add_mask(value);
+            }
+        }
+    }
+
     pub async fn build(self) -> Result<Runner> {
         let cfg = self
             .cfg
@@ -128,84 +354,262 @@ impl RunnerBuilder {
         let env = self
             .env
             .ok_or_else(|| anyhow!("no environment instance provided"))?;
-        let env: Arc<HashMap<String, String>> = Arc::new(
-            pipeline
-                .environment
-                .iter()
-                .map(|e| {
-                    (
-                        e.name.to_string(),
-                        env.get(&e.name).unwrap_or(&e.default_value).to_string(),
-                    )
-                })
-                .collect(),
-        );
+        let mut env_with_file = match &pipeline.environment_file {
+            Some(file) => Self::environment_file(&self.prx, &pip_name, file)?,
+            None => HashMap::new(),
+        };
+        for e in pipeline.environment.iter() {
+            env_with_file.insert(e.name.to_string(), e.default_value.to_string());
+        }
+        // only the variables of the host that are explicitly listed are passed to the pipeline.
+        for name in pipeline.env_passthrough.iter() {
+            if let Ok(value) = std::env::var(name) {
+                env_with_file.insert(name.to_string(), value);
+            }
+        }
+        let mut env: HashMap<String, String> = env_with_file
+            .into_iter()
+            .map(|(name, value)| {
+                let value = env.get(&name).cloned().unwrap_or(value);
+                (name, value)
+            })
+            .collect();
+        // the values are checked against the declared types before any platform is created.
+        for e in pipeline.environment.iter() {
+            if let Some(value) = env.get_mut(&e.name) {
+                *value = e.conform(Variable::label("environment"), value)?;
+            }
+        }
+        let env = Arc::new(env);
         let vars = self
             .vars
             .ok_or_else(|| anyhow!("no variables instance provided"))?;
-        let vars: Arc<HashMap<String, String>> = Arc::new(
-            pipeline
-                .variables
-                .iter()
-                .map(|v| {
-                    (
-                        v.name.to_string(),
-                        vars.get(&v.name).unwrap_or(&v.default_value).to_string(),
-                    )
-                })
-                .collect(),
-        );
-        let platform = match &pipeline.runs_on {
-            RunsOn::Machine => {
-                let machine = Machine::new(&self.run_id, env.clone(), self.lg.clone())?;
-                TargetPlatform::Machine(Box::new(machine))
-            }
-            RunsOn::Docker(img) => {
-                let container = Container::new(
-                    img,
-                    cfg.clone(),
-                    env.clone(),
-                    self.lg.clone(),
-                    self.context.clone(),
+        let mut vars: HashMap<String, String> = pipeline
+            .variables
+            .iter()
+            .map(|v| {
+                (
+                    v.name.to_string(),
+                    vars.get(&v.name).unwrap_or(&v.default_value).to_string(),
                 )
-                .await?;
-                TargetPlatform::Container(Box::new(container))
+            })
+            .collect();
+        let mut matrix = None;
+        let mut lg = self.lg;
+        if let Some(index) = self.matrix_index {
+            let combination = pipeline
+                .matrix_combinations()
+                .into_iter()
+                .nth(index)
+                .ok_or_else(|| anyhow!("invalid matrix index {index} for pipeline {pip_name}"))?;
+            let label = Pipeline::matrix_label(&combination);
+            vars.extend(combination);
+            matrix = Some((index, label));
+            let is_file = lg.lock().unwrap().is_file();
+            if is_file {
+                lg = Logger::file_atom(cfg.clone(), &matrix_log_name(&self.run_id, index))?;
+            }
+        }
+        for v in pipeline.variables.iter() {
+            if let Some(value) = vars.get_mut(&v.name) {
+                *value = v.conform(Variable::label("variables"), value)?;
+            }
+        }
+        // opting out of the log redaction rules is controlled by the server config and
+        // applies to the whole run, including any called pipelines.
+        let redaction_allowlist = &cfg.local.log_redaction_allowlist;
+        if !self.is_child && redaction_allowlist.iter().any(|p| p == &pip_name) {
+            lg.lock().unwrap().disable_redaction();
+        }
+        // the logger is shared by the called pipelines so the secrets are masked once for the whole run.
+        if !self.is_child {
+            lg.lock().unwrap().mask_secrets(self.secrets.values());
+        }
+        // the values of the variables that look like secrets are masked as well.
+        {
+            let mut logger = lg.lock().unwrap();
+            Self::mask_secret_variables(&mut logger, &pipeline.environment, &env);
+            Self::mask_secret_variables(&mut logger, &pipeline.variables, &vars);
+        }
+        // the line limit of the pipeline overrides the default of the server config for the whole run.
+        if let (false, Some(max)) = (self.is_child, pipeline.max_log_lines) {
+            lg.lock().unwrap().max_lines(max);
+        }
+        let vars = Arc::new(Mutex::new(vars));
+        let docker_options = match &pipeline.runs_on {
+            RunsOn::Docker(image, options) | RunsOn::DockerRemote(image, options) => {
+                Self::docker_options(
+                    &cfg,
+                    &pip_name,
+                    image,
+                    options,
+                    pipeline.forwards_ssh_agent(),
+                )?
+            }
+            RunsOn::Machine | RunsOn::Kubernetes { .. } | RunsOn::Ssh { .. } => {
+                DockerOptions::default()
             }
         };
+        let transfers = self
+            .transfers
+            .unwrap_or_else(|| TransferScheduler::atom(cfg.local.artifacts_concurrent_transfers));
+        let machine_id = match (self.machine_id, self.matrix_index) {
+            (Some(id), _) => id,
+            (None, Some(index)) => machine_leg_id(&self.run_id, index),
+            (None, None) => self.run_id.clone(),
+        };
         Ok(Runner {
             run_id: self.run_id,
+            machine_id,
             run_start_time: self.run_start_time,
             cfg,
             ex: self.ex,
-            lg: self.lg,
+            lg,
             prx: self.prx,
+            pip_name,
             pip: pipeline,
             ipc: self.ipc,
             env,
             vars,
+            secrets: self.secrets,
             context: self.context,
-            platform,
+            transfers,
+            artifacts: self.artifacts,
+            platform: OnceCell::new(),
+            docker_options,
             is_child: self.is_child,
+            callers: self.callers,
+            dry_run: self.dry_run,
+            matrix,
             has_faulted: false,
+            calls: Mutex::new(vec![]),
+            tracer: self.tracer,
+            trace_parent: self.trace_parent,
+        })
+    }
+
+    /// Builds a runner for each combination of the pipeline matrix, or a single runner if the
+    /// pipeline doesn't have a matrix. The matrix of called pipelines is not expanded.
+    pub async fn build_matrix(mut self) -> Result<MatrixRunner> {
+        // the runners of the matrix share the transfer scheduler so that the limit applies to the whole run.
+        if let (None, Some(cfg)) = (&self.transfers, &self.cfg) {
+            self.transfers = Some(TransferScheduler::atom(
+                cfg.local.artifacts_concurrent_transfers,
+            ));
+        }
+        let pip_name = self
+            .pip
+            .as_ref()
+            .ok_or_else(|| anyhow!("no pipeline provided"))?;
+        let pipeline = Pipeline::parse(&self.prx.read(pip_name)?)?;
+        // the runners are started in the context of the matrix runner, which is a child of the parent.
+        let trace_parent = self.trace_parent.take();
+        // the main logger of a matrix run is separate from the loggers of its legs.
+        self.lg.lock().unwrap().mask_secrets(self.secrets.values());
+        let combinations = pipeline.matrix_combinations().len();
+        let mut runners = vec![];
+        if combinations == 0 {
+            runners.push(self.clone().build().await?);
+        }
+        for index in 0..combinations {
+            runners.push(self.clone().matrix_index(index).build().await?);
+        }
+        // the called pipelines and the legs of a matrix are executed by the same process, so
+        // they share the cgroup of the run.
+        let cgroup = match &pipeline.cgroup {
+            Some(limits) => MachineCgroup::create(&self.run_id, limits)?,
+            None => None,
+        };
+        let notifications = runners
+            .first()
+            .map(|r| r.notifications())
+            .unwrap_or_default();
+        Ok(MatrixRunner {
+            run_id: self.run_id,
+            cfg: self.cfg,
+            pip_name: pip_name.to_string(),
+            notifications,
+            ex: self.ex,
+            lg: self.lg,
+            ipc: self.ipc,
+            prx: self.prx,
+            dispose_machine: pipeline.dispose && matches!(pipeline.runs_on, RunsOn::Machine),
+            cgroup,
+            runners,
+            tracer: self.tracer,
+            trace_parent,
         })
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+enum CallState {
+    Finished,
+    Faulted,
+    Cancelled,
+}
+
+impl Display for CallState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Finished => write!(f, "{PR_STATE_FINISHED}"),
+            Self::Faulted => write!(f, "{PR_STATE_FAULTED}"),
+            Self::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+/// The contribution of a called pipeline to the final state of its parent.
+struct CallSummary {
+    pipeline: String,
+    state: CallState,
+    rollup: Rollup,
+}
+
+impl CallSummary {
+    fn new(pipeline: String, state: CallState, rollup: Rollup) -> Self {
+        Self {
+            pipeline,
+            state,
+            rollup,
+        }
+    }
+
+    fn effective_state(&self) -> CallState {
+        match (self.state, self.rollup) {
+            (CallState::Faulted, Rollup::Ignore | Rollup::Warn) => CallState::Finished,
+            (state, _) => state,
+        }
+    }
+}
+
 pub struct Runner {
     run_id: String,
+    machine_id: String,
     run_start_time: String,
     cfg: Arc<BldConfig>,
     ex: AtomicExec,
     lg: AtomicLog,
     prx: AtomicProxy,
+    pip_name: String,
     pip: Pipeline,
     ipc: Arc<Option<Sender<WorkerMessages>>>,
     env: AtomicVars,
-    vars: AtomicVars,
+    vars: AtomicMutVars,
+    secrets: AtomicVars,
     context: AtomicContext,
-    platform: TargetPlatform,
+    transfers: AtomicTransfers,
+    artifacts: AtomicArtifacts,
+    platform: OnceCell<TargetPlatform>,
+    docker_options: DockerOptions,
     is_child: bool,
+    callers: Vec<String>,
+    dry_run: bool,
+    matrix: Option<(usize, String)>,
     has_faulted: bool,
+    calls: Mutex<Vec<CallSummary>>,
+    tracer: Option<AtomicTracer>,
+    trace_parent: Option<TraceContext>,
 }
 
 impl Runner {
@@ -216,26 +620,188 @@ impl Runner {
 
     async fn exec_persist_start(&self) {
         let mut exec = self.ex.lock().unwrap();
-        if !self.is_child {
+        if !self.is_child && self.matrix.is_none() {
             let _ = exec.set_as_running();
         }
     }
 
     async fn exec_persist_end(&self) -> Result<()> {
-        if !self.is_child {
-            let mut exec = self.ex.lock().unwrap();
-            let _ = if self.has_faulted {
-                exec.set_as_faulted()
-            } else {
-                exec.set_as_finished()
-            };
+        if !self.is_child && self.matrix.is_none() {
+            {
+                let mut exec = self.ex.lock().unwrap();
+                let _ = if self.has_faulted {
+                    exec.set_as_faulted()
+                } else {
+                    exec.set_as_finished()
+                };
+                exec.reconcile();
+            }
+            // removes the clone of a pipeline run from a git repository.
+            self.prx.dispose()?;
         }
+        let platform = match self.platform.get() {
+            Some(platform) => platform,
+            None => return Ok(()),
+        };
+        // the credentials are removed even when the platform is kept alive.
+        let removed = platform.remove_credentials().await;
         if self.pip.dispose {
-            self.platform.dispose(self.is_child).await?;
+            // the temp dir of a machine is shared with the called pipelines, so it's removed
+            // by the runner that created it.
+            platform.dispose(self.is_child).await?;
         } else {
-            self.platform.keep_alive()?;
+            platform.keep_alive()?;
         }
-        Ok(())
+        removed
+    }
+
+    /// The platform is created when it's first needed, so that a run whose steps are all
+    /// skipped never creates the temp dir of the machine or the container.
+    async fn platform(&self) -> Result<&TargetPlatform> {
+        self.platform
+            .get_or_try_init(|| async {
+                let path_prepend = self.path_prepend()?;
+                let mut docker_options = self.docker_options.clone();
+                docker_options.path_prepend = path_prepend.clone();
+                let credentials = self.credentials()?;
+                let platform = match &self.pip.runs_on {
+                    RunsOn::Machine => {
+                        let env = self.machine_env(&path_prepend)?;
+                        let mut machine = Machine::new(
+                            &self.machine_id,
+                            env,
+                            self.lg.clone(),
+                            self.cfg.local.step_graceful_shutdown_secs,
+                        )?;
+                        if !credentials.is_empty() {
+                            machine.install_credentials(&credentials)?;
+                        }
+                        TargetPlatform::Machine(Box::new(machine))
+                    }
+                    RunsOn::Docker(img, _) => {
+                        let mut container = Container::new(
+                            img,
+                            docker_options.clone(),
+                            self.cfg.clone(),
+                            self.env.clone(),
+                            self.lg.clone(),
+                            self.context.clone(),
+                        )
+                        .await?;
+                        if !credentials.is_empty() {
+                            container.install_credentials(&credentials).await?;
+                        }
+                        TargetPlatform::Container(Box::new(container))
+                    }
+                    RunsOn::DockerRemote(img, _) => {
+                        let mut container = Container::new(
+                            img,
+                            docker_options.clone(),
+                            self.cfg.clone(),
+                            self.env.clone(),
+                            self.lg.clone(),
+                            self.context.clone(),
+                        )
+                        .await?;
+                        if !credentials.is_empty() {
+                            container.install_credentials(&credentials).await?;
+                        }
+                        TargetPlatform::RemoteContainer(Box::new(container))
+                    }
+                    RunsOn::Kubernetes {
+                        image,
+                        namespace,
+                        service_account,
+                    } => {
+                        let pod = KubernetesPlatform::new(
+                            image,
+                            namespace,
+                            service_account,
+                            path_prepend,
+                            self.env.clone(),
+                            self.lg.clone(),
+                        )
+                        .await?;
+                        TargetPlatform::KubernetesPod(Box::new(pod))
+                    }
+                    RunsOn::Ssh {
+                        host,
+                        user,
+                        key_file,
+                    } => {
+                        let machine = SshMachine::new(
+                            &self.machine_id,
+                            host,
+                            user,
+                            key_file,
+                            path_prepend,
+                            self.env.clone(),
+                            self.lg.clone(),
+                        )
+                        .await?;
+                        TargetPlatform::SshMachine(Box::new(machine))
+                    }
+                };
+                if !credentials.is_empty() {
+                    let names: Vec<&str> = credentials.iter().map(|c| c.name.as_str()).collect();
+                    // the tokens are masked in case a step prints them.
+                    let tokens: Vec<String> =
+                        credentials.iter().filter_map(|c| c.token().ok()).collect();
+                    let mut logger = self.lg.lock().unwrap();
+                    logger.mask_secrets(tokens.iter());
+                    logger.infoln(&format!("[bld] Credentials: {}", names.join(", ")));
+                }
+                Ok(platform)
+            })
+            .await
+    }
+
+    /// The credentials of the server that the pipeline references, which are supported only
+    /// on a machine and on docker.
+    fn credentials(&self) -> Result<Vec<&BldCredentialConfig>> {
+        if self.pip.credentials.is_empty() {
+            return Ok(vec![]);
+        }
+        if matches!(
+            self.pip.runs_on,
+            RunsOn::Kubernetes { .. } | RunsOn::Ssh { .. }
+        ) {
+            bail!(
+                "credentials are supported only for pipelines that run on a machine or on docker"
+            );
+        }
+        pipeline_credentials(&self.cfg.local, &self.pip_name, &self.pip.credentials)
+    }
+
+    /// The directories that are prepended to the PATH of the steps, with the context applied.
+    fn path_prepend(&self) -> Result<Vec<String>> {
+        self.pip
+            .path_prepend
+            .iter()
+            .map(|dir| self.apply_context_strict(dir, &None, "path-prepend"))
+            .collect()
+    }
+
+    /// The environment of the machine with the directories of path-prepend added before
+    /// the PATH of the environment or of the host.
+    fn machine_env(&self, path_prepend: &[String]) -> Result<AtomicVars> {
+        if path_prepend.is_empty() {
+            return Ok(self.env.clone());
+        }
+        let original = self
+            .env
+            .get("PATH")
+            .cloned()
+            .or_else(|| std::env::var("PATH").ok())
+            .unwrap_or_default();
+        let dirs = path_prepend
+            .iter()
+            .map(PathBuf::from)
+            .chain(split_paths(&original));
+        let path = join_paths(dirs)?.to_string_lossy().to_string();
+        let mut env = (*self.env).clone();
+        env.insert("PATH".to_string(), path);
+        Ok(Arc::new(env))
     }
 
     fn exec_check_stop_signal(&self) -> Result<()> {
@@ -244,7 +810,7 @@ impl Runner {
     }
 
     async fn ipc_send_completed(&self) -> Result<()> {
-        if !self.is_child {
+        if !self.is_child && self.matrix.is_none() {
             if let Some(ipc) = Option::as_ref(&self.ipc) {
                 ipc.send(WorkerMessages::Completed).await?;
             }
@@ -258,6 +824,14 @@ impl Runner {
             logger.dumpln(&format!("[bld] Pipeline: {name}"));
         }
         logger.dumpln(&format!("[bld] Runs on: {}", self.pip.runs_on));
+        if let Some((_, label)) = &self.matrix {
+            logger.dumpln(&format!("[bld] Matrix: {label}"));
+        }
+        if self.dry_run && !self.is_child {
+            logger.dumpln(
+                "[bld] DRY-RUN, the commands and artifacts of the pipeline won't be executed",
+            );
+        }
     }
 
     fn apply_run_properties(&self, txt: &str) -> String {
@@ -282,7 +856,7 @@ impl Runner {
 
     fn apply_variables(&self, txt: &str) -> String {
         let mut txt_with_vars = String::from(txt);
-        for (key, value) in self.vars.iter() {
+        for (key, value) in self.vars.lock().unwrap().iter() {
             let full_name = format!("{VAR_TOKEN}{key}");
             txt_with_vars = txt_with_vars.replace(&full_name, value);
         }
@@ -293,96 +867,832 @@ impl Runner {
         txt_with_vars
     }
 
-    fn apply_context(&self, txt: &str) -> String {
-        let txt = self.apply_run_properties(txt);
+    fn apply_secrets(&self, txt: &str) -> String {
+        let mut txt_with_secrets = String::from(txt);
+        for (key, value) in self.secrets.iter() {
+            let full_name = format!("{SECRET_TOKEN}{key}");
+            txt_with_secrets = txt_with_secrets.replace(&full_name, value);
+        }
+        txt_with_secrets
+    }
+
+    /// Substitutes the tokens of the text, leaving the escaped tokens protected. The secrets
+    /// are applied last so that their values are never substituted further.
+    fn substitute(&self, txt: &str) -> String {
+        let txt = protect_escaped(txt);
+        let txt = self.apply_run_properties(&txt);
         let txt = self.apply_environment(&txt);
-        self.apply_variables(&txt)
+        let txt = self.apply_variables(&txt);
+        self.apply_secrets(&txt)
+    }
+
+    fn apply_context(&self, txt: &str) -> String {
+        restore_escaped(&self.substitute(txt))
     }
 
+    fn is_strict_substitution(&self) -> bool {
+        self.pip.strict_substitution || self.cfg.local.strict_substitution
+    }
+
+    /// Applies the context to a field of a step and when strict substitution is enabled,
+    /// fails if any token of the field was left unresolved.
+    fn apply_context_strict(
+        &self,
+        txt: &str,
+        step: &Option<String>,
+        field: &str,
+    ) -> Result<String> {
+        let txt = self.substitute(txt);
+        if self.is_strict_substitution() {
+            if let Some(token) = unresolved_token(&txt) {
+                let location = match step {
+                    Some(name) => format!("step {name}"),
+                    None => "pipeline".to_string(),
+                };
+                bail!("unresolved token {token} in the {field} of {location}");
+            }
+        }
+        Ok(restore_escaped(&txt))
+    }
+
+    /// The notifications of the pipeline with the context applied to their targets.
+    fn notifications(&self) -> Vec<Notification> {
+        self.pip
+            .notifications
+            .iter()
+            .map(|n| {
+                let target = match &n.target {
+                    NotificationTarget::Slack { webhook_url } => NotificationTarget::Slack {
+                        webhook_url: self.apply_context(webhook_url),
+                    },
+                    NotificationTarget::Email { to } => NotificationTarget::Email {
+                        to: to.iter().map(|t| self.apply_context(t)).collect(),
+                    },
+                    NotificationTarget::Webhook { url } => NotificationTarget::Webhook {
+                        url: self.apply_context(url),
+                    },
+                };
+                Notification::new(target, n.on.clone())
+            })
+            .collect()
+    }
+
+    fn log_transfer(&self, from: &str, to: &str, progress: TransferProgress) {
+        let message = match progress {
+            TransferProgress::Queued => "waiting for a transfer slot".to_string(),
+            TransferProgress::Started => "started".to_string(),
+            TransferProgress::Finished(elapsed) => {
+                format!("finished in {:.2}s", elapsed.as_secs_f64())
+            }
+            TransferProgress::Failed(elapsed) => {
+                format!("failed after {:.2}s", elapsed.as_secs_f64())
+            }
+        };
+        let mut logger = self.lg.lock().unwrap();
+        logger.dumpln(&format!("[bld] Transfer {from} -> {to}: {message}"));
+    }
+
+    /// Transfers the artifacts that run after the step concurrently through the transfer
+    /// scheduler of the run, which limits the number of transfers that are in flight.
     async fn artifacts(&self, name: &Option<String>) -> Result<()> {
+        let mut transfers = vec![];
         for artifact in self.pip.artifacts.iter().filter(|a| &a.after == name) {
             let can_continue = (artifact.method == Some(PUSH.to_string())
-                || artifact.method == Some(GET.to_string()))
+                || artifact.method == Some(GET.to_string())
+                || artifact.method == Some(TEMPLATE.to_string()))
                 && artifact.from.is_some()
                 && artifact.to.is_some();
             if can_continue {
                 let method = self.apply_context(artifact.method.as_ref().unwrap());
-                let from = self.apply_context(artifact.from.as_ref().unwrap());
-                let to = self.apply_context(artifact.to.as_ref().unwrap());
+                let from = self.apply_context_strict(
+                    artifact.from.as_ref().unwrap(),
+                    name,
+                    "artifact from",
+                )?;
+                let to =
+                    self.apply_context_strict(artifact.to.as_ref().unwrap(), name, "artifact to")?;
+                transfers.push((artifact, method, from, to));
+            }
+        }
+        if transfers.is_empty() {
+            return Ok(());
+        }
+        if self.dry_run {
+            let mut logger = self.lg.lock().unwrap();
+            for (_, method, from, to) in transfers.iter() {
+                logger.dumpln(&format!(
+                    "[bld] DRY-RUN would {method} artifacts from: {from} to: {to}"
+                ));
+            }
+            return Ok(());
+        }
+
+        let platform = self.platform().await?;
+        let zstd_level = self.cfg.local.artifacts_zstd_level;
+        let futures = transfers
+            .iter()
+            .map(|(artifact, method, from, to)| async move {
                 {
                     let mut logger = self.lg.lock().unwrap();
                     logger.dumpln(&format!(
                         "[bld] Copying artifacts from: {from} into container to: {to}",
                     ));
                 }
-                let result = match &method[..] {
-                    PUSH => self.platform.push(&from, &to).await,
-                    GET => self.platform.get(&from, &to).await,
-                    _ => unreachable!(),
+                let compression = artifact.compress;
+                let checksum = artifact.checksum.as_deref();
+                // the checksum is verified on the local side of the transfer, before pushing
+                // the file to the platform or after getting it from the platform.
+                let transfer = async {
+                    match &method[..] {
+                        PUSH => {
+                            Self::verify_checksum(from, checksum)?;
+                            platform.push(from, to, compression, zstd_level).await
+                        }
+                        GET => {
+                            platform.get(from, to, compression, zstd_level).await?;
+                            Self::verify_checksum(to, checksum)?;
+                            self.record_artifact(to)
+                        }
+                        TEMPLATE => {
+                            Self::verify_checksum(from, checksum)?;
+                            let rendered = {
+                                let vars = self.vars.lock().unwrap().clone();
+                                RenderedTemplate::new(
+                                    from,
+                                    &vars,
+                                    &self.env,
+                                    self.is_strict_substitution(),
+                                )?
+                            };
+                            let path = rendered.path.display().to_string();
+                            platform.push(&path, to, compression, zstd_level).await
+                        }
+                        _ => unreachable!(),
+                    }
                 };
-                if !artifact.ignore_errors {
-                    result?;
-                }
+                self.transfers
+                    .transfer(
+                        &self.ex,
+                        |progress| self.log_transfer(from, to, progress),
+                        transfer,
+                    )
+                    .await
+            });
+        let results = join_all(futures).await;
+
+        for ((artifact, ..), result) in transfers.iter().zip(results) {
+            if !artifact.ignore_errors {
+                result?;
             }
         }
         Ok(())
     }
 
+    /// Records the local path that a `get` artifact has been copied to, in the execution of the
+    /// run for the server to serve it and in the collected artifacts of the caller.
+    fn record_artifact(&self, to: &str) -> Result<()> {
+        let path = canonicalize(to)?.display().to_string();
+        self.ex.lock().unwrap().add_artifact(&path)?;
+        self.artifacts.lock().unwrap().push(path);
+        Ok(())
+    }
+
+    /// Compares the sha256 digest of a local artifact file with the one declared in the pipeline.
+    fn verify_checksum(path: &str, checksum: Option<&str>) -> Result<()> {
+        let expected = match checksum {
+            Some(expected) => expected,
+            None => return Ok(()),
+        };
+        if !Path::new(path).is_file() {
+            bail!("checksum can only be verified for a single file, {path} is not a file");
+        }
+        let mut hasher = Sha256::new();
+        io::copy(&mut File::open(path)?, &mut hasher)?;
+        let actual = format!("{:x}", hasher.finalize());
+        if actual != expected {
+            bail!("checksum mismatch for artifact {path}, expected sha256:{expected} but found sha256:{actual}");
+        }
+        Ok(())
+    }
+
     async fn steps(&mut self) -> Result<()> {
-        for step in &self.pip.steps {
-            self.step(step).await?;
-            self.artifacts(&step.name).await?;
+        if self.pip.steps.iter().any(|s| !s.depends_on.is_empty()) {
+            return self.step_graph().await;
+        }
+        // the first error is returned after the steps that should always run have executed,
+        // while the errors of those steps are logged since they aren't returned.
+        let mut first_error = None;
+        for (i, step) in self.pip.steps.iter().enumerate() {
+            if first_error.is_some() && !step.always_run {
+                let name = step.name.as_deref().unwrap_or_default();
+                let mut logger = self.lg.lock().unwrap();
+                logger.infoln(&format!(
+                    "[bld] Skipping step {name} (an earlier step has failed)"
+                ));
+                continue;
+            }
+            let result = self.top_level_step(step, i).await;
             self.exec_check_stop_signal()?;
+            match result {
+                Err(e) if first_error.is_some() => {
+                    let mut logger = self.lg.lock().unwrap();
+                    logger.errorln(&e.to_string());
+                }
+                Err(e) => {
+                    if self.pip.steps[i + 1..].iter().any(|s| s.always_run) {
+                        let name = step.name.as_deref().unwrap_or_default();
+                        let mut logger = self.lg.lock().unwrap();
+                        logger.errorln(&format!(
+                            "[bld] Step {name} failed, running the steps with always-run"
+                        ));
+                    }
+                    first_error = Some(e);
+                }
+                Ok(()) => {}
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    async fn top_level_step(&self, step: &BuildStep, index: usize) -> Result<()> {
+        if !self.step_condition(step)? {
+            return Ok(());
+        }
+        let prefix = self
+            .is_prefix_steps()
+            .then(|| Self::step_prefix(step, index));
+        let result = self.step(step, &prefix).await;
+        self.ignore_error(step, result)?;
+        self.artifacts(&step.name).await
+    }
+
+    /// Logs the error of a step that ignores its errors and lets the run continue.
+    fn ignore_error(&self, step: &BuildStep, result: Result<()>) -> Result<()> {
+        match result {
+            Err(e) if step.ignore_errors => {
+                let name = step.name.as_deref().unwrap_or_default();
+                let mut logger = self.lg.lock().unwrap();
+                logger.errorln(&format!(
+                    "[bld] Step {name} failed with: {e}. Continuing since ignore-errors is set"
+                ));
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// Executes the steps based on their dependencies, starting each step as soon as all of
+    /// its dependencies have completed so that independent steps run concurrently. The graph
+    /// is validated to be acyclic when the pipeline is parsed. After a failure only the steps
+    /// that always run are started, once all of their dependencies have finished.
+    async fn step_graph(&self) -> Result<()> {
+        let steps = &self.pip.steps;
+        let mut started = vec![false; steps.len()];
+        let mut completed = HashSet::new();
+        let mut finished = HashSet::new();
+        let mut running = FuturesUnordered::new();
+        let mut errors = vec![];
+        let mut is_stopped = false;
+        loop {
+            if !is_stopped {
+                for (i, step) in steps.iter().enumerate() {
+                    let is_ready = if step.always_run {
+                        step.depends_on
+                            .iter()
+                            .all(|d| finished.contains(d.as_str()))
+                    } else {
+                        errors.is_empty()
+                            && step
+                                .depends_on
+                                .iter()
+                                .all(|d| completed.contains(d.as_str()))
+                    };
+                    if started[i] || !is_ready {
+                        continue;
+                    }
+                    started[i] = true;
+                    let prefix = Self::step_prefix(step, i);
+                    running.push(async move { (i, self.graph_step(step, prefix).await) });
+                }
+            }
+            match running.next().await {
+                Some((i, result)) => {
+                    if let Some(name) = &steps[i].name {
+                        finished.insert(name.as_str());
+                        if result.is_ok() {
+                            completed.insert(name.as_str());
+                        }
+                    }
+                    if let Err(e) = result {
+                        errors.push(e.to_string());
+                    }
+                }
+                None => break,
+            }
+            if !is_stopped {
+                if let Err(e) = self.exec_check_stop_signal() {
+                    errors.push(e.to_string());
+                    is_stopped = true;
+                }
+            }
+        }
+        if !errors.is_empty() {
+            bail!("steps failed with: {}", errors.join(", "));
         }
         Ok(())
     }
 
-    async fn step(&self, step: &BuildStep) -> Result<()> {
+    /// Executes a step of the dependency graph along with its artifacts. A step skipped due
+    /// to its condition is considered completed.
+    async fn graph_step(&self, step: &BuildStep, prefix: String) -> Result<()> {
+        if !self.step_condition(step)? {
+            return Ok(());
+        }
+        let result = self.step(step, &Some(prefix)).await;
+        self.ignore_error(step, result)?;
+        self.artifacts(&step.name).await
+    }
+
+    /// The prefix of the output lines of a step, with its position used for unnamed steps.
+    fn step_prefix(step: &BuildStep, index: usize) -> String {
+        match &step.name {
+            Some(name) => format!("[{name}] "),
+            None => format!("[{}] ", index + 1),
+        }
+    }
+
+    /// Steps that run concurrently always have their output prefixed, while the rest only
+    /// when the pipeline or the config enable it. The lines of bld itself are never prefixed.
+    fn is_prefix_steps(&self) -> bool {
+        self.pip.prefix_steps || self.cfg.local.prefix_steps
+    }
+
+    fn step_condition(&self, step: &BuildStep) -> Result<bool> {
+        let condition = match &step.when {
+            Some(when) => {
+                let when = self.apply_context(when);
+                let vars = self.vars.lock().unwrap();
+                evaluate(&when, &vars, &self.env)?
+            }
+            None => return Ok(true),
+        };
+        if !condition {
+            let name = step.name.as_deref().unwrap_or_default();
+            let mut logger = self.lg.lock().unwrap();
+            logger.infoln(&format!("[bld] Skipping step {name} (condition not met)"));
+        }
+        Ok(condition)
+    }
+
+    async fn step(&self, step: &BuildStep, prefix: &Option<String>) -> Result<()> {
+        let parent = TraceContext::current();
+        self.retried(step, || {
+            traced(
+                self.tracer.as_ref(),
+                "step",
+                Self::step_attributes(step),
+                &parent,
+                self.step_traced(step, prefix),
+            )
+        })
+        .await
+    }
+
+    /// Executes a step and in case of failure retries it based on the retry configuration of
+    /// the step, so that the commands and called pipelines of the step are executed again in the
+    /// same way for every platform. The stop signal is checked before each retry in order for a
+    /// stopped run to not continue retrying. When all the attempts fail, the error of the first
+    /// one is returned since the later ones are usually a consequence of it.
+    async fn retried<F, Fut>(&self, step: &BuildStep, attempt_step: F) -> Result<()>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let mut attempt = 1;
+        let mut first_error = None;
+        loop {
+            match (attempt_step().await, &step.retry) {
+                (Err(e), Some(retry)) if attempt < retry.attempts => {
+                    self.exec_check_stop_signal()?;
+                    {
+                        let mut logger = self.lg.lock().unwrap();
+                        logger.debugln(&format!("[bld] {e}"));
+                        let name = step
+                            .name
+                            .as_deref()
+                            .map(|n| format!(" {n}"))
+                            .unwrap_or_default();
+                        logger.errorln(&format!(
+                            "[bld] Step{name} failed, retrying (attempt {}/{})",
+                            attempt + 1,
+                            retry.attempts
+                        ));
+                    }
+                    first_error.get_or_insert(e);
+                    sleep(Duration::from_secs(retry.delay_seconds)).await;
+                    self.exec_check_stop_signal()?;
+                    attempt += 1;
+                }
+                (Err(e), _) => return Err(first_error.unwrap_or(e)),
+                (Ok(_), _) => return Ok(()),
+            }
+        }
+    }
+
+    fn step_attributes(step: &BuildStep) -> Vec<KeyValue> {
+        let name = step.name.as_deref().unwrap_or_default();
+        vec![KeyValue::new("step", name.to_string())]
+    }
+
+    async fn step_traced(&self, step: &BuildStep, prefix: &Option<String>) -> Result<()> {
         if let Some(name) = &step.name {
             let mut logger = self.lg.lock().unwrap();
             logger.infoln(&format!("[bld] Step: {name}"));
         }
-        self.call(step).await?;
-        self.sh(step).await?;
+        if !self.is_quiet(step) {
+            self.step_output(step, prefix).await?;
+            return self.checkpoint(step);
+        }
+        self.lg.lock().unwrap().buffer_mode(true);
+        let result = self.step_output(step, prefix).await;
+        {
+            let mut logger = self.lg.lock().unwrap();
+            match &result {
+                Ok(_) => logger.discard_buffer(),
+                Err(_) => logger.flush_buffer(),
+            }
+        }
+        result?;
+        self.checkpoint(step)
+    }
+
+    /// Records the checkpoint of a completed step on the run, in order for external systems
+    /// that wait for it to be notified even though the run continues.
+    fn checkpoint(&self, step: &BuildStep) -> Result<()> {
+        if let Some(checkpoint) = &step.checkpoint {
+            let checkpoint = self.apply_context(checkpoint);
+            self.ex.lock().unwrap().add_checkpoint(&checkpoint)?;
+            let mut logger = self.lg.lock().unwrap();
+            logger.infoln(&format!("[bld] Checkpoint reached: {checkpoint}"));
+        }
+        Ok(())
+    }
+
+    /// The output of a quiet step is buffered by the logger and written only if the step fails.
+    /// Legs of a matrix that share the shell logger run concurrently, so their steps are never
+    /// quiet in order to not hold back the output of the other legs.
+    fn is_quiet(&self, step: &BuildStep) -> bool {
+        step.quiet && (self.matrix.is_none() || self.lg.lock().unwrap().is_file())
+    }
+
+    async fn step_output(&self, step: &BuildStep, prefix: &Option<String>) -> Result<()> {
+        match &step.group {
+            Some(group) if group.parallel => self.parallel_group(group).await?,
+            Some(group) => {
+                for (i, member) in group.steps.iter().enumerate() {
+                    let prefix = match self.is_prefix_steps() {
+                        true => Some(Self::step_prefix(member, i)),
+                        false => prefix.clone(),
+                    };
+                    if self.group_member(member, &prefix).await? {
+                        self.artifacts(&member.name).await?;
+                    }
+                    self.exec_check_stop_signal()?;
+                }
+            }
+            None => {
+                self.call(step).await?;
+                self.sh(step, prefix).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Executes a step of a group and returns false if the step was skipped due to its condition.
+    async fn group_member(&self, member: &BuildStep, prefix: &Option<String>) -> Result<bool> {
+        if !self.step_condition(member)? {
+            return Ok(false);
+        }
+        if let Some(name) = &member.name {
+            let mut logger = self.lg.lock().unwrap();
+            logger.infoln(&format!("[bld] Step: {name}"));
+        }
+        let parent = TraceContext::current();
+        let result = self
+            .retried(member, || {
+                traced(
+                    self.tracer.as_ref(),
+                    "step",
+                    Self::step_attributes(member),
+                    &parent,
+                    async {
+                        self.call(member).await?;
+                        self.sh(member, prefix).await
+                    },
+                )
+            })
+            .await;
+        self.ignore_error(member, result)?;
+        Ok(true)
+    }
+
+    /// Executes all the steps of the group concurrently, with their output prefixed by the
+    /// step name, and waits for all of them to finish before running their artifacts.
+    async fn parallel_group(&self, group: &StepGroup) -> Result<()> {
+        let results = join_all(group.steps.iter().enumerate().map(|(i, member)| {
+            let prefix = Self::step_prefix(member, i);
+            async move { self.group_member(member, &Some(prefix)).await }
+        }))
+        .await;
+
+        self.exec_check_stop_signal()?;
+
+        let mut executed = vec![];
+        let mut errors = vec![];
+        for (member, result) in group.steps.iter().zip(results) {
+            match result {
+                Ok(true) => executed.push(member),
+                Ok(false) => {}
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+        if !errors.is_empty() {
+            bail!("parallel steps failed with: {}", errors.join(", "));
+        }
+
+        for member in executed {
+            self.artifacts(&member.name).await?;
+        }
         Ok(())
     }
 
     async fn call(&self, step: &BuildStep) -> Result<()> {
         for call in &step.call {
-            let call = self.apply_context(call);
+            let name = self.apply_context(&call.pipeline);
+            let mut callers = self.callers.clone();
+            callers.push(self.pip_name.to_string());
+            if callers.contains(&name) {
+                bail!(
+                    "pipeline {name} is called recursively by {} -> {name}",
+                    callers.join(" -> ")
+                );
+            }
+            // the called pipeline gets the variables as they are when it's called, including
+            // the outputs of the steps that have completed.
+            let vars = Arc::new(self.vars.lock().unwrap().clone());
+            let ex = self
+                .ex
+                .lock()
+                .unwrap()
+                .child_atom(&name, step.name.as_deref())?;
             let runner = RunnerBuilder::default()
                 .run_id(&self.run_id)
+                .machine_id(&self.machine_id)
                 .run_start_time(&self.run_start_time)
                 .config(self.cfg.clone())
                 .proxy(self.prx.clone())
-                .pipeline(&call)
-                .execution(self.ex.clone())
+                .pipeline(&name)
+                .execution(ex.clone())
                 .logger(self.lg.clone())
                 .environment(self.env.clone())
-                .variables(self.vars.clone())
+                .variables(vars)
+                .secrets(self.secrets.clone())
                 .ipc(self.ipc.clone())
                 .context(self.context.clone())
+                .transfers(self.transfers.clone())
+                .artifacts(self.artifacts.clone())
                 .is_child(true)
+                .callers(callers)
+                .dry_run(self.dry_run)
+                .tracer(self.tracer.clone())
                 .build()
-                .await?;
-            runner.run().await.await?;
-            self.exec_check_stop_signal()?;
+                .await;
+            let runner = match runner {
+                Ok(runner) => runner,
+                Err(e) => {
+                    let _ = ex.lock().unwrap().set_as_faulted();
+                    return Err(e);
+                }
+            };
+            let _ = ex.lock().unwrap().set_as_running();
+            let result = runner.run().await.await;
+            {
+                let mut exec = ex.lock().unwrap();
+                let _ = match result {
+                    Ok(_) => exec.set_as_finished(),
+                    Err(_) => exec.set_as_faulted(),
+                };
+            }
+            if let Err(e) = self.exec_check_stop_signal() {
+                let summary = CallSummary::new(name, CallState::Cancelled, call.rollup);
+                self.call_summary_push(&ex, summary);
+                return Err(e);
+            }
+            self.rollup(&ex, name, call.rollup, result)?;
         }
         Ok(())
     }
 
-    async fn sh(&self, step: &BuildStep) -> Result<()> {
-        for command in step.commands.iter() {
-            let working_dir = step.working_dir.as_ref().map(|wd| self.apply_context(wd));
-            let command = self.apply_context(command);
-            self.platform
-                .shell(&working_dir, &command, self.ex.clone())
-                .await?;
+    /// Applies the rollup rule of a call to the result of the called pipeline and records
+    /// its contribution in the call summary of the current runner.
+    fn rollup(
+        &self,
+        ex: &AtomicExec,
+        name: String,
+        rollup: Rollup,
+        result: Result<()>,
+    ) -> Result<()> {
+        let state = match &result {
+            Ok(_) => CallState::Finished,
+            Err(_) => CallState::Faulted,
+        };
+        self.call_summary_push(ex, CallSummary::new(name.to_string(), state, rollup));
+        match (result, rollup) {
+            (Ok(_), _) | (Err(_), Rollup::Ignore) => Ok(()),
+            (Err(_), Rollup::Warn) => {
+                let mut logger = self.lg.lock().unwrap();
+                logger.errorln(&format!(
+                    "[bld] Called pipeline {name} faulted, continuing due to rollup: {rollup}"
+                ));
+                Ok(())
+            }
+            (Err(e), Rollup::Fail) => Err(e),
+        }
+    }
+
+    /// Records the contribution of a called pipeline in the call summary and on its run, so
+    /// that it's part of the call tree of the run.
+    fn call_summary_push(&self, ex: &AtomicExec, summary: CallSummary) {
+        let _ = ex.lock().unwrap().set_rollup(
+            &summary.rollup.to_string(),
+            &summary.effective_state().to_string(),
+        );
+        let mut calls = self.calls.lock().unwrap();
+        calls.push(summary);
+    }
+
+    fn call_summary(&self) {
+        let calls = self.calls.lock().unwrap();
+        if calls.is_empty() {
+            return;
+        }
+        let mut logger = self.lg.lock().unwrap();
+        logger.dumpln("[bld] Called pipelines:");
+        for call in calls.iter() {
+            logger.dumpln(&format!(
+                "[bld]   {}: {} (rollup: {}, effective: {})",
+                call.pipeline,
+                call.state,
+                call.rollup,
+                call.effective_state()
+            ));
+        }
+    }
+
+    /// Logs the number of matches of each log redaction rule so that noisy rules can be identified.
+    fn redaction_summary(&self) {
+        if self.is_child {
+            return;
+        }
+        let mut logger = self.lg.lock().unwrap();
+        let summary = logger.redaction_summary();
+        if summary.iter().all(|(_, count)| *count == 0) {
+            return;
+        }
+        logger.dumpln("[bld] Log redactions:");
+        for (rule, count) in summary.iter() {
+            logger.dumpln(&format!("[bld]   {rule}: {count}"));
+        }
+    }
+
+    fn long_lines_summary(&self) {
+        if self.is_child {
+            return;
+        }
+        let mut logger = self.lg.lock().unwrap();
+        let count = logger.long_lines();
+        if count == 0 {
+            return;
+        }
+        let action = match self.cfg.local.long_log_lines {
+            LongLogLines::Split => "split",
+            LongLogLines::Truncate => "truncated",
+        };
+        logger.dumpln(&format!(
+            "[bld] {count} log lines exceeded the maximum length of {} bytes and were {action}",
+            self.cfg.local.max_log_line_length
+        ));
+    }
+
+    async fn sh(&self, step: &BuildStep, prefix: &Option<String>) -> Result<()> {
+        // the context is applied to every command before executing any of them so that
+        // an unresolved token fails the step before it has any effect.
+        let working_dir = step
+            .working_dir
+            .as_ref()
+            .map(|wd| self.apply_context_strict(wd, &step.name, "working-dir"))
+            .transpose()?;
+        let commands = step
+            .commands
+            .iter()
+            .map(|c| self.apply_context_strict(c, &step.name, "command"))
+            .collect::<Result<Vec<String>>>()?;
+        let vars = step
+            .environment
+            .iter()
+            .map(|e| {
+                let value =
+                    self.apply_context_strict(&e.default_value, &step.name, "environment")?;
+                Ok((e.name.to_string(), value))
+            })
+            .collect::<Result<Vec<(String, String)>>>()?;
+        let env = StepEnv::new(vars, step.inherit_container_env, self.pip.output_encoding);
+        if step.output_var.is_some()
+            && matches!(
+                self.pip.runs_on,
+                RunsOn::Kubernetes { .. } | RunsOn::Ssh { .. }
+            )
+        {
+            bail!("output-var is supported only for steps that run on a machine or on docker");
+        }
+        if self.dry_run {
+            let mut logger = self.lg.lock().unwrap();
+            for command in commands.iter() {
+                logger.dumpln(&format!("[bld] DRY-RUN would execute: {command}"));
+            }
+            return Ok(());
+        }
+        let mut output = String::new();
+        for (index, command) in commands.iter().enumerate() {
+            self.lg
+                .lock()
+                .unwrap()
+                .debugln(&format!("[bld] Executing: {command}"));
+            let attributes = vec![KeyValue::new(
+                "command",
+                self.lg.lock().unwrap().mask(command),
+            )];
+            let start = Instant::now();
+            let stdout = traced(
+                self.tracer.as_ref(),
+                "sh",
+                attributes,
+                &TraceContext::current(),
+                async {
+                    self.platform()
+                        .await?
+                        .shell(
+                            &working_dir,
+                            command,
+                            prefix,
+                            step.ssh_agent_forwarding,
+                            step.output_var.is_some(),
+                            &env,
+                            self.ex.clone(),
+                        )
+                        .await
+                },
+            )
+            .await;
+            self.command_timing(step, index, command, start.elapsed());
+            output.push_str(&stdout?);
             self.exec_check_stop_signal()?;
         }
+        if let Some(name) = &step.output_var {
+            self.output_var(name, &output);
+        }
         Ok(())
     }
 
+    /// Records the duration of a command in the summary of the run and
+    /// warns about the commands that took longer than the threshold of the configuration.
+    fn command_timing(&self, step: &BuildStep, index: usize, command: &str, duration: Duration) {
+        let preview = command_preview(&self.lg.lock().unwrap().mask(command));
+        let timing = CommandTiming {
+            pipeline: &self.pip_name,
+            step_name: step.name.as_deref(),
+            matrix: self.matrix.as_ref().map(|(_, label)| label.as_str()),
+            index,
+            preview: &preview,
+            duration,
+        };
+        let _ = self.ex.lock().unwrap().add_command(&timing);
+        if duration.as_secs() >= self.cfg.local.slow_command_seconds {
+            let mut logger = self.lg.lock().unwrap();
+            logger.warnln(&format!(
+                "[bld] command took {}: {preview}",
+                format_duration(duration)
+            ));
+        }
+    }
+
+    /// Sets a variable to the output of the commands of a step, without the trailing new lines
+    /// in the same way as a command substitution of the shell.
+    fn output_var(&self, name: &str, output: &str) {
+        let value = output.trim_end_matches(['\n', '\r']).to_string();
+        self.vars.lock().unwrap().insert(name.to_string(), value);
+    }
+
     async fn start(&self) {
         self.exec_persist_start().await;
         self.info();
@@ -398,7 +1708,16 @@ impl Runner {
             bail!("");
         }
 
-        if let Err(e) = self.steps().await {
+        let steps_result = match self.pip.timeout_seconds {
+            Some(seconds) => timeout(Duration::from_secs(seconds), self.steps())
+                .await
+                .unwrap_or_else(|_| {
+                    Err(anyhow!("[bld] Pipeline timed out after {seconds} seconds"))
+                }),
+            None => self.steps().await,
+        };
+
+        if let Err(e) = steps_result {
             self.log_dump(&e.to_string());
             self.has_faulted = true;
             bail!("");
@@ -415,10 +1734,431 @@ impl Runner {
 
     pub async fn run(mut self) -> RecursiveFuture {
         Box::pin(async move {
-            self.start().await;
-            let execution_result = self.execute().await;
-            let cleanup_result = self.cleanup().await;
-            execution_result.and(cleanup_result)
+            let parent = self
+                .trace_parent
+                .take()
+                .unwrap_or_else(TraceContext::current);
+            let mut attributes = vec![
+                KeyValue::new("pipeline", self.pip_name.to_string()),
+                KeyValue::new("run_id", self.run_id.to_string()),
+            ];
+            if let Some((index, label)) = &self.matrix {
+                attributes.push(KeyValue::new("matrix_index", *index as i64));
+                attributes.push(KeyValue::new("matrix", label.to_string()));
+            }
+            let tracer = self.tracer.clone();
+            traced(
+                tracer.as_ref(),
+                "build_runner",
+                attributes,
+                &parent,
+                self.run_traced(),
+            )
+            .await
         })
     }
+
+    async fn run_traced(&mut self) -> Result<()> {
+        self.start().await;
+        if self.tracer.is_some() && !self.is_child && self.matrix.is_none() {
+            persist_trace_id(&self.ex);
+        }
+        let execution_result = self.execute().await;
+        self.call_summary();
+        self.redaction_summary();
+        self.long_lines_summary();
+        let cleanup_result = self.cleanup().await;
+        if !self.is_child && self.matrix.is_none() {
+            send_notifications(
+                &self.cfg,
+                &self.lg,
+                &self.notifications(),
+                &self.pip_name,
+                &self.run_id,
+                execution_result.is_ok(),
+            )
+            .await;
+        }
+        execution_result.and(cleanup_result)
+    }
+}
+
+/// Runs the legs of a pipeline matrix concurrently and persists the state of the run
+/// once all of them have finished. The run is faulted if any of the legs has faulted.
+pub struct MatrixRunner {
+    run_id: String,
+    cfg: Option<Arc<BldConfig>>,
+    pip_name: String,
+    notifications: Vec<Notification>,
+    ex: AtomicExec,
+    lg: AtomicLog,
+    ipc: Arc<Option<Sender<WorkerMessages>>>,
+    prx: AtomicProxy,
+    dispose_machine: bool,
+    cgroup: Option<MachineCgroup>,
+    runners: Vec<Runner>,
+    tracer: Option<AtomicTracer>,
+    trace_parent: Option<TraceContext>,
+}
+
+impl MatrixRunner {
+    fn persist_start(&self) {
+        let mut exec = self.ex.lock().unwrap();
+        let _ = exec.set_as_running();
+    }
+
+    fn persist_end(&self, has_faulted: bool) -> Result<()> {
+        {
+            let mut exec = self.ex.lock().unwrap();
+            let _ = if has_faulted {
+                exec.set_as_faulted()
+            } else {
+                exec.set_as_finished()
+            };
+            exec.reconcile();
+        }
+        if self.dispose_machine {
+            let tmp_path = path![current_dir()?, LOCAL_MACHINE_TMP_DIR, &self.run_id];
+            if tmp_path.is_dir() {
+                remove_dir_all(tmp_path)?;
+            }
+        }
+        self.prx.dispose()?;
+        Ok(())
+    }
+
+    async fn ipc_send_completed(&self) -> Result<()> {
+        if let Some(ipc) = Option::as_ref(&self.ipc) {
+            ipc.send(WorkerMessages::Completed).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn run(mut self) -> Result<()> {
+        let parent = self
+            .trace_parent
+            .take()
+            .unwrap_or_else(TraceContext::current);
+        let (start_retries, start_breaker_trips) = docker_counters();
+        // the legs of a matrix are children of a span for the whole run, while a single runner
+        // creates the span of the run itself.
+        let is_matrix = self.runners.iter().any(|r| r.matrix.is_some());
+        let tracer = self.tracer.clone().filter(|_| is_matrix);
+        let attributes = vec![
+            KeyValue::new("pipeline", self.pip_name.to_string()),
+            KeyValue::new("run_id", self.run_id.to_string()),
+        ];
+        let result = traced(
+            tracer.as_ref(),
+            "build_runner",
+            attributes,
+            &parent,
+            self.run_legs(),
+        )
+        .await;
+        if let Some(cgroup) = &self.cgroup {
+            if let Err(e) = cgroup.dispose() {
+                error!("unable to remove the cgroup of run {}, {e}", self.run_id);
+            }
+        }
+        // the counters of the retry layer are process wide, so only the ones of this run are recorded.
+        let (retries, breaker_trips) = docker_counters();
+        let (retries, breaker_trips) =
+            (retries - start_retries, breaker_trips - start_breaker_trips);
+        if retries > 0 || breaker_trips > 0 {
+            let mut exec = self.ex.lock().unwrap();
+            let _ = exec.set_docker_counters(retries, breaker_trips);
+        }
+        result
+    }
+
+    async fn run_legs(&mut self) -> Result<()> {
+        let is_matrix = self.runners.iter().any(|r| r.matrix.is_some());
+        if !is_matrix {
+            let mut results = vec![];
+            for runner in self.runners.drain(..) {
+                results.push(runner.run().await.await);
+            }
+            return results.into_iter().collect();
+        }
+
+        self.persist_start();
+        if self.tracer.is_some() {
+            persist_trace_id(&self.ex);
+        }
+        let legs: Vec<(usize, String)> = self
+            .runners
+            .iter()
+            .filter_map(|r| r.matrix.clone())
+            .collect();
+        {
+            let mut logger = self.lg.lock().unwrap();
+            logger.infoln(&format!("[bld] Running {} matrix legs", legs.len()));
+        }
+
+        let mut futures = vec![];
+        for runner in self.runners.drain(..) {
+            futures.push(runner.run().await);
+        }
+        let results = join_all(futures).await;
+
+        let mut has_faulted = false;
+        {
+            let mut logger = self.lg.lock().unwrap();
+            for ((index, label), result) in legs.iter().zip(results.iter()) {
+                let state = match result {
+                    Ok(_) => PR_STATE_FINISHED,
+                    Err(_) => {
+                        has_faulted = true;
+                        PR_STATE_FAULTED
+                    }
+                };
+                logger.dumpln(&format!("[bld] Matrix leg {index} ({label}): {state}"));
+            }
+        }
+
+        let cleanup_result = self
+            .persist_end(has_faulted)
+            .and(self.ipc_send_completed().await);
+        if let Some(cfg) = &self.cfg {
+            send_notifications(
+                cfg,
+                &self.lg,
+                &self.notifications,
+                &self.pip_name,
+                &self.run_id,
+                !has_faulted,
+            )
+            .await;
+        }
+        if has_faulted {
+            bail!("");
+        }
+        cleanup_result
+    }
+}
+
+/// Sends the notifications of a pipeline for the outcome of its run. Notifications are best
+/// effort, so errors are logged without affecting the state of the run.
+async fn send_notifications(
+    cfg: &Arc<BldConfig>,
+    lg: &AtomicLog,
+    notifications: &[Notification],
+    pipeline: &str,
+    run_id: &str,
+    success: bool,
+) {
+    let report = RunReport {
+        pipeline: pipeline.to_string(),
+        run_id: run_id.to_string(),
+        outcome: if success {
+            NotifyOn::Success
+        } else {
+            NotifyOn::Failure
+        },
+    };
+    for notification in notifications.iter() {
+        if !notification.on.contains(&report.outcome) {
+            continue;
+        }
+        if let Err(e) = notify(cfg.clone(), &notification.target, &report).await {
+            let mut logger = lg.lock().unwrap();
+            logger.dumpln(&format!(
+                "[bld] Unable to send {} notification, {e}",
+                notification.target
+            ));
+        }
+    }
+}
+
+/// Checks that the host side of a volume is either a named docker volume or an absolute path under
+/// one of the allowlisted host paths. Paths with `..` components are rejected so that they can't
+/// escape an allowlisted directory.
+fn volume_allowed(volume: &str, allowlist: &[String]) -> bool {
+    let host = volume.split(':').next().unwrap_or_default();
+    let is_named = host
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-')
+        && host.starts_with(|c: char| c.is_ascii_alphanumeric());
+    if is_named {
+        return true;
+    }
+    let host = Path::new(host);
+    if !host.is_absolute() || host.components().any(|c| c == Component::ParentDir) {
+        return false;
+    }
+    allowlist.iter().any(|allowed| host.starts_with(allowed))
+}
+
+/// The id of the temp dir of a matrix leg, nested in the temp dir of the run so that the legs
+/// don't share their working directory and are removed along with the run.
+fn machine_leg_id(run_id: &str, index: usize) -> String {
+    format!("{run_id}/{index}")
+}
+
+/// Finds the credentials of the config that a pipeline references, rejecting the ones that
+/// don't have the pipeline in their allowlist.
+fn pipeline_credentials<'a>(
+    cfg: &'a BldLocalConfig,
+    pip_name: &str,
+    names: &[String],
+) -> Result<Vec<&'a BldCredentialConfig>> {
+    names
+        .iter()
+        .map(|name| {
+            let credential = cfg
+                .credentials
+                .iter()
+                .find(|c| &c.name == name)
+                .ok_or_else(|| anyhow!("credential {name} is not defined in the config"))?;
+            if !credential.allows(pip_name) {
+                bail!("pipeline {pip_name} is not allowed to use credential {name}");
+            }
+            Ok(credential)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bld_config::definitions::TOOL_DIR;
+    use bld_config::BldCredentialKind;
+
+    #[test]
+    fn credentials_are_limited_to_the_allowlisted_pipelines() {
+        let mut cfg = BldLocalConfig::default();
+        cfg.credentials.push(BldCredentialConfig {
+            name: "deploy-key".to_string(),
+            kind: BldCredentialKind::SshKey {
+                key_file: "/etc/bld/keys/deploy".to_string(),
+            },
+            allowed_pipelines: vec!["deploy.yaml".to_string()],
+        });
+        let names = vec!["deploy-key".to_string()];
+
+        let credentials = pipeline_credentials(&cfg, "deploy.yaml", &names).unwrap();
+        assert_eq!(credentials[0].name, "deploy-key");
+        let error = pipeline_credentials(&cfg, "build.yaml", &names)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            error,
+            "pipeline build.yaml is not allowed to use credential deploy-key"
+        );
+        assert!(pipeline_credentials(&cfg, "deploy.yaml", &["missing".to_string()]).is_err());
+    }
+
+    /// A runner of a pipeline that runs on docker, so that creating its platform would fail
+    /// since there is no docker daemon.
+    async fn docker_runner(root: &Path, content: &str, dry_run: bool) -> Runner {
+        let dir = root.join(TOOL_DIR);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("pipeline.yaml"), content).unwrap();
+        let mut cfg = BldConfig::default();
+        cfg.local.docker_url = "tcp://127.0.0.1:1".to_string();
+        RunnerBuilder::default()
+            .config(Arc::new(cfg))
+            .proxy(Arc::new(PipelineFileSystemProxy::Git {
+                root: root.to_path_buf(),
+            }))
+            .pipeline("pipeline.yaml")
+            .environment(Arc::new(HashMap::new()))
+            .variables(Arc::new(HashMap::new()))
+            .dry_run(dry_run)
+            .build()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn platform_is_created_only_when_a_command_runs() {
+        let root = std::env::temp_dir().join(format!("bld-platform-{}", Uuid::new_v4()));
+        let content = r#"
+runs-on: alpine
+variables:
+- RUN: 'false'
+steps:
+- name: skipped
+  when: "${{ vars.RUN == 'true' }}"
+  exec:
+  - echo skipped
+"#;
+
+        // the futures of the runner are boxed since they overflow the stack of a test thread.
+        let mut runner = docker_runner(&root, content, false).await;
+        Box::pin(runner.execute()).await.unwrap();
+        assert!(runner.platform.get().is_none());
+        runner.exec_persist_end().await.unwrap();
+
+        let content = r"
+runs-on: alpine
+steps:
+- exec:
+  - echo dry run
+";
+        let mut runner = docker_runner(&root, content, true).await;
+        Box::pin(runner.execute()).await.unwrap();
+        assert!(runner.platform.get().is_none());
+        runner.exec_persist_end().await.unwrap();
+
+        let mut runner = docker_runner(&root, content, false).await;
+        assert!(Box::pin(runner.execute()).await.is_err());
+        assert!(runner.platform.get().is_none());
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn environment_file_is_resolved_relative_to_the_pipeline() {
+        let root = std::env::temp_dir().join(format!("bld-env-file-{}", Uuid::new_v4()));
+        let dir = root.join(TOOL_DIR).join("nested");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("pipeline.yaml"), "environment-file: .env\n").unwrap();
+        std::fs::write(
+            dir.join(".env"),
+            "FIRST=1\n# comment\nSECOND=\"two words\"\n",
+        )
+        .unwrap();
+        let prx = PipelineFileSystemProxy::Git { root: root.clone() };
+
+        let pipeline = Pipeline::parse(&prx.read("nested/pipeline.yaml").unwrap()).unwrap();
+        assert_eq!(pipeline.environment_file.as_deref(), Some(".env"));
+        let env = RunnerBuilder::environment_file(&prx, "nested/pipeline.yaml", ".env").unwrap();
+        assert_eq!(env.len(), 2);
+        assert_eq!(env["FIRST"], "1");
+        assert_eq!(env["SECOND"], "two words");
+        let error = RunnerBuilder::environment_file(&prx, "nested/pipeline.yaml", "missing.env")
+            .unwrap_err()
+            .to_string();
+        assert!(error.starts_with("unable to load environment file missing.env"));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn matrix_legs_have_their_own_machine_dir() {
+        let first = machine_leg_id("run", 0);
+        let second = machine_leg_id("run", 1);
+        assert_ne!(first, second);
+        assert!(Path::new(&first).starts_with("run"));
+        assert!(Path::new(&second).starts_with("run"));
+    }
+
+    #[test]
+    fn volumes_are_limited_to_the_allowlisted_host_paths() {
+        let allowlist = vec!["/data".to_string()];
+        assert!(volume_allowed("cache:/cache", &allowlist));
+        assert!(volume_allowed("/data:/data:ro", &allowlist));
+        assert!(volume_allowed("/data/sets:/sets", &allowlist));
+        assert!(!volume_allowed("/data2:/data", &allowlist));
+        assert!(!volume_allowed("/data/../etc:/etc", &allowlist));
+        assert!(!volume_allowed("/:/host", &allowlist));
+        assert!(!volume_allowed(
+            "/var/run/docker.sock:/var/run/docker.sock",
+            &allowlist
+        ));
+        assert!(!volume_allowed("./data:/data", &allowlist));
+        assert!(!volume_allowed("/data:/data", &[]));
+    }
 }
@@ -0,0 +1,877 @@
+use serde_json::{json, Map, Value};
+use std::fmt::Write;
+
+const JSON_SCHEMA_DRAFT: &str = "http://json-schema.org/draft-07/schema#";
+
+/// The type of the value of a pipeline entry.
+#[derive(Debug, Clone)]
+pub enum SchemaType {
+    String,
+    Integer,
+    Boolean,
+    /// A string, number or boolean.
+    Scalar,
+    /// A string with one of the provided values.
+    Enum(&'static [&'static str]),
+    List(Box<SchemaType>),
+    /// A mapping with keys of any name and values of the provided type.
+    Map(Box<SchemaType>),
+    Object(Vec<SchemaField>),
+    /// A value of any of the provided types.
+    OneOf(Vec<SchemaType>),
+}
+
+/// An entry of a pipeline along with the details of its value.
+#[derive(Debug, Clone)]
+pub struct SchemaField {
+    pub name: &'static str,
+    pub kind: SchemaType,
+    pub required: bool,
+    pub default: Option<&'static str>,
+    pub description: &'static str,
+}
+
+impl SchemaField {
+    fn new(name: &'static str, kind: SchemaType, description: &'static str) -> Self {
+        Self {
+            name,
+            kind,
+            required: false,
+            default: None,
+            description,
+        }
+    }
+
+    fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    fn default(mut self, default: &'static str) -> Self {
+        self.default = Some(default);
+        self
+    }
+}
+
+fn list(kind: SchemaType) -> SchemaType {
+    SchemaType::List(Box::new(kind))
+}
+
+fn map(kind: SchemaType) -> SchemaType {
+    SchemaType::Map(Box::new(kind))
+}
+
+/// The entries of a docker container that the pipeline runs on.
+fn docker() -> SchemaType {
+    SchemaType::Object(vec![
+        SchemaField::new("image", SchemaType::String, "The image of the container").required(),
+        SchemaField::new(
+            "oom-score-adj",
+            SchemaType::Integer,
+            "The oom score adjustment of the container, between -1000 and 1000",
+        ),
+        SchemaField::new(
+            "oom-kill-disable",
+            SchemaType::Boolean,
+            "Disables the oom killer for the container, only for allowed pipelines",
+        )
+        .default("false"),
+        SchemaField::new(
+            "volumes",
+            list(SchemaType::String),
+            "Volumes of the container in the host_path:container_path[:ro|rw] format",
+        ),
+        SchemaField::new(
+            "network",
+            SchemaType::String,
+            "The docker network of the container",
+        ),
+        SchemaField::new(
+            "registry",
+            SchemaType::String,
+            "The name of a registry of the config that the image is pulled with",
+        ),
+        SchemaField::new(
+            "resources",
+            SchemaType::Object(vec![
+                SchemaField::new(
+                    "memory",
+                    SchemaType::OneOf(vec![SchemaType::Integer, SchemaType::String]),
+                    "The memory limit of the container in bytes or as a size such as 512m or 2g",
+                ),
+                SchemaField::new(
+                    "cpus",
+                    SchemaType::Scalar,
+                    "The number of cpus that the container can use, such as 1.5",
+                ),
+                SchemaField::new(
+                    "cpu-shares",
+                    SchemaType::Integer,
+                    "The cpu weight of the container relative to other containers",
+                ),
+                SchemaField::new(
+                    "pids",
+                    SchemaType::Integer,
+                    "The maximum number of processes in the container",
+                ),
+            ]),
+            "Resource limits of the container, lowered to the maximum of the server",
+        ),
+        SchemaField::new(
+            "docker-remote",
+            SchemaType::Object(vec![
+                SchemaField::new(
+                    "host",
+                    SchemaType::String,
+                    "The tcp, http or https url of the docker daemon",
+                )
+                .required(),
+                SchemaField::new(
+                    "tls-cert",
+                    SchemaType::String,
+                    "The directory with the ca.pem, cert.pem and key.pem files of the daemon",
+                ),
+            ]),
+            "A remote docker daemon that the container is created on",
+        ),
+    ])
+}
+
+fn kubernetes() -> SchemaType {
+    SchemaType::Object(vec![SchemaField::new(
+        "kubernetes",
+        SchemaType::Object(vec![
+            SchemaField::new("image", SchemaType::String, "The image of the pod").required(),
+            SchemaField::new("namespace", SchemaType::String, "The namespace of the pod")
+                .default("default"),
+            SchemaField::new(
+                "service-account",
+                SchemaType::String,
+                "The service account of the pod",
+            ),
+        ]),
+        "A kubernetes pod that the pipeline runs on",
+    )
+    .required()])
+}
+
+fn ssh() -> SchemaType {
+    SchemaType::Object(vec![SchemaField::new(
+        "ssh",
+        SchemaType::Object(vec![
+            SchemaField::new(
+                "host",
+                SchemaType::String,
+                "The host of the machine with an optional port",
+            )
+            .required(),
+            SchemaField::new("user", SchemaType::String, "The user to connect as").required(),
+            SchemaField::new(
+                "key-file",
+                SchemaType::String,
+                "The private key to connect with",
+            )
+            .required(),
+        ]),
+        "A remote machine that the steps are executed on over ssh",
+    )
+    .required()])
+}
+
+/// A list of single entry maps with the name of a variable and either its value or its
+/// default value along with its type.
+fn variables() -> SchemaType {
+    list(map(SchemaType::OneOf(vec![
+        SchemaType::Scalar,
+        SchemaType::Object(vec![
+            SchemaField::new(
+                "default",
+                SchemaType::Scalar,
+                "The default value of the variable",
+            )
+            .required(),
+            SchemaField::new(
+                "type",
+                SchemaType::Enum(&["string", "int", "bool"]),
+                "The type that the values of the variable should conform to",
+            )
+            .default("string"),
+        ]),
+    ])))
+}
+
+fn artifacts() -> SchemaType {
+    list(SchemaType::Object(vec![
+        SchemaField::new(
+            "method",
+            SchemaType::Enum(&["push", "get", "template"]),
+            "Pushes files to the platform or gets files from it",
+        ),
+        SchemaField::new(
+            "from",
+            SchemaType::String,
+            "The source path, which may be a glob",
+        ),
+        SchemaField::new("to", SchemaType::String, "The destination path"),
+        SchemaField::new(
+            "after",
+            SchemaType::String,
+            "The name of the step that the transfer happens after, otherwise before all steps",
+        ),
+        SchemaField::new(
+            "ignore-errors",
+            SchemaType::Boolean,
+            "Continues the run when the transfer fails",
+        )
+        .default("false"),
+        SchemaField::new(
+            "compress",
+            SchemaType::Enum(&["none", "gzip", "zstd"]),
+            "The compression of the transfer",
+        )
+        .default("none"),
+        SchemaField::new(
+            "checksum",
+            SchemaType::String,
+            "The expected sha256:<hex digest> checksum of the transferred file",
+        ),
+    ]))
+}
+
+fn notification(target: SchemaField) -> SchemaType {
+    SchemaType::Object(vec![
+        target,
+        SchemaField::new(
+            "on",
+            list(SchemaType::Enum(&["success", "failure"])),
+            "The outcomes of the run that the notification is sent for",
+        )
+        .default("[failure]"),
+    ])
+}
+
+fn notifications() -> SchemaType {
+    SchemaType::Object(vec![
+        SchemaField::new(
+            "slack",
+            notification(
+                SchemaField::new(
+                    "webhook-url",
+                    SchemaType::String,
+                    "The incoming webhook of the slack channel",
+                )
+                .required(),
+            ),
+            "Sends a message to a slack channel",
+        ),
+        SchemaField::new(
+            "email",
+            notification(
+                SchemaField::new(
+                    "to",
+                    list(SchemaType::String),
+                    "The recipients of the email",
+                )
+                .required(),
+            ),
+            "Sends an email using the smtp settings of the config",
+        ),
+        SchemaField::new(
+            "webhook",
+            notification(
+                SchemaField::new(
+                    "url",
+                    SchemaType::String,
+                    "The url that the run is posted to",
+                )
+                .required(),
+            ),
+            "Posts the outcome of the run to a url",
+        ),
+    ])
+}
+
+/// The entries of a step. Steps of a group can't be groups, checkpoints, quiet, always run or have
+/// dependencies.
+fn step(in_group: bool) -> SchemaType {
+    let mut fields = vec![
+        SchemaField::new("name", SchemaType::String, "The name of the step"),
+        SchemaField::new(
+            "working-dir",
+            SchemaType::String,
+            "The directory that the commands are executed in",
+        ),
+        SchemaField::new("exec", list(SchemaType::String), "The commands of the step"),
+        SchemaField::new(
+            "use",
+            SchemaType::Object(vec![
+                SchemaField::new(
+                    "template",
+                    SchemaType::String,
+                    "The name of the step template",
+                )
+                .required(),
+                SchemaField::new(
+                    "with",
+                    map(SchemaType::Scalar),
+                    "The values of the parameters of the template",
+                ),
+            ]),
+            "Expands the step from a step template of the pipeline",
+        ),
+        SchemaField::new(
+            "call",
+            list(SchemaType::OneOf(vec![
+                SchemaType::String,
+                SchemaType::Object(vec![
+                    SchemaField::new(
+                        "pipeline",
+                        SchemaType::String,
+                        "The name of the called pipeline",
+                    )
+                    .required(),
+                    SchemaField::new(
+                        "rollup",
+                        SchemaType::Enum(&["fail", "warn", "ignore"]),
+                        "How a faulted called pipeline affects the run",
+                    )
+                    .default("fail"),
+                ]),
+            ])),
+            "The pipelines that the step calls",
+        ),
+        SchemaField::new(
+            "retry",
+            SchemaType::Object(vec![
+                SchemaField::new(
+                    "attempts",
+                    SchemaType::Integer,
+                    "The number of attempts including the first one",
+                )
+                .required(),
+                SchemaField::new(
+                    "delay-seconds",
+                    SchemaType::Integer,
+                    "The delay between attempts",
+                )
+                .default("0"),
+            ]),
+            "Retries the step when it fails",
+        ),
+        SchemaField::new(
+            "retries",
+            SchemaType::Integer,
+            "The number of retries after the first attempt, instead of retry",
+        ),
+        SchemaField::new(
+            "retry-backoff-seconds",
+            SchemaType::Integer,
+            "The delay between the retries of the retries entry",
+        )
+        .default("0"),
+        SchemaField::new(
+            "when",
+            SchemaType::String,
+            "A condition that the step is executed only when it is met",
+        ),
+        SchemaField::new(
+            "ssh-agent-forwarding",
+            SchemaType::Boolean,
+            "Forwards the ssh agent of the host, defaults to the value of the pipeline",
+        ),
+        SchemaField::new(
+            "inherit-container-env",
+            SchemaType::Boolean,
+            "Keeps the environment of the container for the commands of the step",
+        )
+        .default("true"),
+        SchemaField::new(
+            "environment",
+            variables(),
+            "Environment variables of the step",
+        ),
+        SchemaField::new(
+            "ignore-errors",
+            SchemaType::Boolean,
+            "Continues the run when the step fails",
+        )
+        .default("false"),
+    ];
+    if !in_group {
+        fields.extend([
+            SchemaField::new(
+                "always-run",
+                SchemaType::Boolean,
+                "Executes the step even when an earlier step has failed",
+            )
+            .default("false"),
+            SchemaField::new(
+                "quiet",
+                SchemaType::Boolean,
+                "Logs the output of the step only when it fails",
+            )
+            .default("false"),
+            SchemaField::new(
+                "checkpoint",
+                SchemaType::String,
+                "A checkpoint that is recorded when the step completes",
+            ),
+            SchemaField::new(
+                "output-var",
+                SchemaType::String,
+                "A variable that is set to the output of the commands of the step",
+            ),
+            SchemaField::new(
+                "group",
+                list(step(true)),
+                "Steps that are executed as part of this step",
+            ),
+            SchemaField::new(
+                "parallel",
+                SchemaType::Boolean,
+                "Executes the steps of the group concurrently",
+            )
+            .default("false"),
+            SchemaField::new(
+                "depends-on",
+                list(SchemaType::String),
+                "The names of the steps that should complete before this one",
+            ),
+        ]);
+    }
+    SchemaType::Object(fields)
+}
+
+/// The entries of a step template, which are the entries of a step along with its parameters.
+/// A template can't use other templates.
+fn step_template() -> SchemaType {
+    let mut fields = match step(false) {
+        SchemaType::Object(fields) => fields,
+        _ => vec![],
+    };
+    fields.retain(|f| f.name != "use");
+    fields.push(SchemaField::new(
+        "params",
+        list(SchemaType::String),
+        "The names of the parameters, referenced as ${{ param.NAME }} in the entries",
+    ));
+    SchemaType::Object(fields)
+}
+
+/// The entries of a pipeline file as accepted by the parser of the pipeline.
+pub fn pipeline_schema() -> Vec<SchemaField> {
+    vec![
+        SchemaField::new(
+            "version",
+            SchemaType::Integer,
+            "The version of the pipeline format",
+        )
+        .default("1"),
+        SchemaField::new("name", SchemaType::String, "The name of the pipeline"),
+        SchemaField::new(
+            "runs-on",
+            SchemaType::OneOf(vec![SchemaType::String, docker(), kubernetes(), ssh()]),
+            "The platform of the run, either machine, the image of a docker container or its options",
+        )
+        .default("machine"),
+        SchemaField::new(
+            "cgroup",
+            SchemaType::Object(vec![
+                SchemaField::new(
+                    "memory-mb",
+                    SchemaType::Integer,
+                    "The memory limit of the run in megabytes",
+                ),
+                SchemaField::new(
+                    "cpu-quota-percent",
+                    SchemaType::Integer,
+                    "The percentage of a cpu that the run can use",
+                ),
+            ]),
+            "Resource limits of a run on a machine, applied with a cgroup v2 on linux",
+        ),
+        SchemaField::new(
+            "dispose",
+            SchemaType::Boolean,
+            "Removes the platform when the run ends",
+        )
+        .default("true"),
+        SchemaField::new(
+            "timeout-seconds",
+            SchemaType::Integer,
+            "Stops the run when it exceeds the timeout",
+        ),
+        SchemaField::new(
+            "max-log-lines",
+            SchemaType::Integer,
+            "The number of lines that the log of the run is truncated to",
+        ),
+        SchemaField::new(
+            "environment-file",
+            SchemaType::String,
+            "A dotenv file with environment variables of the run",
+        ),
+        SchemaField::new(
+            "env-passthrough",
+            list(SchemaType::String),
+            "Environment variables of the host that are passed to the run",
+        ),
+        SchemaField::new(
+            "path-prepend",
+            list(SchemaType::String),
+            "Directories that are prepended to the PATH of the steps",
+        ),
+        SchemaField::new(
+            "ssh-agent-forwarding",
+            SchemaType::Boolean,
+            "Forwards the ssh agent of the host to the steps",
+        )
+        .default("false"),
+        SchemaField::new(
+            "strict-substitution",
+            SchemaType::Boolean,
+            "Fails a step that references an undefined variable",
+        )
+        .default("false"),
+        SchemaField::new(
+            "prefix-steps",
+            SchemaType::Boolean,
+            "Prefixes the output lines of the commands with the name of their step",
+        )
+        .default("false"),
+        SchemaField::new(
+            "output-encoding",
+            SchemaType::Enum(&["utf8", "latin1"]),
+            "The encoding of the output of the commands, which is converted to UTF-8 for the logs",
+        )
+        .default("utf8"),
+        SchemaField::new(
+            "credentials",
+            list(SchemaType::String),
+            "The names of the credentials of the server that are provided to the steps",
+        ),
+        SchemaField::new(
+            "working-dir",
+            SchemaType::String,
+            "The default working directory of the steps",
+        ),
+        SchemaField::new(
+            "environment",
+            variables(),
+            "Environment variables of the run",
+        ),
+        SchemaField::new(
+            "variables",
+            variables(),
+            "Variables of the run that can be overridden when it starts",
+        ),
+        SchemaField::new(
+            "matrix",
+            map(list(SchemaType::Scalar)),
+            "Axes with values that the run is repeated for each combination of",
+        ),
+        SchemaField::new("artifacts", artifacts(), "Files transferred to and from the platform"),
+        SchemaField::new(
+            "step-templates",
+            map(step_template()),
+            "Named steps with parameters that steps can use",
+        ),
+        SchemaField::new("steps", list(step(false)), "The steps of the pipeline"),
+        SchemaField::new(
+            "notifications",
+            notifications(),
+            "Notifications sent when the run ends",
+        ),
+    ]
+}
+
+fn json_default(kind: &SchemaType, default: &str) -> Value {
+    match kind {
+        SchemaType::Integer => default.parse::<i64>().map(Value::from).unwrap_or_default(),
+        SchemaType::Boolean => Value::Bool(default == "true"),
+        SchemaType::List(_) => {
+            let values = default
+                .trim_matches(|c| c == '[' || c == ']')
+                .split(',')
+                .map(|v| Value::String(v.trim().to_string()))
+                .collect();
+            Value::Array(values)
+        }
+        _ => Value::String(default.to_string()),
+    }
+}
+
+fn json_object(fields: &[SchemaField]) -> Value {
+    let mut properties = Map::new();
+    let mut required = vec![];
+    for field in fields.iter() {
+        let mut property = json_type(&field.kind);
+        if let Value::Object(property) = &mut property {
+            property.insert("description".to_string(), json!(field.description));
+            if let Some(default) = field.default {
+                property.insert("default".to_string(), json_default(&field.kind, default));
+            }
+        }
+        properties.insert(field.name.to_string(), property);
+        if field.required {
+            required.push(field.name);
+        }
+    }
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+fn json_type(kind: &SchemaType) -> Value {
+    match kind {
+        SchemaType::String => json!({ "type": "string" }),
+        SchemaType::Integer => json!({ "type": "integer" }),
+        SchemaType::Boolean => json!({ "type": "boolean" }),
+        SchemaType::Scalar => json!({ "type": ["string", "number", "boolean"] }),
+        SchemaType::Enum(values) => json!({ "type": "string", "enum": values }),
+        SchemaType::List(kind) => json!({ "type": "array", "items": json_type(kind) }),
+        SchemaType::Map(kind) => {
+            json!({ "type": "object", "additionalProperties": json_type(kind) })
+        }
+        SchemaType::Object(fields) => json_object(fields),
+        SchemaType::OneOf(kinds) => {
+            json!({ "anyOf": kinds.iter().map(json_type).collect::<Vec<Value>>() })
+        }
+    }
+}
+
+/// Generates a JSON Schema of the pipeline format that editors can use to validate
+/// and complete pipeline files.
+pub fn json_schema() -> Value {
+    let mut schema = json_object(&pipeline_schema());
+    if let Value::Object(schema) = &mut schema {
+        schema.insert("$schema".to_string(), json!(JSON_SCHEMA_DRAFT));
+        schema.insert("title".to_string(), json!("bld pipeline"));
+    }
+    schema
+}
+
+pub(crate) fn markdown_type(kind: &SchemaType) -> String {
+    match kind {
+        SchemaType::String => "string".to_string(),
+        SchemaType::Integer => "integer".to_string(),
+        SchemaType::Boolean => "boolean".to_string(),
+        SchemaType::Scalar => "string, number or boolean".to_string(),
+        SchemaType::Enum(values) => {
+            let values: Vec<String> = values.iter().map(|v| format!("`{v}`")).collect();
+            format!("one of {}", values.join(", "))
+        }
+        SchemaType::List(kind) => format!("list of {}", markdown_type(kind)),
+        SchemaType::Map(kind) => format!("map of {}", markdown_type(kind)),
+        SchemaType::Object(_) => "object".to_string(),
+        SchemaType::OneOf(kinds) => kinds
+            .iter()
+            .map(markdown_type)
+            .collect::<Vec<String>>()
+            .join(" or "),
+    }
+}
+
+/// Collects the objects of a type along with the path of their entries.
+fn markdown_objects<'a>(
+    kind: &'a SchemaType,
+    path: &str,
+    objects: &mut Vec<(String, &'a [SchemaField])>,
+) {
+    match kind {
+        SchemaType::List(kind) => markdown_objects(kind, &format!("{path}[]"), objects),
+        SchemaType::Map(kind) => markdown_objects(kind, &format!("{path}.<name>"), objects),
+        SchemaType::OneOf(kinds) => {
+            for kind in kinds.iter() {
+                markdown_objects(kind, path, objects);
+            }
+        }
+        SchemaType::Object(fields) => objects.push((path.to_string(), fields)),
+        _ => {}
+    }
+}
+
+fn markdown_rows(fields: &[SchemaField], prefix: &str, out: &mut String) {
+    for field in fields.iter() {
+        let path = format!("{prefix}{}", field.name);
+        let required = if field.required { "yes" } else { "no" };
+        let default = field.default.map(|d| format!("`{d}`")).unwrap_or_default();
+        let _ = writeln!(
+            out,
+            "| `{path}` | {} | {required} | {default} | {} |",
+            markdown_type(&field.kind),
+            field.description
+        );
+        let mut objects = vec![];
+        markdown_objects(&field.kind, &path, &mut objects);
+        for (path, fields) in objects {
+            markdown_rows(fields, &format!("{path}."), out);
+        }
+    }
+}
+
+/// Generates a markdown reference of the pipeline format with a row for every entry.
+pub fn markdown_schema() -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# Pipeline format\n");
+    let _ = writeln!(out, "| Entry | Type | Required | Default | Description |");
+    let _ = writeln!(out, "| --- | --- | --- | --- | --- |");
+    markdown_rows(&pipeline_schema(), "", &mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lint_pipeline, lint_report, Pipeline};
+    use std::collections::HashSet;
+    use std::fs::{read_dir, read_to_string};
+    use yaml_rust::{Yaml, YamlLoader};
+
+    fn matches(kind: &SchemaType, yaml: &Yaml, path: &str) -> Result<(), String> {
+        let ok = match (kind, yaml) {
+            (SchemaType::String, Yaml::String(_)) => true,
+            (SchemaType::Integer, Yaml::Integer(_)) => true,
+            (SchemaType::Boolean, Yaml::Boolean(_)) => true,
+            (
+                SchemaType::Scalar,
+                Yaml::String(_) | Yaml::Integer(_) | Yaml::Real(_) | Yaml::Boolean(_),
+            ) => true,
+            (SchemaType::Enum(values), Yaml::String(value)) => values.contains(&value.as_str()),
+            (SchemaType::List(kind), Yaml::Array(entries)) => {
+                for (i, entry) in entries.iter().enumerate() {
+                    matches(kind, entry, &format!("{path}[{i}]"))?;
+                }
+                true
+            }
+            (SchemaType::Map(kind), Yaml::Hash(hash)) => {
+                for (key, value) in hash.iter() {
+                    let key = key.as_str().unwrap_or_default();
+                    matches(kind, value, &format!("{path}.{key}"))?;
+                }
+                true
+            }
+            (SchemaType::Object(fields), Yaml::Hash(_)) => return object(fields, yaml, path),
+            (SchemaType::OneOf(kinds), _) => kinds.iter().any(|k| matches(k, yaml, path).is_ok()),
+            _ => false,
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(format!("{path} is not a valid {}", markdown_type(kind)))
+        }
+    }
+
+    fn object(fields: &[SchemaField], yaml: &Yaml, path: &str) -> Result<(), String> {
+        let hash = yaml.as_hash().ok_or(format!("{path} is not an object"))?;
+        for (key, value) in hash.iter() {
+            let key = key.as_str().unwrap_or_default();
+            let field = fields
+                .iter()
+                .find(|f| f.name == key)
+                .ok_or(format!("{path}.{key} is not a known entry"))?;
+            matches(&field.kind, value, &format!("{path}.{key}"))?;
+        }
+        for field in fields.iter().filter(|f| f.required) {
+            if yaml[field.name].is_badvalue() {
+                return Err(format!("{path}.{} is required", field.name));
+            }
+        }
+        Ok(())
+    }
+
+    fn names(fields: &[SchemaField], names: &mut HashSet<&'static str>) {
+        fn kind_names(kind: &SchemaType, names: &mut HashSet<&'static str>) {
+            match kind {
+                SchemaType::List(kind) | SchemaType::Map(kind) => kind_names(kind, names),
+                SchemaType::OneOf(kinds) => kinds.iter().for_each(|k| kind_names(k, names)),
+                SchemaType::Object(fields) => super::tests::names(fields, names),
+                _ => {}
+            }
+        }
+        for field in fields.iter() {
+            names.insert(field.name);
+            kind_names(&field.kind, names);
+        }
+    }
+
+    #[test]
+    fn schema_contains_every_entry_of_the_parser() {
+        let source = include_str!("pipeline.rs");
+        let source = &source[..source.find("#[cfg(test)]").unwrap()];
+        let mut schema_names = HashSet::new();
+        names(&pipeline_schema(), &mut schema_names);
+
+        // the entries are read by the parser either by indexing a yaml value or through
+        // the helpers of the ssh section and the variables.
+        let mut missing = vec![];
+        for pattern in [
+            "[\"",
+            "entry(\"",
+            "variables(yaml, \"",
+            "variables(step, \"",
+        ] {
+            for (index, _) in source.match_indices(pattern) {
+                // skips lists of strings, which aren't indexing a yaml value.
+                let indexes = source[..index].ends_with(|c: char| c.is_alphanumeric() || c == ']');
+                if pattern == "[\"" && !indexes {
+                    continue;
+                }
+                let part = &source[index + pattern.len()..];
+                let name = &part[..part.find('"').unwrap()];
+                if !schema_names.contains(name) {
+                    missing.push(name.to_string());
+                }
+            }
+        }
+        assert!(
+            missing.is_empty(),
+            "entries missing from the schema: {missing:?}"
+        );
+    }
+
+    #[test]
+    fn fixture_pipelines_are_valid_for_the_schema_and_the_parser() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/pipelines");
+        let schema = pipeline_schema();
+        let mut count = 0;
+        for entry in read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let src = read_to_string(&path).unwrap();
+            let name = path.display();
+            Pipeline::parse(&src).unwrap_or_else(|e| panic!("{name}: {e}"));
+            let yaml = &YamlLoader::load_from_str(&src).unwrap()[0];
+            object(&schema, yaml, "").unwrap_or_else(|e| panic!("{name}: {e}"));
+            let problems = lint_pipeline(&src);
+            assert!(
+                problems.is_empty(),
+                "{name}: {}",
+                lint_report(&src, &problems)
+            );
+            count += 1;
+        }
+        assert!(count > 0);
+
+        let invalid = YamlLoader::load_from_str("runs-on:\n  image: ubuntu\n  pull: true").unwrap();
+        assert!(object(&schema, &invalid[0], "").is_err());
+    }
+
+    #[test]
+    fn json_schema_describes_the_entries() {
+        let schema = json_schema();
+
+        assert_eq!(schema["$schema"], JSON_SCHEMA_DRAFT);
+        assert_eq!(schema["additionalProperties"], false);
+        assert_eq!(schema["properties"]["dispose"]["default"], true);
+        let step = &schema["properties"]["steps"]["items"];
+        assert_eq!(
+            step["properties"]["call"]["items"]["anyOf"][1]["properties"]["rollup"]["enum"],
+            json!(["fail", "warn", "ignore"])
+        );
+        assert!(step["properties"]["group"]["items"]["properties"]["group"].is_null());
+        assert!(markdown_schema().contains("| `steps[].retry.attempts` | integer | yes |"));
+    }
+}
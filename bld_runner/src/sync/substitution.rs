@@ -0,0 +1,63 @@
+use bld_config::definitions::{ENV_TOKEN, RUN_PROPS_TOKEN, SECRET_TOKEN, VAR_TOKEN};
+
+/// A token prefixed with a backslash is kept as literal text instead of being substituted.
+const ESCAPED_TOKEN: &str = "\\bld:";
+const ESCAPED_PLACEHOLDER: &str = "\u{0}";
+const UNESCAPED_TOKEN: &str = "bld:";
+
+/// Replaces the escaped tokens of the text so that they are skipped by the substitution.
+pub(crate) fn protect_escaped(txt: &str) -> String {
+    txt.replace(ESCAPED_TOKEN, ESCAPED_PLACEHOLDER)
+}
+
+/// Turns the escaped tokens of the text back to their literal form without the backslash.
+pub(crate) fn restore_escaped(txt: &str) -> String {
+    txt.replace(ESCAPED_PLACEHOLDER, UNESCAPED_TOKEN)
+}
+
+/// Returns the first token of the text that was not substituted.
+pub(crate) fn unresolved_token(txt: &str) -> Option<String> {
+    [ENV_TOKEN, VAR_TOKEN, SECRET_TOKEN, RUN_PROPS_TOKEN]
+        .iter()
+        .filter_map(|prefix| {
+            txt.match_indices(prefix).find_map(|(index, _)| {
+                let name: String = txt[index + prefix.len()..]
+                    .chars()
+                    .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '-')
+                    .collect();
+                if name.is_empty() {
+                    None
+                } else {
+                    Some((index, format!("{prefix}{name}")))
+                }
+            })
+        })
+        .min_by_key(|(index, _)| *index)
+        .map(|(_, token)| token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substitution_finds_the_first_unresolved_token() {
+        assert_eq!(unresolved_token("echo hello"), None);
+        assert_eq!(unresolved_token("echo bld:var:"), None);
+        assert_eq!(
+            unresolved_token("docker build -t bld:var:IMAGE_TGA bld:env:HOME"),
+            Some("bld:var:IMAGE_TGA".to_string())
+        );
+        assert_eq!(
+            unresolved_token("echo bld:run:start_time bld:var:X"),
+            Some("bld:run:start_time".to_string())
+        );
+    }
+
+    #[test]
+    fn substitution_keeps_escaped_tokens_as_literal_text() {
+        let txt = protect_escaped("echo \\bld:var:NAME");
+        assert_eq!(unresolved_token(&txt), None);
+        assert_eq!(restore_escaped(&txt), "echo bld:var:NAME");
+    }
+}
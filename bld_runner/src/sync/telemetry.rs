@@ -0,0 +1,148 @@
+use anyhow::Result;
+use bld_core::execution::Execution;
+use opentelemetry::global::BoxedTracer;
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::sdk::propagation::TraceContextPropagator;
+use opentelemetry::trace::{FutureExt, SpanBuilder, StatusCode, TraceContextExt};
+use opentelemetry::{Context, KeyValue};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+/// The header of the W3C trace context that links the spans of a run to the trace of the
+/// request that started it.
+pub const TRACEPARENT: &str = "traceparent";
+
+pub type AtomicTracer = Arc<BoxedTracer>;
+
+/// The context of the span in a W3C traceparent, or none if the traceparent isn't valid.
+pub fn trace_parent_context(traceparent: &str) -> Option<Context> {
+    let carrier = HashMap::from([(TRACEPARENT.to_string(), traceparent.to_string())]);
+    let cx = TraceContextPropagator::new().extract(&carrier);
+    if cx.span().span_context().is_valid() {
+        Some(cx)
+    } else {
+        None
+    }
+}
+
+/// The W3C traceparent of the span of the context, or none if the context has no span.
+pub fn traceparent(cx: &Context) -> Option<String> {
+    let mut carrier = HashMap::new();
+    TraceContextPropagator::new().inject_context(cx, &mut carrier);
+    carrier.remove(TRACEPARENT)
+}
+
+/// Executes the future in a span that is a child of the parent context, with the span set as the
+/// current context while the future is polled so that the spans created by the future are its
+/// children. The span is marked as failed if the future returns an error. Without a tracer the
+/// future is executed in the parent context without a span.
+pub(crate) async fn traced<T, F>(
+    tracer: Option<&AtomicTracer>,
+    name: &'static str,
+    attributes: Vec<KeyValue>,
+    parent: &Context,
+    future: F,
+) -> Result<T>
+where
+    F: Future<Output = Result<T>>,
+{
+    let tracer = match tracer {
+        Some(tracer) => tracer,
+        None => return future.with_context(parent.clone()).await,
+    };
+    let span = SpanBuilder::from_name(name)
+        .with_attributes(attributes)
+        .start_with_context(tracer.as_ref(), parent);
+    let cx = parent.with_span(span);
+    let result = future.with_context(cx.clone()).await;
+    let span = cx.span();
+    if let Err(e) = &result {
+        span.set_status(StatusCode::Error, e.to_string());
+    }
+    span.end();
+    result
+}
+
+/// Records the trace of the current span on the run, so that the run can be looked up in the
+/// tracing backend by its trace id.
+pub(crate) fn persist_trace_id(ex: &Arc<Mutex<Execution>>) {
+    let cx = Context::current();
+    let span_context = cx.span().span_context().clone();
+    if span_context.is_valid() {
+        let _ = ex
+            .lock()
+            .unwrap()
+            .set_trace_id(&span_context.trace_id().to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use opentelemetry::global;
+    use opentelemetry::sdk::export::trace::stdout;
+    use std::io::{self, Write};
+
+    const PARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    /// Collects the spans that the stdout exporter writes.
+    #[derive(Clone, Debug, Default)]
+    struct SpanBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SpanBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn traceparents_are_validated() {
+        let cx = trace_parent_context(PARENT).unwrap();
+        assert_eq!(traceparent(&cx).as_deref(), Some(PARENT));
+        assert!(trace_parent_context("00-invalid-00f067aa0ba902b7-01").is_none());
+        assert!(trace_parent_context("").is_none());
+    }
+
+    #[test]
+    fn spans_are_children_of_the_parent_context() {
+        let buffer = SpanBuffer::default();
+        stdout::new_pipeline()
+            .with_writer(buffer.clone())
+            .install_simple();
+        let tracer = Arc::new(global::tracer("bld"));
+        let parent = trace_parent_context(PARENT).unwrap();
+
+        let result: Result<()> = futures::executor::block_on(traced(
+            Some(&tracer),
+            "build_runner",
+            vec![KeyValue::new("pipeline", "build.yaml")],
+            &parent,
+            async {
+                traced(Some(&tracer), "step", vec![], &Context::current(), async {
+                    Err(anyhow!("step failed"))
+                })
+                .await
+            },
+        ));
+        assert!(result.is_err());
+        global::shutdown_tracer_provider();
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let spans: Vec<&str> = output.lines().collect();
+        assert_eq!(spans.len(), 2);
+        assert!(spans[0].contains("name: \"step\""));
+        assert!(spans[0].contains("status_code: Error"));
+        assert!(spans[1].contains("name: \"build_runner\""));
+        assert!(spans[1].contains("parent_span_id: 00f067aa0ba902b7"));
+        assert!(spans
+            .iter()
+            .all(|s| s.contains("trace_id: 4bf92f3577b34da6a3ce929d0e0e4736")));
+    }
+}
@@ -0,0 +1,108 @@
+use anyhow::{anyhow, bail, Result};
+use minijinja::{context, Environment, UndefinedBehavior};
+use std::collections::HashMap;
+use std::fs::{create_dir_all, read_to_string, remove_dir_all, write};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Renders a template with the variables and the environment of a run, which are available
+/// to it as `vars` and `env`. Undefined values are an error when strict is set and are
+/// rendered as empty otherwise.
+pub fn render_template(
+    src: &str,
+    vars: &HashMap<String, String>,
+    env: &HashMap<String, String>,
+    strict: bool,
+) -> Result<String> {
+    let mut templates = Environment::new();
+    if strict {
+        templates.set_undefined_behavior(UndefinedBehavior::Strict);
+    }
+    templates
+        .render_str(src, context! { vars => vars, env => env })
+        .map_err(|e| anyhow!(e))
+}
+
+/// The rendered output of a template artifact, written to a directory of its own in the
+/// temp directory so that it keeps the file name of the template. The directory is removed
+/// when the output is dropped.
+pub struct RenderedTemplate {
+    dir: PathBuf,
+    pub path: PathBuf,
+}
+
+impl RenderedTemplate {
+    pub fn new(
+        from: &str,
+        vars: &HashMap<String, String>,
+        env: &HashMap<String, String>,
+        strict: bool,
+    ) -> Result<Self> {
+        let from_path = Path::new(from);
+        if !from_path.is_file() {
+            bail!("template artifact {from} should be a file");
+        }
+        let file_name = from_path
+            .file_name()
+            .ok_or_else(|| anyhow!("template artifact {from} should be a file"))?;
+        let src = read_to_string(from_path)?;
+        let content = render_template(&src, vars, env, strict)
+            .map_err(|e| anyhow!("unable to render template {from}, {e}"))?;
+        let dir = std::env::temp_dir().join(format!("bld-template-{}", Uuid::new_v4()));
+        create_dir_all(&dir)?;
+        let path = dir.join(file_name);
+        write(&path, content)?;
+        Ok(Self { dir, path })
+    }
+}
+
+impl Drop for RenderedTemplate {
+    fn drop(&mut self) {
+        let _ = remove_dir_all(&self.dir);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> (HashMap<String, String>, HashMap<String, String>) {
+        let vars = HashMap::from([("environment".to_string(), "production".to_string())]);
+        let env = HashMap::from([("PORT".to_string(), "8080".to_string())]);
+        (vars, env)
+    }
+
+    #[test]
+    fn template_is_rendered_with_the_variables_and_the_environment() {
+        let (vars, env) = context();
+        let src = "listen: {{ env.PORT }}\n{% if vars.environment == 'production' %}debug: false{% endif %}";
+
+        assert_eq!(
+            render_template(src, &vars, &env, false).unwrap(),
+            "listen: 8080\ndebug: false"
+        );
+        assert_eq!(
+            render_template("name: {{ vars.name }}", &vars, &env, false).unwrap(),
+            "name: "
+        );
+        assert!(render_template("name: {{ vars.name }}", &vars, &env, true).is_err());
+    }
+
+    #[test]
+    fn rendered_template_keeps_the_file_name_and_is_removed_when_dropped() {
+        let (vars, env) = context();
+        let dir = std::env::temp_dir().join(format!("bld-template-test-{}", Uuid::new_v4()));
+        create_dir_all(&dir).unwrap();
+        let from = dir.join("app.conf");
+        write(&from, "port={{ env.PORT }}").unwrap();
+
+        let rendered = RenderedTemplate::new(from.to_str().unwrap(), &vars, &env, false).unwrap();
+        let path = rendered.path.clone();
+        assert_eq!(path.file_name().unwrap(), "app.conf");
+        assert_eq!(read_to_string(&path).unwrap(), "port=8080");
+
+        drop(rendered);
+        assert!(!path.exists());
+        remove_dir_all(&dir).unwrap();
+    }
+}
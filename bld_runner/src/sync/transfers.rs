@@ -0,0 +1,119 @@
+use anyhow::{Error, Result};
+use bld_core::execution::Execution;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio::time::sleep;
+
+/// The interval that the stop signal of the run is checked while a transfer is in flight.
+const STOP_SIGNAL_INTERVAL: Duration = Duration::from_secs(1);
+
+pub enum TransferProgress {
+    Queued,
+    Started,
+    Finished(Duration),
+    Failed(Duration),
+}
+
+/// Limits the number of artifact transfers that run at the same time. A single scheduler
+/// is shared by all the runners of a run so that the limit applies to the whole run.
+pub struct TransferScheduler {
+    permits: Semaphore,
+}
+
+impl TransferScheduler {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            permits: Semaphore::new(limit.max(1)),
+        }
+    }
+
+    pub fn atom(limit: usize) -> Arc<Self> {
+        Arc::new(Self::new(limit))
+    }
+
+    async fn stop_signal(ex: &Arc<Mutex<Execution>>) -> Error {
+        loop {
+            let result = {
+                let exec = ex.lock().unwrap();
+                exec.check_stop_signal()
+            };
+            if let Err(e) = result {
+                return e;
+            }
+            sleep(STOP_SIGNAL_INTERVAL).await;
+        }
+    }
+
+    /// Runs the transfer once a slot is available, reporting its progress through the callback.
+    /// The transfer is aborted when the run is stopped, either while waiting for a slot or in flight.
+    pub async fn transfer<F, P>(
+        &self,
+        ex: &Arc<Mutex<Execution>>,
+        on_progress: P,
+        transfer: F,
+    ) -> Result<()>
+    where
+        F: Future<Output = Result<()>>,
+        P: Fn(TransferProgress),
+    {
+        if self.permits.available_permits() == 0 {
+            on_progress(TransferProgress::Queued);
+        }
+        let _permit = tokio::select! {
+            permit = self.permits.acquire() => permit?,
+            e = Self::stop_signal(ex) => return Err(e),
+        };
+        on_progress(TransferProgress::Started);
+        let start = Instant::now();
+        let result = tokio::select! {
+            result = transfer => result,
+            e = Self::stop_signal(ex) => Err(e),
+        };
+        match &result {
+            Ok(_) => on_progress(TransferProgress::Finished(start.elapsed())),
+            Err(_) => on_progress(TransferProgress::Failed(start.elapsed())),
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future::join_all;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn transfer_scheduler_limits_concurrent_transfers() {
+        let scheduler = TransferScheduler::new(2);
+        let ex = Execution::empty_atom();
+        let active = AtomicUsize::new(0);
+        let max_active = AtomicUsize::new(0);
+        let queued = AtomicUsize::new(0);
+        let transfers = (0..5).map(|_| {
+            scheduler.transfer(
+                &ex,
+                |progress| {
+                    if let TransferProgress::Queued = progress {
+                        queued.fetch_add(1, Ordering::SeqCst);
+                    }
+                },
+                async {
+                    let current = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_active.fetch_max(current, Ordering::SeqCst);
+                    sleep(Duration::from_millis(20)).await;
+                    active.fetch_sub(1, Ordering::SeqCst);
+                    Ok(())
+                },
+            )
+        });
+
+        let results = join_all(transfers).await;
+
+        assert!(results.iter().all(|r| r.is_ok()));
+        assert_eq!(max_active.load(Ordering::SeqCst), 2);
+        assert_eq!(queued.load(Ordering::SeqCst), 3);
+    }
+}
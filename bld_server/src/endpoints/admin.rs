@@ -0,0 +1,76 @@
+use crate::extractors::User;
+use crate::helpers::ConnectionRegistry;
+use actix_web::web::{Data, Path};
+use actix_web::{delete, get, HttpResponse, Responder};
+use bld_config::{AuthValidation, BldConfig};
+use tracing::info;
+
+/// Servers without authentication don't have users, so every request is treated as coming from an admin.
+pub(crate) fn is_admin(config: &BldConfig, user: &User) -> bool {
+    match config.local.auth {
+        AuthValidation::None => true,
+        _ => {
+            let name = user.name.trim_matches('"');
+            config.local.server.admins.iter().any(|a| a == name)
+        }
+    }
+}
+
+#[get("/admin/config")]
+pub async fn admin_config(user: Option<User>, config: Data<BldConfig>) -> impl Responder {
+    info!("Reached handler for /admin/config route");
+    let user = match user {
+        Some(user) => user,
+        None => return HttpResponse::Unauthorized().body(""),
+    };
+    if !is_admin(&config, &user) {
+        return HttpResponse::Forbidden().body("only admins can view the configuration");
+    }
+    match config.dump(false) {
+        Ok(content) => HttpResponse::Ok()
+            .content_type("application/yaml")
+            .body(content),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+/// Lists the exec and monit websockets that are open on the server.
+#[get("/admin/connections")]
+pub async fn admin_connections(
+    user: Option<User>,
+    config: Data<BldConfig>,
+    registry: Data<ConnectionRegistry>,
+) -> impl Responder {
+    info!("Reached handler for /admin/connections route");
+    let user = match user {
+        Some(user) => user,
+        None => return HttpResponse::Unauthorized().body(""),
+    };
+    if !is_admin(&config, &user) {
+        return HttpResponse::Forbidden().body("only admins can view the connections");
+    }
+    HttpResponse::Ok().json(registry.list())
+}
+
+/// Closes an exec or monit websocket of the server.
+#[delete("/admin/connections/{id}")]
+pub async fn admin_close_connection(
+    user: Option<User>,
+    config: Data<BldConfig>,
+    registry: Data<ConnectionRegistry>,
+    id: Path<String>,
+) -> impl Responder {
+    info!("Reached handler for /admin/connections/{{id}} route");
+    let user = match user {
+        Some(user) => user,
+        None => return HttpResponse::Unauthorized().body(""),
+    };
+    if !is_admin(&config, &user) {
+        return HttpResponse::Forbidden().body("only admins can close connections");
+    }
+    if registry.close(&id) {
+        HttpResponse::Ok().body("")
+    } else {
+        HttpResponse::NotFound().body(format!("connection {id} not found"))
+    }
+}
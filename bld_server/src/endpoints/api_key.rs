@@ -0,0 +1,153 @@
+use super::admin::is_admin;
+use crate::extractors::User;
+use crate::requests::ApiKeyInfo;
+use crate::responses::{ApiKeyEntry, ApiKeyResponse};
+use actix_web::web::{Data, Json, Path};
+use actix_web::{delete, get, post, HttpResponse, Responder};
+use anyhow::{bail, Result};
+use bld_config::BldConfig;
+use bld_core::database::api_keys::{self, InsertApiKey};
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sqlite::SqliteConnection;
+use tracing::info;
+use uuid::Uuid;
+
+/// Creates an api key for clients that can't go through the oauth2 flow, such as ci
+/// environments. The requests of the key are made as the user that created it, and the key is
+/// returned only in the response of this request.
+#[post("/api-keys")]
+pub async fn api_key(
+    user: Option<User>,
+    pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
+    info: Json<ApiKeyInfo>,
+) -> impl Responder {
+    info!("Reached handler for /api-keys route");
+    let user = match user {
+        Some(user) => user,
+        None => return HttpResponse::Unauthorized().body(""),
+    };
+    match create(pool.get_ref(), &info.name, &user) {
+        Ok(key) => HttpResponse::Ok().json(ApiKeyResponse::new(&info.name, &key)),
+        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+    }
+}
+
+/// Lists the api keys of the server, all of them for an admin and the ones that the user has
+/// created otherwise.
+#[get("/api-keys")]
+pub async fn list_api_keys(
+    user: Option<User>,
+    config: Data<BldConfig>,
+    pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
+) -> impl Responder {
+    info!("Reached handler for /api-keys route");
+    let user = match user {
+        Some(user) => user,
+        None => return HttpResponse::Unauthorized().body(""),
+    };
+    match list(pool.get_ref(), &config, &user) {
+        Ok(keys) => HttpResponse::Ok().json(keys),
+        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+    }
+}
+
+/// Revokes an api key, which is allowed for the user that created it and for admins.
+#[delete("/api-keys/{name}")]
+pub async fn revoke_api_key(
+    user: Option<User>,
+    config: Data<BldConfig>,
+    pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
+    name: Path<String>,
+) -> impl Responder {
+    info!("Reached handler for /api-keys/{{name}} route");
+    let user = match user {
+        Some(user) => user,
+        None => return HttpResponse::Unauthorized().body(""),
+    };
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+    };
+    let key = match api_keys::select_by_name(&mut conn, &name) {
+        Ok(key) => key,
+        Err(_) => return HttpResponse::NotFound().body(""),
+    };
+    if key.created_by != created_by(&user) && !is_admin(&config, &user) {
+        return HttpResponse::Forbidden()
+            .body("only the user that created the api key or an admin can revoke it");
+    }
+    match api_keys::delete_by_name(&mut conn, &name) {
+        Ok(()) => {
+            info!("revoked api key {name} as {}", created_by(&user));
+            HttpResponse::Ok().body("")
+        }
+        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+    }
+}
+
+/// The user that an api key is stored as created by, with the requests of a server without
+/// authentication being anonymous.
+fn created_by(user: &User) -> &str {
+    if user.name.is_empty() {
+        "anonymous"
+    } else {
+        &user.name
+    }
+}
+
+fn list(
+    pool: &Pool<ConnectionManager<SqliteConnection>>,
+    config: &BldConfig,
+    user: &User,
+) -> Result<Vec<ApiKeyEntry>> {
+    let mut conn = pool.get()?;
+    let keys = if is_admin(config, user) {
+        api_keys::select_all(&mut conn)?
+    } else {
+        api_keys::select_by_created_by(&mut conn, created_by(user))?
+    };
+    Ok(keys
+        .into_iter()
+        .map(|k| ApiKeyEntry {
+            name: k.name,
+            created_by: k.created_by,
+            date_created: k.date_created,
+        })
+        .collect())
+}
+
+fn create(
+    pool: &Pool<ConnectionManager<SqliteConnection>>,
+    name: &str,
+    user: &User,
+) -> Result<String> {
+    let is_valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !is_valid {
+        bail!("the name of an api key should contain only letters, digits, dashes and underscores");
+    }
+    let mut conn = pool.get()?;
+    if api_keys::select_by_name(&mut conn, name).is_ok() {
+        bail!("an api key named {name} already exists");
+    }
+    let key = format!(
+        "bld_{}{}",
+        Uuid::new_v4().to_simple(),
+        Uuid::new_v4().to_simple()
+    );
+    let id = Uuid::new_v4().to_string();
+    let created_by = created_by(user);
+    api_keys::insert(
+        &mut conn,
+        InsertApiKey {
+            id: &id,
+            name,
+            key_hash: &api_keys::hash(&key),
+            created_by,
+        },
+    )?;
+    info!("created api key {name} for {created_by}");
+    Ok(key)
+}
@@ -0,0 +1,84 @@
+use crate::extractors::User;
+use crate::helpers::run_artifacts;
+use crate::requests::ArtifactQueryInfo;
+use crate::responses::ArtifactsResponse;
+use actix_web::web::{Data, Path, Query};
+use actix_web::{get, HttpResponse, Responder};
+use anyhow::{anyhow, Result};
+use bld_core::database::pipeline_runs;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sqlite::SqliteConnection;
+use tokio::fs::File;
+use tokio_util::io::ReaderStream;
+use tracing::info;
+
+/// Returns the files of the `get` artifacts that a run has copied to the server.
+#[get("/runs/{run_id}/artifacts")]
+pub async fn artifacts(
+    user: Option<User>,
+    db_pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
+    run_id: Path<String>,
+) -> impl Responder {
+    info!("Reached handler for /runs/{{run_id}}/artifacts route");
+    if user.is_none() {
+        return HttpResponse::Unauthorized().body("");
+    }
+    match select_artifacts(db_pool.get_ref(), &run_id) {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(_) => HttpResponse::NotFound().body(format!("run {run_id} not found")),
+    }
+}
+
+/// Streams a file of the artifacts of a run. Only the files that are listed for the run can
+/// be downloaded, so the name can't refer to other paths of the server.
+#[get("/runs/{run_id}/artifact")]
+pub async fn artifact(
+    user: Option<User>,
+    db_pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
+    run_id: Path<String>,
+    params: Query<ArtifactQueryInfo>,
+) -> impl Responder {
+    info!("Reached handler for /runs/{{run_id}}/artifact route");
+    if user.is_none() {
+        return HttpResponse::Unauthorized().body("");
+    }
+    match open_artifact(db_pool.get_ref(), &run_id, &params.name).await {
+        Ok(file) => HttpResponse::Ok()
+            .content_type("application/octet-stream")
+            .streaming(ReaderStream::new(file)),
+        Err(e) => HttpResponse::NotFound().body(e.to_string()),
+    }
+}
+
+fn select_artifacts(
+    db_pool: &Pool<ConnectionManager<SqliteConnection>>,
+    run_id: &str,
+) -> Result<ArtifactsResponse> {
+    let mut conn = db_pool.get()?;
+    let run = pipeline_runs::select_by_id(&mut conn, run_id)?;
+    let entries = run_artifacts(&mut conn, run_id)?
+        .iter()
+        .map(|a| a.entry())
+        .collect();
+    Ok(ArtifactsResponse {
+        run_id: run.id,
+        state: run.state,
+        artifacts: entries,
+    })
+}
+
+async fn open_artifact(
+    db_pool: &Pool<ConnectionManager<SqliteConnection>>,
+    run_id: &str,
+    name: &str,
+) -> Result<File> {
+    let path = {
+        let mut conn = db_pool.get()?;
+        run_artifacts(&mut conn, run_id)?
+            .into_iter()
+            .find(|a| a.name == name)
+            .and_then(|a| a.path)
+            .ok_or_else(|| anyhow!("artifact {name} of run {run_id} not found"))?
+    };
+    File::open(path).await.map_err(|e| anyhow!(e))
+}
@@ -0,0 +1,81 @@
+use crate::extractors::User;
+use crate::requests::CheckpointsQueryInfo;
+use crate::responses::{CheckpointEntry, CheckpointsResponse};
+use actix_web::web::{Data, Path, Query};
+use actix_web::{get, HttpResponse, Responder};
+use anyhow::Result;
+use bld_core::database::{pipeline_run_checkpoints, pipeline_runs};
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sqlite::SqliteConnection;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+use tracing::info;
+
+pub const CHECKPOINTS_DEFAULT_WAIT_SECONDS: u64 = 60;
+const CHECKPOINTS_MAX_WAIT_SECONDS: u64 = 300;
+const CHECKPOINTS_POLL_INTERVAL_MILLIS: u64 = 1000;
+
+/// Returns the checkpoints that a run has reached. When a checkpoint to wait for is provided
+/// the response is held until the checkpoint is reached, the run ends or the timeout expires.
+#[get("/runs/{run_id}/checkpoints")]
+pub async fn checkpoints(
+    user: Option<User>,
+    db_pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
+    run_id: Path<String>,
+    params: Query<CheckpointsQueryInfo>,
+) -> impl Responder {
+    info!("Reached handler for /runs/{{run_id}}/checkpoints route");
+    if user.is_none() {
+        return HttpResponse::Unauthorized().body("");
+    }
+    match wait_checkpoints(db_pool.get_ref(), &run_id, &params).await {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(_) => HttpResponse::NotFound().body(""),
+    }
+}
+
+async fn wait_checkpoints(
+    db_pool: &Pool<ConnectionManager<SqliteConnection>>,
+    run_id: &str,
+    params: &CheckpointsQueryInfo,
+) -> Result<CheckpointsResponse> {
+    let timeout = params
+        .timeout
+        .unwrap_or(CHECKPOINTS_DEFAULT_WAIT_SECONDS)
+        .min(CHECKPOINTS_MAX_WAIT_SECONDS);
+    let timeout = Duration::from_secs(timeout);
+    let start = Instant::now();
+    loop {
+        let response = select_checkpoints(db_pool, run_id)?;
+        let done = match &params.wait {
+            Some(name) => {
+                response.reached(name) || response.run_ended() || start.elapsed() >= timeout
+            }
+            None => true,
+        };
+        if done {
+            return Ok(response);
+        }
+        sleep(Duration::from_millis(CHECKPOINTS_POLL_INTERVAL_MILLIS)).await;
+    }
+}
+
+fn select_checkpoints(
+    db_pool: &Pool<ConnectionManager<SqliteConnection>>,
+    run_id: &str,
+) -> Result<CheckpointsResponse> {
+    let mut conn = db_pool.get()?;
+    let run = pipeline_runs::select_by_id(&mut conn, run_id)?;
+    let entries = pipeline_run_checkpoints::select_by_run_id(&mut conn, run_id)?
+        .into_iter()
+        .map(|c| CheckpointEntry {
+            name: c.name,
+            date_created: c.date_created,
+        })
+        .collect();
+    Ok(CheckpointsResponse {
+        run_id: run.id,
+        state: run.state,
+        checkpoints: entries,
+    })
+}
@@ -1,10 +1,10 @@
 use crate::extractors::User;
 use actix_web::web::{Data, Json};
 use actix_web::{post, HttpResponse, Responder};
-use anyhow::{anyhow, Result};
+use anyhow::{bail, Result};
 use bld_core::proxies::PipelineFileSystemProxy;
-use bld_runner::Pipeline;
-use std::collections::HashMap;
+use bld_runner::{dependency_tree, DependencyNode, DependencyState};
+use std::collections::HashSet;
 use tracing::{debug, info};
 
 #[post("/deps")]
@@ -25,27 +25,27 @@ pub async fn deps(
 }
 
 fn do_deps(prx: &PipelineFileSystemProxy, name: &str) -> Result<Vec<String>> {
-    deps_recursive(prx, name).map(|mut hs| {
-        hs.remove(name);
-        hs.into_iter().map(|(n, _)| n).collect()
-    })
+    let tree = dependency_tree(prx, name);
+    let mut names = HashSet::new();
+    collect(&tree, &mut names)?;
+    names.remove(name);
+    Ok(names.into_iter().collect())
 }
 
-fn deps_recursive(prx: &PipelineFileSystemProxy, name: &str) -> Result<HashMap<String, String>> {
-    debug!("Parsing pipeline {name}");
-    let src = prx
-        .read(name)
-        .map_err(|_| anyhow!("Pipeline with name: {name} not found"))?;
-    let pipeline = Pipeline::parse(&src)?;
-    let mut set = HashMap::new();
-    set.insert(name.to_string(), src);
-    for step in pipeline.steps.iter() {
-        for call in &step.call {
-            let subset = deps_recursive(prx, call)?;
-            for (k, v) in subset {
-                set.insert(k, v);
-            }
+/// Collects the names of the pipelines of the tree. The pipelines that are part of a cycle
+/// are already included by their first occurrence.
+fn collect(node: &DependencyNode, names: &mut HashSet<String>) -> Result<()> {
+    debug!("Collecting pipeline {}", node.pipeline);
+    match &node.state {
+        DependencyState::Resolved => {
+            names.insert(node.pipeline.to_string());
         }
+        DependencyState::Cycle => return Ok(()),
+        DependencyState::Missing => bail!("Pipeline with name: {} not found", node.pipeline),
+        DependencyState::Invalid(e) => bail!("{e}"),
     }
-    Ok(set)
+    for child in node.children.iter() {
+        collect(child, names)?;
+    }
+    Ok(())
 }
@@ -1,9 +1,38 @@
+use crate::helpers::{check_health, Platforms, HEALTH_PROBE_TIMEOUT_MILLIS};
+use crate::responses::HealthStatus;
 use actix_web::web::{Data, Json};
-use actix_web::{post, HttpResponse, Responder};
+use actix_web::{get, post, HttpResponse, Responder};
 use async_raft::raft::{AppendEntriesRequest, InstallSnapshotRequest, VoteRequest};
+use bld_config::BldConfig;
 use bld_core::high_avail::{AgentRequest, HighAvail};
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sqlite::SqliteConnection;
+use std::sync::Arc;
+use std::time::Duration;
 use tracing::{debug, error, info};
 
+/// Probes the subsystems of the server for the health checks of a load balancer, responding
+/// with a 503 status when the server is down.
+#[get("/ha/health")]
+pub async fn ha_health(
+    config: Data<BldConfig>,
+    db_pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
+    platforms: Data<Platforms>,
+) -> impl Responder {
+    info!("Reached handler for /ha/health route");
+    let health = check_health(
+        Arc::clone(&config),
+        db_pool.get_ref().clone(),
+        platforms.docker_configured(),
+        Duration::from_millis(HEALTH_PROBE_TIMEOUT_MILLIS),
+    )
+    .await;
+    match health.status {
+        HealthStatus::Down => HttpResponse::ServiceUnavailable().json(health),
+        HealthStatus::Ok | HealthStatus::Degraded => HttpResponse::Ok().json(health),
+    }
+}
+
 #[post("/ha/appendEntries")]
 pub async fn ha_append_entries(
     body: Json<AppendEntriesRequest<AgentRequest>>,
@@ -1,6 +1,8 @@
 use crate::extractors::User;
+use crate::requests::HistQueryInfo;
 use crate::responses::HistoryEntry;
-use actix_web::{get, web::Data, HttpResponse, Responder};
+use actix_web::web::{Data, Query};
+use actix_web::{get, HttpResponse, Responder};
 use anyhow::Result;
 use bld_core::database::pipeline_runs;
 use diesel::r2d2::{ConnectionManager, Pool};
@@ -11,33 +13,26 @@ use tracing::info;
 pub async fn hist(
     user: Option<User>,
     db_pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
+    params: Query<HistQueryInfo>,
 ) -> impl Responder {
     info!("Reached handler for /hist route");
     if user.is_none() {
         return HttpResponse::Unauthorized().body("");
     }
-    match history_info(db_pool.get_ref()) {
+    match history_info(db_pool.get_ref(), &params.into_inner()) {
         Ok(hist) => HttpResponse::Ok().json(hist),
-        Err(_) => HttpResponse::BadRequest().body(""),
+        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
     }
 }
 
-fn history_info(db_pool: &Pool<ConnectionManager<SqliteConnection>>) -> Result<Vec<HistoryEntry>> {
+fn history_info(
+    db_pool: &Pool<ConnectionManager<SqliteConnection>>,
+    params: &HistQueryInfo,
+) -> Result<Vec<HistoryEntry>> {
+    let filter = params.filter()?;
     let mut conn = db_pool.get()?;
-    let history: Vec<HistoryEntry> = pipeline_runs::select_all(&mut conn)
-        .map(|entries| {
-            entries
-                .into_iter()
-                .map(|p| HistoryEntry {
-                    name: p.name,
-                    id: p.id,
-                    user: p.user,
-                    state: p.state,
-                    start_date_time: p.start_date_time,
-                    end_date_time: p.end_date_time.unwrap_or_default(),
-                })
-                .collect()
-        })
+    let history: Vec<HistoryEntry> = pipeline_runs::select_with_filters(&mut conn, &filter)
+        .map(|entries| entries.into_iter().map(HistoryEntry::from).collect())
         .unwrap_or_else(|_| vec![]);
     Ok(history)
 }
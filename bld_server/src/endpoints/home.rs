@@ -1,4 +1,9 @@
-use actix_web::{get, HttpResponse, Responder};
+use crate::helpers::Platforms;
+use crate::responses::CapabilitiesResponse;
+use actix_web::http::header::ACCEPT;
+use actix_web::web::Data;
+use actix_web::{get, HttpRequest, HttpResponse, Responder};
+use bld_config::BldConfig;
 use tracing::info;
 
 const HOME_HTML: &str = r"
@@ -29,8 +34,25 @@ const HOME_HTML: &str = r"
 </html>
 ";
 
+/// Returns the capabilities of the server when json is requested, otherwise a page
+/// stating that the server is running.
 #[get("/")]
-async fn home() -> impl Responder {
+async fn home(
+    req: HttpRequest,
+    config: Data<BldConfig>,
+    platforms: Data<Platforms>,
+) -> impl Responder {
     info!("Reached handler for / route");
+    let accepts_json = req
+        .headers()
+        .get(ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .map(|accept| accept.contains("application/json"))
+        .unwrap_or(false);
+    if accepts_json {
+        let mut capabilities = CapabilitiesResponse::new(&config);
+        capabilities.platforms = platforms.available();
+        return HttpResponse::Ok().json(capabilities);
+    }
     HttpResponse::Ok().body(HOME_HTML)
 }
@@ -1,6 +1,8 @@
 use crate::extractors::User;
+use crate::helpers::with_etag;
+use actix_web::http::header::ContentType;
 use actix_web::web::{Data, Json};
-use actix_web::{post, HttpResponse, Responder};
+use actix_web::{post, HttpRequest, HttpResponse, Responder};
 use bld_core::proxies::PipelineFileSystemProxy;
 use tracing::info;
 
@@ -9,13 +11,14 @@ pub async fn inspect(
     user: Option<User>,
     prx: Data<PipelineFileSystemProxy>,
     body: Json<String>,
+    req: HttpRequest,
 ) -> impl Responder {
     info!("Reached handler for /inspect route");
     if user.is_none() {
         return HttpResponse::Unauthorized().body("");
     }
     match prx.read(&body.into_inner()) {
-        Ok(content) => HttpResponse::Ok().body(content),
-        Err(_) => HttpResponse::BadRequest().body(""),
+        Ok(content) => with_etag(&req, ContentType::plaintext(), content),
+        Err(_) => HttpResponse::BadRequest().body("Pipeline not found"),
     }
 }
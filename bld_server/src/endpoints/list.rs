@@ -1,5 +1,8 @@
 use crate::extractors::User;
-use actix_web::{get, web::Data, HttpResponse};
+use crate::helpers::with_etag;
+use crate::responses::PipelineEntry;
+use actix_web::http::header::ContentType;
+use actix_web::{get, web::Data, HttpRequest, HttpResponse};
 use anyhow::Result;
 use bld_core::database::pipeline;
 use bld_core::proxies::PipelineFileSystemProxy;
@@ -13,13 +16,16 @@ pub async fn list(
     user: Option<User>,
     prx: Data<PipelineFileSystemProxy>,
     pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
+    req: HttpRequest,
 ) -> HttpResponse {
     info!("Reached handler for /list route");
     if user.is_none() {
         return HttpResponse::Unauthorized().body("");
     }
-    match find_pipelines(prx.get_ref(), pool.get_ref()) {
-        Ok(pips) => HttpResponse::Ok().body(pips),
+    let body = find_pipelines(prx.get_ref(), pool.get_ref())
+        .and_then(|pips| serde_json::to_string(&pips).map_err(anyhow::Error::from));
+    match body {
+        Ok(body) => with_etag(&req, ContentType::json(), body),
         Err(_) => HttpResponse::BadRequest().body("no pipelines found"),
     }
 }
@@ -27,14 +33,21 @@ pub async fn list(
 fn find_pipelines(
     prx: &PipelineFileSystemProxy,
     pool: &Pool<ConnectionManager<SqliteConnection>>,
-) -> Result<String> {
+) -> Result<Vec<PipelineEntry>> {
     let mut conn = pool.get()?;
     let pips = pipeline::select_all(&mut conn)?
-        .iter()
-        .map(|p| (p, prx.path(&p.name)))
+        .into_iter()
+        .map(|p| {
+            let path = prx.path(&p.name);
+            (p, path)
+        })
         .filter(|(_, p)| p.is_ok())
         .filter(|(_, p)| p.as_ref().unwrap().is_yaml())
-        .map(|(p, _)| p.name.clone())
-        .fold(String::new(), |acc, n| format!("{acc}{n}\n"));
+        .map(|(p, _)| PipelineEntry {
+            id: p.id,
+            name: p.name,
+            date_created: p.date_created,
+        })
+        .collect();
     Ok(pips)
 }
@@ -0,0 +1,72 @@
+use crate::extractors::User;
+use crate::helpers::PipelineLocks;
+use crate::requests::LockInfo;
+use crate::responses::LockResponse;
+use actix_web::web::{Data, Json, Path};
+use actix_web::{delete, post, HttpRequest, HttpResponse, Responder};
+use bld_core::proxies::PipelineFileSystemProxy;
+use tracing::info;
+
+pub const LOCK_TOKEN_HEADER: &str = "X-Bld-Lock-Token";
+
+pub(crate) fn holder(user: &User) -> &str {
+    if user.name.is_empty() {
+        "anonymous"
+    } else {
+        &user.name
+    }
+}
+
+/// Acquires an advisory lock on a pipeline that expires after the requested ttl. Pushes
+/// to a locked pipeline are rejected unless they provide the token of the lock. A lock through
+/// an alias is held on the pipeline that the alias resolves to.
+#[post("/pipelines/{name:.*}/lock")]
+pub async fn lock(
+    user: Option<User>,
+    locks: Data<PipelineLocks>,
+    prx: Data<PipelineFileSystemProxy>,
+    name: Path<String>,
+    info: Json<LockInfo>,
+) -> impl Responder {
+    info!("Reached handler for /pipelines/{{name}}/lock route");
+    let user = match user {
+        Some(user) => user,
+        None => return HttpResponse::Unauthorized().body(""),
+    };
+    let info = info.into_inner();
+    let name = prx.canonical_name(&name);
+    match locks.acquire(
+        &name,
+        holder(&user),
+        info.ttl_seconds,
+        info.token.as_deref(),
+    ) {
+        Ok(token) => HttpResponse::Ok().json(LockResponse::new(&name, &token)),
+        Err(e) => HttpResponse::Conflict().body(e.to_string()),
+    }
+}
+
+#[delete("/pipelines/{name:.*}/lock")]
+pub async fn unlock(
+    user: Option<User>,
+    locks: Data<PipelineLocks>,
+    prx: Data<PipelineFileSystemProxy>,
+    name: Path<String>,
+    req: HttpRequest,
+) -> impl Responder {
+    info!("Reached handler for /pipelines/{{name}}/lock route");
+    let user = match user {
+        Some(user) => user,
+        None => return HttpResponse::Unauthorized().body(""),
+    };
+    let token = req
+        .headers()
+        .get(LOCK_TOKEN_HEADER)
+        .and_then(|t| t.to_str().ok())
+        .unwrap_or_default();
+    let name = prx.canonical_name(&name);
+    match locks.release(&name, holder(&user), token) {
+        Ok(()) => HttpResponse::Ok().body(""),
+        Err(e) => HttpResponse::Conflict().body(e.to_string()),
+    }
+}
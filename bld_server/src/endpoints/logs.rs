@@ -0,0 +1,81 @@
+use crate::extractors::User;
+use crate::requests::LogsQueryInfo;
+use actix_web::web::{Data, Path, Query};
+use actix_web::{get, HttpResponse, Responder};
+use anyhow::Result;
+use bld_config::{path, BldConfig};
+use bld_core::database::pipeline_runs::{
+    self, PR_STATE_FAULTED, PR_STATE_FINISHED, PR_STATE_INTERRUPTED,
+};
+use bld_core::scanner::IncrementalReader;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sqlite::SqliteConnection;
+use std::io::SeekFrom;
+use std::path::PathBuf;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, Take};
+use tokio_util::io::ReaderStream;
+use tracing::info;
+
+pub const LOGS_OFFSET_HEADER: &str = "X-Bld-Offset";
+pub const LOGS_FINISHED_HEADER: &str = "X-Bld-Finished";
+
+/// Returns the content of the logs of a run after the provided offset, along with the new
+/// offset and whether the run has finished, so that clients can poll for new content.
+/// The logs of a finished run are streamed from the file instead of being read in memory.
+#[get("/logs/{run_id}")]
+pub async fn logs(
+    user: Option<User>,
+    config: Data<BldConfig>,
+    db_pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
+    run_id: Path<String>,
+    params: Query<LogsQueryInfo>,
+) -> impl Responder {
+    info!("Reached handler for /logs route");
+    if user.is_none() {
+        return HttpResponse::Unauthorized().body("");
+    }
+    let finished = match run_finished(db_pool.get_ref(), &run_id) {
+        Ok(finished) => finished,
+        Err(_) => return HttpResponse::NotFound().body(format!("run {run_id} not found")),
+    };
+    let path = path![&config.local.logs, run_id.as_str()];
+    let offset = params.offset.unwrap_or(0);
+    if finished {
+        return match stream_logs(path, offset).await {
+            Ok((end, stream)) => HttpResponse::Ok()
+                .insert_header((LOGS_OFFSET_HEADER, end.to_string()))
+                .insert_header((LOGS_FINISHED_HEADER, finished.to_string()))
+                .streaming(stream),
+            Err(_) => HttpResponse::NotFound().body(format!("no logs found for run {run_id}")),
+        };
+    }
+    let mut reader = IncrementalReader::new(path, offset);
+    // the remaining content is flushed only when the run has finished since an
+    // incomplete line could still be written to.
+    match reader.read(finished) {
+        Ok(content) => HttpResponse::Ok()
+            .insert_header((LOGS_OFFSET_HEADER, reader.offset().to_string()))
+            .insert_header((LOGS_FINISHED_HEADER, finished.to_string()))
+            .body(content),
+        Err(_) => HttpResponse::BadRequest().body(""),
+    }
+}
+
+/// Opens the log file at the offset and returns the end offset along with a stream of the
+/// content up to it, so that large logs are sent in chunks.
+async fn stream_logs(path: PathBuf, offset: u64) -> Result<(u64, ReaderStream<Take<File>>)> {
+    let mut file = File::open(path).await?;
+    let len = file.metadata().await?.len();
+    let offset = offset.min(len);
+    file.seek(SeekFrom::Start(offset)).await?;
+    Ok((len, ReaderStream::new(file.take(len - offset))))
+}
+
+fn run_finished(db_pool: &Pool<ConnectionManager<SqliteConnection>>, run_id: &str) -> Result<bool> {
+    let mut conn = db_pool.get()?;
+    let run = pipeline_runs::select_by_id(&mut conn, run_id)?;
+    Ok(run.state == PR_STATE_FINISHED
+        || run.state == PR_STATE_FAULTED
+        || run.state == PR_STATE_INTERRUPTED)
+}
@@ -0,0 +1,38 @@
+use crate::extractors::User;
+use crate::helpers::gather_metrics;
+use actix_web::web::Data;
+use actix_web::{get, HttpResponse, Responder};
+use anyhow::Result;
+use bld_config::BldConfig;
+use bld_core::proxies::PipelineFileSystemProxy;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sqlite::SqliteConnection;
+use prometheus::TEXT_FORMAT;
+use tracing::info;
+
+/// Returns the metrics of the runs of the server in the Prometheus text format.
+#[get("/metrics")]
+pub async fn metrics(
+    user: Option<User>,
+    config: Data<BldConfig>,
+    db_pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
+    prx: Data<PipelineFileSystemProxy>,
+) -> impl Responder {
+    info!("Reached handler for /metrics route");
+    if user.is_none() {
+        return HttpResponse::Unauthorized().body("");
+    }
+    match select_metrics(config.get_ref(), db_pool.get_ref(), prx.get_ref()) {
+        Ok(body) => HttpResponse::Ok().content_type(TEXT_FORMAT).body(body),
+        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+    }
+}
+
+fn select_metrics(
+    config: &BldConfig,
+    db_pool: &Pool<ConnectionManager<SqliteConnection>>,
+    prx: &PipelineFileSystemProxy,
+) -> Result<String> {
+    let mut conn = db_pool.get()?;
+    gather_metrics(config, &mut conn, prx.cache_stats())
+}
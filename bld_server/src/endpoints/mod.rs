@@ -1,25 +1,53 @@
+mod admin;
+mod api_key;
+mod artifacts;
 mod auth_redirect;
+mod checkpoints;
 mod deps;
 mod ha;
 mod hist;
 mod home;
 mod inspect;
 mod list;
+mod lock;
+mod logs;
+mod metrics;
+mod pipeline;
 mod pull;
 mod push;
+mod queue;
 mod remove;
 mod run;
+mod schedule;
+mod search;
+mod stats;
 mod stop;
+mod summary;
+mod tree;
 
+pub use admin::*;
+pub use api_key::*;
+pub use artifacts::*;
 pub use auth_redirect::*;
+pub use checkpoints::*;
 pub use deps::*;
 pub use ha::*;
 pub use hist::*;
 pub use home::*;
 pub use inspect::*;
 pub use list::*;
+pub use lock::*;
+pub use logs::*;
+pub use metrics::*;
+pub use pipeline::*;
 pub use pull::*;
 pub use push::*;
+pub use queue::*;
 pub use remove::*;
 pub use run::*;
+pub use schedule::*;
+pub use search::*;
+pub use stats::*;
 pub use stop::*;
+pub use summary::*;
+pub use tree::*;
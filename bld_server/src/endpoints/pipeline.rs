@@ -0,0 +1,34 @@
+use crate::extractors::User;
+use actix_web::web::{Data, Path};
+use actix_web::{get, HttpResponse, Responder};
+use bld_core::proxies::PipelineFileSystemProxy;
+use tracing::info;
+
+/// Returns the content of a pipeline. A pipeline requested by an alias has a Link header
+/// pointing to the canonical name of the pipeline.
+#[get("/pipeline/{name:.*}")]
+pub async fn pipeline(
+    user: Option<User>,
+    prx: Data<PipelineFileSystemProxy>,
+    path: Path<String>,
+) -> impl Responder {
+    info!("Reached handler for /pipeline route");
+    if user.is_none() {
+        return HttpResponse::Unauthorized().body("");
+    }
+    let name = path.into_inner();
+    let canonical = prx.canonical_name(&name);
+    match prx.read(&canonical) {
+        Ok(content) => {
+            let mut response = HttpResponse::Ok();
+            if canonical != name {
+                response.insert_header((
+                    "Link",
+                    format!("</pipeline/{canonical}>; rel=\"canonical\""),
+                ));
+            }
+            response.body(content)
+        }
+        Err(_) => HttpResponse::NotFound().body(""),
+    }
+}
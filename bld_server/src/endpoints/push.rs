@@ -1,41 +1,295 @@
+use super::lock::holder;
 use crate::extractors::User;
+use crate::helpers::{PipelineLocks, Platforms};
 use crate::requests::PushInfo;
+use crate::responses::{AtomicPushResponse, PushResult};
 use actix_web::web::{Data, Json};
 use actix_web::{post, HttpResponse, Responder};
-use anyhow::Result;
+use anyhow::{bail, Result};
+use bld_config::BldConfig;
 use bld_core::database::pipeline;
-use bld_core::proxies::PipelineFileSystemProxy;
+use bld_core::proxies::{content_hash, PipelineFileSystemProxy};
+use bld_runner::{lint_pipeline, lint_report, Pipeline};
 use diesel::r2d2::{ConnectionManager, Pool};
 use diesel::sqlite::SqliteConnection;
+use std::collections::HashSet;
 use tracing::info;
 use uuid::Uuid;
 
 #[post("/push")]
 pub async fn push(
     user: Option<User>,
+    config: Data<BldConfig>,
     prx: Data<PipelineFileSystemProxy>,
     pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
+    locks: Data<PipelineLocks>,
+    platforms: Data<Platforms>,
     info: Json<PushInfo>,
 ) -> impl Responder {
     info!("Reached handler for /push route");
     if user.is_none() {
         return HttpResponse::Unauthorized().body("");
     }
-    match do_push(prx.get_ref(), pool.get_ref(), &info.into_inner()) {
-        Ok(()) => HttpResponse::Ok().body(""),
-        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+    let info = info.into_inner();
+    if config.local.server.strict_pipelines {
+        let problems = lint_pipeline(&info.content);
+        if !problems.is_empty() {
+            return HttpResponse::BadRequest().body(format!(
+                "pipeline {} has {} problem(s)\n{}",
+                info.name,
+                problems.len(),
+                lint_report(&info.content, &problems)
+            ));
+        }
+    }
+    // the warnings are returned in the body so that clients can print them.
+    match push_locked(prx.get_ref(), pool.get_ref(), locks.get_ref(), &info).await {
+        Ok(Ok(())) => HttpResponse::Ok().body(platform_warnings(&platforms, &info).join("\n")),
+        Ok(Err(e)) => HttpResponse::BadRequest().body(e.to_string()),
+        Err(e) => HttpResponse::Conflict().body(e.to_string()),
     }
 }
 
-fn do_push(
+/// Pushes a set of pipelines, such as a pipeline along with the pipelines that it calls, where
+/// either every pipeline is pushed or none of them. The pipelines are locked during the push so
+/// that other pushes to them are rejected, and they are replaced only if all of them are valid
+/// and the pipelines that they call are either part of the set or already on the server.
+#[post("/push/atomic")]
+pub async fn push_atomic(
+    user: Option<User>,
+    config: Data<BldConfig>,
+    prx: Data<PipelineFileSystemProxy>,
+    locks: Data<PipelineLocks>,
+    platforms: Data<Platforms>,
+    info: Json<Vec<PushInfo>>,
+) -> impl Responder {
+    info!("Reached handler for /push/atomic route");
+    let user = match user {
+        Some(user) => user,
+        None => return HttpResponse::Unauthorized().body(""),
+    };
+    let pipelines = info.into_inner();
+    let acquired = match lock_all(locks.get_ref(), prx.get_ref(), holder(&user), &pipelines) {
+        Ok(acquired) => acquired,
+        Err(e) => return HttpResponse::Conflict().body(e.to_string()),
+    };
+    let results = validate_all(config.get_ref(), prx.get_ref(), &platforms, &pipelines);
+    let response = if results.iter().any(|r| !r.errors.is_empty()) {
+        HttpResponse::BadRequest().json(AtomicPushResponse::new(results, None))
+    } else {
+        let contents: Vec<(String, String)> = pipelines
+            .into_iter()
+            .map(|info| (info.name, info.content))
+            .collect();
+        match prx.create_all(&contents) {
+            Ok(()) => HttpResponse::Ok().json(AtomicPushResponse::new(results, None)),
+            Err(e) => HttpResponse::BadRequest()
+                .json(AtomicPushResponse::new(results, Some(e.to_string()))),
+        }
+    };
+    for (name, token) in acquired {
+        let _ = locks.release(&name, holder(&user), &token);
+    }
+    response
+}
+
+/// Locks the pipelines of an atomic push that aren't already locked with a token of the push,
+/// returning the locks that should be released after it. Nothing is left locked on a failure.
+fn lock_all(
+    locks: &PipelineLocks,
+    prx: &PipelineFileSystemProxy,
+    holder: &str,
+    pipelines: &[PushInfo],
+) -> Result<Vec<(String, String)>> {
+    let mut acquired: Vec<(String, String)> = vec![];
+    for info in pipelines {
+        let name = prx.canonical_name(&info.name);
+        let result = match &info.lock_token {
+            Some(token) => locks.check(&name, Some(token)),
+            None => locks
+                .acquire(&name, holder, None, None)
+                .map(|token| acquired.push((name, token))),
+        };
+        if let Err(e) = result {
+            for (name, token) in acquired {
+                let _ = locks.release(&name, holder, &token);
+            }
+            return Err(e);
+        }
+    }
+    Ok(acquired)
+}
+
+/// Validates every pipeline of an atomic push, including the pipelines that they call.
+fn validate_all(
+    config: &BldConfig,
+    prx: &PipelineFileSystemProxy,
+    platforms: &Platforms,
+    pipelines: &[PushInfo],
+) -> Vec<PushResult> {
+    let names: HashSet<&str> = pipelines.iter().map(|info| info.name.as_str()).collect();
+    let mut seen = HashSet::new();
+    pipelines
+        .iter()
+        .map(|info| {
+            let mut errors = vec![];
+            if !seen.insert(info.name.as_str()) {
+                errors.push(format!("pipeline {} is pushed more than once", info.name));
+            }
+            if let Some(base_hash) = &info.base_hash {
+                let hash = prx.hash(&info.name);
+                if matches!(&hash, Ok(hash) if hash != base_hash) {
+                    errors.push(format!(
+                        "pipeline {} has changed since it was pulled",
+                        info.name
+                    ));
+                }
+            }
+            match Pipeline::parse(&info.content) {
+                Ok(pipeline) => {
+                    for call in pipeline.calls() {
+                        if !names.contains(call) && prx.read(call).is_err() {
+                            errors.push(format!(
+                                "pipeline {call} isn't part of the push and doesn't exist on the server"
+                            ));
+                        }
+                    }
+                }
+                Err(e) => errors.push(e.to_string()),
+            }
+            if config.local.server.strict_pipelines {
+                let problems = lint_pipeline(&info.content);
+                if !problems.is_empty() {
+                    errors.push(lint_report(&info.content, &problems));
+                }
+            }
+            let mut result = PushResult::new(&info.name, errors);
+            result.warnings = platform_warnings(platforms, info);
+            result
+        })
+        .collect()
+}
+
+/// Warns about a pipeline that runs on a platform that isn't available on the server, since
+/// its runs will be rejected until the platform becomes available.
+fn platform_warnings(platforms: &Platforms, info: &PushInfo) -> Vec<String> {
+    Pipeline::parse(&info.content)
+        .ok()
+        .and_then(|pipeline| platforms.check(&info.name, &pipeline.runs_on).err())
+        .map(|e| vec![format!("warning: {e}")])
+        .unwrap_or_default()
+}
+
+/// Pushes the pipeline while holding the locks, so that the pipeline can't change between the
+/// conflict checks and the write. The push is rejected if the pipeline, under the name that its
+/// aliases resolve to, is locked by someone else or if it has changed since the version that the
+/// client based its edit on. The outer error is a conflict while the inner one is a failed write.
+async fn push_locked(
     prx: &PipelineFileSystemProxy,
     pool: &Pool<ConnectionManager<SqliteConnection>>,
+    locks: &PipelineLocks,
     info: &PushInfo,
+) -> Result<Result<()>> {
+    let name = prx.canonical_name(&info.name);
+    locks
+        .with_lock(&name, info.lock_token.as_deref(), || {
+            if let (Some(base_hash), Ok(content)) = (&info.base_hash, prx.read(&name)) {
+                let hash = content_hash(&content);
+                if *base_hash != hash {
+                    bail!(
+                        "pipeline {} has changed since it was pulled, its current hash is {hash}",
+                        info.name
+                    );
+                }
+            }
+            Ok(do_push(prx, pool, &name, &info.content))
+        })
+        .await?
+}
+
+fn do_push(
+    prx: &PipelineFileSystemProxy,
+    pool: &Pool<ConnectionManager<SqliteConnection>>,
+    name: &str,
+    content: &str,
 ) -> Result<()> {
     let mut conn = pool.get()?;
-    if pipeline::select_by_name(&mut conn, &info.name).is_err() {
+    if pipeline::select_by_name(&mut conn, name).is_err() {
         let id = Uuid::new_v4().to_string();
-        pipeline::insert(&mut conn, &id, &info.name)?;
+        pipeline::insert(&mut conn, &id, name)?;
+    }
+    prx.create(name, content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bld_core::database::new_connection_pool;
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_dir_all};
+    use std::sync::Arc;
+
+    #[test]
+    fn atomic_push_validates_the_set_and_locks_every_pipeline() {
+        let prx = PipelineFileSystemProxy::Git {
+            root: temp_dir().join(format!("bld-atomic-push-{}", Uuid::new_v4())),
+        };
+        let base = PushInfo::new("base.yaml", "steps:\n- exec:\n  - echo base\n");
+        let caller = PushInfo::new(
+            "caller.yaml",
+            "steps:\n- call:\n  - base.yaml\n  - missing.yaml\n",
+        );
+        let invalid = PushInfo::new("invalid.yaml", "steps: [");
+        let pipelines = vec![base, caller, invalid];
+
+        let platforms = Platforms::default();
+        let results = validate_all(&BldConfig::default(), &prx, &platforms, &pipelines);
+        assert!(results[0].errors.is_empty());
+        assert_eq!(results[1].errors.len(), 1);
+        assert!(results[1].errors[0].contains("missing.yaml"));
+        assert_eq!(results[2].errors.len(), 1);
+        assert!(results.iter().all(|r| r.warnings.is_empty()));
+
+        let locks = PipelineLocks::default();
+        let token = locks.acquire("caller.yaml", "other", None, None).unwrap();
+        assert!(lock_all(&locks, &prx, "user", &pipelines).is_err());
+        assert!(locks.check("base.yaml", None).is_ok());
+        locks.release("caller.yaml", "other", &token).unwrap();
+
+        let acquired = lock_all(&locks, &prx, "user", &pipelines).unwrap();
+        assert_eq!(acquired.len(), 3);
+        assert!(locks.check("base.yaml", None).is_err());
+    }
+
+    #[tokio::test]
+    async fn push_through_an_alias_respects_the_lock_of_the_pipeline() {
+        let root = temp_dir().join(format!("bld-push-alias-{}", Uuid::new_v4()));
+        create_dir_all(&root).unwrap();
+        let mut config = BldConfig::default();
+        config.local.server.pipelines = root.display().to_string();
+        config
+            .local
+            .pipeline_aliases
+            .insert("old.yaml".to_string(), "new.yaml".to_string());
+        let pool = new_connection_pool(&root.display().to_string()).unwrap();
+        let prx = PipelineFileSystemProxy::Server {
+            config: Arc::new(config),
+            pool: Arc::new(pool.clone()),
+            cache: None,
+        };
+        let info = PushInfo::new("old.yaml", "steps:\n- exec:\n  - echo new\n");
+
+        let locks = PipelineLocks::default();
+        let token = locks.acquire("new.yaml", "other", None, None).unwrap();
+        assert!(push_locked(&prx, &pool, &locks, &info).await.is_err());
+
+        locks.release("new.yaml", "other", &token).unwrap();
+        push_locked(&prx, &pool, &locks, &info)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(prx.read("new.yaml").unwrap().contains("echo new"));
+
+        remove_dir_all(root).unwrap();
     }
-    prx.create(&info.name, &info.content)
 }
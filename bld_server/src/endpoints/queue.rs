@@ -0,0 +1,35 @@
+use crate::extractors::User;
+use crate::helpers::queue_status;
+use crate::responses::QueueResponse;
+use actix_web::web::Data;
+use actix_web::{get, HttpResponse, Responder};
+use anyhow::Result;
+use bld_config::BldConfig;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sqlite::SqliteConnection;
+use tracing::info;
+
+/// Returns the number of active workers and the runs that wait for a worker.
+#[get("/queue")]
+pub async fn queue(
+    user: Option<User>,
+    config: Data<BldConfig>,
+    db_pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
+) -> impl Responder {
+    info!("Reached handler for /queue route");
+    if user.is_none() {
+        return HttpResponse::Unauthorized().body("");
+    }
+    match select_queue(config.get_ref(), db_pool.get_ref()) {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(_) => HttpResponse::BadRequest().body(""),
+    }
+}
+
+fn select_queue(
+    config: &BldConfig,
+    db_pool: &Pool<ConnectionManager<SqliteConnection>>,
+) -> Result<QueueResponse> {
+    let mut conn = db_pool.get()?;
+    queue_status(config, &mut conn)
+}
@@ -1,4 +1,6 @@
 use crate::extractors::User;
+use crate::requests::RemoveInfo;
+use crate::responses::RemoveResult;
 use actix_web::web::{Data, Json};
 use actix_web::{post, HttpResponse};
 use bld_core::proxies::PipelineFileSystemProxy;
@@ -8,14 +10,24 @@ use tracing::info;
 pub async fn remove(
     user: Option<User>,
     prx: Data<PipelineFileSystemProxy>,
-    body: Json<String>,
+    body: Json<RemoveInfo>,
 ) -> HttpResponse {
     info!("Reached handler for /remove route");
     if user.is_none() {
         return HttpResponse::Unauthorized().body("");
     }
-    match prx.remove(&body.into_inner()) {
-        Ok(_) => HttpResponse::Ok().body(""),
-        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+    match body.into_inner() {
+        RemoveInfo::Single(name) => match prx.remove(&name) {
+            Ok(_) => HttpResponse::Ok().body(""),
+            Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+        },
+        // every pipeline is removed independently and the failures are reported per pipeline.
+        RemoveInfo::Many(names) => {
+            let results: Vec<RemoveResult> = names
+                .iter()
+                .map(|name| RemoveResult::new(name, prx.remove(name).err().map(|e| e.to_string())))
+                .collect();
+            HttpResponse::Ok().json(results)
+        }
     }
 }
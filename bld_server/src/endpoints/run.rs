@@ -1,8 +1,11 @@
 use crate::extractors::User;
-use crate::helpers::enqueue_worker;
+use crate::helpers::{enqueue_worker, request_traceparent, Platforms};
 use crate::requests::RunInfo;
-use actix_web::web::{Data, Json};
-use actix_web::{post, HttpResponse, Responder};
+use crate::responses::{HistoryEntry, RunResponse};
+use actix_web::web::{Data, Json, Path};
+use actix_web::{get, post, HttpRequest, HttpResponse, Responder};
+use anyhow::Result;
+use bld_core::database::pipeline_runs;
 use bld_core::proxies::PipelineFileSystemProxy;
 use bld_supervisor::base::ServerMessages;
 use diesel::r2d2::{ConnectionManager, Pool};
@@ -16,6 +19,8 @@ pub async fn run(
     proxy: Data<PipelineFileSystemProxy>,
     pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
     enqueue_tx: Data<Sender<ServerMessages>>,
+    platforms: Data<Platforms>,
+    req: HttpRequest,
     data: Json<RunInfo>,
 ) -> impl Responder {
     info!("reached handler for /run route");
@@ -23,8 +28,44 @@ pub async fn run(
         return HttpResponse::Unauthorized().body("");
     }
     let user = user.unwrap();
-    match enqueue_worker(&user, proxy, pool, enqueue_tx, data.into_inner()) {
-        Ok(_) => HttpResponse::Ok().body(""),
+    match enqueue_worker(
+        &user,
+        proxy,
+        pool,
+        enqueue_tx,
+        &platforms,
+        data.into_inner(),
+        request_traceparent(&req),
+    )
+    .await
+    {
+        Ok((run_id, _)) => HttpResponse::Ok().json(RunResponse { run_id }),
         Err(e) => HttpResponse::BadRequest().body(e.to_string()),
     }
 }
+
+/// Returns the state of a run, so that integrations that enqueue runs through the `/run`
+/// endpoint can poll it until it has finished.
+#[get("/runs/{run_id}")]
+pub async fn run_state(
+    user: Option<User>,
+    pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
+    run_id: Path<String>,
+) -> impl Responder {
+    info!("reached handler for /runs/{{run_id}} route");
+    if user.is_none() {
+        return HttpResponse::Unauthorized().body("");
+    }
+    match select_run(pool.get_ref(), &run_id) {
+        Ok(entry) => HttpResponse::Ok().json(entry),
+        Err(_) => HttpResponse::NotFound().body(""),
+    }
+}
+
+fn select_run(
+    db_pool: &Pool<ConnectionManager<SqliteConnection>>,
+    run_id: &str,
+) -> Result<HistoryEntry> {
+    let mut conn = db_pool.get()?;
+    pipeline_runs::select_by_id(&mut conn, run_id).map(HistoryEntry::from)
+}
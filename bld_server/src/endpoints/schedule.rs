@@ -0,0 +1,127 @@
+use crate::extractors::User;
+use crate::helpers::parse_cron;
+use crate::requests::ScheduleInfo;
+use crate::responses::ScheduleEntry;
+use actix_web::web::{Data, Json, Path};
+use actix_web::{delete, get, post, HttpResponse, Responder};
+use anyhow::{bail, Result};
+use bld_core::database::pipeline_schedules::{self, InsertPipelineSchedule};
+use bld_core::proxies::PipelineFileSystemProxy;
+use bld_utils::fs::IsYaml;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sqlite::SqliteConnection;
+use tracing::info;
+use uuid::Uuid;
+
+/// Adds a schedule that runs a pipeline of the server on a cron expression.
+#[post("/schedules")]
+pub async fn add_schedule(
+    user: Option<User>,
+    prx: Data<PipelineFileSystemProxy>,
+    pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
+    info: Json<ScheduleInfo>,
+) -> impl Responder {
+    info!("Reached handler for /schedules route");
+    let user = match user {
+        Some(user) => user,
+        None => return HttpResponse::Unauthorized().body(""),
+    };
+    match add(prx.get_ref(), pool.get_ref(), &user, &info.into_inner()) {
+        Ok(schedule) => HttpResponse::Ok().json(schedule),
+        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+    }
+}
+
+#[get("/schedules")]
+pub async fn schedules(
+    user: Option<User>,
+    pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
+) -> impl Responder {
+    info!("Reached handler for /schedules route");
+    if user.is_none() {
+        return HttpResponse::Unauthorized().body("");
+    }
+    let schedules = pool
+        .get()
+        .map_err(|e| e.into())
+        .and_then(|mut conn| pipeline_schedules::select_all(&mut conn));
+    match schedules {
+        Ok(schedules) => {
+            let entries: Vec<ScheduleEntry> =
+                schedules.into_iter().map(ScheduleEntry::from).collect();
+            HttpResponse::Ok().json(entries)
+        }
+        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+    }
+}
+
+#[delete("/schedules/{id}")]
+pub async fn remove_schedule(
+    user: Option<User>,
+    pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
+    id: Path<String>,
+) -> impl Responder {
+    info!("Reached handler for /schedules/{{id}} route");
+    if user.is_none() {
+        return HttpResponse::Unauthorized().body("");
+    }
+    match remove(pool.get_ref(), &id) {
+        Ok(()) => HttpResponse::Ok().body(""),
+        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+    }
+}
+
+fn add(
+    prx: &PipelineFileSystemProxy,
+    pool: &Pool<ConnectionManager<SqliteConnection>>,
+    user: &User,
+    info: &ScheduleInfo,
+) -> Result<ScheduleEntry> {
+    parse_cron(&info.cron)?;
+    if !prx.path(&info.pipeline).is_ok_and(|p| p.is_yaml()) {
+        bail!("pipeline {} not found", info.pipeline);
+    }
+    let variables = info
+        .variables
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()?;
+    let environment = info
+        .environment
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()?;
+    let created_by = if user.name.is_empty() {
+        "anonymous"
+    } else {
+        &user.name
+    };
+    let id = Uuid::new_v4().to_string();
+    let mut conn = pool.get()?;
+    let schedule = pipeline_schedules::insert(
+        &mut conn,
+        InsertPipelineSchedule {
+            id: &id,
+            pipeline: &info.pipeline,
+            cron: &info.cron,
+            variables: variables.as_deref(),
+            environment: environment.as_deref(),
+            enabled: info.enabled.unwrap_or(true),
+            created_by,
+        },
+    )?;
+    info!(
+        "added schedule {id} of {} on {} by {created_by}",
+        info.pipeline, info.cron
+    );
+    Ok(ScheduleEntry::from(schedule))
+}
+
+fn remove(pool: &Pool<ConnectionManager<SqliteConnection>>, id: &str) -> Result<()> {
+    let mut conn = pool.get()?;
+    if !pipeline_schedules::delete(&mut conn, id)? {
+        bail!("schedule {id} not found");
+    }
+    info!("removed schedule {id}");
+    Ok(())
+}
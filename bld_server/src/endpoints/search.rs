@@ -0,0 +1,54 @@
+use crate::extractors::User;
+use crate::requests::SearchQueryInfo;
+use crate::responses::SearchEntry;
+use actix_web::web::{Data, Query};
+use actix_web::{get, HttpResponse, Responder};
+use anyhow::Result;
+use bld_core::database::pipeline;
+use bld_core::proxies::PipelineFileSystemProxy;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sqlite::SqliteConnection;
+use tracing::{debug, info};
+
+#[get("/search")]
+pub async fn search(
+    user: Option<User>,
+    prx: Data<PipelineFileSystemProxy>,
+    pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
+    params: Query<SearchQueryInfo>,
+) -> impl Responder {
+    info!("Reached handler for /search route");
+    if user.is_none() {
+        return HttpResponse::Unauthorized().body("");
+    }
+    let term = params.q.trim();
+    if term.is_empty() {
+        return HttpResponse::BadRequest().body("the search term can't be empty");
+    }
+    match search_pipelines(prx.get_ref(), pool.get_ref(), term) {
+        Ok(entries) => HttpResponse::Ok().json(entries),
+        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+    }
+}
+
+/// Searches the content of the pipelines of the server, with the pipelines that contain the term
+/// the most times listed first. Pipelines that can't be read are skipped.
+fn search_pipelines(
+    prx: &PipelineFileSystemProxy,
+    pool: &Pool<ConnectionManager<SqliteConnection>>,
+    term: &str,
+) -> Result<Vec<SearchEntry>> {
+    let mut conn = pool.get()?;
+    let mut entries: Vec<SearchEntry> = pipeline::select_all(&mut conn)?
+        .into_iter()
+        .filter_map(|p| match prx.read(&p.name) {
+            Ok(content) => SearchEntry::find(&p.name, &content, term),
+            Err(e) => {
+                debug!("skipping pipeline {} from the search, {e}", p.name);
+                None
+            }
+        })
+        .collect();
+    entries.sort_by(|a, b| b.matches.cmp(&a.matches).then_with(|| a.name.cmp(&b.name)));
+    Ok(entries)
+}
@@ -0,0 +1,35 @@
+use crate::extractors::User;
+use crate::requests::StatsQueryInfo;
+use crate::responses::PipelineStats;
+use actix_web::web::{Data, Query};
+use actix_web::{get, HttpResponse, Responder};
+use anyhow::Result;
+use bld_core::database::pipeline_runs;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sqlite::SqliteConnection;
+use tracing::info;
+
+#[get("/stats")]
+pub async fn pipeline_stats(
+    user: Option<User>,
+    db_pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
+    params: Query<StatsQueryInfo>,
+) -> impl Responder {
+    info!("Reached handler for /stats route");
+    if user.is_none() {
+        return HttpResponse::Unauthorized().body("");
+    }
+    match stats_info(db_pool.get_ref(), &params.name) {
+        Ok(stats) => HttpResponse::Ok().json(stats),
+        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+    }
+}
+
+fn stats_info(
+    db_pool: &Pool<ConnectionManager<SqliteConnection>>,
+    name: &str,
+) -> Result<PipelineStats> {
+    let mut conn = db_pool.get()?;
+    let runs = pipeline_runs::select_all_by_name(&mut conn, name)?;
+    Ok(PipelineStats::new(name, &runs))
+}
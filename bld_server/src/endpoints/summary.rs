@@ -0,0 +1,37 @@
+use crate::extractors::User;
+use crate::responses::RunSummary;
+use actix_web::web::{Data, Path};
+use actix_web::{get, HttpResponse, Responder};
+use anyhow::Result;
+use bld_core::database::{pipeline_run_commands, pipeline_runs};
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sqlite::SqliteConnection;
+use tracing::info;
+
+/// Returns the summary of a run with the durations of the commands of its steps, including
+/// the commands of the pipelines that it called.
+#[get("/runs/{run_id}/summary")]
+pub async fn run_summary(
+    user: Option<User>,
+    db_pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
+    run_id: Path<String>,
+) -> impl Responder {
+    info!("Reached handler for /runs/{{run_id}}/summary route");
+    if user.is_none() {
+        return HttpResponse::Unauthorized().body("");
+    }
+    match select_summary(db_pool.get_ref(), &run_id) {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(_) => HttpResponse::NotFound().body(""),
+    }
+}
+
+fn select_summary(
+    db_pool: &Pool<ConnectionManager<SqliteConnection>>,
+    run_id: &str,
+) -> Result<RunSummary> {
+    let mut conn = db_pool.get()?;
+    let run = pipeline_runs::select_by_id(&mut conn, run_id)?;
+    let commands = pipeline_run_commands::select_by_run_id(&mut conn, run_id)?;
+    Ok(RunSummary::new(&run.id, &run.state, commands))
+}
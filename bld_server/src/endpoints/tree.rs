@@ -0,0 +1,111 @@
+use crate::extractors::User;
+use crate::responses::RunTreeNode;
+use actix_web::web::{Data, Path};
+use actix_web::{get, HttpResponse, Responder};
+use anyhow::Result;
+use bld_core::database::pipeline_run_parents::{self, PipelineRunParents};
+use bld_core::database::pipeline_runs;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sqlite::SqliteConnection;
+use tracing::info;
+
+/// Returns a run along with the runs of the pipelines that were called from its steps.
+#[get("/runs/{run_id}/tree")]
+pub async fn run_tree(
+    user: Option<User>,
+    db_pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
+    run_id: Path<String>,
+) -> impl Responder {
+    info!("Reached handler for /runs/{{run_id}}/tree route");
+    if user.is_none() {
+        return HttpResponse::Unauthorized().body("");
+    }
+    match select_tree(db_pool.get_ref(), &run_id) {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(_) => HttpResponse::NotFound().body(""),
+    }
+}
+
+fn select_tree(
+    db_pool: &Pool<ConnectionManager<SqliteConnection>>,
+    run_id: &str,
+) -> Result<RunTreeNode> {
+    let mut conn = db_pool.get()?;
+    select_node(&mut conn, run_id, None)
+}
+
+fn select_node(
+    conn: &mut SqliteConnection,
+    run_id: &str,
+    parent: Option<PipelineRunParents>,
+) -> Result<RunTreeNode> {
+    let run = pipeline_runs::select_by_id(conn, run_id)?;
+    let children = pipeline_run_parents::select_by_parent_run_id(conn, run_id)?
+        .into_iter()
+        .map(|p| {
+            let child_run_id = p.child_run_id.clone();
+            select_node(conn, &child_run_id, Some(p))
+        })
+        .collect::<Result<Vec<RunTreeNode>>>()?;
+    let (step_name, rollup, effective_state) = match parent {
+        Some(p) => (p.step_name, p.rollup, p.effective_state),
+        None => (None, None, None),
+    };
+    Ok(RunTreeNode {
+        run_id: run.id,
+        pipeline: run.name,
+        step_name,
+        state: run.state,
+        start_date_time: run.start_date_time,
+        end_date_time: run.end_date_time,
+        rollup,
+        effective_state,
+        children,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bld_core::database::new_connection_pool;
+    use bld_core::database::pipeline_runs::PR_STATE_RUNNING;
+    use bld_core::execution::Execution;
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_dir_all};
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    #[test]
+    fn tree_contains_the_rollup_of_the_called_runs() {
+        let root = temp_dir().join(format!("bld-tree-{}", Uuid::new_v4()));
+        create_dir_all(&root).unwrap();
+        let pool = Arc::new(new_connection_pool(&root.display().to_string()).unwrap());
+        {
+            let mut conn = pool.get().unwrap();
+            pipeline_runs::insert(&mut conn, "deploy", "deploy.yaml", "user", None).unwrap();
+            pipeline_runs::update_state(&mut conn, "deploy", PR_STATE_RUNNING).unwrap();
+        }
+        let exec = Execution::pipeline_atom(pool.clone(), "deploy", None);
+        let child = exec
+            .lock()
+            .unwrap()
+            .child_atom("notify.yaml", Some("Notify"))
+            .unwrap();
+        {
+            let mut child = child.lock().unwrap();
+            child.set_as_faulted().unwrap();
+            child.set_rollup("warn", "finished").unwrap();
+        }
+
+        let tree = select_tree(&pool, "deploy").unwrap();
+        assert_eq!((tree.rollup, tree.effective_state), (None, None));
+        let notify = &tree.children[0];
+        assert_eq!(notify.pipeline, "notify.yaml");
+        assert_eq!(notify.step_name.as_deref(), Some("Notify"));
+        assert_eq!(notify.state, "faulted");
+        assert_eq!(notify.rollup.as_deref(), Some("warn"));
+        assert_eq!(notify.effective_state.as_deref(), Some("finished"));
+        drop(pool);
+        remove_dir_all(root).unwrap();
+    }
+}
@@ -1,26 +1,41 @@
 use actix_web::dev::Payload;
 use actix_web::error::ErrorUnauthorized;
-use actix_web::http::header::HeaderValue;
 use actix_web::web::Data;
-use actix_web::{Error, FromRequest, HttpRequest};
-use anyhow::{anyhow, Result};
+use actix_web::{Error, FromRequest, HttpMessage, HttpRequest};
+use anyhow::{anyhow, bail, Result};
 use bld_config::{AuthValidation, BldConfig};
+use bld_core::database::api_keys;
 use bld_utils::request;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sqlite::SqliteConnection;
 use futures::Future;
 use futures_util::future::FutureExt;
 use std::collections::HashMap;
 use std::pin::Pin;
-use tracing::error;
+use tracing::{debug, error};
 
-#[derive(Debug)]
+const API_KEY_SCHEME: &str = "ApiKey ";
+
+#[derive(Debug, Clone)]
 pub struct User {
     pub name: String,
+    /// The name of the api key that the request authenticated with, for the requests that are
+    /// made with a key instead of the credentials of the user.
+    pub api_key: Option<String>,
 }
 
 impl User {
     pub fn new(name: &str) -> Self {
         Self {
             name: name.to_string(),
+            api_key: None,
+        }
+    }
+
+    pub fn with_api_key(name: &str, api_key: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            api_key: Some(api_key.to_string()),
         }
     }
 }
@@ -29,19 +44,40 @@ impl FromRequest for User {
     type Error = Error;
     type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
 
+    /// Uses the user that the authentication middleware stored in the request, and validates
+    /// the request only when it didn't go through the middleware.
     fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
-        let config = req.app_data::<Data<BldConfig>>().unwrap().clone();
-        let bearer = get_bearer(req);
-        async move {
-            if let AuthValidation::OAuth2(url) = &config.get_ref().local.auth {
-                return match oauth2_validate(url.to_string(), bearer).await {
-                    Ok(user) => Ok(user),
-                    Err(_) => Err(ErrorUnauthorized("")),
-                };
-            }
-            Ok(User::new(""))
+        if let Some(user) = req.extensions().get::<User>() {
+            let user = user.clone();
+            return async move { Ok(user) }.boxed_local();
+        }
+        let req = req.clone();
+        async move { authenticate(&req).await.map_err(|_| ErrorUnauthorized("")) }.boxed_local()
+    }
+}
+
+/// Validates the credentials of a request based on the auth method of the server. Api keys are
+/// accepted with every method, while a server without an auth method accepts every request.
+pub async fn authenticate(req: &HttpRequest) -> Result<User> {
+    let config = req
+        .app_data::<Data<BldConfig>>()
+        .cloned()
+        .ok_or_else(|| anyhow!("no config found for the request"))?;
+    let pool = req
+        .app_data::<Data<Pool<ConnectionManager<SqliteConnection>>>>()
+        .cloned();
+    let bearer = get_bearer(req);
+    if let Some(key) = bearer.strip_prefix(API_KEY_SCHEME) {
+        let pool = pool.ok_or_else(|| anyhow!("no database found for the request"))?;
+        return api_key_validate(pool.get_ref(), key);
+    }
+    match &config.get_ref().local.auth {
+        AuthValidation::OAuth2(url) => oauth2_validate(url.to_string(), bearer).await,
+        AuthValidation::Ldap => {
+            error!("ldap validation is not supported, only api keys are accepted");
+            bail!("could not authenticate user")
         }
-        .boxed_local()
+        AuthValidation::None => Ok(User::new("")),
     }
 }
 
@@ -49,12 +85,27 @@ fn get_bearer(request: &HttpRequest) -> String {
     request
         .headers()
         .get("Authorization")
-        .unwrap_or(&HeaderValue::from_static(""))
-        .to_str()
-        .unwrap()
+        .and_then(|header| header.to_str().ok())
+        .unwrap_or_default()
         .to_string()
 }
 
+/// Resolves the user of a request that authenticates with an api key to the user that created
+/// the key, so that a key has the permissions of its creator regardless of its name. The name
+/// of the key is kept with the user so that the actions of a ci environment can be told apart.
+fn api_key_validate(pool: &Pool<ConnectionManager<SqliteConnection>>, key: &str) -> Result<User> {
+    let mut conn = pool.get()?;
+    let api_key = api_keys::select_by_key(&mut conn, key.trim()).map_err(|_| {
+        debug!("no api key found for the request");
+        anyhow!("could not authenticate user")
+    })?;
+    debug!(
+        "authenticated {} with the api key {}",
+        api_key.created_by, api_key.name
+    );
+    Ok(User::with_api_key(&api_key.created_by, &api_key.name))
+}
+
 async fn oauth2_validate(url: String, bearer: String) -> Result<User> {
     let mut headers = HashMap::new();
     headers.insert("Authorization".to_string(), bearer);
@@ -65,3 +116,37 @@ async fn oauth2_validate(url: String, bearer: String) -> Result<User> {
     let value: serde_json::Value = serde_json::from_str(&res)?;
     Ok(User::new(&value["login"].to_string()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bld_core::database::api_keys::InsertApiKey;
+    use bld_core::database::new_connection_pool;
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_dir_all};
+    use uuid::Uuid;
+
+    #[test]
+    fn api_keys_are_resolved_to_the_user_that_created_them() {
+        let root = temp_dir().join(format!("bld-api-key-{}", Uuid::new_v4()));
+        create_dir_all(&root).unwrap();
+        let pool = new_connection_pool(&root.display().to_string()).unwrap();
+        api_keys::insert(
+            &mut pool.get().unwrap(),
+            InsertApiKey {
+                id: "id",
+                name: "alice",
+                key_hash: &api_keys::hash("key"),
+                created_by: "mallory",
+            },
+        )
+        .unwrap();
+
+        let user = api_key_validate(&pool, "key").unwrap();
+        assert_eq!(user.name, "mallory");
+        assert_eq!(user.api_key.as_deref(), Some("alice"));
+        assert!(api_key_validate(&pool, "other").is_err());
+
+        remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,62 @@
+use crate::responses::ArtifactEntry;
+use anyhow::Result;
+use bld_core::database::pipeline_run_artifacts;
+use bld_utils::fs::artifact_files;
+use diesel::sqlite::SqliteConnection;
+use std::path::{Path, PathBuf};
+
+/// A file of the artifacts of a run along with its path on the server.
+pub struct RunArtifact {
+    pub name: String,
+    pub path: Option<PathBuf>,
+    pub size: Option<u64>,
+}
+
+impl RunArtifact {
+    pub fn entry(&self) -> ArtifactEntry {
+        ArtifactEntry {
+            name: self.name.to_string(),
+            size: self.size,
+        }
+    }
+}
+
+/// Lists the files of the paths that the artifacts of a run have been copied to. A path that
+/// no longer exists is listed by its name without a file, and a name that appears more than
+/// once refers to the artifact that was recorded first.
+pub fn run_artifacts(conn: &mut SqliteConnection, run_id: &str) -> Result<Vec<RunArtifact>> {
+    let mut artifacts: Vec<RunArtifact> = vec![];
+    for recorded in pipeline_run_artifacts::select_by_run_id(conn, run_id)? {
+        let path = Path::new(&recorded.path);
+        let files = artifact_files(path)?;
+        let entries = if files.is_empty() && !path.exists() {
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or(recorded.path.to_string());
+            vec![RunArtifact {
+                name,
+                path: None,
+                size: None,
+            }]
+        } else {
+            files
+                .into_iter()
+                .map(|(name, path)| {
+                    let size = path.metadata().map(|m| m.len()).ok();
+                    RunArtifact {
+                        name,
+                        path: Some(path),
+                        size,
+                    }
+                })
+                .collect()
+        };
+        for entry in entries {
+            if !artifacts.iter().any(|a| a.name == entry.name) {
+                artifacts.push(entry);
+            }
+        }
+    }
+    Ok(artifacts)
+}
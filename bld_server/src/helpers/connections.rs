@@ -0,0 +1,189 @@
+use crate::responses::ConnectionResponse;
+use actix::{Message, Recipient};
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::info;
+use uuid::Uuid;
+
+/// The interval that the sockets ping their clients and check their own heartbeat.
+pub const CONNECTION_HEARTBEAT_INTERVAL_SECONDS: u64 = 5;
+/// The time without a heartbeat after which a socket closes itself.
+pub const CONNECTION_TIMEOUT_SECONDS: u64 = 60;
+
+/// Sent to a socket in order for it to close its connection.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct CloseConnection;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionKind {
+    Exec,
+    Monit,
+}
+
+impl Display for ConnectionKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exec => write!(f, "exec"),
+            Self::Monit => write!(f, "monit"),
+        }
+    }
+}
+
+struct Connection {
+    kind: ConnectionKind,
+    user: String,
+    run_ids: Vec<String>,
+    connected_at: u64,
+    last_heartbeat: Instant,
+    closer: Recipient<CloseConnection>,
+}
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// The exec and monit websockets that are open on the server, which are registered by the
+/// socket actors when they start and removed when they stop.
+#[derive(Default)]
+pub struct ConnectionRegistry {
+    connections: Mutex<HashMap<String, Connection>>,
+}
+
+impl ConnectionRegistry {
+    /// Registers a socket and returns the id of its connection.
+    pub fn register(
+        &self,
+        kind: ConnectionKind,
+        user: &str,
+        closer: Recipient<CloseConnection>,
+    ) -> String {
+        let id = Uuid::new_v4().to_string();
+        let connection = Connection {
+            kind,
+            user: user.to_string(),
+            run_ids: vec![],
+            connected_at: unix_timestamp(),
+            last_heartbeat: Instant::now(),
+            closer,
+        };
+        let mut connections = self.connections.lock().unwrap();
+        connections.insert(id.to_string(), connection);
+        id
+    }
+
+    pub fn unregister(&self, id: &str) {
+        let mut connections = self.connections.lock().unwrap();
+        connections.remove(id);
+    }
+
+    pub fn subscribe(&self, id: &str, run_id: &str) {
+        let mut connections = self.connections.lock().unwrap();
+        if let Some(connection) = connections.get_mut(id) {
+            if !connection.run_ids.iter().any(|r| r == run_id) {
+                connection.run_ids.push(run_id.to_string());
+            }
+        }
+    }
+
+    pub fn heartbeat(&self, id: &str) {
+        let mut connections = self.connections.lock().unwrap();
+        if let Some(connection) = connections.get_mut(id) {
+            connection.last_heartbeat = Instant::now();
+        }
+    }
+
+    pub fn list(&self) -> Vec<ConnectionResponse> {
+        let connections = self.connections.lock().unwrap();
+        let mut list: Vec<ConnectionResponse> = connections
+            .iter()
+            .map(|(id, c)| ConnectionResponse {
+                id: id.to_string(),
+                kind: c.kind.to_string(),
+                user: c.user.to_string(),
+                run_ids: c.run_ids.clone(),
+                connected_at: c.connected_at,
+                seconds_since_heartbeat: c.last_heartbeat.elapsed().as_secs(),
+            })
+            .collect();
+        list.sort_by_key(|c| c.connected_at);
+        list
+    }
+
+    /// Asks the socket of a connection to close and removes it from the registry, so that
+    /// a socket that doesn't process the request isn't listed anymore.
+    pub fn close(&self, id: &str) -> bool {
+        let mut connections = self.connections.lock().unwrap();
+        match connections.remove(id) {
+            Some(connection) => {
+                connection.closer.do_send(CloseConnection);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Closes the connections without a heartbeat for longer than the threshold and returns
+    /// their number.
+    pub fn close_stale(&self, threshold: Duration) -> usize {
+        let mut connections = self.connections.lock().unwrap();
+        let mut closed = 0;
+        connections.retain(|id, connection| {
+            let is_stale = connection.last_heartbeat.elapsed() > threshold;
+            if is_stale {
+                info!(
+                    "closing stale {} connection {id} of user {}",
+                    connection.kind, connection.user
+                );
+                connection.closer.do_send(CloseConnection);
+                closed += 1;
+            }
+            !is_stale
+        });
+        closed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix::{Actor, Context, Handler, System};
+
+    struct MockSocket;
+
+    impl Actor for MockSocket {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<CloseConnection> for MockSocket {
+        type Result = ();
+
+        fn handle(&mut self, _msg: CloseConnection, _ctx: &mut Self::Context) {}
+    }
+
+    #[test]
+    fn connection_registry_closes_stale_connections() {
+        System::new().block_on(async {
+            let registry = ConnectionRegistry::default();
+            let closer = MockSocket.start().recipient();
+            let id = registry.register(ConnectionKind::Monit, "mock_user", closer);
+            registry.subscribe(&id, "mock_run_id");
+            registry.subscribe(&id, "mock_run_id");
+
+            let list = registry.list();
+            assert_eq!(list[0].kind, "monit");
+            assert_eq!(list[0].run_ids, vec!["mock_run_id".to_string()]);
+            assert_eq!(registry.close_stale(Duration::from_secs(60)), 0);
+
+            std::thread::sleep(Duration::from_millis(10));
+            assert_eq!(registry.close_stale(Duration::from_millis(1)), 1);
+            assert!(registry.list().is_empty());
+            assert!(!registry.close(&id));
+        });
+    }
+}
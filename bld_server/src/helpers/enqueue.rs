@@ -1,34 +1,88 @@
 use crate::extractors::User;
-use crate::requests::RunInfo;
+use crate::helpers::Platforms;
+use crate::requests::{validate_label, RunInfo};
 use actix_web::rt::spawn;
 use actix_web::web::Data;
+use actix_web::HttpRequest;
 use anyhow::{bail, Result};
 use bld_core::database::pipeline_runs;
 use bld_core::proxies::PipelineFileSystemProxy;
+use bld_runner::{trace_parent_context, traceparent, Pipeline, TRACEPARENT};
 use bld_supervisor::base::ServerMessages;
 use bld_utils::fs::IsYaml;
 use diesel::r2d2::{ConnectionManager, Pool};
 use diesel::SqliteConnection;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use tokio::sync::mpsc::Sender;
+use tokio::task::spawn_blocking;
 use tracing::{debug, error};
 use uuid::Uuid;
 
-pub fn enqueue_worker(
+/// The W3C traceparent of the request, which is propagated to the worker so that the spans of
+/// the run are part of the trace of the request. An invalid traceparent is ignored.
+pub fn request_traceparent(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(TRACEPARENT)
+        .and_then(|t| t.to_str().ok())
+        .and_then(trace_parent_context)
+        .and_then(|cx| traceparent(&cx))
+}
+
+/// Sends the run to the supervisor after validating that the pipeline exists and that it runs
+/// on a platform of the server, and returns the run id along with the content of the pipeline.
+pub async fn enqueue_worker(
     user: &User,
     proxy: Data<PipelineFileSystemProxy>,
     pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
     enqueue_tx: Data<Sender<ServerMessages>>,
+    platforms: &Platforms,
     data: RunInfo,
-) -> Result<String> {
-    let path = proxy.path(&data.name)?;
-    if !path.is_yaml() {
-        bail!("pipeline file not found");
+    traceparent: Option<String>,
+) -> Result<(String, String)> {
+    let content = match &data.git_url {
+        Some(url) => {
+            let url = url.to_string();
+            let git_ref = data.git_ref.clone();
+            let name = data.name.clone();
+            spawn_blocking(move || read_git_pipeline(&url, git_ref.as_deref(), &name)).await??
+        }
+        None => {
+            let path = proxy.path(&data.name)?;
+            if !path.is_yaml() {
+                bail!("pipeline file not found");
+            }
+            proxy.read(&data.name)?
+        }
+    };
+
+    // a dry run doesn't execute the commands so it doesn't need the platform of the pipeline,
+    // while a pipeline that can't be parsed faults in the worker with the parsing error.
+    if !data.dry_run {
+        if let Ok(pipeline) = Pipeline::parse(&content) {
+            platforms.check(&data.name, &pipeline.runs_on)?;
+        }
     }
 
+    let labels = match data.labels.as_ref().filter(|l| !l.is_empty()) {
+        Some(labels) => {
+            for (name, value) in labels.iter() {
+                validate_label(name, value)?;
+            }
+            let sorted: BTreeMap<_, _> = labels.iter().collect();
+            Some(serde_json::to_string(&sorted)?)
+        }
+        None => None,
+    };
+
     let run_id = Uuid::new_v4().to_string();
     let mut conn = pool.get()?;
-    let run = pipeline_runs::insert(&mut conn, &run_id, &data.name, &user.name)?;
+    let run = pipeline_runs::insert(
+        &mut conn,
+        &run_id,
+        &data.name,
+        &user.name,
+        labels.as_deref(),
+    )?;
 
     let variables = data.variables.map(hash_map_to_var_string);
     let environment = data.environment.map(hash_map_to_var_string);
@@ -39,6 +93,11 @@ pub fn enqueue_worker(
             run_id,
             variables,
             environment,
+            secrets: data.secrets,
+            git_url: data.git_url,
+            git_ref: data.git_ref,
+            dry_run: data.dry_run,
+            traceparent,
         };
         match enqueue_tx.send(msg).await {
             Ok(_) => debug!("sent message to supervisor receiver"),
@@ -46,7 +105,17 @@ pub fn enqueue_worker(
         }
     });
 
-    Ok(run.id)
+    Ok((run.id, content))
+}
+
+/// The worker clones the repository again for the run, so this clone is only used to
+/// validate the pipeline before the run is queued. The clone blocks, so it runs on a blocking
+/// thread instead of a worker of the server.
+fn read_git_pipeline(url: &str, git_ref: Option<&str>, name: &str) -> Result<String> {
+    let proxy = PipelineFileSystemProxy::git(url, git_ref)?;
+    let content = proxy.read(name);
+    proxy.dispose()?;
+    content
 }
 
 fn hash_map_to_var_string(hmap: HashMap<String, String>) -> String {
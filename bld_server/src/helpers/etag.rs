@@ -0,0 +1,56 @@
+use actix_web::http::header::{ContentType, ETAG, IF_NONE_MATCH};
+use actix_web::{HttpRequest, HttpResponse};
+use bld_core::proxies::content_hash;
+
+/// The etag of the body of a response, derived from the hash of its content.
+pub fn etag(body: &str) -> String {
+    format!("\"{}\"", content_hash(body))
+}
+
+/// Responds with the body and its etag, or with a not modified status and no body when the
+/// If-None-Match header of the request has the same etag.
+pub fn with_etag(req: &HttpRequest, content_type: ContentType, body: String) -> HttpResponse {
+    let etag = etag(&body);
+    let not_modified = req
+        .headers()
+        .get(IF_NONE_MATCH)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|h| h.split(',').any(|e| e.trim() == etag || e.trim() == "*"));
+    if not_modified {
+        return HttpResponse::NotModified()
+            .insert_header((ETAG, etag))
+            .finish();
+    }
+    HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header((ETAG, etag))
+        .body(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn responses_with_a_matching_etag_are_not_modified() {
+        let body = "name: build".to_string();
+        let req = TestRequest::default().to_http_request();
+        let response = with_etag(&req, ContentType::plaintext(), body.clone());
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get(ETAG).unwrap(), etag(&body).as_str());
+
+        let req = TestRequest::default()
+            .insert_header((IF_NONE_MATCH, format!("\"stale\", {}", etag(&body))))
+            .to_http_request();
+        let response = with_etag(&req, ContentType::plaintext(), body.clone());
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        let req = TestRequest::default()
+            .insert_header((IF_NONE_MATCH, "\"stale\""))
+            .to_http_request();
+        let response = with_etag(&req, ContentType::plaintext(), body);
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
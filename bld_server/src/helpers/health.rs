@@ -0,0 +1,300 @@
+use crate::helpers::queue_status;
+use crate::responses::{HealthResponse, HealthStatus, ProbeResponse, QueueResponse};
+use anyhow::{anyhow, Result};
+use bld_config::{BldConfig, BldLocalSupervisorConfig};
+use bld_runner::docker_version;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sqlite::SqliteConnection;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+#[cfg(unix)]
+use tokio::net::UnixStream;
+use tokio::task::spawn_blocking;
+use tokio::time::timeout;
+use tracing::{debug, warn};
+
+/// The time that each subsystem has to respond, so that a hung docker socket or an exhausted
+/// connection pool doesn't make the health check of a load balancer hang.
+pub const HEALTH_PROBE_TIMEOUT_MILLIS: u64 = 2000;
+
+const PROBE_DATABASE: &str = "database";
+const PROBE_SUPERVISOR: &str = "supervisor";
+const PROBE_DOCKER: &str = "docker";
+
+/// The outcome of a probe along with the details that are logged instead of being part of the
+/// public response.
+struct ProbeOutcome {
+    response: ProbeResponse,
+    error: Option<String>,
+}
+
+/// Probes the database, the supervisor and, if configured, the docker daemon concurrently.
+/// The server is down if the database can't be reached since no request can be served
+/// without it, and degraded if the supervisor or docker can't be reached since runs can't be
+/// started while the rest of the requests are served.
+pub async fn check_health(
+    config: Arc<BldConfig>,
+    pool: Pool<ConnectionManager<SqliteConnection>>,
+    docker_configured: bool,
+    limit: Duration,
+) -> HealthResponse {
+    let (probes, queue) = probe_all(config, pool, docker_configured, limit).await;
+    let probes: Vec<ProbeResponse> = probes
+        .into_iter()
+        .map(|p| {
+            if let Some(e) = &p.error {
+                warn!("health probe {} is down, {e}", p.response.name);
+            }
+            p.response
+        })
+        .collect();
+    let status = if probes[0].status == HealthStatus::Down {
+        HealthStatus::Down
+    } else if probes.iter().any(|p| p.status == HealthStatus::Down) {
+        HealthStatus::Degraded
+    } else {
+        HealthStatus::Ok
+    };
+    HealthResponse {
+        status,
+        probes,
+        active_runs: queue.as_ref().map(|q| q.active),
+        queued_runs: queue.as_ref().map(|q| q.queued.len()),
+    }
+}
+
+/// Runs the probes concurrently, with the database always being the first one, and returns
+/// them along with the queue that the database probe read.
+async fn probe_all(
+    config: Arc<BldConfig>,
+    pool: Pool<ConnectionManager<SqliteConnection>>,
+    docker_configured: bool,
+    limit: Duration,
+) -> (Vec<ProbeOutcome>, Option<QueueResponse>) {
+    let docker = async {
+        if docker_configured {
+            Some(
+                probe(
+                    PROBE_DOCKER,
+                    limit,
+                    docker_version(&config.local.docker_url),
+                )
+                .await,
+            )
+        } else {
+            None
+        }
+    };
+    let (database, supervisor, docker) = futures::join!(
+        probe(
+            PROBE_DATABASE,
+            limit,
+            probe_database(Arc::clone(&config), pool)
+        ),
+        probe(
+            PROBE_SUPERVISOR,
+            limit,
+            probe_supervisor(&config.local.supervisor)
+        ),
+        docker,
+    );
+    let (database, queue) = database;
+    let mut probes = vec![database, supervisor.0];
+    probes.extend(docker.map(|(docker, _)| docker));
+    (probes, queue)
+}
+
+/// Runs a probe within the time limit and logs its duration.
+async fn probe<T, F>(name: &str, limit: Duration, future: F) -> (ProbeOutcome, Option<T>)
+where
+    F: Future<Output = Result<T>>,
+{
+    let start = Instant::now();
+    let result = match timeout(limit, future).await {
+        Ok(result) => result,
+        Err(_) => Err(anyhow!("timed out after {}ms", limit.as_millis())),
+    };
+    debug!(
+        "health probe {name} finished in {}ms",
+        start.elapsed().as_millis()
+    );
+    let (status, error, value) = match result {
+        Ok(value) => (HealthStatus::Ok, None, Some(value)),
+        Err(e) => (HealthStatus::Down, Some(e.to_string()), None),
+    };
+    let response = ProbeResponse {
+        name: name.to_string(),
+        status,
+    };
+    (ProbeOutcome { response, error }, value)
+}
+
+/// Gets a connection from the pool and reads the runs of the queue with it. The connection is
+/// acquired in a blocking thread, which finishes on the timeout of the pool even if the probe
+/// has timed out before it.
+async fn probe_database(
+    config: Arc<BldConfig>,
+    pool: Pool<ConnectionManager<SqliteConnection>>,
+) -> Result<QueueResponse> {
+    spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        queue_status(&config, &mut conn)
+    })
+    .await?
+}
+
+async fn probe_supervisor(supervisor: &BldLocalSupervisorConfig) -> Result<()> {
+    match &supervisor.socket {
+        #[cfg(unix)]
+        Some(socket) => {
+            UnixStream::connect(socket)
+                .await
+                .map_err(|e| anyhow!("unable to connect to the unix socket {socket}, {e}"))?;
+        }
+        #[cfg(not(unix))]
+        Some(socket) => anyhow::bail!(
+            "unable to connect to the unix socket {socket}, unix sockets are not supported"
+        ),
+        None => {
+            let address = format!("{}:{}", supervisor.host, supervisor.port);
+            TcpStream::connect(&address)
+                .await
+                .map_err(|e| anyhow!("unable to connect to {address}, {e}"))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bld_core::database::new_connection_pool;
+    use bld_core::database::pipeline_runs::{self, PR_STATE_RUNNING};
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_dir_all};
+    use std::path::PathBuf;
+    use tokio::net::TcpListener;
+    use uuid::Uuid;
+
+    const LIMIT: Duration = Duration::from_millis(500);
+
+    /// A database with a running run and a supervisor that accepts connections.
+    async fn healthy() -> (
+        PathBuf,
+        BldConfig,
+        Pool<ConnectionManager<SqliteConnection>>,
+        TcpListener,
+    ) {
+        let root = temp_dir().join(format!("bld-health-{}", Uuid::new_v4()));
+        create_dir_all(&root).unwrap();
+        let pool = new_connection_pool(&root.display().to_string()).unwrap();
+        let mut conn = pool.get().unwrap();
+        pipeline_runs::insert(&mut conn, "id", "build.yaml", "user", None).unwrap();
+        pipeline_runs::update_state(&mut conn, "id", PR_STATE_RUNNING).unwrap();
+        let supervisor = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut config = BldConfig::default();
+        config.local.supervisor.host = "127.0.0.1".to_string();
+        config.local.supervisor.port = supervisor.local_addr().unwrap().port() as i64;
+        (root, config, pool, supervisor)
+    }
+
+    fn probe_of<'a>(health: &'a HealthResponse, name: &str) -> &'a ProbeResponse {
+        health.probes.iter().find(|p| p.name == name).unwrap()
+    }
+
+    fn error_of(probes: &[ProbeOutcome], name: &str) -> Option<String> {
+        probes
+            .iter()
+            .find(|p| p.response.name == name)
+            .and_then(|p| p.error.clone())
+    }
+
+    #[tokio::test]
+    async fn healthy_subsystems_are_ok() {
+        let (root, config, pool, _supervisor) = healthy().await;
+
+        let health = check_health(Arc::new(config), pool, false, LIMIT).await;
+        assert_eq!(health.status, HealthStatus::Ok);
+        assert_eq!(health.probes.len(), 2);
+        assert!(health.probes.iter().all(|p| p.status == HealthStatus::Ok));
+        let response = serde_json::to_value(&health).unwrap();
+        assert_eq!(
+            response,
+            serde_json::json!({
+                "status": "ok",
+                "probes": [
+                    { "name": "database", "status": "ok" },
+                    { "name": "supervisor", "status": "ok" }
+                ],
+                "active_runs": 1,
+                "queued_runs": 0
+            })
+        );
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn unreachable_database_is_down() {
+        let (root, config, _pool, _supervisor) = healthy().await;
+        let missing = root.join("missing").join("bld-db");
+        let pool = Pool::builder()
+            .connection_timeout(Duration::from_millis(100))
+            .build_unchecked(ConnectionManager::new(missing.display().to_string()));
+
+        let config = Arc::new(config);
+        let (probes, _) = probe_all(config.clone(), pool.clone(), false, LIMIT).await;
+        assert!(error_of(&probes, PROBE_DATABASE).is_some());
+        let health = check_health(config, pool, false, LIMIT).await;
+        assert_eq!(health.status, HealthStatus::Down);
+        assert_eq!(health.active_runs, None);
+        assert_eq!(probe_of(&health, PROBE_DATABASE).status, HealthStatus::Down);
+        assert_eq!(probe_of(&health, PROBE_SUPERVISOR).status, HealthStatus::Ok);
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn unreachable_supervisor_is_degraded() {
+        let (root, mut config, pool, _supervisor) = healthy().await;
+        let socket = root.join("supervisor.sock").display().to_string();
+        config.local.supervisor.socket = Some(socket.clone());
+
+        let config = Arc::new(config);
+        let (probes, _) = probe_all(config.clone(), pool.clone(), false, LIMIT).await;
+        assert!(error_of(&probes, PROBE_SUPERVISOR)
+            .unwrap()
+            .contains(&socket));
+        let health = check_health(config, pool, false, LIMIT).await;
+        assert_eq!(health.status, HealthStatus::Degraded);
+        assert_eq!(
+            probe_of(&health, PROBE_SUPERVISOR).status,
+            HealthStatus::Down
+        );
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn hung_docker_daemon_times_out() {
+        let (root, mut config, pool, _supervisor) = healthy().await;
+        // the daemon accepts connections but never responds.
+        let docker = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        config.local.docker_url = format!("tcp://{}", docker.local_addr().unwrap());
+
+        let config = Arc::new(config);
+        let start = Instant::now();
+        let (probes, _) = probe_all(config.clone(), pool.clone(), true, LIMIT).await;
+        assert!(start.elapsed() < LIMIT * 4);
+        assert!(error_of(&probes, PROBE_DOCKER)
+            .unwrap()
+            .contains("timed out"));
+        let health = check_health(config, pool, true, LIMIT).await;
+        assert_eq!(health.status, HealthStatus::Degraded);
+        assert_eq!(probe_of(&health, PROBE_DOCKER).status, HealthStatus::Down);
+
+        remove_dir_all(root).unwrap();
+    }
+}
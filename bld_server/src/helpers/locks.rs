@@ -0,0 +1,194 @@
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex as WriteMutex;
+use tracing::info;
+use uuid::Uuid;
+
+pub const DEFAULT_LOCK_TTL_SECONDS: u64 = 300;
+pub const MAX_LOCK_TTL_SECONDS: u64 = 3600;
+
+struct PipelineLock {
+    token: String,
+    holder: String,
+    expires_at: Instant,
+}
+
+/// Advisory locks on server pipelines that prevent concurrent edits from overwriting
+/// each other. Locks expire after their ttl so that a client that crashed while holding
+/// one can't block a pipeline indefinitely.
+#[derive(Default)]
+pub struct PipelineLocks {
+    locks: Mutex<HashMap<String, PipelineLock>>,
+    writes: Mutex<HashMap<String, Arc<WriteMutex<()>>>>,
+}
+
+impl PipelineLocks {
+    fn remove_expired(locks: &mut HashMap<String, PipelineLock>) {
+        let now = Instant::now();
+        locks.retain(|name, lock| {
+            let expired = lock.expires_at <= now;
+            if expired {
+                info!("lock of pipeline {name} held by {} expired", lock.holder);
+            }
+            !expired
+        });
+    }
+
+    fn err_locked(name: &str, lock: &PipelineLock) -> Result<()> {
+        let remaining = lock.expires_at.saturating_duration_since(Instant::now());
+        bail!(
+            "pipeline {name} is locked by {} for another {} seconds",
+            lock.holder,
+            remaining.as_secs()
+        )
+    }
+
+    /// Acquires the lock of a pipeline and returns its token. Providing the token of the
+    /// lock that is currently held renews it instead.
+    pub fn acquire(
+        &self,
+        name: &str,
+        holder: &str,
+        ttl_seconds: Option<u64>,
+        token: Option<&str>,
+    ) -> Result<String> {
+        let ttl = ttl_seconds
+            .unwrap_or(DEFAULT_LOCK_TTL_SECONDS)
+            .clamp(1, MAX_LOCK_TTL_SECONDS);
+        let expires_at = Instant::now() + Duration::from_secs(ttl);
+        let mut locks = self.locks.lock().unwrap();
+        Self::remove_expired(&mut locks);
+        if let Some(lock) = locks.get_mut(name) {
+            if Some(lock.token.as_str()) != token {
+                Self::err_locked(name, lock)?;
+            }
+            lock.expires_at = expires_at;
+            info!("lock of pipeline {name} renewed by {holder} for {ttl} seconds");
+            return Ok(lock.token.to_string());
+        }
+        let lock = PipelineLock {
+            token: Uuid::new_v4().to_string(),
+            holder: holder.to_string(),
+            expires_at,
+        };
+        let token = lock.token.to_string();
+        locks.insert(name.to_string(), lock);
+        info!("lock of pipeline {name} acquired by {holder} for {ttl} seconds");
+        Ok(token)
+    }
+
+    pub fn release(&self, name: &str, holder: &str, token: &str) -> Result<()> {
+        let mut locks = self.locks.lock().unwrap();
+        Self::remove_expired(&mut locks);
+        match locks.get(name) {
+            Some(lock) if lock.token != token => Self::err_locked(name, lock),
+            Some(_) => {
+                locks.remove(name);
+                info!("lock of pipeline {name} released by {holder}");
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Checks that a pipeline is either unlocked or locked with the provided token.
+    pub fn check(&self, name: &str, token: Option<&str>) -> Result<()> {
+        let mut locks = self.locks.lock().unwrap();
+        Self::remove_expired(&mut locks);
+        match locks.get(name) {
+            Some(lock) if Some(lock.token.as_str()) != token => Self::err_locked(name, lock),
+            _ => Ok(()),
+        }
+    }
+
+    /// Runs the operation if the pipeline is either unlocked or locked with the provided token.
+    /// Operations on the same pipeline wait for each other, so that the check and a write of the
+    /// pipeline can't be interleaved with another push, while operations on other pipelines
+    /// aren't blocked by it.
+    pub async fn with_lock<T, F>(&self, name: &str, token: Option<&str>, f: F) -> Result<T>
+    where
+        F: FnOnce() -> T,
+    {
+        let write = self
+            .writes
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_default()
+            .clone();
+        let result = {
+            let _guard = write.lock().await;
+            self.check(name, token).map(|_| f())
+        };
+        let mut writes = self.writes.lock().unwrap();
+        if Arc::strong_count(&write) == 2 {
+            writes.remove(name);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pipeline_locks_reject_other_holders() {
+        let locks = PipelineLocks::default();
+        let token = locks.acquire("mock", "user1", None, None).unwrap();
+
+        assert!(locks.acquire("mock", "user2", None, None).is_err());
+        assert!(locks.check("mock", None).is_err());
+        assert!(locks.check("mock", Some(&token)).is_ok());
+        assert!(locks.release("mock", "user2", "invalid").is_err());
+        assert_eq!(
+            locks.acquire("mock", "user1", None, Some(&token)).unwrap(),
+            token
+        );
+
+        locks.release("mock", "user1", &token).unwrap();
+        assert!(locks.check("mock", None).is_ok());
+        assert!(locks.acquire("mock", "user2", None, None).is_ok());
+    }
+
+    #[tokio::test]
+    async fn pipeline_locks_run_operations_only_for_the_holder() {
+        let locks = PipelineLocks::default();
+        let token = locks.acquire("mock", "user1", None, None).unwrap();
+
+        assert!(locks.with_lock("mock", None, || ()).await.is_err());
+        assert_eq!(
+            locks.with_lock("mock", Some(&token), || 1).await.unwrap(),
+            1
+        );
+        assert_eq!(locks.with_lock("other", None, || 2).await.unwrap(), 2);
+        assert!(locks.writes.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn pipeline_locks_only_wait_for_operations_on_the_same_pipeline() {
+        let locks = Arc::new(PipelineLocks::default());
+        let write = locks
+            .writes
+            .lock()
+            .unwrap()
+            .entry("mock".to_string())
+            .or_default()
+            .clone();
+        let guard = write.lock().await;
+
+        assert_eq!(locks.with_lock("other", None, || 1).await.unwrap(), 1);
+
+        let waiting = tokio::spawn({
+            let locks = Arc::clone(&locks);
+            async move { locks.with_lock("mock", None, || 2).await.unwrap() }
+        });
+        tokio::task::yield_now().await;
+        assert!(!waiting.is_finished());
+
+        drop(guard);
+        assert_eq!(waiting.await.unwrap(), 2);
+    }
+}
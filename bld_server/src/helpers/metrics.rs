@@ -0,0 +1,187 @@
+use crate::helpers::queue_status;
+use crate::responses::seconds_between;
+use anyhow::Result;
+use bld_config::BldConfig;
+use bld_core::database::pipeline_runs;
+use bld_core::proxies::PipelineCacheStats;
+use diesel::sqlite::SqliteConnection;
+use prometheus::{
+    histogram_opts, opts, Encoder, HistogramVec, IntCounter, IntCounterVec, IntGauge, Registry,
+    TextEncoder,
+};
+
+/// Gathers the metrics of the runs in the Prometheus text format. The runs are executed by the
+/// workers of the supervisor in processes of their own, so the metrics are read from the runs
+/// that the workers persist instead of being kept in memory by the server. The metrics of the
+/// pipeline cache are the ones of the server itself. The runs are counted by the database
+/// instead of being loaded, apart from the dates of the ended runs for the durations.
+pub fn gather_metrics(
+    config: &BldConfig,
+    conn: &mut SqliteConnection,
+    cache: Option<PipelineCacheStats>,
+) -> Result<String> {
+    let runs_total = IntCounterVec::new(
+        opts!(
+            "bld_pipeline_runs_total",
+            "The number of runs of a pipeline by status"
+        ),
+        &["name", "status"],
+    )?;
+    let duration = HistogramVec::new(
+        histogram_opts!(
+            "bld_pipeline_duration_seconds",
+            "The duration of the ended runs of a pipeline in seconds"
+        ),
+        &["name"],
+    )?;
+    let started_total = IntCounterVec::new(
+        opts!(
+            "bld_pipeline_runs_started_total",
+            "The number of runs of a pipeline that started running"
+        ),
+        &["name"],
+    )?;
+    let build_errors_total = IntCounterVec::new(
+        opts!(
+            "bld_pipeline_build_errors_total",
+            "The number of runs of a pipeline whose runner failed to be built"
+        ),
+        &["name"],
+    )?;
+    let active_runs = IntGauge::new("bld_active_runs", "The number of running workers")?;
+    let queue_depth = IntGauge::new(
+        "bld_queue_depth",
+        "The number of runs that wait for a worker",
+    )?;
+
+    let docker_retries = IntCounter::new(
+        "bld_docker_api_retries_total",
+        "The number of docker api calls of the runs that were retried",
+    )?;
+    let docker_breaker_trips = IntCounter::new(
+        "bld_docker_breaker_trips_total",
+        "The number of times that the docker api circuit breaker of a run opened",
+    )?;
+
+    for (name, state, count) in pipeline_runs::count_by_state(conn)? {
+        runs_total
+            .with_label_values(&[&name, &state])
+            .inc_by(count.max(0) as u64);
+    }
+    for (name, count) in pipeline_runs::count_started(conn)? {
+        started_total
+            .with_label_values(&[&name])
+            .inc_by(count.max(0) as u64);
+    }
+    for (name, count) in pipeline_runs::count_build_failed(conn)? {
+        build_errors_total
+            .with_label_values(&[&name])
+            .inc_by(count.max(0) as u64);
+    }
+    for (name, start, end) in pipeline_runs::select_ended_dates(conn)? {
+        if let Some(seconds) = seconds_between(&start, &end) {
+            duration.with_label_values(&[&name]).observe(seconds);
+        }
+    }
+    let (retries, breaker_trips) = pipeline_runs::sum_docker_counters(conn)?;
+    docker_retries.inc_by(retries.max(0) as u64);
+    docker_breaker_trips.inc_by(breaker_trips.max(0) as u64);
+    let queue = queue_status(config, conn)?;
+    active_runs.set(queue.active as i64);
+    queue_depth.set(queue.queued.len() as i64);
+
+    let registry = Registry::new();
+    registry.register(Box::new(runs_total))?;
+    registry.register(Box::new(duration))?;
+    registry.register(Box::new(started_total))?;
+    registry.register(Box::new(build_errors_total))?;
+    registry.register(Box::new(active_runs))?;
+    registry.register(Box::new(queue_depth))?;
+    registry.register(Box::new(docker_retries))?;
+    registry.register(Box::new(docker_breaker_trips))?;
+
+    if let Some(cache) = cache {
+        let lookups = IntCounterVec::new(
+            opts!(
+                "bld_pipeline_cache_lookups_total",
+                "The number of reads of pipelines by whether they were found in the cache"
+            ),
+            &["result"],
+        )?;
+        lookups.with_label_values(&["hit"]).inc_by(cache.hits);
+        lookups.with_label_values(&["miss"]).inc_by(cache.misses);
+        let entries = IntGauge::new(
+            "bld_pipeline_cache_entries",
+            "The number of pipelines in the cache",
+        )?;
+        entries.set(cache.entries as i64);
+        registry.register(Box::new(lookups))?;
+        registry.register(Box::new(entries))?;
+    }
+
+    let mut buffer = vec![];
+    TextEncoder::new().encode(&registry.gather(), &mut buffer)?;
+    Ok(String::from_utf8(buffer)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bld_core::database::new_connection_pool;
+    use bld_core::database::pipeline_runs::{PR_STATE_FINISHED, PR_STATE_QUEUED, PR_STATE_RUNNING};
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_dir_all};
+    use uuid::Uuid;
+
+    #[test]
+    fn metrics_are_gathered_from_the_runs() {
+        let root = temp_dir().join(format!("bld-metrics-{}", Uuid::new_v4()));
+        create_dir_all(&root).unwrap();
+        let pool = new_connection_pool(&root.display().to_string()).unwrap();
+        let mut conn = pool.get().unwrap();
+        for (run_id, state) in [
+            ("0", PR_STATE_FINISHED),
+            ("1", PR_STATE_FINISHED),
+            ("2", PR_STATE_RUNNING),
+            ("3", PR_STATE_QUEUED),
+            ("4", PR_STATE_QUEUED),
+        ] {
+            pipeline_runs::insert(&mut conn, run_id, "build.yaml", "user", None).unwrap();
+            if state == PR_STATE_FINISHED {
+                pipeline_runs::update_state(&mut conn, run_id, PR_STATE_RUNNING).unwrap();
+            }
+            pipeline_runs::update_state(&mut conn, run_id, state).unwrap();
+        }
+        pipeline_runs::update_docker_counters(&mut conn, "0", 2, 1).unwrap();
+        pipeline_runs::update_build_failed(&mut conn, "4").unwrap();
+
+        let cache = PipelineCacheStats {
+            hits: 3,
+            misses: 1,
+            entries: 1,
+            watched: true,
+        };
+        let metrics = gather_metrics(&BldConfig::default(), &mut conn, Some(cache)).unwrap();
+        assert!(
+            metrics.contains("bld_pipeline_runs_total{name=\"build.yaml\",status=\"finished\"} 2")
+        );
+        assert!(
+            metrics.contains("bld_pipeline_runs_total{name=\"build.yaml\",status=\"running\"} 1")
+        );
+        assert!(metrics.contains("bld_pipeline_duration_seconds_count{name=\"build.yaml\"} 3"));
+        assert!(metrics.contains("bld_pipeline_runs_started_total{name=\"build.yaml\"} 3"));
+        assert!(metrics.contains("bld_pipeline_build_errors_total{name=\"build.yaml\"} 1"));
+        assert!(
+            metrics.contains("bld_pipeline_runs_total{name=\"build.yaml\",status=\"faulted\"} 1")
+        );
+        assert!(metrics.contains("bld_active_runs 1"));
+        assert!(metrics.contains("bld_queue_depth 1"));
+        assert!(metrics.contains("bld_docker_api_retries_total 2"));
+        assert!(metrics.contains("bld_docker_breaker_trips_total 1"));
+        assert!(metrics.contains("bld_pipeline_cache_lookups_total{result=\"hit\"} 3"));
+        assert!(metrics.contains("bld_pipeline_cache_lookups_total{result=\"miss\"} 1"));
+        assert!(metrics.contains("bld_pipeline_cache_entries 1"));
+        drop(conn);
+        remove_dir_all(root).unwrap();
+    }
+}
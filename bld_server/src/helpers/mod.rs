@@ -1,3 +1,21 @@
+mod artifacts;
+mod connections;
 mod enqueue;
+mod etag;
+mod health;
+mod locks;
+mod metrics;
+mod platforms;
+mod queue;
+mod scheduler;
 
+pub use artifacts::*;
+pub use connections::*;
 pub use enqueue::*;
+pub use etag::*;
+pub use health::*;
+pub use locks::*;
+pub use metrics::*;
+pub use platforms::*;
+pub use queue::*;
+pub use scheduler::*;
@@ -0,0 +1,143 @@
+use crate::responses::PlatformInfo;
+use actix_web::web::Data;
+use anyhow::{bail, Result};
+use bld_config::BldConfig;
+use bld_runner::{docker_version, RunsOn};
+use std::env::var;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+use tracing::{debug, warn};
+
+pub const PLATFORM_MACHINE: &str = "machine";
+pub const PLATFORM_DOCKER: &str = "docker";
+pub const PLATFORM_KUBERNETES: &str = "kubernetes";
+pub const PLATFORM_SSH: &str = "ssh";
+
+/// The interval that the available platforms are detected again, so that a docker daemon
+/// that starts after the server is picked up.
+pub const PLATFORMS_REFRESH_SECONDS: u64 = 60;
+
+const DOCKER_PROBE_TIMEOUT_SECONDS: u64 = 5;
+
+/// The platforms that the workers of the server can execute pipelines on, used to reject runs
+/// of pipelines that would fault once a worker picks them up.
+#[derive(Default)]
+pub struct Platforms {
+    available: RwLock<Vec<PlatformInfo>>,
+    docker_configured: AtomicBool,
+}
+
+impl Platforms {
+    pub fn new(available: Vec<PlatformInfo>) -> Self {
+        let docker_configured = AtomicBool::new(has_docker(&available));
+        Self {
+            available: RwLock::new(available),
+            docker_configured,
+        }
+    }
+
+    /// Detects the platforms of the host. Pipelines on a machine or over ssh can always be
+    /// executed, while docker is available only if its daemon responds and kubernetes only if
+    /// a kube config or an in-cluster service account is found.
+    pub async fn detect(config: &BldConfig) -> Vec<PlatformInfo> {
+        let mut available = vec![
+            PlatformInfo::new(PLATFORM_MACHINE, None),
+            PlatformInfo::new(PLATFORM_SSH, None),
+        ];
+        let docker_url = &config.local.docker_url;
+        let duration = Duration::from_secs(DOCKER_PROBE_TIMEOUT_SECONDS);
+        match timeout(duration, docker_version(docker_url)).await {
+            Ok(Ok(_)) => available.push(PlatformInfo::new(PLATFORM_DOCKER, Some(docker_url))),
+            Ok(Err(e)) => debug!("docker is unavailable at {docker_url}. {e}"),
+            Err(_) => debug!("docker is unavailable at {docker_url}, timed out"),
+        }
+        let kube_config = var("HOME")
+            .map(|home| Path::new(&home).join(".kube").join("config").is_file())
+            .unwrap_or(false);
+        if var("KUBECONFIG").is_ok() || var("KUBERNETES_SERVICE_HOST").is_ok() || kube_config {
+            available.push(PlatformInfo::new(PLATFORM_KUBERNETES, None));
+        }
+        available
+    }
+
+    pub fn available(&self) -> Vec<PlatformInfo> {
+        self.available.read().unwrap().clone()
+    }
+
+    pub fn set_available(&self, available: Vec<PlatformInfo>) {
+        if has_docker(&available) {
+            self.docker_configured.store(true, Ordering::Relaxed);
+        }
+        *self.available.write().unwrap() = available;
+    }
+
+    /// Whether the docker daemon has responded since the server started, so that a server
+    /// without docker isn't reported as unhealthy for it.
+    pub fn docker_configured(&self) -> bool {
+        self.docker_configured.load(Ordering::Relaxed)
+    }
+
+    /// Fails with the missing platform when the pipeline runs on one that isn't available.
+    /// Pipelines on a remote docker host don't need the docker daemon of the server.
+    pub fn check(&self, name: &str, runs_on: &RunsOn) -> Result<()> {
+        let platform = match runs_on {
+            RunsOn::Docker(..) => PLATFORM_DOCKER,
+            RunsOn::Kubernetes { .. } => PLATFORM_KUBERNETES,
+            RunsOn::Machine | RunsOn::DockerRemote(..) | RunsOn::Ssh { .. } => return Ok(()),
+        };
+        if !self.available().iter().any(|p| p.name == platform) {
+            bail!("pipeline {name} runs on {platform} but the {platform} platform isn't available on the server");
+        }
+        Ok(())
+    }
+}
+
+fn has_docker(available: &[PlatformInfo]) -> bool {
+    available.iter().any(|p| p.name == PLATFORM_DOCKER)
+}
+
+/// Detects the available platforms periodically for the lifetime of the server.
+pub async fn refresh_platforms(platforms: Data<Platforms>, config: Arc<BldConfig>) {
+    loop {
+        sleep(Duration::from_secs(PLATFORMS_REFRESH_SECONDS)).await;
+        let available = Platforms::detect(&config).await;
+        if !has_docker(&available) {
+            warn!("docker is unavailable at {}", config.local.docker_url);
+        }
+        platforms.set_available(available);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bld_runner::Pipeline;
+
+    #[test]
+    fn pipelines_on_unavailable_platforms_are_rejected() {
+        let platforms = Platforms::new(vec![PlatformInfo::new(PLATFORM_MACHINE, None)]);
+        let machine = Pipeline::parse("runs-on: machine\nsteps: []\n").unwrap();
+        let docker = Pipeline::parse("runs-on: ubuntu\nsteps: []\n").unwrap();
+        let remote = Pipeline::parse(
+            "runs-on:\n  image: ubuntu\n  docker-remote:\n    host: tcp://10.0.0.2:2376\nsteps: []\n",
+        )
+        .unwrap();
+
+        assert!(platforms.check("machine.yaml", &machine.runs_on).is_ok());
+        assert!(platforms.check("remote.yaml", &remote.runs_on).is_ok());
+        let error = platforms
+            .check("docker.yaml", &docker.runs_on)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("docker platform"));
+
+        platforms.set_available(vec![PlatformInfo::new(
+            PLATFORM_DOCKER,
+            Some("tcp://127.0.0.1:2376"),
+        )]);
+        assert!(platforms.check("docker.yaml", &docker.runs_on).is_ok());
+    }
+}
@@ -0,0 +1,21 @@
+use crate::responses::QueueResponse;
+use anyhow::Result;
+use bld_config::BldConfig;
+use bld_core::database::pipeline_runs::{self, PR_STATE_QUEUED, PR_STATE_RUNNING};
+use diesel::sqlite::SqliteConnection;
+
+/// Reads the state of the worker queue of the supervisor from the runs, since the supervisor
+/// sets the runs that wait for a worker as queued. Called pipelines are executed by the worker
+/// of their parent so they aren't counted.
+pub fn queue_status(config: &BldConfig, conn: &mut SqliteConnection) -> Result<QueueResponse> {
+    let active = pipeline_runs::select_top_level_by_state(conn, PR_STATE_RUNNING)?.len();
+    let queued = pipeline_runs::select_top_level_by_state(conn, PR_STATE_QUEUED)?
+        .into_iter()
+        .map(|r| r.id)
+        .collect();
+    Ok(QueueResponse {
+        max_workers: config.local.supervisor.workers as usize,
+        active,
+        queued,
+    })
+}
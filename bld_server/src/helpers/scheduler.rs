@@ -0,0 +1,147 @@
+use crate::extractors::User;
+use crate::helpers::{enqueue_worker, Platforms};
+use crate::requests::RunInfo;
+use actix_web::web::Data;
+use anyhow::{anyhow, Result};
+use bld_core::database::pipeline_schedules::{self, PipelineSchedule};
+use bld_core::proxies::PipelineFileSystemProxy;
+use bld_supervisor::base::ServerMessages;
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::SqliteConnection;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::sync::mpsc::Sender;
+use tokio::time::sleep;
+use tracing::{debug, error, info};
+
+pub const SCHEDULE_CHECK_INTERVAL_SECONDS: u64 = 10;
+pub const SCHEDULE_LABEL: &str = "schedule";
+
+/// Parses a cron expression in the standard five field format, or in the extended format of
+/// the cron crate with a field for seconds and an optional one for the year. Times are in UTC.
+pub fn parse_cron(expression: &str) -> Result<Schedule> {
+    let fields = expression.split_whitespace().count();
+    let expression = if fields == 5 {
+        format!("0 {expression}")
+    } else {
+        expression.to_string()
+    };
+    Schedule::from_str(&expression).map_err(|e| anyhow!("invalid cron expression. {e}"))
+}
+
+/// The next time that a cron expression is due, if it's valid and has one.
+pub fn next_schedule_run(expression: &str) -> Option<DateTime<Utc>> {
+    parse_cron(expression).ok()?.upcoming(Utc).next()
+}
+
+/// Checks if a schedule was due in the period after the start and up to the end.
+fn is_due(schedule: &PipelineSchedule, start: &DateTime<Utc>, end: &DateTime<Utc>) -> bool {
+    match parse_cron(&schedule.cron) {
+        Ok(cron) => cron.after(start).next().is_some_and(|next| next <= *end),
+        Err(e) => {
+            error!("schedule {} has an {e}", schedule.id);
+            false
+        }
+    }
+}
+
+/// Enqueues the runs of the enabled schedules that were due since the previous check, as the
+/// user that created each schedule. Schedules that were due while the server was down are
+/// skipped, so each one runs at most once per check.
+pub async fn run_schedules(
+    proxy: Data<PipelineFileSystemProxy>,
+    pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
+    enqueue_tx: Data<Sender<ServerMessages>>,
+    platforms: Data<Platforms>,
+) {
+    let mut start = Utc::now();
+    loop {
+        sleep(Duration::from_secs(SCHEDULE_CHECK_INTERVAL_SECONDS)).await;
+        let end = Utc::now();
+        let schedules = pool
+            .get()
+            .map_err(|e| anyhow!(e))
+            .and_then(|mut conn| pipeline_schedules::select_enabled(&mut conn));
+        let schedules = match schedules {
+            Ok(schedules) => schedules,
+            Err(e) => {
+                error!("unable to load the pipeline schedules. {e}");
+                continue;
+            }
+        };
+        for schedule in schedules.iter().filter(|s| is_due(s, &start, &end)) {
+            debug!("schedule {} of {} is due", schedule.id, schedule.pipeline);
+            let mut run = RunInfo::new(
+                &schedule.pipeline,
+                Some(schedule.environment()),
+                Some(schedule.variables()),
+            );
+            run.labels = Some(HashMap::from([(
+                SCHEDULE_LABEL.to_string(),
+                schedule.id.to_string(),
+            )]));
+            let user = User::new(&schedule.created_by);
+            let result = enqueue_worker(
+                &user,
+                proxy.clone(),
+                pool.clone(),
+                enqueue_tx.clone(),
+                &platforms,
+                run,
+                None,
+            )
+            .await;
+            match result {
+                Ok((run_id, _)) => info!(
+                    "enqueued run {run_id} of {} for schedule {}",
+                    schedule.pipeline, schedule.id
+                ),
+                Err(e) => error!(
+                    "unable to enqueue {} for schedule {}. {e}",
+                    schedule.pipeline, schedule.id
+                ),
+            }
+        }
+        start = end;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn schedule(cron: &str) -> PipelineSchedule {
+        PipelineSchedule {
+            id: "id".to_string(),
+            pipeline: "nightly.yaml".to_string(),
+            cron: cron.to_string(),
+            variables: None,
+            environment: None,
+            enabled: true,
+            created_by: "user".to_string(),
+            date_created: String::new(),
+        }
+    }
+
+    #[test]
+    fn schedules_are_due_once_their_cron_time_is_reached() {
+        assert!(parse_cron("0 2 * * *").is_ok());
+        assert!(parse_cron("30 0 2 * * * *").is_ok());
+        assert!(parse_cron("every day").is_err());
+        assert!(parse_cron("61 * * * *").is_err());
+
+        let start = Utc.ymd(2024, 1, 31).and_hms(1, 59, 55);
+        let end = Utc.ymd(2024, 1, 31).and_hms(2, 0, 5);
+        assert!(is_due(&schedule("0 2 * * *"), &start, &end));
+        assert!(!is_due(&schedule("0 3 * * *"), &start, &end));
+        assert!(!is_due(&schedule("invalid"), &start, &end));
+
+        let start = Utc.ymd(2024, 1, 31).and_hms(2, 0, 0);
+        assert!(!is_due(&schedule("0 2 * * *"), &start, &end));
+        assert!(next_schedule_run("*/5 * * * *").is_some());
+    }
+}
@@ -1,6 +1,7 @@
 pub mod endpoints;
 pub mod extractors;
 mod helpers;
+pub mod middlewares;
 pub mod requests;
 pub mod responses;
 mod server;
@@ -0,0 +1,107 @@
+use crate::extractors::authenticate;
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::web::Data;
+use actix_web::{Error, HttpMessage, HttpRequest, HttpResponse};
+use bld_config::BldConfig;
+use bld_core::high_avail::HA_SECRET_HEADER;
+use futures::future::{ready, LocalBoxFuture, Ready};
+use std::rc::Rc;
+use tracing::debug;
+
+/// The routes that are served without authentication, which are the home page, the redirect of
+/// the oauth2 login and the health check of load balancers.
+const PUBLIC_ROUTES: [&str; 3] = ["/", "/authRedirect", "/ha/health"];
+
+/// The routes that the nodes of a high availability cluster call each other with, which are
+/// accepted only with the shared secret of the cluster instead of a user.
+const HA_ROUTES: [&str; 3] = ["/ha/appendEntries", "/ha/installSnapshot", "/ha/vote"];
+
+/// Checks the secret header of a request against the secret of the cluster, comparing every byte
+/// so that the time taken doesn't reveal how much of the secret matched.
+fn has_ha_secret(req: &HttpRequest) -> bool {
+    let expected = req
+        .app_data::<Data<BldConfig>>()
+        .and_then(|config| config.local.ha_secret.clone());
+    let secret = req
+        .headers()
+        .get(HA_SECRET_HEADER)
+        .and_then(|h| h.to_str().ok());
+    match (expected, secret) {
+        (Some(expected), Some(secret)) if expected.len() == secret.len() => {
+            expected
+                .bytes()
+                .zip(secret.bytes())
+                .fold(0, |acc, (a, b)| acc | (a ^ b))
+                == 0
+        }
+        _ => false,
+    }
+}
+
+/// Rejects with a 401 status the requests to every route that isn't public and can't be
+/// authenticated, so that a new endpoint is protected even if it doesn't extract the user.
+/// The user of an authenticated request is stored in it for the user extractor.
+pub struct Authentication;
+
+impl<S, B> Transform<S, ServiceRequest> for Authentication
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = AuthenticationMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AuthenticationMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct AuthenticationMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for AuthenticationMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        Box::pin(async move {
+            if HA_ROUTES.contains(&req.path()) {
+                if !has_ha_secret(req.request()) {
+                    debug!("rejecting request to {} without the ha secret", req.path());
+                    let response = HttpResponse::Unauthorized().finish();
+                    return Ok(req.into_response(response).map_into_right_body());
+                }
+            } else if !PUBLIC_ROUTES.contains(&req.path()) {
+                match authenticate(req.request()).await {
+                    Ok(user) => {
+                        req.extensions_mut().insert(user);
+                    }
+                    Err(e) => {
+                        debug!("rejecting request to {}, {e}", req.path());
+                        let response = HttpResponse::Unauthorized().finish();
+                        return Ok(req.into_response(response).map_into_right_body());
+                    }
+                }
+            }
+            service
+                .call(req)
+                .await
+                .map(ServiceResponse::map_into_left_body)
+        })
+    }
+}
@@ -0,0 +1,7 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CheckpointsQueryInfo {
+    pub wait: Option<String>,
+    pub timeout: Option<u64>,
+}
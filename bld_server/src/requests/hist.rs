@@ -0,0 +1,111 @@
+use crate::requests::parse_label;
+use anyhow::{anyhow, bail, Result};
+use bld_config::definitions::{HIST_DEFAULT_LIMIT, HIST_MAX_LIMIT};
+use bld_core::database::pipeline_runs::HistoryFilter;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HistQueryInfo {
+    #[serde(alias = "name")]
+    pub pipeline: Option<String>,
+    pub state: Option<String>,
+    pub running: Option<bool>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    #[serde(alias = "since")]
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub label: Option<String>,
+}
+
+impl HistQueryInfo {
+    pub fn new(pipeline: Option<String>, state: Option<String>, limit: Option<i64>) -> Self {
+        Self {
+            pipeline,
+            state,
+            limit,
+            ..Default::default()
+        }
+    }
+
+    /// The filter of the history for the query. A page, starting from 1, takes the place of the
+    /// offset and the number of runs per page the place of the limit.
+    pub fn filter(&self) -> Result<HistoryFilter> {
+        if let Some(page) = self.page.filter(|p| *p < 1) {
+            bail!("invalid page {page}, expected a number greater than 0");
+        }
+        let limit = self.per_page.or(self.limit).unwrap_or(HIST_DEFAULT_LIMIT);
+        if !(1..=HIST_MAX_LIMIT).contains(&limit) {
+            bail!("invalid limit {limit}, expected a number between 1 and {HIST_MAX_LIMIT}");
+        }
+        let offset = match self.page {
+            Some(page) => page
+                .checked_sub(1)
+                .and_then(|p| p.checked_mul(limit))
+                .ok_or_else(|| anyhow!("invalid page {page}, the offset is out of range"))?,
+            None => self.offset.unwrap_or_default(),
+        };
+        if offset < 0 {
+            bail!("invalid offset {offset}, expected a non negative number");
+        }
+        Ok(HistoryFilter {
+            name: self.pipeline.clone(),
+            state: self.state.clone(),
+            running: self.running,
+            from: self.from.clone(),
+            to: self.to.clone(),
+            labels: self.labels()?,
+            limit,
+            offset,
+        })
+    }
+
+    /// The labels that the runs should have, which are given as comma separated `name:value` pairs.
+    pub fn labels(&self) -> Result<Vec<(String, String)>> {
+        self.label
+            .iter()
+            .flat_map(|label| label.split(','))
+            .map(parse_label)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::web::Query;
+
+    #[test]
+    fn hist_query_is_converted_to_a_filter_of_a_page() {
+        let query = "page=3&per_page=25&name=foo&running=false&since=2024-01-01";
+        let params = Query::<HistQueryInfo>::from_query(query).unwrap();
+        let filter = params.filter().unwrap();
+        assert_eq!(filter.name.as_deref(), Some("foo"));
+        assert_eq!(filter.running, Some(false));
+        assert_eq!(filter.from.as_deref(), Some("2024-01-01"));
+        assert_eq!((filter.limit, filter.offset), (25, 50));
+
+        let params = Query::<HistQueryInfo>::from_query("limit=10&offset=5").unwrap();
+        let filter = params.filter().unwrap();
+        assert_eq!((filter.limit, filter.offset), (10, 5));
+
+        let params = Query::<HistQueryInfo>::from_query("").unwrap();
+        assert_eq!(params.filter().unwrap().limit, HIST_DEFAULT_LIMIT);
+
+        let params = Query::<HistQueryInfo>::from_query("page=0").unwrap();
+        assert!(params.filter().is_err());
+        let params = Query::<HistQueryInfo>::from_query("per_page=-1").unwrap();
+        assert!(params.filter().is_err());
+        let params = Query::<HistQueryInfo>::from_query("limit=0").unwrap();
+        assert!(params.filter().is_err());
+        let params = Query::<HistQueryInfo>::from_query("per_page=1001").unwrap();
+        assert!(params.filter().is_err());
+        let params = Query::<HistQueryInfo>::from_query("offset=-1").unwrap();
+        assert!(params.filter().is_err());
+        let query = format!("page={}&per_page={HIST_MAX_LIMIT}", i64::MAX);
+        let params = Query::<HistQueryInfo>::from_query(&query).unwrap();
+        assert!(params.filter().is_err());
+    }
+}
@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct LockInfo {
+    pub ttl_seconds: Option<u64>,
+    pub token: Option<String>,
+}
+
+impl LockInfo {
+    pub fn new(ttl_seconds: Option<u64>, token: Option<String>) -> Self {
+        Self { ttl_seconds, token }
+    }
+}
@@ -1,7 +1,27 @@
+mod api_key;
+mod artifacts;
+mod checkpoints;
+mod hist;
+mod lock;
+mod logs;
 mod monit;
 mod push;
+mod remove;
 mod run;
+mod schedule;
+mod search;
+mod stats;
 
+pub use api_key::*;
+pub use artifacts::*;
+pub use checkpoints::*;
+pub use hist::*;
+pub use lock::*;
+pub use logs::*;
 pub use monit::*;
 pub use push::*;
+pub use remove::*;
 pub use run::*;
+pub use schedule::*;
+pub use search::*;
+pub use stats::*;
@@ -7,10 +7,27 @@ pub struct MonitInfo {
     pub id: Option<String>,
     pub name: Option<String>,
     pub last: bool,
+    /// Additional runs to monitor in the same session, with their lines prefixed by their ids.
+    #[serde(default)]
+    pub ids: Vec<String>,
+    /// Monitors the runs that are running or queued, including the ones that start later.
+    #[serde(default)]
+    pub all: bool,
 }
 
 impl MonitInfo {
     pub fn new(id: Option<String>, name: Option<String>, last: bool) -> Self {
-        Self { id, name, last }
+        Self {
+            id,
+            name,
+            last,
+            ..Default::default()
+        }
+    }
+
+    pub fn runs(mut self, ids: Vec<String>, all: bool) -> Self {
+        self.ids = ids;
+        self.all = all;
+        self
     }
 }
@@ -4,6 +4,8 @@ use serde::{Deserialize, Serialize};
 pub struct PushInfo {
     pub name: String,
     pub content: String,
+    pub base_hash: Option<String>,
+    pub lock_token: Option<String>,
 }
 
 impl PushInfo {
@@ -11,6 +13,8 @@ impl PushInfo {
         PushInfo {
             name: name.to_string(),
             content: content.to_string(),
+            base_hash: None,
+            lock_token: None,
         }
     }
 }
@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+/// The pipelines of a remove request, given either as a single name, as sent by older clients,
+/// or as a list of names.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum RemoveInfo {
+    Single(String),
+    Many(Vec<String>),
+}
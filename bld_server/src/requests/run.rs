@@ -1,13 +1,29 @@
 use actix::Message;
+use anyhow::{anyhow, bail, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+/// The run of a pipeline. The entries of a run request file are accepted as aliases, so that
+/// the same file can be posted to the `/run` endpoint as json.
 #[derive(Serialize, Deserialize, Debug, Message)]
 #[rtype(result = "()")]
 pub struct RunInfo {
+    #[serde(alias = "pipeline")]
     pub name: String,
+    #[serde(default)]
     pub environment: Option<HashMap<String, String>>,
+    #[serde(default)]
     pub variables: Option<HashMap<String, String>>,
+    #[serde(default)]
+    pub secrets: Option<HashMap<String, String>>,
+    #[serde(default, alias = "git-url")]
+    pub git_url: Option<String>,
+    #[serde(default, alias = "git-ref")]
+    pub git_ref: Option<String>,
+    #[serde(default, alias = "dry-run")]
+    pub dry_run: bool,
+    #[serde(default)]
+    pub labels: Option<HashMap<String, String>>,
 }
 
 impl RunInfo {
@@ -20,6 +36,81 @@ impl RunInfo {
             name: name.to_string(),
             environment: env,
             variables: vars,
+            secrets: None,
+            git_url: None,
+            git_ref: None,
+            dry_run: false,
+            labels: None,
         }
     }
+
+    /// Sets the labels of the run, which are stored with it and can be used to filter the history.
+    pub fn labels(mut self, labels: Option<HashMap<String, String>>) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Sets the secrets of the run, which are masked in its logs.
+    pub fn secrets(mut self, secrets: Option<HashMap<String, String>>) -> Self {
+        self.secrets = secrets;
+        self
+    }
+
+    /// Logs the commands and artifacts of the run instead of executing them.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Runs the pipeline from a git repository instead of the ones pushed to the server.
+    pub fn git(mut self, url: Option<String>, git_ref: Option<String>) -> Self {
+        self.git_url = url;
+        self.git_ref = git_ref;
+        self
+    }
+}
+
+/// Parses a label in the `name:value` format of the run and hist commands.
+pub fn parse_label(label: &str) -> Result<(String, String)> {
+    let (name, value) = label
+        .split_once(':')
+        .ok_or_else(|| anyhow!("invalid label {label}, expected the name:value format"))?;
+    validate_label(name, value)?;
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// Checks that a label can be used in a filter of the history, where the labels are separated
+/// by commas and their names by a colon from their values.
+pub fn validate_label(name: &str, value: &str) -> Result<()> {
+    if name.is_empty() {
+        bail!("the name of a label can't be empty");
+    }
+    if name.contains(':') || name.contains(',') || value.contains(',') {
+        bail!("invalid label {name}:{value}, a label can't contain commas or a colon in its name");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_labels_are_parsed_and_validated() {
+        assert_eq!(
+            parse_label("env:production").unwrap(),
+            ("env".to_string(), "production".to_string())
+        );
+        assert_eq!(
+            parse_label("url:http://host").unwrap(),
+            ("url".to_string(), "http://host".to_string())
+        );
+        assert_eq!(
+            parse_label("env:").unwrap(),
+            ("env".to_string(), String::new())
+        );
+        assert!(parse_label("production").is_err());
+        assert!(parse_label(":production").is_err());
+        assert!(parse_label("env:production,staging").is_err());
+    }
 }
@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ScheduleInfo {
+    pub pipeline: String,
+    pub cron: String,
+    #[serde(default)]
+    pub variables: Option<HashMap<String, String>>,
+    #[serde(default)]
+    pub environment: Option<HashMap<String, String>>,
+    #[serde(default)]
+    pub enabled: Option<bool>,
+}
+
+impl ScheduleInfo {
+    pub fn new(pipeline: &str, cron: &str) -> Self {
+        Self {
+            pipeline: pipeline.to_string(),
+            cron: cron.to_string(),
+            variables: None,
+            environment: None,
+            enabled: None,
+        }
+    }
+}
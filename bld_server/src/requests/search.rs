@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SearchQueryInfo {
+    pub q: String,
+}
+
+impl SearchQueryInfo {
+    pub fn new(term: &str) -> Self {
+        Self {
+            q: term.to_string(),
+        }
+    }
+}
@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatsQueryInfo {
+    #[serde(alias = "pipeline")]
+    pub name: String,
+}
+
+impl StatsQueryInfo {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+use tabled::Tabled;
+
+/// A created api key. The key is returned only once since the server stores its hash.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiKeyResponse {
+    pub name: String,
+    pub key: String,
+}
+
+impl ApiKeyResponse {
+    pub fn new(name: &str, key: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            key: key.to_string(),
+        }
+    }
+}
+
+/// An api key of the server without its hash, along with the user that its requests are made as.
+#[derive(Serialize, Deserialize, Debug, Tabled)]
+pub struct ApiKeyEntry {
+    pub name: String,
+    pub created_by: String,
+    pub date_created: String,
+}
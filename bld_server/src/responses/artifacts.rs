@@ -0,0 +1,28 @@
+use bld_core::database::pipeline_runs::{
+    PR_STATE_FAULTED, PR_STATE_FINISHED, PR_STATE_INTERRUPTED,
+};
+use serde::{Deserialize, Serialize};
+
+/// A file of the artifacts of a run, without a size when its path has been removed from the
+/// server after the run.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ArtifactEntry {
+    pub name: String,
+    pub size: Option<u64>,
+}
+
+/// The files of the `get` artifacts of a run along with its current state.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ArtifactsResponse {
+    pub run_id: String,
+    pub state: String,
+    pub artifacts: Vec<ArtifactEntry>,
+}
+
+impl ArtifactsResponse {
+    pub fn run_ended(&self) -> bool {
+        self.state == PR_STATE_FINISHED
+            || self.state == PR_STATE_FAULTED
+            || self.state == PR_STATE_INTERRUPTED
+    }
+}
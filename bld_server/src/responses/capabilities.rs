@@ -0,0 +1,177 @@
+use crate::responses::RUN_SUMMARY_VERSION;
+use bld_config::definitions::VERSION;
+use bld_config::{AuthValidation, BldConfig};
+use serde::{Deserialize, Serialize};
+
+pub const CAPABILITIES_SCHEMA_VERSION: u32 = 1;
+
+pub const FEATURE_PIPELINE_LOCKS: &str = "pipeline-locks";
+pub const FEATURE_LOGS_POLLING: &str = "logs-polling";
+pub const FEATURE_GIT_RUNS: &str = "git-runs";
+pub const FEATURE_CHECKPOINTS: &str = "checkpoints";
+pub const FEATURE_HIGH_AVAILABILITY: &str = "high-availability";
+pub const FEATURE_LOG_REDACTION: &str = "log-redaction";
+pub const FEATURE_EMAIL_NOTIFICATIONS: &str = "email-notifications";
+pub const FEATURE_DRY_RUNS: &str = "dry-runs";
+pub const FEATURE_RUN_TREES: &str = "run-trees";
+pub const FEATURE_CONNECTIONS: &str = "connections";
+pub const FEATURE_QUEUE: &str = "queue";
+pub const FEATURE_ARTIFACTS: &str = "artifacts";
+pub const FEATURE_RUN_REQUESTS: &str = "run-requests";
+pub const FEATURE_API_KEYS: &str = "api-keys";
+pub const FEATURE_STRICT_PIPELINES: &str = "strict-pipelines";
+pub const FEATURE_SEARCH: &str = "search";
+pub const FEATURE_SCHEDULES: &str = "schedules";
+pub const FEATURE_STATS: &str = "stats";
+pub const FEATURE_BULK_REMOVE: &str = "bulk-remove";
+pub const FEATURE_METRICS: &str = "metrics";
+pub const FEATURE_RUN_SUMMARIES: &str = "run-summaries";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FeatureInfo {
+    pub name: String,
+    pub version: u32,
+}
+
+/// A platform that the server can execute pipelines on, along with the endpoint that it's
+/// reached at when it has one, such as the url of the docker daemon.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlatformInfo {
+    pub name: String,
+    pub endpoint: Option<String>,
+}
+
+impl PlatformInfo {
+    pub fn new(name: &str, endpoint: Option<&str>) -> Self {
+        Self {
+            name: name.to_string(),
+            endpoint: endpoint.map(|e| e.to_string()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LimitsInfo {
+    pub max_pipeline_size: usize,
+    pub max_exec_frame: usize,
+}
+
+/// The features, limits and auth methods of a server so that clients can adapt to it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CapabilitiesResponse {
+    pub version: String,
+    pub schema_version: u32,
+    pub features: Vec<FeatureInfo>,
+    pub limits: Option<LimitsInfo>,
+    pub auth: Vec<String>,
+    #[serde(default)]
+    pub platforms: Vec<PlatformInfo>,
+}
+
+impl CapabilitiesResponse {
+    /// Generates the capabilities from the configuration of the server, with the
+    /// features that depend on it listed only when they are enabled. The platforms are
+    /// detected by the server and set separately.
+    pub fn new(config: &BldConfig) -> Self {
+        let local = &config.local;
+        let mut features = vec![
+            (FEATURE_PIPELINE_LOCKS, 1),
+            (FEATURE_LOGS_POLLING, 1),
+            (FEATURE_GIT_RUNS, 1),
+            (FEATURE_CHECKPOINTS, 1),
+            (FEATURE_DRY_RUNS, 1),
+            (FEATURE_RUN_TREES, 1),
+            (FEATURE_CONNECTIONS, 1),
+            (FEATURE_QUEUE, 1),
+            (FEATURE_ARTIFACTS, 1),
+            (FEATURE_RUN_REQUESTS, 1),
+            (FEATURE_API_KEYS, 2),
+            (FEATURE_SEARCH, 1),
+            (FEATURE_SCHEDULES, 1),
+            (FEATURE_STATS, 1),
+            (FEATURE_BULK_REMOVE, 1),
+            (FEATURE_METRICS, 1),
+            (FEATURE_RUN_SUMMARIES, RUN_SUMMARY_VERSION),
+        ];
+        if local.ha_mode {
+            features.push((FEATURE_HIGH_AVAILABILITY, 1));
+        }
+        if !local.log_redaction.is_empty() {
+            features.push((FEATURE_LOG_REDACTION, 1));
+        }
+        if local.smtp.is_some() {
+            features.push((FEATURE_EMAIL_NOTIFICATIONS, 1));
+        }
+        if local.server.strict_pipelines {
+            features.push((FEATURE_STRICT_PIPELINES, 1));
+        }
+        let auth = match &local.auth {
+            AuthValidation::Ldap => "ldap",
+            AuthValidation::OAuth2(_) => "oauth2",
+            AuthValidation::None => "none",
+        };
+        Self {
+            version: VERSION.to_string(),
+            schema_version: CAPABILITIES_SCHEMA_VERSION,
+            features: features
+                .into_iter()
+                .map(|(name, version)| FeatureInfo {
+                    name: name.to_string(),
+                    version,
+                })
+                .collect(),
+            limits: Some(LimitsInfo {
+                max_pipeline_size: local.server.max_pipeline_size,
+                max_exec_frame: local.server.max_exec_frame,
+            }),
+            auth: vec![auth.to_string(), "api-key".to_string()],
+            platforms: vec![],
+        }
+    }
+
+    /// The capabilities of a server that predates the manifest, which has none of
+    /// the optional features.
+    pub fn legacy() -> Self {
+        Self {
+            version: String::new(),
+            schema_version: 0,
+            features: vec![],
+            limits: None,
+            auth: vec![],
+            platforms: vec![],
+        }
+    }
+
+    /// The version of a feature if the server supports it.
+    pub fn feature(&self, name: &str) -> Option<u32> {
+        self.features
+            .iter()
+            .find(|f| f.name == name)
+            .map(|f| f.version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capabilities_are_generated_from_the_config() {
+        let mut config = BldConfig::default();
+        let capabilities = CapabilitiesResponse::new(&config);
+
+        assert_eq!(capabilities.feature(FEATURE_PIPELINE_LOCKS), Some(1));
+        assert_eq!(capabilities.feature(FEATURE_HIGH_AVAILABILITY), None);
+        assert_eq!(
+            capabilities.auth,
+            vec!["none".to_string(), "api-key".to_string()]
+        );
+
+        config.local.ha_mode = true;
+        config.local.auth = AuthValidation::Ldap;
+        let capabilities = CapabilitiesResponse::new(&config);
+
+        assert_eq!(capabilities.feature(FEATURE_HIGH_AVAILABILITY), Some(1));
+        assert_eq!(capabilities.auth[0], "ldap");
+    }
+}
@@ -0,0 +1,30 @@
+use bld_core::database::pipeline_runs::{
+    PR_STATE_FAULTED, PR_STATE_FINISHED, PR_STATE_INTERRUPTED,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CheckpointEntry {
+    pub name: String,
+    pub date_created: String,
+}
+
+/// The checkpoints that a run has reached along with its current state.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CheckpointsResponse {
+    pub run_id: String,
+    pub state: String,
+    pub checkpoints: Vec<CheckpointEntry>,
+}
+
+impl CheckpointsResponse {
+    pub fn reached(&self, name: &str) -> bool {
+        self.checkpoints.iter().any(|c| c.name == name)
+    }
+
+    pub fn run_ended(&self) -> bool {
+        self.state == PR_STATE_FINISHED
+            || self.state == PR_STATE_FAULTED
+            || self.state == PR_STATE_INTERRUPTED
+    }
+}
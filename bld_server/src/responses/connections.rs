@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+/// An exec or monit websocket that is open on the server.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ConnectionResponse {
+    pub id: String,
+    pub kind: String,
+    pub user: String,
+    pub run_ids: Vec<String>,
+    pub connected_at: u64,
+    pub seconds_since_heartbeat: u64,
+}
@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Ok,
+    Degraded,
+    Down,
+}
+
+/// The outcome of probing a subsystem of the server. The error of a failed probe is only
+/// logged, since the response is public.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ProbeResponse {
+    pub name: String,
+    pub status: HealthStatus,
+}
+
+/// The health of the server, which is down if the database can't be reached and degraded if any
+/// other subsystem can't. The run counts are missing when the database can't be reached.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HealthResponse {
+    pub status: HealthStatus,
+    pub probes: Vec<ProbeResponse>,
+    pub active_runs: Option<usize>,
+    pub queued_runs: Option<usize>,
+}
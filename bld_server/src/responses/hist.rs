@@ -1,4 +1,6 @@
+use bld_core::database::pipeline_runs::PipelineRuns;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use tabled::Tabled;
 
 #[derive(Serialize, Deserialize, Tabled)]
@@ -9,4 +11,34 @@ pub struct HistoryEntry {
     pub state: String,
     pub start_date_time: String,
     pub end_date_time: String,
+    #[serde(default)]
+    #[tabled(display_with = "display_labels")]
+    pub labels: HashMap<String, String>,
+    #[serde(default)]
+    #[tabled(skip)]
+    pub trace_id: Option<String>,
+}
+
+fn display_labels(labels: &HashMap<String, String>) -> String {
+    let mut labels: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| format!("{name}:{value}"))
+        .collect();
+    labels.sort();
+    labels.join(", ")
+}
+
+impl From<PipelineRuns> for HistoryEntry {
+    fn from(run: PipelineRuns) -> Self {
+        Self {
+            labels: run.labels(),
+            trace_id: run.trace_id,
+            name: run.name,
+            id: run.id,
+            user: run.user,
+            state: run.state,
+            start_date_time: run.start_date_time,
+            end_date_time: run.end_date_time.unwrap_or_default(),
+        }
+    }
 }
@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+use tabled::Tabled;
+
+#[derive(Serialize, Deserialize, Tabled)]
+pub struct PipelineEntry {
+    pub id: String,
+    pub name: String,
+    pub date_created: String,
+}
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LockResponse {
+    pub name: String,
+    pub token: String,
+}
+
+impl LockResponse {
+    pub fn new(name: &str, token: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            token: token.to_string(),
+        }
+    }
+}
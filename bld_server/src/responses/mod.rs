@@ -1,5 +1,39 @@
+mod api_key;
+mod artifacts;
+mod capabilities;
+mod checkpoints;
+mod connections;
+mod health;
 mod hist;
+mod list;
+mod lock;
 mod pull;
+mod push;
+mod queue;
+mod remove;
+mod run;
+mod schedule;
+mod search;
+mod stats;
+mod summary;
+mod tree;
 
+pub use api_key::*;
+pub use artifacts::*;
+pub use capabilities::*;
+pub use checkpoints::*;
+pub use connections::*;
+pub use health::*;
 pub use hist::*;
+pub use list::*;
+pub use lock::*;
 pub use pull::*;
+pub use push::*;
+pub use queue::*;
+pub use remove::*;
+pub use run::*;
+pub use schedule::*;
+pub use search::*;
+pub use stats::*;
+pub use summary::*;
+pub use tree::*;
@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+/// The result of the validation of a pipeline of an atomic push.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PushResult {
+    pub name: String,
+    pub errors: Vec<String>,
+    #[serde(default)]
+    pub warnings: Vec<String>,
+}
+
+impl PushResult {
+    pub fn new(name: &str, errors: Vec<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            errors,
+            warnings: vec![],
+        }
+    }
+}
+
+/// The response of an atomic push, where either every pipeline or none of them was pushed.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AtomicPushResponse {
+    pub pushed: bool,
+    pub error: Option<String>,
+    pub pipelines: Vec<PushResult>,
+}
+
+impl AtomicPushResponse {
+    pub fn new(pipelines: Vec<PushResult>, error: Option<String>) -> Self {
+        let pushed = error.is_none() && pipelines.iter().all(|p| p.errors.is_empty());
+        Self {
+            pushed,
+            error,
+            pipelines,
+        }
+    }
+}
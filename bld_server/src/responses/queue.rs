@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+/// The workers of the supervisor that are running along with the runs that wait for one,
+/// in the order that they will be started.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct QueueResponse {
+    pub max_workers: usize,
+    pub active: usize,
+    pub queued: Vec<String>,
+}
+
+impl QueueResponse {
+    /// The position of a run in the queue, starting from 1 for the next run to be started.
+    pub fn position(&self, run_id: &str) -> Option<usize> {
+        self.queued.iter().position(|r| r == run_id).map(|p| p + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queue_position_starts_from_the_next_run() {
+        let queue = QueueResponse {
+            max_workers: 1,
+            active: 1,
+            queued: vec!["first".to_string(), "second".to_string()],
+        };
+
+        assert_eq!(queue.position("first"), Some(1));
+        assert_eq!(queue.position("second"), Some(2));
+        assert_eq!(queue.position("running"), None);
+    }
+}
@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+/// The result of the removal of a pipeline of a bulk remove.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RemoveResult {
+    pub name: String,
+    pub error: Option<String>,
+}
+
+impl RemoveResult {
+    pub fn new(name: &str, error: Option<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            error,
+        }
+    }
+}
@@ -0,0 +1,7 @@
+use serde::{Deserialize, Serialize};
+
+/// The run that a request to the `/run` endpoint has enqueued.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RunResponse {
+    pub run_id: String,
+}
@@ -0,0 +1,47 @@
+use crate::helpers::next_schedule_run;
+use bld_core::database::pipeline_schedules::PipelineSchedule;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tabled::Tabled;
+
+#[derive(Serialize, Deserialize, Tabled)]
+pub struct ScheduleEntry {
+    pub id: String,
+    pub pipeline: String,
+    pub cron: String,
+    pub enabled: bool,
+    pub next_run: String,
+    #[tabled(display_with = "display_values")]
+    pub variables: HashMap<String, String>,
+    #[tabled(display_with = "display_values")]
+    pub environment: HashMap<String, String>,
+    pub created_by: String,
+}
+
+fn display_values(values: &HashMap<String, String>) -> String {
+    let mut values: Vec<String> = values
+        .iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect();
+    values.sort();
+    values.join(", ")
+}
+
+impl From<PipelineSchedule> for ScheduleEntry {
+    fn from(schedule: PipelineSchedule) -> Self {
+        let next_run = next_schedule_run(&schedule.cron)
+            .filter(|_| schedule.enabled)
+            .map(|next| next.format("%F %X").to_string())
+            .unwrap_or_default();
+        Self {
+            variables: schedule.variables(),
+            environment: schedule.environment(),
+            id: schedule.id,
+            pipeline: schedule.pipeline,
+            cron: schedule.cron,
+            enabled: schedule.enabled,
+            next_run,
+            created_by: schedule.created_by,
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+use tabled::Tabled;
+
+/// A pipeline that contains a search term, with the numbers of the lines that contain it and
+/// the number of times that it appears, which is used to order the results.
+#[derive(Debug, Serialize, Deserialize, Tabled)]
+pub struct SearchEntry {
+    pub name: String,
+    pub matches: usize,
+    #[tabled(display_with = "display_lines")]
+    pub lines: Vec<usize>,
+}
+
+fn display_lines(lines: &[usize]) -> String {
+    lines
+        .iter()
+        .map(|line| line.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+impl SearchEntry {
+    /// Searches the content of a pipeline for a term, ignoring its case. Returns nothing when
+    /// the term doesn't appear in the pipeline.
+    pub fn find(name: &str, content: &str, term: &str) -> Option<Self> {
+        let term = term.to_lowercase();
+        let mut matches = 0;
+        let mut lines = vec![];
+        for (index, line) in content.lines().enumerate() {
+            let count = line.to_lowercase().matches(&term).count();
+            if count > 0 {
+                matches += count;
+                lines.push(index + 1);
+            }
+        }
+        if matches == 0 {
+            return None;
+        }
+        Some(Self {
+            name: name.to_string(),
+            matches,
+            lines,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_entry_has_the_lines_and_matches_of_a_term() {
+        let content = "name: Deploy\nruns-on: machine\nsteps:\n- exec:\n  - ./deploy.sh deploy\n";
+        let entry = SearchEntry::find("deploy.yaml", content, "DEPLOY").unwrap();
+        assert_eq!(entry.name, "deploy.yaml");
+        assert_eq!(entry.lines, vec![1, 5]);
+        assert_eq!(entry.matches, 3);
+        assert!(SearchEntry::find("deploy.yaml", content, "docker").is_none());
+    }
+}
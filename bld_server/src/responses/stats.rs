@@ -0,0 +1,167 @@
+use bld_core::database::pipeline_runs::{
+    PipelineRuns, PR_STATE_FAULTED, PR_STATE_FINISHED, PR_STATE_INTERRUPTED,
+};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use tabled::Tabled;
+
+const RUN_DATE_TIME_FORMAT: &str = "%F %X";
+
+/// The statistics of the runs of a pipeline. The success rate is the ratio of the runs that
+/// finished without being stopped to the runs that have ended, and the durations are in seconds
+/// over the runs that have an end date time.
+#[derive(Debug, Serialize, Deserialize, Tabled)]
+pub struct PipelineStats {
+    pub pipeline: String,
+    pub total_runs: usize,
+    #[tabled(display_with = "display_rate")]
+    pub success_rate: Option<f64>,
+    #[tabled(display_with = "display_seconds")]
+    pub avg_duration_seconds: Option<f64>,
+    #[tabled(display_with = "display_seconds")]
+    pub p95_duration_seconds: Option<f64>,
+    #[tabled(display_with = "display_date_time")]
+    pub last_run_at: Option<String>,
+}
+
+fn display_rate(rate: &Option<f64>) -> String {
+    rate.map(|rate| format!("{:.1}%", rate * 100.0))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn display_seconds(seconds: &Option<f64>) -> String {
+    seconds
+        .map(|seconds| format!("{seconds:.1}s"))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn display_date_time(date_time: &Option<String>) -> String {
+    date_time.clone().unwrap_or_else(|| "-".to_string())
+}
+
+/// The duration of a run in seconds, if it has ended.
+pub fn duration_seconds(run: &PipelineRuns) -> Option<f64> {
+    seconds_between(&run.start_date_time, run.end_date_time.as_deref()?)
+}
+
+/// The seconds between the start and end date time of a run.
+pub fn seconds_between(start: &str, end: &str) -> Option<f64> {
+    let start = NaiveDateTime::parse_from_str(start, RUN_DATE_TIME_FORMAT).ok()?;
+    let end = NaiveDateTime::parse_from_str(end, RUN_DATE_TIME_FORMAT).ok()?;
+    Some((end - start).num_seconds().max(0) as f64)
+}
+
+impl PipelineStats {
+    pub fn new(pipeline: &str, runs: &[PipelineRuns]) -> Self {
+        let ended: Vec<&PipelineRuns> = runs
+            .iter()
+            .filter(|run| {
+                [PR_STATE_FINISHED, PR_STATE_FAULTED, PR_STATE_INTERRUPTED]
+                    .contains(&run.state.as_str())
+            })
+            .collect();
+        let succeeded = ended
+            .iter()
+            .filter(|run| run.state == PR_STATE_FINISHED && run.stopped != Some(true))
+            .count();
+        let success_rate = if ended.is_empty() {
+            None
+        } else {
+            Some(succeeded as f64 / ended.len() as f64)
+        };
+
+        let mut durations: Vec<f64> = runs.iter().filter_map(duration_seconds).collect();
+        durations.sort_by(|a, b| a.total_cmp(b));
+        let avg_duration_seconds = if durations.is_empty() {
+            None
+        } else {
+            Some(durations.iter().sum::<f64>() / durations.len() as f64)
+        };
+        // the nearest rank of the 95th percentile.
+        let p95_duration_seconds = if durations.is_empty() {
+            None
+        } else {
+            let rank = (0.95 * durations.len() as f64).ceil() as usize;
+            Some(durations[rank.max(1) - 1])
+        };
+
+        let last_run_at = runs.iter().map(|run| &run.start_date_time).max().cloned();
+
+        Self {
+            pipeline: pipeline.to_string(),
+            total_runs: runs.len(),
+            success_rate,
+            avg_duration_seconds,
+            p95_duration_seconds,
+            last_run_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(state: &str, start: &str, end: Option<&str>) -> PipelineRuns {
+        PipelineRuns {
+            id: start.to_string(),
+            name: "build.yaml".to_string(),
+            state: state.to_string(),
+            user: "user".to_string(),
+            start_date_time: start.to_string(),
+            end_date_time: end.map(|end| end.to_string()),
+            stopped: None,
+            labels: None,
+            worker_pid: None,
+            trace_id: None,
+            docker_retries: None,
+            docker_breaker_trips: None,
+            started: None,
+            build_failed: None,
+        }
+    }
+
+    #[test]
+    fn pipeline_stats_are_aggregated_over_the_runs() {
+        let mut stopped = run(
+            PR_STATE_FINISHED,
+            "2022-11-01 10:03:00",
+            Some("2022-11-01 10:03:05"),
+        );
+        stopped.stopped = Some(true);
+        let mut runs: Vec<PipelineRuns> = (0..19)
+            .map(|i| {
+                run(
+                    PR_STATE_FINISHED,
+                    &format!("2022-11-01 09:{i:02}:00"),
+                    Some(&format!("2022-11-01 09:{i:02}:10")),
+                )
+            })
+            .collect();
+        runs.push(run(
+            PR_STATE_FAULTED,
+            "2022-11-01 10:00:00",
+            Some("2022-11-01 10:01:00"),
+        ));
+        runs.push(run(PR_STATE_INTERRUPTED, "2022-11-01 10:02:00", None));
+        runs.push(stopped);
+        runs.push(run("running", "2022-11-02 08:00:00", None));
+
+        let stats = PipelineStats::new("build.yaml", &runs);
+        assert_eq!(stats.total_runs, 23);
+        assert_eq!(stats.success_rate, Some(19.0 / 22.0));
+        assert_eq!(stats.avg_duration_seconds, Some(255.0 / 21.0));
+        assert_eq!(stats.p95_duration_seconds, Some(10.0));
+        assert_eq!(stats.last_run_at.as_deref(), Some("2022-11-02 08:00:00"));
+
+        let stats = PipelineStats::new("build.yaml", &runs[19..]);
+        assert_eq!(stats.p95_duration_seconds, Some(60.0));
+
+        let stats = PipelineStats::new("build.yaml", &[]);
+        assert_eq!(stats.total_runs, 0);
+        assert!(stats.success_rate.is_none());
+        assert!(stats.avg_duration_seconds.is_none());
+        assert!(stats.p95_duration_seconds.is_none());
+        assert!(stats.last_run_at.is_none());
+    }
+}
@@ -0,0 +1,151 @@
+use bld_core::database::pipeline_run_commands::PipelineRunCommands;
+use serde::{Deserialize, Serialize};
+
+/// The version of the summary of a run, which is increased when the entries of the summary
+/// change so that its consumers can tell the summaries that they don't support. Entries that
+/// are added in a later version are optional for the consumers of an earlier one.
+pub const RUN_SUMMARY_VERSION: u32 = 1;
+
+/// A command of a step identified by its index in the step and a preview of its text, with
+/// the secrets of the run masked.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CommandSummary {
+    pub index: usize,
+    pub preview: String,
+    pub duration_millis: u64,
+}
+
+/// The commands of a step in the order of the step, for a step of the pipeline of the run,
+/// of a called pipeline or of a leg of a matrix.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StepSummary {
+    pub pipeline: String,
+    pub step: Option<String>,
+    pub matrix: Option<String>,
+    #[serde(default)]
+    pub commands: Vec<CommandSummary>,
+}
+
+impl StepSummary {
+    fn is_same_step(&self, command: &PipelineRunCommands) -> bool {
+        self.pipeline == command.pipeline
+            && self.step == command.step_name
+            && self.matrix == command.matrix
+    }
+
+    /// The name of the step along with its pipeline and the leg of the matrix.
+    pub fn label(&self) -> String {
+        let step = self.step.as_deref().unwrap_or("-");
+        match &self.matrix {
+            Some(matrix) => format!("{} > {step} [{matrix}]", self.pipeline),
+            None => format!("{} > {step}", self.pipeline),
+        }
+    }
+}
+
+/// The summary of a run with the duration of every command that it executed, grouped by
+/// their steps in the order that the steps were executed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RunSummary {
+    pub version: u32,
+    pub run_id: String,
+    pub state: String,
+    #[serde(default)]
+    pub steps: Vec<StepSummary>,
+}
+
+impl RunSummary {
+    pub fn new(run_id: &str, state: &str, commands: Vec<PipelineRunCommands>) -> Self {
+        let mut steps: Vec<StepSummary> = vec![];
+        for command in commands.into_iter() {
+            let position = steps.iter().position(|s| s.is_same_step(&command));
+            let step = match position {
+                Some(position) => &mut steps[position],
+                None => {
+                    steps.push(StepSummary {
+                        pipeline: command.pipeline.clone(),
+                        step: command.step_name.clone(),
+                        matrix: command.matrix.clone(),
+                        commands: vec![],
+                    });
+                    steps.last_mut().unwrap()
+                }
+            };
+            step.commands.push(CommandSummary {
+                index: command.command_index as usize,
+                preview: command.preview,
+                duration_millis: command.duration_millis as u64,
+            });
+        }
+        for step in steps.iter_mut() {
+            step.commands.sort_by_key(|c| c.index);
+        }
+        Self {
+            version: RUN_SUMMARY_VERSION,
+            run_id: run_id.to_string(),
+            state: state.to_string(),
+            steps,
+        }
+    }
+
+    /// The slowest commands of the run along with their steps, starting from the slowest.
+    pub fn slowest(&self, count: usize) -> Vec<(&StepSummary, &CommandSummary)> {
+        let mut commands: Vec<(&StepSummary, &CommandSummary)> = self
+            .steps
+            .iter()
+            .flat_map(|s| s.commands.iter().map(move |c| (s, c)))
+            .collect();
+        commands.sort_by_key(|c| std::cmp::Reverse(c.1.duration_millis));
+        commands.truncate(count);
+        commands
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(step: &str, index: i32, duration_millis: i64) -> PipelineRunCommands {
+        PipelineRunCommands {
+            id: format!("{step}-{index}"),
+            run_id: "run".to_string(),
+            pipeline: "build.yaml".to_string(),
+            step_name: Some(step.to_string()),
+            matrix: None,
+            command_index: index,
+            preview: format!("echo {index}"),
+            duration_millis,
+            date_created: String::new(),
+        }
+    }
+
+    #[test]
+    fn commands_are_grouped_by_their_steps() {
+        let commands = vec![
+            command("build", 1, 300),
+            command("build", 0, 100),
+            command("test", 0, 5000),
+            command("build", 2, 200),
+        ];
+
+        let summary = RunSummary::new("run", "finished", commands);
+        assert_eq!(summary.version, RUN_SUMMARY_VERSION);
+        assert_eq!(summary.steps.len(), 2);
+        assert_eq!(summary.steps[0].step.as_deref(), Some("build"));
+        let indexes: Vec<usize> = summary.steps[0].commands.iter().map(|c| c.index).collect();
+        assert_eq!(indexes, vec![0, 1, 2]);
+        assert_eq!(summary.steps[1].label(), "build.yaml > test");
+
+        let slowest = summary.slowest(2);
+        assert_eq!(slowest.len(), 2);
+        assert_eq!(slowest[0].0.step.as_deref(), Some("test"));
+        assert_eq!(slowest[1].1.preview, "echo 1");
+    }
+
+    #[test]
+    fn summaries_without_steps_are_accepted() {
+        let summary: RunSummary =
+            serde_json::from_str(r#"{"version":1,"run_id":"run","state":"finished"}"#).unwrap();
+        assert!(summary.steps.is_empty());
+    }
+}
@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+/// A run along with the runs of the pipelines that it called, as a node of a call tree. The
+/// rollup rule of a called run and the state that it contributed to its parent are set once
+/// the call has ended.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RunTreeNode {
+    pub run_id: String,
+    pub pipeline: String,
+    pub step_name: Option<String>,
+    pub state: String,
+    pub start_date_time: String,
+    pub end_date_time: Option<String>,
+    pub rollup: Option<String>,
+    pub effective_state: Option<String>,
+    pub children: Vec<RunTreeNode>,
+}
@@ -1,28 +1,108 @@
 use crate::endpoints::{
-    auth_redirect, deps, ha_append_entries, ha_install_snapshot, ha_vote, hist, home, inspect,
-    list, pull, push, remove, run, stop,
+    add_schedule, admin_close_connection, admin_config, admin_connections, api_key, artifact,
+    artifacts, auth_redirect, checkpoints, deps, ha_append_entries, ha_health, ha_install_snapshot,
+    ha_vote, hist, home, inspect, list, list_api_keys, lock, logs, metrics, pipeline,
+    pipeline_stats, pull, push, push_atomic, queue, remove, remove_schedule, revoke_api_key, run,
+    run_state, run_summary, run_tree, schedules, search, stop, unlock,
 };
+use crate::helpers::{
+    refresh_platforms, run_schedules, ConnectionRegistry, PipelineLocks, Platforms,
+    CONNECTION_HEARTBEAT_INTERVAL_SECONDS,
+};
+use crate::middlewares::Authentication;
 use crate::sockets::{ws_exec, ws_high_avail, ws_monit, EnqueueClient};
 use actix::io::SinkWrite;
 use actix::{Actor, Addr, StreamHandler};
 use actix_web::rt::spawn;
-use actix_web::web::{get, resource, Data};
+use actix_web::web::{get, resource, Data, JsonConfig, ServiceConfig};
 use actix_web::{middleware, App, HttpServer};
-use anyhow::{anyhow, Result};
-use awc::http::Version;
-use awc::Client;
-use bld_config::BldConfig;
+use anyhow::{anyhow, bail, Result};
+use awc::ws::{Frame, Message};
+use bld_config::definitions::VERSION;
+use bld_config::{BldConfig, BldLocalSupervisorConfig};
 use bld_core::database::new_connection_pool;
+use bld_core::execution::{execution_journal, replay_journal};
 use bld_core::high_avail::HighAvail;
 use bld_core::proxies::PipelineFileSystemProxy;
-use bld_supervisor::base::ServerMessages;
-use futures::{join, stream::StreamExt};
+use bld_supervisor::base::{ServerMessages, SupervisorMessages};
+use bld_supervisor::client::ws_client;
+use futures::future::pending;
+use futures::{join, stream::StreamExt, SinkExt};
+#[cfg(unix)]
+use nix::sys::signal::{kill, Signal};
+#[cfg(unix)]
+use nix::unistd::Pid;
 use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
+use std::collections::VecDeque;
 use std::env::{current_exe, set_var};
+#[cfg(unix)]
+use std::fs::metadata;
+#[cfg(unix)]
+use std::io::ErrorKind;
+#[cfg(unix)]
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::process::{Child, Command};
 use tokio::sync::mpsc::{channel, Receiver, Sender};
-use tracing::{debug, error, info};
+use tokio::time::{sleep, timeout};
+use tracing::{debug, error, info, warn};
+
+const SUPERVISOR_STARTUP_ATTEMPTS: u32 = 6;
+const SUPERVISOR_RETRY_BASE_DELAY_MILLIS: u64 = 200;
+const SUPERVISOR_RETRY_MAX_DELAY_MILLIS: u64 = 30000;
+const SUPERVISOR_HELLO_TIMEOUT_SECONDS: u64 = 5;
+const SUPERVISOR_RETRY_BUFFER_SIZE: usize = 100;
+const SUPERVISOR_STOP_MARGIN_SECONDS: u64 = 5;
+
+/// The routes of the server, which require an authenticated user apart from the public ones
+/// of the authentication middleware.
+pub fn routes(cfg: &mut ServiceConfig) {
+    cfg.service(ha_append_entries)
+        .service(ha_install_snapshot)
+        .service(ha_vote)
+        .service(ha_health)
+        .service(home)
+        .service(admin_config)
+        .service(admin_connections)
+        .service(admin_close_connection)
+        .service(auth_redirect)
+        .service(api_key)
+        .service(list_api_keys)
+        .service(revoke_api_key)
+        .service(hist)
+        .service(list)
+        .service(remove)
+        .service(run)
+        .service(run_state)
+        .service(push)
+        .service(push_atomic)
+        .service(deps)
+        .service(queue)
+        .service(pull)
+        .service(stop)
+        .service(inspect)
+        .service(pipeline)
+        .service(logs)
+        .service(checkpoints)
+        .service(artifacts)
+        .service(artifact)
+        .service(run_tree)
+        .service(run_summary)
+        .service(search)
+        .service(add_schedule)
+        .service(schedules)
+        .service(remove_schedule)
+        .service(pipeline_stats)
+        .service(metrics)
+        .service(lock)
+        .service(unlock)
+        .service(resource("/ws-exec/").route(get().to(ws_exec)))
+        .service(resource("/ws-monit/").route(get().to(ws_monit)))
+        .service(resource("/ws-ha/").route(get().to(ws_high_avail)));
+}
 
 async fn spawn_server(
     config: Data<BldConfig>,
@@ -31,16 +111,33 @@ async fn spawn_server(
     enqueue_tx: Sender<ServerMessages>,
 ) -> Result<()> {
     info!("starting bld server at {}:{}", host, port);
+    config.local.check_pipeline_aliases()?;
 
     let config_clone = config.clone();
+    let max_pipeline_size = config.local.server.max_pipeline_size;
     let pool = new_connection_pool(&config.local.db)?;
+    replay_journal(&pool, &execution_journal(&config.local.db))?;
     let enqueue_tx = Data::new(enqueue_tx);
     let ha = Data::new(HighAvail::new(&config, pool.clone()).await?);
+    let locks = Data::new(PipelineLocks::default());
+    let connections = Data::new(ConnectionRegistry::default());
+    spawn(sweep_connections(
+        connections.clone(),
+        config.local.server.stale_connection_seconds,
+    ));
     let pool = Data::new(pool);
-    let prx = Data::new(PipelineFileSystemProxy::Server {
-        config: Arc::clone(&config),
-        pool: Arc::clone(&pool),
-    });
+    let prx = Data::new(PipelineFileSystemProxy::server(
+        Arc::clone(&config),
+        Arc::clone(&pool),
+    ));
+    let platforms = Data::new(Platforms::new(Platforms::detect(&config).await));
+    spawn(refresh_platforms(platforms.clone(), Arc::clone(&config)));
+    spawn(run_schedules(
+        prx.clone(),
+        pool.clone(),
+        enqueue_tx.clone(),
+        platforms.clone(),
+    ));
 
     set_var("RUST_LOG", "actix_server=info,actix_web=debug");
     let mut server = HttpServer::new(move || {
@@ -50,29 +147,19 @@ async fn spawn_server(
             .app_data(ha.clone())
             .app_data(pool.clone())
             .app_data(prx.clone())
+            .app_data(locks.clone())
+            .app_data(connections.clone())
+            .app_data(platforms.clone())
+            .app_data(JsonConfig::default().limit(max_pipeline_size))
+            .wrap(Authentication)
             .wrap(middleware::Logger::default())
-            .service(ha_append_entries)
-            .service(ha_install_snapshot)
-            .service(ha_vote)
-            .service(home)
-            .service(auth_redirect)
-            .service(hist)
-            .service(list)
-            .service(remove)
-            .service(run)
-            .service(push)
-            .service(deps)
-            .service(pull)
-            .service(stop)
-            .service(inspect)
-            .service(resource("/ws-exec/").route(get().to(ws_exec)))
-            .service(resource("/ws-monit/").route(get().to(ws_monit)))
-            .service(resource("/ws-ha/").route(get().to(ws_high_avail)))
+            .configure(routes)
     });
 
     let address = format!("{host}:{port}");
     server = match &config.local.server.tls {
         Some(tls) => {
+            tls.check_files()?;
             let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
             builder.set_private_key_file(&tls.private_key, SslFiletype::PEM)?;
             builder.set_certificate_chain_file(&tls.cert_chain)?;
@@ -85,27 +172,98 @@ async fn spawn_server(
     Ok(())
 }
 
-async fn supervisor_socket(
-    config: Arc<BldConfig>,
-    mut enqueue_rx: Receiver<ServerMessages>,
-) -> Result<Addr<EnqueueClient>> {
+/// Closes the websockets without a heartbeat for longer than the threshold, in order to catch
+/// the sockets that didn't close themselves such as actors that got stuck.
+async fn sweep_connections(connections: Data<ConnectionRegistry>, stale_seconds: u64) {
+    let threshold = Duration::from_secs(stale_seconds);
+    loop {
+        sleep(Duration::from_secs(CONNECTION_HEARTBEAT_INTERVAL_SECONDS)).await;
+        let closed = connections.close_stale(threshold);
+        if closed > 0 {
+            warn!("closed {closed} stale websocket connections");
+        }
+    }
+}
+
+fn supervisor_address(supervisor: &BldLocalSupervisorConfig) -> String {
+    match &supervisor.socket {
+        Some(socket) => format!("unix socket {socket}"),
+        None => format!("{}:{}", supervisor.host, supervisor.port),
+    }
+}
+
+/// Verifies that the supervisor socket exists and that the server user has the
+/// permissions to connect to it.
+#[cfg(unix)]
+fn check_socket(socket: &str) -> Result<()> {
+    let metadata = metadata(socket)
+        .map_err(|e| anyhow!("supervisor socket {socket} is not accessible, {e}"))?;
+    if !metadata.file_type().is_socket() {
+        bail!("supervisor socket path {socket} is not a unix socket");
+    }
+    match UnixStream::connect(socket) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => bail!(
+            "the server user is not allowed to connect to the supervisor socket {socket} (mode: {:o}, uid: {}, gid: {}), {e}",
+            metadata.mode() & 0o777,
+            metadata.uid(),
+            metadata.gid()
+        ),
+        Err(e) => bail!("unable to connect to the supervisor socket {socket}, {e}"),
+    }
+}
+
+#[cfg(not(unix))]
+fn check_socket(socket: &str) -> Result<()> {
+    bail!("unable to use the supervisor socket {socket}, unix sockets are not supported")
+}
+
+/// Connects to the supervisor and exchanges a hello message in order to verify that
+/// the server and the supervisor agree on the address and version.
+async fn supervisor_connect(config: &BldConfig) -> Result<Addr<EnqueueClient>> {
     let supervisor = &config.local.supervisor;
-    let url = format!(
-        "{}://{}:{}/ws-server/",
-        supervisor.ws_protocol(),
-        supervisor.host,
-        supervisor.port
-    );
+    let address = supervisor_address(supervisor);
+    if let Some(socket) = &supervisor.socket {
+        check_socket(socket)?;
+    }
+
+    let url = supervisor.ws_url("/ws-server/");
 
     debug!("establishing web socket connection on {}", url);
 
-    let client = Client::builder()
-        .max_http_version(Version::HTTP_11)
-        .finish();
-    let (_, framed) = client.ws(url).connect().await.map_err(|e| {
-        error!("{e}");
-        anyhow!(e.to_string())
-    })?;
+    let client = ws_client(supervisor);
+    let (_, mut framed) = client
+        .ws(url)
+        .connect()
+        .await
+        .map_err(|e| anyhow!("unable to connect to the supervisor at {address}, {e}"))?;
+
+    let hello = ServerMessages::Hello {
+        version: VERSION.to_string(),
+    };
+    framed
+        .send(Message::Binary(serde_json::to_vec(&hello)?.into()))
+        .await
+        .map_err(|e| anyhow!("unable to send hello message to the supervisor at {address}, {e}"))?;
+    let reply = timeout(
+        Duration::from_secs(SUPERVISOR_HELLO_TIMEOUT_SECONDS),
+        framed.next(),
+    )
+    .await
+    .map_err(|_| anyhow!("supervisor at {address} didn't reply to the hello message"))?;
+    let version = match reply {
+        Some(Ok(Frame::Binary(bytes))) => match serde_json::from_slice(&bytes)? {
+            SupervisorMessages::Hello { version } => version,
+        },
+        Some(Ok(frame)) => bail!("unexpected reply {frame:?} from the supervisor at {address}"),
+        Some(Err(e)) => bail!("invalid reply from the supervisor at {address}, {e}"),
+        None => bail!("supervisor at {address} closed the connection"),
+    };
+    if version != VERSION {
+        bail!(
+            "supervisor at {address} has version {version} while the server has version {VERSION}"
+        );
+    }
 
     let (sink, stream) = framed.split();
     let addr = EnqueueClient::create(|ctx| {
@@ -114,22 +272,143 @@ async fn supervisor_socket(
     });
 
     addr.send(ServerMessages::Ack).await?;
+    info!("connected to the supervisor at {address}");
+    Ok(addr)
+}
 
-    while let Some(msg) = enqueue_rx.recv().await {
-        addr.send(msg).await?;
+fn next_delay(delay: u64) -> u64 {
+    (delay * 2).min(SUPERVISOR_RETRY_MAX_DELAY_MILLIS)
+}
+
+/// Connects to the supervisor on startup, retrying with a backoff since the supervisor
+/// process is spawned right before the server.
+async fn supervisor_startup(config: &BldConfig) -> Result<Addr<EnqueueClient>> {
+    let mut delay = SUPERVISOR_RETRY_BASE_DELAY_MILLIS;
+    let mut attempt = 1;
+    loop {
+        match supervisor_connect(config).await {
+            Ok(addr) => return Ok(addr),
+            Err(e) if attempt >= SUPERVISOR_STARTUP_ATTEMPTS => return Err(e),
+            Err(e) => {
+                debug!("{e}, retrying in {delay}ms");
+                sleep(Duration::from_millis(delay)).await;
+                delay = next_delay(delay);
+                attempt += 1;
+            }
+        }
     }
+}
 
-    Ok(addr)
+fn buffer_push(buffer: &mut VecDeque<ServerMessages>, msg: ServerMessages) {
+    if buffer.len() >= SUPERVISOR_RETRY_BUFFER_SIZE {
+        if let Some(dropped) = buffer.pop_front() {
+            error!("supervisor retry buffer is full, dropping message {dropped:?}");
+        }
+    }
+    buffer.push_back(msg);
+}
+
+/// Forwards the enqueue messages to the supervisor. If the connection is lost, the messages
+/// are kept in a retry buffer while reconnecting with a backoff and are flushed once the
+/// connection is re-established.
+async fn supervisor_socket(
+    config: Arc<BldConfig>,
+    addr: Addr<EnqueueClient>,
+    mut enqueue_rx: Receiver<ServerMessages>,
+) -> Result<()> {
+    let mut addr = Some(addr);
+    let mut buffer: VecDeque<ServerMessages> = VecDeque::new();
+    let mut delay = SUPERVISOR_RETRY_BASE_DELAY_MILLIS;
+    loop {
+        match addr.take() {
+            Some(connected) if connected.connected() => {
+                while let Some(msg) = buffer.pop_front() {
+                    if let Err(e) = connected.send(msg.clone()).await {
+                        error!("unable to send message to the supervisor, {e}");
+                        buffer.push_front(msg);
+                        break;
+                    }
+                }
+                if !buffer.is_empty() {
+                    continue;
+                }
+                match enqueue_rx.recv().await {
+                    Some(msg) => buffer_push(&mut buffer, msg),
+                    None => return Ok(()),
+                }
+                addr = Some(connected);
+            }
+            Some(_) | None => match supervisor_connect(&config).await {
+                Ok(connected) => {
+                    info!(
+                        "reconnected to the supervisor, flushing {} buffered messages",
+                        buffer.len()
+                    );
+                    addr = Some(connected);
+                    delay = SUPERVISOR_RETRY_BASE_DELAY_MILLIS;
+                }
+                Err(e) => {
+                    warn!("supervisor connection lost, {e}. retrying in {delay}ms");
+                    // keep receiving enqueue messages during the outage.
+                    let _ = timeout(Duration::from_millis(delay), async {
+                        while let Some(msg) = enqueue_rx.recv().await {
+                            buffer_push(&mut buffer, msg);
+                        }
+                        pending::<()>().await
+                    })
+                    .await;
+                    delay = next_delay(delay);
+                }
+            },
+        }
+    }
 }
 
 fn create_supervisor() -> Result<Child> {
     Ok(Command::new(current_exe()?).arg("supervisor").spawn()?)
 }
 
+/// Sends a SIGTERM to the supervisor so that it stops its workers within their grace period,
+/// and kills it if it hasn't exited shortly after that.
+async fn stop_supervisor(supervisor: &mut Child, grace_seconds: u64) -> Result<()> {
+    if let Some(pid) = supervisor.id() {
+        let grace = Duration::from_secs(grace_seconds + SUPERVISOR_STOP_MARGIN_SECONDS);
+        if terminate(pid) && timeout(grace, supervisor.wait()).await.is_ok() {
+            return Ok(());
+        }
+    }
+    supervisor.kill().await?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn terminate(pid: u32) -> bool {
+    kill(Pid::from_raw(pid as i32), Signal::SIGTERM).is_ok()
+}
+
+/// Stops the supervisor along with its workers using taskkill, since there are no signals
+/// on windows for the supervisor to stop its workers gracefully.
+#[cfg(not(unix))]
+fn terminate(pid: u32) -> bool {
+    std::process::Command::new("taskkill")
+        .args(["/PID", &pid.to_string(), "/T", "/F"])
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
 pub async fn start(config: BldConfig, host: String, port: i64) -> Result<()> {
     let config = Data::new(config);
     let config_clone = Arc::clone(&config);
+    let grace_seconds = config.local.supervisor.shutdown_grace_seconds;
     let mut supervisor = create_supervisor()?; // set to kill the supervisor process on drop.
+    let supervisor_addr = match supervisor_startup(&config).await {
+        Ok(addr) => addr,
+        Err(e) => {
+            supervisor.kill().await?;
+            bail!("refusing to start the server since the supervisor is unreachable, {e}");
+        }
+    };
     let (enqueue_tx, enqueue_rx) = channel(4096);
 
     let web_server_handle = spawn(async move {
@@ -139,7 +418,7 @@ pub async fn start(config: BldConfig, host: String, port: i64) -> Result<()> {
     });
 
     let socket_handle = spawn(async move {
-        if let Err(e) = supervisor_socket(config_clone, enqueue_rx).await {
+        if let Err(e) = supervisor_socket(config_clone, supervisor_addr, enqueue_rx).await {
             error!("supervisor socket error, {e}");
         }
     });
@@ -154,6 +433,206 @@ pub async fn start(config: BldConfig, host: String, port: i64) -> Result<()> {
         error!("{e}");
     }
 
-    supervisor.kill().await?;
-    Ok(())
+    stop_supervisor(&mut supervisor, grace_seconds).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::{Method, StatusCode};
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::web::{self, Json};
+    use actix_web::{HttpRequest, HttpResponse};
+    use bld_config::AuthValidation;
+    use bld_core::database::api_keys::{self, InsertApiKey};
+    use bld_core::high_avail::HA_SECRET_HEADER;
+    use serde_json::json;
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_dir_all};
+    use uuid::Uuid;
+
+    const VALID_TOKEN: &str = "Bearer valid-token";
+    const API_KEY: &str = "stub-api-key";
+    const HA_SECRET: &str = "stub-ha-secret";
+
+    /// The routes that require a user, with the method that each one is served with.
+    const PROTECTED_ROUTES: [(&str, &str); 36] = [
+        ("GET", "/admin/config"),
+        ("GET", "/admin/connections"),
+        ("DELETE", "/admin/connections/id"),
+        ("POST", "/api-keys"),
+        ("GET", "/api-keys"),
+        ("DELETE", "/api-keys/name"),
+        ("GET", "/hist"),
+        ("GET", "/list"),
+        ("POST", "/remove"),
+        ("POST", "/run"),
+        ("GET", "/runs/id"),
+        ("POST", "/push"),
+        ("POST", "/push/atomic"),
+        ("POST", "/deps"),
+        ("GET", "/queue"),
+        ("POST", "/pull"),
+        ("POST", "/stop"),
+        ("POST", "/inspect"),
+        ("GET", "/pipeline/name"),
+        ("GET", "/logs/id"),
+        ("GET", "/runs/id/checkpoints"),
+        ("GET", "/runs/id/artifacts"),
+        ("GET", "/runs/id/artifact"),
+        ("GET", "/runs/id/tree"),
+        ("GET", "/runs/id/summary"),
+        ("GET", "/search?q=term"),
+        ("POST", "/schedules"),
+        ("GET", "/schedules"),
+        ("DELETE", "/schedules/id"),
+        ("GET", "/stats?name=build.yaml"),
+        ("GET", "/metrics"),
+        ("POST", "/pipelines/name/lock"),
+        ("DELETE", "/pipelines/name/lock"),
+        ("GET", "/ws-exec/"),
+        ("GET", "/ws-monit/"),
+        ("GET", "/ws-ha/"),
+    ];
+
+    const PUBLIC_ROUTES: [(&str, &str); 3] = [
+        ("GET", "/"),
+        ("GET", "/authRedirect?code=code&state=state"),
+        ("GET", "/ha/health"),
+    ];
+
+    const HA_ROUTES: [(&str, &str); 3] = [
+        ("POST", "/ha/appendEntries"),
+        ("POST", "/ha/installSnapshot"),
+        ("POST", "/ha/vote"),
+    ];
+
+    /// A stub of the validation url of an oauth2 provider that accepts a single token.
+    async fn stub_validation(req: HttpRequest) -> HttpResponse {
+        let authorization = req.headers().get("Authorization");
+        if authorization.and_then(|h| h.to_str().ok()) == Some(VALID_TOKEN) {
+            return HttpResponse::Ok().json(json!({ "login": "stub-user" }));
+        }
+        HttpResponse::Unauthorized().finish()
+    }
+
+    fn request(method: &str, path: &str, authorization: Option<&str>) -> actix_http::Request {
+        let mut request = TestRequest::default()
+            .method(Method::from_bytes(method.as_bytes()).unwrap())
+            .uri(path);
+        if let Some(authorization) = authorization {
+            request = request.insert_header(("Authorization", authorization));
+        }
+        request.to_request()
+    }
+
+    #[actix_web::test]
+    async fn server_routes_require_an_authenticated_user() {
+        let validation =
+            HttpServer::new(|| App::new().route("/user", web::get().to(stub_validation)))
+                .workers(1)
+                .bind(("127.0.0.1", 0))
+                .unwrap();
+        let address = validation.addrs()[0];
+        spawn(validation.run());
+
+        let root = temp_dir().join(format!("bld-auth-{}", Uuid::new_v4()));
+        create_dir_all(&root).unwrap();
+        let mut config = BldConfig::default();
+        config.local.db = root.display().to_string();
+        config.local.server.pipelines = root.join("pipelines").display().to_string();
+        config.local.auth = AuthValidation::OAuth2(format!("http://{address}/user"));
+        config.local.ha_secret = Some(HA_SECRET.to_string());
+        let config = Data::new(config);
+        let pool = new_connection_pool(&config.local.db).unwrap();
+        api_keys::insert(
+            &mut pool.get().unwrap(),
+            InsertApiKey {
+                id: "id",
+                name: "stub-key",
+                key_hash: &api_keys::hash(API_KEY),
+                created_by: "stub-user",
+            },
+        )
+        .unwrap();
+        let pool = Data::new(pool);
+        let (enqueue_tx, _enqueue_rx) = channel::<ServerMessages>(16);
+        let prx = Data::new(PipelineFileSystemProxy::server(
+            Arc::clone(&config),
+            Arc::clone(&pool),
+        ));
+        let app = init_service(
+            App::new()
+                .app_data(config)
+                .app_data(Data::new(enqueue_tx))
+                .app_data(Data::new(HighAvail::Disabled))
+                .app_data(pool)
+                .app_data(prx)
+                .app_data(Data::new(PipelineLocks::default()))
+                .app_data(Data::new(ConnectionRegistry::default()))
+                .app_data(Data::new(Platforms::default()))
+                .wrap(Authentication)
+                .configure(routes)
+                .route(
+                    "/stub",
+                    web::post().to(|body: Json<String>| async move { body.0 }),
+                ),
+        )
+        .await;
+
+        let key_header = format!("ApiKey {API_KEY}");
+        for (method, path) in PROTECTED_ROUTES.iter().chain([("POST", "/stub")].iter()) {
+            for authorization in [None, Some("Bearer invalid-token"), Some("ApiKey invalid")] {
+                let response = call_service(&app, request(method, path, authorization)).await;
+                assert_eq!(
+                    response.status(),
+                    StatusCode::UNAUTHORIZED,
+                    "{method} {path} should reject {authorization:?}"
+                );
+            }
+            for authorization in [VALID_TOKEN, &key_header] {
+                let response = call_service(&app, request(method, path, Some(authorization))).await;
+                assert_ne!(
+                    response.status(),
+                    StatusCode::UNAUTHORIZED,
+                    "{method} {path} should accept {authorization}"
+                );
+            }
+        }
+        for (method, path) in PUBLIC_ROUTES {
+            let response = call_service(&app, request(method, path, None)).await;
+            assert_ne!(
+                response.status(),
+                StatusCode::UNAUTHORIZED,
+                "{method} {path} should be public"
+            );
+        }
+        for (method, path) in HA_ROUTES {
+            for authorization in [None, Some(VALID_TOKEN), Some(key_header.as_str())] {
+                let response = call_service(&app, request(method, path, authorization)).await;
+                assert_eq!(
+                    response.status(),
+                    StatusCode::UNAUTHORIZED,
+                    "{method} {path} should reject {authorization:?} without the ha secret"
+                );
+            }
+            for secret in ["invalid-secret", HA_SECRET] {
+                let request = TestRequest::default()
+                    .method(Method::from_bytes(method.as_bytes()).unwrap())
+                    .uri(path)
+                    .insert_header((HA_SECRET_HEADER, secret))
+                    .to_request();
+                let response = call_service(&app, request).await;
+                assert_eq!(
+                    response.status() == StatusCode::UNAUTHORIZED,
+                    secret != HA_SECRET,
+                    "{method} {path} with ha secret {secret}"
+                );
+            }
+        }
+        let response = call_service(&app, request("GET", "/unknown", None)).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        remove_dir_all(root).unwrap();
+    }
 }
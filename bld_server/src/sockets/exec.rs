@@ -1,6 +1,10 @@
 use crate::extractors::User;
-use crate::helpers::enqueue_worker;
+use crate::helpers::{
+    enqueue_worker, request_traceparent, CloseConnection, ConnectionKind, ConnectionRegistry,
+    Platforms, CONNECTION_HEARTBEAT_INTERVAL_SECONDS, CONNECTION_TIMEOUT_SECONDS,
+};
 use crate::requests::RunInfo;
+use crate::sockets::{finish_log_lines, queue_log_lines, LogMessage};
 use actix::prelude::*;
 use actix_web::error::ErrorUnauthorized;
 use actix_web::web::{Data, Payload};
@@ -9,26 +13,37 @@ use actix_web_actors::ws;
 use anyhow::Result;
 use bld_config::BldConfig;
 use bld_core::database::pipeline_runs::{
-    self, PR_STATE_FAULTED, PR_STATE_FINISHED, PR_STATE_QUEUED,
+    self, PR_STATE_FAULTED, PR_STATE_FINISHED, PR_STATE_INTERRUPTED, PR_STATE_QUEUED,
 };
+use bld_core::logger::matrix_log_name;
 use bld_core::proxies::PipelineFileSystemProxy;
 use bld_core::scanner::{FileScanner, Scanner};
+use bld_runner::Pipeline;
 use bld_supervisor::base::ServerMessages;
 use diesel::r2d2::{ConnectionManager, Pool};
 use diesel::sqlite::SqliteConnection;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc::Sender;
-use tracing::error;
+use tracing::{error, info};
+
+/// The first line that the socket sends for an enqueued run, so that the client knows its id.
+pub const RUN_ID_PREFIX: &str = "[bld] Run id: ";
 
 pub struct ExecutePipelineSocket {
     config: Data<BldConfig>,
     enqueue_tx: Data<Sender<ServerMessages>>,
     pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
     proxy: Data<PipelineFileSystemProxy>,
+    platforms: Data<Platforms>,
+    traceparent: Option<String>,
     user: User,
-    scanner: Option<FileScanner>,
+    scanners: Vec<(Option<String>, FileScanner)>,
     run_id: Option<String>,
+    registry: Data<ConnectionRegistry>,
+    connection_id: String,
+    hb: Instant,
+    finished: bool,
 }
 
 impl ExecutePipelineSocket {
@@ -38,36 +53,81 @@ impl ExecutePipelineSocket {
         enqueue_tx: Data<Sender<ServerMessages>>,
         pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
         proxy: Data<PipelineFileSystemProxy>,
+        platforms: Data<Platforms>,
+        registry: Data<ConnectionRegistry>,
     ) -> Self {
         Self {
             config,
             enqueue_tx,
             pool,
             proxy,
+            platforms,
+            traceparent: None,
             user,
-            scanner: None,
+            scanners: vec![],
             run_id: None,
+            registry,
+            connection_id: String::new(),
+            hb: Instant::now(),
+            finished: false,
+        }
+    }
+
+    fn heartbeat(act: &Self, ctx: &mut <Self as Actor>::Context) {
+        if act.hb.elapsed() > Duration::from_secs(CONNECTION_TIMEOUT_SECONDS) {
+            info!("exec websocket heartbeat failed. disconnecting!");
+            ctx.stop();
+            return;
         }
+        ctx.ping(b"");
+    }
+
+    fn alive(&mut self) {
+        self.hb = Instant::now();
+        self.registry.heartbeat(&self.connection_id);
     }
 
-    fn scan(act: &mut Self, ctx: &mut <Self as Actor>::Context) {
-        if let Some(scanner) = act.scanner.as_mut() {
-            let content = scanner.fetch();
-            for line in content.iter() {
-                ctx.text(line.to_string());
+    fn scan(act: &mut Self, ctx: &mut <Self as Actor>::Context, flush: bool) {
+        let mut lines = vec![];
+        for (prefix, scanner) in act.scanners.iter_mut() {
+            let content = if flush {
+                scanner.flush()
+            } else {
+                scanner.fetch()
+            };
+            for line in content.into_iter() {
+                match prefix {
+                    Some(prefix) => lines.push(format!("{prefix}{line}")),
+                    None => lines.push(line),
+                }
             }
         }
+        if flush {
+            finish_log_lines(&ctx.address(), lines);
+        } else {
+            queue_log_lines(&ctx.address(), lines);
+        }
     }
 
     fn exec(act: &mut Self, ctx: &mut <Self as Actor>::Context) {
+        if act.finished {
+            return;
+        }
         if let Ok(mut conn) = act.pool.get() {
             if let Some(run_id) = act.run_id.as_ref() {
                 match pipeline_runs::select_by_id(&mut conn, run_id) {
-                    Ok(run) if run.state == PR_STATE_FINISHED || run.state == PR_STATE_FAULTED => {
-                        ctx.stop()
+                    Ok(run)
+                        if run.state == PR_STATE_FINISHED
+                            || run.state == PR_STATE_FAULTED
+                            || run.state == PR_STATE_INTERRUPTED =>
+                    {
+                        // sending any remaining output, including the error of a faulted or
+                        // timed out run, before closing the socket.
+                        act.finished = true;
+                        ExecutePipelineSocket::scan(act, ctx, true);
                     }
                     Ok(run) if run.state == PR_STATE_QUEUED => {
-                        ctx.text("run with id {run_id} has been queued, use the monit command to see the output when it's started");
+                        ctx.text(format!("run with id {run_id} has been queued, use the monit command to see the output when it's started"));
                         ctx.stop()
                     }
                     Err(_) => {
@@ -80,19 +140,63 @@ impl ExecutePipelineSocket {
         }
     }
 
-    fn enqueue(&mut self, text: &str) -> Result<()> {
+    /// Creates a scanner for the main log of the run and one for each leg of the pipeline
+    /// matrix, with the output of the legs prefixed by their combination.
+    fn scanners(&mut self, content: &str, run_id: &str) -> Result<()> {
+        let pipeline = Pipeline::parse(content)?;
+        let config = Arc::clone(&self.config);
+        self.scanners = vec![(None, FileScanner::new(Arc::clone(&config), run_id))];
+        for (index, combination) in pipeline.matrix_combinations().iter().enumerate() {
+            let label = Pipeline::matrix_label(combination);
+            let scanner = FileScanner::new(Arc::clone(&config), &matrix_log_name(run_id, index));
+            self.scanners.push((Some(format!("[{label}] ")), scanner));
+        }
+        Ok(())
+    }
+
+    /// Enqueues the run without blocking the socket, which doesn't handle other messages until
+    /// the run has been enqueued.
+    fn enqueue(&mut self, text: &str, ctx: &mut <Self as Actor>::Context) -> Result<()> {
         let data = serde_json::from_str::<RunInfo>(text)?;
-        enqueue_worker(
-            &self.user,
-            self.proxy.clone(),
-            self.pool.clone(),
-            self.enqueue_tx.clone(),
-            data,
-        )
-        .map(|run_id| {
-            self.scanner = Some(FileScanner::new(Arc::clone(&self.config), &run_id));
-            self.run_id = Some(run_id);
-        })
+        let user = self.user.clone();
+        let proxy = self.proxy.clone();
+        let pool = self.pool.clone();
+        let enqueue_tx = self.enqueue_tx.clone();
+        let platforms = self.platforms.clone();
+        let traceparent = self.traceparent.clone();
+        let enqueue = async move {
+            enqueue_worker(
+                &user,
+                proxy,
+                pool,
+                enqueue_tx,
+                &platforms,
+                data,
+                traceparent,
+            )
+            .await
+        };
+        ctx.wait(enqueue.into_actor(self).map(|result, act, ctx| {
+            let started = result.and_then(|(run_id, content)| {
+                act.scanners(&content, &run_id)?;
+                act.registry.subscribe(&act.connection_id, &run_id);
+                Ok(run_id)
+            });
+            match started {
+                Ok(run_id) => {
+                    ctx.text(format!("{RUN_ID_PREFIX}{run_id}"));
+                    act.run_id = Some(run_id);
+                }
+                Err(e) => act.enqueue_failed(e, ctx),
+            }
+        }));
+        Ok(())
+    }
+
+    fn enqueue_failed(&self, e: anyhow::Error, ctx: &mut <Self as Actor>::Context) {
+        error!("{}", e.to_string());
+        ctx.text("Unable to run pipeline");
+        ctx.stop();
     }
 }
 
@@ -100,12 +204,50 @@ impl Actor for ExecutePipelineSocket {
     type Context = ws::WebsocketContext<Self>;
 
     fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.set_mailbox_capacity(self.config.local.ws_message_queue_depth);
+        let closer = ctx.address().recipient();
+        self.connection_id = self
+            .registry
+            .register(ConnectionKind::Exec, &self.user.name, closer);
         ctx.run_interval(Duration::from_millis(500), |act, ctx| {
-            ExecutePipelineSocket::scan(act, ctx);
+            ExecutePipelineSocket::scan(act, ctx, false);
         });
         ctx.run_interval(Duration::from_secs(1), |act, ctx| {
             ExecutePipelineSocket::exec(act, ctx);
         });
+        ctx.run_interval(
+            Duration::from_secs(CONNECTION_HEARTBEAT_INTERVAL_SECONDS),
+            |act, ctx| {
+                ExecutePipelineSocket::heartbeat(act, ctx);
+            },
+        );
+    }
+
+    fn stopped(&mut self, _ctx: &mut Self::Context) {
+        self.registry.unregister(&self.connection_id);
+    }
+}
+
+impl Handler<CloseConnection> for ExecutePipelineSocket {
+    type Result = ();
+
+    fn handle(&mut self, _msg: CloseConnection, ctx: &mut Self::Context) {
+        ctx.close(Some(ws::CloseCode::Away.into()));
+        ctx.stop();
+    }
+}
+
+impl Handler<LogMessage> for ExecutePipelineSocket {
+    type Result = ();
+
+    fn handle(&mut self, msg: LogMessage, ctx: &mut Self::Context) {
+        match msg {
+            LogMessage::Line(line) => ctx.text(line),
+            LogMessage::Finished => {
+                ctx.close(Some(ws::CloseCode::Normal.into()));
+                ctx.stop()
+            }
+        }
     }
 }
 
@@ -113,16 +255,16 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for ExecutePipelineSo
     fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
         match msg {
             Ok(ws::Message::Text(txt)) => {
-                if let Err(e) = self.enqueue(&txt) {
-                    error!("{}", e.to_string());
-                    ctx.text("Unable to run pipeline");
-                    ctx.stop();
+                self.alive();
+                if let Err(e) = self.enqueue(&txt, ctx) {
+                    self.enqueue_failed(e, ctx);
                 }
             }
             Ok(ws::Message::Ping(msg)) => {
+                self.alive();
                 ctx.pong(&msg);
             }
-            Ok(ws::Message::Pong(_)) => {}
+            Ok(ws::Message::Pong(_)) => self.alive(),
             Ok(ws::Message::Close(reason)) => {
                 ctx.close(reason);
                 ctx.stop();
@@ -132,6 +274,7 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for ExecutePipelineSo
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn ws_exec(
     user: Option<User>,
     req: HttpRequest,
@@ -140,11 +283,18 @@ pub async fn ws_exec(
     enqueue_tx: Data<Sender<ServerMessages>>,
     pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
     proxy: Data<PipelineFileSystemProxy>,
+    platforms: Data<Platforms>,
+    registry: Data<ConnectionRegistry>,
 ) -> Result<HttpResponse, Error> {
     let user = user.ok_or_else(|| ErrorUnauthorized(""))?;
     println!("{req:?}");
-    let socket = ExecutePipelineSocket::new(user, cfg, enqueue_tx, pool, proxy);
-    let res = ws::start(socket, &req, stream);
+    let frame_size = cfg.local.server.max_exec_frame;
+    let mut socket =
+        ExecutePipelineSocket::new(user, cfg, enqueue_tx, pool, proxy, platforms, registry);
+    socket.traceparent = request_traceparent(&req);
+    let res = ws::WsResponseBuilder::new(socket, &req, stream)
+        .frame_size(frame_size)
+        .start();
     println!("{res:?}");
     res
 }
@@ -1,4 +1,5 @@
 use crate::requests::RunInfo;
+use crate::sockets::RUN_ID_PREFIX;
 use actix::io::{SinkWrite, WriteHandler};
 use actix::{Actor, ActorContext, Context, Handler, StreamHandler};
 use actix_codec::Framed;
@@ -7,15 +8,26 @@ use awc::error::WsProtocolError;
 use awc::ws::{Codec, Frame, Message};
 use awc::BoxedSocket;
 use futures::stream::SplitSink;
+use std::sync::{Arc, Mutex};
 use tracing::debug;
 
 pub struct ExecClient {
     writer: SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>,
+    run_id: Arc<Mutex<Option<String>>>,
 }
 
 impl ExecClient {
     pub fn new(writer: SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>) -> Self {
-        Self { writer }
+        Self {
+            writer,
+            run_id: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Sets where the id of the run is stored once the server has sent it.
+    pub fn run_id(mut self, run_id: Arc<Mutex<Option<String>>>) -> Self {
+        self.run_id = run_id;
+        self
     }
 }
 
@@ -47,7 +59,16 @@ impl Handler<RunInfo> for ExecClient {
 impl StreamHandler<Result<Frame, WsProtocolError>> for ExecClient {
     fn handle(&mut self, msg: Result<Frame, WsProtocolError>, ctx: &mut Context<Self>) {
         match msg {
-            Ok(Frame::Text(bt)) => println!("{}", String::from_utf8_lossy(&bt[..])),
+            Ok(Frame::Text(bt)) => {
+                let text = String::from_utf8_lossy(&bt[..]);
+                if let Some(id) = text.strip_prefix(RUN_ID_PREFIX) {
+                    *self.run_id.lock().unwrap() = Some(id.to_string());
+                }
+                println!("{text}");
+            }
+            Ok(Frame::Ping(msg)) => {
+                let _ = self.writer.write(Message::Pong(msg));
+            }
             Ok(Frame::Close(_)) => ctx.stop(),
             _ => {}
         }
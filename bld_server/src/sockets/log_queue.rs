@@ -0,0 +1,262 @@
+use actix::dev::ToEnvelope;
+use actix::prelude::*;
+use tracing::warn;
+
+pub const LOG_MESSAGES_DROPPED: &str = "[bld] Log messages dropped due to slow consumer";
+
+/// The output of a run that a socket queues in its own mailbox before sending it to its
+/// client, so that the capacity of the mailbox limits the messages waiting for a slow client.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub enum LogMessage {
+    Line(String),
+    /// Sent after the last lines of a finished run in order for the socket to close.
+    Finished,
+}
+
+/// Queues the lines in the mailbox of a socket. When the mailbox is full the rest of the
+/// lines are dropped and a warning is queued in their place.
+pub fn queue_log_lines<A, I>(addr: &Addr<A>, lines: I)
+where
+    A: Actor + Handler<LogMessage>,
+    A::Context: ToEnvelope<A, LogMessage>,
+    I: IntoIterator<Item = String>,
+{
+    let mut lines = lines.into_iter();
+    while let Some(line) = lines.next() {
+        match addr.try_send(LogMessage::Line(line)) {
+            Ok(()) => {}
+            Err(SendError::Full(_)) => {
+                let dropped = 1 + lines.count();
+                warn!("dropped {dropped} log messages of a slow websocket client");
+                addr.do_send(LogMessage::Line(LOG_MESSAGES_DROPPED.to_string()));
+                return;
+            }
+            Err(SendError::Closed(_)) => return,
+        }
+    }
+}
+
+/// Queues the last lines of a finished run, which are never dropped.
+pub fn flush_log_lines<A, I>(addr: &Addr<A>, lines: I)
+where
+    A: Actor + Handler<LogMessage>,
+    A::Context: ToEnvelope<A, LogMessage>,
+    I: IntoIterator<Item = String>,
+{
+    for line in lines {
+        addr.do_send(LogMessage::Line(line));
+    }
+}
+
+/// Queues the last lines of a finished run, which are never dropped, followed by the
+/// message that closes the socket.
+pub fn finish_log_lines<A, I>(addr: &Addr<A>, lines: I)
+where
+    A: Actor + Handler<LogMessage>,
+    A::Context: ToEnvelope<A, LogMessage>,
+    I: IntoIterator<Item = String>,
+{
+    flush_log_lines(addr, lines);
+    addr.do_send(LogMessage::Finished);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bld_config::BldConfig;
+    use bld_core::logger::{Logger, LONG_LINE_MARKER};
+    use bld_core::scanner::{FileScanner, Scanner};
+    use bld_runner::{OutputDecoder, OutputEncoding};
+    use std::fs::{create_dir_all, remove_dir_all, OpenOptions};
+    use std::io::Write;
+    use std::sync::Arc;
+
+    #[derive(Default)]
+    struct MockSocket {
+        lines: Vec<String>,
+        finished: bool,
+    }
+
+    impl Actor for MockSocket {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<LogMessage> for MockSocket {
+        type Result = ();
+
+        fn handle(&mut self, msg: LogMessage, _ctx: &mut Self::Context) {
+            match msg {
+                LogMessage::Line(line) => self.lines.push(line),
+                LogMessage::Finished => self.finished = true,
+            }
+        }
+    }
+
+    #[derive(Message)]
+    #[rtype(result = "(Vec<String>, bool)")]
+    struct Received;
+
+    impl Handler<Received> for MockSocket {
+        type Result = MessageResult<Received>;
+
+        fn handle(&mut self, _msg: Received, _ctx: &mut Self::Context) -> Self::Result {
+            MessageResult((self.lines.clone(), self.finished))
+        }
+    }
+
+    #[test]
+    fn log_lines_are_dropped_when_the_mailbox_is_full() {
+        System::new().block_on(async {
+            let mut ctx = Context::new();
+            ctx.set_mailbox_capacity(4);
+            let addr = ctx.run(MockSocket::default());
+
+            let lines = (0..10).map(|i| format!("line {i}"));
+            queue_log_lines(&addr, lines);
+            let (received, finished) = addr.send(Received).await.unwrap();
+            assert!(received.len() < 10);
+            assert_eq!(received[0], "line 0");
+            assert_eq!(received.last().unwrap(), LOG_MESSAGES_DROPPED);
+            assert!(!finished);
+
+            let lines = (0..10).map(|i| format!("last line {i}"));
+            finish_log_lines(&addr, lines);
+            let (received, finished) = addr.send(Received).await.unwrap();
+            assert_eq!(received.last().unwrap(), "last line 9");
+            assert!(finished);
+        });
+    }
+
+    #[test]
+    fn long_log_lines_are_split_through_the_logger_the_scanner_and_the_socket() {
+        let logs = std::env::temp_dir().join(format!("bld-long-lines-{}", std::process::id()));
+        create_dir_all(&logs).unwrap();
+        let mut config = BldConfig::default();
+        config.local.logs = logs.display().to_string();
+        config.local.max_log_line_length = 1024;
+        let config = Arc::new(config);
+        let max = config.local.max_log_line_length + LONG_LINE_MARKER.len();
+
+        // one line written by the logger and one appended to the file without it.
+        let line = "x".repeat(6 * 1024 * 1024);
+        let logger = Logger::file_atom(config.clone(), "run").unwrap();
+        logger.lock().unwrap().dumpln(&line);
+        assert_eq!(logger.lock().unwrap().long_lines(), 1);
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(logs.join("run"))
+            .unwrap();
+        write!(file, "{line}").unwrap();
+
+        let mut scanner = FileScanner::new(config, "run");
+        let mut lines = vec![];
+        loop {
+            let fetched = scanner.fetch();
+            if fetched.is_empty() {
+                break;
+            }
+            lines.extend(fetched);
+        }
+        lines.extend(scanner.flush());
+
+        System::new().block_on(async {
+            let mut ctx = Context::new();
+            ctx.set_mailbox_capacity(16);
+            let addr = ctx.run(MockSocket::default());
+            finish_log_lines(&addr, lines);
+            let (received, finished) = addr.send(Received).await.unwrap();
+            assert!(finished);
+            assert!(received.iter().all(|l| l.len() <= max));
+            let content: String = received
+                .iter()
+                .map(|l| l.trim_end_matches(LONG_LINE_MARKER))
+                .collect();
+            assert_eq!(content.len(), 2 * line.len());
+        });
+        remove_dir_all(logs).unwrap();
+    }
+
+    fn scan(scanner: &mut FileScanner) -> Vec<String> {
+        let mut lines = vec![];
+        loop {
+            let fetched = scanner.fetch();
+            if fetched.is_empty() {
+                break;
+            }
+            lines.extend(fetched);
+        }
+        lines.extend(scanner.flush());
+        lines
+    }
+
+    /// Removes the new lines and markers of the split lines in order to compare the content.
+    fn content(text: &str) -> String {
+        text.chars()
+            .filter(|c| !['\n', '\r'].contains(c) && !LONG_LINE_MARKER.contains(*c))
+            .collect()
+    }
+
+    #[test]
+    fn random_bytes_are_logged_through_the_logger_the_scanner_and_the_socket() {
+        let logs = std::env::temp_dir().join(format!("bld-random-bytes-{}", std::process::id()));
+        create_dir_all(&logs).unwrap();
+        let mut config = BldConfig::default();
+        config.local.logs = logs.display().to_string();
+        config.local.max_log_line_length = 64;
+        let config = Arc::new(config);
+        let max = config.local.max_log_line_length + LONG_LINE_MARKER.len();
+
+        // xorshift, in order for the bytes to be the same on every run.
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let bytes: Vec<u8> = (0..64 * 1024).map(|_| next() as u8).collect();
+
+        let logger = Logger::file_atom(config.clone(), "run").unwrap();
+        let mut decoder = OutputDecoder::new(OutputEncoding::Utf8);
+        let mut decoded = String::new();
+        let mut start = 0;
+        while start < bytes.len() {
+            let end = (start + 1 + next() as usize % 16).min(bytes.len());
+            let chunk = decoder.decode(&bytes[start..end]);
+            logger.lock().unwrap().dump(&chunk);
+            decoded.push_str(&chunk);
+            start = end;
+        }
+        let rest = decoder.finish();
+        logger.lock().unwrap().dump(&rest);
+        decoded.push_str(&rest);
+        assert_eq!(decoded, String::from_utf8_lossy(&bytes));
+
+        let mut scanner = FileScanner::new(config, "run");
+        let lines = scan(&mut scanner);
+        assert_eq!(content(&lines.concat()), content(&decoded));
+
+        // bytes appended to the file without the logger are scanned as well.
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(logs.join("run"))
+            .unwrap();
+        file.write_all(&bytes).unwrap();
+        let mut raw_lines = scan(&mut scanner);
+        assert!(!raw_lines.is_empty());
+
+        raw_lines.splice(0..0, lines);
+        System::new().block_on(async {
+            let mut ctx = Context::new();
+            ctx.set_mailbox_capacity(16);
+            let addr = ctx.run(MockSocket::default());
+            finish_log_lines(&addr, raw_lines.clone());
+            let (received, finished) = addr.send(Received).await.unwrap();
+            assert!(finished);
+            assert!(received.iter().all(|l| l.chars().count() <= max));
+            assert_eq!(received, raw_lines);
+        });
+        remove_dir_all(logs).unwrap();
+    }
+}
@@ -2,10 +2,12 @@ mod enqueue_client;
 mod exec;
 mod exec_client;
 mod ha;
+mod log_queue;
 mod monit;
 
 pub use enqueue_client::*;
 pub use exec::*;
 pub use exec_client::*;
 pub use ha::*;
+pub use log_queue::*;
 pub use monit::*;
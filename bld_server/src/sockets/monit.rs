@@ -1,3 +1,8 @@
+use crate::helpers::{
+    queue_status, CloseConnection, ConnectionKind, ConnectionRegistry,
+    CONNECTION_HEARTBEAT_INTERVAL_SECONDS, CONNECTION_TIMEOUT_SECONDS,
+};
+use crate::sockets::{finish_log_lines, flush_log_lines, queue_log_lines, LogMessage};
 use crate::{extractors::User, requests::MonitInfo};
 use actix::prelude::*;
 use actix_web::error::ErrorUnauthorized;
@@ -6,74 +11,258 @@ use actix_web::{Error, HttpRequest, HttpResponse};
 use actix_web_actors::ws;
 use anyhow::{anyhow, Result};
 use bld_config::BldConfig;
-use bld_core::database::pipeline_runs::{self, PR_STATE_FAULTED, PR_STATE_FINISHED};
+use bld_core::database::pipeline_runs::{
+    self, PipelineRuns, PR_STATE_FAULTED, PR_STATE_FINISHED, PR_STATE_INTERRUPTED, PR_STATE_QUEUED,
+    PR_STATE_RUNNING,
+};
 use bld_core::scanner::{FileScanner, Scanner};
 use diesel::r2d2::{ConnectionManager, Pool};
 use diesel::sqlite::SqliteConnection;
-use std::{sync::Arc, time::Duration};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{error, info};
+
+const SHORT_RUN_ID_LENGTH: usize = 8;
+
+/// A run that the socket sends the output of.
+struct MonitoredRun {
+    scanner: FileScanner,
+    queue_position: Option<usize>,
+}
 
 pub struct MonitorPipelineSocket {
-    id: String,
+    runs: BTreeMap<String, MonitoredRun>,
     pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
     config: Data<BldConfig>,
-    scanner: Option<FileScanner>,
+    user: User,
+    registry: Data<ConnectionRegistry>,
+    connection_id: String,
+    hb: Instant,
+    requested: bool,
+    finished: bool,
+    multiplexed: bool,
+    all: bool,
 }
 
 impl MonitorPipelineSocket {
     pub fn new(
+        user: User,
         pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
         config: Data<BldConfig>,
+        registry: Data<ConnectionRegistry>,
     ) -> Self {
         Self {
-            id: String::new(),
+            runs: BTreeMap::new(),
             pool,
             config,
-            scanner: None,
+            user,
+            registry,
+            connection_id: String::new(),
+            hb: Instant::now(),
+            requested: false,
+            finished: false,
+            multiplexed: false,
+            all: false,
+        }
+    }
+
+    fn heartbeat(act: &Self, ctx: &mut <Self as Actor>::Context) {
+        if act.hb.elapsed() > Duration::from_secs(CONNECTION_TIMEOUT_SECONDS) {
+            info!("monit websocket heartbeat failed. disconnecting!");
+            ctx.stop();
+            return;
+        }
+        ctx.ping(b"");
+    }
+
+    fn alive(&mut self) {
+        self.hb = Instant::now();
+        self.registry.heartbeat(&self.connection_id);
+    }
+
+    /// Prefixes the lines of a run with its short id when the output of many runs is
+    /// sent over the socket.
+    fn lines(&self, run_id: &str, lines: Vec<String>) -> Vec<String> {
+        if !self.multiplexed {
+            return lines;
         }
+        let short_id: String = run_id.chars().take(SHORT_RUN_ID_LENGTH).collect();
+        lines
+            .into_iter()
+            .map(|line| format!("[{short_id}] {line}"))
+            .collect()
     }
 
     fn scan(act: &mut Self, ctx: &mut <Self as Actor>::Context) {
-        if let Some(scanner) = act.scanner.as_mut() {
-            let content = scanner.fetch();
-            for line in content.iter() {
-                ctx.text(line.to_string());
-            }
+        let fetched: Vec<(String, Vec<String>)> = act
+            .runs
+            .iter_mut()
+            .map(|(id, run)| (id.to_string(), run.scanner.fetch()))
+            .collect();
+        for (id, lines) in fetched {
+            queue_log_lines(&ctx.address(), act.lines(&id, lines));
         }
     }
 
     fn exec(act: &mut Self, ctx: &mut <Self as Actor>::Context) {
-        if let Ok(mut conn) = act.pool.get() {
-            match pipeline_runs::select_by_id(&mut conn, &act.id) {
-                Ok(run) if run.state == PR_STATE_FINISHED || run.state == PR_STATE_FAULTED => {
-                    ctx.stop()
+        if act.finished || !act.requested {
+            return;
+        }
+        let mut conn = match act.pool.get() {
+            Ok(conn) => conn,
+            Err(_) => return,
+        };
+        if act.all {
+            MonitorPipelineSocket::discover(act, ctx, &mut conn);
+        }
+        let ids: Vec<String> = act.runs.keys().cloned().collect();
+        for id in ids {
+            match pipeline_runs::select_by_id(&mut conn, &id) {
+                Ok(run)
+                    if run.state == PR_STATE_FINISHED
+                        || run.state == PR_STATE_FAULTED
+                        || run.state == PR_STATE_INTERRUPTED =>
+                {
+                    MonitorPipelineSocket::run_ended(act, ctx, &run);
+                }
+                Ok(run) if run.state == PR_STATE_QUEUED => {
+                    MonitorPipelineSocket::queued(act, ctx, &mut conn, &id);
                 }
                 Err(_) => {
                     ctx.text("internal server error");
                     ctx.stop();
+                    return;
                 }
                 _ => {}
             }
         }
     }
 
+    /// Sends the last lines of a run that has ended and stops monitoring it. The socket is
+    /// stopped once the queued output of the last run has been sent.
+    fn run_ended(act: &mut Self, ctx: &mut <Self as Actor>::Context, run: &PipelineRuns) {
+        let mut monitored = match act.runs.remove(&run.id) {
+            Some(monitored) => monitored,
+            None => return,
+        };
+        let mut lines = act.lines(&run.id, monitored.scanner.flush());
+        if act.multiplexed {
+            let short_id: String = run.id.chars().take(SHORT_RUN_ID_LENGTH).collect();
+            lines.push(format!(
+                "[bld] Run {short_id} of pipeline {} {}",
+                run.name, run.state
+            ));
+        }
+        if act.runs.is_empty() && !act.all {
+            act.finished = true;
+            finish_log_lines(&ctx.address(), lines);
+        } else {
+            flush_log_lines(&ctx.address(), lines);
+        }
+    }
+
+    /// Starts monitoring the runs that are running or queued and aren't monitored yet.
+    fn discover(act: &mut Self, ctx: &mut <Self as Actor>::Context, conn: &mut SqliteConnection) {
+        let mut active = vec![];
+        for state in [PR_STATE_RUNNING, PR_STATE_QUEUED] {
+            match pipeline_runs::select_top_level_by_state(conn, state) {
+                Ok(runs) => active.extend(runs),
+                Err(e) => {
+                    error!("unable to read the active runs, {e}");
+                    return;
+                }
+            }
+        }
+        for run in active {
+            if !act.runs.contains_key(&run.id) {
+                let short_id: String = run.id.chars().take(SHORT_RUN_ID_LENGTH).collect();
+                ctx.text(format!(
+                    "[bld] Monitoring run {short_id} of pipeline {}",
+                    run.name
+                ));
+                act.follow(&run.id);
+            }
+        }
+    }
+
+    /// Tells the client the position of a queued run in the worker queue whenever it changes.
+    fn queued(
+        act: &mut Self,
+        ctx: &mut <Self as Actor>::Context,
+        conn: &mut SqliteConnection,
+        id: &str,
+    ) {
+        let status = match queue_status(&act.config, conn) {
+            Ok(status) => status,
+            Err(e) => {
+                error!("unable to read the worker queue, {e}");
+                return;
+            }
+        };
+        let position = status.position(id);
+        let previous = act.runs.get(id).and_then(|r| r.queue_position);
+        if let Some(position) = position.filter(|p| Some(*p) != previous) {
+            let line = format!(
+                "[bld] Run is queued at position {position} of {}, with {} of {} workers active",
+                status.queued.len(),
+                status.active,
+                status.max_workers
+            );
+            for line in act.lines(id, vec![line]) {
+                ctx.text(line);
+            }
+        }
+        if let Some(run) = act.runs.get_mut(id) {
+            run.queue_position = position;
+        }
+    }
+
+    fn follow(&mut self, run_id: &str) {
+        self.registry.subscribe(&self.connection_id, run_id);
+        self.runs.insert(
+            run_id.to_string(),
+            MonitoredRun {
+                scanner: FileScanner::new(Arc::clone(&self.config), run_id),
+                queue_position: None,
+            },
+        );
+    }
+
     fn dependencies(&mut self, data: &str) -> Result<()> {
         let data = serde_json::from_str::<MonitInfo>(data)?;
         let mut conn = self.pool.get()?;
 
-        let run = if data.last {
-            pipeline_runs::select_last(&mut conn)
-        } else if let Some(id) = data.id {
-            pipeline_runs::select_by_id(&mut conn, &id)
+        if data.all {
+            self.all = true;
+            self.multiplexed = true;
+            self.requested = true;
+            return Ok(());
+        }
+
+        let mut ids = data.ids;
+        if let Some(id) = data.id {
+            ids.insert(0, id);
+        }
+
+        let runs = if data.last {
+            pipeline_runs::select_last(&mut conn).map(|run| vec![run])
+        } else if !ids.is_empty() {
+            ids.iter()
+                .map(|id| pipeline_runs::select_by_id(&mut conn, id))
+                .collect()
         } else if let Some(name) = data.name {
-            pipeline_runs::select_by_name(&mut conn, &name)
+            pipeline_runs::select_by_name(&mut conn, &name).map(|run| vec![run])
         } else {
             return Err(anyhow!("pipeline not found"));
         }
         .map_err(|_| anyhow!("pipeline not found"))?;
 
-        self.id = run.id.clone();
-
-        self.scanner = Some(FileScanner::new(Arc::clone(&self.config), &run.id));
+        for run in runs {
+            self.follow(&run.id);
+        }
+        self.multiplexed = self.runs.len() > 1;
+        self.requested = true;
         Ok(())
     }
 }
@@ -82,12 +271,47 @@ impl Actor for MonitorPipelineSocket {
     type Context = ws::WebsocketContext<Self>;
 
     fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.set_mailbox_capacity(self.config.local.ws_message_queue_depth);
+        let closer = ctx.address().recipient();
+        self.connection_id = self
+            .registry
+            .register(ConnectionKind::Monit, &self.user.name, closer);
         ctx.run_interval(Duration::from_millis(500), |act, ctx| {
             MonitorPipelineSocket::scan(act, ctx);
         });
         ctx.run_interval(Duration::from_secs(1), |act, ctx| {
             MonitorPipelineSocket::exec(act, ctx);
         });
+        ctx.run_interval(
+            Duration::from_secs(CONNECTION_HEARTBEAT_INTERVAL_SECONDS),
+            |act, ctx| {
+                MonitorPipelineSocket::heartbeat(act, ctx);
+            },
+        );
+    }
+
+    fn stopped(&mut self, _ctx: &mut Self::Context) {
+        self.registry.unregister(&self.connection_id);
+    }
+}
+
+impl Handler<CloseConnection> for MonitorPipelineSocket {
+    type Result = ();
+
+    fn handle(&mut self, _msg: CloseConnection, ctx: &mut Self::Context) {
+        ctx.close(Some(ws::CloseCode::Away.into()));
+        ctx.stop();
+    }
+}
+
+impl Handler<LogMessage> for MonitorPipelineSocket {
+    type Result = ();
+
+    fn handle(&mut self, msg: LogMessage, ctx: &mut Self::Context) {
+        match msg {
+            LogMessage::Line(line) => ctx.text(line),
+            LogMessage::Finished => ctx.stop(),
+        }
     }
 }
 
@@ -95,6 +319,7 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for MonitorPipelineSo
     fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
         match msg {
             Ok(ws::Message::Text(txt)) => {
+                self.alive();
                 if let Err(e) = self.dependencies(&txt) {
                     eprintln!("{e}");
                     ctx.text("internal server error");
@@ -102,9 +327,10 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for MonitorPipelineSo
                 }
             }
             Ok(ws::Message::Ping(msg)) => {
+                self.alive();
                 ctx.pong(&msg);
             }
-            Ok(ws::Message::Pong(_)) => {}
+            Ok(ws::Message::Pong(_)) => self.alive(),
             Ok(ws::Message::Close(reason)) => {
                 ctx.close(reason);
                 ctx.stop();
@@ -120,12 +346,12 @@ pub async fn ws_monit(
     stream: Payload,
     pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
     config: Data<BldConfig>,
+    registry: Data<ConnectionRegistry>,
 ) -> Result<HttpResponse, Error> {
-    if user.is_none() {
-        return Err(ErrorUnauthorized(""));
-    }
+    let user = user.ok_or_else(|| ErrorUnauthorized(""))?;
     println!("{req:?}");
-    let res = ws::start(MonitorPipelineSocket::new(pool, config), &req, stream);
+    let socket = MonitorPipelineSocket::new(user, pool, config, registry);
+    let res = ws::start(socket, &req, stream);
     println!("{res:?}");
     res
 }
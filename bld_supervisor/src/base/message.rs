@@ -1,18 +1,30 @@
 use actix::Message;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 pub static SERVER: &str = "server";
 pub static WORKER: &str = "worker";
 
-#[derive(Debug, Serialize, Deserialize, Message)]
+#[derive(Debug, Clone, Serialize, Deserialize, Message)]
 #[rtype(result = "()")]
 pub enum ServerMessages {
     Ack,
+    Hello {
+        version: String,
+    },
     Enqueue {
         pipeline: String,
         run_id: String,
         variables: Option<String>,
         environment: Option<String>,
+        #[serde(default)]
+        secrets: Option<HashMap<String, String>>,
+        git_url: Option<String>,
+        git_ref: Option<String>,
+        #[serde(default)]
+        dry_run: bool,
+        #[serde(default)]
+        traceparent: Option<String>,
     },
 }
 
@@ -23,3 +35,8 @@ pub enum WorkerMessages {
     WhoAmI { pid: u32 },
     Completed,
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum SupervisorMessages {
+    Hello { version: String },
+}
@@ -0,0 +1,57 @@
+use actix_service::fn_service;
+use actix_tls::connect::{ConnectError, ConnectInfo, Connection};
+#[cfg(unix)]
+use actix_web::rt::net::UnixStream;
+use awc::http::{Uri, Version};
+use awc::{Client, Connector};
+use bld_config::BldLocalSupervisorConfig;
+
+/// Creates an http 1.1 client that connects to the provided unix socket regardless
+/// of the host in the url of a request.
+#[cfg(unix)]
+pub fn unix_socket_client(socket: &str) -> Client {
+    let socket = socket.to_string();
+    let connector = Connector::new().connector(fn_service(move |req: ConnectInfo<Uri>| {
+        let socket = socket.clone();
+        async move {
+            let stream = UnixStream::connect(socket)
+                .await
+                .map_err(ConnectError::Io)?;
+            Ok::<_, ConnectError>(Connection::new(req.request().clone(), stream))
+        }
+    }));
+    Client::builder()
+        .max_http_version(Version::HTTP_11)
+        .connector(connector)
+        .finish()
+}
+
+/// Creates an http 1.1 client whose connections fail, since unix sockets are supported
+/// only on unix systems.
+#[cfg(not(unix))]
+pub fn unix_socket_client(socket: &str) -> Client {
+    let socket = socket.to_string();
+    let connector = Connector::new().connector(fn_service(move |_: ConnectInfo<Uri>| {
+        let socket = socket.clone();
+        async move {
+            let message = format!("unable to connect to {socket}, unix sockets are not supported");
+            let error = std::io::Error::new(std::io::ErrorKind::Unsupported, message);
+            Err::<Connection<Uri, actix_web::rt::net::TcpStream>, _>(ConnectError::Io(error))
+        }
+    }));
+    Client::builder()
+        .max_http_version(Version::HTTP_11)
+        .connector(connector)
+        .finish()
+}
+
+/// Creates a client for the supervisor web sockets that connects either to the configured
+/// unix socket or to the host and port of the supervisor.
+pub fn ws_client(config: &BldLocalSupervisorConfig) -> Client {
+    match &config.socket {
+        Some(socket) => unix_socket_client(socket),
+        None => Client::builder()
+            .max_http_version(Version::HTTP_11)
+            .finish(),
+    }
+}
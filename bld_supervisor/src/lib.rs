@@ -1,4 +1,5 @@
 pub mod base;
+pub mod client;
 mod queues;
 pub mod sockets;
 pub mod supervisor;
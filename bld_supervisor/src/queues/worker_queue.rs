@@ -4,8 +4,10 @@ use actix_web::web::Data;
 use anyhow::Result;
 use bld_config::BldConfig;
 use bld_core::database::pipeline_run_containers::{self, PRC_STATE_REMOVED};
+use bld_core::database::pipeline_run_parents;
 use bld_core::database::pipeline_runs::{
-    self, PR_STATE_FAULTED, PR_STATE_FINISHED, PR_STATE_QUEUED,
+    self, PR_STATE_FAULTED, PR_STATE_FINISHED, PR_STATE_INTERRUPTED, PR_STATE_QUEUED,
+    PR_STATE_RUNNING,
 };
 use bld_core::workers::PipelineWorker;
 use diesel::r2d2::{ConnectionManager, Pool};
@@ -24,6 +26,7 @@ pub struct WorkerQueue {
     backlog: VecDeque<PipelineWorker>,
     config: Data<BldConfig>,
     pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
+    stopping: bool,
 }
 
 impl WorkerQueue {
@@ -32,6 +35,11 @@ impl WorkerQueue {
         config: Data<BldConfig>,
         pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
     ) -> Self {
+        // the orphaned runs are interrupted before the cleanup of the containers so that
+        // their containers are removed as well.
+        if let Err(e) = try_interrupt_orphaned_runs(pool.clone()) {
+            error!("error while interrupting orphaned runs, {e}");
+        }
         let config_clone = config.clone();
         let pool_clone = pool.clone();
         spawn(async move {
@@ -45,6 +53,7 @@ impl WorkerQueue {
             backlog: VecDeque::new(),
             config,
             pool,
+            stopping: false,
         }
     }
 
@@ -53,10 +62,72 @@ impl WorkerQueue {
             error!("{e}");
             e
         })?;
+        if let Some(pid) = worker.get_pid() {
+            let mut conn = self.pool.get()?;
+            let _ = pipeline_runs::update_worker_pid(&mut conn, worker.get_run_id(), pid as i32);
+        }
         self.active.push(worker);
         Ok(())
     }
 
+    /// Starts the shutdown of the queue by sending a SIGTERM to the active workers, so that
+    /// they stop their runs and dispose their platforms. No more workers are started after
+    /// this and the ids of the runs that were running are returned.
+    pub fn begin_shutdown(&mut self) -> Vec<String> {
+        self.stopping = true;
+        let mut running = vec![];
+        let mut conn = self.pool.get().ok();
+        for worker in self.active.iter() {
+            let run_id = worker.get_run_id();
+            let is_running = conn
+                .as_mut()
+                .and_then(|conn| pipeline_runs::select_by_id(conn, run_id).ok())
+                .map(|run| run.state == PR_STATE_RUNNING)
+                .unwrap_or(true);
+            if is_running {
+                running.push(run_id.to_string());
+            }
+            info!("sending SIGTERM to the worker of run {run_id}");
+            if let Err(e) = worker.terminate() {
+                error!("unable to terminate the worker of run {run_id}, {e}");
+            }
+        }
+        running
+    }
+
+    pub fn has_active_workers(&mut self) -> bool {
+        self.active.iter_mut().any(|w| !w.has_exited())
+    }
+
+    /// Completes the shutdown by killing the workers that are still active after the grace
+    /// period and marking the provided runs that haven't finished as interrupted, along with
+    /// the runs of the backlog that will never start.
+    pub fn finish_shutdown(&mut self, running: &[String]) -> Result<()> {
+        for worker in self.active.iter_mut() {
+            if !worker.has_exited() {
+                info!("killing the worker of run {}", worker.get_run_id());
+                if let Err(e) = worker.kill() {
+                    error!(
+                        "unable to kill the worker of run {}, {e}",
+                        worker.get_run_id()
+                    );
+                }
+            }
+        }
+        self.active.clear();
+        let mut conn = self.pool.get()?;
+        for run_id in running.iter() {
+            let run = pipeline_runs::select_by_id(&mut conn, run_id)?;
+            if run.state != PR_STATE_FINISHED {
+                interrupt_run(&mut conn, run_id)?;
+            }
+        }
+        for worker in self.backlog.drain(..) {
+            interrupt_run(&mut conn, worker.get_run_id())?;
+        }
+        Ok(())
+    }
+
     fn add_backlog(&mut self, worker: PipelineWorker) -> Result<()> {
         let mut conn = self.pool.get()?;
         pipeline_runs::update_state(&mut conn, worker.get_run_id(), PR_STATE_QUEUED)?;
@@ -68,7 +139,7 @@ impl WorkerQueue {
 impl Queue<PipelineWorker> for WorkerQueue {
     /// Used to spawn the child process of the worker and add it to the active workers vector.
     fn enqueue(&mut self, item: PipelineWorker) -> Result<()> {
-        if self.active.len() < self.capacity {
+        if self.active.len() < self.capacity && !self.stopping {
             self.activate(item)?;
         } else {
             self.add_backlog(item)?;
@@ -93,6 +164,9 @@ impl Queue<PipelineWorker> for WorkerQueue {
             }
             !found
         });
+        if self.stopping {
+            return Ok(());
+        }
         for _ in 0..(self.capacity - self.active.len()) {
             if let Some(worker) = self.backlog.pop_front() {
                 self.activate(worker)?;
@@ -144,6 +218,42 @@ fn try_cleanup_process(
     Ok(())
 }
 
+/// Marks a run as interrupted along with the pipelines that it called and are still running.
+fn interrupt_run(conn: &mut SqliteConnection, run_id: &str) -> Result<()> {
+    pipeline_runs::update_state(conn, run_id, PR_STATE_INTERRUPTED)?;
+    let _ = pipeline_run_containers::update_running_containers_to_faulted(conn, run_id);
+    for parent in pipeline_run_parents::select_by_parent_run_id(conn, run_id)? {
+        let child = pipeline_runs::select_by_id(conn, &parent.child_run_id)?;
+        if child.state == PR_STATE_RUNNING {
+            interrupt_run(conn, &child.id)?;
+        }
+    }
+    Ok(())
+}
+
+/// This function will mark the runs that are still running without a worker process as
+/// interrupted. These are the runs whose workers exited or were killed while the supervisor
+/// wasn't running, so they would otherwise stay in the running state forever.
+fn try_interrupt_orphaned_runs(
+    pool: Data<Pool<ConnectionManager<SqliteConnection>>>,
+) -> Result<()> {
+    let mut conn = pool.get()?;
+    for run in pipeline_runs::select_top_level_by_state(&mut conn, PR_STATE_RUNNING)? {
+        let has_worker = run
+            .worker_pid
+            .map(|pid| PipelineWorker::is_alive(pid as u32))
+            .unwrap_or(false);
+        if !has_worker {
+            info!(
+                "interrupting run {} since its worker is no longer running",
+                run.id
+            );
+            interrupt_run(&mut conn, &run.id)?;
+        }
+    }
+    Ok(())
+}
+
 /// This function will fetch all containers with faulted state or those in active state
 /// with runs that have either finished or faulted, and try to stop and remove them using the docker
 /// engine API and then set their state as removed.
@@ -190,3 +300,57 @@ pub async fn try_cleanup_containers(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bld_core::database::new_connection_pool;
+    use bld_core::database::pipeline_run_parents::InsertPipelineRunParent;
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_dir_all};
+    use uuid::Uuid;
+
+    #[test]
+    fn runs_without_a_live_worker_are_interrupted() {
+        let root = temp_dir().join(format!("bld-orphans-{}", Uuid::new_v4()));
+        create_dir_all(&root).unwrap();
+        let pool = new_connection_pool(&root.display().to_string()).unwrap();
+        let mut conn = pool.get().unwrap();
+        // a pid above the maximum pid of linux, which never belongs to a process.
+        let workers = [
+            ("orphan", Some(i32::MAX)),
+            ("alive", Some(std::process::id() as i32)),
+            ("unknown", None),
+        ];
+        for (run_id, worker_pid) in workers {
+            pipeline_runs::insert(&mut conn, run_id, "build.yaml", "user", None).unwrap();
+            pipeline_runs::update_state(&mut conn, run_id, PR_STATE_RUNNING).unwrap();
+            if let Some(pid) = worker_pid {
+                pipeline_runs::update_worker_pid(&mut conn, run_id, pid).unwrap();
+            }
+        }
+        pipeline_runs::insert(&mut conn, "child", "test.yaml", "user", None).unwrap();
+        pipeline_runs::update_state(&mut conn, "child", PR_STATE_RUNNING).unwrap();
+        let parent = InsertPipelineRunParent {
+            id: "link",
+            child_run_id: "child",
+            parent_run_id: "orphan",
+            step_name: None,
+        };
+        pipeline_run_parents::insert(&mut conn, parent).unwrap();
+
+        try_interrupt_orphaned_runs(Data::new(pool.clone())).unwrap();
+
+        let mut state = |run_id: &str| {
+            pipeline_runs::select_by_id(&mut conn, run_id)
+                .unwrap()
+                .state
+        };
+        assert_eq!(state("orphan"), PR_STATE_INTERRUPTED);
+        assert_eq!(state("child"), PR_STATE_INTERRUPTED);
+        assert_eq!(state("unknown"), PR_STATE_INTERRUPTED);
+        assert_eq!(state("alive"), PR_STATE_RUNNING);
+        drop(conn);
+        remove_dir_all(root).unwrap();
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{
-    base::{Queue, ServerMessages},
+    base::{Queue, ServerMessages, SupervisorMessages},
     queues::WorkerQueue,
 };
 use actix::prelude::*;
@@ -8,6 +8,8 @@ use actix_web::{
     Error, HttpRequest, HttpResponse,
 };
 use actix_web_actors::ws;
+use bld_config::definitions::{VERSION, WORKER_SECRETS_ENV};
+use bld_config::BldConfig;
 use bld_core::workers::PipelineWorker;
 use std::env::current_exe;
 use std::process::Command;
@@ -15,23 +17,43 @@ use std::sync::Mutex;
 use tracing::{debug, error, info};
 
 pub struct ServerSocket {
+    config: Data<BldConfig>,
     worker_queue: Data<Mutex<WorkerQueue>>,
 }
 
 impl ServerSocket {
-    pub fn new(worker_queue: Data<Mutex<WorkerQueue>>) -> Self {
-        Self { worker_queue }
+    pub fn new(config: Data<BldConfig>, worker_queue: Data<Mutex<WorkerQueue>>) -> Self {
+        Self {
+            config,
+            worker_queue,
+        }
     }
 
-    fn handle_message(&self, bytes: &Bytes) -> anyhow::Result<()> {
+    fn handle_message(
+        &self,
+        bytes: &Bytes,
+        ctx: &mut <Self as Actor>::Context,
+    ) -> anyhow::Result<()> {
         let msg: ServerMessages = serde_json::from_slice(&bytes[..])?;
         match msg {
             ServerMessages::Ack => info!("a new server connection was acknowledged"),
+            ServerMessages::Hello { version } => {
+                info!("server with version {version} sent a hello message");
+                let reply = SupervisorMessages::Hello {
+                    version: VERSION.to_string(),
+                };
+                ctx.binary(serde_json::to_vec(&reply)?);
+            }
             ServerMessages::Enqueue {
                 pipeline,
                 run_id,
                 variables,
                 environment,
+                secrets,
+                git_url,
+                git_ref,
+                dry_run,
+                traceparent,
             } => {
                 info!("server sent an enqueue message for pipeline: {pipeline}");
                 let exe = current_exe().map_err(|e| {
@@ -52,6 +74,30 @@ impl ServerSocket {
                     command.arg("--environment");
                     command.arg(&environment);
                 }
+                // secrets are passed through the environment of the worker so that they
+                // are not visible in its arguments.
+                if let Some(secrets) = secrets.filter(|s| !s.is_empty()) {
+                    command.env(WORKER_SECRETS_ENV, serde_json::to_string(&secrets)?);
+                }
+                if let Some(git_url) = git_url {
+                    command.arg("--git-url");
+                    command.arg(&git_url);
+                }
+                if let Some(git_ref) = git_ref {
+                    command.arg("--git-ref");
+                    command.arg(&git_ref);
+                }
+                if dry_run {
+                    command.arg("--dry-run");
+                }
+                if let Some(traceparent) = traceparent {
+                    command.arg("--traceparent");
+                    command.arg(&traceparent);
+                }
+                if let Some(socket) = &self.config.local.supervisor.socket {
+                    command.arg("--socket");
+                    command.arg(socket);
+                }
                 let mut queue = self.worker_queue.lock().unwrap();
                 queue.enqueue(PipelineWorker::new(run_id, command))?;
                 info!("worker for pipeline: {pipeline} has been queued");
@@ -78,7 +124,7 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for ServerSocket {
         match msg {
             Ok(ws::Message::Binary(bytes)) => {
                 debug!("received binary message from server");
-                if let Err(e) = self.handle_message(&bytes) {
+                if let Err(e) = self.handle_message(&bytes, ctx) {
                     error!("handling message error. {e}");
                 }
             }
@@ -98,8 +144,9 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for ServerSocket {
 pub async fn ws_server_socket(
     req: HttpRequest,
     stream: Payload,
+    config: Data<BldConfig>,
     worker_queue: Data<Mutex<WorkerQueue>>,
 ) -> Result<HttpResponse, Error> {
-    let socket = ServerSocket::new(worker_queue);
+    let socket = ServerSocket::new(config, worker_queue);
     ws::start(socket, &req, stream)
 }
@@ -1,12 +1,26 @@
 use crate::queues::WorkerQueue;
 use crate::sockets::{ws_server_socket, ws_worker_socket};
+use actix_web::rt::time::sleep;
 use actix_web::web::{get, resource, Data};
 use actix_web::{App, HttpServer};
 use anyhow::{anyhow, Result};
 use bld_config::BldConfig;
 use bld_core::database::new_connection_pool;
 use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
+#[cfg(unix)]
+use std::fs::{remove_file, symlink_metadata};
+#[cfg(unix)]
+use std::io::ErrorKind;
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::signal::ctrl_c;
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::{debug, error, info};
 
 pub async fn start(config: BldConfig) -> Result<()> {
     let address = format!(
@@ -21,7 +35,9 @@ pub async fn start(config: BldConfig) -> Result<()> {
         config.clone(),
         pool.clone(),
     )));
+    let worker_queue_clone = worker_queue.clone();
 
+    // the signals are handled by the supervisor in order to stop the workers before the server.
     let mut server = HttpServer::new(move || {
         App::new()
             .app_data(config_clone.clone())
@@ -29,17 +45,124 @@ pub async fn start(config: BldConfig) -> Result<()> {
             .app_data(worker_queue.clone())
             .service(resource("/ws-server/").route(get().to(ws_server_socket)))
             .service(resource("/ws-worker/").route(get().to(ws_worker_socket)))
-    });
+    })
+    .disable_signals();
 
-    server = match &config.local.supervisor.tls {
-        Some(tls) => {
+    server = match (
+        &config.local.supervisor.socket,
+        &config.local.supervisor.tls,
+    ) {
+        #[cfg(unix)]
+        (Some(socket), _) => {
+            remove_stale_socket(socket)?;
+            server.bind_uds(socket)?
+        }
+        #[cfg(not(unix))]
+        (Some(_), _) => anyhow::bail!("the supervisor socket is supported only on unix systems"),
+        (None, Some(tls)) => {
+            tls.check_files()?;
             let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
             builder.set_private_key_file(&tls.private_key, SslFiletype::PEM)?;
             builder.set_certificate_chain_file(&tls.cert_chain)?;
             server.bind_openssl(address, builder)?
         }
-        None => server.bind(address)?,
+        (None, None) => server.bind(address)?,
     };
 
-    server.run().await.map_err(|e| anyhow!(e))
+    let server = server.run();
+    let handle = server.handle();
+    tokio::select! {
+        result = server => return result.map_err(|e| anyhow!(e)),
+        result = terminated() => {
+            result?;
+            info!("received SIGTERM, shutting down");
+        }
+        _ = ctrl_c() => info!("received SIGINT, shutting down"),
+    }
+
+    let grace = Duration::from_secs(config.local.supervisor.shutdown_grace_seconds);
+    shutdown_workers(&worker_queue_clone, grace).await;
+    handle.stop(true).await;
+    Ok(())
+}
+
+/// Removes the socket of a previous supervisor that didn't shut down cleanly. The supervisor
+/// refuses to start if another one is listening on the socket or if the path isn't a socket.
+#[cfg(unix)]
+fn remove_stale_socket(socket: &str) -> Result<()> {
+    let metadata = match symlink_metadata(socket) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    if !metadata.file_type().is_socket() {
+        anyhow::bail!("{socket} exists and isn't a socket");
+    }
+    match UnixStream::connect(socket) {
+        Ok(_) => anyhow::bail!("another supervisor is listening on {socket}"),
+        Err(e) if e.kind() == ErrorKind::ConnectionRefused => {
+            debug!("removing stale supervisor socket {socket}");
+            remove_file(socket)?;
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Waits for a SIGTERM. There is no such signal on windows, so only ctrl-c stops the supervisor there.
+#[cfg(unix)]
+async fn terminated() -> Result<()> {
+    signal(SignalKind::terminate())?.recv().await;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn terminated() -> Result<()> {
+    std::future::pending().await
+}
+
+/// Sends a SIGTERM to the active workers and waits for them to exit for the grace period,
+/// after which the remaining ones are killed and their runs are marked as interrupted.
+async fn shutdown_workers(worker_queue: &Mutex<WorkerQueue>, grace: Duration) {
+    let running = worker_queue.lock().unwrap().begin_shutdown();
+    let start = Instant::now();
+    while start.elapsed() < grace && worker_queue.lock().unwrap().has_active_workers() {
+        sleep(Duration::from_millis(100)).await;
+    }
+    if let Err(e) = worker_queue.lock().unwrap().finish_shutdown(&running) {
+        error!("error while interrupting the runs of the workers, {e}");
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+    use std::os::unix::net::UnixListener;
+    use uuid::Uuid;
+
+    #[test]
+    fn only_stale_sockets_are_removed() {
+        let root = temp_dir().join(format!("bld-supervisor-{}", Uuid::new_v4()));
+        create_dir_all(&root).unwrap();
+        let socket = root.join("supervisor.sock");
+        let socket_path = socket.display().to_string();
+
+        assert!(remove_stale_socket(&socket_path).is_ok());
+
+        let listener = UnixListener::bind(&socket).unwrap();
+        assert!(remove_stale_socket(&socket_path).is_err());
+        assert!(socket.exists());
+
+        drop(listener);
+        assert!(remove_stale_socket(&socket_path).is_ok());
+        assert!(!socket.exists());
+
+        write(&socket, "").unwrap();
+        assert!(remove_stale_socket(&socket_path).is_err());
+        assert!(socket.exists());
+
+        remove_dir_all(root).unwrap();
+    }
 }
@@ -0,0 +1,59 @@
+use anyhow::{anyhow, Result};
+use bld_config::{definitions::REMOTE_SERVER_OAUTH2, path, OAuth2Info};
+use oauth2::basic::BasicClient;
+use oauth2::reqwest::async_http_client;
+use oauth2::{RefreshToken, TokenResponse};
+use std::fs::{create_dir_all, read_to_string, remove_file, write};
+use std::path::PathBuf;
+
+fn access_token_path(server: &str) -> PathBuf {
+    path![REMOTE_SERVER_OAUTH2, server]
+}
+
+fn refresh_token_path(server: &str) -> PathBuf {
+    path![REMOTE_SERVER_OAUTH2, format!("{server}.refresh")]
+}
+
+/// The persisted access token of a server, if a login to it has happened.
+pub fn access_token(server: &str) -> Option<String> {
+    read_to_string(access_token_path(server)).ok()
+}
+
+/// Persists the tokens of a login to a server. The refresh token of an older login is removed
+/// when the provider doesn't return one, in order to not be used along with the new access token.
+pub fn persist_tokens(server: &str, access_token: &str, refresh_token: Option<&str>) -> Result<()> {
+    create_dir_all(REMOTE_SERVER_OAUTH2)?;
+    write(access_token_path(server), access_token)?;
+    let refresh_path = refresh_token_path(server);
+    match refresh_token {
+        Some(token) => write(refresh_path, token)?,
+        None if refresh_path.is_file() => remove_file(refresh_path)?,
+        None => {}
+    }
+    Ok(())
+}
+
+/// Exchanges the persisted refresh token of a server for a new access token with the oauth2
+/// provider and persists it. Providers that rotate refresh tokens return a new one as well.
+pub async fn refresh_access_token(server: &str, info: &OAuth2Info) -> Result<String> {
+    let refresh_token = read_to_string(refresh_token_path(server))
+        .map_err(|_| anyhow!("the access token for server {server} has expired, login again"))?;
+    let client = BasicClient::new(
+        info.client_id.clone(),
+        Some(info.client_secret.clone()),
+        info.auth_url.clone(),
+        Some(info.token_url.clone()),
+    );
+    let response = client
+        .exchange_refresh_token(&RefreshToken::new(refresh_token.trim().to_string()))
+        .request_async(async_http_client)
+        .await
+        .map_err(|e| anyhow!("unable to refresh the access token for server {server}, {e}"))?;
+    let access_token = response.access_token().secret().to_string();
+    let refresh_token = response
+        .refresh_token()
+        .map(|t| t.secret().to_string())
+        .unwrap_or(refresh_token);
+    persist_tokens(server, &access_token, Some(&refresh_token))?;
+    Ok(access_token)
+}
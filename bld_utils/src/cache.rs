@@ -0,0 +1,133 @@
+use anyhow::Result;
+use bld_config::definitions::REMOTE_RESPONSE_CACHE;
+use bld_config::{path, BldRemoteServerConfig};
+use serde_derive::{Deserialize, Serialize};
+use std::fs::{create_dir_all, read_to_string, remove_file, write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The key of the cached response of the list endpoint.
+pub const LIST_KEY: &str = "list";
+
+/// The key of the cached response of the inspect endpoint for a pipeline.
+pub fn inspect_key(pipeline: &str) -> String {
+    format!("inspect/{pipeline}")
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub etag: String,
+    pub body: String,
+    pub saved_at: u64,
+}
+
+/// The last responses of the endpoints of a server along with their etags, stored under the
+/// cache directory so that repeated requests can be sent as conditional ones.
+pub struct ResponseCache {
+    root: PathBuf,
+    ttl_seconds: u64,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+impl ResponseCache {
+    pub fn new(server: &BldRemoteServerConfig) -> Self {
+        Self {
+            root: path![REMOTE_RESPONSE_CACHE, &server.name],
+            ttl_seconds: server.cache_ttl_seconds,
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        path![&self.root, key]
+    }
+
+    /// The cached response for the key, if one exists and hasn't expired.
+    pub fn entry(&self, key: &str) -> Option<CacheEntry> {
+        let content = read_to_string(self.path(key)).ok()?;
+        let entry: CacheEntry = serde_json::from_str(&content).ok()?;
+        if now().saturating_sub(entry.saved_at) >= self.ttl_seconds {
+            self.invalidate(key);
+            return None;
+        }
+        Some(entry)
+    }
+
+    pub fn save(&self, key: &str, etag: &str, body: &str) -> Result<()> {
+        let path = self.path(key);
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        let entry = CacheEntry {
+            etag: etag.to_string(),
+            body: body.to_string(),
+            saved_at: now(),
+        };
+        write(path, serde_json::to_string(&entry)?)?;
+        Ok(())
+    }
+
+    pub fn invalidate(&self, key: &str) {
+        let _ = remove_file(self.path(key));
+    }
+
+    /// Removes the cached responses that a change to the pipeline makes outdated.
+    pub fn invalidate_pipeline(&self, pipeline: &str) {
+        self.invalidate(LIST_KEY);
+        self.invalidate(&inspect_key(pipeline));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::remove_dir_all;
+
+    fn cache(ttl_seconds: u64) -> ResponseCache {
+        ResponseCache {
+            root: temp_dir().join(format!("bld-cache-{}-{ttl_seconds}", std::process::id())),
+            ttl_seconds,
+        }
+    }
+
+    #[test]
+    fn cached_responses_are_invalidated_by_pipeline() {
+        let cache = cache(60);
+        cache.save(LIST_KEY, "\"list\"", "[]").unwrap();
+        cache
+            .save(
+                &inspect_key("nested/build.yaml"),
+                "\"build\"",
+                "name: build",
+            )
+            .unwrap();
+        cache
+            .save(&inspect_key("deploy.yaml"), "\"deploy\"", "name: deploy")
+            .unwrap();
+
+        let entry = cache.entry(&inspect_key("nested/build.yaml")).unwrap();
+        assert_eq!(entry.etag, "\"build\"");
+        assert_eq!(entry.body, "name: build");
+
+        cache.invalidate_pipeline("nested/build.yaml");
+        assert!(cache.entry(LIST_KEY).is_none());
+        assert!(cache.entry(&inspect_key("nested/build.yaml")).is_none());
+        assert!(cache.entry(&inspect_key("deploy.yaml")).is_some());
+        remove_dir_all(&cache.root).unwrap();
+    }
+
+    #[test]
+    fn cached_responses_expire_after_the_ttl() {
+        let cache = cache(0);
+        cache.save(LIST_KEY, "\"list\"", "[]").unwrap();
+        assert!(cache.entry(LIST_KEY).is_none());
+        assert!(!cache.path(LIST_KEY).exists());
+        remove_dir_all(&cache.root).unwrap();
+    }
+}
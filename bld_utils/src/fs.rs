@@ -1,5 +1,6 @@
+use anyhow::{anyhow, Result};
 use bld_config::definitions::TOOL_DEFAULT_CONFIG;
-use std::fs::DirEntry;
+use std::fs::{read_dir, DirEntry};
 use std::path::{Path, PathBuf};
 
 pub trait IsYaml {
@@ -52,3 +53,56 @@ impl IsYaml for DirEntry {
         }
     }
 }
+
+/// Lists the files of an artifact path, which is either a single file or a directory, along with
+/// their names relative to the parent of the path so that a directory keeps its own name.
+pub fn artifact_files(path: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let parent = path.parent().unwrap_or(path);
+    let mut files = vec![];
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        if current.is_dir() {
+            for entry in read_dir(&current)? {
+                pending.push(entry?.path());
+            }
+        } else if current.is_file() {
+            let name = current
+                .strip_prefix(parent)
+                .map_err(|e| anyhow!(e))?
+                .to_string_lossy()
+                .to_string();
+            files.push((name, current));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    #[test]
+    fn artifact_files_keep_the_name_of_their_directory() {
+        let root = temp_dir().join(format!("bld-artifact-files-{}", std::process::id()));
+        let output = root.join("output");
+        create_dir_all(output.join("lib")).unwrap();
+        write(output.join("app"), "app").unwrap();
+        write(output.join("lib").join("app.so"), "so").unwrap();
+
+        let names: Vec<String> = artifact_files(&output)
+            .unwrap()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, vec!["output/app", "output/lib/app.so"]);
+
+        let files = artifact_files(&output.join("app")).unwrap();
+        assert_eq!(files, vec![("app".to_string(), output.join("app"))]);
+        assert!(artifact_files(&root.join("missing")).unwrap().is_empty());
+
+        remove_dir_all(root).unwrap();
+    }
+}
@@ -1,3 +1,5 @@
+pub mod auth;
+pub mod cache;
 pub mod errors;
 pub mod fs;
 pub mod request;
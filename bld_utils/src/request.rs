@@ -1,32 +1,181 @@
-use anyhow::{anyhow, Result};
-use bld_config::{definitions::REMOTE_SERVER_OAUTH2, path, Auth};
-use reqwest::{Client, StatusCode};
+use crate::auth;
+use crate::cache::ResponseCache;
+use anyhow::{anyhow, bail, Result};
+use bld_config::{Auth, BldRemoteConfig, BldRemoteServerConfig, OAuth2Info};
+use reqwest::header::{HeaderMap, ETAG, IF_NONE_MATCH};
+use reqwest::{Certificate, Client, RequestBuilder, Response, StatusCode};
 use serde::Serialize;
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::Path;
+use std::sync::Mutex;
+
+const AUTHORIZATION: &str = "Authorization";
+
+/// The custom certificate authorities that are trusted for the servers of this invocation,
+/// along with the host and port of the server that each one is used for.
+static CA_FILES: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+/// The oauth2 settings of the servers of this invocation along with the host and port of the
+/// server and the name of the server that the tokens are persisted for, used to refresh an
+/// access token that the server rejects.
+static OAUTH2_SERVERS: Mutex<Vec<(String, String, OAuth2Info)>> = Mutex::new(Vec::new());
 
 pub fn headers(server: &str, auth: &Auth) -> Result<HashMap<String, String>> {
     let mut headers = HashMap::new();
-    if let Auth::OAuth2(_info) = auth {
-        if let Ok(token) = fs::read_to_string(path![REMOTE_SERVER_OAUTH2, server]) {
-            headers.insert("Authorization".to_string(), format!("Bearer {token}"));
+    match auth {
+        Auth::OAuth2(_info) => {
+            if let Some(token) = auth::access_token(server) {
+                headers.insert(AUTHORIZATION.to_string(), format!("Bearer {token}"));
+            }
+        }
+        Auth::ApiKey(key) => {
+            headers.insert(AUTHORIZATION.to_string(), format!("ApiKey {key}"));
         }
+        _ => {}
     }
     Ok(headers)
 }
 
-pub async fn get(url: String, headers: HashMap<String, String>) -> Result<String> {
-    let client = Client::new();
-    let mut request = client.get(url);
+/// Creates the headers for a request to a server, using the auth settings of the server
+/// defined in its same-auth-as entry if one exists.
+pub fn server_headers(
+    remote: &BldRemoteConfig,
+    server: &BldRemoteServerConfig,
+) -> Result<HashMap<String, String>> {
+    trust_ca_file(server)?;
+    let server_auth = remote.same_auth_as(server)?;
+    if let Auth::OAuth2(info) = &server_auth.auth {
+        let authority = format!("{}:{}", server.host, server.port);
+        let mut servers = OAUTH2_SERVERS.lock().unwrap();
+        if !servers.iter().any(|(a, _, _)| a == &authority) {
+            servers.push((authority, server_auth.name.to_string(), *info.clone()));
+        }
+    }
+    headers(&server_auth.name, &server_auth.auth)
+}
+
+/// Registers the custom certificate authority of a server, if it has one, so that the requests
+/// and web socket connections to the server trust it. This is done when creating the headers
+/// for a server since every request to it needs them.
+fn trust_ca_file(server: &BldRemoteServerConfig) -> Result<()> {
+    let ca_file = match &server.ca_file {
+        Some(ca_file) => ca_file,
+        None => return Ok(()),
+    };
+    if !Path::new(ca_file).is_file() {
+        bail!("ca file {ca_file} of server {} not found", server.name);
+    }
+    let authority = format!("{}:{}", server.host, server.port);
+    let mut ca_files = CA_FILES.lock().unwrap();
+    if !ca_files.iter().any(|(a, _)| a == &authority) {
+        ca_files.push((authority, ca_file.to_string()));
+    }
+    Ok(())
+}
+
+/// The host and port of the url.
+fn authority(url: &str) -> &str {
+    url.split("://")
+        .nth(1)
+        .and_then(|u| u.split('/').next())
+        .unwrap_or_default()
+}
+
+/// The custom certificate authority that is trusted for the host and port of the url.
+pub fn ca_file(url: &str) -> Option<String> {
+    let authority = authority(url);
+    CA_FILES
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(a, _)| a == authority)
+        .map(|(_, f)| f.to_string())
+}
+
+fn client(url: &str) -> Result<Client> {
+    let mut builder = Client::builder();
+    if let Some(ca_file) = ca_file(url) {
+        let pem =
+            fs::read(&ca_file).map_err(|e| anyhow!("could not read ca file {ca_file}, {e}"))?;
+        builder = builder.add_root_certificate(Certificate::from_pem(&pem)?);
+    }
+    builder.build().map_err(|e| anyhow!(e))
+}
+
+fn with_headers(mut request: RequestBuilder, headers: &HashMap<String, String>) -> RequestBuilder {
     for (key, value) in headers.iter() {
         request = request.header(&key[..], &value[..]);
     }
-    request = request.header("User-Agent", "Bld");
-    let response = request.send().await?;
+    request.header("User-Agent", "Bld")
+}
+
+/// Sends a request and when the server rejects its access token, sends it once more with
+/// the refreshed access token of the server if the token can be refreshed.
+async fn send<F>(url: &str, headers: HashMap<String, String>, build: F) -> Result<Response>
+where
+    F: Fn(&Client) -> RequestBuilder,
+{
+    let client = client(url)?;
+    let response = with_headers(build(&client), &headers).send().await?;
+    if response.status() != StatusCode::UNAUTHORIZED {
+        return Ok(response);
+    }
+    match refreshed_headers(url, &headers).await? {
+        Some(headers) => with_headers(build(&client), &headers)
+            .send()
+            .await
+            .map_err(|e| anyhow!(e)),
+        None => Ok(response),
+    }
+}
+
+/// The headers with the refreshed access token of the server of the url, for a request that
+/// the server has rejected. Returns None when the headers have no access token or the server
+/// has no oauth2 settings to refresh it with.
+pub async fn refreshed_headers(
+    url: &str,
+    headers: &HashMap<String, String>,
+) -> Result<Option<HashMap<String, String>>> {
+    if !headers.contains_key(AUTHORIZATION) {
+        return Ok(None);
+    }
+    let token = match refreshed_access_token(url, headers).await? {
+        Some(token) => token,
+        None => return Ok(None),
+    };
+    let mut headers = headers.clone();
+    headers.insert(AUTHORIZATION.to_string(), format!("Bearer {token}"));
+    Ok(Some(headers))
+}
+
+/// Refreshes the access token of the server of the url. A persisted token that differs from
+/// the one sent has already been refreshed by another request, so it's used as it is.
+async fn refreshed_access_token(
+    url: &str,
+    headers: &HashMap<String, String>,
+) -> Result<Option<String>> {
+    let authority = authority(url);
+    let (server, info) = {
+        let servers = OAUTH2_SERVERS.lock().unwrap();
+        match servers.iter().find(|(a, _, _)| a == authority) {
+            Some((_, server, info)) => (server.to_string(), info.clone()),
+            None => return Ok(None),
+        }
+    };
+    if let Some(token) = auth::access_token(&server) {
+        if headers.get(AUTHORIZATION) != Some(&format!("Bearer {token}")) {
+            return Ok(Some(token));
+        }
+    }
+    auth::refresh_access_token(&server, &info).await.map(Some)
+}
+
+pub async fn get(url: String, headers: HashMap<String, String>) -> Result<String> {
+    let response = send(&url, headers, |client| client.get(&url)).await?;
     match response.status() {
         StatusCode::OK => response.text().await.map_err(|e| anyhow!(e)),
-        StatusCode::BAD_REQUEST => {
+        StatusCode::BAD_REQUEST | StatusCode::CONFLICT => {
             let msg = response.text().await.map_err(|e| anyhow!(e))?;
             Err(anyhow!(msg))
         }
@@ -37,20 +186,122 @@ pub async fn get(url: String, headers: HashMap<String, String>) -> Result<String
     }
 }
 
+/// Sends a request that reuses the cached response for the key when the server replies that
+/// it's not modified, and caches the response when the server replies with an etag.
+async fn send_cached<F>(
+    url: &str,
+    mut headers: HashMap<String, String>,
+    cache: &ResponseCache,
+    key: &str,
+    build: F,
+) -> Result<String>
+where
+    F: Fn(&Client) -> RequestBuilder,
+{
+    let entry = cache.entry(key);
+    if let Some(entry) = &entry {
+        headers.insert(IF_NONE_MATCH.to_string(), entry.etag.to_string());
+    }
+    let response = send(url, headers, build).await?;
+    match (response.status(), entry) {
+        (StatusCode::NOT_MODIFIED, Some(entry)) => Ok(entry.body),
+        (StatusCode::OK, _) => {
+            let etag = response
+                .headers()
+                .get(ETAG)
+                .and_then(|e| e.to_str().ok())
+                .map(|e| e.to_string());
+            let body = response.text().await.map_err(|e| anyhow!(e))?;
+            match etag {
+                Some(etag) => cache.save(key, &etag, &body)?,
+                None => cache.invalidate(key),
+            }
+            Ok(body)
+        }
+        (StatusCode::BAD_REQUEST | StatusCode::CONFLICT, _) => {
+            cache.invalidate(key);
+            let msg = response.text().await.map_err(|e| anyhow!(e))?;
+            Err(anyhow!(msg))
+        }
+        (st, _) => Err(anyhow!(
+            "request returned failed with status code: {}",
+            st.to_string()
+        )),
+    }
+}
+
+/// Sends a get request as a conditional one when the response for the key is cached.
+pub async fn get_cached(
+    url: String,
+    headers: HashMap<String, String>,
+    cache: &ResponseCache,
+    key: &str,
+) -> Result<String> {
+    send_cached(&url, headers, cache, key, |client| client.get(&url)).await
+}
+
+/// Sends a post request as a conditional one when the response for the key is cached.
+pub async fn post_cached<T>(
+    url: String,
+    headers: HashMap<String, String>,
+    body: T,
+    cache: &ResponseCache,
+    key: &str,
+) -> Result<String>
+where
+    T: Serialize,
+{
+    send_cached(&url, headers, cache, key, |client| {
+        client.post(&url).json(&body)
+    })
+    .await
+}
+
+pub async fn get_with_query<Q>(
+    url: String,
+    headers: HashMap<String, String>,
+    query: &Q,
+) -> Result<String>
+where
+    Q: Serialize,
+{
+    let response = send(&url, headers, |client| client.get(&url).query(query)).await?;
+    match response.status() {
+        StatusCode::OK => response.text().await.map_err(|e| anyhow!(e)),
+        StatusCode::BAD_REQUEST | StatusCode::CONFLICT => {
+            let msg = response.text().await.map_err(|e| anyhow!(e))?;
+            Err(anyhow!(msg))
+        }
+        st => Err(anyhow!(
+            "request returned failed with status code: {}",
+            st.to_string()
+        )),
+    }
+}
+
+pub async fn delete(url: String, headers: HashMap<String, String>) -> Result<String> {
+    let response = send(&url, headers, |client| client.delete(&url)).await?;
+    match response.status() {
+        StatusCode::OK => response.text().await.map_err(|e| anyhow!(e)),
+        StatusCode::BAD_REQUEST | StatusCode::CONFLICT => {
+            let msg = response.text().await.map_err(|e| anyhow!(e))?;
+            Err(anyhow!(msg))
+        }
+        st => Err(anyhow!(
+            "http request returned failed with status code: {}",
+            st.to_string()
+        )),
+    }
+}
+
 pub async fn post<T>(url: String, headers: HashMap<String, String>, body: T) -> Result<String>
 where
     T: 'static + Serialize,
 {
-    let client = reqwest::Client::new();
-    let mut request = client.post(url);
-    for (key, value) in headers.iter() {
-        request = request.header(&key[..], &value[..]);
-    }
-    request = request.header("User-Agent", "Bld");
-    let response = request.json(&body).send().await?;
+    let response = send(&url, headers, |client| client.post(&url).json(&body)).await?;
     match response.status() {
         StatusCode::OK => response.text().await.map_err(|e| anyhow!(e)),
-        StatusCode::BAD_REQUEST => {
+        StatusCode::BAD_REQUEST | StatusCode::CONFLICT => {
             let msg = response.text().await.map_err(|e| anyhow!(e))?;
             Err(anyhow!(msg))
         }
@@ -60,3 +311,35 @@ where
         )),
     }
 }
+
+/// Sends a get request and passes the body of the response to the callback in chunks as it is
+/// received instead of buffering it, returning the headers of the response.
+pub async fn get_streamed<Q, F>(
+    url: String,
+    headers: HashMap<String, String>,
+    query: &Q,
+    mut on_chunk: F,
+) -> Result<HeaderMap>
+where
+    Q: Serialize,
+    F: FnMut(&[u8]) -> Result<()>,
+{
+    let mut response = send(&url, headers, |client| client.get(&url).query(query)).await?;
+    match response.status() {
+        StatusCode::OK => {
+            let headers = response.headers().clone();
+            while let Some(chunk) = response.chunk().await? {
+                on_chunk(&chunk)?;
+            }
+            Ok(headers)
+        }
+        StatusCode::BAD_REQUEST | StatusCode::NOT_FOUND => {
+            let msg = response.text().await.map_err(|e| anyhow!(e))?;
+            Err(anyhow!(msg))
+        }
+        st => Err(anyhow!(
+            "request returned failed with status code: {}",
+            st.to_string()
+        )),
+    }
+}
@@ -1,25 +1,83 @@
 use anyhow::Result;
-use std::io::Write;
+use std::io::{stderr, stdout, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 
-pub fn print_info(text: &str) -> Result<()> {
-    let mut stdout = StandardStream::stdout(ColorChoice::Always);
+/// Whether the verbose flag was given to this invocation, which enables the debug output.
+static VERBOSE: AtomicBool = AtomicBool::new(false);
 
-    stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?;
-    writeln!(&mut stdout, "{}", text)?;
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
 
-    stdout.set_color(ColorSpec::new().set_fg(None))?;
+pub fn is_verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+/// Colors are written only when the stream is a terminal, so that redirected output
+/// doesn't contain escape codes.
+fn color_choice(is_terminal: bool) -> ColorChoice {
+    if is_terminal {
+        ColorChoice::Auto
+    } else {
+        ColorChoice::Never
+    }
+}
+
+pub fn stdout_stream() -> StandardStream {
+    StandardStream::stdout(color_choice(stdout().is_terminal()))
+}
+
+pub fn stderr_stream() -> StandardStream {
+    StandardStream::stderr(color_choice(stderr().is_terminal()))
+}
 
+/// Writes the text to the stream with the color, resetting the color afterwards.
+pub fn write_colored(stream: &mut StandardStream, color: Color, text: &str) -> Result<()> {
+    stream.set_color(ColorSpec::new().set_fg(Some(color)))?;
+    write!(stream, "{text}")?;
+    stream.reset()?;
+    stream.flush()?;
     Ok(())
 }
 
+pub fn print_info(text: &str) -> Result<()> {
+    write_colored(&mut stdout_stream(), Color::Green, &format!("{text}\n"))
+}
+
+pub fn print_warning(text: &str) -> Result<()> {
+    write_colored(&mut stderr_stream(), Color::Yellow, &format!("{text}\n"))
+}
+
 pub fn print_error(text: &str) -> Result<()> {
-    let mut stderr = StandardStream::stderr(ColorChoice::Always);
+    write_colored(&mut stderr_stream(), Color::Red, &format!("{text}\n"))
+}
 
-    stderr.set_color(ColorSpec::new().set_fg(Some(Color::Red)))?;
-    writeln!(&mut stderr, "{}", text)?;
+/// Formats a duration such as 4m12s, with the fractions of a second shown only for durations
+/// shorter than a minute.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds {
+        0..=59 => format!("{:.2}s", duration.as_secs_f64()),
+        60..=3599 => format!("{}m{}s", seconds / 60, seconds % 60),
+        _ => format!(
+            "{}h{}m{}s",
+            seconds / 3600,
+            seconds % 3600 / 60,
+            seconds % 60
+        ),
+    }
+}
 
-    stderr.set_color(ColorSpec::new().set_fg(None))?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    Ok(())
+    #[test]
+    fn durations_are_formatted_by_their_largest_unit() {
+        assert_eq!(format_duration(Duration::from_millis(1250)), "1.25s");
+        assert_eq!(format_duration(Duration::from_secs(252)), "4m12s");
+        assert_eq!(format_duration(Duration::from_secs(3723)), "1h2m3s");
+    }
 }
@@ -1,14 +1,14 @@
 use anyhow::anyhow;
 use bld_commands::*;
 use bld_config::definitions::VERSION;
-use bld_utils::term::print_error;
+use bld_utils::term::{print_error, set_verbose};
 use clap::{Arg, ArgAction, ArgMatches, Command};
 use tracing_subscriber::filter::LevelFilter;
 
-const VERBOSITY: &str = "verbosity";
+const VERBOSE: &str = "verbose";
 
 fn tracing_level(matches: &ArgMatches) -> LevelFilter {
-    if matches.get_flag(VERBOSITY) {
+    if matches.get_flag(VERBOSE) {
         LevelFilter::DEBUG
     } else {
         LevelFilter::INFO
@@ -23,20 +23,33 @@ fn tracing(matches: &ArgMatches) {
 
 fn main() {
     let commands: Vec<Box<dyn BldCommand>> = vec![
+        api_key::ApiKeyCommand::boxed(),
         auth::AuthCommand::boxed(),
+        check::CheckCommand::boxed(),
         config::ConfigCommand::boxed(),
+        cron::CronCommand::boxed(),
+        deps::DepsCommand::boxed(),
+        document::DocumentCommand::boxed(),
+        edit::EditCommand::boxed(),
         hist::HistCommand::boxed(),
         init::InitCommand::boxed(),
         inspect::InspectCommand::boxed(),
         list::ListCommand::boxed(),
+        logs::LogsCommand::boxed(),
+        migrate::MigrateCommand::boxed(),
         remove::RemoveCommand::boxed(),
         monit::MonitCommand::boxed(),
         supervisor::SupervisorCommand::boxed(),
         push::PushCommand::boxed(),
         pull::PullCommand::boxed(),
         run::RunCommand::boxed(),
+        schema::SchemaCommand::boxed(),
+        search::SearchCommand::boxed(),
         server::ServerCommand::boxed(),
+        stats::StatsCommand::boxed(),
         stop::StopCommand::boxed(),
+        tree::TreeCommand::boxed(),
+        wait::WaitCommand::boxed(),
         worker::WorkerCommand::boxed(),
     ];
 
@@ -50,15 +63,19 @@ fn main() {
                 .collect::<Vec<Command>>(),
         )
         .arg(
-            Arg::new(VERBOSITY)
+            Arg::new(VERBOSE)
                 .short('v')
-                .help("Sets the level of verbosity")
+                .long(VERBOSE)
+                .help(
+                    "Prints debug output, such as the commands of a run after their substitutions",
+                )
                 .required(false)
                 .action(ArgAction::SetTrue),
         )
         .get_matches();
 
     tracing(&cli);
+    set_verbose(cli.get_flag(VERBOSE));
 
     let result = match cli.subcommand() {
         Some((id, matches)) => commands